@@ -0,0 +1,110 @@
+/*
+Copyright 2024 The Hyperlight Authors.
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+    http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+/// The prefix of the names of the host functions Hyperlight provides, such
+/// as the ones the host's `BuiltinServices` provide. Hosts can't register
+/// functions whose names start with it themselves.
+pub const RESERVED_PREFIX: &str = "hyperlight::";
+
+/// `hyperlight::print(String) -> Int` writes the string to the host's
+/// standard output, returning the number of bytes written.
+pub const PRINT: &str = "hyperlight::print";
+
+/// `hyperlight::time::unix_nanos() -> ULong` returns the host's wall clock
+/// time in nanoseconds since the Unix epoch.
+pub const TIME_UNIX_NANOS: &str = "hyperlight::time::unix_nanos";
+
+/// `hyperlight::time::monotonic_nanos() -> ULong` returns the nanoseconds
+/// since the built-in services were registered with the sandbox, from a
+/// clock that never goes backwards.
+pub const TIME_MONOTONIC_NANOS: &str = "hyperlight::time::monotonic_nanos";
+
+/// `hyperlight::entropy::random_bytes(Int) -> VecBytes` returns that many
+/// bytes from a cryptographically secure random number generator.
+pub const ENTROPY_RANDOM_BYTES: &str = "hyperlight::entropy::random_bytes";
+
+/// `hyperlight::metrics::increment(String, ULong) -> Void` adds to the
+/// counter with the given name in the host's `builtin_service_counters`
+/// metric. It fails for names the host didn't declare.
+pub const METRICS_INCREMENT: &str = "hyperlight::metrics::increment";
+
+/// `hyperlight::kv::get(String) -> VecBytes` returns the value of a key in
+/// the sandbox's key-value store, which is empty if the key isn't set.
+pub const KV_GET: &str = "hyperlight::kv::get";
+
+/// `hyperlight::kv::set(String, VecBytes) -> Void` sets a key in the
+/// sandbox's key-value store, or deletes it if the value is empty. It fails
+/// if the store would go over its quotas.
+pub const KV_SET: &str = "hyperlight::kv::set";
+
+/// `hyperlight::kv::delete(String) -> Void` deletes a key from the sandbox's
+/// key-value store.
+pub const KV_DELETE: &str = "hyperlight::kv::delete";
+
+/// `hyperlight::fs::open(String, Int) -> Int` opens a file in the guest's
+/// file system with one of the `FS_OPEN_*` modes, returning a handle for
+/// the other `hyperlight::fs::*` functions.
+pub const FS_OPEN: &str = "hyperlight::fs::open";
+
+/// `hyperlight::fs::read(Int, Int) -> VecBytes` reads at most the given
+/// number of bytes from an open file, returning no bytes at its end.
+pub const FS_READ: &str = "hyperlight::fs::read";
+
+/// `hyperlight::fs::write(Int, VecBytes) -> Int` writes to an open file,
+/// returning the number of bytes written.
+pub const FS_WRITE: &str = "hyperlight::fs::write";
+
+/// `hyperlight::fs::seek(Int, ULong) -> Void` moves the position of an open
+/// file to the given offset from its start.
+pub const FS_SEEK: &str = "hyperlight::fs::seek";
+
+/// `hyperlight::fs::close(Int) -> Void` closes an open file.
+pub const FS_CLOSE: &str = "hyperlight::fs::close";
+
+/// `hyperlight::fs::list(String) -> String` returns the names of the
+/// entries in a directory, one per line, with a `/` after the names of
+/// directories.
+pub const FS_LIST: &str = "hyperlight::fs::list";
+
+/// Open a file for reading
+pub const FS_OPEN_READ: i32 = 0;
+
+/// Open a file for writing, creating it if it doesn't exist and emptying it
+/// if it does
+pub const FS_OPEN_WRITE: i32 = 1;
+
+/// Open a file for writing at its end, creating it if it doesn't exist
+pub const FS_OPEN_APPEND: i32 = 2;
+
+/// `hyperlight::http::request(String, String, String, VecBytes) -> VecBytes`
+/// makes an HTTP request with the given method, URL, headers and body,
+/// returning the encoded `http::HttpResponse`. Headers are given as lines
+/// of `Name: value`.
+pub const HTTP_REQUEST: &str = "hyperlight::http::request";
+
+/// `hyperlight::bus::publish(String, VecBytes) -> Void` publishes an event
+/// to a topic of the event bus the host gave the sandbox, for every other
+/// sandbox subscribed to the topic. Events can't be empty.
+pub const BUS_PUBLISH: &str = "hyperlight::bus::publish";
+
+/// `hyperlight::bus::subscribe(String) -> Void` starts queueing the events
+/// published to a topic for the guest.
+pub const BUS_SUBSCRIBE: &str = "hyperlight::bus::subscribe";
+
+/// `hyperlight::bus::receive(String) -> VecBytes` returns the oldest event
+/// queued for the guest on a topic it subscribes to, which is empty if
+/// there is none.
+pub const BUS_RECEIVE: &str = "hyperlight::bus::receive";
@@ -0,0 +1,151 @@
+/*
+Copyright 2024 The Hyperlight Authors.
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+    http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+//! Compression of large `VecBytes` parameters and return values.
+//!
+//! A compressed value is stored in the flatbuffer in place of the raw
+//! bytes, with the `compression` field of the table recording how it was
+//! compressed. Decoding always understands compressed values, while
+//! encoders only compress when asked to, see
+//! [`FunctionCall::encode_with_compression`](super::function_call::FunctionCall::encode_with_compression)
+//! and [`ReturnValue::encode_with_compression`](super::function_types::ReturnValue::encode_with_compression).
+
+use alloc::borrow::Cow;
+use alloc::vec::Vec;
+
+use anyhow::{bail, Result};
+
+/// Bit in `CompressionData::guestCompressionSupport` in the PEB that the
+/// guest sets when it can decompress LZ4 values.
+pub const LZ4_SUPPORTED: u64 = 1 << Compression::Lz4 as u64;
+
+/// The best compression ratio LZ4 can achieve, used to reject values that
+/// claim to decompress to far more data than they could possibly hold.
+const MAX_LZ4_RATIO: usize = 255;
+
+/// How a `VecBytes` value is compressed in a flatbuffer.
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Compression {
+    /// The value holds the raw bytes.
+    None = 0,
+    /// The value is an LZ4 block prefixed with its little endian u32
+    /// uncompressed size.
+    Lz4 = 1,
+}
+
+impl TryFrom<u8> for Compression {
+    type Error = anyhow::Error;
+
+    fn try_from(value: u8) -> Result<Self> {
+        match value {
+            0 => Ok(Compression::None),
+            1 => Ok(Compression::Lz4),
+            other => bail!("Unknown compression: {}", other),
+        }
+    }
+}
+
+/// Compress `data` if `threshold` is set and `data` is at least
+/// `threshold` bytes long, returning the bytes to put in the flatbuffer
+/// along with how they are compressed.
+///
+/// `data` is returned unchanged when compressing it wouldn't make it
+/// smaller.
+pub fn compress(data: &[u8], threshold: Option<usize>) -> (Compression, Cow<'_, [u8]>) {
+    match threshold {
+        Some(threshold) if threshold > 0 && data.len() >= threshold => {
+            let compressed = lz4_flex::block::compress_prepend_size(data);
+            if compressed.len() < data.len() {
+                (Compression::Lz4, Cow::Owned(compressed))
+            } else {
+                (Compression::None, Cow::Borrowed(data))
+            }
+        }
+        _ => (Compression::None, Cow::Borrowed(data)),
+    }
+}
+
+/// Recover the original bytes of a value read from a flatbuffer whose
+/// `compression` field is `compression`.
+pub fn decompress(compression: u8, data: &[u8]) -> Result<Vec<u8>> {
+    match Compression::try_from(compression)? {
+        Compression::None => Ok(data.to_vec()),
+        Compression::Lz4 => {
+            let Some((size, block)) = data.split_first_chunk::<4>() else {
+                bail!("Compressed value is too short to hold its size");
+            };
+            let size = u32::from_le_bytes(*size) as usize;
+            if size > block.len().saturating_mul(MAX_LZ4_RATIO) {
+                bail!(
+                    "Compressed value of {} bytes cannot decompress to {} bytes",
+                    block.len(),
+                    size
+                );
+            }
+            lz4_flex::block::decompress(block, size)
+                .map_err(|e| anyhow::anyhow!("Failed to decompress value: {}", e))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::vec;
+
+    use super::*;
+
+    #[test]
+    fn round_trip() {
+        let data = vec![7u8; 64 * 1024];
+        let (compression, compressed) = compress(&data, Some(1024));
+        assert_eq!(compression, Compression::Lz4);
+        assert!(compressed.len() < data.len());
+        assert_eq!(decompress(compression as u8, &compressed).unwrap(), data);
+    }
+
+    #[test]
+    fn below_threshold_or_disabled_is_not_compressed() {
+        let data = vec![7u8; 1023];
+        assert_eq!(compress(&data, Some(1024)).0, Compression::None);
+        assert_eq!(compress(&data, None).0, Compression::None);
+        assert_eq!(compress(&data, Some(0)).0, Compression::None);
+    }
+
+    #[test]
+    fn incompressible_data_is_sent_as_is() {
+        // a simple LCG gives bytes LZ4 can't find matches in
+        let mut state = 1u32;
+        let data: Vec<u8> = (0..4096)
+            .map(|_| {
+                state = state.wrapping_mul(1103515245).wrapping_add(12345);
+                (state >> 24) as u8
+            })
+            .collect();
+        let (compression, bytes) = compress(&data, Some(1));
+        assert_eq!(compression, Compression::None);
+        assert_eq!(bytes, data.as_slice());
+    }
+
+    #[test]
+    fn implausible_sizes_are_rejected() {
+        let mut data = u32::MAX.to_le_bytes().to_vec();
+        data.extend_from_slice(&[0; 8]);
+        assert!(decompress(Compression::Lz4 as u8, &data).is_err());
+        assert!(decompress(Compression::Lz4 as u8, &[0; 2]).is_err());
+        assert!(decompress(42, &[]).is_err());
+    }
+}
@@ -18,17 +18,18 @@ use alloc::string::{String, ToString};
 use alloc::vec::Vec;
 
 use anyhow::{bail, Error, Result};
-use flatbuffers::{size_prefixed_root, WIPOffset};
+use flatbuffers::{size_prefixed_root, FlatBufferBuilder, WIPOffset};
 #[cfg(feature = "tracing")]
 use tracing::{instrument, Span};
 
+use super::compression::compress;
 use super::function_types::{ParameterValue, ReturnType};
 use crate::flatbuffers::hyperlight::generated::{
     hlbool, hlboolArgs, hldouble, hldoubleArgs, hlfloat, hlfloatArgs, hlint, hlintArgs, hllong,
-    hllongArgs, hlstring, hlstringArgs, hluint, hluintArgs, hlulong, hlulongArgs, hlvecbytes,
-    hlvecbytesArgs, FunctionCall as FbFunctionCall, FunctionCallArgs as FbFunctionCallArgs,
-    FunctionCallType as FbFunctionCallType, Parameter, ParameterArgs,
-    ParameterValue as FbParameterValue,
+    hllongArgs, hlsecret, hlsecretArgs, hlstring, hlstringArgs, hluint, hluintArgs, hlulong,
+    hlulongArgs, hlvecbytes, hlvecbytesArgs, FunctionCall as FbFunctionCall,
+    FunctionCallArgs as FbFunctionCallArgs, FunctionCallType as FbFunctionCallType, Parameter,
+    ParameterArgs, ParameterValue as FbParameterValue,
 };
 
 /// The type of function call.
@@ -50,6 +51,11 @@ pub struct FunctionCall {
     function_call_type: FunctionCallType,
     /// The return type of the function call
     pub expected_return_type: ReturnType,
+    /// The id the guest assigned to the function during initialisation,
+    /// if known. When set, the guest dispatches the call by id rather
+    /// than looking the function up by name, and the host leaves
+    /// `function_name` empty rather than sending it.
+    pub function_id: Option<u32>,
 }
 
 impl FunctionCall {
@@ -65,13 +71,29 @@ impl FunctionCall {
             parameters,
             function_call_type,
             expected_return_type,
+            function_id: None,
         }
     }
 
+    /// Set the id of the function being called, see `function_id`.
+    pub fn with_function_id(mut self, function_id: u32) -> Self {
+        self.function_id = Some(function_id);
+        self
+    }
+
     /// The type of the function call.
     pub fn function_call_type(&self) -> FunctionCallType {
         self.function_call_type.clone()
     }
+
+    /// Whether any of the parameters is a `Secret`, so that copies of
+    /// the serialized call should be zeroed once they have been used.
+    pub fn has_secrets(&self) -> bool {
+        self.parameters
+            .iter()
+            .flatten()
+            .any(|p| matches!(p, ParameterValue::Secret(_)))
+    }
 }
 
 #[cfg_attr(feature = "tracing", instrument(err(Debug), skip_all, parent = Span::current(), level= "Trace"))]
@@ -123,30 +145,50 @@ impl TryFrom<&[u8]> for FunctionCall {
             })
             .transpose()?;
 
+        let function_id = match function_call_fb.function_id() {
+            0 => None,
+            id => Some(id),
+        };
+
         Ok(Self {
             function_name: function_name.to_string(),
             parameters,
             function_call_type,
             expected_return_type,
+            function_id,
         })
     }
 }
 
-impl TryFrom<FunctionCall> for Vec<u8> {
-    type Error = Error;
-    #[cfg_attr(feature = "tracing", instrument(err(Debug), skip_all, parent = Span::current(), level= "Trace"))]
-    fn try_from(value: FunctionCall) -> Result<Vec<u8>> {
-        let mut builder = flatbuffers::FlatBufferBuilder::new();
-        let function_name = builder.create_string(&value.function_name);
+impl FunctionCall {
+    /// Serialize this `FunctionCall` into the given `FlatBufferBuilder`,
+    /// returning the size-prefixed flatbuffer bytes.
+    ///
+    /// The builder is reset before use, so the same builder can be reused
+    /// across calls to avoid allocating a new buffer each time.
+    pub fn encode<'a>(&self, builder: &'a mut FlatBufferBuilder) -> &'a [u8] {
+        self.encode_with_compression(builder, None)
+    }
 
-        let function_call_type = match value.function_call_type {
+    /// Like [`encode`](Self::encode), but compresses `VecBytes` parameters
+    /// that are at least `compression_threshold` bytes long.
+    #[cfg_attr(feature = "tracing", instrument(skip_all, parent = Span::current(), level= "Trace"))]
+    pub fn encode_with_compression<'a>(
+        &self,
+        builder: &'a mut FlatBufferBuilder,
+        compression_threshold: Option<usize>,
+    ) -> &'a [u8] {
+        builder.reset();
+        let function_name = builder.create_string(&self.function_name);
+
+        let function_call_type = match self.function_call_type {
             FunctionCallType::Guest => FbFunctionCallType::guest,
             FunctionCallType::Host => FbFunctionCallType::host,
         };
 
-        let expected_return_type = value.expected_return_type.into();
+        let expected_return_type = self.expected_return_type.into();
 
-        let parameters = match &value.parameters {
+        let parameters = match &self.parameters {
             Some(p) => {
                 let num_items = p.len();
                 let mut parameters: Vec<WIPOffset<Parameter>> = Vec::with_capacity(num_items);
@@ -154,9 +196,9 @@ impl TryFrom<FunctionCall> for Vec<u8> {
                 for param in p {
                     match param {
                         ParameterValue::Int(i) => {
-                            let hlint = hlint::create(&mut builder, &hlintArgs { value: *i });
+                            let hlint = hlint::create(builder, &hlintArgs { value: *i });
                             let parameter = Parameter::create(
-                                &mut builder,
+                                builder,
                                 &ParameterArgs {
                                     value_type: FbParameterValue::hlint,
                                     value: Some(hlint.as_union_value()),
@@ -165,9 +207,9 @@ impl TryFrom<FunctionCall> for Vec<u8> {
                             parameters.push(parameter);
                         }
                         ParameterValue::UInt(ui) => {
-                            let hluint = hluint::create(&mut builder, &hluintArgs { value: *ui });
+                            let hluint = hluint::create(builder, &hluintArgs { value: *ui });
                             let parameter = Parameter::create(
-                                &mut builder,
+                                builder,
                                 &ParameterArgs {
                                     value_type: FbParameterValue::hluint,
                                     value: Some(hluint.as_union_value()),
@@ -176,9 +218,9 @@ impl TryFrom<FunctionCall> for Vec<u8> {
                             parameters.push(parameter);
                         }
                         ParameterValue::Long(l) => {
-                            let hllong = hllong::create(&mut builder, &hllongArgs { value: *l });
+                            let hllong = hllong::create(builder, &hllongArgs { value: *l });
                             let parameter = Parameter::create(
-                                &mut builder,
+                                builder,
                                 &ParameterArgs {
                                     value_type: FbParameterValue::hllong,
                                     value: Some(hllong.as_union_value()),
@@ -187,10 +229,9 @@ impl TryFrom<FunctionCall> for Vec<u8> {
                             parameters.push(parameter);
                         }
                         ParameterValue::ULong(ul) => {
-                            let hlulong =
-                                hlulong::create(&mut builder, &hlulongArgs { value: *ul });
+                            let hlulong = hlulong::create(builder, &hlulongArgs { value: *ul });
                             let parameter = Parameter::create(
-                                &mut builder,
+                                builder,
                                 &ParameterArgs {
                                     value_type: FbParameterValue::hlulong,
                                     value: Some(hlulong.as_union_value()),
@@ -199,9 +240,9 @@ impl TryFrom<FunctionCall> for Vec<u8> {
                             parameters.push(parameter);
                         }
                         ParameterValue::Float(f) => {
-                            let hlfloat = hlfloat::create(&mut builder, &hlfloatArgs { value: *f });
+                            let hlfloat = hlfloat::create(builder, &hlfloatArgs { value: *f });
                             let parameter = Parameter::create(
-                                &mut builder,
+                                builder,
                                 &ParameterArgs {
                                     value_type: FbParameterValue::hlfloat,
                                     value: Some(hlfloat.as_union_value()),
@@ -210,10 +251,9 @@ impl TryFrom<FunctionCall> for Vec<u8> {
                             parameters.push(parameter);
                         }
                         ParameterValue::Double(d) => {
-                            let hldouble =
-                                hldouble::create(&mut builder, &hldoubleArgs { value: *d });
+                            let hldouble = hldouble::create(builder, &hldoubleArgs { value: *d });
                             let parameter = Parameter::create(
-                                &mut builder,
+                                builder,
                                 &ParameterArgs {
                                     value_type: FbParameterValue::hldouble,
                                     value: Some(hldouble.as_union_value()),
@@ -223,9 +263,9 @@ impl TryFrom<FunctionCall> for Vec<u8> {
                         }
                         ParameterValue::Bool(b) => {
                             let hlbool: WIPOffset<hlbool<'_>> =
-                                hlbool::create(&mut builder, &hlboolArgs { value: *b });
+                                hlbool::create(builder, &hlboolArgs { value: *b });
                             let parameter = Parameter::create(
-                                &mut builder,
+                                builder,
                                 &ParameterArgs {
                                     value_type: FbParameterValue::hlbool,
                                     value: Some(hlbool.as_union_value()),
@@ -236,10 +276,10 @@ impl TryFrom<FunctionCall> for Vec<u8> {
                         ParameterValue::String(s) => {
                             let hlstring = {
                                 let val = builder.create_string(s.as_str());
-                                hlstring::create(&mut builder, &hlstringArgs { value: Some(val) })
+                                hlstring::create(builder, &hlstringArgs { value: Some(val) })
                             };
                             let parameter = Parameter::create(
-                                &mut builder,
+                                builder,
                                 &ParameterArgs {
                                     value_type: FbParameterValue::hlstring,
                                     value: Some(hlstring.as_union_value()),
@@ -248,16 +288,18 @@ impl TryFrom<FunctionCall> for Vec<u8> {
                             parameters.push(parameter);
                         }
                         ParameterValue::VecBytes(v) => {
-                            let vec_bytes = builder.create_vector(v);
+                            let (compression, bytes) = compress(v, compression_threshold);
+                            let vec_bytes = builder.create_vector(&bytes);
 
                             let hlvecbytes = hlvecbytes::create(
-                                &mut builder,
+                                builder,
                                 &hlvecbytesArgs {
                                     value: Some(vec_bytes),
+                                    compression: compression as u8,
                                 },
                             );
                             let parameter = Parameter::create(
-                                &mut builder,
+                                builder,
                                 &ParameterArgs {
                                     value_type: FbParameterValue::hlvecbytes,
                                     value: Some(hlvecbytes.as_union_value()),
@@ -265,6 +307,21 @@ impl TryFrom<FunctionCall> for Vec<u8> {
                             );
                             parameters.push(parameter);
                         }
+                        ParameterValue::Secret(secret) => {
+                            // never compressed, as how well a value
+                            // compresses says something about it
+                            let value = builder.create_vector(secret.expose());
+                            let hlsecret =
+                                hlsecret::create(builder, &hlsecretArgs { value: Some(value) });
+                            let parameter = Parameter::create(
+                                builder,
+                                &ParameterArgs {
+                                    value_type: FbParameterValue::hlsecret,
+                                    value: Some(hlsecret.as_union_value()),
+                                },
+                            );
+                            parameters.push(parameter);
+                        }
                     }
                 }
                 parameters
@@ -279,18 +336,26 @@ impl TryFrom<FunctionCall> for Vec<u8> {
         };
 
         let function_call = FbFunctionCall::create(
-            &mut builder,
+            builder,
             &FbFunctionCallArgs {
                 function_name: Some(function_name),
                 parameters,
                 function_call_type,
                 expected_return_type,
+                function_id: self.function_id.unwrap_or(0),
             },
         );
         builder.finish_size_prefixed(function_call, None);
-        let res = builder.finished_data().to_vec();
+        builder.finished_data()
+    }
+}
 
-        Ok(res)
+impl TryFrom<FunctionCall> for Vec<u8> {
+    type Error = Error;
+    #[cfg_attr(feature = "tracing", instrument(err(Debug), skip_all, parent = Span::current(), level= "Trace"))]
+    fn try_from(value: FunctionCall) -> Result<Vec<u8>> {
+        let mut builder = FlatBufferBuilder::new();
+        Ok(value.encode(&mut builder).to_vec())
     }
 }
 
@@ -300,6 +365,7 @@ mod tests {
 
     use super::*;
     use crate::flatbuffer_wrappers::function_types::ReturnType;
+    use crate::secret::Secret;
 
     #[test]
     fn read_from_flatbuffer() -> Result<()> {
@@ -349,4 +415,79 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn encode_reuses_builder() -> Result<()> {
+        let mut builder = FlatBufferBuilder::new();
+
+        let first = FunctionCall::new(
+            "PrintOutput".to_string(),
+            Some(vec![ParameterValue::String("hello".to_string())]),
+            FunctionCallType::Host,
+            ReturnType::Int,
+        );
+        let expected: Vec<u8> = first.clone().try_into()?;
+        assert_eq!(first.encode(&mut builder), expected.as_slice());
+
+        let second = FunctionCall::new(
+            "GetStatic".to_string(),
+            None,
+            FunctionCallType::Guest,
+            ReturnType::Void,
+        );
+        let function_call = FunctionCall::try_from(second.encode(&mut builder))?;
+        assert_eq!(function_call.function_name, "GetStatic");
+        assert!(function_call.parameters.is_none());
+        assert_eq!(function_call.function_call_type, FunctionCallType::Guest);
+
+        Ok(())
+    }
+
+    #[test]
+    fn compressed_parameters_round_trip() -> Result<()> {
+        let mut builder = FlatBufferBuilder::new();
+        let parameters = vec![
+            ParameterValue::VecBytes(vec![1; 0x10000]),
+            ParameterValue::VecBytes(vec![2; 16]),
+        ];
+        let call = FunctionCall::new(
+            "SetByteArrayToZero".to_string(),
+            Some(parameters.clone()),
+            FunctionCallType::Guest,
+            ReturnType::VecBytes,
+        );
+
+        let uncompressed_len = call.encode(&mut builder).len();
+        let buffer = call.encode_with_compression(&mut builder, Some(0x1000));
+        assert!(buffer.len() < uncompressed_len / 10);
+
+        let function_call = FunctionCall::try_from(buffer)?;
+        assert_eq!(function_call.parameters, Some(parameters));
+
+        Ok(())
+    }
+
+    #[test]
+    fn secret_parameters_round_trip_uncompressed() -> Result<()> {
+        let mut builder = FlatBufferBuilder::new();
+        let secret = Secret::new(vec![7; 0x10000]);
+        let call = FunctionCall::new(
+            "Login".to_string(),
+            Some(vec![ParameterValue::Secret(secret.clone())]),
+            FunctionCallType::Host,
+            ReturnType::Bool,
+        );
+        assert!(call.has_secrets());
+
+        let buffer = call.encode_with_compression(&mut builder, Some(0x1000));
+        assert!(buffer.len() > 0x10000);
+
+        let function_call = FunctionCall::try_from(buffer)?;
+        assert_eq!(
+            function_call.parameters,
+            Some(vec![ParameterValue::Secret(secret)])
+        );
+
+        Ok(())
+    }
 }
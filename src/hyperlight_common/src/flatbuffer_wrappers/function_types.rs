@@ -18,10 +18,11 @@ use alloc::string::{String, ToString};
 use alloc::vec::Vec;
 
 use anyhow::{anyhow, bail, Error, Result};
-use flatbuffers::size_prefixed_root;
+use flatbuffers::{size_prefixed_root, FlatBufferBuilder};
 #[cfg(feature = "tracing")]
 use tracing::{instrument, Span};
 
+use super::compression::{compress, decompress};
 use crate::flatbuffers::hyperlight::generated::{
     hlbool, hlboolArgs, hldouble, hldoubleArgs, hlfloat, hlfloatArgs, hlint, hlintArgs, hllong,
     hllongArgs, hlsizeprefixedbuffer, hlsizeprefixedbufferArgs, hlstring, hlstringArgs, hluint,
@@ -30,6 +31,7 @@ use crate::flatbuffers::hyperlight::generated::{
     Parameter, ParameterType as FbParameterType, ParameterValue as FbParameterValue,
     ReturnType as FbReturnType, ReturnValue as FbReturnValue,
 };
+use crate::secret::Secret;
 
 /// Supported parameter types with values for function calling.
 #[derive(Debug, Clone, PartialEq)]
@@ -52,6 +54,8 @@ pub enum ParameterValue {
     Bool(bool),
     /// Vec<u8>
     VecBytes(Vec<u8>),
+    /// Vec<u8> that is zeroed after use and redacted from logs
+    Secret(Secret),
 }
 
 /// Supported parameter types for function calling.
@@ -76,6 +80,8 @@ pub enum ParameterType {
     Bool,
     /// Vec<u8>
     VecBytes,
+    /// Secret
+    Secret,
 }
 
 /// Supported return types with values from function calling.
@@ -143,6 +149,7 @@ impl From<&ParameterValue> for ParameterType {
             ParameterValue::String(_) => ParameterType::String,
             ParameterValue::Bool(_) => ParameterType::Bool,
             ParameterValue::VecBytes(_) => ParameterType::VecBytes,
+            ParameterValue::Secret(_) => ParameterType::Secret,
         }
     }
 }
@@ -178,8 +185,17 @@ impl TryFrom<Parameter<'_>> for ParameterValue {
             FbParameterValue::hlstring => param.value_as_hlstring().map(|hlstring| {
                 ParameterValue::String(hlstring.value().unwrap_or_default().to_string())
             }),
-            FbParameterValue::hlvecbytes => param.value_as_hlvecbytes().map(|hlvecbytes| {
-                ParameterValue::VecBytes(hlvecbytes.value().unwrap_or_default().iter().collect())
+            FbParameterValue::hlvecbytes => match param.value_as_hlvecbytes() {
+                Some(hlvecbytes) => Some(ParameterValue::VecBytes(decompress(
+                    hlvecbytes.compression(),
+                    hlvecbytes.value().unwrap_or_default().bytes(),
+                )?)),
+                None => None,
+            },
+            FbParameterValue::hlsecret => param.value_as_hlsecret().map(|hlsecret| {
+                ParameterValue::Secret(Secret::new(
+                    hlsecret.value().unwrap_or_default().bytes().to_vec(),
+                ))
             }),
             other => {
                 bail!("Unexpected flatbuffer parameter value type: {:?}", other);
@@ -202,6 +218,7 @@ impl From<ParameterType> for FbParameterType {
             ParameterType::String => FbParameterType::hlstring,
             ParameterType::Bool => FbParameterType::hlbool,
             ParameterType::VecBytes => FbParameterType::hlvecbytes,
+            ParameterType::Secret => FbParameterType::hlsecret,
         }
     }
 }
@@ -238,6 +255,7 @@ impl TryFrom<FbParameterType> for ParameterType {
             FbParameterType::hlstring => Ok(ParameterType::String),
             FbParameterType::hlbool => Ok(ParameterType::Bool),
             FbParameterType::hlvecbytes => Ok(ParameterType::VecBytes),
+            FbParameterType::hlsecret => Ok(ParameterType::Secret),
             _ => {
                 bail!("Unexpected flatbuffer parameter type: {:?}", value)
             }
@@ -384,6 +402,19 @@ impl TryFrom<ParameterValue> for Vec<u8> {
     }
 }
 
+impl TryFrom<ParameterValue> for Secret {
+    type Error = Error;
+    #[cfg_attr(feature = "tracing", instrument(err(Debug), skip_all, parent = Span::current(), level= "Trace"))]
+    fn try_from(value: ParameterValue) -> Result<Self> {
+        match value {
+            ParameterValue::Secret(v) => Ok(v),
+            _ => {
+                bail!("Unexpected parameter value type: {:?}", value)
+            }
+        }
+    }
+}
+
 impl TryFrom<ReturnValue> for i32 {
     type Error = Error;
     #[cfg_attr(feature = "tracing", instrument(err(Debug), skip_all, parent = Span::current(), level= "Trace"))]
@@ -574,7 +605,8 @@ impl TryFrom<FbFunctionCallResult<'_>> for ReturnValue {
                     match function_call_result_fb.return_value_as_hlsizeprefixedbuffer() {
                         Some(hlvecbytes) => hlvecbytes
                             .value()
-                            .map(|val| val.iter().collect::<Vec<u8>>()),
+                            .map(|val| decompress(hlvecbytes.compression(), val.bytes()))
+                            .transpose()?,
                         None => None,
                     };
                 Ok(ReturnValue::VecBytes(hlvecbytes.unwrap_or(Vec::new())))
@@ -596,146 +628,150 @@ impl TryFrom<&[u8]> for ReturnValue {
     }
 }
 
-impl TryFrom<&ReturnValue> for Vec<u8> {
-    type Error = Error;
-    #[cfg_attr(feature = "tracing", instrument(err(Debug), skip_all, parent = Span::current(), level= "Trace"))]
-    fn try_from(value: &ReturnValue) -> Result<Vec<u8>> {
-        let mut builder = flatbuffers::FlatBufferBuilder::new();
-        let result = match value {
+impl ReturnValue {
+    /// Serialize this `ReturnValue` into the given `FlatBufferBuilder`,
+    /// returning the size-prefixed flatbuffer bytes.
+    ///
+    /// The builder is reset before use so it can be kept around and reused.
+    pub fn encode<'a>(&self, builder: &'a mut FlatBufferBuilder) -> &'a [u8] {
+        self.encode_with_compression(builder, None)
+    }
+
+    /// Like [`encode`](Self::encode), but compresses a `VecBytes` value
+    /// that is at least `compression_threshold` bytes long.
+    #[cfg_attr(feature = "tracing", instrument(skip_all, parent = Span::current(), level= "Trace"))]
+    pub fn encode_with_compression<'a>(
+        &self,
+        builder: &'a mut FlatBufferBuilder,
+        compression_threshold: Option<usize>,
+    ) -> &'a [u8] {
+        builder.reset();
+        let function_call_result = match self {
             ReturnValue::Int(i) => {
-                let hlint = hlint::create(&mut builder, &hlintArgs { value: *i });
-                let function_call_result = FbFunctionCallResult::create(
-                    &mut builder,
+                let hlint = hlint::create(builder, &hlintArgs { value: *i });
+                FbFunctionCallResult::create(
+                    builder,
                     &FbFunctionCallResultArgs {
                         return_value: Some(hlint.as_union_value()),
                         return_value_type: FbReturnValue::hlint,
                     },
-                );
-                builder.finish_size_prefixed(function_call_result, None);
-                builder.finished_data().to_vec()
+                )
             }
             ReturnValue::UInt(ui) => {
-                let hluint = hluint::create(&mut builder, &hluintArgs { value: *ui });
-                let function_call_result = FbFunctionCallResult::create(
-                    &mut builder,
+                let hluint = hluint::create(builder, &hluintArgs { value: *ui });
+                FbFunctionCallResult::create(
+                    builder,
                     &FbFunctionCallResultArgs {
                         return_value: Some(hluint.as_union_value()),
                         return_value_type: FbReturnValue::hluint,
                     },
-                );
-                builder.finish_size_prefixed(function_call_result, None);
-                builder.finished_data().to_vec()
+                )
             }
             ReturnValue::Long(l) => {
-                let hllong = hllong::create(&mut builder, &hllongArgs { value: *l });
-                let function_call_result = FbFunctionCallResult::create(
-                    &mut builder,
+                let hllong = hllong::create(builder, &hllongArgs { value: *l });
+                FbFunctionCallResult::create(
+                    builder,
                     &FbFunctionCallResultArgs {
                         return_value: Some(hllong.as_union_value()),
                         return_value_type: FbReturnValue::hllong,
                     },
-                );
-                builder.finish_size_prefixed(function_call_result, None);
-                builder.finished_data().to_vec()
+                )
             }
             ReturnValue::ULong(ul) => {
-                let hlulong = hlulong::create(&mut builder, &hlulongArgs { value: *ul });
-                let function_call_result = FbFunctionCallResult::create(
-                    &mut builder,
+                let hlulong = hlulong::create(builder, &hlulongArgs { value: *ul });
+                FbFunctionCallResult::create(
+                    builder,
                     &FbFunctionCallResultArgs {
                         return_value: Some(hlulong.as_union_value()),
                         return_value_type: FbReturnValue::hlulong,
                     },
-                );
-                builder.finish_size_prefixed(function_call_result, None);
-                builder.finished_data().to_vec()
+                )
             }
             ReturnValue::Float(f) => {
-                let hlfloat = hlfloat::create(&mut builder, &hlfloatArgs { value: *f });
-                let function_call_result = FbFunctionCallResult::create(
-                    &mut builder,
+                let hlfloat = hlfloat::create(builder, &hlfloatArgs { value: *f });
+                FbFunctionCallResult::create(
+                    builder,
                     &FbFunctionCallResultArgs {
                         return_value: Some(hlfloat.as_union_value()),
                         return_value_type: FbReturnValue::hlfloat,
                     },
-                );
-                builder.finish_size_prefixed(function_call_result, None);
-                builder.finished_data().to_vec()
+                )
             }
             ReturnValue::Double(d) => {
-                let hldouble = hldouble::create(&mut builder, &hldoubleArgs { value: *d });
-                let function_call_result = FbFunctionCallResult::create(
-                    &mut builder,
+                let hldouble = hldouble::create(builder, &hldoubleArgs { value: *d });
+                FbFunctionCallResult::create(
+                    builder,
                     &FbFunctionCallResultArgs {
                         return_value: Some(hldouble.as_union_value()),
                         return_value_type: FbReturnValue::hldouble,
                     },
-                );
-                builder.finish_size_prefixed(function_call_result, None);
-                builder.finished_data().to_vec()
+                )
             }
             ReturnValue::Bool(b) => {
-                let hlbool = hlbool::create(&mut builder, &hlboolArgs { value: *b });
-                let function_call_result = FbFunctionCallResult::create(
-                    &mut builder,
+                let hlbool = hlbool::create(builder, &hlboolArgs { value: *b });
+                FbFunctionCallResult::create(
+                    builder,
                     &FbFunctionCallResultArgs {
                         return_value: Some(hlbool.as_union_value()),
                         return_value_type: FbReturnValue::hlbool,
                     },
-                );
-                builder.finish_size_prefixed(function_call_result, None);
-                builder.finished_data().to_vec()
+                )
             }
             ReturnValue::String(s) => {
                 let hlstring = {
                     let val = builder.create_string(s.as_str());
-                    hlstring::create(&mut builder, &hlstringArgs { value: Some(val) })
+                    hlstring::create(builder, &hlstringArgs { value: Some(val) })
                 };
-                let function_call_result = FbFunctionCallResult::create(
-                    &mut builder,
+                FbFunctionCallResult::create(
+                    builder,
                     &FbFunctionCallResultArgs {
                         return_value: Some(hlstring.as_union_value()),
                         return_value_type: FbReturnValue::hlstring,
                     },
-                );
-                builder.finish_size_prefixed(function_call_result, None);
-                builder.finished_data().to_vec()
+                )
             }
             ReturnValue::VecBytes(v) => {
                 let hlvecbytes = {
-                    let val = builder.create_vector(v.as_slice());
+                    let (compression, bytes) = compress(v, compression_threshold);
+                    let val = builder.create_vector(&bytes);
                     hlsizeprefixedbuffer::create(
-                        &mut builder,
+                        builder,
                         &hlsizeprefixedbufferArgs {
                             value: Some(val),
                             size_: v.len() as i32,
+                            compression: compression as u8,
                         },
                     )
                 };
-                let function_call_result = FbFunctionCallResult::create(
-                    &mut builder,
+                FbFunctionCallResult::create(
+                    builder,
                     &FbFunctionCallResultArgs {
                         return_value: Some(hlvecbytes.as_union_value()),
                         return_value_type: FbReturnValue::hlsizeprefixedbuffer,
                     },
-                );
-                builder.finish_size_prefixed(function_call_result, None);
-                builder.finished_data().to_vec()
+                )
             }
             ReturnValue::Void => {
-                let hlvoid = hlvoid::create(&mut builder, &hlvoidArgs {});
-                let function_call_result = FbFunctionCallResult::create(
-                    &mut builder,
+                let hlvoid = hlvoid::create(builder, &hlvoidArgs {});
+                FbFunctionCallResult::create(
+                    builder,
                     &FbFunctionCallResultArgs {
                         return_value: Some(hlvoid.as_union_value()),
                         return_value_type: FbReturnValue::hlvoid,
                     },
-                );
-                builder.finish_size_prefixed(function_call_result, None);
-                builder.finished_data().to_vec()
+                )
             }
         };
+        builder.finish_size_prefixed(function_call_result, None);
+        builder.finished_data()
+    }
+}
 
-        Ok(result)
+impl TryFrom<&ReturnValue> for Vec<u8> {
+    type Error = Error;
+    #[cfg_attr(feature = "tracing", instrument(err(Debug), skip_all, parent = Span::current(), level= "Trace"))]
+    fn try_from(value: &ReturnValue) -> Result<Vec<u8>> {
+        let mut builder = FlatBufferBuilder::new();
+        Ok(value.encode(&mut builder).to_vec())
     }
 }
@@ -28,6 +28,11 @@ use crate::flatbuffers::hyperlight::generated::{
     ErrorCode as FbErrorCode, GuestError as FbGuestError, GuestErrorArgs,
 };
 
+/// Error codes from `USER_DEFINED_ERROR_CODE_BASE` up to
+/// `USER_DEFINED_ERROR_CODE_BASE + u32::MAX` are reserved for errors defined
+/// by guest authors, see `GuestError::custom`.
+pub const USER_DEFINED_ERROR_CODE_BASE: u64 = 1 << 32;
+
 #[derive(Debug, Clone, Eq, PartialEq)]
 #[repr(C)]
 /// `ErrorCode` represents an error that occurred in the Hyperlight Guest.
@@ -48,6 +53,7 @@ pub enum ErrorCode {
     GuestFunctionParameterTypeMismatch = 14,
     GuestError = 15,
     ArrayLengthParamIsMissing = 16,
+    GuestException = 17,
 }
 
 impl From<ErrorCode> for FbErrorCode {
@@ -73,6 +79,7 @@ impl From<ErrorCode> for FbErrorCode {
             }
             ErrorCode::GuestError => Self::GuestError,
             ErrorCode::ArrayLengthParamIsMissing => Self::ArrayLengthParamIsMissing,
+            ErrorCode::GuestException => Self::GuestException,
         }
     }
 }
@@ -99,6 +106,7 @@ impl From<FbErrorCode> for ErrorCode {
             }
             FbErrorCode::GuestError => Self::GuestError,
             FbErrorCode::ArrayLengthParamIsMissing => Self::ArrayLengthParamIsMissing,
+            FbErrorCode::GuestException => Self::GuestException,
             _ => Self::UnknownError,
         }
     }
@@ -123,6 +131,7 @@ impl From<u64> for ErrorCode {
             14 => Self::GuestFunctionParameterTypeMismatch,
             15 => Self::GuestError,
             16 => Self::ArrayLengthParamIsMissing,
+            17 => Self::GuestException,
             _ => Self::UnknownError,
         }
     }
@@ -147,6 +156,7 @@ impl From<ErrorCode> for u64 {
             ErrorCode::GuestFunctionParameterTypeMismatch => 14,
             ErrorCode::GuestError => 15,
             ErrorCode::ArrayLengthParamIsMissing => 16,
+            ErrorCode::GuestException => 17,
         }
     }
 }
@@ -174,6 +184,7 @@ impl From<ErrorCode> for String {
             }
             ErrorCode::GuestError => "GuestError".to_string(),
             ErrorCode::ArrayLengthParamIsMissing => "ArrayLengthParamIsMissing".to_string(),
+            ErrorCode::GuestException => "GuestException".to_string(),
         }
     }
 }
@@ -185,12 +196,42 @@ pub struct GuestError {
     pub code: ErrorCode,
     /// The error message.
     pub message: String,
+    /// The code of an error defined by the guest author rather than by
+    /// Hyperlight. `code` is `ErrorCode::GuestError` when this is set.
+    pub custom_code: Option<u32>,
+    /// Data attached to the error by the guest, such as serialized
+    /// diagnostics.
+    pub payload: Option<Vec<u8>>,
 }
 
 impl GuestError {
     #[cfg_attr(feature = "tracing", instrument(skip_all, parent = Span::current(), level= "Trace"))]
     pub fn new(code: ErrorCode, message: String) -> Self {
-        Self { code, message }
+        Self {
+            code,
+            message,
+            custom_code: None,
+            payload: None,
+        }
+    }
+
+    /// Create a `GuestError` for an error defined by the guest author,
+    /// identified by `code`.
+    #[cfg_attr(feature = "tracing", instrument(skip_all, parent = Span::current(), level= "Trace"))]
+    pub fn custom(code: u32, message: String) -> Self {
+        Self {
+            code: ErrorCode::GuestError,
+            message,
+            custom_code: Some(code),
+            payload: None,
+        }
+    }
+
+    /// Attach `payload` to the error.
+    #[cfg_attr(feature = "tracing", instrument(skip_all, parent = Span::current(), level= "Trace"))]
+    pub fn with_payload(mut self, payload: Vec<u8>) -> Self {
+        self.payload = Some(payload);
+        self
     }
 }
 
@@ -205,9 +246,18 @@ impl TryFrom<&[u8]> for GuestError {
             Some(message) => message.to_string(),
             None => String::new(),
         };
+        let custom_code = code
+            .0
+            .checked_sub(USER_DEFINED_ERROR_CODE_BASE)
+            .and_then(|c| u32::try_from(c).ok());
         Ok(Self {
-            code: code.into(),
+            code: match custom_code {
+                Some(_) => ErrorCode::GuestError,
+                None => code.into(),
+            },
             message,
+            custom_code,
+            payload: guest_error_fb.payload().map(|p| p.bytes().to_vec()),
         })
     }
 }
@@ -218,12 +268,17 @@ impl TryFrom<&GuestError> for Vec<u8> {
     fn try_from(value: &GuestError) -> Result<Vec<u8>> {
         let mut builder = flatbuffers::FlatBufferBuilder::new();
         let message = builder.create_string(&value.message);
+        let payload = value.payload.as_ref().map(|p| builder.create_vector(p));
 
         let guest_error_fb = FbGuestError::create(
             &mut builder,
             &GuestErrorArgs {
-                code: value.code.clone().into(),
+                code: match value.custom_code {
+                    Some(c) => FbErrorCode(USER_DEFINED_ERROR_CODE_BASE + c as u64),
+                    None => value.code.clone().into(),
+                },
                 message: Some(message),
+                payload,
             },
         );
         builder.finish_size_prefixed(guest_error_fb, None);
@@ -239,6 +294,47 @@ impl Default for GuestError {
         Self {
             code: ErrorCode::NoError,
             message: String::new(),
+            custom_code: None,
+            payload: None,
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use alloc::string::ToString;
+    use alloc::vec;
+    use alloc::vec::Vec;
+
+    use super::{ErrorCode, GuestError};
+
+    #[test]
+    fn custom_error_round_trip() {
+        let error = GuestError::custom(42, "custom".to_string());
+        let buffer: Vec<u8> = (&error).try_into().unwrap();
+        let decoded = GuestError::try_from(buffer.as_slice()).unwrap();
+        assert_eq!(decoded.code, ErrorCode::GuestError);
+        assert_eq!(decoded.custom_code, Some(42));
+        assert_eq!(decoded.message, "custom");
+
+        let error = GuestError::new(ErrorCode::GuestError, "builtin".to_string());
+        let buffer: Vec<u8> = (&error).try_into().unwrap();
+        let decoded = GuestError::try_from(buffer.as_slice()).unwrap();
+        assert_eq!(decoded.code, ErrorCode::GuestError);
+        assert_eq!(decoded.custom_code, None);
+    }
+
+    #[test]
+    fn payload_round_trip() {
+        let error = GuestError::custom(7, "with payload".to_string()).with_payload(vec![1, 2, 3]);
+        let buffer: Vec<u8> = (&error).try_into().unwrap();
+        let decoded = GuestError::try_from(buffer.as_slice()).unwrap();
+        assert_eq!(decoded.custom_code, Some(7));
+        assert_eq!(decoded.payload, Some(vec![1, 2, 3]));
+
+        let error = GuestError::new(ErrorCode::GuestError, "no payload".to_string());
+        let buffer: Vec<u8> = (&error).try_into().unwrap();
+        let decoded = GuestError::try_from(buffer.as_slice()).unwrap();
+        assert_eq!(decoded.payload, None);
+    }
+}
@@ -0,0 +1,116 @@
+/*
+Copyright 2024 The Hyperlight Authors.
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+    http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+
+use anyhow::{Error, Result};
+use flatbuffers::size_prefixed_root;
+#[cfg(feature = "tracing")]
+use tracing::{instrument, Span};
+
+use crate::flatbuffers::hyperlight::generated::{
+    GuestFunctionDetails as FbGuestFunctionDetails,
+    GuestFunctionDetailsArgs as FbGuestFunctionDetailsArgs,
+};
+
+/// `GuestFunctionDetails` is the table of functions a guest registered
+/// during initialisation. The position of a name in `function_names`
+/// determines the id of that function: the first function has id 1, so
+/// that an id of 0 can mean "no id".
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct GuestFunctionDetails {
+    /// The names of the registered guest functions, in id order.
+    pub function_names: Vec<String>,
+}
+
+impl GuestFunctionDetails {
+    /// Create a new `GuestFunctionDetails`.
+    #[cfg_attr(feature = "tracing", instrument(skip_all, parent = Span::current(), level= "Trace"))]
+    pub fn new(function_names: Vec<String>) -> Self {
+        Self { function_names }
+    }
+
+    /// Iterate over `(id, name)` pairs for every function in the table.
+    pub fn ids(&self) -> impl Iterator<Item = (u32, &str)> {
+        self.function_names
+            .iter()
+            .enumerate()
+            .map(|(i, name)| (i as u32 + 1, name.as_str()))
+    }
+}
+
+impl TryFrom<&[u8]> for GuestFunctionDetails {
+    type Error = Error;
+    #[cfg_attr(feature = "tracing", instrument(err(Debug), skip_all, parent = Span::current(), level= "Trace"))]
+    fn try_from(value: &[u8]) -> Result<Self> {
+        let guest_function_details_fb = size_prefixed_root::<FbGuestFunctionDetails>(value)
+            .map_err(|e| anyhow::anyhow!("Error while reading GuestFunctionDetails: {:?}", e))?;
+
+        let function_names = guest_function_details_fb
+            .function_names()
+            .map(|names| names.iter().map(|name| name.to_string()).collect())
+            .unwrap_or_default();
+
+        Ok(Self { function_names })
+    }
+}
+
+impl TryFrom<&GuestFunctionDetails> for Vec<u8> {
+    type Error = Error;
+    #[cfg_attr(feature = "tracing", instrument(err(Debug), skip_all, parent = Span::current(), level= "Trace"))]
+    fn try_from(value: &GuestFunctionDetails) -> Result<Vec<u8>> {
+        let mut builder = flatbuffers::FlatBufferBuilder::new();
+        let function_names = value
+            .function_names
+            .iter()
+            .map(|name| builder.create_string(name))
+            .collect::<Vec<_>>();
+        let function_names = builder.create_vector(&function_names);
+
+        let guest_function_details = FbGuestFunctionDetails::create(
+            &mut builder,
+            &FbGuestFunctionDetailsArgs {
+                function_names: Some(function_names),
+            },
+        );
+        builder.finish_size_prefixed(guest_function_details, None);
+        let res = builder.finished_data().to_vec();
+
+        Ok(res)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::vec;
+
+    use super::*;
+
+    #[test]
+    fn round_trip() -> Result<()> {
+        let details =
+            GuestFunctionDetails::new(vec!["Echo".to_string(), "PrintOutput".to_string()]);
+        let buffer: Vec<u8> = (&details).try_into()?;
+        let read_back = GuestFunctionDetails::try_from(buffer.as_slice())?;
+        assert_eq!(details, read_back);
+        assert_eq!(
+            read_back.ids().collect::<Vec<_>>(),
+            vec![(1, "Echo"), (2, "PrintOutput")]
+        );
+        Ok(())
+    }
+}
@@ -37,6 +37,9 @@ pub struct GuestLogData {
     pub caller: String,
     pub source_file: String,
     pub line: u32,
+    /// The target the guest logged the message to, if it chose one rather
+    /// than the default
+    pub target: Option<String>,
 }
 
 impl GuestLogData {
@@ -56,8 +59,16 @@ impl GuestLogData {
             caller,
             source_file,
             line,
+            target: None,
         }
     }
+
+    /// Set the target the message was logged to.
+    #[cfg_attr(feature = "tracing", instrument(skip_all, parent = Span::current(), level= "Trace"))]
+    pub fn with_target(mut self, target: String) -> Self {
+        self.target = Some(target);
+        self
+    }
 }
 
 impl TryFrom<&[u8]> for GuestLogData {
@@ -72,6 +83,7 @@ impl TryFrom<&[u8]> for GuestLogData {
         let caller = convert_generated_option("caller", gld_gen.caller())?;
         let source_file = convert_generated_option("source file", gld_gen.source_file())?;
         let line = gld_gen.line();
+        let target = gld_gen.target().map(|s| s.to_string());
 
         Ok(GuestLogData {
             message,
@@ -80,6 +92,7 @@ impl TryFrom<&[u8]> for GuestLogData {
             caller,
             source_file,
             line,
+            target,
         })
     }
 }
@@ -94,6 +107,10 @@ impl TryFrom<&GuestLogData> for Vec<u8> {
         let caller = builder.create_string(&value.caller);
         let source_file = builder.create_string(&value.source_file);
         let level = FbLogLevel::from(&value.level);
+        let target = value
+            .target
+            .as_ref()
+            .map(|target| builder.create_string(target));
 
         let guest_log_data_fb = FbGuestLogData::create(
             &mut builder,
@@ -104,6 +121,7 @@ impl TryFrom<&GuestLogData> for Vec<u8> {
                 caller: Some(caller),
                 source_file: Some(source_file),
                 line: value.line,
+                target,
             },
         );
         builder.finish_size_prefixed(guest_log_data_fb, None);
@@ -126,3 +144,33 @@ fn convert_generated_option(field_name: &str, opt: Option<&str>) -> Result<Strin
     opt.map(|s| s.to_string())
         .ok_or_else(|| anyhow!("Missing field: {}", field_name))
 }
+
+#[cfg(test)]
+mod tests {
+    use alloc::string::ToString;
+    use alloc::vec::Vec;
+
+    use super::GuestLogData;
+    use crate::flatbuffer_wrappers::guest_log_level::LogLevel;
+
+    #[test]
+    fn target_round_trip() {
+        let log_data = GuestLogData::new(
+            "message".to_string(),
+            "source".to_string(),
+            LogLevel::Information,
+            "caller".to_string(),
+            "file".to_string(),
+            1,
+        );
+        let buffer: Vec<u8> = (&log_data).try_into().unwrap();
+        let decoded = GuestLogData::try_from(buffer.as_slice()).unwrap();
+        assert_eq!(decoded, log_data);
+        assert_eq!(decoded.target, None);
+
+        let log_data = log_data.with_target("audit".to_string());
+        let buffer: Vec<u8> = (&log_data).try_into().unwrap();
+        let decoded = GuestLogData::try_from(buffer.as_slice()).unwrap();
+        assert_eq!(decoded.target.as_deref(), Some("audit"));
+    }
+}
@@ -103,6 +103,15 @@ impl HostFunctionDefinition {
         }
         Ok(())
     }
+
+    /// Returns true if this function takes exactly `parameter_types`.
+    ///
+    /// Host functions may be overloaded by signature, so this is used to
+    /// pick the definition that matches a call.
+    #[cfg_attr(feature = "tracing", instrument(skip_all, parent = Span::current(), level= "Trace"))]
+    pub fn has_parameter_types(&self, parameter_types: &[ParameterType]) -> bool {
+        self.parameter_types.as_deref().unwrap_or_default() == parameter_types
+    }
 }
 
 impl TryFrom<&FbHostFunctionDefinition<'_>> for HostFunctionDefinition {
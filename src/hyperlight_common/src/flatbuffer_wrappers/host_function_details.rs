@@ -21,6 +21,7 @@ use flatbuffers::{size_prefixed_root, WIPOffset};
 #[cfg(feature = "tracing")]
 use tracing::{instrument, Span};
 
+use super::function_types::ParameterType;
 use super::host_function_definition::HostFunctionDefinition;
 use crate::flatbuffers::hyperlight::generated::{
     HostFunctionDefinition as FbHostFunctionDefinition,
@@ -33,20 +34,38 @@ use crate::flatbuffers::hyperlight::generated::{
 pub struct HostFunctionDetails {
     /// The host functions.
     pub host_functions: Option<Vec<HostFunctionDefinition>>,
+    /// Whether the host has a fallback handler for calls to functions that
+    /// are not in `host_functions`.
+    pub has_fallback: bool,
 }
 
 impl HostFunctionDetails {
     /// Create a new `HostFunctionDetails`.
     #[cfg_attr(feature = "tracing", instrument(skip_all, parent = Span::current(), level= "Trace"))]
     pub fn new(host_functions: Option<Vec<HostFunctionDefinition>>) -> Self {
-        Self { host_functions }
+        Self {
+            host_functions,
+            has_fallback: false,
+        }
     }
 
     /// Insert a host function into the host function details.
+    ///
+    /// Host functions can be overloaded by parameter types, so an existing
+    /// definition is only replaced if it has the same name and parameter types.
     #[cfg_attr(feature = "tracing", instrument(skip_all, parent = Span::current(), level= "Trace"))]
     pub fn insert_host_function(&mut self, host_function: HostFunctionDefinition) {
         match &mut self.host_functions {
-            Some(host_functions) => host_functions.push(host_function),
+            Some(host_functions) => {
+                let parameter_types = host_function.parameter_types.as_deref().unwrap_or_default();
+                match host_functions.iter_mut().find(|f| {
+                    f.function_name == host_function.function_name
+                        && f.has_parameter_types(parameter_types)
+                }) {
+                    Some(existing) => *existing = host_function,
+                    None => host_functions.push(host_function),
+                }
+            }
             None => {
                 let host_functions = Vec::from(&[host_function]);
                 self.host_functions = Some(host_functions);
@@ -54,6 +73,18 @@ impl HostFunctionDetails {
         }
     }
 
+    /// Remove every overload of the host function named `function_name`,
+    /// returning whether there were any.
+    #[cfg_attr(feature = "tracing", instrument(skip_all, parent = Span::current(), level= "Trace"))]
+    pub fn remove_host_function(&mut self, function_name: &str) -> bool {
+        let Some(host_functions) = &mut self.host_functions else {
+            return false;
+        };
+        let len = host_functions.len();
+        host_functions.retain(|host_function| host_function.function_name != function_name);
+        host_functions.len() != len
+    }
+
     /// Sort the host functions by name.
     #[cfg_attr(feature = "tracing", instrument(skip_all, parent = Span::current(), level= "Trace"))]
     pub fn sort_host_functions_by_name(&mut self) {
@@ -65,6 +96,20 @@ impl HostFunctionDetails {
         }
     }
 
+    /// Find the overload of the host function named `function_name` that
+    /// takes exactly `parameter_types`.
+    #[cfg_attr(feature = "tracing", instrument(skip_all, parent = Span::current(), level= "Trace"))]
+    pub fn find_by_function_name_and_parameter_types(
+        &self,
+        function_name: &str,
+        parameter_types: &[ParameterType],
+    ) -> Option<&HostFunctionDefinition> {
+        self.host_functions.iter().flatten().find(|host_function| {
+            host_function.function_name == function_name
+                && host_function.has_parameter_types(parameter_types)
+        })
+    }
+
     /// Find a host function by name.
     #[cfg_attr(feature = "tracing", instrument(skip_all, parent = Span::current(), level= "Trace"))]
     pub fn find_by_function_name(&self, function_name: &str) -> Option<HostFunctionDefinition> {
@@ -108,6 +153,7 @@ impl TryFrom<&[u8]> for HostFunctionDetails {
 
         Ok(Self {
             host_functions: host_function_definitions,
+            has_fallback: host_function_details_fb.has_fallback(),
         })
     }
 }
@@ -140,6 +186,7 @@ impl TryFrom<&HostFunctionDetails> for Vec<u8> {
             &mut builder,
             &FbHostFunctionDetailsArgs {
                 functions: fb_host_function_definitions,
+                has_fallback: value.has_fallback,
             },
         );
         builder.finish_size_prefixed(host_function_details, None);
@@ -14,10 +14,14 @@ See the License for the specific language governing permissions and
 limitations under the License.
 */
 
+/// cbindgen:ignore
+pub mod compression;
 pub mod function_call;
 pub mod function_types;
 pub mod guest_error;
 /// cbindgen:ignore
+pub mod guest_function_details;
+/// cbindgen:ignore
 pub mod guest_log_data;
 /// cbindgen:ignore
 pub mod guest_log_level;
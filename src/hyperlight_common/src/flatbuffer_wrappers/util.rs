@@ -18,6 +18,7 @@ use alloc::vec::Vec;
 
 use flatbuffers::{FlatBufferBuilder, UnionWIPOffset, WIPOffset};
 
+use super::compression::Compression;
 use crate::flatbuffers::hyperlight::generated::{
     hldouble as Fbhldouble, hldoubleArgs as FbhldoubleArgs, hlfloat as Fbhlfloat,
     hlfloatArgs as FbhlfloatArgs, hlint as Fbhlint, hlintArgs as FbhlintArgs, hllong as Fbhllong,
@@ -126,6 +127,7 @@ pub fn get_flatbuffer_result_from_vec(data: &[u8]) -> Vec<u8> {
         &FbhlsizeprefixedbufferArgs {
             size_: data.len() as i32,
             value: Some(vec_offset),
+            compression: Compression::None as u8,
         },
     );
 
@@ -32,6 +32,7 @@ impl<'a> FunctionCall<'a> {
     pub const VT_PARAMETERS: flatbuffers::VOffsetT = 6;
     pub const VT_FUNCTION_CALL_TYPE: flatbuffers::VOffsetT = 8;
     pub const VT_EXPECTED_RETURN_TYPE: flatbuffers::VOffsetT = 10;
+    pub const VT_FUNCTION_ID: flatbuffers::VOffsetT = 12;
 
     #[inline]
     pub unsafe fn init_from_table(table: flatbuffers::Table<'a>) -> Self {
@@ -43,6 +44,7 @@ impl<'a> FunctionCall<'a> {
         args: &'args FunctionCallArgs<'args>,
     ) -> flatbuffers::WIPOffset<FunctionCall<'bldr>> {
         let mut builder = FunctionCallBuilder::new(_fbb);
+        builder.add_function_id(args.function_id);
         if let Some(x) = args.parameters {
             builder.add_parameters(x);
         }
@@ -116,6 +118,17 @@ impl<'a> FunctionCall<'a> {
                 .unwrap()
         }
     }
+    #[inline]
+    pub fn function_id(&self) -> u32 {
+        // Safety:
+        // Created from valid Table for this object
+        // which contains a valid value in this slot
+        unsafe {
+            self._tab
+                .get::<u32>(FunctionCall::VT_FUNCTION_ID, Some(0))
+                .unwrap()
+        }
+    }
 }
 
 impl flatbuffers::Verifiable for FunctionCall<'_> {
@@ -144,6 +157,7 @@ impl flatbuffers::Verifiable for FunctionCall<'_> {
                 Self::VT_EXPECTED_RETURN_TYPE,
                 false,
             )?
+            .visit_field::<u32>("function_id", Self::VT_FUNCTION_ID, false)?
             .finish();
         Ok(())
     }
@@ -157,6 +171,7 @@ pub struct FunctionCallArgs<'a> {
     >,
     pub function_call_type: FunctionCallType,
     pub expected_return_type: ReturnType,
+    pub function_id: u32,
 }
 impl<'a> Default for FunctionCallArgs<'a> {
     #[inline]
@@ -166,6 +181,7 @@ impl<'a> Default for FunctionCallArgs<'a> {
             parameters: None,
             function_call_type: FunctionCallType::none,
             expected_return_type: ReturnType::hlint,
+            function_id: 0,
         }
     }
 }
@@ -209,6 +225,11 @@ impl<'a: 'b, 'b, A: flatbuffers::Allocator + 'a> FunctionCallBuilder<'a, 'b, A>
         );
     }
     #[inline]
+    pub fn add_function_id(&mut self, function_id: u32) {
+        self.fbb_
+            .push_slot::<u32>(FunctionCall::VT_FUNCTION_ID, function_id, 0);
+    }
+    #[inline]
     pub fn new(
         _fbb: &'b mut flatbuffers::FlatBufferBuilder<'a, A>,
     ) -> FunctionCallBuilder<'a, 'b, A> {
@@ -234,6 +255,7 @@ impl core::fmt::Debug for FunctionCall<'_> {
         ds.field("parameters", &self.parameters());
         ds.field("function_call_type", &self.function_call_type());
         ds.field("expected_return_type", &self.expected_return_type());
+        ds.field("function_id", &self.function_id());
         ds.finish()
     }
 }
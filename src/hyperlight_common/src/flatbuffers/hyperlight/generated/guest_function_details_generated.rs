@@ -0,0 +1,218 @@
+// automatically generated by the FlatBuffers compiler, do not modify
+// @generated
+extern crate alloc;
+extern crate flatbuffers;
+use alloc::boxed::Box;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use core::cmp::Ordering;
+use core::mem;
+
+use self::flatbuffers::{EndianScalar, Follow};
+use super::*;
+pub enum GuestFunctionDetailsOffset {}
+#[derive(Copy, Clone, PartialEq)]
+
+pub struct GuestFunctionDetails<'a> {
+    pub _tab: flatbuffers::Table<'a>,
+}
+
+impl<'a> flatbuffers::Follow<'a> for GuestFunctionDetails<'a> {
+    type Inner = GuestFunctionDetails<'a>;
+    #[inline]
+    unsafe fn follow(buf: &'a [u8], loc: usize) -> Self::Inner {
+        Self {
+            _tab: flatbuffers::Table::new(buf, loc),
+        }
+    }
+}
+
+impl<'a> GuestFunctionDetails<'a> {
+    pub const VT_FUNCTION_NAMES: flatbuffers::VOffsetT = 4;
+
+    #[inline]
+    pub unsafe fn init_from_table(table: flatbuffers::Table<'a>) -> Self {
+        GuestFunctionDetails { _tab: table }
+    }
+    #[allow(unused_mut)]
+    pub fn create<'bldr: 'args, 'args: 'mut_bldr, 'mut_bldr, A: flatbuffers::Allocator + 'bldr>(
+        _fbb: &'mut_bldr mut flatbuffers::FlatBufferBuilder<'bldr, A>,
+        args: &'args GuestFunctionDetailsArgs<'args>,
+    ) -> flatbuffers::WIPOffset<GuestFunctionDetails<'bldr>> {
+        let mut builder = GuestFunctionDetailsBuilder::new(_fbb);
+        if let Some(x) = args.function_names {
+            builder.add_function_names(x);
+        }
+        builder.finish()
+    }
+
+    #[inline]
+    pub fn function_names(
+        &self,
+    ) -> Option<flatbuffers::Vector<'a, flatbuffers::ForwardsUOffset<&'a str>>> {
+        // Safety:
+        // Created from valid Table for this object
+        // which contains a valid value in this slot
+        unsafe {
+            self._tab.get::<flatbuffers::ForwardsUOffset<
+                flatbuffers::Vector<'a, flatbuffers::ForwardsUOffset<&'a str>>,
+            >>(GuestFunctionDetails::VT_FUNCTION_NAMES, None)
+        }
+    }
+}
+
+impl flatbuffers::Verifiable for GuestFunctionDetails<'_> {
+    #[inline]
+    fn run_verifier(
+        v: &mut flatbuffers::Verifier,
+        pos: usize,
+    ) -> Result<(), flatbuffers::InvalidFlatbuffer> {
+        use self::flatbuffers::Verifiable;
+        v.visit_table(pos)?
+            .visit_field::<flatbuffers::ForwardsUOffset<
+                flatbuffers::Vector<'_, flatbuffers::ForwardsUOffset<&'_ str>>,
+            >>("function_names", Self::VT_FUNCTION_NAMES, false)?
+            .finish();
+        Ok(())
+    }
+}
+pub struct GuestFunctionDetailsArgs<'a> {
+    pub function_names: Option<
+        flatbuffers::WIPOffset<flatbuffers::Vector<'a, flatbuffers::ForwardsUOffset<&'a str>>>,
+    >,
+}
+impl<'a> Default for GuestFunctionDetailsArgs<'a> {
+    #[inline]
+    fn default() -> Self {
+        GuestFunctionDetailsArgs {
+            function_names: None,
+        }
+    }
+}
+
+pub struct GuestFunctionDetailsBuilder<'a: 'b, 'b, A: flatbuffers::Allocator + 'a> {
+    fbb_: &'b mut flatbuffers::FlatBufferBuilder<'a, A>,
+    start_: flatbuffers::WIPOffset<flatbuffers::TableUnfinishedWIPOffset>,
+}
+impl<'a: 'b, 'b, A: flatbuffers::Allocator + 'a> GuestFunctionDetailsBuilder<'a, 'b, A> {
+    #[inline]
+    pub fn add_function_names(
+        &mut self,
+        function_names: flatbuffers::WIPOffset<
+            flatbuffers::Vector<'b, flatbuffers::ForwardsUOffset<&'b str>>,
+        >,
+    ) {
+        self.fbb_.push_slot_always::<flatbuffers::WIPOffset<_>>(
+            GuestFunctionDetails::VT_FUNCTION_NAMES,
+            function_names,
+        );
+    }
+    #[inline]
+    pub fn new(
+        _fbb: &'b mut flatbuffers::FlatBufferBuilder<'a, A>,
+    ) -> GuestFunctionDetailsBuilder<'a, 'b, A> {
+        let start = _fbb.start_table();
+        GuestFunctionDetailsBuilder {
+            fbb_: _fbb,
+            start_: start,
+        }
+    }
+    #[inline]
+    pub fn finish(self) -> flatbuffers::WIPOffset<GuestFunctionDetails<'a>> {
+        let o = self.fbb_.end_table(self.start_);
+        flatbuffers::WIPOffset::new(o.value())
+    }
+}
+
+impl core::fmt::Debug for GuestFunctionDetails<'_> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        let mut ds = f.debug_struct("GuestFunctionDetails");
+        ds.field("function_names", &self.function_names());
+        ds.finish()
+    }
+}
+#[inline]
+/// Verifies that a buffer of bytes contains a `GuestFunctionDetails`
+/// and returns it.
+/// Note that verification is still experimental and may not
+/// catch every error, or be maximally performant. For the
+/// previous, unchecked, behavior use
+/// `root_as_guest_function_details_unchecked`.
+pub fn root_as_guest_function_details(
+    buf: &[u8],
+) -> Result<GuestFunctionDetails, flatbuffers::InvalidFlatbuffer> {
+    flatbuffers::root::<GuestFunctionDetails>(buf)
+}
+#[inline]
+/// Verifies that a buffer of bytes contains a size prefixed
+/// `GuestFunctionDetails` and returns it.
+/// Note that verification is still experimental and may not
+/// catch every error, or be maximally performant. For the
+/// previous, unchecked, behavior use
+/// `size_prefixed_root_as_guest_function_details_unchecked`.
+pub fn size_prefixed_root_as_guest_function_details(
+    buf: &[u8],
+) -> Result<GuestFunctionDetails, flatbuffers::InvalidFlatbuffer> {
+    flatbuffers::size_prefixed_root::<GuestFunctionDetails>(buf)
+}
+#[inline]
+/// Verifies, with the given options, that a buffer of bytes
+/// contains a `GuestFunctionDetails` and returns it.
+/// Note that verification is still experimental and may not
+/// catch every error, or be maximally performant. For the
+/// previous, unchecked, behavior use
+/// `root_as_guest_function_details_unchecked`.
+pub fn root_as_guest_function_details_with_opts<'b, 'o>(
+    opts: &'o flatbuffers::VerifierOptions,
+    buf: &'b [u8],
+) -> Result<GuestFunctionDetails<'b>, flatbuffers::InvalidFlatbuffer> {
+    flatbuffers::root_with_opts::<GuestFunctionDetails<'b>>(opts, buf)
+}
+#[inline]
+/// Verifies, with the given verifier options, that a buffer of
+/// bytes contains a size prefixed `GuestFunctionDetails` and returns
+/// it. Note that verification is still experimental and may not
+/// catch every error, or be maximally performant. For the
+/// previous, unchecked, behavior use
+/// `root_as_guest_function_details_unchecked`.
+pub fn size_prefixed_root_as_guest_function_details_with_opts<'b, 'o>(
+    opts: &'o flatbuffers::VerifierOptions,
+    buf: &'b [u8],
+) -> Result<GuestFunctionDetails<'b>, flatbuffers::InvalidFlatbuffer> {
+    flatbuffers::size_prefixed_root_with_opts::<GuestFunctionDetails<'b>>(opts, buf)
+}
+#[inline]
+/// Assumes, without verification, that a buffer of bytes contains a GuestFunctionDetails and returns it.
+/// # Safety
+/// Callers must trust the given bytes do indeed contain a valid `GuestFunctionDetails`.
+pub unsafe fn root_as_guest_function_details_unchecked(buf: &[u8]) -> GuestFunctionDetails {
+    flatbuffers::root_unchecked::<GuestFunctionDetails>(buf)
+}
+#[inline]
+/// Assumes, without verification, that a buffer of bytes contains a size prefixed GuestFunctionDetails and returns it.
+/// # Safety
+/// Callers must trust the given bytes do indeed contain a valid size prefixed `GuestFunctionDetails`.
+pub unsafe fn size_prefixed_root_as_guest_function_details_unchecked(
+    buf: &[u8],
+) -> GuestFunctionDetails {
+    flatbuffers::size_prefixed_root_unchecked::<GuestFunctionDetails>(buf)
+}
+#[inline]
+pub fn finish_guest_function_details_buffer<'a, 'b, A: flatbuffers::Allocator + 'a>(
+    fbb: &'b mut flatbuffers::FlatBufferBuilder<'a, A>,
+    root: flatbuffers::WIPOffset<GuestFunctionDetails<'a>>,
+) {
+    fbb.finish(root, None);
+}
+
+#[inline]
+pub fn finish_size_prefixed_guest_function_details_buffer<
+    'a,
+    'b,
+    A: flatbuffers::Allocator + 'a,
+>(
+    fbb: &'b mut flatbuffers::FlatBufferBuilder<'a, A>,
+    root: flatbuffers::WIPOffset<GuestFunctionDetails<'a>>,
+) {
+    fbb.finish_size_prefixed(root, None);
+}
@@ -30,6 +30,7 @@ impl<'a> flatbuffers::Follow<'a> for hlsizeprefixedbuffer<'a> {
 impl<'a> hlsizeprefixedbuffer<'a> {
     pub const VT_SIZE_: flatbuffers::VOffsetT = 4;
     pub const VT_VALUE: flatbuffers::VOffsetT = 6;
+    pub const VT_COMPRESSION: flatbuffers::VOffsetT = 8;
 
     #[inline]
     pub unsafe fn init_from_table(table: flatbuffers::Table<'a>) -> Self {
@@ -45,6 +46,7 @@ impl<'a> hlsizeprefixedbuffer<'a> {
             builder.add_value(x);
         }
         builder.add_size_(args.size_);
+        builder.add_compression(args.compression);
         builder.finish()
     }
 
@@ -72,6 +74,17 @@ impl<'a> hlsizeprefixedbuffer<'a> {
                 )
         }
     }
+    #[inline]
+    pub fn compression(&self) -> u8 {
+        // Safety:
+        // Created from valid Table for this object
+        // which contains a valid value in this slot
+        unsafe {
+            self._tab
+                .get::<u8>(hlsizeprefixedbuffer::VT_COMPRESSION, Some(0))
+                .unwrap()
+        }
+    }
 }
 
 impl flatbuffers::Verifiable for hlsizeprefixedbuffer<'_> {
@@ -88,6 +101,7 @@ impl flatbuffers::Verifiable for hlsizeprefixedbuffer<'_> {
                 Self::VT_VALUE,
                 false,
             )?
+            .visit_field::<u8>("compression", Self::VT_COMPRESSION, false)?
             .finish();
         Ok(())
     }
@@ -95,6 +109,7 @@ impl flatbuffers::Verifiable for hlsizeprefixedbuffer<'_> {
 pub struct hlsizeprefixedbufferArgs<'a> {
     pub size_: i32,
     pub value: Option<flatbuffers::WIPOffset<flatbuffers::Vector<'a, u8>>>,
+    pub compression: u8,
 }
 impl<'a> Default for hlsizeprefixedbufferArgs<'a> {
     #[inline]
@@ -102,6 +117,7 @@ impl<'a> Default for hlsizeprefixedbufferArgs<'a> {
         hlsizeprefixedbufferArgs {
             size_: 0,
             value: None,
+            compression: 0,
         }
     }
 }
@@ -122,6 +138,11 @@ impl<'a: 'b, 'b, A: flatbuffers::Allocator + 'a> hlsizeprefixedbufferBuilder<'a,
             .push_slot_always::<flatbuffers::WIPOffset<_>>(hlsizeprefixedbuffer::VT_VALUE, value);
     }
     #[inline]
+    pub fn add_compression(&mut self, compression: u8) {
+        self.fbb_
+            .push_slot::<u8>(hlsizeprefixedbuffer::VT_COMPRESSION, compression, 0);
+    }
+    #[inline]
     pub fn new(
         _fbb: &'b mut flatbuffers::FlatBufferBuilder<'a, A>,
     ) -> hlsizeprefixedbufferBuilder<'a, 'b, A> {
@@ -143,6 +164,7 @@ impl core::fmt::Debug for hlsizeprefixedbuffer<'_> {
         let mut ds = f.debug_struct("hlsizeprefixedbuffer");
         ds.field("size_", &self.size_());
         ds.field("value", &self.value());
+        ds.field("compression", &self.compression());
         ds.finish()
     }
 }
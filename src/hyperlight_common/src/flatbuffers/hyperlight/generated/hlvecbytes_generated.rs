@@ -29,6 +29,7 @@ impl<'a> flatbuffers::Follow<'a> for hlvecbytes<'a> {
 
 impl<'a> hlvecbytes<'a> {
     pub const VT_VALUE: flatbuffers::VOffsetT = 4;
+    pub const VT_COMPRESSION: flatbuffers::VOffsetT = 6;
 
     #[inline]
     pub unsafe fn init_from_table(table: flatbuffers::Table<'a>) -> Self {
@@ -43,6 +44,7 @@ impl<'a> hlvecbytes<'a> {
         if let Some(x) = args.value {
             builder.add_value(x);
         }
+        builder.add_compression(args.compression);
         builder.finish()
     }
 
@@ -59,6 +61,17 @@ impl<'a> hlvecbytes<'a> {
                 )
         }
     }
+    #[inline]
+    pub fn compression(&self) -> u8 {
+        // Safety:
+        // Created from valid Table for this object
+        // which contains a valid value in this slot
+        unsafe {
+            self._tab
+                .get::<u8>(hlvecbytes::VT_COMPRESSION, Some(0))
+                .unwrap()
+        }
+    }
 }
 
 impl flatbuffers::Verifiable for hlvecbytes<'_> {
@@ -74,17 +87,22 @@ impl flatbuffers::Verifiable for hlvecbytes<'_> {
                 Self::VT_VALUE,
                 false,
             )?
+            .visit_field::<u8>("compression", Self::VT_COMPRESSION, false)?
             .finish();
         Ok(())
     }
 }
 pub struct hlvecbytesArgs<'a> {
     pub value: Option<flatbuffers::WIPOffset<flatbuffers::Vector<'a, u8>>>,
+    pub compression: u8,
 }
 impl<'a> Default for hlvecbytesArgs<'a> {
     #[inline]
     fn default() -> Self {
-        hlvecbytesArgs { value: None }
+        hlvecbytesArgs {
+            value: None,
+            compression: 0,
+        }
     }
 }
 
@@ -99,6 +117,11 @@ impl<'a: 'b, 'b, A: flatbuffers::Allocator + 'a> hlvecbytesBuilder<'a, 'b, A> {
             .push_slot_always::<flatbuffers::WIPOffset<_>>(hlvecbytes::VT_VALUE, value);
     }
     #[inline]
+    pub fn add_compression(&mut self, compression: u8) {
+        self.fbb_
+            .push_slot::<u8>(hlvecbytes::VT_COMPRESSION, compression, 0);
+    }
+    #[inline]
     pub fn new(
         _fbb: &'b mut flatbuffers::FlatBufferBuilder<'a, A>,
     ) -> hlvecbytesBuilder<'a, 'b, A> {
@@ -119,6 +142,7 @@ impl core::fmt::Debug for hlvecbytes<'_> {
     fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
         let mut ds = f.debug_struct("hlvecbytes");
         ds.field("value", &self.value());
+        ds.field("compression", &self.compression());
         ds.finish()
     }
 }
@@ -29,6 +29,7 @@ impl<'a> flatbuffers::Follow<'a> for HostFunctionDetails<'a> {
 
 impl<'a> HostFunctionDetails<'a> {
     pub const VT_FUNCTIONS: flatbuffers::VOffsetT = 4;
+    pub const VT_HAS_FALLBACK: flatbuffers::VOffsetT = 6;
 
     #[inline]
     pub unsafe fn init_from_table(table: flatbuffers::Table<'a>) -> Self {
@@ -43,6 +44,7 @@ impl<'a> HostFunctionDetails<'a> {
         if let Some(x) = args.functions {
             builder.add_functions(x);
         }
+        builder.add_has_fallback(args.has_fallback);
         builder.finish()
     }
 
@@ -60,6 +62,17 @@ impl<'a> HostFunctionDetails<'a> {
             >>(HostFunctionDetails::VT_FUNCTIONS, None)
         }
     }
+    #[inline]
+    pub fn has_fallback(&self) -> bool {
+        // Safety:
+        // Created from valid Table for this object
+        // which contains a valid value in this slot
+        unsafe {
+            self._tab
+                .get::<bool>(HostFunctionDetails::VT_HAS_FALLBACK, Some(false))
+                .unwrap()
+        }
+    }
 }
 
 impl flatbuffers::Verifiable for HostFunctionDetails<'_> {
@@ -73,6 +86,7 @@ impl flatbuffers::Verifiable for HostFunctionDetails<'_> {
             .visit_field::<flatbuffers::ForwardsUOffset<
                 flatbuffers::Vector<'_, flatbuffers::ForwardsUOffset<HostFunctionDefinition>>,
             >>("functions", Self::VT_FUNCTIONS, false)?
+            .visit_field::<bool>("has_fallback", Self::VT_HAS_FALLBACK, false)?
             .finish();
         Ok(())
     }
@@ -83,11 +97,15 @@ pub struct HostFunctionDetailsArgs<'a> {
             flatbuffers::Vector<'a, flatbuffers::ForwardsUOffset<HostFunctionDefinition<'a>>>,
         >,
     >,
+    pub has_fallback: bool,
 }
 impl<'a> Default for HostFunctionDetailsArgs<'a> {
     #[inline]
     fn default() -> Self {
-        HostFunctionDetailsArgs { functions: None }
+        HostFunctionDetailsArgs {
+            functions: None,
+            has_fallback: false,
+        }
     }
 }
 
@@ -109,6 +127,11 @@ impl<'a: 'b, 'b, A: flatbuffers::Allocator + 'a> HostFunctionDetailsBuilder<'a,
         );
     }
     #[inline]
+    pub fn add_has_fallback(&mut self, has_fallback: bool) {
+        self.fbb_
+            .push_slot::<bool>(HostFunctionDetails::VT_HAS_FALLBACK, has_fallback, false);
+    }
+    #[inline]
     pub fn new(
         _fbb: &'b mut flatbuffers::FlatBufferBuilder<'a, A>,
     ) -> HostFunctionDetailsBuilder<'a, 'b, A> {
@@ -129,6 +152,7 @@ impl core::fmt::Debug for HostFunctionDetails<'_> {
     fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
         let mut ds = f.debug_struct("HostFunctionDetails");
         ds.field("functions", &self.functions());
+        ds.field("has_fallback", &self.has_fallback());
         ds.finish()
     }
 }
@@ -198,6 +198,20 @@ impl<'a> Parameter<'a> {
             None
         }
     }
+
+    #[inline]
+    #[allow(non_snake_case)]
+    pub fn value_as_hlsecret(&self) -> Option<hlsecret<'a>> {
+        if self.value_type() == ParameterValue::hlsecret {
+            let u = self.value();
+            // Safety:
+            // Created from a valid Table for this object
+            // Which contains a valid union in this slot
+            Some(unsafe { hlsecret::init_from_table(u) })
+        } else {
+            None
+        }
+    }
 }
 
 impl flatbuffers::Verifiable for Parameter<'_> {
@@ -260,6 +274,11 @@ impl flatbuffers::Verifiable for Parameter<'_> {
                             "ParameterValue::hlvecbytes",
                             pos,
                         ),
+                    ParameterValue::hlsecret => v
+                        .verify_union_variant::<flatbuffers::ForwardsUOffset<hlsecret>>(
+                            "ParameterValue::hlsecret",
+                            pos,
+                        ),
                     _ => Ok(()),
                 },
             )?
@@ -410,6 +429,16 @@ impl core::fmt::Debug for Parameter<'_> {
                     )
                 }
             }
+            ParameterValue::hlsecret => {
+                if let Some(x) = self.value_as_hlsecret() {
+                    ds.field("value", &x)
+                } else {
+                    ds.field(
+                        "value",
+                        &"InvalidFlatbuffer: Union discriminant does not match value.",
+                    )
+                }
+            }
             _ => {
                 let x: Option<()> = None;
                 ds.field("value", &x)
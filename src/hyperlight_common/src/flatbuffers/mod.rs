@@ -30,6 +30,8 @@ pub mod hyperlight {
         pub use self::hlbool_generated::*;
         mod hlvecbytes_generated;
         pub use self::hlvecbytes_generated::*;
+        mod hlsecret_generated;
+        pub use self::hlsecret_generated::*;
         mod hlvoid_generated;
         pub use self::hlvoid_generated::*;
         mod function_call_result_generated;
@@ -54,5 +56,7 @@ pub mod hyperlight {
         pub use self::log_level_generated::*;
         mod guest_log_data_generated;
         pub use self::guest_log_data_generated::*;
+        mod guest_function_details_generated;
+        pub use self::guest_function_details_generated::*;
     }
 }
@@ -0,0 +1,99 @@
+/*
+Copyright 2024 The Hyperlight Authors.
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+    http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+use alloc::vec::Vec;
+
+/// Encode the variables of a guest's environment as the guest reads them:
+/// the number of variables as a little endian `u32`, then each name and
+/// value as a little endian `u32` length followed by that many bytes.
+/// The variables are sorted by name.
+pub fn encode<'a>(vars: impl IntoIterator<Item = (&'a str, &'a str)>) -> Vec<u8> {
+    let mut vars: Vec<_> = vars.into_iter().collect();
+    vars.sort_unstable();
+    let mut bytes = Vec::new();
+    bytes.extend_from_slice(&(vars.len() as u32).to_le_bytes());
+    for (name, value) in vars {
+        for part in [name, value] {
+            bytes.extend_from_slice(&(part.len() as u32).to_le_bytes());
+            bytes.extend_from_slice(part.as_bytes());
+        }
+    }
+    bytes
+}
+
+/// The variables of a guest's environment, encoded with `encode`
+#[derive(Debug, Clone, Copy)]
+pub struct GuestEnv<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> GuestEnv<'a> {
+    /// Read the environment encoded in `bytes`
+    pub fn new(bytes: &'a [u8]) -> Self {
+        Self { bytes }
+    }
+
+    /// The value of the variable called `name`
+    pub fn get(&self, name: &str) -> Option<&'a str> {
+        self.iter()
+            .find(|(var, _)| *var == name)
+            .map(|(_, value)| value)
+    }
+
+    /// The names and values of the variables, sorted by name. Iteration
+    /// stops early if the encoding is malformed.
+    pub fn iter(&self) -> impl Iterator<Item = (&'a str, &'a str)> {
+        let mut rest = self.bytes;
+        let count = take_str_len(&mut rest).unwrap_or(0);
+        (0..count).map_while(move |_| Some((take_str(&mut rest)?, take_str(&mut rest)?)))
+    }
+}
+
+/// Split a little endian `u32` length off `rest`
+fn take_str_len(rest: &mut &[u8]) -> Option<usize> {
+    let (len, remaining) = rest.split_first_chunk::<4>()?;
+    *rest = remaining;
+    Some(u32::from_le_bytes(*len) as usize)
+}
+
+/// Split a length and a string of that length off `rest`
+fn take_str<'a>(rest: &mut &'a [u8]) -> Option<&'a str> {
+    let len = take_str_len(rest)?;
+    if rest.len() < len {
+        return None;
+    }
+    let (s, remaining) = rest.split_at(len);
+    *rest = remaining;
+    core::str::from_utf8(s).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trip() {
+        let bytes = encode([("b", "2"), ("a", ""), ("c", "three")]);
+        let env = GuestEnv::new(&bytes);
+        assert_eq!(env.get("a"), Some(""));
+        assert_eq!(env.get("c"), Some("three"));
+        assert_eq!(env.get("d"), None);
+        assert!(env.iter().map(|(name, _)| name).eq(["a", "b", "c"]));
+
+        assert_eq!(GuestEnv::new(&[]).iter().count(), 0);
+        assert_eq!(GuestEnv::new(&bytes[..bytes.len() - 1]).iter().count(), 2);
+    }
+}
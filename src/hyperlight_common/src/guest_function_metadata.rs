@@ -0,0 +1,242 @@
+/*
+Copyright 2024 The Hyperlight Authors.
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+    http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use anyhow::{bail, Result};
+
+use crate::flatbuffer_wrappers::function_types::{ParameterType, ReturnType};
+
+/// The section of the guest binary that `register_guest_function!` puts a
+/// record of each function it registers in, so that the host can list a
+/// guest's functions without running it. The name fits in the 8 bytes PE
+/// section names are limited to.
+pub const SECTION_NAME: &str = ".hlfuncs";
+
+/// The first byte of every record. Linkers may pad the section with zeros
+/// between records, which readers skip.
+const RECORD_VERSION: u8 = 1;
+
+/// The signature of a function a guest exposes to the host, as recorded in
+/// the guest binary
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GuestFunctionSignature {
+    /// The name the host calls the function by
+    pub name: String,
+    /// The types of the function's parameters
+    pub parameter_types: Vec<ParameterType>,
+    /// The type of the function's return value
+    pub return_type: ReturnType,
+}
+
+/// The length of the record of a function called `name` with
+/// `parameter_count` parameters
+pub const fn record_len(name: &str, parameter_count: usize) -> usize {
+    // version, name length, name, parameter count, parameters, return type
+    1 + 2 + name.len() + 1 + parameter_count + 1
+}
+
+/// The record of a function, which is `N = record_len(..)` bytes long: the
+/// record version, the length of the name as a little endian `u16`, the
+/// name, the number of parameters as a `u8`, a byte for each parameter type
+/// and a byte for the return type.
+///
+/// This is a `const fn` so that the record can be built into the guest
+/// binary, and panics, failing the guest's build, if `N` is wrong or the
+/// name or parameter list is too long to record.
+pub const fn encode_record<const N: usize>(
+    name: &str,
+    parameter_types: &[ParameterType],
+    return_type: ReturnType,
+) -> [u8; N] {
+    if N != record_len(name, parameter_types.len()) {
+        panic!("the record length doesn't match the function's signature");
+    }
+    if name.len() > u16::MAX as usize || parameter_types.len() > u8::MAX as usize {
+        panic!("the function's name or parameter list is too long to record");
+    }
+
+    let mut record = [0u8; N];
+    record[0] = RECORD_VERSION;
+    let name_len = (name.len() as u16).to_le_bytes();
+    record[1] = name_len[0];
+    record[2] = name_len[1];
+    let name = name.as_bytes();
+    let mut i = 0;
+    while i < name.len() {
+        record[3 + i] = name[i];
+        i += 1;
+    }
+    let mut offset = 3 + name.len();
+    record[offset] = parameter_types.len() as u8;
+    offset += 1;
+    let mut i = 0;
+    while i < parameter_types.len() {
+        record[offset + i] = parameter_type_tag(&parameter_types[i]);
+        i += 1;
+    }
+    record[offset + parameter_types.len()] = return_type_tag(return_type);
+    record
+}
+
+/// Read the signatures recorded in the contents of a guest binary's
+/// `SECTION_NAME` section
+pub fn decode_section(mut section: &[u8]) -> Result<Vec<GuestFunctionSignature>> {
+    let mut signatures = Vec::new();
+    loop {
+        // skip the padding between records
+        while let [0, rest @ ..] = section {
+            section = rest;
+        }
+        let (version, rest) = match section {
+            [] => return Ok(signatures),
+            [version, rest @ ..] => (*version, rest),
+        };
+        if version != RECORD_VERSION {
+            bail!("Unsupported guest function record version {}", version);
+        }
+
+        let (name_len, rest) = split(rest, 2)?;
+        let (name, rest) = split(
+            rest,
+            u16::from_le_bytes([name_len[0], name_len[1]]) as usize,
+        )?;
+        let name = core::str::from_utf8(name)
+            .map_err(|e| anyhow::anyhow!("Guest function name isn't UTF-8: {}", e))?;
+        let (parameter_count, rest) = split(rest, 1)?;
+        let (parameters, rest) = split(rest, parameter_count[0] as usize)?;
+        let (return_type, rest) = split(rest, 1)?;
+
+        signatures.push(GuestFunctionSignature {
+            name: String::from(name),
+            parameter_types: parameters
+                .iter()
+                .map(|tag| parameter_type(*tag))
+                .collect::<Result<_>>()?,
+            return_type: return_type_from_tag(return_type[0])?,
+        });
+        section = rest;
+    }
+}
+
+fn split(bytes: &[u8], len: usize) -> Result<(&[u8], &[u8])> {
+    if bytes.len() < len {
+        bail!("Guest function record is truncated");
+    }
+    Ok(bytes.split_at(len))
+}
+
+const fn parameter_type_tag(parameter_type: &ParameterType) -> u8 {
+    match parameter_type {
+        ParameterType::Int => 0,
+        ParameterType::UInt => 1,
+        ParameterType::Long => 2,
+        ParameterType::ULong => 3,
+        ParameterType::Float => 4,
+        ParameterType::Double => 5,
+        ParameterType::String => 6,
+        ParameterType::Bool => 7,
+        ParameterType::VecBytes => 8,
+        ParameterType::Secret => 9,
+    }
+}
+
+fn parameter_type(tag: u8) -> Result<ParameterType> {
+    Ok(match tag {
+        0 => ParameterType::Int,
+        1 => ParameterType::UInt,
+        2 => ParameterType::Long,
+        3 => ParameterType::ULong,
+        4 => ParameterType::Float,
+        5 => ParameterType::Double,
+        6 => ParameterType::String,
+        7 => ParameterType::Bool,
+        8 => ParameterType::VecBytes,
+        9 => ParameterType::Secret,
+        other => bail!("Unknown guest function parameter type {}", other),
+    })
+}
+
+const fn return_type_tag(return_type: ReturnType) -> u8 {
+    match return_type {
+        ReturnType::Int => 0,
+        ReturnType::UInt => 1,
+        ReturnType::Long => 2,
+        ReturnType::ULong => 3,
+        ReturnType::Float => 4,
+        ReturnType::Double => 5,
+        ReturnType::String => 6,
+        ReturnType::Bool => 7,
+        ReturnType::Void => 8,
+        ReturnType::VecBytes => 9,
+    }
+}
+
+fn return_type_from_tag(tag: u8) -> Result<ReturnType> {
+    Ok(match tag {
+        0 => ReturnType::Int,
+        1 => ReturnType::UInt,
+        2 => ReturnType::Long,
+        3 => ReturnType::ULong,
+        4 => ReturnType::Float,
+        5 => ReturnType::Double,
+        6 => ReturnType::String,
+        7 => ReturnType::Bool,
+        8 => ReturnType::Void,
+        9 => ReturnType::VecBytes,
+        other => bail!("Unknown guest function return type {}", other),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::vec;
+
+    use super::*;
+
+    #[test]
+    fn round_trip() {
+        const ECHO: [u8; record_len("Echo", 1)] =
+            encode_record("Echo", &[ParameterType::String], ReturnType::String);
+        const NOOP: [u8; record_len("Noop", 0)] = encode_record("Noop", &[], ReturnType::Void);
+
+        // as a linker might lay the records out
+        let mut section = ECHO.to_vec();
+        section.extend_from_slice(&[0; 3]);
+        section.extend_from_slice(&NOOP);
+        section.extend_from_slice(&[0; 8]);
+
+        assert_eq!(
+            decode_section(&section).unwrap(),
+            vec![
+                GuestFunctionSignature {
+                    name: "Echo".into(),
+                    parameter_types: vec![ParameterType::String],
+                    return_type: ReturnType::String,
+                },
+                GuestFunctionSignature {
+                    name: "Noop".into(),
+                    parameter_types: vec![],
+                    return_type: ReturnType::Void,
+                },
+            ]
+        );
+        assert!(decode_section(&[]).unwrap().is_empty());
+        assert!(decode_section(&ECHO[..ECHO.len() - 1]).is_err());
+        assert!(decode_section(&[2, 0, 0, 0, 0]).is_err());
+    }
+}
@@ -0,0 +1,213 @@
+/*
+Copyright 2024 The Hyperlight Authors.
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+    http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+use alloc::vec::Vec;
+
+use anyhow::{bail, Error, Result};
+
+/// The name of the host function that guests built with the
+/// `heap_profiling` feature call to send their heap profile to the host.
+/// Every sandbox registers it, so hosts must not use the name themselves.
+pub const HEAP_PROFILE_HOST_FUNCTION: &str = "HyperlightHeapProfile";
+
+/// The number of buckets in the allocation size histogram. Bucket `i`
+/// counts the allocations of at least `2^i` bytes and less than
+/// `2^(i + 1)`, except the first, which also counts empty allocations, and
+/// the last, which counts everything bigger.
+pub const SIZE_BUCKETS: usize = 32;
+
+/// The number of return addresses recorded for each allocation
+pub const CALL_STACK_DEPTH: usize = 4;
+
+/// The number of distinct call stacks a guest keeps track of. Allocations
+/// from stacks seen after the table is full are only counted in
+/// `HeapProfile::untracked_allocations`.
+pub const MAX_CALL_SITES: usize = 64;
+
+/// The bucket of the allocation size histogram that `size` falls in
+pub fn size_bucket(size: usize) -> usize {
+    match size {
+        0 => 0,
+        size => (size.ilog2() as usize).min(SIZE_BUCKETS - 1),
+    }
+}
+
+/// The allocations made from one call stack in the guest
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct HeapCallSite {
+    /// The return addresses of the stack, innermost first, starting in the
+    /// function that called the allocator. There may be fewer than
+    /// `CALL_STACK_DEPTH` if the stack was shallower.
+    pub stack: Vec<u64>,
+    /// The number of allocations made from the stack
+    pub allocations: u64,
+    /// The number of bytes allocated from the stack
+    pub bytes: u64,
+}
+
+/// What a guest built with the `heap_profiling` feature has allocated since
+/// it started, or since its profile was last reset.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct HeapProfile {
+    /// The number of allocations, including reallocations
+    pub allocations: u64,
+    /// The number of deallocations, including reallocations
+    pub deallocations: u64,
+    /// The total number of bytes allocated
+    pub allocated_bytes: u64,
+    /// The number of bytes that are allocated and not yet freed
+    pub live_bytes: u64,
+    /// The highest `live_bytes` has been
+    pub peak_live_bytes: u64,
+    /// The number of allocations in each size bucket, see `size_bucket`
+    pub size_histogram: Vec<u64>,
+    /// The call stacks allocations were made from, the one that allocated
+    /// the most bytes first
+    pub call_sites: Vec<HeapCallSite>,
+    /// The number of allocations made from call stacks that didn't fit in
+    /// the guest's table of call sites
+    pub untracked_allocations: u64,
+}
+
+/// A heap profile is sent as a sequence of little endian `u64`s: the
+/// counters, the histogram, then the number of call sites followed by each
+/// call site's counters, stack length and stack.
+impl From<&HeapProfile> for Vec<u8> {
+    fn from(profile: &HeapProfile) -> Vec<u8> {
+        let mut words = Vec::new();
+        words.extend_from_slice(&[
+            profile.allocations,
+            profile.deallocations,
+            profile.allocated_bytes,
+            profile.live_bytes,
+            profile.peak_live_bytes,
+            profile.untracked_allocations,
+            profile.size_histogram.len() as u64,
+        ]);
+        words.extend_from_slice(&profile.size_histogram);
+        words.push(profile.call_sites.len() as u64);
+        for site in &profile.call_sites {
+            words.extend_from_slice(&[site.allocations, site.bytes, site.stack.len() as u64]);
+            words.extend_from_slice(&site.stack);
+        }
+        words.iter().flat_map(|word| word.to_le_bytes()).collect()
+    }
+}
+
+impl TryFrom<&[u8]> for HeapProfile {
+    type Error = Error;
+    fn try_from(raw_bytes: &[u8]) -> Result<Self> {
+        if raw_bytes.len() % 8 != 0 {
+            bail!("Heap profile is {} bytes long", raw_bytes.len());
+        }
+        let mut words = raw_bytes.chunks_exact(8).map(|word| {
+            u64::from_le_bytes([
+                word[0], word[1], word[2], word[3], word[4], word[5], word[6], word[7],
+            ])
+        });
+        let mut next = || match words.next() {
+            Some(word) => Ok(word),
+            None => bail!("Heap profile is truncated"),
+        };
+
+        let allocations = next()?;
+        let deallocations = next()?;
+        let allocated_bytes = next()?;
+        let live_bytes = next()?;
+        let peak_live_bytes = next()?;
+        let untracked_allocations = next()?;
+        let size_histogram = read_words(&mut next)?;
+        let call_sites = (0..next()?)
+            .map(|_| {
+                let allocations = next()?;
+                let bytes = next()?;
+                let stack = read_words(&mut next)?;
+                Ok(HeapCallSite {
+                    stack,
+                    allocations,
+                    bytes,
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        Ok(Self {
+            allocations,
+            deallocations,
+            allocated_bytes,
+            live_bytes,
+            peak_live_bytes,
+            size_histogram,
+            call_sites,
+            untracked_allocations,
+        })
+    }
+}
+
+/// Read a length followed by that many words
+fn read_words(next: &mut dyn FnMut() -> Result<u64>) -> Result<Vec<u64>> {
+    let len = next()?;
+    (0..len).map(|_| next()).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::vec;
+
+    use super::*;
+
+    #[test]
+    fn size_buckets() {
+        assert_eq!(size_bucket(0), 0);
+        assert_eq!(size_bucket(1), 0);
+        assert_eq!(size_bucket(2), 1);
+        assert_eq!(size_bucket(4095), 11);
+        assert_eq!(size_bucket(4096), 12);
+        assert_eq!(size_bucket(usize::MAX), SIZE_BUCKETS - 1);
+    }
+
+    #[test]
+    fn round_trip() {
+        let mut size_histogram = vec![0; SIZE_BUCKETS];
+        size_histogram[4] = 2;
+        size_histogram[12] = 1;
+        let profile = HeapProfile {
+            allocations: 3,
+            deallocations: 1,
+            allocated_bytes: 4128,
+            live_bytes: 4096,
+            peak_live_bytes: 4128,
+            size_histogram,
+            call_sites: vec![
+                HeapCallSite {
+                    stack: vec![0x1000, 0x2000, 0x3000, 0x4000],
+                    allocations: 1,
+                    bytes: 4096,
+                },
+                HeapCallSite {
+                    stack: vec![0x5000],
+                    allocations: 2,
+                    bytes: 32,
+                },
+            ],
+            untracked_allocations: 0,
+        };
+
+        let bytes = Vec::from(&profile);
+        assert_eq!(HeapProfile::try_from(bytes.as_slice()).unwrap(), profile);
+        assert!(HeapProfile::try_from(&bytes[..bytes.len() - 8]).is_err());
+        assert!(HeapProfile::try_from(&bytes[..5]).is_err());
+    }
+}
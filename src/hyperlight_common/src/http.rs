@@ -0,0 +1,125 @@
+/*
+Copyright 2024 The Hyperlight Authors.
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+    http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use anyhow::{bail, Error, Result};
+
+/// The response to a request a guest made with the
+/// `hyperlight::http::request` built-in service
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct HttpResponse {
+    /// The status code, such as 200
+    pub status: u16,
+    /// The names and values of the headers, in the order the server sent
+    /// them
+    pub headers: Vec<(String, String)>,
+    /// The body
+    pub body: Vec<u8>,
+}
+
+impl HttpResponse {
+    /// The value of the first header called `name`, ignoring case
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(header, _)| header.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+}
+
+/// A response is sent as the status as a little endian `u16`, the number
+/// of headers as a little endian `u32`, each header's name and value as a
+/// little endian `u32` length followed by that many bytes, then the body.
+impl From<&HttpResponse> for Vec<u8> {
+    fn from(response: &HttpResponse) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(response.body.len() + 6);
+        bytes.extend_from_slice(&response.status.to_le_bytes());
+        bytes.extend_from_slice(&(response.headers.len() as u32).to_le_bytes());
+        for (name, value) in &response.headers {
+            for part in [name, value] {
+                bytes.extend_from_slice(&(part.len() as u32).to_le_bytes());
+                bytes.extend_from_slice(part.as_bytes());
+            }
+        }
+        bytes.extend_from_slice(&response.body);
+        bytes
+    }
+}
+
+impl TryFrom<&[u8]> for HttpResponse {
+    type Error = Error;
+    fn try_from(raw_bytes: &[u8]) -> Result<Self> {
+        let mut rest = raw_bytes;
+        let status = take(&mut rest, 2)?;
+        let status = u16::from_le_bytes([status[0], status[1]]);
+        let mut headers = Vec::new();
+        for _ in 0..take_len(&mut rest)? {
+            let len = take_len(&mut rest)?;
+            let name = String::from_utf8(take(&mut rest, len)?.to_vec())?;
+            let len = take_len(&mut rest)?;
+            let value = String::from_utf8(take(&mut rest, len)?.to_vec())?;
+            headers.push((name, value));
+        }
+
+        Ok(Self {
+            status,
+            headers,
+            body: rest.to_vec(),
+        })
+    }
+}
+
+/// Split the first `len` bytes off `rest`
+fn take<'a>(rest: &mut &'a [u8], len: usize) -> Result<&'a [u8]> {
+    if rest.len() < len {
+        bail!("HTTP response is truncated");
+    }
+    let (taken, remaining) = rest.split_at(len);
+    *rest = remaining;
+    Ok(taken)
+}
+
+/// Split a little endian `u32` length off `rest`
+fn take_len(rest: &mut &[u8]) -> Result<usize> {
+    let bytes = take(rest, 4)?;
+    Ok(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]) as usize)
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::string::ToString;
+    use alloc::vec;
+
+    use super::*;
+
+    #[test]
+    fn round_trip() {
+        let response = HttpResponse {
+            status: 404,
+            headers: vec![
+                ("Content-Type".to_string(), "text/plain".to_string()),
+                ("X-Empty".to_string(), String::new()),
+            ],
+            body: b"not found".to_vec(),
+        };
+        let bytes = Vec::from(&response);
+        assert_eq!(HttpResponse::try_from(bytes.as_slice()).unwrap(), response);
+        assert_eq!(response.header("content-type"), Some("text/plain"));
+        assert!(HttpResponse::try_from(&bytes[..9]).is_err());
+    }
+}
@@ -0,0 +1,70 @@
+/*
+Copyright 2024 The Hyperlight Authors.
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+    http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+//! Integrity tags for elements pushed onto the shared input and output
+//! buffers.
+//!
+//! When the host turns shared buffer integrity checks on, it sets
+//! `SharedBufferIntegrity::enabled` in the PEB, and every element pushed
+//! onto either buffer is followed by an `INTEGRITY_TAG_LEN` byte tag that
+//! covers the element's data and where it starts in the buffer.
+//!
+//! The host tags the elements it pushes onto the input buffer with an
+//! HMAC-SHA256, keyed with a key it generates when the sandbox is created
+//! and never writes to shared memory. It checks the tags of the elements
+//! still on the input buffer before it next pushes onto it or pops what
+//! the guest returned, so a guest that rewrites their data or framing is
+//! caught.
+//!
+//! The guest tags the elements it pushes onto the output buffer with
+//! `integrity_tag`, which the host checks before popping them, along with
+//! their length prefix.
+
+use sha2::{Digest, Sha256};
+
+/// The length of the tag appended to each element
+pub const INTEGRITY_TAG_LEN: usize = 32;
+
+/// Compute the tag the guest appends to `element`, which starts
+/// `element_offset` bytes into the output buffer
+pub fn integrity_tag(element_offset: u64, element: &[u8]) -> [u8; INTEGRITY_TAG_LEN] {
+    let mut digest = Sha256::new();
+    digest.update(element_offset.to_le_bytes());
+    digest.update(element);
+    digest.finalize().into()
+}
+
+/// Check that `tag` is the tag for `element`, which starts
+/// `element_offset` bytes into the output buffer
+pub fn verify_integrity_tag(element_offset: u64, element: &[u8], tag: &[u8]) -> bool {
+    integrity_tag(element_offset, element) == tag
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tags_cover_the_element_and_its_offset() {
+        let element = b"\x04\0\0\0data";
+        let tag = integrity_tag(8, element);
+        assert!(verify_integrity_tag(8, element, &tag));
+
+        assert!(!verify_integrity_tag(16, element, &tag));
+        assert!(!verify_integrity_tag(8, b"\x04\0\0\0dat4", &tag));
+        assert!(!verify_integrity_tag(8, element, &tag[1..]));
+    }
+}
@@ -18,6 +18,9 @@ limitations under the License.
 
 extern crate alloc;
 
+/// cbindgen:ignore
+/// The names of the host functions the host's built-in services provide
+pub mod builtin_services;
 pub mod flatbuffer_wrappers;
 /// cbindgen:ignore
 /// FlatBuffers-related utilities and (mostly) generated code
@@ -30,4 +33,28 @@ pub mod flatbuffer_wrappers;
 )]
 mod flatbuffers;
 /// cbindgen:ignore
+/// The environment variables hosts give guests when they are loaded
+pub mod guest_env;
+/// cbindgen:ignore
+/// The records of guest functions built into guest binaries
+pub mod guest_function_metadata;
+/// cbindgen:ignore
+/// The heap profiles guests built with the `heap_profiling` feature send to
+/// the host
+pub mod heap_profile;
+/// cbindgen:ignore
+/// The responses to the HTTP requests guests make through the host
+pub mod http;
+/// cbindgen:ignore
+pub mod integrity;
+/// cbindgen:ignore
 pub mod mem;
+/// cbindgen:ignore
+/// Outb ports reserved for user-defined channels between guest and host
+pub mod outb;
+/// cbindgen:ignore
+/// The progress guests report while running guest function calls
+pub mod progress;
+/// cbindgen:ignore
+/// Secrets passed between guest and host, and helpers for handling them
+pub mod secret;
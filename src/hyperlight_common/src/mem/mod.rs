@@ -22,6 +22,25 @@ pub const PAGE_SIZE_USIZE: usize = 1 << 12;
 
 use core::ffi::{c_char, c_void};
 
+/// Set in the length prefix of an element of the input or output buffer
+/// when the element is a segment of a value too large for the buffer, and
+/// more segments follow. The receiver appends the data of each segment to
+/// the ones before it, up to and including the first segment without the
+/// flag. Flatbuffers can't be larger than 2GB, so the flag is never set in
+/// the size prefix of a whole value.
+///
+/// Both sides zero the copies they make of a value sent in segments once
+/// they are done with them. Values this large are rare enough to zero
+/// whether or not they hold secrets.
+pub const CHUNK_FLAG: u32 = 1 << 31;
+
+/// The version of the interface between the host and the guest: the layout
+/// of the PEB and the protocols the two speak over it. The guest writes it
+/// to `HyperlightPEB::guestAbiVersion` when it starts, and the host refuses
+/// to run guests built against any other version. Bump it whenever a change
+/// means an existing guest binary would no longer work with the host.
+pub const GUEST_ABI_VERSION: u64 = 1;
+
 #[repr(C)]
 pub struct HostFunctionDefinitions {
     pub fbHostFunctionDetailsSize: u64,
@@ -49,6 +68,16 @@ pub enum RunMode {
     Invalid = 4,
 }
 
+/// How the guest signals the host when it runs under a hypervisor: with
+/// `out dx, al`, or by writing to the MMIO doorbell region described in
+/// `crate::outb`.
+#[repr(u64)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum OutBTransport {
+    PortIo = 0,
+    Mmio = 1,
+}
+
 #[repr(C)]
 pub struct InputData {
     pub inputDataSize: u64,
@@ -59,6 +88,29 @@ pub struct InputData {
 pub struct OutputData {
     pub outputDataSize: u64,
     pub outputDataBuffer: *mut c_void,
+    /// The size the guest can grow `outputDataSize` to when the data it
+    /// pushes doesn't fit, which the host has reserved memory for
+    pub outputDataMaxSize: u64,
+}
+
+/// Negotiates compression of large `VecBytes` values, see
+/// `crate::flatbuffer_wrappers::compression`
+#[repr(C)]
+pub struct CompressionData {
+    /// Values at least this many bytes long may be compressed, 0 turns
+    /// compression off. Set by the host.
+    pub compressionThreshold: u64,
+    /// Bits such as `LZ4_SUPPORTED` for the compression the guest can
+    /// decode. Set by the guest when it starts.
+    pub guestCompressionSupport: u64,
+}
+
+/// Whether elements pushed onto the shared buffers are tagged, see
+/// `crate::integrity`
+#[repr(C)]
+pub struct SharedBufferIntegrity {
+    /// Non-zero if integrity checks are on. Set by the host.
+    pub enabled: u64,
 }
 
 #[repr(C)]
@@ -79,6 +131,49 @@ pub struct GuestStackData {
     pub bootStackAddress: u64,
 }
 
+/// Where the guest finds the environment the host gave it, see
+/// `crate::guest_env`
+#[repr(C)]
+pub struct GuestEnvData {
+    /// The length of the encoded environment, 0 if the host gave none
+    pub guestEnvSize: u64,
+    /// Points to the encoded environment, in memory the guest can read but
+    /// not write
+    pub guestEnvBuffer: *mut c_void,
+}
+
+/// Where the guest finds the arguments the host gave `hyperlight_main`
+#[repr(C)]
+pub struct StartupArgsData {
+    /// The length of the `FunctionCall` flatbuffer holding the arguments, 0
+    /// if the host gave none
+    pub startupArgsSize: u64,
+    /// Points to the arguments, in memory the guest can read but not write
+    pub startupArgsBuffer: *mut c_void,
+}
+
+/// The heap pages the guest has freed and wants the host to reclaim, set
+/// before it raises `OutBAction::ReleaseMemory`
+#[repr(C)]
+pub struct ReleasedMemoryData {
+    /// The offset of the first page from the start of the guest heap
+    pub releasedMemoryOffset: u64,
+    /// The number of bytes to reclaim, a multiple of the page size
+    pub releasedMemorySize: u64,
+}
+
+/// How long the host gives the guest function call that is running,
+/// updated by the host each time it calls the guest
+#[repr(C)]
+pub struct CallDeadlineData {
+    /// The nanoseconds the host gave the call when it dispatched it, 0 if
+    /// the call has no deadline
+    pub callDeadlineNanos: u64,
+    /// The frequency of the guest's time stamp counter in kHz, for
+    /// measuring how long the call has run, 0 if the host doesn't know it
+    pub guestTscKhz: u64,
+}
+
 #[repr(C)]
 pub struct GuestPanicContextData {
     pub guestPanicContextDataSize: u64,
@@ -87,6 +182,10 @@ pub struct GuestPanicContextData {
 
 #[repr(C)]
 pub struct HyperlightPEB {
+    /// Set by the guest to `GUEST_ABI_VERSION` when it starts. This must
+    /// stay the first field so that the host can find it whichever version
+    /// of the PEB the guest was built against.
+    pub guestAbiVersion: u64,
     pub security_cookie_seed: u64,
     pub guest_function_dispatch_ptr: u64,
     pub hostFunctionDefinitions: HostFunctionDefinitions,
@@ -96,9 +195,16 @@ pub struct HyperlightPEB {
     pub pOutb: *mut c_void,
     pub pOutbContext: *mut c_void,
     pub runMode: RunMode,
+    pub outbTransport: OutBTransport,
     pub inputdata: InputData,
     pub outputdata: OutputData,
+    pub compressionData: CompressionData,
+    pub sharedBufferIntegrity: SharedBufferIntegrity,
     pub guestPanicContextData: GuestPanicContextData,
     pub guestheapData: GuestHeapData,
     pub gueststackData: GuestStackData,
+    pub guestEnvData: GuestEnvData,
+    pub startupArgsData: StartupArgsData,
+    pub releasedMemoryData: ReleasedMemoryData,
+    pub callDeadlineData: CallDeadlineData,
 }
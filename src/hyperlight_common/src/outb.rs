@@ -0,0 +1,74 @@
+/*
+Copyright 2024 The Hyperlight Authors.
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+    http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+/// The first outb port reserved for channels defined by users of
+/// Hyperlight, rather than by Hyperlight itself. Guests signal the host on
+/// these ports and the host registers a handler for each port it serves.
+pub const USER_PORT_BASE: u16 = 0x1000;
+
+/// The number of outb ports reserved for user-defined channels, starting
+/// at `USER_PORT_BASE`.
+pub const USER_PORT_COUNT: u16 = 0x100;
+
+/// Returns whether `port` is in the range reserved for user-defined
+/// channels.
+pub fn is_user_port(port: u16) -> bool {
+    (USER_PORT_BASE..USER_PORT_BASE + USER_PORT_COUNT).contains(&port)
+}
+
+/// The guest physical address of the MMIO doorbell region, which guests
+/// use instead of port I/O when the PEB says `OutBTransport::Mmio`. A
+/// one-byte write of `value` to `MMIO_DOORBELL_BASE + port` is handled by
+/// the host exactly like `out port, value`.
+///
+/// The region sits in the first 2MB of the guest address space, below any
+/// memory the host maps into the guest, so that every access to it exits
+/// to the host.
+pub const MMIO_DOORBELL_BASE: u64 = 0x100000;
+
+/// The size of the MMIO doorbell region, which has one byte for every
+/// possible port.
+pub const MMIO_DOORBELL_SIZE: u64 = 0x10000;
+
+/// The doorbell port that guests ring to halt on architectures without an
+/// instruction that exits to the host, in place of `hlt` on x86.
+pub const MMIO_HALT_PORT: u16 = 103;
+
+/// Returns the port that a write to the guest physical address `addr`
+/// signals, or `None` if `addr` is outside the MMIO doorbell region.
+pub fn doorbell_port(addr: u64) -> Option<u16> {
+    match addr.checked_sub(MMIO_DOORBELL_BASE) {
+        Some(port) if port < MMIO_DOORBELL_SIZE => Some(port as u16),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{doorbell_port, MMIO_DOORBELL_BASE, MMIO_DOORBELL_SIZE};
+
+    #[test]
+    fn doorbell_ports() {
+        assert_eq!(doorbell_port(MMIO_DOORBELL_BASE), Some(0));
+        assert_eq!(doorbell_port(MMIO_DOORBELL_BASE + 0x1000), Some(0x1000));
+        assert_eq!(
+            doorbell_port(MMIO_DOORBELL_BASE + MMIO_DOORBELL_SIZE - 1),
+            Some(u16::MAX)
+        );
+        assert_eq!(doorbell_port(MMIO_DOORBELL_BASE - 1), None);
+        assert_eq!(doorbell_port(MMIO_DOORBELL_BASE + MMIO_DOORBELL_SIZE), None);
+    }
+}
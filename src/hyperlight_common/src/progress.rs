@@ -0,0 +1,22 @@
+/*
+Copyright 2024 The Hyperlight Authors.
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+    http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+/// The name of the host function guests call to report the progress of the
+/// guest function call they are running, as `hyperlight::progress::report(
+/// UInt, String) -> Void` with a percentage and a message. Every sandbox
+/// has it, under the `builtin_services::RESERVED_PREFIX` hosts can't
+/// register functions under themselves.
+pub const REPORT_PROGRESS_HOST_FUNCTION: &str = "hyperlight::progress::report";
@@ -0,0 +1,131 @@
+/*
+Copyright 2024 The Hyperlight Authors.
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+    http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+use alloc::vec::Vec;
+use core::fmt;
+use core::hint::black_box;
+use core::ptr::write_volatile;
+use core::sync::atomic::{compiler_fence, Ordering};
+
+/// Bytes that must not leak, such as a password or a key, passed between
+/// guest and host as a `ParameterValue::Secret`.
+///
+/// The bytes are zeroed when the `Secret` is dropped, are never compressed
+/// on their way across, and are left out of its `Debug` output, so they
+/// don't end up in logs or error messages. Comparing two secrets takes
+/// the same time wherever they differ.
+#[derive(Clone, Default)]
+pub struct Secret(Vec<u8>);
+
+impl Secret {
+    /// Take ownership of `bytes` as a secret
+    pub fn new(bytes: Vec<u8>) -> Self {
+        Self(bytes)
+    }
+
+    /// The bytes of the secret. Copies made of them aren't zeroed when the
+    /// secret is dropped.
+    pub fn expose(&self) -> &[u8] {
+        &self.0
+    }
+
+    /// Whether the secret is `other`, in time that depends only on the
+    /// length of the two
+    pub fn ct_eq(&self, other: &[u8]) -> bool {
+        ct_eq(&self.0, other)
+    }
+}
+
+impl From<Vec<u8>> for Secret {
+    fn from(bytes: Vec<u8>) -> Self {
+        Self::new(bytes)
+    }
+}
+
+impl PartialEq for Secret {
+    fn eq(&self, other: &Self) -> bool {
+        self.ct_eq(&other.0)
+    }
+}
+
+impl Eq for Secret {}
+
+impl fmt::Debug for Secret {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Secret(<redacted>)")
+    }
+}
+
+impl Drop for Secret {
+    fn drop(&mut self) {
+        zeroize(&mut self.0);
+    }
+}
+
+/// Whether `a` and `b` are equal, in time that depends only on their
+/// lengths
+pub fn ct_eq(a: &[u8], b: &[u8]) -> bool {
+    if a.len() != b.len() {
+        return false;
+    }
+    // hiding each step from the compiler keeps it from turning the fold
+    // into an early return once a difference has been found
+    let difference = a
+        .iter()
+        .zip(b)
+        .fold(0u8, |acc, (x, y)| black_box(acc | (x ^ y)));
+    difference == 0
+}
+
+/// Overwrite `bytes` with zeros in a way the compiler can't optimise
+/// away, even if `bytes` is about to be freed
+pub fn zeroize(bytes: &mut [u8]) {
+    for byte in bytes.iter_mut() {
+        // Safety: `byte` is a valid, aligned reference
+        unsafe { write_volatile(byte, 0) };
+    }
+    compiler_fence(Ordering::SeqCst);
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::{format, vec};
+
+    use super::*;
+
+    #[test]
+    fn secrets_compare_by_value() {
+        let secret = Secret::new(vec![1, 2, 3]);
+        assert!(secret.ct_eq(&[1, 2, 3]));
+        assert!(!secret.ct_eq(&[1, 2, 4]));
+        assert!(!secret.ct_eq(&[1, 2]));
+        assert_eq!(secret, Secret::from(vec![1, 2, 3]));
+        assert_ne!(secret, Secret::default());
+    }
+
+    #[test]
+    fn secrets_are_redacted() {
+        let secret = Secret::new(b"hunter2".to_vec());
+        assert_eq!(format!("{:?}", secret), "Secret(<redacted>)");
+    }
+
+    #[test]
+    fn zeroize_clears_bytes() {
+        let mut bytes = vec![0xffu8; 32];
+        zeroize(&mut bytes);
+        assert!(bytes.iter().all(|&b| b == 0));
+    }
+}
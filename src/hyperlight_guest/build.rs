@@ -39,6 +39,16 @@ fn copy_includes<P: AsRef<Path>, Q: AsRef<Path> + std::fmt::Debug>(include_dir:
     }
 }
 
+/// The flag that keeps frame pointers, so that the host can unwind the
+/// guest's stack, in the syntax of `clang-cl` or of `clang`
+fn frame_pointer_flag(is_pe: bool) -> &'static str {
+    if is_pe {
+        "/Oy-"
+    } else {
+        "-fno-omit-frame-pointer"
+    }
+}
+
 fn cargo_main() {
     println!("cargo:rerun-if-changed=third_party");
     println!("cargo:rerun-if-changed=src/alloca");
@@ -114,6 +124,7 @@ fn cargo_main() {
             cfg.flag("-mstack-probe-size=4096");
             cfg.compiler("clang");
         }
+        cfg.flag(frame_pointer_flag(is_pe));
 
         if cfg!(windows) {
             env::set_var("AR_x86_64_unknown_none", "llvm-ar");
@@ -144,6 +155,22 @@ fn cargo_main() {
         .expect("out dir include dir was not valid utf-8");
     println!("cargo::metadata=include={}", include_str);
 
+    // the linker script and target spec that ELF guests are built with,
+    // see hyperlight-guest-build
+    let manifest_dir =
+        PathBuf::from(env::var("CARGO_MANIFEST_DIR").expect("cargo CARGO_MANIFEST_DIR not set"));
+    for (key, file) in [
+        ("linker_script", "hyperlight_guest.ld"),
+        ("target_spec", "x86_64-hyperlight-none.json"),
+    ] {
+        println!("cargo:rerun-if-changed={}", file);
+        println!(
+            "cargo::metadata={}={}",
+            key,
+            manifest_dir.join(file).display()
+        );
+    }
+
     /* Correctly setting up the libc include paths for downstream
      * libraries which depend on -sys packages which need to build C
      * libraries is surprisingly difficult. Ideally, we would
@@ -283,6 +310,7 @@ fn main() -> std::process::ExitCode {
                 "-fno-stack-protector",
                 "-fstack-clash-protection",
                 "-mstack-probe-size=4096",
+                frame_pointer_flag(false),
             ])
             .arg("-nostdinc")
             .arg("-isystem")
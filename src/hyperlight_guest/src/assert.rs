@@ -0,0 +1,94 @@
+/*
+Copyright 2024 The Hyperlight Authors.
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+    http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+use alloc::format;
+use core::ffi::c_char;
+use core::fmt;
+
+use hyperlight_common::flatbuffer_wrappers::guest_error::ErrorCode;
+
+use crate::entrypoint::abort_with_code_and_message;
+use crate::P_PEB;
+
+/// The code `hl_assert!` and `hl_bail!` abort with unless another one is
+/// given
+pub const DEFAULT_ABORT_CODE: i32 = ErrorCode::GuestError as i32;
+
+/// Abort with `code` and the message `file:line: message`, truncated to
+/// fit in the panic context buffer.
+///
+/// This is used by `hl_assert!` and `hl_bail!`, which should be used
+/// instead of calling it directly.
+#[doc(hidden)]
+pub fn abort_at(code: i32, file: &str, line: u32, message: fmt::Arguments) -> ! {
+    let mut message = format!("{}:{}: {}", file, line, message).replace('\0', " ");
+    // leave room for the NUL terminator
+    let mut max_len = unsafe {
+        (*P_PEB.unwrap())
+            .guestPanicContextData
+            .guestPanicContextDataSize as usize
+    }
+    .saturating_sub(1);
+    if message.len() > max_len {
+        while !message.is_char_boundary(max_len) {
+            max_len -= 1;
+        }
+        message.truncate(max_len);
+    }
+    message.push('\0');
+    unsafe { abort_with_code_and_message(code, message.as_ptr() as *const c_char) }
+}
+
+/// Abort the guest, reporting the file and line the macro was called from
+/// followed by a formatted message to the host, which returns it in
+/// `HyperlightError::GuestAborted`.
+///
+/// The guest aborts with `DEFAULT_ABORT_CODE` unless a code is given:
+///
+/// ```ignore
+/// hl_bail!("unsupported format {}", format);
+/// hl_bail!(code: 42, "unsupported format {}", format);
+/// ```
+#[macro_export]
+macro_rules! hl_bail {
+    (code: $code:expr, $($arg:tt)+) => {
+        $crate::assert::abort_at($code as i32, file!(), line!(), format_args!($($arg)+))
+    };
+    ($($arg:tt)+) => {
+        $crate::hl_bail!(code: $crate::assert::DEFAULT_ABORT_CODE, $($arg)+)
+    };
+}
+
+/// Abort the guest as `hl_bail!` does if `cond` is false, with either the
+/// given message or the condition that failed.
+///
+/// ```ignore
+/// hl_assert!(len <= MAX_LEN);
+/// hl_assert!(len <= MAX_LEN, "{} bytes is too long", len);
+/// ```
+#[macro_export]
+macro_rules! hl_assert {
+    ($cond:expr $(,)?) => {
+        if !$cond {
+            $crate::hl_bail!("assertion failed: {}", stringify!($cond))
+        }
+    };
+    ($cond:expr, $($arg:tt)+) => {
+        if !$cond {
+            $crate::hl_bail!($($arg)+)
+        }
+    };
+}
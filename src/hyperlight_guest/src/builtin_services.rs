@@ -0,0 +1,155 @@
+/*
+Copyright 2024 The Hyperlight Authors.
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+    http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+//! Calls to the host's built-in services, which the host must have
+//! registered with `BuiltinServices::register`.
+
+use alloc::string::String;
+use alloc::vec;
+use alloc::vec::Vec;
+
+use hyperlight_common::builtin_services::{
+    BUS_PUBLISH, BUS_RECEIVE, BUS_SUBSCRIBE, ENTROPY_RANDOM_BYTES, KV_DELETE, KV_GET, KV_SET,
+    METRICS_INCREMENT, PRINT, TIME_MONOTONIC_NANOS, TIME_UNIX_NANOS,
+};
+use hyperlight_common::flatbuffer_wrappers::function_types::{ParameterValue, ReturnType};
+
+use crate::error::Result;
+use crate::host_function_call::{
+    call_host_function, get_host_value_return_as_int, get_host_value_return_as_ulong,
+    get_host_value_return_as_vecbytes, get_host_value_return_as_void,
+};
+
+/// Write `message` to the host's standard output, returning the number of
+/// bytes written
+pub fn print(message: &str) -> Result<i32> {
+    call_host_function(
+        PRINT,
+        Some(vec![ParameterValue::String(String::from(message))]),
+        ReturnType::Int,
+    )?;
+    get_host_value_return_as_int()
+}
+
+/// The host's wall clock time, in nanoseconds since the Unix epoch
+pub fn unix_nanos() -> Result<u64> {
+    call_host_function(TIME_UNIX_NANOS, None, ReturnType::ULong)?;
+    get_host_value_return_as_ulong()
+}
+
+/// Nanoseconds since the host registered the built-in services, which
+/// never go backwards
+pub fn monotonic_nanos() -> Result<u64> {
+    call_host_function(TIME_MONOTONIC_NANOS, None, ReturnType::ULong)?;
+    get_host_value_return_as_ulong()
+}
+
+/// `len` random bytes from the host's operating system
+pub fn random_bytes(len: i32) -> Result<Vec<u8>> {
+    call_host_function(
+        ENTROPY_RANDOM_BYTES,
+        Some(vec![ParameterValue::Int(len)]),
+        ReturnType::VecBytes,
+    )?;
+    get_host_value_return_as_vecbytes()
+}
+
+/// Add `value` to the host's counter called `name`, which the host must
+/// have declared
+pub fn increment_counter(name: &str, value: u64) -> Result<()> {
+    call_host_function(
+        METRICS_INCREMENT,
+        Some(vec![
+            ParameterValue::String(String::from(name)),
+            ParameterValue::ULong(value),
+        ]),
+        ReturnType::Void,
+    )?;
+    get_host_value_return_as_void()
+}
+
+/// The value of `key` in the key-value store the host keeps for the
+/// sandbox, which outlives calls to the guest
+pub fn kv_get(key: &str) -> Result<Option<Vec<u8>>> {
+    call_host_function(
+        KV_GET,
+        Some(vec![ParameterValue::String(String::from(key))]),
+        ReturnType::VecBytes,
+    )?;
+    let value = get_host_value_return_as_vecbytes()?;
+    Ok((!value.is_empty()).then_some(value))
+}
+
+/// Set `key` to `value` in the sandbox's key-value store, or delete it if
+/// `value` is empty. This fails if the store would go over the quotas the
+/// host set.
+pub fn kv_set(key: &str, value: &[u8]) -> Result<()> {
+    call_host_function(
+        KV_SET,
+        Some(vec![
+            ParameterValue::String(String::from(key)),
+            ParameterValue::VecBytes(value.to_vec()),
+        ]),
+        ReturnType::Void,
+    )?;
+    get_host_value_return_as_void()
+}
+
+/// Delete `key` from the sandbox's key-value store
+pub fn kv_delete(key: &str) -> Result<()> {
+    call_host_function(
+        KV_DELETE,
+        Some(vec![ParameterValue::String(String::from(key))]),
+        ReturnType::Void,
+    )?;
+    get_host_value_return_as_void()
+}
+
+/// Publish `payload`, which can't be empty, to `topic` of the host's event
+/// bus, for every other sandbox subscribed to it
+pub fn publish(topic: &str, payload: &[u8]) -> Result<()> {
+    call_host_function(
+        BUS_PUBLISH,
+        Some(vec![
+            ParameterValue::String(String::from(topic)),
+            ParameterValue::VecBytes(payload.to_vec()),
+        ]),
+        ReturnType::Void,
+    )?;
+    get_host_value_return_as_void()
+}
+
+/// Start queueing the events published to `topic` for `receive`
+pub fn subscribe(topic: &str) -> Result<()> {
+    call_host_function(
+        BUS_SUBSCRIBE,
+        Some(vec![ParameterValue::String(String::from(topic))]),
+        ReturnType::Void,
+    )?;
+    get_host_value_return_as_void()
+}
+
+/// The oldest event queued on `topic` since the guest subscribed to it,
+/// if there is one
+pub fn receive(topic: &str) -> Result<Option<Vec<u8>>> {
+    call_host_function(
+        BUS_RECEIVE,
+        Some(vec![ParameterValue::String(String::from(topic))]),
+        ReturnType::VecBytes,
+    )?;
+    let event = get_host_value_return_as_vecbytes()?;
+    Ok((!event.is_empty()).then_some(event))
+}
@@ -0,0 +1,59 @@
+/*
+Copyright 2024 The Hyperlight Authors.
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+    http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+use alloc::vec::Vec;
+
+use flatbuffers::FlatBufferBuilder;
+use hyperlight_common::flatbuffer_wrappers::compression::LZ4_SUPPORTED;
+use hyperlight_common::flatbuffer_wrappers::function_types::ReturnValue;
+
+use crate::P_PEB;
+
+/// Tell the host which compressed values the guest can decode
+pub(crate) fn advertise_compression_support() {
+    unsafe {
+        (*P_PEB.unwrap()).compressionData.guestCompressionSupport = LZ4_SUPPORTED;
+    }
+}
+
+/// The size at which `VecBytes` values sent to the host are compressed, if
+/// the host has turned compression on
+pub(crate) fn compression_threshold() -> Option<usize> {
+    let threshold = unsafe { (*P_PEB.unwrap()).compressionData.compressionThreshold };
+    (threshold != 0).then_some(threshold as usize)
+}
+
+/// Compress the `VecBytes` in a guest function's serialized return value
+/// if it is large enough.
+///
+/// Guest functions serialize their own return values, so they are decoded
+/// and encoded again here rather than each function having to know about
+/// compression.
+pub(crate) fn compress_return_value(result: Vec<u8>) -> Vec<u8> {
+    let Some(threshold) = compression_threshold() else {
+        return result;
+    };
+    // the serialized value is always larger than the bytes it holds
+    if result.len() < threshold {
+        return result;
+    }
+    match ReturnValue::try_from(result.as_slice()) {
+        Ok(return_value @ ReturnValue::VecBytes(_)) => return_value
+            .encode_with_compression(&mut FlatBufferBuilder::new(), Some(threshold))
+            .to_vec(),
+        _ => result,
+    }
+}
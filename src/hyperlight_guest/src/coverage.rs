@@ -0,0 +1,28 @@
+/*
+Copyright 2024 The Hyperlight Authors.
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+    http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+// Guests built with SanitizerCoverage call these from a module constructor
+// to register their counters. The host reads the counters straight out of
+// the `__sancov_cntrs` section instead, so they have nothing to do, but they
+// must exist for the guest to link.
+
+///cbindgen:ignore
+#[no_mangle]
+pub extern "C" fn __sanitizer_cov_8bit_counters_init(_start: *mut u8, _stop: *mut u8) {}
+
+///cbindgen:ignore
+#[no_mangle]
+pub extern "C" fn __sanitizer_cov_pcs_init(_start: *const usize, _stop: *const usize) {}
@@ -14,28 +14,51 @@ See the License for the specific language governing permissions and
 limitations under the License.
 */
 
+use alloc::vec::Vec;
+#[cfg(target_arch = "x86_64")]
 use core::arch::asm;
-use core::ffi::{c_char, c_void, CStr};
+#[cfg(target_arch = "x86_64")]
+use core::ffi::c_void;
+use core::ffi::{c_char, CStr};
 use core::ptr::copy_nonoverlapping;
+use core::slice::from_raw_parts;
 
-use hyperlight_common::mem::{HyperlightPEB, RunMode};
+use hyperlight_common::flatbuffer_wrappers::function_call::FunctionCall;
+use hyperlight_common::flatbuffer_wrappers::function_types::ParameterValue;
+use hyperlight_common::mem::{HyperlightPEB, RunMode, GUEST_ABI_VERSION};
+#[cfg(not(target_arch = "x86_64"))]
+use hyperlight_common::outb::MMIO_HALT_PORT;
 use log::LevelFilter;
 use spin::Once;
 
-use crate::guest_error::reset_error;
+use crate::compression::advertise_compression_support;
+use crate::error::{HyperlightGuestError, Result};
+#[cfg(target_arch = "x86_64")]
+use crate::exceptions::init_idt;
+use crate::guest_error::{reset_error, write_guest_error};
 use crate::guest_function_call::dispatch_function;
 use crate::guest_logger::init_logger;
+#[cfg(not(target_arch = "x86_64"))]
+use crate::host_function_call::mmio_outb;
 use crate::host_function_call::{outb, OutBAction};
+use crate::memory::init_heap;
+use crate::shared_output_data::push_shared_output_data;
 use crate::{
-    __security_cookie, HEAP_ALLOCATOR, MIN_STACK_ADDRESS, OS_PAGE_SIZE, OUTB_PTR,
-    OUTB_PTR_WITH_CONTEXT, P_PEB, RUNNING_MODE,
+    __security_cookie, MIN_STACK_ADDRESS, OS_PAGE_SIZE, OUTB_TRANSPORT, P_PEB,
+    REGISTERED_GUEST_FUNCTIONS, RUNNING_MODE,
 };
+#[cfg(target_arch = "x86_64")]
+use crate::{OUTB_PTR, OUTB_PTR_WITH_CONTEXT};
 
 #[inline(never)]
 pub fn halt() {
     unsafe {
         if RUNNING_MODE == RunMode::Hypervisor {
-            asm!("hlt", options(nostack))
+            #[cfg(target_arch = "x86_64")]
+            asm!("hlt", options(nostack));
+            // there is no instruction that exits to the host, so ring the doorbell instead
+            #[cfg(not(target_arch = "x86_64"))]
+            mmio_outb(MMIO_HALT_PORT, 0);
         }
     }
 }
@@ -72,10 +95,59 @@ extern "C" {
 
 static INIT: Once = Once::new();
 
+/// The arguments the host passed to `hyperlight_main` with
+/// `UninitializedSandbox::set_startup_args`, or none if it passed none.
+/// The guest can read them at any time, but they are meant for
+/// `hyperlight_main` to configure the guest with.
+pub fn startup_args() -> Result<Vec<ParameterValue>> {
+    let args_data = unsafe { &(*P_PEB.unwrap()).startupArgsData };
+    if args_data.startupArgsSize == 0 {
+        return Ok(Vec::new());
+    }
+    // the host never changes the arguments once the guest is running
+    let bytes = unsafe {
+        from_raw_parts(
+            args_data.startupArgsBuffer as *const u8,
+            args_data.startupArgsSize as usize,
+        )
+    };
+    let function_call = FunctionCall::try_from(bytes)?;
+    Ok(function_call.parameters.unwrap_or_default())
+}
+
+/// Report that the guest failed to initialize, for `hyperlight_main` to call
+/// before returning when it can't set the guest up, for example because its
+/// startup arguments are invalid. Creating the sandbox then fails with
+/// `error`, rather than the first guest call finding the guest broken.
+pub fn fail_initialization(error: HyperlightGuestError) {
+    write_guest_error((&error).into());
+}
+
+/// Leave the table of functions registered by `hyperlight_main` in the shared
+/// output buffer, so the host can assign function ids before the first call.
+fn publish_guest_function_details() -> Result<()> {
+    #[allow(static_mut_refs)]
+    let details = unsafe { REGISTERED_GUEST_FUNCTIONS.function_details() };
+    let buffer: Vec<u8> = (&details).try_into()?;
+    push_shared_output_data(&buffer)
+}
+
 // Note: entrypoint cannot currently have a stackframe >4KB, as that will invoke __chkstk on msvc
 //       target without first having setup global `RUNNING_MODE` variable, which __chkstk relies on.
+#[cfg(target_arch = "x86_64")]
 #[no_mangle]
 pub extern "win64" fn entrypoint(peb_address: u64, seed: u64, ops: u64, max_log_level: u64) {
+    entrypoint_impl(peb_address, seed, ops, max_log_level)
+}
+
+#[cfg(not(target_arch = "x86_64"))]
+#[no_mangle]
+pub extern "C" fn entrypoint(peb_address: u64, seed: u64, ops: u64, max_log_level: u64) {
+    entrypoint_impl(peb_address, seed, ops, max_log_level)
+}
+
+#[inline(always)]
+fn entrypoint_impl(peb_address: u64, seed: u64, ops: u64, max_log_level: u64) {
     if peb_address == 0 {
         panic!("PEB address is null");
     }
@@ -97,14 +169,21 @@ pub extern "win64" fn entrypoint(peb_address: u64, seed: u64, ops: u64, max_log_
                 .expect("Invalid log level");
             init_logger(max_log_level);
 
+            advertise_compression_support();
+
             match (*peb_ptr).runMode {
                 RunMode::Hypervisor => {
                     RUNNING_MODE = RunMode::Hypervisor;
+                    OUTB_TRANSPORT = (*peb_ptr).outbTransport;
                     // This static is to make it easier to implement the __chkstk function in assembly.
                     // It also means that should we change the layout of the struct in the future, we
                     // don't have to change the assembly code.
                     MIN_STACK_ADDRESS = (*peb_ptr).gueststackData.minUserStackAddress;
+                    // report CPU exceptions to the host rather than triple faulting
+                    #[cfg(target_arch = "x86_64")]
+                    init_idt();
                 }
+                #[cfg(target_arch = "x86_64")]
                 RunMode::InProcessLinux | RunMode::InProcessWindows => {
                     RUNNING_MODE = (*peb_ptr).runMode;
 
@@ -131,18 +210,22 @@ pub extern "win64" fn entrypoint(peb_address: u64, seed: u64, ops: u64, max_log_
 
             let heap_start = (*peb_ptr).guestheapData.guestHeapBuffer as usize;
             let heap_size = (*peb_ptr).guestheapData.guestHeapSize as usize;
-            HEAP_ALLOCATOR
-                .try_lock()
-                .expect("Failed to access HEAP_ALLOCATOR")
-                .init(heap_start, heap_size);
+            init_heap(heap_start, heap_size);
 
             OS_PAGE_SIZE = ops as u32;
 
+            (*peb_ptr).guestAbiVersion = GUEST_ABI_VERSION;
             (*peb_ptr).guest_function_dispatch_ptr = dispatch_function as usize as u64;
 
             reset_error();
 
             hyperlight_main();
+
+            // creating the sandbox fails with the error, as it does when
+            // `hyperlight_main` calls `fail_initialization`
+            if let Err(e) = publish_guest_function_details() {
+                fail_initialization(e);
+            }
         }
     });
 
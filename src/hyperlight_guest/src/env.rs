@@ -0,0 +1,50 @@
+/*
+Copyright 2024 The Hyperlight Authors.
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+    http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+//! The environment the host gave the guest with
+//! `UninitializedSandbox::set_guest_env`, which the guest reads from its
+//! own memory without calling the host.
+
+use core::slice::from_raw_parts;
+
+use hyperlight_common::guest_env::GuestEnv;
+
+use crate::P_PEB;
+
+fn env() -> GuestEnv<'static> {
+    let env_data = unsafe { &(*P_PEB.unwrap()).guestEnvData };
+    if env_data.guestEnvSize == 0 {
+        return GuestEnv::new(&[]);
+    }
+    // the host never changes the environment once the guest is running
+    let bytes = unsafe {
+        from_raw_parts(
+            env_data.guestEnvBuffer as *const u8,
+            env_data.guestEnvSize as usize,
+        )
+    };
+    GuestEnv::new(bytes)
+}
+
+/// The value of the environment variable called `name`
+pub fn get(name: &str) -> Option<&'static str> {
+    env().get(name)
+}
+
+/// The names and values of the environment variables, sorted by name
+pub fn vars() -> impl Iterator<Item = (&'static str, &'static str)> {
+    env().iter()
+}
@@ -16,8 +16,9 @@ limitations under the License.
 
 use alloc::format;
 use alloc::string::String;
+use alloc::vec::Vec;
 
-use hyperlight_common::flatbuffer_wrappers::guest_error::ErrorCode;
+use hyperlight_common::flatbuffer_wrappers::guest_error::{ErrorCode, GuestError};
 use {anyhow, serde_json};
 
 pub type Result<T> = core::result::Result<T, HyperlightGuestError>;
@@ -26,11 +27,52 @@ pub type Result<T> = core::result::Result<T, HyperlightGuestError>;
 pub struct HyperlightGuestError {
     pub kind: ErrorCode,
     pub message: String,
+    /// The code of an error defined by the guest author, see
+    /// `HyperlightGuestError::custom`.
+    pub custom_code: Option<u32>,
+    /// Data attached to the error, see `HyperlightGuestError::with_payload`.
+    pub payload: Option<Vec<u8>>,
 }
 
 impl HyperlightGuestError {
     pub fn new(kind: ErrorCode, message: String) -> Self {
-        Self { kind, message }
+        Self {
+            kind,
+            message,
+            custom_code: None,
+            payload: None,
+        }
+    }
+
+    /// Create an error with a code defined by the guest author, which the
+    /// host surfaces as `HyperlightError::GuestCustomError`.
+    pub fn custom(code: u32, message: String) -> Self {
+        Self {
+            kind: ErrorCode::GuestError,
+            message,
+            custom_code: Some(code),
+            payload: None,
+        }
+    }
+
+    /// Attach `payload`, for example serialized diagnostics, to the error.
+    /// The host exposes it on the error returned from the guest call.
+    pub fn with_payload(mut self, payload: Vec<u8>) -> Self {
+        self.payload = Some(payload);
+        self
+    }
+}
+
+impl From<&HyperlightGuestError> for GuestError {
+    fn from(error: &HyperlightGuestError) -> Self {
+        let guest_error = match error.custom_code {
+            Some(code) => GuestError::custom(code, error.message.clone()),
+            None => GuestError::new(error.kind.clone(), error.message.clone()),
+        };
+        match &error.payload {
+            Some(payload) => guest_error.with_payload(payload.clone()),
+            None => guest_error,
+        }
     }
 }
 
@@ -39,6 +81,8 @@ impl From<anyhow::Error> for HyperlightGuestError {
         Self {
             kind: ErrorCode::GuestError,
             message: format!("Error: {:?}", error),
+            custom_code: None,
+            payload: None,
         }
     }
 }
@@ -48,6 +92,8 @@ impl From<serde_json::Error> for HyperlightGuestError {
         Self {
             kind: ErrorCode::GuestError,
             message: format!("Error: {:?}", error),
+            custom_code: None,
+            payload: None,
         }
     }
 }
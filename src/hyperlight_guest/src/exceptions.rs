@@ -0,0 +1,308 @@
+/*
+Copyright 2024 The Hyperlight Authors.
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+    http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+use core::arch::{asm, global_asm};
+use core::fmt::Write;
+use core::mem::size_of;
+
+use hyperlight_common::flatbuffer_wrappers::guest_error::ErrorCode;
+
+use crate::host_function_call::{outb, OutBAction};
+use crate::P_PEB;
+
+const EXCEPTION_COUNT: usize = 32;
+const PAGE_FAULT: u64 = 14;
+/// The spacing of the stubs emitted below, so that the address of the stub
+/// for a vector can be computed from the address of the first one
+const STUB_SIZE: u64 = 16;
+/// The selector of the code segment in `GDT`
+const CODE_SELECTOR: u16 = 0x08;
+/// The selector of the task state segment in `GDT`
+const TSS_SELECTOR: u16 = 0x10;
+/// A present, ring 0, 64-bit interrupt gate
+const INTERRUPT_GATE: u8 = 0x8e;
+/// A present, available 64-bit TSS
+const AVAILABLE_TSS: u64 = 0x89;
+/// The interrupt stack table entry the handlers run on
+const EXCEPTION_IST: u8 = 1;
+/// The size of the stack the handlers run on
+const EXCEPTION_STACK_SIZE: usize = 0x4000;
+/// The longest description of an exception reported to the host
+const MAX_MESSAGE_LEN: usize = 128;
+
+extern "win64" {
+    fn hl_exception_stubs();
+}
+
+// Each stub pushes a zero in place of the error code for the exceptions
+// that don't have one, so that every exception leaves the same frame on
+// the stack, then pushes its vector and jumps to the common handler.
+global_asm!(
+    "
+    .global hl_exception_stubs
+    .balign 16
+    hl_exception_stubs:
+    .irp vector, 0,1,2,3,4,5,6,7,8,9,10,11,12,13,14,15,16,17,18,19,20,21,22,23,24,25,26,27,28,29,30,31
+        .balign 16
+        .if (\\vector == 8) || ((\\vector >= 10) && (\\vector <= 14)) || (\\vector == 17) || (\\vector == 21) || (\\vector == 29) || (\\vector == 30)
+        .else
+            push 0
+        .endif
+        push \\vector
+        jmp hl_exception_common
+    .endr
+
+    hl_exception_common:
+        /* Pass the frame to the handler, leaving it shadow space and a 16 byte aligned stack */
+        mov rcx, rsp
+        sub rsp, 0x28
+        call {handler}
+        hlt",
+    handler = sym hl_exception_handler
+);
+
+/// The frame on the stack when a stub calls `hl_exception_handler`
+#[repr(C)]
+struct ExceptionFrame {
+    vector: u64,
+    error_code: u64,
+    rip: u64,
+    cs: u64,
+    rflags: u64,
+    rsp: u64,
+    ss: u64,
+}
+
+#[repr(C, packed)]
+#[derive(Clone, Copy)]
+struct IdtEntry {
+    offset_low: u16,
+    selector: u16,
+    ist: u8,
+    type_attr: u8,
+    offset_mid: u16,
+    offset_high: u32,
+    reserved: u32,
+}
+
+impl IdtEntry {
+    const fn missing() -> Self {
+        Self {
+            offset_low: 0,
+            selector: 0,
+            ist: 0,
+            type_attr: 0,
+            offset_mid: 0,
+            offset_high: 0,
+            reserved: 0,
+        }
+    }
+
+    fn new(handler: u64) -> Self {
+        Self {
+            offset_low: handler as u16,
+            selector: CODE_SELECTOR,
+            ist: EXCEPTION_IST,
+            type_attr: INTERRUPT_GATE,
+            offset_mid: (handler >> 16) as u16,
+            offset_high: (handler >> 32) as u32,
+            reserved: 0,
+        }
+    }
+}
+
+#[repr(C, packed)]
+struct DescriptorTablePointer {
+    limit: u16,
+    base: u64,
+}
+
+/// The 64-bit task state segment, which holds the interrupt stack table
+#[repr(C, packed)]
+struct TaskStateSegment {
+    reserved0: u32,
+    rsp: [u64; 3],
+    reserved1: u64,
+    ist: [u64; 7],
+    reserved2: u64,
+    reserved3: u16,
+    iomap_base: u16,
+}
+
+#[repr(C, align(16))]
+struct ExceptionStack([u8; EXCEPTION_STACK_SIZE]);
+
+/// The null descriptor, a ring 0 64-bit code segment for the interrupt
+/// gates to switch to, and the two halves of the TSS descriptor, which
+/// `init_idt` fills in. The CPU marks the TSS descriptor busy when it is
+/// loaded, so this can't be read-only.
+static mut GDT: [u64; 4] = [0, 0x00af_9a00_0000_ffff, 0, 0];
+
+static mut IDT: [IdtEntry; EXCEPTION_COUNT] = [IdtEntry::missing(); EXCEPTION_COUNT];
+
+static mut TSS: TaskStateSegment = TaskStateSegment {
+    reserved0: 0,
+    rsp: [0; 3],
+    reserved1: 0,
+    ist: [0; 7],
+    reserved2: 0,
+    reserved3: 0,
+    // no I/O permission bitmap
+    iomap_base: size_of::<TaskStateSegment>() as u16,
+};
+
+/// The handlers run on their own stack, so that an exception caused by
+/// the guest's stack, such as a fault on its guard page, can still be
+/// reported
+static mut EXCEPTION_STACK: ExceptionStack = ExceptionStack([0; EXCEPTION_STACK_SIZE]);
+
+/// Install handlers for the CPU exceptions, which report the exception to
+/// the host through the guest panic context and abort, rather than
+/// leaving the vCPU to triple fault.
+///
+/// This must only be called when running in a hypervisor.
+pub(crate) fn init_idt() {
+    unsafe {
+        let stack = core::ptr::addr_of!(EXCEPTION_STACK) as u64;
+        TSS.ist[EXCEPTION_IST as usize - 1] = stack + EXCEPTION_STACK_SIZE as u64;
+
+        // the descriptor is written afresh, as it is left marked busy in
+        // the memory of a guest that is initialized again
+        let tss = core::ptr::addr_of!(TSS) as u64;
+        let limit = size_of::<TaskStateSegment>() as u64 - 1;
+        GDT[2] = (limit & 0xffff)
+            | (tss & 0xff_ffff) << 16
+            | AVAILABLE_TSS << 40
+            | (limit >> 16 & 0xf) << 48
+            | (tss >> 24 & 0xff) << 56;
+        GDT[3] = tss >> 32;
+
+        let stubs = hl_exception_stubs as *const () as u64;
+        #[allow(static_mut_refs)]
+        for (vector, entry) in IDT.iter_mut().enumerate() {
+            *entry = IdtEntry::new(stubs + vector as u64 * STUB_SIZE);
+        }
+
+        let gdt = DescriptorTablePointer {
+            limit: (size_of::<[u64; 4]>() - 1) as u16,
+            base: core::ptr::addr_of!(GDT) as u64,
+        };
+        let idt = DescriptorTablePointer {
+            limit: (size_of::<[IdtEntry; EXCEPTION_COUNT]>() - 1) as u16,
+            base: core::ptr::addr_of!(IDT) as u64,
+        };
+        asm!(
+            "lgdt [{gdt}]",
+            "lidt [{idt}]",
+            "ltr {tss:x}",
+            gdt = in(reg) &gdt,
+            idt = in(reg) &idt,
+            tss = in(reg) TSS_SELECTOR,
+            options(nostack, preserves_flags)
+        );
+    }
+}
+
+fn exception_name(vector: u64) -> &'static str {
+    match vector {
+        0 => "Divide error",
+        1 => "Debug exception",
+        2 => "Non-maskable interrupt",
+        3 => "Breakpoint",
+        4 => "Overflow",
+        5 => "BOUND range exceeded",
+        6 => "Invalid opcode",
+        7 => "Device not available",
+        8 => "Double fault",
+        10 => "Invalid TSS",
+        11 => "Segment not present",
+        12 => "Stack-segment fault",
+        13 => "General protection fault",
+        14 => "Page fault",
+        16 => "x87 floating-point error",
+        17 => "Alignment check",
+        18 => "Machine check",
+        19 => "SIMD floating-point exception",
+        20 => "Virtualization exception",
+        21 => "Control protection exception",
+        _ => "Reserved exception",
+    }
+}
+
+/// A message of at most `N` bytes, cut short if more is written to it, for
+/// the exception and panic handlers, which can't rely on the heap
+pub(crate) struct Message<const N: usize> {
+    buffer: [u8; N],
+    len: usize,
+}
+
+impl<const N: usize> Message<N> {
+    pub(crate) const fn new() -> Self {
+        Self {
+            buffer: [0; N],
+            len: 0,
+        }
+    }
+
+    pub(crate) fn as_str(&self) -> &str {
+        // only whole characters are written
+        core::str::from_utf8(&self.buffer[..self.len]).unwrap_or_default()
+    }
+}
+
+impl<const N: usize> Write for Message<N> {
+    fn write_str(&mut self, s: &str) -> core::fmt::Result {
+        let mut len = s.len().min(N - self.len);
+        while !s.is_char_boundary(len) {
+            len -= 1;
+        }
+        self.buffer[self.len..self.len + len].copy_from_slice(&s.as_bytes()[..len]);
+        self.len += len;
+        Ok(())
+    }
+}
+
+extern "win64" fn hl_exception_handler(frame: *const ExceptionFrame) {
+    let frame = unsafe { &*frame };
+    let mut message = Message::<MAX_MESSAGE_LEN>::new();
+    let _ = write!(
+        message,
+        "{} (vector {}) at RIP {:#x}",
+        exception_name(frame.vector),
+        frame.vector,
+        frame.rip
+    );
+    if frame.vector == PAGE_FAULT {
+        let address: u64;
+        unsafe {
+            asm!("mov {}, cr2", out(reg) address, options(nomem, nostack, preserves_flags));
+        }
+        let _ = write!(message, " addr {:#x}", address);
+    }
+    let _ = write!(message, " error code {:#x}", frame.error_code);
+
+    // the host reads the panic context up to the first NUL
+    unsafe {
+        let peb_ptr = P_PEB.unwrap();
+        let size = (*peb_ptr).guestPanicContextData.guestPanicContextDataSize as usize;
+        let message = message.as_str();
+        let len = message.len().min(size.saturating_sub(1));
+        let buffer = (*peb_ptr).guestPanicContextData.guestPanicContextDataBuffer as *mut u8;
+        core::ptr::copy_nonoverlapping(message.as_ptr(), buffer, len);
+        buffer.add(len).write(0);
+    }
+    outb(OutBAction::Abort as u16, ErrorCode::GuestException as u8);
+}
@@ -0,0 +1,174 @@
+/*
+Copyright 2024 The Hyperlight Authors.
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+    http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+//! The file system the host gives the guest with the `hyperlight::fs::*`
+//! built-in services. Paths are relative to the root of that file system,
+//! which the guest can't leave.
+
+use alloc::string::{String, ToString};
+use alloc::vec;
+use alloc::vec::Vec;
+
+use hyperlight_common::builtin_services::{
+    FS_CLOSE, FS_LIST, FS_OPEN, FS_OPEN_APPEND, FS_OPEN_READ, FS_OPEN_WRITE, FS_READ, FS_SEEK,
+    FS_WRITE,
+};
+use hyperlight_common::flatbuffer_wrappers::function_types::{
+    ParameterValue, ReturnType, ReturnValue,
+};
+use hyperlight_common::flatbuffer_wrappers::guest_error::ErrorCode;
+
+use crate::error::{HyperlightGuestError, Result};
+use crate::host_function_call::{
+    call_host_function, get_host_value_return_as_int, get_host_value_return_as_vecbytes,
+    get_host_value_return_as_void,
+};
+use crate::shared_input_data::try_pop_shared_input_data_into;
+
+/// The number of bytes `File::read_to_end` asks the host for at a time
+const READ_CHUNK_SIZE: i32 = 4096;
+
+/// A file open in the host's file system, which is closed when dropped
+#[derive(Debug)]
+pub struct File {
+    handle: i32,
+}
+
+impl File {
+    fn open_with_mode(path: &str, mode: i32) -> Result<Self> {
+        call_host_function(
+            FS_OPEN,
+            Some(vec![
+                ParameterValue::String(path.to_string()),
+                ParameterValue::Int(mode),
+            ]),
+            ReturnType::Int,
+        )?;
+        Ok(Self {
+            handle: get_host_value_return_as_int()?,
+        })
+    }
+
+    /// Open the file at `path` for reading
+    pub fn open(path: &str) -> Result<Self> {
+        Self::open_with_mode(path, FS_OPEN_READ)
+    }
+
+    /// Open the file at `path` for writing, creating it if it doesn't exist
+    /// and emptying it if it does
+    pub fn create(path: &str) -> Result<Self> {
+        Self::open_with_mode(path, FS_OPEN_WRITE)
+    }
+
+    /// Open the file at `path` for writing at its end, creating it if it
+    /// doesn't exist
+    pub fn append(path: &str) -> Result<Self> {
+        Self::open_with_mode(path, FS_OPEN_APPEND)
+    }
+
+    /// Read at most `len` bytes, returning no bytes at the end of the file
+    pub fn read(&mut self, len: usize) -> Result<Vec<u8>> {
+        call_host_function(
+            FS_READ,
+            Some(vec![
+                ParameterValue::Int(self.handle),
+                ParameterValue::Int(len.min(i32::MAX as usize) as i32),
+            ]),
+            ReturnType::VecBytes,
+        )?;
+        get_host_value_return_as_vecbytes()
+    }
+
+    /// Read the rest of the file
+    pub fn read_to_end(&mut self) -> Result<Vec<u8>> {
+        let mut contents = Vec::new();
+        loop {
+            let chunk = self.read(READ_CHUNK_SIZE as usize)?;
+            if chunk.is_empty() {
+                return Ok(contents);
+            }
+            contents.extend_from_slice(&chunk);
+        }
+    }
+
+    /// Write `bytes`, returning the number of bytes written
+    pub fn write(&mut self, bytes: &[u8]) -> Result<usize> {
+        call_host_function(
+            FS_WRITE,
+            Some(vec![
+                ParameterValue::Int(self.handle),
+                ParameterValue::VecBytes(bytes.to_vec()),
+            ]),
+            ReturnType::Int,
+        )?;
+        Ok(get_host_value_return_as_int()? as usize)
+    }
+
+    /// Move to `offset` bytes from the start of the file
+    pub fn seek(&mut self, offset: u64) -> Result<()> {
+        call_host_function(
+            FS_SEEK,
+            Some(vec![
+                ParameterValue::Int(self.handle),
+                ParameterValue::ULong(offset),
+            ]),
+            ReturnType::Void,
+        )?;
+        get_host_value_return_as_void()
+    }
+}
+
+impl Drop for File {
+    fn drop(&mut self) {
+        let closed = call_host_function(
+            FS_CLOSE,
+            Some(vec![ParameterValue::Int(self.handle)]),
+            ReturnType::Void,
+        );
+        if closed.is_ok() {
+            let _ = get_host_value_return_as_void();
+        }
+    }
+}
+
+/// Read the whole file at `path`
+pub fn read(path: &str) -> Result<Vec<u8>> {
+    File::open(path)?.read_to_end()
+}
+
+/// Write `contents` to the file at `path`, replacing what it held
+pub fn write(path: &str, contents: &[u8]) -> Result<()> {
+    File::create(path)?.write(contents)?;
+    Ok(())
+}
+
+/// The names of the entries in the directory at `path`, with a `/` after
+/// the names of directories
+pub fn list(path: &str) -> Result<Vec<String>> {
+    call_host_function(
+        FS_LIST,
+        Some(vec![ParameterValue::String(path.to_string())]),
+        ReturnType::String,
+    )?;
+    match try_pop_shared_input_data_into::<ReturnValue>()? {
+        ReturnValue::String(names) if names.is_empty() => Ok(Vec::new()),
+        ReturnValue::String(names) => Ok(names.split('\n').map(String::from).collect()),
+        _ => Err(HyperlightGuestError::new(
+            ErrorCode::GuestError,
+            "Host did not return a string".to_string(),
+        )),
+    }
+}
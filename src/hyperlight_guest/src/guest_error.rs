@@ -18,25 +18,43 @@ use alloc::string::{String, ToString};
 use alloc::vec::Vec;
 use core::ffi::{c_char, CStr};
 
-use hyperlight_common::flatbuffer_wrappers::guest_error::{ErrorCode, GuestError};
+use hyperlight_common::flatbuffer_wrappers::guest_error::{
+    ErrorCode, GuestError, USER_DEFINED_ERROR_CODE_BASE,
+};
 use log::error;
 
 use crate::entrypoint::halt;
+#[cfg(target_arch = "x86_64")]
 use crate::host_function_call::{outb, OutBAction};
 use crate::P_PEB;
 
 pub(crate) fn write_error(error_code: ErrorCode, message: Option<&str>) {
-    let guest_error = GuestError::new(
-        error_code.clone(),
+    write_guest_error(GuestError::new(
+        error_code,
         message.map_or("".to_string(), |m| m.to_string()),
-    );
+    ));
+}
+
+pub(crate) fn write_guest_error(mut guest_error: GuestError) {
     let mut guest_error_buffer: Vec<u8> = (&guest_error)
         .try_into()
         .expect("Invalid guest_error_buffer, could not be converted to a Vec<u8>");
 
     unsafe {
         assert!(!(*P_PEB.unwrap()).guestErrorData.guestErrorBuffer.is_null());
-        let len = guest_error_buffer.len();
+        if guest_error_buffer.len() > (*P_PEB.unwrap()).guestErrorData.guestErrorSize as usize
+            && guest_error.payload.is_some()
+        {
+            error!(
+                "Guest error buffer is too small to hold the error payload: size {} buffer size {} payload will be dropped",
+                guest_error_buffer.len(),
+                (*P_PEB.unwrap()).guestErrorData.guestErrorSize as usize
+            );
+            guest_error.payload = None;
+            guest_error_buffer = (&guest_error)
+                .try_into()
+                .expect("Invalid guest_error_buffer, could not be converted to a Vec<u8>");
+        }
         if guest_error_buffer.len() > (*P_PEB.unwrap()).guestErrorData.guestErrorSize as usize {
             error!(
                 "Guest error buffer is too small to hold the error message: size {} buffer size {} message may be truncated",
@@ -44,17 +62,16 @@ pub(crate) fn write_error(error_code: ErrorCode, message: Option<&str>) {
                 (*P_PEB.unwrap()).guestErrorData.guestErrorSize as usize
             );
             // get the length of the message
-            let message_len = message.map_or("".to_string(), |m| m.to_string()).len();
+            let message_len = guest_error.message.len();
             // message is too long, truncate it
             let truncate_len = message_len
                 - (guest_error_buffer.len()
                     - (*P_PEB.unwrap()).guestErrorData.guestErrorSize as usize);
-            let truncated_message = message
-                .map_or("".to_string(), |m| m.to_string())
+            guest_error.message = guest_error
+                .message
                 .chars()
                 .take(truncate_len)
                 .collect::<String>();
-            let guest_error = GuestError::new(error_code, truncated_message);
             guest_error_buffer = (&guest_error)
                 .try_into()
                 .expect("Invalid guest_error_buffer, could not be converted to a Vec<u8>");
@@ -68,6 +85,7 @@ pub(crate) fn write_error(error_code: ErrorCode, message: Option<&str>) {
         // Instead, we do the prior asserts/checks to check the destination pointer isn't null
         // and that there is enough space in the destination buffer for the copy.
         let dest_ptr = (*P_PEB.unwrap()).guestErrorData.guestErrorBuffer as *mut u8;
+        let len = guest_error_buffer.len();
         core::ptr::copy_nonoverlapping(guest_error_buffer.as_ptr(), dest_ptr, len);
     }
 }
@@ -87,16 +105,22 @@ pub(crate) fn set_error(error_code: ErrorCode, message: &str) {
     write_error(error_code, Some(message));
 }
 
+pub(crate) fn set_custom_error(code: u32, message: &str) {
+    write_guest_error(GuestError::custom(code, message.to_string()));
+}
+
 pub(crate) fn set_error_and_halt(error_code: ErrorCode, message: &str) {
     set_error(error_code, message);
     halt();
 }
 
+#[cfg(target_arch = "x86_64")]
 #[no_mangle]
 pub(crate) extern "win64" fn set_stack_allocate_error() {
     outb(OutBAction::Abort as u16, ErrorCode::StackOverflow as u8);
 }
 
+#[cfg(target_arch = "x86_64")]
 #[no_mangle]
 pub(crate) extern "win64" fn set_invalid_runmode_error() {
     panic!("Invalid run mode in __chkstk");
@@ -110,14 +134,20 @@ pub(crate) extern "win64" fn set_invalid_runmode_error() {
 #[no_mangle]
 #[allow(non_camel_case_types)]
 pub unsafe extern "C" fn setError(code: u64, message: *const c_char) {
-    let error_code = ErrorCode::from(code);
-    match message.is_null() {
-        true => write_error(error_code, None),
-        false => {
-            let message = unsafe { CStr::from_ptr(message).to_str().ok() }
-                .expect("Invalid error message, could not be converted to a string");
-            write_error(error_code, Some(message));
-        }
+    let message = match message.is_null() {
+        true => None,
+        false => Some(
+            unsafe { CStr::from_ptr(message).to_str().ok() }
+                .expect("Invalid error message, could not be converted to a string"),
+        ),
+    };
+    // codes in the user-defined range are custom errors
+    match code
+        .checked_sub(USER_DEFINED_ERROR_CODE_BASE)
+        .and_then(|c| u32::try_from(c).ok())
+    {
+        Some(custom_code) => set_custom_error(custom_code, message.unwrap_or_default()),
+        None => write_error(ErrorCode::from(code), message),
     }
     halt();
 }
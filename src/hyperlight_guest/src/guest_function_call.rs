@@ -21,15 +21,14 @@ use hyperlight_common::flatbuffer_wrappers::function_call::{FunctionCall, Functi
 use hyperlight_common::flatbuffer_wrappers::function_types::ParameterType;
 use hyperlight_common::flatbuffer_wrappers::guest_error::ErrorCode;
 
+use crate::compression::compress_return_value;
 use crate::entrypoint::halt;
 use crate::error::{HyperlightGuestError, Result};
-use crate::guest_error::{reset_error, set_error};
+use crate::guest_error::{reset_error, write_guest_error};
 use crate::shared_input_data::try_pop_shared_input_data_into;
 use crate::shared_output_data::push_shared_output_data;
 use crate::REGISTERED_GUEST_FUNCTIONS;
 
-type GuestFunc = fn(&FunctionCall) -> Result<Vec<u8>>;
-
 pub(crate) fn call_guest_function(function_call: FunctionCall) -> Result<Vec<u8>> {
     // Validate this is a Guest Function Call
     if function_call.function_call_type() != FunctionCallType::Guest {
@@ -42,10 +41,27 @@ pub(crate) fn call_guest_function(function_call: FunctionCall) -> Result<Vec<u8>
         ));
     }
 
-    // Find the function definition for the function call.
-    if let Some(registered_function_definition) =
-        unsafe { REGISTERED_GUEST_FUNCTIONS.get(&function_call.function_name) }
-    {
+    // Find the function definition for the function call. Calls the host
+    // makes by the id from the table we published during initialisation
+    // carry no name, so an unknown id can't fall back to the name.
+    #[allow(static_mut_refs)]
+    let registered_function_definition = unsafe {
+        match function_call.function_id {
+            Some(function_id) => Some(
+                REGISTERED_GUEST_FUNCTIONS
+                    .get_by_id(function_id)
+                    .ok_or_else(|| {
+                        HyperlightGuestError::new(
+                            ErrorCode::GuestFunctionNotFound,
+                            format!("No guest function has id {}", function_id),
+                        )
+                    })?,
+            ),
+            None => REGISTERED_GUEST_FUNCTIONS.get(&function_call.function_name),
+        }
+    };
+
+    if let Some(registered_function_definition) = registered_function_definition {
         let function_call_parameter_types: Vec<ParameterType> = function_call
             .parameters
             .iter()
@@ -56,12 +72,7 @@ pub(crate) fn call_guest_function(function_call: FunctionCall) -> Result<Vec<u8>
         // Verify that the function call has the correct parameter types and length.
         registered_function_definition.verify_parameters(&function_call_parameter_types)?;
 
-        let p_function = unsafe {
-            let function_pointer = registered_function_definition.function_pointer;
-            core::mem::transmute::<i64, GuestFunc>(function_pointer)
-        };
-
-        p_function(&function_call)
+        (registered_function_definition.function_pointer)(&function_call)
     } else {
         // The given function is not registered. The guest should implement a function called guest_dispatch_function to handle this.
 
@@ -76,30 +87,59 @@ pub(crate) fn call_guest_function(function_call: FunctionCall) -> Result<Vec<u8>
     }
 }
 
+/// Call the guest function, turning a panic into an error if the guest was
+/// built with the `unwind_to_error` feature
+#[cfg(all(feature = "unwind_to_error", target_arch = "x86_64"))]
+fn call_guest_function_at_boundary(function_call: FunctionCall) -> Result<Vec<u8>> {
+    crate::panic_recovery::catch_panic(|| call_guest_function(function_call)).unwrap_or_else(
+        |message| {
+            Err(HyperlightGuestError::new(
+                ErrorCode::GuestError,
+                format!("Guest function panicked: {}", message),
+            ))
+        },
+    )
+}
+
+#[cfg(not(all(feature = "unwind_to_error", target_arch = "x86_64")))]
+fn call_guest_function_at_boundary(function_call: FunctionCall) -> Result<Vec<u8>> {
+    call_guest_function(function_call)
+}
+
 // This function is marked as no_mangle/inline to prevent the compiler from inlining it , if its inlined the epilogue will not be called
 // and we will leak memory as the epilogue will not be called as halt() is not going to return.
 #[no_mangle]
 #[inline(never)]
 fn internal_dispatch_function() -> Result<()> {
+    #[cfg(target_arch = "x86_64")]
+    crate::time::start_call();
     reset_error();
 
     #[cfg(debug_assertions)]
     log::trace!("internal_dispatch_function");
 
-    let function_call = try_pop_shared_input_data_into::<FunctionCall>()
-        .expect("Function call deserialization failed");
-
-    let result_vec = call_guest_function(function_call).inspect_err(|e| {
-        set_error(e.kind.clone(), e.message.as_str());
-    })?;
-
-    push_shared_output_data(result_vec)
+    // anything that goes wrong, including the host sending a function call
+    // that can't be deserialized, is reported back to the host as an error
+    // rather than aborting the guest
+    try_pop_shared_input_data_into::<FunctionCall>()
+        .and_then(call_guest_function_at_boundary)
+        .and_then(|result_vec| push_shared_output_data(&compress_return_value(result_vec)))
+        .inspect_err(|e| {
+            write_guest_error(e.into());
+        })
 }
 
 // This is implemented as a separate function to make sure that epilogue in the internal_dispatch_function is called before the halt()
 // which if it were included in the internal_dispatch_function cause the epilogue to not be called because the halt() would not return
 // when running in the hypervisor.
+#[cfg(target_arch = "x86_64")]
 pub(crate) extern "win64" fn dispatch_function() {
     let _ = internal_dispatch_function();
     halt();
 }
+
+#[cfg(not(target_arch = "x86_64"))]
+pub(crate) extern "C" fn dispatch_function() {
+    let _ = internal_dispatch_function();
+    halt();
+}
@@ -18,14 +18,22 @@ use alloc::format;
 use alloc::string::String;
 use alloc::vec::Vec;
 
+use hyperlight_common::flatbuffer_wrappers::function_call::FunctionCall;
 use hyperlight_common::flatbuffer_wrappers::function_types::{ParameterType, ReturnType};
 use hyperlight_common::flatbuffer_wrappers::guest_error::ErrorCode;
 
 use crate::error::{HyperlightGuestError, Result};
 
+/// The signature of a guest function that can be called from the host
+pub type GuestFunc = fn(&FunctionCall) -> Result<Vec<u8>>;
+
 /// The definition of a function exposed from the guest to the host
+///
+/// `F` is the type of the function pointer that is called to handle
+/// the function, so that guests built on other ABIs (e.g. C guests)
+/// can keep their own typed handlers rather than casting them to integers.
 #[derive(Debug, Clone, PartialEq, Eq)]
-pub struct GuestFunctionDefinition {
+pub struct GuestFunctionDefinition<F: Copy = GuestFunc> {
     /// The function name
     pub function_name: String,
     /// The type of the parameter values for the host function call.
@@ -33,16 +41,16 @@ pub struct GuestFunctionDefinition {
     /// The type of the return value from the host function call
     pub return_type: ReturnType,
     /// The function pointer to the guest function
-    pub function_pointer: i64,
+    pub function_pointer: F,
 }
 
-impl GuestFunctionDefinition {
+impl GuestFunctionDefinition<GuestFunc> {
     /// Create a new `GuestFunctionDefinition`.
     pub fn new(
         function_name: String,
         parameter_types: Vec<ParameterType>,
         return_type: ReturnType,
-        function_pointer: i64,
+        function_pointer: GuestFunc,
     ) -> Self {
         Self {
             function_name,
@@ -51,7 +59,9 @@ impl GuestFunctionDefinition {
             function_pointer,
         }
     }
+}
 
+impl<F: Copy> GuestFunctionDefinition<F> {
     /// Verify that `self` has same signature as the provided `parameter_types`.
     pub fn verify_parameters(&self, parameter_types: &[ParameterType]) -> Result<()> {
         // Verify that the function does not have more than `MAX_PARAMETERS` parameters.
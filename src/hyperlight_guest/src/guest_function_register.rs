@@ -15,41 +15,89 @@ limitations under the License.
 */
 
 use alloc::collections::BTreeMap;
-use alloc::string::String;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
 
-use super::guest_function_definition::GuestFunctionDefinition;
+#[doc(hidden)]
+pub use hyperlight_common::flatbuffer_wrappers::function_types::{ParameterType, ReturnType};
+use hyperlight_common::flatbuffer_wrappers::guest_function_details::GuestFunctionDetails;
+#[doc(hidden)]
+pub use hyperlight_common::guest_function_metadata::{encode_record, record_len};
+
+use super::guest_function_definition::{GuestFunc, GuestFunctionDefinition};
 use crate::REGISTERED_GUEST_FUNCTIONS;
 
 /// Represents the functions that the guest exposes to the host.
+///
+/// Functions are kept in the order they were first registered. A
+/// function's id is its position in that order plus one, which is what the
+/// host is told during initialisation and can then use to dispatch without
+/// a name lookup. Ids are never reused or moved, so functions registered
+/// after the host was told about the others don't change their ids.
 #[derive(Debug, Default, Clone)]
-pub struct GuestFunctionRegister {
-    /// Currently registered guest functions
-    guest_functions: BTreeMap<String, GuestFunctionDefinition>,
+pub struct GuestFunctionRegister<F: Copy = GuestFunc> {
+    /// Currently registered guest functions, in id order
+    guest_functions: Vec<GuestFunctionDefinition<F>>,
+    /// The position of each function in `guest_functions`, by name
+    positions: BTreeMap<String, usize>,
 }
 
-impl GuestFunctionRegister {
+impl<F: Copy> GuestFunctionRegister<F> {
     /// Create a new `GuestFunctionDetails`.
     pub const fn new() -> Self {
         Self {
-            guest_functions: BTreeMap::new(),
+            guest_functions: Vec::new(),
+            positions: BTreeMap::new(),
         }
     }
 
     /// Register a new `GuestFunctionDefinition` into self.
-    /// If a function with the same name already exists, it will be replaced.
+    /// If a function with the same name already exists, it will be replaced
+    /// and keep its id. Otherwise the function gets the next id.
     /// None is returned if the function name was not previously registered,
     /// otherwise the previous `GuestFunctionDefinition` is returned.
     pub fn register(
         &mut self,
-        guest_function: GuestFunctionDefinition,
-    ) -> Option<GuestFunctionDefinition> {
-        self.guest_functions
-            .insert(guest_function.function_name.clone(), guest_function)
+        guest_function: GuestFunctionDefinition<F>,
+    ) -> Option<GuestFunctionDefinition<F>> {
+        match self.positions.get(&guest_function.function_name) {
+            Some(&i) => Some(core::mem::replace(
+                &mut self.guest_functions[i],
+                guest_function,
+            )),
+            None => {
+                self.positions.insert(
+                    guest_function.function_name.clone(),
+                    self.guest_functions.len(),
+                );
+                self.guest_functions.push(guest_function);
+                None
+            }
+        }
     }
 
     /// Gets a `GuestFunctionDefinition` by its `name` field.
-    pub fn get(&self, function_name: &str) -> Option<&GuestFunctionDefinition> {
-        self.guest_functions.get(function_name)
+    pub fn get(&self, function_name: &str) -> Option<&GuestFunctionDefinition<F>> {
+        self.positions
+            .get(function_name)
+            .map(|&i| &self.guest_functions[i])
+    }
+
+    /// Gets a `GuestFunctionDefinition` by the id it was assigned.
+    pub fn get_by_id(&self, function_id: u32) -> Option<&GuestFunctionDefinition<F>> {
+        let index = (function_id as usize).checked_sub(1)?;
+        self.guest_functions.get(index)
+    }
+
+    /// The table of registered function names in id order, as reported to
+    /// the host.
+    pub fn function_details(&self) -> GuestFunctionDetails {
+        GuestFunctionDetails::new(
+            self.guest_functions
+                .iter()
+                .map(|f| f.function_name.clone())
+                .collect::<Vec<String>>(),
+        )
     }
 }
 
@@ -62,3 +110,57 @@ pub fn register_function(function_definition: GuestFunctionDefinition) {
         gfd.register(function_definition);
     }
 }
+
+/// Register `function` as a guest function called `name`, and record its
+/// signature in the guest binary, where the host can read it without
+/// running the guest, see `GuestBinary::inspect` in `hyperlight_host`.
+///
+/// `name` must be a constant, such as a string literal:
+///
+/// ```ignore
+/// register_guest_function!("Echo", [ParameterType::String], ReturnType::String, echo);
+/// ```
+#[macro_export]
+macro_rules! register_guest_function {
+    ($name:expr, [$($parameter_type:expr),* $(,)?], $return_type:expr, $function:expr $(,)?) => {{
+        const NAME: &str = $name;
+        const PARAMETER_TYPES: &[$crate::guest_function_register::ParameterType] =
+            &[$($parameter_type),*];
+        const RETURN_TYPE: $crate::guest_function_register::ReturnType = $return_type;
+        const RECORD_LEN: usize =
+            $crate::guest_function_register::record_len(NAME, PARAMETER_TYPES.len());
+        // the section is hyperlight_common::guest_function_metadata::SECTION_NAME
+        #[link_section = ".hlfuncs"]
+        #[used]
+        static RECORD: [u8; RECORD_LEN] =
+            $crate::guest_function_register::encode_record(NAME, PARAMETER_TYPES, RETURN_TYPE);
+        $crate::guest_function_register::register_recorded_function(
+            NAME,
+            PARAMETER_TYPES,
+            RETURN_TYPE,
+            $function,
+            &RECORD,
+        )
+    }};
+}
+
+/// Register a function whose signature `register_guest_function!` has
+/// recorded in `record`
+#[doc(hidden)]
+pub fn register_recorded_function(
+    name: &str,
+    parameter_types: &[ParameterType],
+    return_type: ReturnType,
+    function: GuestFunc,
+    record: &'static [u8],
+) {
+    // refer to the record, so that the linker keeps it even though the
+    // guest never reads it
+    core::hint::black_box(record);
+    register_function(GuestFunctionDefinition::new(
+        name.to_string(),
+        parameter_types.to_vec(),
+        return_type,
+        function,
+    ));
+}
@@ -18,7 +18,7 @@ use alloc::format;
 
 use log::{LevelFilter, Metadata, Record};
 
-use crate::logging::log_message;
+use crate::logging::{log_message, log_message_with_target};
 
 // this is private on purpose so that `log` can only be called though the `log!` macros.
 struct GuestLogger {}
@@ -40,14 +40,32 @@ impl log::Log for GuestLogger {
 
     fn log(&self, record: &Record) {
         if self.enabled(record.metadata()) {
-            log_message(
-                record.level().into(),
-                format!("{}", record.args()).as_str(),
-                record.module_path().unwrap_or("Unknown"),
-                record.target(),
-                record.file().unwrap_or("Unknown"),
-                record.line().unwrap_or(0),
-            );
+            let message = format!("{}", record.args());
+            let module_path = record.module_path().unwrap_or("Unknown");
+            let file = record.file().unwrap_or("Unknown");
+            let line = record.line().unwrap_or(0);
+            // The target defaults to the module path, so only pass it on
+            // when the caller chose one with `target:`
+            if record.module_path() == Some(record.target()) {
+                log_message(
+                    record.level().into(),
+                    &message,
+                    module_path,
+                    record.target(),
+                    file,
+                    line,
+                );
+            } else {
+                log_message_with_target(
+                    record.target(),
+                    record.level().into(),
+                    &message,
+                    module_path,
+                    record.target(),
+                    file,
+                    line,
+                );
+            }
         }
     }
 
@@ -0,0 +1,241 @@
+/*
+Copyright 2024 The Hyperlight Authors.
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+    http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+//! Tracks what the guest allocates, for guests built with the
+//! `heap_profiling` feature. Every allocation is counted in a size
+//! histogram and against the call stack it was made from, and the guest
+//! sends the result to the host with `send_heap_profile`, where it is
+//! passed to the sandbox's event subscribers.
+//!
+//! Call stacks are found by walking frame pointers, so guests should be
+//! built with `-C force-frame-pointers=yes`. The return addresses can be
+//! turned into function names with `addr2line` and the guest binary.
+
+use alloc::vec;
+use alloc::vec::Vec;
+use core::arch::asm;
+use core::cmp::Reverse;
+
+use hyperlight_common::flatbuffer_wrappers::function_types::{ParameterValue, ReturnType};
+use hyperlight_common::heap_profile::{
+    size_bucket, HeapCallSite, HeapProfile, CALL_STACK_DEPTH, HEAP_PROFILE_HOST_FUNCTION,
+    MAX_CALL_SITES, SIZE_BUCKETS,
+};
+use spin::Mutex;
+
+use crate::error::Result;
+use crate::host_function_call::{call_host_function, get_host_value_return_as_void};
+use crate::{peb, MIN_STACK_ADDRESS};
+
+#[derive(Clone, Copy)]
+struct CallSite {
+    stack: [u64; CALL_STACK_DEPTH],
+    allocations: u64,
+    bytes: u64,
+}
+
+/// The profile as the allocator keeps it, which must not allocate
+#[derive(Clone, Copy)]
+struct Stats {
+    allocations: u64,
+    deallocations: u64,
+    allocated_bytes: u64,
+    live_bytes: u64,
+    peak_live_bytes: u64,
+    size_histogram: [u64; SIZE_BUCKETS],
+    call_sites: [CallSite; MAX_CALL_SITES],
+    call_site_count: usize,
+    untracked_allocations: u64,
+}
+
+impl Stats {
+    const fn new() -> Self {
+        Self {
+            allocations: 0,
+            deallocations: 0,
+            allocated_bytes: 0,
+            live_bytes: 0,
+            peak_live_bytes: 0,
+            size_histogram: [0; SIZE_BUCKETS],
+            call_sites: [CallSite {
+                stack: [0; CALL_STACK_DEPTH],
+                allocations: 0,
+                bytes: 0,
+            }; MAX_CALL_SITES],
+            call_site_count: 0,
+            untracked_allocations: 0,
+        }
+    }
+
+    fn record_alloc(&mut self, size: usize, stack: [u64; CALL_STACK_DEPTH]) {
+        let size = size as u64;
+        self.allocations += 1;
+        self.allocated_bytes += size;
+        self.live_bytes += size;
+        self.peak_live_bytes = self.peak_live_bytes.max(self.live_bytes);
+        self.size_histogram[size_bucket(size as usize)] += 1;
+
+        let sites = &mut self.call_sites[..self.call_site_count];
+        let site = match sites.iter_mut().position(|site| site.stack == stack) {
+            Some(i) => &mut sites[i],
+            None if self.call_site_count < MAX_CALL_SITES => {
+                self.call_site_count += 1;
+                let site = &mut self.call_sites[self.call_site_count - 1];
+                site.stack = stack;
+                site
+            }
+            None => {
+                self.untracked_allocations += 1;
+                return;
+            }
+        };
+        site.allocations += 1;
+        site.bytes += size;
+    }
+
+    fn record_dealloc(&mut self, size: usize) {
+        self.deallocations += 1;
+        self.live_bytes = self.live_bytes.saturating_sub(size as u64);
+    }
+}
+
+impl From<&Stats> for HeapProfile {
+    fn from(stats: &Stats) -> Self {
+        let mut call_sites: Vec<HeapCallSite> = stats.call_sites[..stats.call_site_count]
+            .iter()
+            .map(|site| HeapCallSite {
+                stack: site
+                    .stack
+                    .iter()
+                    .copied()
+                    .take_while(|&address| address != 0)
+                    .collect(),
+                allocations: site.allocations,
+                bytes: site.bytes,
+            })
+            .collect();
+        call_sites.sort_by_key(|site| Reverse(site.bytes));
+        HeapProfile {
+            allocations: stats.allocations,
+            deallocations: stats.deallocations,
+            allocated_bytes: stats.allocated_bytes,
+            live_bytes: stats.live_bytes,
+            peak_live_bytes: stats.peak_live_bytes,
+            size_histogram: stats.size_histogram.to_vec(),
+            call_sites,
+            untracked_allocations: stats.untracked_allocations,
+        }
+    }
+}
+
+/// What the guest has allocated, which the global allocator records
+/// every allocation in
+static STATS: Mutex<Stats> = Mutex::new(Stats::new());
+
+/// Record an allocation of `size` bytes, made from the call stack above
+/// the caller's frame
+#[inline(always)]
+pub(crate) fn record_alloc(size: usize) {
+    let stack = call_stack();
+    STATS.lock().record_alloc(size, stack);
+}
+
+/// Record freeing an allocation of `size` bytes
+pub(crate) fn record_dealloc(size: usize) {
+    STATS.lock().record_dealloc(size);
+}
+
+/// The return addresses of the frames above the caller's, found by
+/// following the saved frame pointers. Unused entries are zero.
+///
+/// At most `CALL_STACK_DEPTH` frame records are read, each of which must
+/// lie within the guest's stack and above the one before it, so a
+/// corrupted frame pointer ends the walk instead of making it loop or read
+/// outside the stack.
+#[inline(always)]
+fn call_stack() -> [u64; CALL_STACK_DEPTH] {
+    let mut stack = [0; CALL_STACK_DEPTH];
+    let mut frame: u64;
+    // SAFETY: this only reads the frame pointer register
+    unsafe {
+        #[cfg(target_arch = "x86_64")]
+        asm!("mov {}, rbp", out(reg) frame, options(nomem, nostack, preserves_flags));
+        #[cfg(target_arch = "aarch64")]
+        asm!("mov {}, x29", out(reg) frame, options(nomem, nostack, preserves_flags));
+    }
+    let Some((stack_bottom, stack_top)) = stack_bounds() else {
+        return stack;
+    };
+    // one frame record is read for each entry of the stack
+    for address in stack.iter_mut() {
+        // a frame record is the caller's frame pointer followed by the
+        // return address
+        if frame % 8 != 0 || frame < stack_bottom || frame > stack_top - 16 {
+            break;
+        }
+        // SAFETY: frame is the address of a frame record on the stack,
+        // which holds the caller's frame pointer and the return address
+        let (saved_frame, return_address) =
+            unsafe { (*(frame as *const u64), *((frame + 8) as *const u64)) };
+        *address = return_address;
+        // the stack grows down, so each caller's frame is above its callee's
+        if return_address == 0 || saved_frame <= frame {
+            break;
+        }
+        frame = saved_frame;
+    }
+    stack
+}
+
+/// The lowest and highest addresses of the guest's stack, or `None` if the
+/// entrypoint hasn't found them yet
+fn stack_bounds() -> Option<(u64, u64)> {
+    let peb = peb()?;
+    // SAFETY: the host sets up the PEB before calling the entrypoint, and
+    // the entrypoint sets MIN_STACK_ADDRESS from it
+    let (bottom, top) = unsafe { (MIN_STACK_ADDRESS, (*peb).gueststackData.userStackAddress) };
+    (bottom != 0 && top >= bottom + 16).then_some((bottom, top))
+}
+
+fn stats() -> Stats {
+    *STATS.lock()
+}
+
+/// Send what the guest has allocated so far to the host, which passes it
+/// to the `on_heap_profile` callback of the sandbox's event subscribers.
+///
+/// Building the profile allocates, and those allocations are counted in
+/// the next profile.
+pub fn send_heap_profile() -> Result<()> {
+    let profile = HeapProfile::from(&stats());
+    call_host_function(
+        HEAP_PROFILE_HOST_FUNCTION,
+        Some(vec![ParameterValue::VecBytes(Vec::from(&profile))]),
+        ReturnType::Void,
+    )?;
+    get_host_value_return_as_void()
+}
+
+/// Forget everything allocated so far, apart from the bytes that are still
+/// allocated, so that the next profile covers only what happens from now
+pub fn reset_heap_profile() {
+    let mut stats = STATS.lock();
+    *stats = Stats {
+        live_bytes: stats.live_bytes,
+        peak_live_bytes: stats.live_bytes,
+        ..Stats::new()
+    };
+}
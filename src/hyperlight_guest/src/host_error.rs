@@ -19,7 +19,7 @@ use core::slice::from_raw_parts;
 
 use hyperlight_common::flatbuffer_wrappers::guest_error::{ErrorCode, GuestError};
 
-use crate::P_PEB;
+use crate::{hl_bail, P_PEB};
 
 pub(crate) fn check_for_host_error() {
     unsafe {
@@ -30,12 +30,19 @@ pub(crate) fn check_for_host_error() {
         let guest_error_buffer = from_raw_parts(guest_error_buffer_ptr, guest_error_buffer_size);
 
         if !guest_error_buffer.is_empty() {
-            let guest_error = GuestError::try_from(guest_error_buffer).expect("Invalid GuestError");
+            // there is no way to return an error from here, so abort with
+            // a description of what went wrong rather than panicking
+            let guest_error = match GuestError::try_from(guest_error_buffer) {
+                Ok(guest_error) => guest_error,
+                Err(e) => hl_bail!(code: ErrorCode::GuestError, "Invalid host error: {}", e),
+            };
             if guest_error.code != ErrorCode::NoError {
                 (*peb_ptr).outputdata.outputDataBuffer = usize::MAX as *mut c_void;
-                panic!(
+                hl_bail!(
+                    code: ErrorCode::UnknownError,
                     "Guest Error: {:?} - {}",
-                    guest_error.code, guest_error.message
+                    guest_error.code,
+                    guest_error.message
                 );
             }
         }
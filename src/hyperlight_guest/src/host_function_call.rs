@@ -14,34 +14,52 @@ See the License for the specific language governing permissions and
 limitations under the License.
 */
 
+use alloc::format;
 use alloc::string::ToString;
 use alloc::vec::Vec;
+#[cfg(target_arch = "x86_64")]
 use core::arch::global_asm;
+use core::ptr::write_volatile;
 
+use flatbuffers::FlatBufferBuilder;
 use hyperlight_common::flatbuffer_wrappers::function_call::{FunctionCall, FunctionCallType};
 use hyperlight_common::flatbuffer_wrappers::function_types::{
     ParameterValue, ReturnType, ReturnValue,
 };
 use hyperlight_common::flatbuffer_wrappers::guest_error::ErrorCode;
 use hyperlight_common::flatbuffer_wrappers::util::get_flatbuffer_result_from_int;
+#[cfg(target_arch = "x86_64")]
+use hyperlight_common::mem::OutBTransport;
 use hyperlight_common::mem::RunMode;
+use hyperlight_common::outb::{is_user_port, MMIO_DOORBELL_BASE, USER_PORT_BASE, USER_PORT_COUNT};
+use hyperlight_common::secret::zeroize;
+use spin::Mutex;
 
+use crate::compression::compression_threshold;
 use crate::error::{HyperlightGuestError, Result};
 use crate::host_error::check_for_host_error;
 use crate::host_functions::validate_host_function_call;
 use crate::shared_input_data::try_pop_shared_input_data_into;
 use crate::shared_output_data::push_shared_output_data;
-use crate::{OUTB_PTR, OUTB_PTR_WITH_CONTEXT, P_PEB, RUNNING_MODE};
+use crate::RUNNING_MODE;
+#[cfg(target_arch = "x86_64")]
+use crate::{OUTB_PTR, OUTB_PTR_WITH_CONTEXT, OUTB_TRANSPORT, P_PEB};
+
+/// Builder reused to serialize host function calls, so that repeated calls
+/// don't each allocate a fresh flatbuffer.
+static HOST_FUNCTION_CALL_BUILDER: Mutex<Option<FlatBufferBuilder<'static>>> = Mutex::new(None);
 
 pub enum OutBAction {
     Log = 99,
     CallFunction = 101,
     Abort = 102,
+    PushChunk = 104,
+    PopChunk = 105,
+    ReleaseMemory = 106,
 }
 
 pub fn get_host_value_return_as_void() -> Result<()> {
-    let return_value = try_pop_shared_input_data_into::<ReturnValue>()
-        .expect("Unable to deserialize a return value from host");
+    let return_value = try_pop_shared_input_data_into::<ReturnValue>()?;
     if let ReturnValue::Void = return_value {
         Ok(())
     } else {
@@ -53,8 +71,7 @@ pub fn get_host_value_return_as_void() -> Result<()> {
 }
 
 pub fn get_host_value_return_as_int() -> Result<i32> {
-    let return_value = try_pop_shared_input_data_into::<ReturnValue>()
-        .expect("Unable to deserialize return value from host");
+    let return_value = try_pop_shared_input_data_into::<ReturnValue>()?;
 
     // check that return value is an int and return
     if let ReturnValue::Int(i) = return_value {
@@ -68,8 +85,7 @@ pub fn get_host_value_return_as_int() -> Result<i32> {
 }
 
 pub fn get_host_value_return_as_uint() -> Result<u32> {
-    let return_value = try_pop_shared_input_data_into::<ReturnValue>()
-        .expect("Unable to deserialize return value from host");
+    let return_value = try_pop_shared_input_data_into::<ReturnValue>()?;
 
     // check that return value is an int and return
     if let ReturnValue::UInt(ui) = return_value {
@@ -83,8 +99,7 @@ pub fn get_host_value_return_as_uint() -> Result<u32> {
 }
 
 pub fn get_host_value_return_as_long() -> Result<i64> {
-    let return_value = try_pop_shared_input_data_into::<ReturnValue>()
-        .expect("Unable to deserialize return value from host");
+    let return_value = try_pop_shared_input_data_into::<ReturnValue>()?;
 
     // check that return value is an int and return
     if let ReturnValue::Long(l) = return_value {
@@ -98,8 +113,7 @@ pub fn get_host_value_return_as_long() -> Result<i64> {
 }
 
 pub fn get_host_value_return_as_ulong() -> Result<u64> {
-    let return_value = try_pop_shared_input_data_into::<ReturnValue>()
-        .expect("Unable to deserialize return value from host");
+    let return_value = try_pop_shared_input_data_into::<ReturnValue>()?;
 
     // check that return value is an int and return
     if let ReturnValue::ULong(ul) = return_value {
@@ -115,8 +129,7 @@ pub fn get_host_value_return_as_ulong() -> Result<u64> {
 // TODO: Make this generic, return a Result<T, ErrorCode>
 
 pub fn get_host_value_return_as_vecbytes() -> Result<Vec<u8>> {
-    let return_value = try_pop_shared_input_data_into::<ReturnValue>()
-        .expect("Unable to deserialize return value from host");
+    let return_value = try_pop_shared_input_data_into::<ReturnValue>()?;
 
     // check that return value is an Vec<u8> and return
     if let ReturnValue::VecBytes(v) = return_value {
@@ -146,11 +159,18 @@ pub fn call_host_function(
 
     validate_host_function_call(&host_function_call)?;
 
-    let host_function_call_buffer: Vec<u8> = host_function_call
-        .try_into()
-        .expect("Unable to serialize host function call");
-
-    push_shared_output_data(host_function_call_buffer)?;
+    {
+        let mut builder = HOST_FUNCTION_CALL_BUILDER.lock();
+        let builder = builder.get_or_insert_with(FlatBufferBuilder::new);
+        let res = push_shared_output_data(
+            host_function_call.encode_with_compression(builder, compression_threshold()),
+        );
+        // the host zeroes the copy in shared memory when it pops it
+        if host_function_call.has_secrets() {
+            zeroize(builder.mut_finished_buffer().0);
+        }
+        res?;
+    }
 
     outb(OutBAction::CallFunction as u16, 0);
 
@@ -160,9 +180,15 @@ pub fn call_host_function(
 pub fn outb(port: u16, value: u8) {
     unsafe {
         match RUNNING_MODE {
-            RunMode::Hypervisor => {
-                hloutb(port, value);
-            }
+            #[cfg(target_arch = "x86_64")]
+            RunMode::Hypervisor => match OUTB_TRANSPORT {
+                OutBTransport::PortIo => hloutb(port, value),
+                OutBTransport::Mmio => mmio_outb(port, value),
+            },
+            // there is no port I/O on other architectures
+            #[cfg(not(target_arch = "x86_64"))]
+            RunMode::Hypervisor => mmio_outb(port, value),
+            #[cfg(target_arch = "x86_64")]
             RunMode::InProcessLinux | RunMode::InProcessWindows => {
                 if let Some(outb_func) = OUTB_PTR_WITH_CONTEXT {
                     if let Some(peb_ptr) = P_PEB {
@@ -183,6 +209,36 @@ pub fn outb(port: u16, value: u8) {
     }
 }
 
+/// Signal the host by writing `value` to `port`, which must be in the
+/// range reserved for user-defined channels (see
+/// `hyperlight_common::outb`). The host handles the signal with the
+/// handler it registered for `port`, without going through the host
+/// function call machinery.
+pub fn signal_host(port: u16, value: u8) -> Result<()> {
+    if !is_user_port(port) {
+        return Err(HyperlightGuestError::new(
+            ErrorCode::GuestError,
+            format!(
+                "Port {} is outside the user-defined range {}..{}",
+                port,
+                USER_PORT_BASE,
+                USER_PORT_BASE + USER_PORT_COUNT
+            ),
+        ));
+    }
+
+    outb(port, value);
+
+    Ok(())
+}
+
+/// Write `value` to the MMIO doorbell for `port`, which the host handles
+/// exactly like `out port, value`.
+pub(crate) fn mmio_outb(port: u16, value: u8) {
+    unsafe { write_volatile((MMIO_DOORBELL_BASE + port as u64) as *mut u8, value) }
+}
+
+#[cfg(target_arch = "x86_64")]
 extern "win64" {
     fn hloutb(port: u16, value: u8);
 }
@@ -205,6 +261,7 @@ pub fn print_output_as_guest_function(function_call: &FunctionCall) -> Result<Ve
 }
 
 // port: RCX(cx), value: RDX(dl)
+#[cfg(target_arch = "x86_64")]
 global_asm!(
     ".global hloutb
         hloutb:
@@ -29,22 +29,49 @@ use crate::P_PEB;
 
 pub(crate) fn validate_host_function_call(function_call: &FunctionCall) -> Result<()> {
     // get host function details
-    let host_function_details = get_host_function_details();
+    let host_function_details = get_host_function_details()?;
 
     // check if there are any host functions
-    if host_function_details.host_functions.is_none() {
+    if host_function_details.host_functions.is_none() && !host_function_details.has_fallback {
         return Err(HyperlightGuestError::new(
             ErrorCode::GuestError,
             "No host functions found".to_string(),
         ));
     }
 
-    // check if function w/ given name exists
-    let host_function = if let Some(host_function) =
-        host_function_details.find_by_function_name(&function_call.function_name)
+    let function_call_parameter_types = function_call
+        .parameters
+        .iter()
+        .flatten()
+        .map(|p| p.into())
+        .collect::<Vec<ParameterType>>();
+
+    // Host functions can be overloaded by parameter types, so look for an
+    // overload that matches the call exactly.
+    if host_function_details
+        .find_by_function_name_and_parameter_types(
+            &function_call.function_name,
+            &function_call_parameter_types,
+        )
+        .is_some()
     {
-        host_function
-    } else {
+        return Ok(());
+    }
+
+    // No overload matches, work out why for the error message.
+    let mut overloads = host_function_details
+        .host_functions
+        .iter()
+        .flatten()
+        .filter(|host_function| host_function.function_name == function_call.function_name)
+        .peekable();
+
+    if overloads.peek().is_none() {
+        // The host handles calls to functions it doesn't know about itself.
+        if host_function_details.has_fallback {
+            return Ok(());
+        }
+
         return Err(HyperlightGuestError::new(
             ErrorCode::GuestError,
             format!(
@@ -52,46 +79,36 @@ pub(crate) fn validate_host_function_call(function_call: &FunctionCall) -> Resul
                 function_call.function_name.clone()
             ),
         ));
-    };
-
-    let function_call_fparameters = if let Some(parameters) = function_call.parameters.clone() {
-        parameters
-    } else {
-        if host_function.parameter_types.is_some() {
-            return Err(HyperlightGuestError::new(
-                ErrorCode::GuestError,
-                format!(
-                    "Incorrect parameter count for function: {}",
-                    function_call.function_name.clone()
-                ),
-            ));
-        }
+    }
 
-        Vec::new() // if no parameters (and no mismatches), return empty vector
-    };
+    let parameter_count_matches = overloads.any(|host_function| {
+        host_function
+            .parameter_types
+            .as_ref()
+            .map_or(0, |p| p.len())
+            == function_call_parameter_types.len()
+    });
 
-    let function_call_parameter_types = function_call_fparameters
-        .iter()
-        .map(|p| p.into())
-        .collect::<Vec<ParameterType>>();
+    if !parameter_count_matches {
+        return Err(HyperlightGuestError::new(
+            ErrorCode::GuestError,
+            format!(
+                "Incorrect parameter count for function: {}",
+                function_call.function_name.clone()
+            ),
+        ));
+    }
 
-    // Verify that the function call has the correct parameter types.
-    host_function
-        .verify_equal_parameter_types(&function_call_parameter_types)
-        .map_err(|_| {
-            HyperlightGuestError::new(
-                ErrorCode::GuestError,
-                format!(
-                    "Incorrect parameter type for function: {}",
-                    function_call.function_name.clone()
-                ),
-            )
-        })?;
-
-    Ok(())
+    Err(HyperlightGuestError::new(
+        ErrorCode::GuestError,
+        format!(
+            "Incorrect parameter type for function: {}",
+            function_call.function_name.clone()
+        ),
+    ))
 }
 
-pub fn get_host_function_details() -> HostFunctionDetails {
+pub fn get_host_function_details() -> Result<HostFunctionDetails> {
     let peb_ptr = unsafe { P_PEB.unwrap() };
 
     let host_function_details_buffer =
@@ -106,7 +123,10 @@ pub fn get_host_function_details() -> HostFunctionDetails {
         )
     };
 
-    host_function_details_slice
-        .try_into()
-        .expect("Failed to convert buffer to HostFunctionDetails")
+    host_function_details_slice.try_into().map_err(|e| {
+        HyperlightGuestError::new(
+            ErrorCode::GuestError,
+            format!("Failed to convert buffer to HostFunctionDetails: {}", e),
+        )
+    })
 }
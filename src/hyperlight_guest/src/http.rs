@@ -0,0 +1,71 @@
+/*
+Copyright 2024 The Hyperlight Authors.
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+    http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+//! A client for the HTTP requests the host lets the guest make with the
+//! `hyperlight::http::request` built-in service. The host decides which
+//! URLs the guest can reach, and never follows redirects for it.
+
+use alloc::string::{String, ToString};
+use alloc::{format, vec};
+
+use hyperlight_common::builtin_services::HTTP_REQUEST;
+use hyperlight_common::flatbuffer_wrappers::function_types::{ParameterValue, ReturnType};
+use hyperlight_common::flatbuffer_wrappers::guest_error::ErrorCode;
+pub use hyperlight_common::http::HttpResponse;
+
+use crate::error::{HyperlightGuestError, Result};
+use crate::host_function_call::{call_host_function, get_host_value_return_as_vecbytes};
+
+/// Make a request with `method`, such as `"GET"`, to `url`, with `headers`
+/// as pairs of names and values
+pub fn request(
+    method: &str,
+    url: &str,
+    headers: &[(&str, &str)],
+    body: &[u8],
+) -> Result<HttpResponse> {
+    let headers: String = headers
+        .iter()
+        .map(|(name, value)| format!("{}: {}\n", name, value))
+        .collect();
+    call_host_function(
+        HTTP_REQUEST,
+        Some(vec![
+            ParameterValue::String(method.to_string()),
+            ParameterValue::String(url.to_string()),
+            ParameterValue::String(headers),
+            ParameterValue::VecBytes(body.to_vec()),
+        ]),
+        ReturnType::VecBytes,
+    )?;
+    let response = get_host_value_return_as_vecbytes()?;
+    HttpResponse::try_from(response.as_slice()).map_err(|e| {
+        HyperlightGuestError::new(
+            ErrorCode::GuestError,
+            format!("Host returned an invalid HTTP response: {}", e),
+        )
+    })
+}
+
+/// Make a `GET` request to `url`
+pub fn get(url: &str) -> Result<HttpResponse> {
+    request("GET", url, &[], &[])
+}
+
+/// Make a `POST` request to `url` with `body`, of type `content_type`
+pub fn post(url: &str, content_type: &str, body: &[u8]) -> Result<HttpResponse> {
+    request("POST", url, &[("Content-Type", content_type)], body)
+}
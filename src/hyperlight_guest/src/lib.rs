@@ -20,10 +20,9 @@ use alloc::string::ToString;
 use core::hint::unreachable_unchecked;
 use core::ptr::copy_nonoverlapping;
 
-use buddy_system_allocator::LockedHeap;
 use guest_function_register::GuestFunctionRegister;
 use hyperlight_common::flatbuffer_wrappers::guest_error::ErrorCode;
-use hyperlight_common::mem::{HyperlightPEB, RunMode};
+use hyperlight_common::mem::{HyperlightPEB, OutBTransport, RunMode};
 
 use crate::host_function_call::{outb, OutBAction};
 extern crate alloc;
@@ -43,14 +42,33 @@ pub mod host_function_call;
 pub mod host_functions;
 
 pub mod alloca;
+pub mod assert;
+pub mod builtin_services;
+pub(crate) mod compression;
+#[cfg(feature = "coverage")]
+pub mod coverage;
+pub mod env;
+pub mod fs;
 pub(crate) mod guest_logger;
+#[cfg(feature = "heap_profiling")]
+pub mod heap_profile;
+pub mod http;
 pub mod memory;
+#[cfg(all(feature = "unwind_to_error", target_arch = "x86_64"))]
+pub(crate) mod panic_recovery;
 pub mod print;
+pub mod progress;
 pub(crate) mod security_check;
+#[cfg(target_arch = "x86_64")]
 pub mod setjmp;
+#[cfg(target_arch = "x86_64")]
+pub mod time;
 
+#[cfg(target_arch = "x86_64")]
 pub mod chkstk;
 pub mod error;
+#[cfg(target_arch = "x86_64")]
+pub(crate) mod exceptions;
 pub mod logging;
 
 // Unresolved symbols
@@ -70,6 +88,9 @@ pub(crate) static _fltused: i32 = 0;
 // to satisfy the clippy when cfg == test
 #[allow(dead_code)]
 fn panic(info: &core::panic::PanicInfo) -> ! {
+    #[cfg(all(feature = "unwind_to_error", target_arch = "x86_64"))]
+    panic_recovery::return_to_checkpoint(info);
+
     unsafe {
         let peb_ptr = P_PEB.unwrap();
         copy_nonoverlapping(
@@ -83,22 +104,32 @@ fn panic(info: &core::panic::PanicInfo) -> ! {
 }
 
 // Globals
-#[global_allocator]
-pub(crate) static HEAP_ALLOCATOR: LockedHeap<32> = LockedHeap::<32>::empty();
+#[cfg(feature = "default_allocator")]
+guest_allocator!(HEAP_ALLOCATOR: memory::BuddyAllocator = memory::BuddyAllocator::new());
 
 ///cbindgen:ignore
 #[no_mangle]
 pub(crate) static mut __security_cookie: u64 = 0;
 
 pub(crate) static mut P_PEB: Option<*mut HyperlightPEB> = None;
+
+/// The PEB the host passed to the entrypoint, or `None` if the entrypoint
+/// hasn't run yet
+pub fn peb() -> Option<*mut HyperlightPEB> {
+    unsafe { P_PEB }
+}
+
 pub static mut MIN_STACK_ADDRESS: u64 = 0;
 
 pub static mut OS_PAGE_SIZE: u32 = 0;
+#[cfg(target_arch = "x86_64")]
 pub(crate) static mut OUTB_PTR: Option<extern "win64" fn(u16, u8)> = None;
+#[cfg(target_arch = "x86_64")]
 pub(crate) static mut OUTB_PTR_WITH_CONTEXT: Option<
     extern "win64" fn(*mut core::ffi::c_void, u16, u8),
 > = None;
 pub static mut RUNNING_MODE: RunMode = RunMode::None;
+pub(crate) static mut OUTB_TRANSPORT: OutBTransport = OutBTransport::PortIo;
 
 pub(crate) static mut REGISTERED_GUEST_FUNCTIONS: GuestFunctionRegister =
     GuestFunctionRegister::new();
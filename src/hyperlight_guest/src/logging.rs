@@ -30,8 +30,9 @@ fn write_log_data(
     caller: &str,
     source_file: &str,
     line: u32,
+    target: Option<&str>,
 ) {
-    let guest_log_data = GuestLogData::new(
+    let mut guest_log_data = GuestLogData::new(
         message.to_string(),
         source.to_string(),
         log_level,
@@ -39,12 +40,15 @@ fn write_log_data(
         source_file.to_string(),
         line,
     );
+    if let Some(target) = target {
+        guest_log_data = guest_log_data.with_target(target.to_string());
+    }
 
     let bytes: Vec<u8> = guest_log_data
         .try_into()
         .expect("Failed to convert GuestLogData to bytes");
 
-    push_shared_output_data(bytes).expect("Unable to push log data to shared output data");
+    push_shared_output_data(&bytes).expect("Unable to push log data to shared output data");
 }
 
 pub fn log_message(
@@ -55,6 +59,30 @@ pub fn log_message(
     source_file: &str,
     line: u32,
 ) {
-    write_log_data(log_level, message, source, caller, source_file, line);
+    write_log_data(log_level, message, source, caller, source_file, line, None);
+    outb(OutBAction::Log as u16, 0);
+}
+
+/// Log a message to `target`, which the host logs it under as
+/// `hyperlight-guest::<target>`, so that hosts can filter the guest's
+/// messages by target.
+pub fn log_message_with_target(
+    target: &str,
+    log_level: LogLevel,
+    message: &str,
+    source: &str,
+    caller: &str,
+    source_file: &str,
+    line: u32,
+) {
+    write_log_data(
+        log_level,
+        message,
+        source,
+        caller,
+        source_file,
+        line,
+        Some(target),
+    );
     outb(OutBAction::Log as u16, 0);
 }
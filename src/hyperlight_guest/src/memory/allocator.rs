@@ -0,0 +1,175 @@
+/*
+Copyright 2024 The Hyperlight Authors.
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+    http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+use core::alloc::{GlobalAlloc, Layout};
+use core::sync::atomic::{AtomicU64, Ordering};
+
+/// An allocator for the guest's heap, which hyperlight wraps in a
+/// `TrackedAllocator` to keep track of how the heap is used.
+///
+/// The entrypoint gives the allocator the memory the host set aside for
+/// the heap before the guest allocates anything. Guests that want an
+/// allocator other than the default `BuddyAllocator` disable the
+/// `default_allocator` feature and declare theirs with
+/// `hyperlight_guest::guest_allocator!`.
+///
+/// # Safety
+/// Implementations must uphold the contract of `GlobalAlloc::alloc` and
+/// `GlobalAlloc::dealloc`, and must only hand out memory from the heap
+/// they were given in `init`.
+pub unsafe trait GuestAllocator: Sync {
+    /// Take the `size` bytes starting at `start` as the heap. This is
+    /// called once, before any other method.
+    ///
+    /// # Safety
+    /// The memory must be valid, unused and never accessed other than
+    /// through the allocator.
+    unsafe fn init(&self, start: usize, size: usize);
+
+    /// Allocate memory as described by `layout`, returning null if there
+    /// is not enough free memory.
+    ///
+    /// # Safety
+    /// See `GlobalAlloc::alloc`.
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8;
+
+    /// Free the memory at `ptr`.
+    ///
+    /// # Safety
+    /// See `GlobalAlloc::dealloc`.
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout);
+
+    /// The bytes of the heap that could still be allocated, if the
+    /// allocator knows. This is reported in `HeapUsage::free_bytes`.
+    fn free_bytes(&self) -> Option<usize> {
+        None
+    }
+}
+
+/// How the guest has used its heap since it started
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct HeapUsage {
+    /// The number of allocations made
+    pub allocations: u64,
+    /// The number of allocations freed
+    pub deallocations: u64,
+    /// The bytes currently allocated
+    pub live_bytes: u64,
+    /// The most bytes that have been allocated at once
+    pub peak_live_bytes: u64,
+    /// The bytes of the heap that could still be allocated, if the
+    /// allocator knows
+    pub free_bytes: Option<usize>,
+}
+
+static ALLOCATIONS: AtomicU64 = AtomicU64::new(0);
+static DEALLOCATIONS: AtomicU64 = AtomicU64::new(0);
+static LIVE_BYTES: AtomicU64 = AtomicU64::new(0);
+static PEAK_LIVE_BYTES: AtomicU64 = AtomicU64::new(0);
+
+/// The guest's global allocator, which counts what a `GuestAllocator`
+/// allocates before passing the allocation on to it. With the
+/// `heap_profiling` feature, allocations are also recorded in the heap
+/// profile.
+pub struct TrackedAllocator<A> {
+    allocator: A,
+}
+
+impl<A: GuestAllocator> TrackedAllocator<A> {
+    /// Track the allocations made with `allocator`
+    pub const fn new(allocator: A) -> Self {
+        Self { allocator }
+    }
+
+    /// Give the wrapped allocator the guest's heap, see
+    /// `GuestAllocator::init`
+    ///
+    /// # Safety
+    /// See `GuestAllocator::init`.
+    pub unsafe fn init(&self, start: usize, size: usize) {
+        unsafe { self.allocator.init(start, size) }
+    }
+
+    /// How the guest has used its heap so far
+    pub fn usage(&self) -> HeapUsage {
+        HeapUsage {
+            allocations: ALLOCATIONS.load(Ordering::Relaxed),
+            deallocations: DEALLOCATIONS.load(Ordering::Relaxed),
+            live_bytes: LIVE_BYTES.load(Ordering::Relaxed),
+            peak_live_bytes: PEAK_LIVE_BYTES.load(Ordering::Relaxed),
+            free_bytes: self.allocator.free_bytes(),
+        }
+    }
+}
+
+unsafe impl<A: GuestAllocator> GlobalAlloc for TrackedAllocator<A> {
+    // the heap profile records the call stack from the frame above this one
+    #[inline(never)]
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let ptr = unsafe { self.allocator.alloc(layout) };
+        if !ptr.is_null() {
+            let size = layout.size() as u64;
+            ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+            let live = LIVE_BYTES.fetch_add(size, Ordering::Relaxed) + size;
+            PEAK_LIVE_BYTES.fetch_max(live, Ordering::Relaxed);
+            #[cfg(feature = "heap_profiling")]
+            crate::heap_profile::record_alloc(layout.size());
+        }
+        ptr
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        unsafe { self.allocator.dealloc(ptr, layout) };
+        DEALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        LIVE_BYTES.fetch_sub(layout.size() as u64, Ordering::Relaxed);
+        #[cfg(feature = "heap_profiling")]
+        crate::heap_profile::record_dealloc(layout.size());
+    }
+}
+
+/// Declare `$name`, a `TrackedAllocator` wrapping the `GuestAllocator`
+/// `$init` of type `$allocator`, as the guest's global allocator, and have
+/// the entrypoint give it the heap.
+///
+/// Guests must declare exactly one allocator this way when the
+/// `default_allocator` feature is disabled, and none when it is enabled.
+///
+/// ```ignore
+/// hyperlight_guest::guest_allocator!(ALLOCATOR: MyAllocator = MyAllocator::new());
+/// ```
+#[macro_export]
+macro_rules! guest_allocator {
+    ($name:ident: $allocator:ty = $init:expr) => {
+        #[global_allocator]
+        static $name: $crate::memory::TrackedAllocator<$allocator> =
+            $crate::memory::TrackedAllocator::new($init);
+
+        /// Called by the entrypoint to give the global allocator the heap
+        ///
+        /// # Safety
+        /// See `GuestAllocator::init`.
+        #[no_mangle]
+        pub unsafe fn __hyperlight_guest_init_heap(start: usize, size: usize) {
+            unsafe { $name.init(start, size) }
+        }
+
+        /// Called by `hyperlight_guest::memory::heap_usage`
+        #[no_mangle]
+        pub fn __hyperlight_guest_heap_usage() -> $crate::memory::HeapUsage {
+            $name.usage()
+        }
+    };
+}
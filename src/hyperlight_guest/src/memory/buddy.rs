@@ -0,0 +1,77 @@
+/*
+Copyright 2024 The Hyperlight Authors.
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+    http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+use core::alloc::{GlobalAlloc, Layout};
+
+use buddy_system_allocator::LockedHeap;
+
+use super::{release_pages, GuestAllocator};
+
+/// Freed blocks at least this large have their pages given back to the
+/// host, see `release_pages`
+const RELEASE_THRESHOLD: usize = 64 * 1024;
+
+/// The allocator guests use unless they declare their own, a buddy
+/// allocator from `buddy_system_allocator`
+pub struct BuddyAllocator {
+    heap: LockedHeap<32>,
+}
+
+impl BuddyAllocator {
+    /// Create an allocator with no heap, for the entrypoint to give it one
+    pub const fn new() -> Self {
+        Self {
+            heap: LockedHeap::<32>::empty(),
+        }
+    }
+}
+
+impl Default for BuddyAllocator {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+unsafe impl GuestAllocator for BuddyAllocator {
+    unsafe fn init(&self, start: usize, size: usize) {
+        unsafe {
+            self.heap
+                .try_lock()
+                .expect("Failed to access the heap")
+                .init(start, size)
+        }
+    }
+
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        unsafe { self.heap.alloc(layout) }
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        unsafe {
+            self.heap.dealloc(ptr, layout);
+            if layout.size() >= RELEASE_THRESHOLD {
+                // the allocator keeps its free list in the first bytes of
+                // free blocks, so the first page of the block stays
+                release_pages(ptr as usize + 1, layout.size() - 1);
+            }
+        }
+    }
+
+    fn free_bytes(&self) -> Option<usize> {
+        let heap = self.heap.lock();
+        Some(heap.stats_total_bytes() - heap.stats_alloc_actual())
+    }
+}
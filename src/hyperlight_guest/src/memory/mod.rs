@@ -20,11 +20,71 @@ use core::mem::{align_of, size_of};
 use core::ptr;
 
 use hyperlight_common::flatbuffer_wrappers::guest_error::ErrorCode;
+use hyperlight_common::mem::PAGE_SIZE_USIZE;
 
 use crate::entrypoint::abort_with_code;
+use crate::host_function_call::{outb, OutBAction};
+use crate::P_PEB;
 
 extern crate alloc;
 
+mod allocator;
+#[cfg(feature = "default_allocator")]
+mod buddy;
+
+pub use allocator::{GuestAllocator, HeapUsage, TrackedAllocator};
+#[cfg(feature = "default_allocator")]
+pub use buddy::BuddyAllocator;
+
+extern "Rust" {
+    // defined by `guest_allocator!`
+    fn __hyperlight_guest_init_heap(start: usize, size: usize);
+    fn __hyperlight_guest_heap_usage() -> HeapUsage;
+}
+
+/// Give the global allocator the heap the host set aside for the guest
+///
+/// # Safety
+/// This must only be called once, by the entrypoint.
+pub(crate) unsafe fn init_heap(start: usize, size: usize) {
+    unsafe { __hyperlight_guest_init_heap(start, size) }
+}
+
+/// How the guest has used its heap since it started
+pub fn heap_usage() -> HeapUsage {
+    unsafe { __hyperlight_guest_heap_usage() }
+}
+
+/// Tell the host that the guest no longer needs the whole pages of heap
+/// memory in `[start, start + len)`, so that it can give them back to the
+/// OS. Parts of pages at either end are kept. The default allocator does
+/// this for large blocks it frees, guests that declare their own allocator
+/// can do the same.
+///
+/// # Safety
+/// Nothing may be stored in the range: the host is free to replace its
+/// contents with zeros, or on Windows with anything, until the guest writes
+/// to it again.
+pub unsafe fn release_pages(start: usize, len: usize) {
+    let Some(peb_ptr) = (unsafe { P_PEB }) else {
+        return;
+    };
+    let first = start.next_multiple_of(PAGE_SIZE_USIZE);
+    let end = start.saturating_add(len) & !(PAGE_SIZE_USIZE - 1);
+    if first >= end {
+        return;
+    }
+    unsafe {
+        let heap_start = (*peb_ptr).guestheapData.guestHeapBuffer as usize;
+        if first < heap_start {
+            return;
+        }
+        (*peb_ptr).releasedMemoryData.releasedMemoryOffset = (first - heap_start) as u64;
+        (*peb_ptr).releasedMemoryData.releasedMemorySize = (end - first) as u64;
+    }
+    outb(OutBAction::ReleaseMemory as u16, 0);
+}
+
 /*
     C-wrappers for Rust's registered global allocator.
 
@@ -0,0 +1,141 @@
+/*
+Copyright 2024 The Hyperlight Authors.
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+    http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+//! Turns the panics of guest functions into errors, for guests built with
+//! the `unwind_to_error` feature.
+//!
+//! Guests are built with `panic = "abort"`, so nothing unwinds the stack
+//! when a guest function panics. Instead, the dispatch function saves a
+//! checkpoint of its registers before calling the guest function, and the
+//! panic handler jumps back to it, as `setjmp` and `longjmp` do in C. The
+//! call then fails with the panic message as a `GuestError`, and the guest
+//! can be called again.
+//!
+//! Jumping back skips the destructors of everything on the stack of the
+//! guest function, so whatever it had allocated is leaked and any locks it
+//! held stay locked. `MultiUseSandbox` restores the guest's memory after
+//! each call, which undoes both.
+//!
+//! The panic message is kept in a fixed size buffer until the jump back,
+//! so that a panic because the heap is exhausted can be recovered from.
+//! Checkpoints nest, up to `MAX_CHECKPOINTS` deep, and a panic jumps back
+//! to the innermost one.
+
+use alloc::string::{String, ToString};
+use core::arch::global_asm;
+use core::ffi::c_void;
+use core::fmt::Write;
+use core::ptr::addr_of_mut;
+
+use crate::exceptions::Message;
+
+/// How deeply calls to `catch_panic` can nest. Calls nested any deeper
+/// don't save a checkpoint, so their panics jump back to the innermost
+/// checkpoint there is.
+const MAX_CHECKPOINTS: usize = 4;
+/// The longest panic message kept, longer ones are cut short
+const MAX_PANIC_MESSAGE_LEN: usize = 512;
+
+// Call `f(ctx)`, returning 0, after saving a checkpoint in the buffer that
+// `win64_longjmp` returns 1 from. Calling `win64_setjmp` from here rather
+// than from Rust keeps the frame it returns to twice out of the hands of
+// the compiler.
+global_asm!(
+    "
+.global hl_call_with_checkpoint
+hl_call_with_checkpoint:
+    sub     rsp, 56
+    mov     [rsp+32], rdx
+    mov     [rsp+40], r8
+    call    win64_setjmp
+    test    eax, eax
+    jnz     2f
+    mov     rcx, [rsp+40]
+    call    qword ptr [rsp+32]
+    xor     eax, eax
+2:
+    add     rsp, 56
+    ret
+"
+);
+
+extern "win64" {
+    fn hl_call_with_checkpoint(
+        checkpoint: *mut Checkpoint,
+        f: extern "win64" fn(*mut c_void),
+        ctx: *mut c_void,
+    ) -> u64;
+    fn win64_longjmp(checkpoint: u64, value: u64) -> !;
+}
+
+/// The registers `win64_setjmp` saves
+#[repr(C, align(16))]
+#[derive(Clone, Copy)]
+struct Checkpoint([u64; 32]);
+
+static mut CHECKPOINTS: [Checkpoint; MAX_CHECKPOINTS] = [Checkpoint([0; 32]); MAX_CHECKPOINTS];
+/// How many of `CHECKPOINTS` are in use
+static mut DEPTH: usize = 0;
+static mut PANIC_MESSAGE: Message<MAX_PANIC_MESSAGE_LEN> = Message::new();
+
+/// Call `f`, returning the message it panicked with if it panics
+pub(crate) fn catch_panic<F: FnOnce() -> R, R>(f: F) -> Result<R, String> {
+    unsafe {
+        let depth = DEPTH;
+        if depth == MAX_CHECKPOINTS {
+            return Ok(f());
+        }
+        let mut state: (Option<F>, Option<R>) = (Some(f), None);
+        DEPTH = depth + 1;
+        let panicked = hl_call_with_checkpoint(
+            addr_of_mut!(CHECKPOINTS[depth]),
+            call::<F, R>,
+            &mut state as *mut (Option<F>, Option<R>) as *mut c_void,
+        );
+        DEPTH = depth;
+        match (panicked, state.1) {
+            (0, Some(result)) => Ok(result),
+            _ => match (*addr_of_mut!(PANIC_MESSAGE)).as_str() {
+                "" => Err("Guest function panicked".to_string()),
+                message => Err(message.to_string()),
+            },
+        }
+    }
+}
+
+extern "win64" fn call<F: FnOnce() -> R, R>(state: *mut c_void) {
+    let state = unsafe { &mut *(state as *mut (Option<F>, Option<R>)) };
+    if let Some(f) = state.0.take() {
+        state.1 = Some(f());
+    }
+}
+
+/// Called by the panic handler: if a guest function is being called with
+/// `catch_panic`, jump back to the innermost checkpoint, otherwise return
+/// so that the guest aborts.
+pub(crate) fn return_to_checkpoint(info: &core::panic::PanicInfo) {
+    unsafe {
+        if DEPTH > 0 {
+            // a panic while formatting the message goes to the checkpoint
+            // outside this one
+            DEPTH -= 1;
+            let message = &mut *addr_of_mut!(PANIC_MESSAGE);
+            *message = Message::new();
+            let _ = write!(message, "{}", info);
+            win64_longjmp(addr_of_mut!(CHECKPOINTS[DEPTH]) as u64, 1);
+        }
+    }
+}
@@ -0,0 +1,43 @@
+/*
+Copyright 2024 The Hyperlight Authors.
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+    http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+//! Reporting the progress of long-running guest function calls to the
+//! host, which passes it to the progress callback the call was given.
+
+use alloc::string::ToString;
+use alloc::vec;
+
+use hyperlight_common::flatbuffer_wrappers::function_types::{ParameterValue, ReturnType};
+use hyperlight_common::progress::REPORT_PROGRESS_HOST_FUNCTION;
+
+use crate::error::Result;
+use crate::host_function_call::{call_host_function, get_host_value_return_as_void};
+
+/// Report that the guest function call that is running is `percent` done,
+/// with a `message` describing what it is doing. Percentages over 100 are
+/// reported as 100. Nothing happens if the host didn't give the call a
+/// progress callback.
+pub fn report_progress(percent: u32, message: &str) -> Result<()> {
+    call_host_function(
+        REPORT_PROGRESS_HOST_FUNCTION,
+        Some(vec![
+            ParameterValue::UInt(percent),
+            ParameterValue::String(message.to_string()),
+        ]),
+        ReturnType::Void,
+    )?;
+    get_host_value_return_as_void()
+}
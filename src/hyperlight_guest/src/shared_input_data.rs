@@ -16,12 +16,17 @@ limitations under the License.
 
 use alloc::format;
 use alloc::string::ToString;
+use alloc::vec::Vec;
 use core::any::type_name;
 use core::slice::from_raw_parts_mut;
 
 use hyperlight_common::flatbuffer_wrappers::guest_error::ErrorCode;
+use hyperlight_common::mem::CHUNK_FLAG;
+use hyperlight_common::secret::zeroize;
 
 use crate::error::{HyperlightGuestError, Result};
+use crate::host_function_call::{outb, OutBAction};
+use crate::shared_output_data::integrity_tag_len;
 use crate::P_PEB;
 
 // Pops the top element from the shared input data buffer and returns it as a T
@@ -29,6 +34,72 @@ pub fn try_pop_shared_input_data_into<T>() -> Result<T>
 where
     T: for<'a> TryFrom<&'a [u8]>,
 {
+    let (idb, stack_ptr_rel, last_element_offset_rel, _) = top_of_shared_input_data()?;
+
+    let prefix = element_prefix(idb, last_element_offset_rel);
+    if prefix & CHUNK_FLAG != 0 {
+        return try_pop_chunked_shared_input_data_into();
+    }
+
+    let buffer = &idb[last_element_offset_rel..];
+
+    // convert the buffer to T
+    let type_t = convert_buffer(buffer);
+
+    // update the stack pointer to point to the element we just popped of since that is now free
+    idb[..8].copy_from_slice(&last_element_offset_rel.to_le_bytes());
+
+    // zero out popped off buffer
+    idb[last_element_offset_rel..stack_ptr_rel].fill(0);
+
+    type_t
+}
+
+/// Pop a value the host split into segments because it was too large for
+/// the buffer, asking the host for each segment after the first.
+fn try_pop_chunked_shared_input_data_into<T>() -> Result<T>
+where
+    T: for<'a> TryFrom<&'a [u8]>,
+{
+    let mut data = Vec::new();
+    loop {
+        let (idb, stack_ptr_rel, last_element_offset_rel, element_end) =
+            top_of_shared_input_data()?;
+        let prefix = element_prefix(idb, last_element_offset_rel);
+        let start = last_element_offset_rel + 4;
+        let end = start + (prefix & !CHUNK_FLAG) as usize;
+        if end > element_end {
+            return Err(HyperlightGuestError::new(
+                ErrorCode::GuestError,
+                format!(
+                    "Invalid segment length: {} in pop_shared_input_data_into",
+                    prefix & !CHUNK_FLAG
+                ),
+            ));
+        }
+        data.extend_from_slice(&idb[start..end]);
+
+        idb[..8].copy_from_slice(&last_element_offset_rel.to_le_bytes());
+        idb[last_element_offset_rel..stack_ptr_rel].fill(0);
+
+        if prefix & CHUNK_FLAG == 0 {
+            let res = convert_buffer(&data);
+            // zeroed whether or not it holds secrets, see `CHUNK_FLAG`
+            zeroize(&mut data);
+            return res;
+        }
+        // the host pushes the next segment before returning
+        outb(OutBAction::PopChunk as u16, 0);
+    }
+}
+
+/// Get the shared input buffer, the offset of the next free address in
+/// it, and the offsets of the start and end of the element on top of it.
+///
+/// If the host turned integrity checks on, the element is followed by a
+/// tag, which isn't part of the element. Only the host has the key to
+/// check the tag with, but the element's length prefix is checked here.
+fn top_of_shared_input_data() -> Result<(&'static mut [u8], usize, usize, usize)> {
     let peb_ptr = unsafe { P_PEB.unwrap() };
     let shared_buffer_size = unsafe { (*peb_ptr).inputdata.inputDataSize as usize };
 
@@ -66,25 +137,51 @@ where
             .try_into()
             .expect("Invalid stack pointer in pop_shared_input_data_into"),
     );
+    let tag_len = integrity_tag_len();
+    if last_element_offset_rel < 8 || last_element_offset_rel + 4 + tag_len > stack_ptr_rel - 8 {
+        return Err(HyperlightGuestError::new(
+            ErrorCode::GuestError,
+            format!(
+                "Invalid element offset: {} in pop_shared_input_data_into",
+                last_element_offset_rel
+            ),
+        ));
+    }
 
-    let buffer = &idb[last_element_offset_rel..];
-
-    // convert the buffer to T
-    let type_t = match T::try_from(buffer) {
-        Ok(t) => Ok(t),
-        Err(_e) => {
+    let element_end = stack_ptr_rel - 8 - tag_len;
+    if tag_len != 0 {
+        let element = &idb[last_element_offset_rel..element_end];
+        let prefix = element_prefix(idb, last_element_offset_rel);
+        if (prefix & !CHUNK_FLAG) as usize + 4 != element.len() {
             return Err(HyperlightGuestError::new(
                 ErrorCode::GuestError,
-                format!("Unable to convert buffer to {}", type_name::<T>()),
+                format!(
+                    "Shared buffer integrity check failed: input buffer element at {} has the wrong length prefix",
+                    last_element_offset_rel
+                ),
             ));
         }
-    };
+    }
 
-    // update the stack pointer to point to the element we just popped of since that is now free
-    idb[..8].copy_from_slice(&last_element_offset_rel.to_le_bytes());
+    Ok((idb, stack_ptr_rel, last_element_offset_rel, element_end))
+}
 
-    // zero out popped off buffer
-    idb[last_element_offset_rel..stack_ptr_rel].fill(0);
+fn element_prefix(idb: &[u8], element_offset_rel: usize) -> u32 {
+    u32::from_le_bytes(
+        idb[element_offset_rel..element_offset_rel + 4]
+            .try_into()
+            .expect("Invalid element offset in pop_shared_input_data_into"),
+    )
+}
 
-    type_t
+fn convert_buffer<T>(buffer: &[u8]) -> Result<T>
+where
+    T: for<'a> TryFrom<&'a [u8]>,
+{
+    T::try_from(buffer).map_err(|_e| {
+        HyperlightGuestError::new(
+            ErrorCode::GuestError,
+            format!("Unable to convert buffer to {}", type_name::<T>()),
+        )
+    })
 }
@@ -16,17 +16,73 @@ limitations under the License.
 
 use alloc::format;
 use alloc::string::ToString;
-use alloc::vec::Vec;
+use core::cmp::{max, min};
 use core::slice::from_raw_parts_mut;
 
 use hyperlight_common::flatbuffer_wrappers::guest_error::ErrorCode;
+use hyperlight_common::integrity::{integrity_tag, INTEGRITY_TAG_LEN};
+use hyperlight_common::mem::{CHUNK_FLAG, PAGE_SIZE_USIZE};
 
 use crate::error::{HyperlightGuestError, Result};
+use crate::host_function_call::{outb, OutBAction};
 use crate::P_PEB;
 
-pub fn push_shared_output_data(data: Vec<u8>) -> Result<()> {
+/// The size of the length prefix and stack pointer that each segment of
+/// a chunked value takes up in the buffer besides its data
+const SEGMENT_OVERHEAD: usize = 4 + 8;
+
+pub fn push_shared_output_data(data: &[u8]) -> Result<()> {
+    let tag_len = integrity_tag_len();
+
+    // the data plus the pointer pointing to the data
+    let size_required = data.len() + 8 + tag_len;
+    let (odb, stack_ptr_rel) = shared_output_buffer(size_required)?;
+    if size_required <= odb.len() - stack_ptr_rel {
+        push_element(odb, stack_ptr_rel, &[], data, tag_len);
+        return Ok(());
+    }
+
+    // The data doesn't fit even once the buffer has grown, so send it in
+    // segments, each of which the host pops off the buffer and appends to
+    // the ones before it. Every segment but the last has `CHUNK_FLAG` set
+    // in its length prefix.
+    let mut remaining = data;
+    loop {
+        let (odb, stack_ptr_rel) = shared_output_buffer(0)?;
+        let size_available = odb.len() - stack_ptr_rel;
+        if size_available <= SEGMENT_OVERHEAD + tag_len {
+            return Err(HyperlightGuestError::new(
+                ErrorCode::GuestError,
+                format!(
+                    "Not enough space in shared output buffer. Required: {}, Available: {}",
+                    size_required, size_available
+                ),
+            ));
+        }
+        let (segment, rest) = remaining.split_at(min(
+            remaining.len(),
+            size_available - SEGMENT_OVERHEAD - tag_len,
+        ));
+        let mut prefix = segment.len() as u32;
+        if !rest.is_empty() {
+            prefix |= CHUNK_FLAG;
+        }
+        push_element(odb, stack_ptr_rel, &prefix.to_le_bytes(), segment, tag_len);
+        if rest.is_empty() {
+            return Ok(());
+        }
+        outb(OutBAction::PushChunk as u16, 0);
+        remaining = rest;
+    }
+}
+
+/// Get the shared output buffer and the offset of the next free address
+/// in it, first growing the buffer if fewer than `size_required` bytes
+/// are free and the host reserved memory for it to grow into.
+fn shared_output_buffer(size_required: usize) -> Result<(&'static mut [u8], usize)> {
     let peb_ptr = unsafe { P_PEB.unwrap() };
-    let shared_buffer_size = unsafe { (*peb_ptr).outputdata.outputDataSize as usize };
+    let mut shared_buffer_size = unsafe { (*peb_ptr).outputdata.outputDataSize as usize };
+    let max_buffer_size = unsafe { (*peb_ptr).outputdata.outputDataMaxSize as usize };
     let odb = unsafe {
         from_raw_parts_mut(
             (*peb_ptr).outputdata.outputDataBuffer as *mut u8,
@@ -58,29 +114,65 @@ pub fn push_shared_output_data(data: Vec<u8>) -> Result<()> {
         ));
     }
 
-    // check if there is enough space in the buffer
-    let size_required = data.len() + 8; // the data plus the pointer pointing to the data
-    let size_available = shared_buffer_size - stack_ptr_rel;
-    if size_required > size_available {
-        return Err(HyperlightGuestError::new(
-            ErrorCode::GuestError,
-            format!(
-                "Not enough space in shared output buffer. Required: {}, Available: {}",
-                size_required, size_available
-            ),
-        ));
+    if size_required <= shared_buffer_size - stack_ptr_rel || max_buffer_size <= shared_buffer_size
+    {
+        return Ok((odb, stack_ptr_rel));
+    }
+
+    // the host reserved memory for the buffer to grow into, so grow it
+    // and tell the host how much of it is in use
+    shared_buffer_size = min(
+        max(
+            shared_buffer_size * 2,
+            (stack_ptr_rel + size_required).next_multiple_of(PAGE_SIZE_USIZE),
+        ),
+        max_buffer_size,
+    );
+    let odb = unsafe {
+        (*peb_ptr).outputdata.outputDataSize = shared_buffer_size as u64;
+        from_raw_parts_mut(
+            (*peb_ptr).outputdata.outputDataBuffer as *mut u8,
+            shared_buffer_size,
+        )
+    };
+    Ok((odb, stack_ptr_rel))
+}
+
+/// The length of the tag appended to each element on the shared buffers,
+/// which is 0 unless the host turned integrity checks on
+pub(crate) fn integrity_tag_len() -> usize {
+    if unsafe { (*P_PEB.unwrap()).sharedBufferIntegrity.enabled } != 0 {
+        INTEGRITY_TAG_LEN
+    } else {
+        0
     }
+}
+
+/// Push `prefix` followed by `data` onto `odb` as one element, followed by
+/// its tag if `tag_len` isn't 0. The caller must have checked that there
+/// is room for it.
+fn push_element(odb: &mut [u8], stack_ptr_rel: usize, prefix: &[u8], data: &[u8], tag_len: usize) {
+    let mut len = prefix.len() + data.len();
 
     // write the actual data
-    odb[stack_ptr_rel..stack_ptr_rel + data.len()].copy_from_slice(&data);
+    odb[stack_ptr_rel..stack_ptr_rel + prefix.len()].copy_from_slice(prefix);
+    odb[stack_ptr_rel + prefix.len()..stack_ptr_rel + len].copy_from_slice(data);
+
+    // followed by its tag, which the host checks before popping it
+    if tag_len != 0 {
+        let tag = integrity_tag(
+            stack_ptr_rel as u64,
+            &odb[stack_ptr_rel..stack_ptr_rel + len],
+        );
+        odb[stack_ptr_rel + len..stack_ptr_rel + len + INTEGRITY_TAG_LEN].copy_from_slice(&tag);
+        len += INTEGRITY_TAG_LEN;
+    }
 
     // write the offset to the newly written data, to the top of the stack
     let bytes = stack_ptr_rel.to_le_bytes();
-    odb[stack_ptr_rel + data.len()..stack_ptr_rel + data.len() + 8].copy_from_slice(&bytes);
+    odb[stack_ptr_rel + len..stack_ptr_rel + len + 8].copy_from_slice(&bytes);
 
     // update stack pointer to point to next free address
-    let new_stack_ptr_rel = stack_ptr_rel + data.len() + 8;
+    let new_stack_ptr_rel = stack_ptr_rel + len + 8;
     odb[0..8].copy_from_slice(&(new_stack_ptr_rel).to_le_bytes());
-
-    Ok(())
 }
@@ -0,0 +1,59 @@
+/*
+Copyright 2024 The Hyperlight Authors.
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+    http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+//! How long the host has left the guest function call that is running,
+//! so that guests can stop early and return what they have rather than be
+//! cancelled when the host's deadline passes.
+
+use core::arch::x86_64::_rdtsc;
+use core::time::Duration;
+
+use crate::P_PEB;
+
+/// The time stamp counter when the host dispatched the call that is
+/// running
+static mut CALL_START_TSC: u64 = 0;
+
+/// Start timing a call the host has just dispatched
+pub(crate) fn start_call() {
+    // SAFETY: reading the TSC has no side effects, and the guest runs on
+    // a single thread
+    unsafe { CALL_START_TSC = _rdtsc() };
+}
+
+/// The time left before the host cancels the guest function call that is
+/// running, or `None` if the call has no deadline.
+///
+/// This is measured with the guest's time stamp counter, so it doesn't
+/// count time the counter is stopped, such as during host function calls
+/// when the host pauses it with `TimeOptions::pause_during_host_calls`. If
+/// the host doesn't know how fast the counter runs, it is the time the
+/// host gave the call when it dispatched it.
+pub fn remaining() -> Option<Duration> {
+    let deadline_data = unsafe { &(*P_PEB.unwrap()).callDeadlineData };
+    if deadline_data.callDeadlineNanos == 0 {
+        return None;
+    }
+    let deadline = Duration::from_nanos(deadline_data.callDeadlineNanos);
+    if deadline_data.guestTscKhz == 0 {
+        return Some(deadline);
+    }
+    let ticks = unsafe { _rdtsc().wrapping_sub(CALL_START_TSC) };
+    let elapsed = ticks as u128 * 1_000_000 / deadline_data.guestTscKhz as u128;
+    Some(deadline.saturating_sub(Duration::from_nanos(
+        u64::try_from(elapsed).unwrap_or(u64::MAX),
+    )))
+}
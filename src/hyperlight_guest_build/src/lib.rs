@@ -0,0 +1,340 @@
+/*
+Copyright 2024 The Hyperlight Authors.
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+    http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+//! Build script support for Hyperlight guests.
+//!
+//! A guest binary has to be linked with the entrypoint Hyperlight calls and,
+//! for PE guests, with the stack and heap sizes and section alignment the
+//! loader expects. ELF guests are linked with the linker script that ships
+//! with `hyperlight_guest`, which lays out the image the way the loader
+//! maps it. Rather than copying the linker arguments into each guest's
+//! `.cargo/config.toml`, a guest adds this crate as a build dependency and
+//! calls it from its build script, which can be as short as:
+//!
+//! ```no_run
+//! // the whole of the guest's build.rs
+//! hyperlight_guest_build::build_guest!();
+//! ```
+//!
+//! `configure` fails the build if the guest isn't being built for a target
+//! Hyperlight can load, or with a panic strategy other than `abort`, and
+//! warns about the compiler flags a build script can't set, which still
+//! belong in `.cargo/config.toml`, see `GuestFormat::rustflags`. Guests
+//! built for the target spec that ships with `hyperlight_guest`, see
+//! `target_spec`, get those flags from the spec instead.
+#![deny(missing_docs)]
+
+use std::env;
+use std::path::PathBuf;
+
+/// The symbol Hyperlight starts guests at, which `hyperlight_guest` defines
+pub const ENTRYPOINT: &str = "entrypoint";
+
+/// The name of the target spec that ships with `hyperlight_guest`, which
+/// is what `TARGET` is set to for guests built with it
+pub const HYPERLIGHT_TARGET: &str = "x86_64-hyperlight-none";
+
+/// The path of the linker script that ships with `hyperlight_guest`, which
+/// cargo passes to the build scripts of the crates that depend on it
+pub fn linker_script() -> Option<PathBuf> {
+    env::var_os("DEP_C_LINKER_SCRIPT").map(PathBuf::from)
+}
+
+/// The path of the target spec that ships with `hyperlight_guest`, which
+/// cargo passes to the build scripts of the crates that depend on it.
+/// Building for it needs a nightly toolchain and `-Zbuild-std=core,alloc`.
+pub fn target_spec() -> Option<PathBuf> {
+    env::var_os("DEP_C_TARGET_SPEC").map(PathBuf::from)
+}
+
+/// The kinds of binary a guest can be built as
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GuestFormat {
+    /// An ELF binary, built for `x86_64-unknown-none` or for the target
+    /// spec that ships with `hyperlight_guest`
+    Elf,
+    /// A PE binary, built for `x86_64-pc-windows-msvc`
+    Pe,
+}
+
+impl GuestFormat {
+    /// The format of guests built for the target triple `target`
+    pub fn for_target(target: &str) -> Result<Self, String> {
+        match target {
+            "x86_64-unknown-none" | HYPERLIGHT_TARGET => Ok(Self::Elf),
+            "x86_64-pc-windows-msvc" => Ok(Self::Pe),
+            other => Err(format!(
+                "Hyperlight guests can't be built for {}, build them with `--target x86_64-unknown-none` \
+                 or `--target x86_64-pc-windows-msvc`, or set `build.target` in .cargo/config.toml",
+                other
+            )),
+        }
+    }
+
+    /// The target triple guests of this format are built for
+    pub fn target(self) -> &'static str {
+        match self {
+            Self::Elf => "x86_64-unknown-none",
+            Self::Pe => "x86_64-pc-windows-msvc",
+        }
+    }
+
+    /// The `-C` codegen options guests of this format are compiled with.
+    /// A build script can't set these, so they go in the `rustflags` of
+    /// the target in `.cargo/config.toml`.
+    pub fn rustflags(self) -> &'static [&'static str] {
+        match self {
+            // x86_64-unknown-none defaults to the kernel code model, which
+            // expects code in the top 2GB of the address space
+            Self::Elf => &["code-model=small", "force-frame-pointers=yes"],
+            Self::Pe => &["force-frame-pointers=yes"],
+        }
+    }
+}
+
+/// The settings a guest binary is linked with
+#[derive(Debug, Clone)]
+pub struct GuestBuild {
+    stack_size: u64,
+    heap_size: u64,
+    linker_script: Option<PathBuf>,
+}
+
+impl Default for GuestBuild {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl GuestBuild {
+    /// The stack size PE guests are linked with unless it is changed
+    pub const DEFAULT_STACK_SIZE: u64 = 0x10000;
+    /// The heap size PE guests are linked with unless it is changed
+    pub const DEFAULT_HEAP_SIZE: u64 = 0x20000;
+
+    /// Link guests with the default settings, and ELF guests with the
+    /// linker script that ships with `hyperlight_guest` if the guest
+    /// depends on it
+    pub fn new() -> Self {
+        Self {
+            stack_size: Self::DEFAULT_STACK_SIZE,
+            heap_size: Self::DEFAULT_HEAP_SIZE,
+            linker_script: linker_script(),
+        }
+    }
+
+    /// Set the stack size written into the headers of PE guests, which
+    /// Hyperlight uses unless the sandbox's configuration overrides it.
+    /// ELF guests get their stack size from the sandbox's configuration.
+    pub fn stack_size(mut self, bytes: u64) -> Self {
+        self.stack_size = bytes;
+        self
+    }
+
+    /// Set the heap size written into the headers of PE guests, which
+    /// Hyperlight uses unless the sandbox's configuration overrides it.
+    /// ELF guests get their heap size from the sandbox's configuration.
+    pub fn heap_size(mut self, bytes: u64) -> Self {
+        self.heap_size = bytes;
+        self
+    }
+
+    /// Link ELF guests with the linker script at `path` instead, or with
+    /// the linker's default layout if `path` is `None`. The script has to
+    /// put the guest's segments in ascending order from address 0, as the
+    /// one that ships with `hyperlight_guest` does.
+    pub fn linker_script(mut self, path: Option<PathBuf>) -> Self {
+        self.linker_script = path;
+        self
+    }
+
+    /// The arguments guests of `format` are linked with
+    pub fn link_args(&self, format: GuestFormat) -> Vec<String> {
+        match format {
+            GuestFormat::Elf => {
+                let mut args = vec![format!("--entry={}", ENTRYPOINT)];
+                if let Some(script) = &self.linker_script {
+                    args.push(format!("-T{}", script.display()));
+                }
+                args
+            }
+            GuestFormat::Pe => vec![
+                "/RELEASE".to_string(),
+                "/DEBUG".to_string(),
+                "/NOLOGO".to_string(),
+                "/NXCOMPAT".to_string(),
+                "/SAFESEH:NO".to_string(),
+                format!("/ENTRY:{}", ENTRYPOINT),
+                "/SUBSYSTEM:NATIVE".to_string(),
+                "/ALIGN:4096".to_string(),
+                "/FILEALIGN:4096".to_string(),
+                "/NODEFAULTLIB".to_string(),
+                format!("/HEAP:{},{}", self.heap_size, self.heap_size),
+                "/DYNAMICBASE".to_string(),
+                format!("/STACK:{},{}", self.stack_size, self.stack_size),
+                "/MACHINE:X64".to_string(),
+            ],
+        }
+    }
+
+    /// Configure the build of the guest whose build script calls this,
+    /// panicking, and so failing the build, if the guest can't be built
+    /// as it is set up
+    pub fn configure(&self) {
+        if let Err(e) = self.try_configure() {
+            panic!("{}", e);
+        }
+    }
+
+    /// Configure the build of the guest whose build script calls this, or
+    /// say why the guest can't be built as it is set up
+    pub fn try_configure(&self) -> Result<(), String> {
+        let target = env::var("TARGET").map_err(|e| format!("TARGET isn't set: {}", e))?;
+        let format = GuestFormat::for_target(&target)?;
+        if env::var("CARGO_CFG_PANIC").as_deref() != Ok("abort") {
+            return Err(
+                "Hyperlight guests can't unwind, set `panic = \"abort\"` in the profiles in Cargo.toml"
+                    .to_string(),
+            );
+        }
+
+        // the target spec that ships with hyperlight_guest sets the flags
+        let rustflags = env::var("CARGO_ENCODED_RUSTFLAGS").unwrap_or_default();
+        let missing = match target.as_str() {
+            HYPERLIGHT_TARGET => vec![],
+            _ => missing_rustflags(format, &rustflags),
+        };
+        for flag in missing {
+            println!(
+                "cargo:warning=Hyperlight guests are built with `-C {}`, add it to the rustflags for {} in .cargo/config.toml",
+                flag,
+                format.target()
+            );
+        }
+
+        println!("cargo:rerun-if-changed=build.rs");
+        if let Some(script) = self
+            .linker_script
+            .as_ref()
+            .filter(|_| format == GuestFormat::Elf)
+        {
+            println!("cargo:rerun-if-changed={}", script.display());
+        }
+        for arg in self.link_args(format) {
+            println!("cargo:rustc-link-arg-bins={}", arg);
+        }
+        Ok(())
+    }
+}
+
+/// Write a guest's build script, which calls `GuestBuild::configure` with
+/// any settings given as `setting = value`, one for each method of
+/// `GuestBuild` that takes a value:
+///
+/// ```no_run
+/// // the whole of the guest's build.rs
+/// hyperlight_guest_build::build_guest!(stack_size = 0x8000, heap_size = 0x40000);
+/// ```
+///
+/// A guest whose build script does more than this calls
+/// `GuestBuild::configure` from its own `main` instead.
+#[macro_export]
+macro_rules! build_guest {
+    ($($setting:ident = $value:expr),* $(,)?) => {
+        fn main() {
+            $crate::GuestBuild::new()$(.$setting($value))*.configure();
+        }
+    };
+}
+
+/// The flags in `GuestFormat::rustflags` that aren't in the rustflags
+/// `encoded` as in `CARGO_ENCODED_RUSTFLAGS`, separated by `0x1f`
+fn missing_rustflags(format: GuestFormat, encoded: &str) -> Vec<&'static str> {
+    let flags: Vec<&str> = encoded.split('\x1f').collect();
+    // each flag is given either as `-C flag` or `-Cflag`
+    let present = |flag: &str| {
+        flags.windows(2).any(|w| w[0] == "-C" && w[1] == flag)
+            || flags.iter().any(|f| f.strip_prefix("-C") == Some(flag))
+    };
+    format
+        .rustflags()
+        .iter()
+        .copied()
+        .filter(|flag| !present(flag))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn formats_follow_targets() {
+        assert_eq!(
+            GuestFormat::for_target("x86_64-unknown-none"),
+            Ok(GuestFormat::Elf)
+        );
+        assert_eq!(
+            GuestFormat::for_target("x86_64-pc-windows-msvc"),
+            Ok(GuestFormat::Pe)
+        );
+        assert_eq!(
+            GuestFormat::for_target(HYPERLIGHT_TARGET),
+            Ok(GuestFormat::Elf)
+        );
+        assert!(GuestFormat::for_target("x86_64-unknown-linux-gnu").is_err());
+        for format in [GuestFormat::Elf, GuestFormat::Pe] {
+            assert_eq!(GuestFormat::for_target(format.target()), Ok(format));
+        }
+    }
+
+    #[test]
+    fn link_args_set_entrypoint_and_sizes() {
+        let build = GuestBuild::new()
+            .stack_size(0x8000)
+            .heap_size(0x40000)
+            .linker_script(None);
+        assert_eq!(
+            build.link_args(GuestFormat::Elf),
+            vec!["--entry=entrypoint"]
+        );
+        assert_eq!(
+            build
+                .clone()
+                .linker_script(Some("/guest/hyperlight_guest.ld".into()))
+                .link_args(GuestFormat::Elf),
+            vec!["--entry=entrypoint", "-T/guest/hyperlight_guest.ld"]
+        );
+        let pe = build.link_args(GuestFormat::Pe);
+        assert!(pe.contains(&"/ENTRY:entrypoint".to_string()));
+        assert!(pe.contains(&"/STACK:32768,32768".to_string()));
+        assert!(pe.contains(&"/HEAP:262144,262144".to_string()));
+    }
+
+    #[test]
+    fn missing_rustflags_are_found() {
+        let encoded = ["-C", "code-model=small", "-Cforce-frame-pointers=yes"].join("\x1f");
+        assert!(missing_rustflags(GuestFormat::Elf, &encoded).is_empty());
+        assert_eq!(
+            missing_rustflags(GuestFormat::Elf, "-C\x1fforce-frame-pointers=yes"),
+            vec!["code-model=small"]
+        );
+        assert_eq!(
+            missing_rustflags(GuestFormat::Pe, ""),
+            vec!["force-frame-pointers=yes"]
+        );
+    }
+}
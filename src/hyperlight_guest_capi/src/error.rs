@@ -1,6 +1,10 @@
-use core::ffi::c_char;
+use core::ffi::{c_char, CStr};
 
-use hyperlight_common::flatbuffer_wrappers::guest_error::ErrorCode;
+use hyperlight_common::flatbuffer_wrappers::guest_error::{
+    ErrorCode, USER_DEFINED_ERROR_CODE_BASE,
+};
+use hyperlight_guest::entrypoint::fail_initialization;
+use hyperlight_guest::error::HyperlightGuestError;
 use hyperlight_guest::guest_error::setError;
 
 #[no_mangle]
@@ -10,6 +14,23 @@ pub extern "C" fn hl_set_error(err: ErrorCode, message: *const c_char) {
     }
 }
 
+#[no_mangle]
+pub extern "C" fn hl_set_custom_error(code: u32, message: *const c_char) {
+    unsafe {
+        setError(USER_DEFINED_ERROR_CODE_BASE + code as u64, message);
+    }
+}
+
+/// Report that the guest failed to initialize, see
+/// `hyperlight_guest::entrypoint::fail_initialization`. Unlike
+/// `hl_set_error` this returns, and `hyperlight_main` should return after
+/// calling it.
+#[no_mangle]
+pub extern "C" fn hl_fail_initialization(err: ErrorCode, message: *const c_char) {
+    let message = unsafe { CStr::from_ptr(message).to_string_lossy().into_owned() };
+    fail_initialization(HyperlightGuestError::new(err, message));
+}
+
 #[no_mangle]
 pub extern "C" fn hl_abort_with_code(err: i32) {
     hyperlight_guest::entrypoint::abort_with_code(err);
@@ -7,4 +7,5 @@ pub mod dispatch;
 pub mod error;
 pub mod flatbuffer;
 pub mod logging;
+pub mod runtime;
 pub mod types;
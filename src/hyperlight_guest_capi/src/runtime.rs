@@ -0,0 +1,78 @@
+use alloc::boxed::Box;
+use core::{ptr, slice};
+
+use hyperlight_common::mem::HyperlightPEB;
+use hyperlight_guest::entrypoint::halt;
+use hyperlight_guest::host_function_call::{outb, signal_host};
+use hyperlight_guest::shared_input_data::try_pop_shared_input_data_into;
+use hyperlight_guest::shared_output_data::push_shared_output_data;
+
+use crate::types::FfiVec;
+
+/// Returns the PEB the host passed to the guest, or NULL if the guest
+/// hasn't been entered yet.
+#[no_mangle]
+pub extern "C" fn hl_get_peb() -> *mut HyperlightPEB {
+    hyperlight_guest::peb().unwrap_or(ptr::null_mut())
+}
+
+/// Pushes `len` bytes starting at `data` onto the shared output buffer for
+/// the host to pop. Returns false if they don't fit.
+///
+/// # Safety
+/// `data` must point to `len` readable bytes.
+#[no_mangle]
+pub unsafe extern "C" fn hl_push_shared_output_data(data: *const u8, len: usize) -> bool {
+    let data = if len == 0 {
+        &[]
+    } else {
+        unsafe { slice::from_raw_parts(data, len) }
+    };
+    push_shared_output_data(data).is_ok()
+}
+
+/// Pops the element the host last pushed onto the shared input buffer.
+/// Returns NULL if the buffer is empty or corrupt.
+///
+/// The returned `hl_Vec` must be freed with `hl_free_vec`.
+#[no_mangle]
+pub extern "C" fn hl_pop_shared_input_data() -> *mut FfiVec {
+    match try_pop_shared_input_data_into::<alloc::vec::Vec<u8>>() {
+        Ok(data) => Box::into_raw(Box::new(unsafe { FfiVec::from_vec(data) })),
+        Err(_) => ptr::null_mut(),
+    }
+}
+
+/// Frees a `hl_Vec` returned by `hl_pop_shared_input_data`.
+///
+/// # Safety
+/// `vec` must have been returned by `hl_pop_shared_input_data` and not
+/// freed already. NULL is ignored.
+#[no_mangle]
+pub unsafe extern "C" fn hl_free_vec(vec: *mut FfiVec) {
+    if !vec.is_null() {
+        let vec = unsafe { Box::from_raw(vec) };
+        drop(unsafe { FfiVec::into_vec(*vec) });
+    }
+}
+
+/// Writes `value` to `port`, exiting to the host, which handles it as it
+/// handles the same outb from a Rust guest. Aborts the guest if the host
+/// reports an error.
+#[no_mangle]
+pub extern "C" fn hl_outb(port: u16, value: u8) {
+    outb(port, value);
+}
+
+/// Signals the host on one of the user-defined ports. Returns false if
+/// `port` is not in the user-defined range.
+#[no_mangle]
+pub extern "C" fn hl_signal_host(port: u16, value: u8) -> bool {
+    signal_host(port, value).is_ok()
+}
+
+/// Exits to the host, ending the current call into the guest.
+#[no_mangle]
+pub extern "C" fn hl_halt() {
+    halt();
+}
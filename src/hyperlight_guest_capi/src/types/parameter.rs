@@ -2,6 +2,7 @@ use alloc::ffi::CString;
 use core::ffi::{c_char, CStr};
 
 use hyperlight_common::flatbuffer_wrappers::function_types::{ParameterType, ParameterValue};
+use hyperlight_common::secret::Secret;
 use hyperlight_guest::error::Result;
 
 use crate::types::FfiVec;
@@ -22,6 +23,7 @@ pub union FfiParameterValue {
     pub Bool: bool,
     pub String: *mut c_char,
     pub VecBytes: FfiVec,
+    pub Secret: FfiVec,
 }
 
 /// An owned FFI version Of `ParameterValue`
@@ -55,6 +57,11 @@ impl FfiParameter {
                     FfiParameterValue { VecBytes: leaked },
                 )
             }
+            ParameterValue::Secret(v) => {
+                // zeroed again when the parameter is dropped
+                let leaked = unsafe { FfiVec::from_vec(v.expose().to_vec()) };
+                (ParameterType::Secret, FfiParameterValue { Secret: leaked })
+            }
         };
         Ok(FfiParameter { tag, value: union })
     }
@@ -79,6 +86,9 @@ impl FfiParameter {
             ParameterType::VecBytes => {
                 ParameterValue::VecBytes(unsafe { self.value.VecBytes.copy_to_vec() })
             }
+            ParameterType::Secret => {
+                ParameterValue::Secret(Secret::new(unsafe { self.value.Secret.copy_to_vec() }))
+            }
         }
     }
 }
@@ -92,6 +102,9 @@ impl Drop for FfiParameter {
             ParameterType::VecBytes => unsafe {
                 drop(self.value.VecBytes.into_vec());
             },
+            ParameterType::Secret => unsafe {
+                drop(Secret::new(self.value.Secret.into_vec()));
+            },
             _ => {}
         }
     }
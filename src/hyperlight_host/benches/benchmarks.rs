@@ -23,7 +23,7 @@ use hyperlight_host::sandbox::{MultiUseSandbox, UninitializedSandbox};
 use hyperlight_host::sandbox_state::sandbox::EvolvableSandbox;
 use hyperlight_host::sandbox_state::transition::Noop;
 use hyperlight_host::GuestBinary;
-use hyperlight_testing::simple_guest_as_string;
+use hyperlight_testing::{compute_guest_as_string, simple_guest_as_string};
 
 fn create_uninit_sandbox() -> UninitializedSandbox {
     let path = simple_guest_as_string().unwrap();
@@ -101,6 +101,67 @@ fn guest_call_benchmark(c: &mut Criterion) {
     group.finish();
 }
 
+fn compute_benchmark(c: &mut Criterion) {
+    let mut group = c.benchmark_group("compute");
+
+    let create_call_ctx = || {
+        let path = compute_guest_as_string().unwrap();
+        let sandbox: MultiUseSandbox =
+            UninitializedSandbox::new(GuestBinary::FilePath(path), None, None, None)
+                .unwrap()
+                .evolve(Noop::default())
+                .unwrap();
+        sandbox.new_call_context()
+    };
+
+    // Benchmarks guest functions that spend most of their time computing
+    // rather than in calls between the host and the guest.
+    group.bench_function("fibonacci", |b| {
+        let mut call_ctx = create_call_ctx();
+
+        b.iter(|| {
+            call_ctx
+                .call(
+                    "Fibonacci",
+                    ReturnType::ULong,
+                    Some(vec![ParameterValue::Int(20)]),
+                )
+                .unwrap()
+        });
+    });
+
+    group.bench_function("matrix_multiply", |b| {
+        let mut call_ctx = create_call_ctx();
+
+        b.iter(|| {
+            call_ctx
+                .call(
+                    "MatrixMultiply",
+                    ReturnType::ULong,
+                    Some(vec![ParameterValue::Int(32)]),
+                )
+                .unwrap()
+        });
+    });
+
+    group.bench_function("sha256_8k", |b| {
+        let mut call_ctx = create_call_ctx();
+        let data = vec![0xa5u8; 8 * 1024];
+
+        b.iter(|| {
+            call_ctx
+                .call(
+                    "Sha256",
+                    ReturnType::VecBytes,
+                    Some(vec![ParameterValue::VecBytes(data.clone())]),
+                )
+                .unwrap()
+        });
+    });
+
+    group.finish();
+}
+
 fn sandbox_benchmark(c: &mut Criterion) {
     let mut group = c.benchmark_group("sandboxes");
 
@@ -143,6 +204,6 @@ fn sandbox_benchmark(c: &mut Criterion) {
 criterion_group! {
     name = benches;
     config = Criterion::default();
-    targets = guest_call_benchmark, sandbox_benchmark
+    targets = guest_call_benchmark, compute_benchmark, sandbox_benchmark
 }
 criterion_main!(benches);
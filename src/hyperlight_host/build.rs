@@ -98,6 +98,10 @@ fn main() -> Result<()> {
         crashdump: { all(feature = "crashdump", debug_assertions) },
         // print_debug feature is aliased with debug_assertions to make it only available in debug-builds.
         print_debug: { all(feature = "print_debug", debug_assertions) },
+        // pointer_audit feature is aliased with debug_assertions to make it only available in debug-builds.
+        pointer_audit: { all(feature = "pointer_audit", debug_assertions) },
+        // deterministic feature is aliased with debug_assertions to make it only available in debug-builds.
+        deterministic: { all(feature = "deterministic", debug_assertions) },
     }
 
     write_built_file()?;
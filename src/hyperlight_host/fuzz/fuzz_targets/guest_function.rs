@@ -0,0 +1,59 @@
+/*
+Copyright 2024 The Hyperlight Authors.
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+    http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+#![no_main]
+
+use std::cell::RefCell;
+
+use hyperlight_common::flatbuffer_wrappers::function_types::ParameterType;
+use hyperlight_fuzz::GuestFunctionFuzzer;
+use hyperlight_host::func::ReturnType;
+use hyperlight_host::mem::symbols::GuestSymbols;
+use hyperlight_host::sandbox::uninitialized::GuestBinary;
+use hyperlight_host::sandbox_state::sandbox::EvolvableSandbox;
+use hyperlight_host::sandbox_state::transition::Noop;
+use hyperlight_host::{MultiUseSandbox, UninitializedSandbox};
+use hyperlight_testing::simple_guest_as_string;
+use libfuzzer_sys::fuzz_target;
+
+thread_local! {
+    // The sandbox is created once and restored after every call
+    static FUZZER: RefCell<GuestFunctionFuzzer> = RefCell::new(create_fuzzer());
+}
+
+fn create_fuzzer() -> GuestFunctionFuzzer {
+    let binary = GuestBinary::FilePath(simple_guest_as_string().expect("Guest Binary Missing"));
+    let symbols = GuestSymbols::from_binary(&binary).unwrap();
+    let u_sbox = UninitializedSandbox::new(binary, None, None, None).unwrap();
+    let symbols = symbols.with_load_address(u_sbox.guest_load_address());
+    let mu_sbox: MultiUseSandbox = u_sbox.evolve(Noop::default()).unwrap();
+
+    GuestFunctionFuzzer::new(
+        mu_sbox,
+        "PrintThreeArgs",
+        vec![
+            ParameterType::String,
+            ParameterType::Int,
+            ParameterType::Long,
+        ],
+        ReturnType::Int,
+    )
+    .with_symbols(symbols)
+}
+
+fuzz_target!(|data: &[u8]| {
+    FUZZER.with(|fuzzer| fuzzer.borrow_mut().run(data));
+});
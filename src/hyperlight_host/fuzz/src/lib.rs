@@ -0,0 +1,269 @@
+/*
+Copyright 2024 The Hyperlight Authors.
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+    http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+//! Helpers for fuzzing the functions a guest exports with `cargo-fuzz`.
+//!
+//! A `GuestFunctionFuzzer` turns each fuzz input into arguments for a guest
+//! function, calls it, and restores the sandbox after every call, so that
+//! each input runs against the same guest state. Guest aborts, including
+//! CPU exceptions, are turned into panics carrying a symbolized crash
+//! report, so that libFuzzer records the input that caused them:
+//!
+//! ```ignore
+//! #![no_main]
+//!
+//! fuzz_target!(init: { ... }, |data: &[u8]| {
+//!     FUZZER.lock().unwrap().run(data);
+//! });
+//! ```
+
+use std::fs;
+use std::path::PathBuf;
+
+use arbitrary::Unstructured;
+use hyperlight_common::flatbuffer_wrappers::function_types::ParameterType;
+use hyperlight_host::func::{ParameterValue, ReturnType, ReturnValue, Secret};
+use hyperlight_host::hypervisor::CrashDump;
+use hyperlight_host::mem::symbols::GuestSymbols;
+use hyperlight_host::{HyperlightError, MultiUseSandbox, Result};
+
+/// Generate a value of the given type from the fuzz input.
+pub fn arbitrary_parameter(
+    u: &mut Unstructured,
+    parameter_type: &ParameterType,
+) -> arbitrary::Result<ParameterValue> {
+    Ok(match parameter_type {
+        ParameterType::Int => ParameterValue::Int(u.arbitrary()?),
+        ParameterType::UInt => ParameterValue::UInt(u.arbitrary()?),
+        ParameterType::Long => ParameterValue::Long(u.arbitrary()?),
+        ParameterType::ULong => ParameterValue::ULong(u.arbitrary()?),
+        ParameterType::Float => ParameterValue::Float(u.arbitrary()?),
+        ParameterType::Double => ParameterValue::Double(u.arbitrary()?),
+        ParameterType::String => ParameterValue::String(u.arbitrary()?),
+        ParameterType::Bool => ParameterValue::Bool(u.arbitrary()?),
+        ParameterType::VecBytes => ParameterValue::VecBytes(u.arbitrary()?),
+        ParameterType::Secret => ParameterValue::Secret(Secret::new(u.arbitrary()?)),
+    })
+}
+
+/// Generate a value for each of the given types from the fuzz input.
+pub fn arbitrary_parameters(
+    u: &mut Unstructured,
+    parameter_types: &[ParameterType],
+) -> arbitrary::Result<Vec<ParameterValue>> {
+    parameter_types
+        .iter()
+        .map(|parameter_type| arbitrary_parameter(u, parameter_type))
+        .collect()
+}
+
+/// The result of calling the guest function with one fuzz input
+#[derive(Debug)]
+pub enum FuzzOutcome {
+    /// The input was too short to generate the arguments from
+    Skipped,
+    /// The guest function returned
+    Returned(ReturnValue),
+    /// The call failed without the guest aborting, for example because the
+    /// guest function rejected its arguments
+    Failed(HyperlightError),
+    /// The guest aborted, with a report of where it was executing
+    Crashed(String),
+}
+
+/// Calls a guest function over and over with arguments generated from
+/// fuzz inputs.
+pub struct GuestFunctionFuzzer {
+    sandbox: MultiUseSandbox,
+    function_name: String,
+    parameter_types: Vec<ParameterType>,
+    return_type: ReturnType,
+    symbols: Option<GuestSymbols>,
+    crash_dir: Option<PathBuf>,
+}
+
+impl GuestFunctionFuzzer {
+    /// Create a fuzzer for the guest function `function_name`, which takes
+    /// arguments of `parameter_types` and returns `return_type`.
+    pub fn new(
+        sandbox: MultiUseSandbox,
+        function_name: impl Into<String>,
+        parameter_types: Vec<ParameterType>,
+        return_type: ReturnType,
+    ) -> Self {
+        Self {
+            sandbox,
+            function_name: function_name.into(),
+            parameter_types,
+            return_type,
+            symbols: None,
+            crash_dir: None,
+        }
+    }
+
+    /// Symbolize the addresses in crash reports with the given symbols,
+    /// which should be relocated to the guest's load address.
+    pub fn with_symbols(mut self, symbols: GuestSymbols) -> Self {
+        self.symbols = Some(symbols);
+        self
+    }
+
+    /// Also write each crash report to a file in `crash_dir`.
+    pub fn with_crash_dir(mut self, crash_dir: impl Into<PathBuf>) -> Self {
+        self.crash_dir = Some(crash_dir.into());
+        self
+    }
+
+    /// Call the guest function with arguments generated from `data`,
+    /// restoring the sandbox afterwards, so that each input runs against
+    /// the same guest state.
+    pub fn call(&mut self, data: &[u8]) -> Result<FuzzOutcome> {
+        let mut u = Unstructured::new(data);
+        let Ok(args) = arbitrary_parameters(&mut u, &self.parameter_types) else {
+            return Ok(FuzzOutcome::Skipped);
+        };
+        let args = (!args.is_empty()).then_some(args);
+
+        let error = match self.sandbox.call_guest_function_by_name(
+            &self.function_name,
+            self.return_type,
+            args,
+        ) {
+            Ok(value) => return Ok(FuzzOutcome::Returned(value)),
+            Err(error) => error,
+        };
+        // Calls that fail aren't restored, so do that here
+        self.sandbox.clear_poison()?;
+
+        match CrashDump::from_error(&error) {
+            Some(crash_dump) => {
+                let report = self.report(&error, &crash_dump, data)?;
+                Ok(FuzzOutcome::Crashed(report))
+            }
+            None => Ok(FuzzOutcome::Failed(error)),
+        }
+    }
+
+    /// Call the guest function with arguments generated from `data`,
+    /// panicking with the crash report if the guest aborts.
+    ///
+    /// This is meant to be called from a `fuzz_target!`.
+    pub fn run(&mut self, data: &[u8]) {
+        match self.call(data) {
+            Ok(FuzzOutcome::Crashed(report)) => panic!("{}", report),
+            Ok(_) => {}
+            Err(e) => panic!("Failed to restore the sandbox: {:?}", e),
+        }
+    }
+
+    fn report(
+        &self,
+        error: &HyperlightError,
+        crash_dump: &CrashDump,
+        data: &[u8],
+    ) -> Result<String> {
+        let mut report = format!("{} aborted: {}\n", self.function_name, error);
+        match &self.symbols {
+            Some(symbols) => report.push_str(&crash_dump.symbolize(symbols)),
+            None => {
+                report.push_str(&format!("RIP {:#x}\n", crash_dump.rip));
+                for (i, address) in crash_dump.backtrace.iter().enumerate() {
+                    report.push_str(&format!("#{} {:#x}\n", i, address));
+                }
+            }
+        }
+
+        if let Some(crash_dir) = &self.crash_dir {
+            fs::create_dir_all(crash_dir)?;
+            let name = format!("{}-{:016x}.txt", self.function_name, fnv1a(data));
+            fs::write(crash_dir.join(name), &report)?;
+        }
+        Ok(report)
+    }
+}
+
+/// Hash the fuzz input to name its crash report, so that the same input
+/// always overwrites the same file
+fn fnv1a(data: &[u8]) -> u64 {
+    data.iter().fold(0xcbf2_9ce4_8422_2325, |hash, byte| {
+        (hash ^ *byte as u64).wrapping_mul(0x0100_0000_01b3)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use arbitrary::Unstructured;
+    use hyperlight_common::flatbuffer_wrappers::function_types::ParameterType;
+    use hyperlight_host::func::{ParameterValue, ReturnType, ReturnValue};
+    use hyperlight_host::sandbox::uninitialized::GuestBinary;
+    use hyperlight_host::sandbox_state::sandbox::EvolvableSandbox;
+    use hyperlight_host::sandbox_state::transition::Noop;
+    use hyperlight_host::{MultiUseSandbox, UninitializedSandbox};
+    use hyperlight_testing::simple_guest_as_string;
+
+    use super::{arbitrary_parameters, FuzzOutcome, GuestFunctionFuzzer};
+
+    #[test]
+    fn generates_parameters_of_each_type() {
+        let data = [0x11u8; 64];
+        let mut u = Unstructured::new(&data);
+        let types = [
+            ParameterType::Int,
+            ParameterType::Long,
+            ParameterType::Bool,
+            ParameterType::VecBytes,
+        ];
+        let values = arbitrary_parameters(&mut u, &types).unwrap();
+
+        assert!(matches!(
+            values.as_slice(),
+            [
+                ParameterValue::Int(_),
+                ParameterValue::Long(_),
+                ParameterValue::Bool(_),
+                ParameterValue::VecBytes(_),
+            ]
+        ));
+    }
+
+    /// A guest function that changes the guest's state and then fails,
+    /// without poisoning the sandbox, mustn't leave that state behind for
+    /// the next input
+    #[test]
+    fn failed_calls_are_restored() {
+        let binary = GuestBinary::FilePath(simple_guest_as_string().unwrap());
+        let sandbox: MultiUseSandbox = UninitializedSandbox::new(binary, None, None, None)
+            .unwrap()
+            .evolve(Noop::default())
+            .unwrap();
+        let mut fuzzer = GuestFunctionFuzzer::new(
+            sandbox,
+            "AddToStaticAndFail",
+            vec![ParameterType::Int],
+            ReturnType::Int,
+        );
+
+        let outcome = fuzzer.call(&[1, 0, 0, 0]).unwrap();
+        assert!(matches!(outcome, FuzzOutcome::Failed(_)));
+        assert!(!fuzzer.sandbox.is_poisoned());
+
+        let res = fuzzer
+            .sandbox
+            .call_guest_function_by_name("GetStatic", ReturnType::Int, None)
+            .unwrap();
+        assert_eq!(res, ReturnValue::Int(0));
+    }
+}
@@ -37,6 +37,7 @@ use thiserror::Error;
 use crate::hypervisor::wrappers::HandleWrapper;
 use crate::mem::memory_region::MemoryRegionFlags;
 use crate::mem::ptr::RawPtr;
+use crate::sandbox::SandboxId;
 
 #[derive(Serialize, Deserialize, Debug, PartialEq, Eq)]
 pub(crate) struct HyperlightHostError {
@@ -105,26 +106,67 @@ pub enum HyperlightError {
     #[error("Field Name {0} not found in decoded GuestLogData")]
     FieldIsMissingInGuestLogData(String),
 
+    /// The guest was built against a different version of the interface
+    /// between the host and the guest than the host implements
+    #[error("Guest ABI version {guest} does not match the host ABI version {host}")]
+    GuestAbiMismatch {
+        /// The version the guest was built against, 0 if the guest predates
+        /// ABI versioning
+        guest: u64,
+        /// The version the host implements
+        host: u64,
+    },
+
     /// Guest aborted during outb
-    #[error("Guest aborted: {0} {1}")]
-    GuestAborted(u8, String),
+    #[error("Guest aborted: {code} {message}")]
+    GuestAborted {
+        /// The code the guest aborted with
+        code: u8,
+        /// The message the guest left in the panic context buffer, if any
+        message: String,
+        /// The guest's instruction pointer when it aborted, followed by the
+        /// return addresses of the calls in progress, if the hypervisor
+        /// could unwind the guest's stack
+        backtrace: Vec<u64>,
+    },
 
     ///Cannot run from guest binary unless the binary is a file
     #[error("Cannot run from guest binary when guest binary is a buffer")]
     GuestBinaryShouldBeAFile(),
 
-    /// Guest call resulted in error in guest
+    /// Guest call resulted in error in guest, with any data the guest
+    /// attached to it
     #[error("Guest error occurred {0:?}: {1}")]
-    GuestError(ErrorCode, String),
+    GuestError(ErrorCode, String, Option<Vec<u8>>),
+
+    /// Guest call resulted in an error defined by the guest author
+    #[error("Guest custom error occurred {code}: {message}")]
+    GuestCustomError {
+        /// The code the guest gave the error
+        code: u32,
+        /// The error message
+        message: String,
+        /// Data the guest attached to the error
+        payload: Option<Vec<u8>>,
+    },
 
     /// An attempt to cancel guest execution failed because it is hanging on a host function call
-    #[error("Guest execution hung on the execution of a host function call")]
-    GuestExecutionHungOnHostFunctionCall(),
+    #[error("Guest execution in sandbox {0} hung on the execution of a host function call")]
+    GuestExecutionHungOnHostFunctionCall(SandboxId),
 
     /// Guest call already in progress
     #[error("Guest call is already in progress")]
     GuestFunctionCallAlreadyInProgress(),
 
+    /// The guest's `hyperlight_main` reported that it failed to initialize
+    #[error("Guest initialization failed {code:?}: {message}")]
+    GuestInitializationFailed {
+        /// The code the guest reported the failure with
+        code: ErrorCode,
+        /// The error message
+        message: String,
+    },
+
     /// The given type is not supported by the guest interface.
     #[error("Unsupported type: {0}")]
     GuestInterfaceUnsupportedType(String),
@@ -133,6 +175,10 @@ pub enum HyperlightError {
     #[error("The guest offset {0} is invalid.")]
     GuestOffsetIsInvalid(usize),
 
+    /// The guest binary is not signed by a trusted key
+    #[error("Guest binary signature verification failed: {0}")]
+    GuestSignatureInvalid(String),
+
     /// A Host function was called by the guest but it was not registered.
     #[error("HostFunction {0} was not found")]
     HostFunctionNotFound(String),
@@ -231,6 +277,11 @@ pub enum HyperlightError {
     #[error("Failure processing PE File {0:?}")]
     PEFileProcessingFailure(#[from] goblin::error::Error),
 
+    /// The guest aborted part way through a previous call, so the sandbox
+    /// has to be restored before it can be called again
+    #[error("The sandbox {0} is poisoned because the guest aborted during a previous call")]
+    PoisonedSandbox(SandboxId),
+
     /// a Prometheus error occurred
     #[error("Prometheus Error {0:?}")]
     Prometheus(#[from] prometheus::Error),
@@ -251,6 +302,17 @@ pub enum HyperlightError {
     #[error("Failed To Convert Return Value {0:?} to {1:?}")]
     ReturnValueConversionFailure(ReturnValue, &'static str),
 
+    /// An element on a shared buffer failed its integrity check, see
+    /// `SandboxConfiguration::set_shared_buffer_integrity`
+    #[error("Shared buffer integrity check failed: {0}")]
+    SharedBufferIntegrity(String),
+
+    /// The guest hit a breakpoint or watchpoint set with
+    /// `MultiUseSandbox::set_debug_registers`, at the given instruction
+    /// pointer and with the given DR6
+    #[error("Guest hit a hardware breakpoint at {0:#x}, DR6 {1:#x}")]
+    GuestDebugTrap(u64, u64),
+
     /// Stack overflow detected in guest
     #[error("Stack overflow detected")]
     StackOverflow(),
@@ -269,6 +331,11 @@ pub enum HyperlightError {
     #[error("SystemTimeError {0:?}")]
     SystemTimeError(#[from] SystemTimeError),
 
+    /// Conversion of str to TOML failed
+    #[error("Conversion of str data to toml failed")]
+    #[cfg(feature = "toml_config")]
+    TomlConversionFailure(#[from] toml::de::Error),
+
     /// Error occurred converting a slice to an array
     #[error("TryFromSliceError {0:?}")]
     TryFromSliceError(#[from] TryFromSliceError),
@@ -0,0 +1,247 @@
+/*
+Copyright 2024 The Hyperlight Authors.
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+    http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+#![allow(non_snake_case)]
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{mpsc, Arc, Mutex};
+use std::thread;
+
+use paste::paste;
+use tokio::runtime::Handle;
+use tracing::{instrument, Span};
+
+use super::host_functions::*;
+use super::{SupportedParameterType, SupportedReturnType};
+use crate::sandbox::{ExtraAllowedSyscall, UninitializedSandbox};
+use crate::{new_error, Result};
+
+type Task = Pin<Box<dyn Future<Output = ()> + Send>>;
+
+/// Spawns tasks on a tokio runtime from a thread of its own.
+///
+/// Waking the runtime writes to an eventfd, which the seccomp filter of the
+/// host function worker thread only allows for stdout and stderr. Handing
+/// the task to this thread over a channel only needs `futex`, so async host
+/// functions don't need `write` allowed on any other fd.
+struct Spawner {
+    tasks: mpsc::Sender<Task>,
+}
+
+impl Spawner {
+    fn new(runtime: Handle) -> Result<Self> {
+        let (tasks, receiver) = mpsc::channel::<Task>();
+        thread::Builder::new()
+            .name("hyperlight-async-host-function-spawner".to_string())
+            // the thread exits once the host function, and with it the
+            // sender, is dropped
+            .spawn(move || {
+                for task in receiver {
+                    runtime.spawn(task);
+                }
+            })?;
+        Ok(Self { tasks })
+    }
+}
+
+/// Spawn `future` with `spawner` and block the calling thread, which is the
+/// one handling the guest's call, until it completes. The guest's vCPU
+/// stays suspended in the meantime.
+fn run_on<R: Send + 'static>(
+    spawner: &Spawner,
+    name: &str,
+    future: impl Future<Output = Result<R>> + Send + 'static,
+) -> Result<R> {
+    let (sender, receiver) = mpsc::sync_channel(1);
+    spawner
+        .tasks
+        .send(Box::pin(async move {
+            // the receiver only goes away if the guest's call does
+            let _ = sender.send(future.await);
+        }))
+        .map_err(|_| new_error!("The spawner of async host function {} exited", name))?;
+    receiver.recv().map_err(|_| {
+        new_error!(
+            "Async host function {} panicked or its runtime was shut down",
+            name
+        )
+    })?
+}
+
+macro_rules! async_host_function {
+    ($N:expr $(, $P:ident)*) => {
+        paste! {
+            /// Trait for registering an async host function with $N parameters.
+            ///
+            /// The future the function returns is spawned on a tokio runtime,
+            /// while the guest waits for it to complete. The runtime must be
+            /// able to make progress while the guest function that called
+            /// the host function is running, so guest functions should not
+            /// be called from the only thread of a current-thread runtime.
+            pub trait [<AsyncHostFunction $N>]<$($P,)* R>
+            where
+                $($P: SupportedParameterType<$P> + Clone + 'static,)*
+                R: SupportedReturnType<R>,
+            {
+                /// Register the host function with the given name in the
+                /// sandbox, running the futures it returns on `runtime`.
+                fn register_async(
+                    &self,
+                    sandbox: &mut UninitializedSandbox,
+                    name: &str,
+                    runtime: &Handle,
+                ) -> Result<()>;
+
+                /// Register the host function with the given name in the
+                /// sandbox, running the futures it returns on `runtime` and
+                /// allowing extra syscalls.
+                #[cfg(all(feature = "seccomp", target_os = "linux"))]
+                fn register_async_with_extra_allowed_syscalls(
+                    &self,
+                    sandbox: &mut UninitializedSandbox,
+                    name: &str,
+                    runtime: &Handle,
+                    extra_allowed_syscalls: Vec<ExtraAllowedSyscall>,
+                ) -> Result<()>;
+            }
+
+            impl<T, Fut, $($P,)* R> [<AsyncHostFunction $N>]<$($P,)* R> for Arc<Mutex<T>>
+            where
+                T: FnMut($($P),*) -> Fut + Send + 'static,
+                Fut: Future<Output = Result<R>> + Send + 'static,
+                $($P: SupportedParameterType<$P> + Clone + 'static,)*
+                R: SupportedReturnType<R> + Send + 'static,
+            {
+                #[cfg(all(feature = "seccomp", target_os = "linux"))]
+                #[instrument(
+                    err(Debug), skip(self, sandbox, runtime), parent = Span::current(), level = "Trace"
+                )]
+                fn register_async(
+                    &self,
+                    sandbox: &mut UninitializedSandbox,
+                    name: &str,
+                    runtime: &Handle,
+                ) -> Result<()> {
+                    self.register_async_with_extra_allowed_syscalls(sandbox, name, runtime, Vec::new())
+                }
+
+                #[cfg(not(all(feature = "seccomp", target_os = "linux")))]
+                #[instrument(
+                    err(Debug), skip(self, sandbox, runtime), parent = Span::current(), level = "Trace"
+                )]
+                fn register_async(
+                    &self,
+                    sandbox: &mut UninitializedSandbox,
+                    name: &str,
+                    runtime: &Handle,
+                ) -> Result<()> {
+                    let func = [<blocking_ $N>](self.clone(), runtime.clone(), name)?;
+                    [<HostFunction $N>]::<$($P,)* R>::register(&func, sandbox, name)
+                }
+
+                #[cfg(all(feature = "seccomp", target_os = "linux"))]
+                #[instrument(
+                    err(Debug), skip(self, sandbox, runtime, extra_allowed_syscalls),
+                    parent = Span::current(), level = "Trace"
+                )]
+                fn register_async_with_extra_allowed_syscalls(
+                    &self,
+                    sandbox: &mut UninitializedSandbox,
+                    name: &str,
+                    runtime: &Handle,
+                    extra_allowed_syscalls: Vec<ExtraAllowedSyscall>,
+                ) -> Result<()> {
+                    let func = [<blocking_ $N>](self.clone(), runtime.clone(), name)?;
+                    [<HostFunction $N>]::<$($P,)* R>::register_with_extra_allowed_syscalls(
+                        &func,
+                        sandbox,
+                        name,
+                        extra_allowed_syscalls,
+                    )
+                }
+            }
+
+            /// Wrap `self_` in a function that blocks until the future it
+            /// returns has run on `runtime`
+            #[allow(clippy::type_complexity)]
+            fn [<blocking_ $N>]<T, Fut, $($P,)* R>(
+                self_: Arc<Mutex<T>>,
+                runtime: Handle,
+                name: &str,
+            ) -> Result<Arc<Mutex<impl FnMut($($P),*) -> Result<R> + Send + 'static>>>
+            where
+                T: FnMut($($P),*) -> Fut + Send + 'static,
+                Fut: Future<Output = Result<R>> + Send + 'static,
+                R: Send + 'static,
+            {
+                let name = name.to_string();
+                let spawner = Spawner::new(runtime)?;
+                Ok(Arc::new(Mutex::new(move |$($P: $P),*| {
+                    let future = self_
+                        .try_lock()
+                        .map_err(|e| new_error!("Error locking at {}:{}: {}", file!(), line!(), e))?(
+                            $($P),*
+                        );
+                    run_on(&spawner, &name, future)
+                })))
+            }
+        }
+    };
+}
+
+async_host_function!(0);
+async_host_function!(1, P1);
+async_host_function!(2, P1, P2);
+async_host_function!(3, P1, P2, P3);
+async_host_function!(4, P1, P2, P3, P4);
+async_host_function!(5, P1, P2, P3, P4, P5);
+async_host_function!(6, P1, P2, P3, P4, P5, P6);
+async_host_function!(7, P1, P2, P3, P4, P5, P6, P7);
+async_host_function!(8, P1, P2, P3, P4, P5, P6, P7, P8);
+async_host_function!(9, P1, P2, P3, P4, P5, P6, P7, P8, P9);
+async_host_function!(10, P1, P2, P3, P4, P5, P6, P7, P8, P9, P10);
+
+#[cfg(test)]
+mod tests {
+    use tokio::runtime::Builder;
+
+    use super::*;
+
+    #[test]
+    fn run_on_runtime() {
+        let runtime = Builder::new_multi_thread()
+            .worker_threads(1)
+            .build()
+            .unwrap();
+        let spawner = Spawner::new(runtime.handle().clone()).unwrap();
+        let thread = std::thread::current().id();
+        let ran_on = run_on(&spawner, "test", async move {
+            tokio::task::yield_now().await;
+            Ok(std::thread::current().id())
+        })
+        .unwrap();
+        assert_ne!(ran_on, thread);
+
+        let res = run_on::<i32>(&spawner, "test", async {
+            Err(new_error!("the host function failed"))
+        });
+        assert!(res.unwrap_err().to_string().contains("failed"));
+        let res = run_on::<i32>(&spawner, "test", async {
+            panic!("the host function panicked")
+        });
+        assert!(res.unwrap_err().to_string().contains("panicked"));
+    }
+}
@@ -72,7 +72,8 @@ impl MultiUseGuestCallContext {
         // !Send (and !Sync), we also don't need to worry about
         // synchronization
 
-        call_function_on_guest(&mut self.sbox, func_name, func_ret_type, args)
+        self.sbox.prepare_for_call()?;
+        call_function_on_guest(&mut self.sbox, func_name, None, func_ret_type, args)
     }
 
     /// Close out the context and get back the internally-stored
@@ -149,7 +150,8 @@ impl SingleUseGuestCallContext {
         // !Send (and !Sync), we also don't need to worry about
         // synchronization
 
-        call_function_on_guest(&mut self.sbox, func_name, func_ret_type, args)
+        self.sbox.ensure_initialized()?;
+        call_function_on_guest(&mut self.sbox, func_name, None, func_ret_type, args)
     }
 
     /// This function allows for a `SingleUseSandbox` to be used to make multiple calls to guest functions before it is dropped.
@@ -0,0 +1,134 @@
+/*
+Copyright 2024 The Hyperlight Authors.
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+    http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+use std::any::Any;
+use std::cell::RefCell;
+use std::sync::Arc;
+
+use tracing::{instrument, Span};
+
+/// A callback for the progress guests report with the
+/// `hyperlight::progress::report` host function, given the percentage
+/// done, at most 100, and a message
+pub type ProgressCallback = Arc<dyn Fn(u32, &str) + Send + Sync>;
+
+/// The progress a guest reported, on its way from the thread running the
+/// guest to the one that called it
+pub(crate) type ProgressReport = (u32, String);
+
+/// Options for a single guest function call, see
+/// `MultiUseSandbox::call_guest_function_with_options`
+#[derive(Clone, Default)]
+pub struct CallOptions {
+    /// Request-scoped data, such as the caller's credentials or a trace
+    /// ID, that the host functions the guest calls during the call can
+    /// read with `call_context`
+    pub context: Option<Arc<dyn Any + Send + Sync>>,
+    /// Called with the progress the guest reports during the call, for
+    /// example to update a progress bar. It runs on the thread making the
+    /// call, while it waits for the guest, so it isn't subject to the
+    /// seccomp filter host functions run under.
+    pub on_progress: Option<ProgressCallback>,
+}
+
+impl CallOptions {
+    /// Create options that change nothing about the call
+    #[instrument(skip_all, parent = Span::current(), level = "Trace")]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Give the host functions the guest calls during the call `context`
+    #[instrument(skip_all, parent = Span::current(), level = "Trace")]
+    pub fn with_context<T: Any + Send + Sync>(mut self, context: T) -> Self {
+        self.context = Some(Arc::new(context));
+        self
+    }
+
+    /// Call `callback` with the progress the guest reports during the call
+    #[instrument(skip_all, parent = Span::current(), level = "Trace")]
+    pub fn with_progress_callback<F>(mut self, callback: F) -> Self
+    where
+        F: Fn(u32, &str) + Send + Sync + 'static,
+    {
+        self.on_progress = Some(Arc::new(callback));
+        self
+    }
+
+    /// Whether the options leave the call as it would be without them
+    pub(crate) fn is_empty(&self) -> bool {
+        self.context.is_none() && self.on_progress.is_none()
+    }
+}
+
+thread_local! {
+    static CALL_OPTIONS: RefCell<Option<CallOptions>> = const { RefCell::new(None) };
+}
+
+/// The context of the guest function call a host function is handling, if
+/// the call was given one of type `T` through `CallOptions::context`.
+///
+/// This is only set while a host function runs, so async host functions
+/// have to read it before their future is first polled.
+#[instrument(skip_all, parent = Span::current(), level = "Trace")]
+pub fn call_context<T: Any + Send + Sync>() -> Option<Arc<T>> {
+    CALL_OPTIONS
+        .with(|options| options.borrow().as_ref()?.context.clone())?
+        .downcast()
+        .ok()
+}
+
+/// Run `f`, a host function, with `options` as the options of the call it
+/// sees
+pub(crate) fn with_call_options<R>(options: Option<CallOptions>, f: impl FnOnce() -> R) -> R {
+    /// Puts back the options `f` replaced, even if it panics
+    struct Restore(Option<CallOptions>);
+    impl Drop for Restore {
+        fn drop(&mut self) {
+            let previous = self.0.take();
+            CALL_OPTIONS.with(|current| *current.borrow_mut() = previous);
+        }
+    }
+
+    let _restore = Restore(CALL_OPTIONS.with(|current| current.replace(options)));
+    f()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn context_is_scoped_to_the_call() {
+        let options = CallOptions::new().with_context("trace-id".to_string());
+        assert!(call_context::<String>().is_none());
+        with_call_options(Some(options), || {
+            assert_eq!(*call_context::<String>().unwrap(), "trace-id");
+            assert!(call_context::<u32>().is_none());
+        });
+        assert!(call_context::<String>().is_none());
+    }
+
+    #[test]
+    fn context_is_cleared_after_a_panic() {
+        let options = CallOptions::new().with_context("trace-id".to_string());
+        let res = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            with_call_options(Some(options), || panic!("host function panicked"))
+        }));
+        assert!(res.is_err());
+        assert!(call_context::<String>().is_none());
+    }
+}
@@ -0,0 +1,204 @@
+/*
+Copyright 2024 The Hyperlight Authors.
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+    http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+use std::time::{Duration, Instant};
+
+use hyperlight_common::flatbuffer_wrappers::function_types::{
+    ParameterValue, ReturnType, ReturnValue,
+};
+
+use super::ret_type::SupportedReturnType;
+use crate::{MultiUseSandbox, Result};
+
+/// The number of guest calls made through a `GuestCaller`, and how long
+/// they took
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CallStats {
+    /// The number of calls made
+    pub calls: u64,
+    /// The number of calls that returned an error
+    pub failures: u64,
+    /// The time spent in calls, including ones that failed
+    pub time_in_calls: Duration,
+}
+
+impl CallStats {
+    /// Count a call that took `elapsed` and returned `result`
+    pub fn record<T>(&mut self, elapsed: Duration, result: &Result<T>) {
+        self.calls += 1;
+        if result.is_err() {
+            self.failures += 1;
+        }
+        self.time_in_calls += elapsed;
+    }
+}
+
+/// Something guest functions can be called through, such as a
+/// `MultiUseSandbox`.
+///
+/// Application code that takes a `GuestCaller`, generically or as a
+/// `Box<dyn GuestCaller>`, can be handed a sandbox in production and a
+/// test double, such as the `MockGuestCaller` in `hyperlight_testing`, in
+/// unit tests that shouldn't need a hypervisor.
+pub trait GuestCaller {
+    /// Call the guest function called `func_name` with `args`, expecting a
+    /// return value of type `func_ret_type`
+    fn call(
+        &mut self,
+        func_name: &str,
+        func_ret_type: ReturnType,
+        args: Option<Vec<ParameterValue>>,
+    ) -> Result<ReturnValue>;
+
+    /// Call the guest function called `func_name` with `args`, converting
+    /// its return value to `Output`. Calling this on a `&mut dyn
+    /// GuestCaller` needs the fully qualified form,
+    /// `GuestCaller::call_typed(&mut caller, ...)`.
+    fn call_typed<Output: SupportedReturnType<Output>>(
+        &mut self,
+        func_name: &str,
+        args: Option<Vec<ParameterValue>>,
+    ) -> Result<Output>
+    where
+        Self: Sized,
+    {
+        let ret = self.call(func_name, Output::get_hyperlight_type(), args)?;
+        Output::get_inner(ret)
+    }
+
+    /// The calls made through this so far
+    fn stats(&self) -> CallStats;
+}
+
+impl<T: GuestCaller + ?Sized> GuestCaller for &mut T {
+    fn call(
+        &mut self,
+        func_name: &str,
+        func_ret_type: ReturnType,
+        args: Option<Vec<ParameterValue>>,
+    ) -> Result<ReturnValue> {
+        (**self).call(func_name, func_ret_type, args)
+    }
+
+    fn stats(&self) -> CallStats {
+        (**self).stats()
+    }
+}
+
+impl<T: GuestCaller + ?Sized> GuestCaller for Box<T> {
+    fn call(
+        &mut self,
+        func_name: &str,
+        func_ret_type: ReturnType,
+        args: Option<Vec<ParameterValue>>,
+    ) -> Result<ReturnValue> {
+        (**self).call(func_name, func_ret_type, args)
+    }
+
+    fn stats(&self) -> CallStats {
+        (**self).stats()
+    }
+}
+
+impl GuestCaller for MultiUseSandbox {
+    fn call(
+        &mut self,
+        func_name: &str,
+        func_ret_type: ReturnType,
+        args: Option<Vec<ParameterValue>>,
+    ) -> Result<ReturnValue> {
+        self.call_guest_function_by_name(func_name, func_ret_type, args)
+    }
+
+    fn stats(&self) -> CallStats {
+        self.call_stats()
+    }
+}
+
+/// Time `call` and count it in `stats`, for implementations of
+/// `GuestCaller`
+pub fn timed<T>(stats: &mut CallStats, call: impl FnOnce() -> Result<T>) -> Result<T> {
+    let start = Instant::now();
+    let result = call();
+    stats.record(start.elapsed(), &result);
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use hyperlight_common::flatbuffer_wrappers::function_types::{
+        ParameterValue, ReturnType, ReturnValue,
+    };
+
+    use super::{timed, CallStats, GuestCaller};
+    use crate::{new_error, Result};
+
+    /// Doubles its argument, and fails for negative ones
+    #[derive(Default)]
+    struct Doubler {
+        stats: CallStats,
+    }
+
+    impl GuestCaller for Doubler {
+        fn call(
+            &mut self,
+            _func_name: &str,
+            _func_ret_type: ReturnType,
+            args: Option<Vec<ParameterValue>>,
+        ) -> Result<ReturnValue> {
+            timed(&mut self.stats, || match args.as_deref() {
+                Some([ParameterValue::Int(i)]) if *i >= 0 => Ok(ReturnValue::Int(i * 2)),
+                _ => Err(new_error!("bad argument")),
+            })
+        }
+
+        fn stats(&self) -> CallStats {
+            self.stats
+        }
+    }
+
+    fn call_through_dyn(mut caller: &mut dyn GuestCaller, i: i32) -> Result<i32> {
+        GuestCaller::call_typed(&mut caller, "Double", Some(vec![ParameterValue::Int(i)]))
+    }
+
+    #[test]
+    fn calls_are_counted() {
+        let mut doubler = Doubler::default();
+        assert_eq!(
+            doubler
+                .call_typed::<i32>("Double", Some(vec![ParameterValue::Int(2)]))
+                .unwrap(),
+            4
+        );
+        // the guest returned an Int, not a String
+        assert!(doubler
+            .call_typed::<String>("Double", Some(vec![ParameterValue::Int(2)]))
+            .is_err());
+
+        let mut boxed: Box<dyn GuestCaller> = Box::new(doubler);
+        assert_eq!(
+            boxed
+                .call_typed::<i32>("Double", Some(vec![ParameterValue::Int(3)]))
+                .unwrap(),
+            6
+        );
+        assert!(call_through_dyn(&mut *boxed, -1).is_err());
+
+        let stats = boxed.stats();
+        assert_eq!(stats.calls, 4);
+        assert_eq!(stats.failures, 1);
+    }
+}
@@ -14,6 +14,8 @@ See the License for the specific language governing permissions and
 limitations under the License.
 */
 
+use std::time::Instant;
+
 use hyperlight_common::flatbuffer_wrappers::function_call::{FunctionCall, FunctionCallType};
 use hyperlight_common::flatbuffer_wrappers::function_types::{
     ParameterValue, ReturnType, ReturnValue,
@@ -22,11 +24,23 @@ use tracing::{instrument, Span};
 
 use super::guest_err::check_for_guest_error;
 use crate::hypervisor::hypervisor_handler::HypervisorHandlerAction;
+use crate::sandbox::isolation_audit::{IsolationAudit, IsolationViolation};
 use crate::sandbox::WrapperGetter;
-use crate::HyperlightError::GuestExecutionHungOnHostFunctionCall;
-use crate::{HyperlightError, Result};
-
-/// Call a guest function by name, using the given `wrapper_getter`.
+use crate::HyperlightError::{GuestExecutionHungOnHostFunctionCall, PoisonedSandbox};
+use crate::{log_then_return, HyperlightError, Result};
+
+/// A guest function resolved once with `MultiUseSandbox::guest_function_id`,
+/// so that calls to it with `MultiUseSandbox::call_guest_function_by_id`
+/// send the guest the function's id rather than its name.
+///
+/// Ids are only meaningful to the sandbox they were resolved in, and to
+/// others running the same guest binary.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct GuestFunctionId(pub(crate) u32);
+
+/// Call a guest function by name, using the given `wrapper_getter`. If the
+/// function was resolved to `function_id`, the guest is sent the id
+/// rather than `function_name`, which is still used to report the call.
 #[instrument(
     err(Debug),
     skip(wrapper_getter, args),
@@ -36,25 +50,129 @@ use crate::{HyperlightError, Result};
 pub(crate) fn call_function_on_guest<WrapperGetterT: WrapperGetter>(
     wrapper_getter: &mut WrapperGetterT,
     function_name: &str,
+    function_id: Option<GuestFunctionId>,
     return_type: ReturnType,
     args: Option<Vec<ParameterValue>>,
 ) -> Result<ReturnValue> {
-    let mut timedout = false;
+    let hv_handler = wrapper_getter.get_hv_handler();
+    if log::log_enabled!(target: "hyperlight_host::audit", log::Level::Info) {
+        log::info!(
+            target: "hyperlight_host::audit",
+            "Calling guest function {} in sandbox {} with {}",
+            function_name,
+            hv_handler.sandbox_id(),
+            hv_handler
+                .redaction()
+                .describe_parameters(args.as_deref().unwrap_or_default())
+        );
+    }
+    let sandbox_id = hv_handler.sandbox_id();
+    let events = hv_handler.events().clone();
+    let audit = if wrapper_getter.get_mgr_wrapper().as_ref().isolation_audit() {
+        Some(IsolationAudit::start(
+            wrapper_getter.get_mgr_wrapper().as_ref(),
+        )?)
+    } else {
+        None
+    };
+    events.emit(|subscriber, id| subscriber.on_guest_call_started(id, function_name));
+    let start = Instant::now();
 
-    let fc = FunctionCall::new(
-        function_name.to_string(),
-        args,
-        FunctionCallType::Guest,
+    let result = dispatch_function_to_guest(
+        wrapper_getter,
+        function_name,
+        function_id,
         return_type,
+        args,
     );
 
-    let buffer: Vec<u8> = fc
-        .try_into()
-        .map_err(|_| HyperlightError::Error("Failed to serialize FunctionCall".to_string()))?;
+    if let Err(e) = &result {
+        events.emit(|subscriber, id| subscriber.on_guest_error(id, function_name, e));
+    }
+    if let Some(audit) = audit {
+        // violations are reported rather than failing the call, which may
+        // well have succeeded
+        match finish_isolation_audit(wrapper_getter, audit) {
+            Ok(violations) => {
+                for violation in violations {
+                    log::warn!(
+                        target: "hyperlight_host::audit",
+                        "Isolation violation after guest function {} in sandbox {}: {}",
+                        function_name,
+                        sandbox_id,
+                        violation
+                    );
+                    events.emit(|subscriber, id| {
+                        subscriber.on_isolation_violation(id, function_name, &violation)
+                    });
+                }
+            }
+            Err(e) => log::error!("Failed to audit guest function {}: {}", function_name, e),
+        }
+    }
+    events.emit(|subscriber, id| {
+        subscriber.on_guest_call_finished(id, function_name, start.elapsed())
+    });
+    result
+}
+
+/// Check what the guest left behind after a call that started `audit`
+fn finish_isolation_audit<WrapperGetterT: WrapperGetter>(
+    wrapper_getter: &WrapperGetterT,
+    audit: IsolationAudit,
+) -> Result<Vec<IsolationViolation>> {
+    let mem_mgr = wrapper_getter.get_mgr_wrapper();
+    let mut violations = Vec::new();
+    if !mem_mgr.check_stack_guard()? {
+        violations.push(IsolationViolation::StackCookieOverwritten);
+    }
+    violations.extend(audit.finish(mem_mgr.as_ref())?);
+    Ok(violations)
+}
+
+fn dispatch_function_to_guest<WrapperGetterT: WrapperGetter>(
+    wrapper_getter: &mut WrapperGetterT,
+    function_name: &str,
+    function_id: Option<GuestFunctionId>,
+    return_type: ReturnType,
+    args: Option<Vec<ParameterValue>>,
+) -> Result<ReturnValue> {
+    let mut timedout = false;
+
+    if wrapper_getter.get_mgr_wrapper().as_ref().is_poisoned() {
+        log_then_return!(PoisonedSandbox(
+            wrapper_getter.get_hv_handler().sandbox_id()
+        ));
+    }
+
+    let fc = match function_id {
+        // the guest finds the function by its id, so its name isn't sent
+        Some(GuestFunctionId(id)) => {
+            FunctionCall::new(String::new(), args, FunctionCallType::Guest, return_type)
+                .with_function_id(id)
+        }
+        None => FunctionCall::new(
+            function_name.to_string(),
+            args,
+            FunctionCallType::Guest,
+            return_type,
+        ),
+    };
 
+    let profiler = wrapper_getter.get_hv_handler().events().profiler().clone();
+    let max_exec_time = wrapper_getter.get_hv_handler().max_exec_time();
+    let guest_tsc_khz = wrapper_getter.get_hv_handler().guest_tsc_khz();
     {
         let mem_mgr = wrapper_getter.get_mgr_wrapper_mut();
-        mem_mgr.as_mut().write_guest_function_call(&buffer)?;
+        // the call is timed from when it is dispatched, just below
+        mem_mgr
+            .as_mut()
+            .write_call_deadline(max_exec_time, guest_tsc_khz)?;
+        let start = Instant::now();
+        mem_mgr.as_mut().write_guest_function_call(&fc)?;
+        profiler.record_serialization(start.elapsed(), false);
+        #[cfg(pointer_audit)]
+        mem_mgr.as_mut().audit_host_pointers()?;
     }
 
     let mut hv_handler = wrapper_getter.get_hv_handler().clone();
@@ -75,15 +193,23 @@ pub(crate) fn call_function_on_guest<WrapperGetterT: WrapperGetter>(
                     e => return Err(e),
                 }
             }
+            e @ (HyperlightError::GuestAborted { .. } | HyperlightError::StackOverflow()) => {
+                // the guest stopped part way through the call, so its memory
+                // can't be trusted until it is restored
+                wrapper_getter.get_mgr_wrapper_mut().as_mut().set_poisoned();
+                return Err(e);
+            }
             e => return Err(e),
         },
     };
 
+    let sandbox_id = wrapper_getter.get_hv_handler().sandbox_id();
     let mem_mgr = wrapper_getter.get_mgr_wrapper_mut();
     mem_mgr.check_stack_guard()?; // <- wrapper around mem_mgr `check_for_stack_guard`
     check_for_guest_error(mem_mgr)?;
 
-    mem_mgr
+    let start = Instant::now();
+    let result = mem_mgr
         .as_mut()
         .get_guest_function_call_result()
         .map_err(|e| {
@@ -96,11 +222,13 @@ pub(crate) fn call_function_on_guest<WrapperGetterT: WrapperGetter>(
                 // unlike w/ the previous scoped thread usage,
                 // we can't check if the thread completed or not.
                 log::error!("Guest execution hung on host function call");
-                GuestExecutionHungOnHostFunctionCall()
+                GuestExecutionHungOnHostFunctionCall(sandbox_id)
             } else {
                 e
             }
-        })
+        });
+    profiler.record_serialization(start.elapsed(), false);
+    result
 }
 
 #[cfg(test)]
@@ -108,6 +236,8 @@ mod tests {
     use std::sync::{Arc, Mutex};
     use std::thread;
 
+    use flatbuffers::FlatBufferBuilder;
+    use hyperlight_common::flatbuffer_wrappers::guest_error::ErrorCode;
     use hyperlight_testing::{callback_guest_as_string, simple_guest_as_string};
 
     use super::*;
@@ -218,6 +348,33 @@ mod tests {
             }
         }
 
+        // Third, answer the call from the fallback, allowing `SYS_getpid`
+        #[cfg(feature = "seccomp")]
+        {
+            let mut usbox = UninitializedSandbox::new(
+                GuestBinary::FilePath(simple_guest_as_string().expect("Guest Binary Missing")),
+                None,
+                None,
+                None,
+            )
+            .unwrap();
+
+            usbox.register_fallback_host_function(
+                |_, _| Ok(ReturnValue::ULong(make_get_pid_syscall()?)),
+                vec![libc::SYS_getpid],
+            )?;
+
+            let mut sbox: MultiUseSandbox = usbox.evolve(Noop::default())?;
+
+            let res =
+                sbox.call_guest_function_by_name("ViolateSeccompFilters", ReturnType::ULong, None);
+
+            match res {
+                Ok(_) => {}
+                Err(e) => panic!("Expected the fallback to be allowed SYS_getpid: {}", e),
+            }
+        }
+
         Ok(())
     }
 
@@ -475,11 +632,74 @@ mod tests {
 
         assert!(result.is_err());
         match result.unwrap_err() {
-            HyperlightError::GuestExecutionHungOnHostFunctionCall() => {}
+            HyperlightError::GuestExecutionHungOnHostFunctionCall(_) => {}
             e => panic!(
                 "Expected HyperlightError::GuestExecutionHungOnHostFunctionCall but got {:?}",
                 e
             ),
         }
     }
+
+    /// Push `buffer` onto the input buffer in place of a serialized function
+    /// call and have the guest dispatch it
+    fn dispatch_raw_function_call(sbox: &mut MultiUseSandbox, buffer: &[u8]) -> Result<()> {
+        sbox.get_mgr_wrapper_mut()
+            .as_mut()
+            .write_raw_guest_function_call(buffer)?;
+
+        sbox.get_hv_handler()
+            .clone()
+            .execute_hypervisor_handler_action(HypervisorHandlerAction::DispatchCallFromHost(
+                "Corrupted".to_string(),
+            ))?;
+        check_for_guest_error(sbox.get_mgr_wrapper())
+    }
+
+    #[test]
+    fn test_corrupted_function_calls_are_reported_as_guest_errors() {
+        let mut sbox: MultiUseSandbox = UninitializedSandbox::new(guest_bin(), None, None, None)
+            .unwrap()
+            .evolve(Noop::default())
+            .unwrap();
+
+        let echo = || {
+            FunctionCall::new(
+                "Echo".to_string(),
+                Some(vec![ParameterValue::String("hello".to_string())]),
+                FunctionCallType::Guest,
+                ReturnType::String,
+            )
+            .encode(&mut FlatBufferBuilder::new())
+            .to_vec()
+        };
+
+        // garbage after a valid size prefix
+        let mut garbage = 16u32.to_le_bytes().to_vec();
+        garbage.extend_from_slice(&[0xff; 16]);
+
+        // a size prefix claiming more data than the element holds
+        let mut truncated = echo();
+        truncated.truncate(truncated.len() / 2);
+
+        // a root table offset pointing outside the buffer
+        let mut bad_root = echo();
+        bad_root[4..8].copy_from_slice(&u32::MAX.to_le_bytes());
+
+        for buffer in [garbage, truncated, bad_root] {
+            match dispatch_raw_function_call(&mut sbox, &buffer) {
+                Err(HyperlightError::GuestError(ErrorCode::GuestError, ..)) => {}
+                other => panic!("Expected a GuestError but got {:?}", other),
+            }
+        }
+
+        // the guest is still usable afterwards
+        let result = sbox
+            .call_guest_function_by_name(
+                "Echo",
+                ReturnType::String,
+                Some(vec![ParameterValue::String("hello".to_string())]),
+            )
+            .unwrap();
+        assert_eq!(result, ReturnValue::String("hello".to_string()));
+    }
 }
@@ -18,7 +18,9 @@ use hyperlight_common::flatbuffer_wrappers::guest_error::{
     ErrorCode, GuestError as GuestErrorStruct,
 };
 
-use crate::error::HyperlightError::{GuestError, OutBHandlingError, StackOverflow};
+use crate::error::HyperlightError::{
+    GuestCustomError, GuestError, OutBHandlingError, StackOverflow,
+};
 use crate::mem::shared_mem::HostSharedMemory;
 use crate::sandbox::mem_mgr::MemMgrWrapper;
 use crate::sandbox::metrics::SandboxMetric::GuestErrorCount;
@@ -27,6 +29,14 @@ use crate::{int_counter_vec_inc, log_then_return, Result};
 /// and `Ok` if one was not found.
 pub(crate) fn check_for_guest_error(mgr: &MemMgrWrapper<HostSharedMemory>) -> Result<()> {
     let guest_err = mgr.as_ref().get_guest_error()?;
+    if let Some(code) = guest_err.custom_code {
+        increment_guest_error_count(&guest_err);
+        log_then_return!(GuestCustomError {
+            code,
+            message: guest_err.message.clone(),
+            payload: guest_err.payload.clone()
+        });
+    }
     match guest_err.code {
         ErrorCode::NoError => Ok(()),
         ErrorCode::OutbError => match mgr.as_ref().get_host_error()? {
@@ -48,7 +58,8 @@ pub(crate) fn check_for_guest_error(mgr: &MemMgrWrapper<HostSharedMemory>) -> Re
             increment_guest_error_count(&guest_err.clone());
             log_then_return!(GuestError(
                 guest_err.code.clone(),
-                guest_err.message.clone()
+                guest_err.message.clone(),
+                guest_err.payload.clone()
             ));
         }
     }
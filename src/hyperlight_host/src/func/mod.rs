@@ -15,10 +15,20 @@ limitations under the License.
 */
 
 use crate::{new_error, Result};
+/// Host functions that return futures, which are run on a tokio runtime
+/// while the guest waits for them
+#[cfg(feature = "async_host_functions")]
+pub mod async_host_functions;
 /// Context structures used to allow the user to call one or more guest
 /// functions on the same Hyperlight sandbox instance, all from within the
 /// same state and mutual exclusion context.
 pub mod call_ctx;
+/// Options for individual guest function calls, such as the per-call
+/// context host functions can read and the callback for guest progress
+pub mod call_options;
+/// A trait for things guest functions can be called through, so that code
+/// calling guests can be tested without a hypervisor
+pub mod guest_caller;
 /// Functionality to dispatch a call from the host to the guest
 pub(crate) mod guest_dispatch;
 /// Functionality to check for errors after a guest call
@@ -42,12 +52,20 @@ pub mod ret_type;
 
 use std::sync::{Arc, Mutex};
 
+pub use call_options::{call_context, CallOptions, ProgressCallback};
+pub use guest_caller::{CallStats, GuestCaller};
+pub use guest_dispatch::GuestFunctionId;
 /// Re-export for `ParameterValue` enum
 pub use hyperlight_common::flatbuffer_wrappers::function_types::ParameterValue;
 /// Re-export for `ReturnType` enum
 pub use hyperlight_common::flatbuffer_wrappers::function_types::ReturnType;
 /// Re-export for `ReturnType` enum
 pub use hyperlight_common::flatbuffer_wrappers::function_types::ReturnValue;
+/// Re-export for `GuestFunctionSignature`, the signature of a guest function
+/// recorded in the guest binary
+pub use hyperlight_common::guest_function_metadata::GuestFunctionSignature;
+/// Re-export for `Secret`, the type of `ParameterValue::Secret`
+pub use hyperlight_common::secret::Secret;
 pub use param_type::SupportedParameterType;
 pub use ret_type::SupportedReturnType;
 use tracing::{instrument, Span};
@@ -77,6 +95,41 @@ impl HyperlightFunction {
     }
 }
 
+type HLFallbackFunc =
+    Arc<Mutex<Box<dyn FnMut(String, Vec<ParameterValue>) -> Result<ReturnValue> + Send>>>;
+
+/// Handler for guest calls to host functions that have not been registered,
+/// receiving the name of the function the guest called along with its
+/// arguments.
+#[derive(Clone)]
+pub(crate) struct HyperlightFallbackFunction(HLFallbackFunc);
+
+impl HyperlightFallbackFunction {
+    #[instrument(skip_all, parent = Span::current(), level= "Trace")]
+    pub(crate) fn new<F>(f: F) -> Self
+    where
+        F: FnMut(String, Vec<ParameterValue>) -> Result<ReturnValue> + Send + 'static,
+    {
+        Self(Arc::new(Mutex::new(Box::new(f))))
+    }
+
+    #[instrument(err(Debug), skip_all, parent = Span::current(), level= "Trace")]
+    pub(crate) fn call(&self, name: &str, args: Vec<ParameterValue>) -> Result<ReturnValue> {
+        let mut f = self
+            .0
+            .try_lock()
+            .map_err(|e| new_error!("Error locking at {}:{}: {}", file!(), line!(), e))?;
+        f(name.to_string(), args)
+    }
+}
+
+/// Re-exports for the `AsyncHostFunction` traits
+#[cfg(feature = "async_host_functions")]
+pub use async_host_functions::{
+    AsyncHostFunction0, AsyncHostFunction1, AsyncHostFunction10, AsyncHostFunction2,
+    AsyncHostFunction3, AsyncHostFunction4, AsyncHostFunction5, AsyncHostFunction6,
+    AsyncHostFunction7, AsyncHostFunction8, AsyncHostFunction9,
+};
 /// Re-export for `HostFunction0` trait
 pub use host_functions::HostFunction0;
 /// Re-export for `HostFunction1` trait
@@ -15,6 +15,7 @@ limitations under the License.
 */
 
 use hyperlight_common::flatbuffer_wrappers::function_types::{ParameterType, ParameterValue};
+use hyperlight_common::secret::Secret;
 use tracing::{instrument, Span};
 
 use crate::HyperlightError::ParameterValueConversionFailure;
@@ -190,3 +191,25 @@ impl SupportedParameterType<Vec<u8>> for Vec<u8> {
         }
     }
 }
+
+impl SupportedParameterType<Secret> for Secret {
+    #[instrument(skip_all, parent = Span::current(), level= "Trace")]
+    fn get_hyperlight_type() -> ParameterType {
+        ParameterType::Secret
+    }
+
+    #[instrument(skip_all, parent = Span::current(), level= "Trace")]
+    fn get_hyperlight_value(&self) -> ParameterValue {
+        ParameterValue::Secret(self.clone())
+    }
+
+    #[instrument(err(Debug), skip_all, parent = Span::current(), level= "Trace")]
+    fn get_inner(a: ParameterValue) -> Result<Secret> {
+        match a {
+            ParameterValue::Secret(i) => Ok(i),
+            other => {
+                log_then_return!(ParameterValueConversionFailure(other.clone(), "Secret"));
+            }
+        }
+    }
+}
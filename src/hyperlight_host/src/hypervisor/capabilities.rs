@@ -0,0 +1,109 @@
+/*
+Copyright 2024 The Hyperlight Authors.
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+    http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+use tracing::{instrument, Span};
+
+/// A report of the features of the hypervisors available on this host,
+/// as returned by `capabilities()`.
+///
+/// Fields describing a hypervisor that isn't available are zero or `false`.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct HypervisorCapabilities {
+    /// The version returned by `KVM_GET_API_VERSION`, or 0 if KVM is not
+    /// available
+    pub kvm_api_version: i32,
+    /// Whether the Microsoft Hypervisor (mshv) is available
+    pub mshv_present: bool,
+    /// The maximum number of memory slots a KVM VM can have
+    pub max_memory_slots: u32,
+    /// Whether KVM supports manually protected dirty page logging
+    pub supports_dirty_logging: bool,
+    /// Whether KVM supports `immediate_exit`, which lets the host cancel
+    /// a vCPU run without a signal racing the `KVM_RUN` ioctl
+    pub supports_immediate_exit: bool,
+    /// Whether the KVM kernel module allows nested virtualization
+    pub nested_virtualization: bool,
+}
+
+/// Probe the hypervisors available on this host and report their
+/// features, so that callers can decide which features to use before
+/// creating a sandbox.
+#[instrument(skip_all, parent = Span::current(), level = "Trace")]
+pub fn capabilities() -> HypervisorCapabilities {
+    #[allow(unused_mut)]
+    let mut caps = HypervisorCapabilities::default();
+
+    #[cfg(kvm)]
+    if let Ok(kvm) = kvm_ioctls::Kvm::new() {
+        caps.kvm_api_version = kvm.get_api_version();
+        caps.max_memory_slots = kvm.get_nr_memslots() as u32;
+        caps.supports_dirty_logging = kvm
+            .check_extension_raw(kvm_bindings::KVM_CAP_MANUAL_DIRTY_LOG_PROTECT2 as libc::c_ulong)
+            > 0;
+        caps.supports_immediate_exit = kvm.check_extension(kvm_ioctls::Cap::ImmediateExit);
+        caps.nested_virtualization = kvm_nested_enabled();
+    }
+
+    #[cfg(mshv)]
+    {
+        caps.mshv_present = super::hyperv_linux::is_hypervisor_present();
+    }
+
+    caps
+}
+
+/// Return `true` if the loaded KVM vendor module was loaded with its
+/// `nested` parameter enabled
+#[cfg(kvm)]
+fn kvm_nested_enabled() -> bool {
+    ["kvm_intel", "kvm_amd"].iter().any(|module| {
+        std::fs::read_to_string(format!("/sys/module/{}/parameters/nested", module))
+            .map(|value| matches!(value.trim(), "Y" | "1"))
+            .unwrap_or(false)
+    })
+}
+
+/// C API wrapper around `capabilities()`.
+#[no_mangle]
+pub extern "C" fn hyperlight_hypervisor_capabilities() -> HypervisorCapabilities {
+    capabilities()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::capabilities;
+
+    #[test]
+    fn test_capabilities() {
+        let caps = capabilities();
+
+        #[cfg(kvm)]
+        if super::super::kvm::is_hypervisor_present() {
+            assert_eq!(caps.kvm_api_version, 12);
+            assert!(caps.max_memory_slots > 0);
+        }
+
+        #[cfg(mshv)]
+        assert_eq!(
+            caps.mshv_present,
+            super::super::hyperv_linux::is_hypervisor_present()
+        );
+
+        #[cfg(not(any(kvm, mshv)))]
+        assert_eq!(caps, Default::default());
+    }
+}
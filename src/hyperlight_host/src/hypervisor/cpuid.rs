@@ -0,0 +1,229 @@
+/*
+Copyright 2024 The Hyperlight Authors.
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+    http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+use tracing::{instrument, Span};
+
+/// The vendor signature reported in leaf `0x40000000` when
+/// `CpuidOptions::with_hypervisor_signature` is used
+pub const HYPERVISOR_SIGNATURE: &[u8; 12] = b"HyperlightVM";
+
+const HYPERVISOR_LEAF: u32 = 0x4000_0000;
+/// CPUID.1:ECX bit 31 is set when running under a hypervisor
+const HYPERVISOR_PRESENT: u32 = 1 << 31;
+/// AVX512F, AVX512DQ, AVX512IFMA, AVX512PF, AVX512ER, AVX512CD, AVX512BW and AVX512VL
+const AVX512_LEAF_7_EBX: u32 =
+    (1 << 16) | (1 << 17) | (1 << 21) | (1 << 26) | (1 << 27) | (1 << 28) | (1 << 30) | (1 << 31);
+/// AVX512_VBMI, AVX512_VBMI2, AVX512_VNNI, AVX512_BITALG and AVX512_VPOPCNTDQ
+const AVX512_LEAF_7_ECX: u32 = (1 << 1) | (1 << 6) | (1 << 11) | (1 << 12) | (1 << 14);
+/// AVX512_4VNNIW, AVX512_4FMAPS, AVX512_VP2INTERSECT and AVX512_FP16
+const AVX512_LEAF_7_EDX: u32 = (1 << 2) | (1 << 3) | (1 << 8) | (1 << 23);
+/// The opmask, ZMM_Hi256 and Hi16_ZMM XSAVE state components
+const AVX512_XCR0: u32 = (1 << 5) | (1 << 6) | (1 << 7);
+
+/// The values returned by the CPUID instruction for a leaf (`function`)
+/// and subleaf (`index`)
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CpuidEntry {
+    /// The leaf, passed to CPUID in EAX
+    pub function: u32,
+    /// The subleaf, passed to CPUID in ECX
+    pub index: u32,
+    /// The value returned in EAX
+    pub eax: u32,
+    /// The value returned in EBX
+    pub ebx: u32,
+    /// The value returned in ECX
+    pub ecx: u32,
+    /// The value returned in EDX
+    pub edx: u32,
+}
+
+/// Options controlling the CPUID leaves presented to the guest's vCPU.
+///
+/// The options are applied on top of the leaves the hypervisor supports.
+/// They are only implemented by the KVM and mshv drivers on x86-64, and
+/// evolving a sandbox with them set fails on other hypervisors.
+#[derive(Debug, Clone, Default)]
+pub struct CpuidOptions {
+    hide_avx512: bool,
+    hypervisor_signature: bool,
+    overrides: Vec<CpuidEntry>,
+}
+
+impl CpuidOptions {
+    /// Create options that present the leaves the hypervisor supports
+    /// unchanged.
+    #[instrument(skip_all, parent = Span::current(), level = "Trace")]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Hide AVX-512 from the guest, so that it isn't used even if the
+    /// host supports it.
+    #[instrument(skip_all, parent = Span::current(), level = "Trace")]
+    pub fn hide_avx512(mut self) -> Self {
+        self.hide_avx512 = true;
+        self
+    }
+
+    /// Set the hypervisor present bit, and report `HYPERVISOR_SIGNATURE`
+    /// in leaf `0x40000000`, so that the guest can detect it is running in
+    /// a Hyperlight sandbox.
+    #[instrument(skip_all, parent = Span::current(), level = "Trace")]
+    pub fn with_hypervisor_signature(mut self) -> Self {
+        self.hypervisor_signature = true;
+        self
+    }
+
+    /// Present `entry` to the guest, replacing any leaf with the same
+    /// `function` and `index`.
+    #[instrument(skip_all, parent = Span::current(), level = "Trace")]
+    pub fn with_entry(mut self, entry: CpuidEntry) -> Self {
+        self.overrides.push(entry);
+        self
+    }
+
+    /// Apply the options to the leaves supported by the hypervisor.
+    #[cfg_attr(not(any(all(kvm, target_arch = "x86_64"), mshv)), allow(dead_code))]
+    #[instrument(skip_all, parent = Span::current(), level = "Trace")]
+    pub(crate) fn apply(&self, entries: &mut Vec<CpuidEntry>) {
+        if self.hide_avx512 {
+            for entry in entries.iter_mut() {
+                match (entry.function, entry.index) {
+                    (7, 0) => {
+                        entry.ebx &= !AVX512_LEAF_7_EBX;
+                        entry.ecx &= !AVX512_LEAF_7_ECX;
+                        entry.edx &= !AVX512_LEAF_7_EDX;
+                    }
+                    (0xd, 0) => entry.eax &= !AVX512_XCR0,
+                    _ => {}
+                }
+            }
+        }
+
+        if self.hypervisor_signature {
+            entries
+                .iter_mut()
+                .filter(|entry| entry.function == 1)
+                .for_each(|entry| entry.ecx |= HYPERVISOR_PRESENT);
+
+            let word = |i: usize| {
+                u32::from_le_bytes(HYPERVISOR_SIGNATURE[i * 4..i * 4 + 4].try_into().unwrap())
+            };
+            set_entry(
+                entries,
+                CpuidEntry {
+                    function: HYPERVISOR_LEAF,
+                    index: 0,
+                    eax: HYPERVISOR_LEAF,
+                    ebx: word(0),
+                    ecx: word(1),
+                    edx: word(2),
+                },
+            );
+        }
+
+        for entry in &self.overrides {
+            set_entry(entries, *entry);
+        }
+    }
+}
+
+#[cfg_attr(not(any(all(kvm, target_arch = "x86_64"), mshv)), allow(dead_code))]
+fn set_entry(entries: &mut Vec<CpuidEntry>, entry: CpuidEntry) {
+    match entries
+        .iter_mut()
+        .find(|e| e.function == entry.function && e.index == entry.index)
+    {
+        Some(existing) => *existing = entry,
+        None => entries.push(entry),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{CpuidEntry, CpuidOptions, HYPERVISOR_SIGNATURE};
+
+    fn supported() -> Vec<CpuidEntry> {
+        vec![
+            CpuidEntry {
+                function: 1,
+                ..Default::default()
+            },
+            CpuidEntry {
+                function: 7,
+                ebx: u32::MAX,
+                ecx: u32::MAX,
+                edx: u32::MAX,
+                ..Default::default()
+            },
+            CpuidEntry {
+                function: 0xd,
+                eax: 0xff,
+                ..Default::default()
+            },
+        ]
+    }
+
+    #[test]
+    fn default_options_change_nothing() {
+        let mut entries = supported();
+        CpuidOptions::new().apply(&mut entries);
+        assert_eq!(entries, supported());
+    }
+
+    #[test]
+    fn hide_avx512() {
+        let mut entries = supported();
+        CpuidOptions::new().hide_avx512().apply(&mut entries);
+
+        // AVX512F
+        assert_eq!(entries[1].ebx & (1 << 16), 0);
+        // AVX2 is left alone
+        assert_ne!(entries[1].ebx & (1 << 5), 0);
+        assert_eq!(entries[2].eax, 0x1f);
+    }
+
+    #[test]
+    fn hypervisor_signature() {
+        let mut entries = supported();
+        CpuidOptions::new()
+            .with_hypervisor_signature()
+            .apply(&mut entries);
+
+        assert_ne!(entries[0].ecx & (1 << 31), 0);
+        let leaf = entries.iter().find(|e| e.function == 0x4000_0000).unwrap();
+        let signature: Vec<u8> = [leaf.ebx, leaf.ecx, leaf.edx]
+            .iter()
+            .flat_map(|w| w.to_le_bytes())
+            .collect();
+        assert_eq!(signature, HYPERVISOR_SIGNATURE);
+    }
+
+    #[test]
+    fn with_entry() {
+        let entry = CpuidEntry {
+            function: 7,
+            ebx: 1,
+            ..Default::default()
+        };
+        let mut entries = supported();
+        CpuidOptions::new().with_entry(entry).apply(&mut entries);
+
+        assert_eq!(entries.len(), supported().len());
+        assert_eq!(entries[1], entry);
+    }
+}
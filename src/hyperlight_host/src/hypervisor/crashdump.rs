@@ -1,18 +1,160 @@
+/*
+Copyright 2024 The Hyperlight Authors.
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+    http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+#[cfg(crashdump)]
 use std::io::Write;
+use std::mem::size_of;
 
+use hyperlight_common::flatbuffer_wrappers::guest_error::ErrorCode;
+#[cfg(crashdump)]
 use tempfile::NamedTempFile;
 
+#[cfg(crashdump)]
 use super::Hypervisor;
+use crate::mem::memory_region::MemoryRegion;
+use crate::mem::symbols::GuestSymbols;
+#[cfg(crashdump)]
+use crate::sandbox::redaction::RedactionPolicy;
+#[cfg(crashdump)]
+use crate::sandbox::GuestInfo;
+use crate::HyperlightError;
+#[cfg(crashdump)]
 use crate::{new_error, Result};
 
-/// Dump registers + memory regions + raw memory to a tempfile
+/// Where the guest was executing when it failed, as reported by the
+/// guest or read from its vCPU.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CrashDump {
+    /// The guest's instruction pointer
+    pub rip: u64,
+    /// The return addresses of the calls in progress, innermost first
+    pub backtrace: Vec<u64>,
+}
+
+impl CrashDump {
+    /// Extract the crash dump from an error reporting that the guest
+    /// aborted, or return `None` for any other error.
+    ///
+    /// If the guest aborted because it took a CPU exception, `rip` is where
+    /// the exception happened, otherwise it is where the guest aborted.
+    pub fn from_error(error: &HyperlightError) -> Option<Self> {
+        match error {
+            HyperlightError::GuestAborted {
+                code,
+                message,
+                backtrace,
+            } => {
+                let (aborted_at, return_addresses) = match backtrace.split_first() {
+                    Some((rip, rest)) => (*rip, rest.to_vec()),
+                    None => (0, Vec::new()),
+                };
+                let rip = match *code == ErrorCode::GuestException as u8 {
+                    true => {
+                        let rip = message.split_once("RIP 0x")?.1.split_whitespace().next()?;
+                        u64::from_str_radix(rip, 16).ok()?
+                    }
+                    false => aborted_at,
+                };
+                Some(Self {
+                    rip,
+                    backtrace: return_addresses,
+                })
+            }
+            _ => None,
+        }
+    }
+
+    /// Describe the crash with each address translated to the
+    /// `function+offset` it is in, one address per line.
+    pub fn symbolize(&self, symbols: &GuestSymbols) -> String {
+        let mut report = format!("RIP {}\n", symbols.symbolize(self.rip));
+        for (i, address) in self.backtrace.iter().enumerate() {
+            report.push_str(&format!("#{} {}\n", i, symbols.symbolize(*address)));
+        }
+        report
+    }
+}
+
+/// The most frames `walk_frame_pointers` follows, in case the chain of
+/// saved frame pointers is corrupt
+const MAX_FRAMES: usize = 64;
+
+/// Unwind the guest's stack by following the chain of frame pointers
+/// starting at `rbp`, returning the return address saved in each frame.
+///
+/// This relies on the guest being built with frame pointers, and stops at
+/// the first frame that doesn't look valid.
+#[cfg_attr(not(target_arch = "x86_64"), allow(dead_code))]
+pub(crate) fn walk_frame_pointers(mem_regions: &[MemoryRegion], mut rbp: u64) -> Vec<u64> {
+    let mut backtrace = Vec::new();
+    while backtrace.len() < MAX_FRAMES && rbp != 0 && rbp % 8 == 0 {
+        let (Some(saved_rbp), Some(return_address)) = (
+            read_guest_u64(mem_regions, rbp),
+            read_guest_u64(mem_regions, rbp + 8),
+        ) else {
+            break;
+        };
+        if return_address == 0 {
+            break;
+        }
+        backtrace.push(return_address);
+        // the stack grows down, so each caller's frame is above its callee's
+        if saved_rbp <= rbp {
+            break;
+        }
+        rbp = saved_rbp;
+    }
+    backtrace
+}
+
+#[cfg_attr(not(target_arch = "x86_64"), allow(dead_code))]
+fn read_guest_u64(mem_regions: &[MemoryRegion], address: u64) -> Option<u64> {
+    let start = usize::try_from(address).ok()?;
+    let end = start.checked_add(size_of::<u64>())?;
+    let region = mem_regions
+        .iter()
+        .find(|region| region.guest_region.start <= start && end <= region.guest_region.end)?;
+    if region.host_region.start == 0 {
+        return None;
+    }
+    let host_address = region.host_region.start + (start - region.guest_region.start);
+    // SAFETY: the region is mapped into the host for as long as the hypervisor
+    // driver that owns it exists
+    Some(unsafe { (host_address as *const u64).read_unaligned() })
+}
+
+/// Dump the guest binary's provenance + registers + memory regions + raw
+/// memory to a tempfile, leaving out what `redaction` excludes
 #[cfg(crashdump)]
-pub(crate) fn crashdump_to_tempfile(hv: &dyn Hypervisor) -> Result<()> {
+pub(crate) fn crashdump_to_tempfile(
+    hv: &dyn Hypervisor,
+    guest_info: Option<&GuestInfo>,
+    redaction: RedactionPolicy,
+) -> Result<()> {
     let mut temp_file = NamedTempFile::with_prefix("mem")?;
     let hv_details = format!("{:#x?}", hv);
 
+    if let Some(guest_info) = guest_info {
+        writeln!(temp_file, "Guest: {}", guest_info)?;
+    }
     // write hypervisor details such as registers, info about mapped memory regions, etc.
     temp_file.write_all(hv_details.as_bytes())?;
+    if redaction.exclude_io_buffers {
+        writeln!(temp_file, "Input and output buffers excluded")?;
+    }
     temp_file.write_all(b"================ MEMORY DUMP =================\n")?;
 
     // write the raw memory dump for each memory region
@@ -20,6 +162,11 @@ pub(crate) fn crashdump_to_tempfile(hv: &dyn Hypervisor) -> Result<()> {
         if region.host_region.start == 0 || region.host_region.is_empty() {
             continue;
         }
+        if redaction.excludes_region(region.region_type) {
+            // zeros keep the regions after this one at the same offsets
+            temp_file.write_all(&vec![0; region.host_region.len()])?;
+            continue;
+        }
         // SAFETY: we got this memory region from the hypervisor so should never be invalid
         let region_slice = unsafe {
             std::slice::from_raw_parts(
@@ -42,3 +189,59 @@ pub(crate) fn crashdump_to_tempfile(hv: &dyn Hypervisor) -> Result<()> {
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use hyperlight_common::flatbuffer_wrappers::guest_error::ErrorCode;
+
+    use super::{walk_frame_pointers, CrashDump};
+    use crate::mem::memory_region::{MemoryRegion, MemoryRegionFlags, MemoryRegionType};
+    use crate::HyperlightError;
+
+    #[test]
+    fn walk_frames() {
+        const STACK_BASE: usize = 0x10000;
+        // three frames, each holding the caller's frame pointer then the return address
+        let stack: Vec<u64> = vec![0, 0x10018, 0x1001, 0x10028, 0x2002, 0, 0x3003, 0];
+        let regions = [MemoryRegion {
+            guest_region: STACK_BASE..STACK_BASE + stack.len() * 8,
+            host_region: stack.as_ptr() as usize..stack.as_ptr() as usize + stack.len() * 8,
+            flags: MemoryRegionFlags::READ | MemoryRegionFlags::WRITE,
+            region_type: MemoryRegionType::Stack,
+        }];
+
+        assert_eq!(
+            walk_frame_pointers(&regions, 0x10008),
+            vec![0x1001, 0x2002, 0x3003]
+        );
+        // frame pointers outside guest memory end the walk
+        assert!(walk_frame_pointers(&regions, 0x1000).is_empty());
+    }
+
+    #[test]
+    fn from_error() {
+        let error = HyperlightError::GuestAborted {
+            code: ErrorCode::GuestException as u8,
+            message: "Page fault (vector 14) at RIP 0x2031a4 addr 0x100000000 error code 0x0"
+                .to_string(),
+            backtrace: vec![0x203000, 0x203300],
+        };
+        let dump = CrashDump::from_error(&error).unwrap();
+        assert_eq!(dump.rip, 0x2031a4);
+        assert_eq!(dump.backtrace, vec![0x203300]);
+
+        let error = HyperlightError::GuestAborted {
+            code: 13,
+            message: String::new(),
+            backtrace: vec![0x1000, 0x2000, 0x3000],
+        };
+        let dump = CrashDump::from_error(&error).unwrap();
+        assert_eq!(dump.rip, 0x1000);
+        assert_eq!(dump.backtrace, vec![0x2000, 0x3000]);
+
+        assert_eq!(
+            CrashDump::from_error(&HyperlightError::StackOverflow()),
+            None
+        );
+    }
+}
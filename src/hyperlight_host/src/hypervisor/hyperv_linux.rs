@@ -14,19 +14,22 @@ See the License for the specific language governing permissions and
 limitations under the License.
 */
 
+use std::arch::x86_64::__cpuid_count;
 use std::fmt::{Debug, Formatter};
 
 use log::error;
 use mshv_bindings::{
-    hv_message, hv_message_type, hv_message_type_HVMSG_GPA_INTERCEPT,
-    hv_message_type_HVMSG_UNMAPPED_GPA, hv_message_type_HVMSG_X64_HALT,
-    hv_message_type_HVMSG_X64_IO_PORT_INTERCEPT, hv_register_assoc,
+    hv_cpuid_entry, hv_message, hv_message_type, hv_message_type_HVMSG_GPA_INTERCEPT,
+    hv_message_type_HVMSG_UNMAPPED_GPA, hv_message_type_HVMSG_UNRECOVERABLE_EXCEPTION,
+    hv_message_type_HVMSG_X64_HALT, hv_message_type_HVMSG_X64_IO_PORT_INTERCEPT, hv_register_assoc,
     hv_register_name_HV_X64_REGISTER_RIP, hv_register_value, mshv_user_mem_region,
-    FloatingPointUnit, SegmentRegister, SpecialRegisters, StandardRegisters,
+    FloatingPointUnit, SegmentRegister, SpecialRegisters, StandardRegisters, XSave,
 };
 use mshv_ioctls::{Mshv, VcpuFd, VmFd};
 use tracing::{instrument, Span};
 
+use super::cpuid::{CpuidEntry, CpuidOptions};
+use super::crashdump::walk_frame_pointers;
 use super::fpu::{FP_CONTROL_WORD_DEFAULT, FP_TAG_WORD_DEFAULT, MXCSR_DEFAULT};
 use super::handlers::{MemAccessHandlerWrapper, OutBHandlerWrapper};
 use super::{
@@ -66,6 +69,7 @@ pub(super) struct HypervLinuxDriver {
     entrypoint: u64,
     mem_regions: Vec<MemoryRegion>,
     orig_rsp: GuestPtr,
+    saved_fpu_states: Vec<XSave>,
 }
 
 impl HypervLinuxDriver {
@@ -83,6 +87,7 @@ impl HypervLinuxDriver {
         entrypoint_ptr: GuestPtr,
         rsp_ptr: GuestPtr,
         pml4_ptr: GuestPtr,
+        cpuid_options: Option<&CpuidOptions>,
     ) -> Result<Self> {
         let mshv = Mshv::new()?;
         let pr = Default::default();
@@ -95,6 +100,17 @@ impl HypervLinuxDriver {
         })?;
 
         Self::setup_initial_sregs(&mut vcpu_fd, pml4_ptr.absolute()?)?;
+        if let Some(options) = cpuid_options {
+            Self::setup_cpuid(&vcpu_fd, options)?;
+        }
+
+        vcpu_fd.set_fpu(&FloatingPointUnit {
+            fcw: FP_CONTROL_WORD_DEFAULT,
+            ftwx: FP_TAG_WORD_DEFAULT,
+            mxcsr: MXCSR_DEFAULT,
+            ..Default::default() // zero out the rest
+        })?;
+        let saved_fpu_state = vcpu_fd.get_xsave()?;
 
         Ok(Self {
             _mshv: mshv,
@@ -103,9 +119,48 @@ impl HypervLinuxDriver {
             mem_regions,
             entrypoint: entrypoint_ptr.absolute()?,
             orig_rsp: rsp_ptr,
+            saved_fpu_states: vec![saved_fpu_state],
         })
     }
 
+    /// Override the CPUID leaves `options` modifies. mshv doesn't report
+    /// the leaves it presents to the guest, so the host's are used as the
+    /// starting point instead.
+    #[instrument(err(Debug), skip_all, parent = Span::current(), level = "Trace")]
+    fn setup_cpuid(vcpu: &VcpuFd, options: &CpuidOptions) -> Result<()> {
+        let host: Vec<CpuidEntry> = [(1, 0), (7, 0), (0xd, 0)]
+            .into_iter()
+            .map(|(function, index)| {
+                #[allow(unused_unsafe)]
+                let result = unsafe { __cpuid_count(function, index) };
+                CpuidEntry {
+                    function,
+                    index,
+                    eax: result.eax,
+                    ebx: result.ebx,
+                    ecx: result.ecx,
+                    edx: result.edx,
+                }
+            })
+            .collect();
+        let mut entries = host.clone();
+        options.apply(&mut entries);
+
+        for entry in entries.iter().filter(|entry| !host.contains(entry)) {
+            let entry = hv_cpuid_entry {
+                function: entry.function,
+                index: entry.index,
+                eax: entry.eax,
+                ebx: entry.ebx,
+                ecx: entry.ecx,
+                edx: entry.edx,
+                ..Default::default()
+            };
+            vcpu.register_intercept_result_cpuid_entry(&entry, Some(1), Some(1))?;
+        }
+        Ok(())
+    }
+
     #[instrument(err(Debug), skip_all, parent = Span::current(), level = "Trace")]
     fn setup_initial_sregs(vcpu: &mut VcpuFd, pml4_addr: u64) -> Result<()> {
         let sregs = SpecialRegisters {
@@ -171,6 +226,7 @@ impl Hypervisor for HypervLinuxDriver {
         mem_access_hdl: MemAccessHandlerWrapper,
         hv_handler: Option<HypervisorHandler>,
     ) -> Result<()> {
+        self.restore_fpu_state()?;
         let regs = StandardRegisters {
             rip: self.entrypoint,
             rsp: self.orig_rsp.absolute()?,
@@ -192,6 +248,7 @@ impl Hypervisor for HypervLinuxDriver {
             outb_hdl,
             mem_access_hdl,
         )?;
+        self.save_fpu_state()?;
 
         // reset RSP to what it was before initialise
         self.vcpu_fd.set_regs(&StandardRegisters {
@@ -220,14 +277,7 @@ impl Hypervisor for HypervLinuxDriver {
         };
         self.vcpu_fd.set_regs(&regs)?;
 
-        // reset fpu state
-        let fpu = FloatingPointUnit {
-            fcw: FP_CONTROL_WORD_DEFAULT,
-            ftwx: FP_TAG_WORD_DEFAULT,
-            mxcsr: MXCSR_DEFAULT,
-            ..Default::default() // zero out the rest
-        };
-        self.vcpu_fd.set_fpu(&fpu)?;
+        self.restore_fpu_state()?;
 
         // run
         VirtualCPU::run(
@@ -272,6 +322,31 @@ impl Hypervisor for HypervLinuxDriver {
         Ok(())
     }
 
+    #[instrument(err(Debug), skip_all, parent = Span::current(), level = "Trace")]
+    fn save_fpu_state(&mut self) -> Result<()> {
+        self.saved_fpu_states.push(self.vcpu_fd.get_xsave()?);
+        Ok(())
+    }
+
+    #[instrument(err(Debug), skip_all, parent = Span::current(), level = "Trace")]
+    fn restore_fpu_state(&mut self) -> Result<()> {
+        let state = self
+            .saved_fpu_states
+            .last()
+            .ok_or_else(|| new_error!("No saved FPU state to restore"))?;
+        self.vcpu_fd.set_xsave(state)?;
+        Ok(())
+    }
+
+    #[instrument(err(Debug), skip_all, parent = Span::current(), level = "Trace")]
+    fn discard_fpu_state(&mut self) -> Result<()> {
+        if self.saved_fpu_states.len() <= 1 {
+            log_then_return!("No saved FPU state to discard");
+        }
+        self.saved_fpu_states.pop();
+        Ok(())
+    }
+
     #[instrument(err(Debug), skip_all, parent = Span::current(), level = "Trace")]
     fn run(&mut self) -> Result<super::HyperlightExit> {
         const HALT_MESSAGE: hv_message_type = hv_message_type_HVMSG_X64_HALT;
@@ -279,6 +354,8 @@ impl Hypervisor for HypervLinuxDriver {
             hv_message_type_HVMSG_X64_IO_PORT_INTERCEPT;
         const UNMAPPED_GPA_MESSAGE: hv_message_type = hv_message_type_HVMSG_UNMAPPED_GPA;
         const INVALID_GPA_ACCESS_MESSAGE: hv_message_type = hv_message_type_HVMSG_GPA_INTERCEPT;
+        const UNRECOVERABLE_EXCEPTION_MESSAGE: hv_message_type =
+            hv_message_type_HVMSG_UNRECOVERABLE_EXCEPTION;
 
         let hv_message: hv_message = Default::default();
         let result = match &self.vcpu_fd.run(hv_message) {
@@ -329,6 +406,10 @@ impl Hypervisor for HypervLinuxDriver {
                         None => HyperlightExit::Mmio(gpa),
                     }
                 }
+                UNRECOVERABLE_EXCEPTION_MESSAGE => {
+                    crate::debug!("mshv - Unrecoverable Exception Details : {:#?}", &self);
+                    HyperlightExit::Shutdown()
+                }
                 other => {
                     crate::debug!("mshv Other Exit: Exit: {:#?} \n {:#?}", other, &self);
                     log_then_return!("unknown Hyper-V run message type {:?}", other);
@@ -347,6 +428,14 @@ impl Hypervisor for HypervLinuxDriver {
         Ok(result)
     }
 
+    #[instrument(err(Debug), skip_all, parent = Span::current(), level = "Trace")]
+    fn backtrace(&self) -> Result<Vec<u64>> {
+        let regs = self.vcpu_fd.get_regs()?;
+        let mut backtrace = vec![regs.rip];
+        backtrace.extend(walk_frame_pointers(&self.mem_regions, regs.rbp));
+        Ok(backtrace)
+    }
+
     #[instrument(skip_all, parent = Span::current(), level = "Trace")]
     fn as_mut_hypervisor(&mut self) -> &mut dyn Hypervisor {
         self as &mut dyn Hypervisor
@@ -480,6 +569,7 @@ mod tests {
             MemoryRegionFlags::READ | MemoryRegionFlags::WRITE | MemoryRegionFlags::EXECUTE,
             crate::mem::memory_region::MemoryRegionType::Code,
         );
-        super::HypervLinuxDriver::new(regions.build(), entrypoint_ptr, rsp_ptr, pml4_ptr).unwrap();
+        super::HypervLinuxDriver::new(regions.build(), entrypoint_ptr, rsp_ptr, pml4_ptr, None)
+            .unwrap();
     }
 }
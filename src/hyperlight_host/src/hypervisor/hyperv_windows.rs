@@ -28,6 +28,7 @@ use windows::Win32::System::Hypervisor::{
     WHV_RUN_VP_EXIT_REASON, WHV_X64_SEGMENT_REGISTER, WHV_X64_SEGMENT_REGISTER_0,
 };
 
+use super::crashdump::walk_frame_pointers;
 use super::fpu::{FP_TAG_WORD_DEFAULT, MXCSR_DEFAULT};
 use super::handlers::{MemAccessHandlerWrapper, OutBHandlerWrapper};
 use super::surrogate_process::SurrogateProcess;
@@ -55,6 +56,7 @@ pub(crate) struct HypervWindowsDriver {
     entrypoint: u64,
     orig_rsp: GuestPtr,
     mem_regions: Vec<MemoryRegion>,
+    saved_fpu_states: Vec<Vec<u8>>,
 }
 /* This does not automatically impl Send/Sync because the host
  * address of the shared memory region is a raw pointer, which are
@@ -89,6 +91,14 @@ impl HypervWindowsDriver {
         let mut proc = VMProcessor::new(partition)?;
         Self::setup_initial_sregs(&mut proc, pml4_address)?;
 
+        proc.set_fpu(&WHvFPURegisters {
+            fp_control_word: FP_CONTROL_WORD_DEFAULT,
+            fp_tag_word: FP_TAG_WORD_DEFAULT,
+            mxcsr: MXCSR_DEFAULT,
+            ..Default::default() // zero out the rest
+        })?;
+        let saved_fpu_state = proc.get_xsave()?;
+
         // subtract 2 pages for the guard pages, since when we copy memory to and from surrogate process,
         // we don't want to copy the guard pages themselves (that would cause access violation)
         let mem_size = raw_size - 2 * PAGE_SIZE_USIZE;
@@ -100,6 +110,7 @@ impl HypervWindowsDriver {
             entrypoint,
             orig_rsp: GuestPtr::try_from(RawPtr::from(rsp))?,
             mem_regions,
+            saved_fpu_states: vec![saved_fpu_state],
         })
     }
 
@@ -307,6 +318,7 @@ impl Hypervisor for HypervWindowsDriver {
         mem_access_hdl: MemAccessHandlerWrapper,
         hv_handler: Option<HypervisorHandler>,
     ) -> Result<()> {
+        self.restore_fpu_state()?;
         let regs = WHvGeneralRegisters {
             rip: self.entrypoint,
             rsp: self.orig_rsp.absolute()?,
@@ -328,6 +340,7 @@ impl Hypervisor for HypervWindowsDriver {
             outb_hdl,
             mem_access_hdl,
         )?;
+        self.save_fpu_state()?;
 
         // reset RSP to what it was before initialise
         self.processor
@@ -356,13 +369,7 @@ impl Hypervisor for HypervWindowsDriver {
         };
         self.processor.set_general_purpose_registers(&regs)?;
 
-        // reset fpu state
-        self.processor.set_fpu(&WHvFPURegisters {
-            fp_control_word: FP_CONTROL_WORD_DEFAULT,
-            fp_tag_word: FP_TAG_WORD_DEFAULT,
-            mxcsr: MXCSR_DEFAULT,
-            ..Default::default() // zero out the rest
-        })?;
+        self.restore_fpu_state()?;
 
         VirtualCPU::run(
             self.as_mut_hypervisor(),
@@ -400,6 +407,30 @@ impl Hypervisor for HypervWindowsDriver {
         self.processor.set_general_purpose_registers(&regs)
     }
 
+    #[instrument(err(Debug), skip_all, parent = Span::current(), level = "Trace")]
+    fn save_fpu_state(&mut self) -> Result<()> {
+        self.saved_fpu_states.push(self.processor.get_xsave()?);
+        Ok(())
+    }
+
+    #[instrument(err(Debug), skip_all, parent = Span::current(), level = "Trace")]
+    fn restore_fpu_state(&mut self) -> Result<()> {
+        let state = self
+            .saved_fpu_states
+            .last()
+            .ok_or_else(|| new_error!("No saved FPU state to restore"))?;
+        self.processor.set_xsave(state)
+    }
+
+    #[instrument(err(Debug), skip_all, parent = Span::current(), level = "Trace")]
+    fn discard_fpu_state(&mut self) -> Result<()> {
+        if self.saved_fpu_states.len() <= 1 {
+            log_then_return!("No saved FPU state to discard");
+        }
+        self.saved_fpu_states.pop();
+        Ok(())
+    }
+
     #[instrument(err(Debug), skip_all, parent = Span::current(), level = "Trace")]
     fn run(&mut self) -> Result<super::HyperlightExit> {
         let bytes_written: Option<*mut usize> = None;
@@ -500,6 +531,17 @@ impl Hypervisor for HypervWindowsDriver {
                     None => HyperlightExit::Mmio(gpa),
                 }
             }
+            // WHvRunVpExitReasonUnrecoverableException
+            WHV_RUN_VP_EXIT_REASON(4i32) => {
+                debug!("HyperV Unrecoverable Exception Details :\n {:#?}", &self);
+                HyperlightExit::Shutdown()
+            }
+            // WHvRunVpExitReasonInvalidVpRegisterValue
+            // The vCPU could not be entered because of its register state
+            WHV_RUN_VP_EXIT_REASON(5i32) => {
+                debug!("HyperV Invalid VP Register Value Details :\n {:#?}", &self);
+                HyperlightExit::FailEntry(exit_context.ExitReason.0 as u64)
+            }
             //  WHvRunVpExitReasonCanceled
             //  Execution was cancelled by the host.
             //  This will happen when guest code runs for too long
@@ -526,6 +568,14 @@ impl Hypervisor for HypervWindowsDriver {
         self.processor.get_partition_hdl()
     }
 
+    #[instrument(err(Debug), skip_all, parent = Span::current(), level = "Trace")]
+    fn backtrace(&self) -> Result<Vec<u64>> {
+        let regs = self.processor.get_regs()?;
+        let mut backtrace = vec![regs.rip];
+        backtrace.extend(walk_frame_pointers(&self.mem_regions, regs.rbp));
+        Ok(backtrace)
+    }
+
     #[instrument(skip_all, parent = Span::current(), level = "Trace")]
     fn as_mut_hypervisor(&mut self) -> &mut dyn Hypervisor {
         self as &mut dyn Hypervisor
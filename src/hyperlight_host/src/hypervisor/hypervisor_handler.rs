@@ -21,7 +21,7 @@ use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::{Arc, Mutex};
 use std::thread;
 use std::thread::{sleep, JoinHandle};
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
 #[cfg(target_os = "linux")]
 use crossbeam::atomic::AtomicCell;
@@ -35,18 +35,30 @@ use vmm_sys_util::signal::SIGRTMIN;
 #[cfg(target_os = "windows")]
 use windows::Win32::System::Hypervisor::{WHvCancelRunVirtualProcessor, WHV_PARTITION_HANDLE};
 
+use crate::func::call_options::{ProgressCallback, ProgressReport};
 #[cfg(feature = "function_call_metrics")]
 use crate::histogram_vec_observe;
+use crate::hypervisor::cpuid::CpuidOptions;
 use crate::hypervisor::handlers::{MemAccessHandlerWrapper, OutBHandlerWrapper};
+use crate::hypervisor::sampling::SamplingProfiler;
+use crate::hypervisor::time::{host_tsc_khz, TimeOptions};
+use crate::hypervisor::vcpu_stats::{VcpuStats, VcpuStatsSource};
+#[cfg(target_arch = "x86_64")]
+use crate::hypervisor::DebugRegisters;
 use crate::hypervisor::Hypervisor;
 use crate::mem::layout::SandboxMemoryLayout;
 use crate::mem::mgr::SandboxMemoryManager;
 use crate::mem::ptr::{GuestPtr, RawPtr};
 use crate::mem::ptr_offset::Offset;
 use crate::mem::shared_mem::{GuestSharedMemory, HostSharedMemory, SharedMemory};
+#[cfg(all(feature = "cgroup", target_os = "linux"))]
+use crate::sandbox::cgroup::{CgroupUsage, SandboxCgroup};
+use crate::sandbox::events::SandboxEvents;
 use crate::sandbox::hypervisor::{get_available_hypervisor, HypervisorType};
 #[cfg(feature = "function_call_metrics")]
 use crate::sandbox::metrics::SandboxMetric::GuestFunctionCallDurationMicroseconds;
+use crate::sandbox::redaction::RedactionPolicy;
+use crate::sandbox::{CreationReport, GuestInfo, GuestProfile, SandboxId};
 #[cfg(target_os = "linux")]
 use crate::signal_handlers::setup_signal_handlers;
 use crate::HyperlightError::{
@@ -65,9 +77,82 @@ pub(crate) struct HypervisorHandler {
     communication_channels: HvHandlerCommChannels,
     configuration: HvHandlerConfig,
     execution_variables: HvHandlerExecVars,
+    /// Called with the progress the guest reports during the call in
+    /// progress, see `CallOptions::on_progress`
+    on_progress: Option<ProgressCallback>,
 }
 
 impl HypervisorHandler {
+    /// The `GuestInfo` the handler was configured with
+    pub(crate) fn guest_info(&self) -> &GuestInfo {
+        &self.configuration.guest_info
+    }
+
+    /// The ID of the sandbox running in the VM
+    pub(crate) fn sandbox_id(&self) -> SandboxId {
+        self.configuration.sandbox_id
+    }
+
+    /// The redaction the crash dumps of this handler's vCPU use
+    pub(crate) fn redaction(&self) -> RedactionPolicy {
+        self.configuration.redaction
+    }
+
+    /// The statistics the hypervisor keeps about the vCPU, or `None` if
+    /// the hypervisor doesn't provide any
+    pub(crate) fn vcpu_stats(&self) -> Result<Option<VcpuStats>> {
+        self.execution_variables
+            .get_vcpu_stats()?
+            .map(|source| source.read())
+            .transpose()
+    }
+
+    /// What the threads in the sandbox's cgroup have used, or `None` if it
+    /// wasn't placed in one
+    #[cfg(all(feature = "cgroup", target_os = "linux"))]
+    pub(crate) fn cgroup_usage(&self) -> Result<Option<CgroupUsage>> {
+        self.configuration
+            .cgroup
+            .as_ref()
+            .map(|cgroup| cgroup.usage())
+            .transpose()
+    }
+
+    /// The subscribers to the events of the sandbox running in the VM
+    pub(crate) fn events(&self) -> &SandboxEvents {
+        &self.configuration.events
+    }
+
+    /// How long each stage of creating the sandbox took, so far
+    pub(crate) fn creation_report(&self) -> Result<CreationReport> {
+        self.configuration
+            .creation_report
+            .lock()
+            .map(|report| *report)
+            .map_err(|e| new_error!("Error locking at {}:{}: {}", file!(), line!(), e))
+    }
+
+    /// How long a guest function call can run for before it is cancelled
+    pub(crate) fn max_exec_time(&self) -> Duration {
+        self.configuration.max_exec_time
+    }
+
+    /// Change how long guest function calls can run for before they are
+    /// cancelled
+    pub(crate) fn set_max_exec_time(&mut self, max_exec_time: Duration) {
+        self.configuration.max_exec_time = max_exec_time;
+    }
+
+    /// The frequency of the guest's TSC in kHz, 0 if it isn't known
+    pub(crate) fn guest_tsc_khz(&self) -> u64 {
+        match self.configuration.time_options.as_ref() {
+            Some(TimeOptions {
+                tsc_khz: Some(khz), ..
+            }) => *khz as u64,
+            _ => host_tsc_khz(),
+        }
+    }
+
     pub(crate) fn set_running(&self, running: bool) {
         self.execution_variables
             .running
@@ -78,6 +163,65 @@ impl HypervisorHandler {
     pub(crate) fn set_run_cancelled(&self, run_cancelled: bool) {
         self.execution_variables.run_cancelled.store(run_cancelled);
     }
+
+    /// Whether the host has cancelled the execution in progress, rather
+    /// than the vCPU having been interrupted for some other reason
+    #[cfg(target_os = "linux")]
+    pub(crate) fn cancel_requested(&self) -> bool {
+        self.execution_variables
+            .cancel_requested
+            .load(Ordering::SeqCst)
+    }
+
+    /// Start interrupting the vCPU every `interval` to sample where the
+    /// guest is, replacing any sampling already in progress
+    #[cfg(target_os = "linux")]
+    pub(crate) fn start_sampling(&self, interval: Duration, stack_depth: usize) -> Result<()> {
+        let running = self.execution_variables.running.clone();
+        let thread_id = self.execution_variables.thread_id.clone();
+        let sampler = SamplingProfiler::start(interval, stack_depth, move || {
+            if !running.load(Ordering::SeqCst) {
+                return;
+            }
+            let Some(thread_id) = thread_id.lock().ok().and_then(|thread_id| *thread_id) else {
+                return;
+            };
+            // the signal makes the vCPU exit if it is running the guest, and
+            // is ignored otherwise
+            unsafe { pthread_kill(thread_id, SIGRTMIN()) };
+        })?;
+        *self
+            .execution_variables
+            .sampler
+            .lock()
+            .map_err(|_| new_error!("Failed to start_sampling"))? = Some(sampler);
+        Ok(())
+    }
+
+    #[cfg(target_os = "windows")]
+    pub(crate) fn start_sampling(&self, _interval: Duration, _stack_depth: usize) -> Result<()> {
+        log_then_return!("The sampling profiler is only supported on Linux");
+    }
+
+    /// Stop sampling, returning the samples that haven't been taken yet
+    pub(crate) fn stop_sampling(&self) -> Result<Option<GuestProfile>> {
+        Ok(self
+            .execution_variables
+            .sampler
+            .lock()
+            .map_err(|_| new_error!("Failed to stop_sampling"))?
+            .take()
+            .map(|sampler| sampler.take_profile()))
+    }
+
+    /// The sampling profiler, if sampling has been started
+    pub(crate) fn sampler(&self) -> Option<Arc<SamplingProfiler>> {
+        self.execution_variables
+            .sampler
+            .lock()
+            .ok()
+            .and_then(|sampler| sampler.clone())
+    }
 }
 
 // Note: `join_handle` and `running` have to be `Arc` because we need
@@ -97,6 +241,12 @@ struct HvHandlerExecVars {
     running: Arc<AtomicBool>,
     #[cfg(target_os = "linux")]
     run_cancelled: Arc<crossbeam::atomic::AtomicCell<bool>>,
+    /// Set when the host cancels execution, so that the vCPU can tell a
+    /// cancellation from an interrupt by the sampling profiler
+    #[cfg(target_os = "linux")]
+    cancel_requested: Arc<AtomicBool>,
+    vcpu_stats: Arc<Mutex<Option<Arc<dyn VcpuStatsSource>>>>,
+    sampler: Arc<Mutex<Option<Arc<SamplingProfiler>>>>,
 }
 
 impl HvHandlerExecVars {
@@ -147,6 +297,23 @@ impl HvHandlerExecVars {
             .map_err(|_| new_error!("Failed to get_partition_handle"))?)
     }
 
+    fn set_vcpu_stats(&mut self, vcpu_stats: Option<Arc<dyn VcpuStatsSource>>) -> Result<()> {
+        *self
+            .vcpu_stats
+            .try_lock()
+            .map_err(|_| new_error!("Failed to set_vcpu_stats"))? = vcpu_stats;
+
+        Ok(())
+    }
+
+    fn get_vcpu_stats(&self) -> Result<Option<Arc<dyn VcpuStatsSource>>> {
+        Ok(self
+            .vcpu_stats
+            .lock()
+            .map_err(|_| new_error!("Failed to get_vcpu_stats"))?
+            .clone())
+    }
+
     fn set_timeout(&mut self, timeout: Duration) -> Result<()> {
         *self
             .timeout
@@ -170,6 +337,8 @@ struct HvHandlerCommChannels {
     to_handler_rx: HypervisorHandlerRx,
     from_handler_tx: HandlerMsgTx,
     from_handler_rx: HandlerMsgRx,
+    progress_tx: Sender<ProgressReport>,
+    progress_rx: Receiver<ProgressReport>,
 }
 
 #[derive(Clone)]
@@ -183,6 +352,30 @@ pub(crate) struct HvHandlerConfig {
     pub(crate) outb_handler: OutBHandlerWrapper,
     pub(crate) mem_access_handler: MemAccessHandlerWrapper,
     pub(crate) max_wait_for_cancellation: Duration,
+    /// When the action being executed times out, for handlers the guest
+    /// waits on, such as a full guest log queue, to give up by
+    pub(crate) call_deadline: Arc<Mutex<Option<Instant>>>,
+    /// How long threads waiting on messages spin for before they block,
+    /// see `SandboxConfiguration::set_latency_profile`
+    pub(crate) busy_poll_window: Option<Duration>,
+    pub(crate) cpuid_options: Option<CpuidOptions>,
+    pub(crate) time_options: Option<TimeOptions>,
+    /// The CPUs the handler thread may run on, empty if it may run on any
+    #[cfg(target_os = "linux")]
+    pub(crate) cpu_affinity: Vec<usize>,
+    /// The cgroup the handler thread is placed in, if any
+    #[cfg(all(feature = "cgroup", target_os = "linux"))]
+    pub(crate) cgroup: Option<Arc<SandboxCgroup>>,
+    /// Taken from the `UninitializedSandbox` the handler was created for
+    pub(crate) redaction: RedactionPolicy,
+    pub(crate) guest_info: GuestInfo,
+    pub(crate) sandbox_id: SandboxId,
+    pub(crate) sandbox_name: Option<String>,
+    pub(crate) events: SandboxEvents,
+    /// How long each stage of creating the sandbox took, the ones after
+    /// loading the guest binary filled in by the handler thread when it
+    /// initialises the guest
+    pub(crate) creation_report: Arc<Mutex<CreationReport>>,
 }
 
 impl HypervisorHandler {
@@ -191,12 +384,15 @@ impl HypervisorHandler {
     pub(crate) fn new(configuration: HvHandlerConfig) -> Self {
         let (to_handler_tx, to_handler_rx) = crossbeam_channel::unbounded();
         let (from_handler_tx, from_handler_rx) = crossbeam_channel::unbounded();
+        let (progress_tx, progress_rx) = crossbeam_channel::unbounded();
 
         let communication_channels = HvHandlerCommChannels {
             to_handler_tx,
             to_handler_rx,
             from_handler_tx,
             from_handler_rx,
+            progress_tx,
+            progress_rx,
         };
 
         let execution_variables = HvHandlerExecVars {
@@ -209,16 +405,34 @@ impl HypervisorHandler {
             running: Arc::new(AtomicBool::new(false)),
             #[cfg(target_os = "linux")]
             run_cancelled: Arc::new(AtomicCell::new(false)),
+            #[cfg(target_os = "linux")]
+            cancel_requested: Arc::new(AtomicBool::new(false)),
             timeout: Arc::new(Mutex::new(configuration.max_init_time)),
+            vcpu_stats: Arc::new(Mutex::new(None)),
+            sampler: Arc::new(Mutex::new(None)),
         };
 
         Self {
             communication_channels,
             configuration,
             execution_variables,
+            on_progress: None,
         }
     }
 
+    /// Where the host functions the guest calls send the progress it
+    /// reports, for the thread waiting on the handler to pass on to
+    /// `on_progress`
+    pub(crate) fn progress_sender(&self) -> Sender<ProgressReport> {
+        self.communication_channels.progress_tx.clone()
+    }
+
+    /// Set what the progress the guest reports is passed to while the
+    /// handler is waited on, until it is set again
+    pub(crate) fn set_progress_callback(&mut self, on_progress: Option<ProgressCallback>) {
+        self.on_progress = on_progress;
+    }
+
     /// Sets up a Hypervisor 'handler', designed to listen to messages to execute a specific action,
     /// such as:
     /// - `initialise` resources,
@@ -283,17 +497,42 @@ impl HypervisorHandler {
                 .name("Hypervisor Handler".to_string())
                 .spawn(move || -> Result<()> {
                     let mut hv: Option<Box<dyn Hypervisor>> = None;
-                    for action in to_handler_rx {
+                    let next_action = || {
+                        busy_poll(configuration.busy_poll_window, || to_handler_rx.try_recv().ok())
+                            .or_else(|| to_handler_rx.recv().ok())
+                    };
+                    while let Some(action) = next_action() {
                         match action {
                             HypervisorHandlerAction::Initialise => {
+                                #[cfg(target_os = "linux")]
+                                if !configuration.cpu_affinity.is_empty() {
+                                    set_thread_affinity(&configuration.cpu_affinity)?;
+                                }
+                                #[cfg(all(feature = "cgroup", target_os = "linux"))]
+                                if let Some(cgroup) = &configuration.cgroup {
+                                    cgroup.add_current_thread()?;
+                                }
+                                let mut creation_report = *configuration
+                                    .creation_report
+                                    .lock()
+                                    .map_err(|e| new_error!("Error locking at {}:{}: {}", file!(), line!(), e))?;
                                 {
+                                    let start = Instant::now();
                                     hv = Some(set_up_hypervisor_partition(
                                         execution_variables.shm.try_lock().unwrap().deref_mut().as_mut().unwrap(),
                                         configuration.outb_handler.clone(),
+                                        configuration.cpuid_options.as_ref(),
+                                        configuration.time_options.as_ref(),
+                                        &mut creation_report,
                                     )?);
+                                    // the rest of setting up is creating the partition
+                                    creation_report.partition =
+                                        start.elapsed().saturating_sub(creation_report.page_tables);
                                 }
                                 let hv = hv.as_mut().unwrap();
 
+                                execution_variables.set_vcpu_stats(hv.vcpu_stats_source())?;
+
                                 #[cfg(target_os = "windows")]
                                 if !in_process {
                                     execution_variables
@@ -310,6 +549,10 @@ impl HypervisorHandler {
 
                                 #[cfg(target_os = "linux")]
                                 execution_variables.run_cancelled.store(false);
+                                #[cfg(target_os = "linux")]
+                                execution_variables
+                                    .cancel_requested
+                                    .store(false, Ordering::SeqCst);
 
                                 log::info!("Initialising Hypervisor Handler");
 
@@ -331,6 +574,7 @@ impl HypervisorHandler {
                                     .lock
                                     .try_read();
 
+                                let start = Instant::now();
                                 let res = hv.initialise(
                                     configuration.peb_addr.clone(),
                                     configuration.seed,
@@ -339,9 +583,16 @@ impl HypervisorHandler {
                                     configuration.mem_access_handler.clone(),
                                     Some(hv_handler_clone.clone()),
                                 );
+                                creation_report.entrypoint = start.elapsed();
                                 drop(mem_lock_guard);
                                 drop(evar_lock_guard);
 
+                                *configuration
+                                    .creation_report
+                                    .lock()
+                                    .map_err(|e| new_error!("Error locking at {}:{}: {}", file!(), line!(), e))? =
+                                    creation_report;
+
                                 execution_variables.running.store(false, Ordering::SeqCst);
 
                                 match res {
@@ -372,8 +623,17 @@ impl HypervisorHandler {
 
                                 #[cfg(target_os = "linux")]
                                 execution_variables.run_cancelled.store(false);
-
-                                info!("Dispatching call from host: {}", function_name);
+                                #[cfg(target_os = "linux")]
+                                execution_variables
+                                    .cancel_requested
+                                    .store(false, Ordering::SeqCst);
+
+                                let sandbox_name =
+                                    configuration.sandbox_name.as_deref().unwrap_or_default();
+                                info!(
+                                    "Dispatching call from host: {} in sandbox {} {:?}",
+                                    function_name, configuration.sandbox_id, sandbox_name
+                                );
 
                                 let dispatch_function_addr = configuration
                                     .dispatch_function_addr
@@ -463,6 +723,45 @@ impl HypervisorHandler {
                                     }
                                 }
                             }
+                            HypervisorHandlerAction::SaveFpuState
+                            | HypervisorHandlerAction::DiscardFpuState => {
+                                let hv = hv.as_mut().unwrap();
+                                let res = match action {
+                                    HypervisorHandlerAction::SaveFpuState => hv.save_fpu_state(),
+                                    _ => hv.discard_fpu_state(),
+                                };
+                                let msg = match res {
+                                    Ok(()) => HandlerMsg::FinishedHypervisorHandlerAction,
+                                    Err(e) => HandlerMsg::Error(e),
+                                };
+                                from_handler_tx.send(msg).map_err(|_| {
+                                    HyperlightError::HypervisorHandlerCommunicationFailure()
+                                })?;
+                            }
+                            #[cfg(target_arch = "x86_64")]
+                            HypervisorHandlerAction::SetDebugRegisters(regs) => {
+                                let msg = match hv.as_mut().unwrap().set_debug_registers(&regs) {
+                                    Ok(()) => HandlerMsg::FinishedHypervisorHandlerAction,
+                                    Err(e) => HandlerMsg::Error(e),
+                                };
+                                from_handler_tx.send(msg).map_err(|_| {
+                                    HyperlightError::HypervisorHandlerCommunicationFailure()
+                                })?;
+                            }
+                            #[cfg(target_arch = "x86_64")]
+                            HypervisorHandlerAction::GetDebugRegisters(regs_tx) => {
+                                let msg = match hv.as_mut().unwrap().debug_registers() {
+                                    Ok(regs) => {
+                                        // the receiver waits for the message that follows
+                                        let _ = regs_tx.send(regs);
+                                        HandlerMsg::FinishedHypervisorHandlerAction
+                                    }
+                                    Err(e) => HandlerMsg::Error(e),
+                                };
+                                from_handler_tx.send(msg).map_err(|_| {
+                                    HyperlightError::HypervisorHandlerCommunicationFailure()
+                                })?;
+                            }
                             HypervisorHandlerAction::TerminateHandlerThread => {
                                 info!("Terminating Hypervisor Handler Thread");
                                 break;
@@ -556,6 +855,15 @@ impl HypervisorHandler {
             HypervisorHandlerAction::DispatchCallFromHost(_) => self
                 .execution_variables
                 .set_timeout(self.configuration.max_exec_time)?,
+            HypervisorHandlerAction::SaveFpuState | HypervisorHandlerAction::DiscardFpuState => {
+                self.execution_variables
+                    .set_timeout(self.configuration.max_init_time)?
+            }
+            #[cfg(target_arch = "x86_64")]
+            HypervisorHandlerAction::SetDebugRegisters(_)
+            | HypervisorHandlerAction::GetDebugRegisters(_) => self
+                .execution_variables
+                .set_timeout(self.configuration.max_init_time)?,
             HypervisorHandlerAction::TerminateHandlerThread => self
                 .execution_variables
                 .set_timeout(self.configuration.max_init_time)?,
@@ -563,6 +871,12 @@ impl HypervisorHandler {
             // for completion of the match statement, and it is not really needed for
             // `TerminateHandlerThread`.
         }
+        *self
+            .configuration
+            .call_deadline
+            .lock()
+            .map_err(|e| new_error!("Error locking at {}:{}: {}", file!(), line!(), e))? =
+            Some(Instant::now() + self.execution_variables.get_timeout()?);
 
         self.communication_channels
             .to_handler_tx
@@ -583,16 +897,22 @@ impl HypervisorHandler {
     /// and still have to receive after sorting that out without sending
     /// an extra message.
     pub(crate) fn try_receive_handler_msg(&self) -> Result<()> {
-        match self
-            .communication_channels
-            .from_handler_rx
-            .recv_timeout(self.execution_variables.get_timeout()?)
-        {
-            Ok(msg) => match msg {
+        let from_handler_rx = &self.communication_channels.from_handler_rx;
+        let timeout = self.execution_variables.get_timeout()?;
+        let msg = busy_poll(self.configuration.busy_poll_window, || {
+            from_handler_rx.try_recv().ok()
+        })
+        .or_else(|| self.recv_handler_msg(timeout));
+        // the guest may have reported progress just before it returned
+        for (percent, message) in self.communication_channels.progress_rx.try_iter() {
+            self.report_progress(percent, &message);
+        }
+        match msg {
+            Some(msg) => match msg {
                 HandlerMsg::Error(e) => Err(e),
                 HandlerMsg::FinishedHypervisorHandlerAction => Ok(()),
             },
-            Err(_) => {
+            None => {
                 // If we have timed out it may be that the handler thread returned an error before it sent a message, so rather than just timeout here
                 // we will try and get the join handle for the thread and if it has finished check to see if it returned an error
                 // if it did then we will return that error, otherwise we will return the timeout error
@@ -618,10 +938,34 @@ impl HypervisorHandler {
         }
     }
 
+    /// Wait up to `timeout` for a message from the Hypervisor Handler
+    /// Thread, passing the progress the guest reports in the meantime to
+    /// `on_progress` on this thread
+    fn recv_handler_msg(&self, timeout: Duration) -> Option<HandlerMsg> {
+        let deadline = Instant::now() + timeout;
+        loop {
+            crossbeam_channel::select! {
+                recv(self.communication_channels.from_handler_rx) -> msg => return msg.ok(),
+                recv(self.communication_channels.progress_rx) -> report => {
+                    if let Ok((percent, message)) = report {
+                        self.report_progress(percent, &message);
+                    }
+                }
+                default(deadline.saturating_duration_since(Instant::now())) => return None,
+            }
+        }
+    }
+
+    fn report_progress(&self, percent: u32, message: &str) {
+        if let Some(on_progress) = &self.on_progress {
+            on_progress(percent, message);
+        }
+    }
+
     /// Terminate the execution of the hypervisor handler
     ///
     /// This function is intended to be called after a guest function called has
-    /// timed-out (i.e., `from_handler_rx.recv_timeout(timeout).is_err()`).
+    /// timed-out (i.e., `try_receive_handler_msg` got no message in time).
     ///
     /// It is possible that, even after we timed-out, the guest function execution will
     /// finish. If that is the case, this function is fundamentally a NOOP, because it
@@ -658,7 +1002,9 @@ impl HypervisorHandler {
                 // `WHvCancelRunVirtualProcessor` didn't unlock.
 
                 log::info!("Tried to cancel guest execution on host function call");
-                return Err(GuestExecutionHungOnHostFunctionCall());
+                return Err(GuestExecutionHungOnHostFunctionCall(
+                    self.configuration.sandbox_id,
+                ));
             }
         }
 
@@ -714,6 +1060,9 @@ impl HypervisorHandler {
 
         #[cfg(target_os = "linux")]
         {
+            self.execution_variables
+                .cancel_requested
+                .store(true, Ordering::SeqCst);
             let thread_id = self.execution_variables.get_thread_id()?;
             if thread_id == u64::MAX {
                 log_then_return!("Failed to get thread id to signal thread");
@@ -749,7 +1098,9 @@ impl HypervisorHandler {
                 std::thread::sleep(Duration::from_micros(500));
             }
             if !self.execution_variables.run_cancelled.load() {
-                log_then_return!(GuestExecutionHungOnHostFunctionCall());
+                log_then_return!(GuestExecutionHungOnHostFunctionCall(
+                    self.configuration.sandbox_id
+                ));
             }
         }
         #[cfg(target_os = "windows")]
@@ -780,6 +1131,17 @@ pub enum HypervisorHandlerAction {
     Initialise,
     /// Execute a function call (String = name) from the host
     DispatchCallFromHost(String),
+    /// Save the vCPU's FPU state, to go with a new memory snapshot
+    SaveFpuState,
+    /// Discard the last saved FPU state, to go with the memory snapshot
+    /// being discarded
+    DiscardFpuState,
+    /// Set the vCPU's debug registers
+    #[cfg(target_arch = "x86_64")]
+    SetDebugRegisters(DebugRegisters),
+    /// Get the vCPU's debug registers, sending them to the given channel
+    #[cfg(target_arch = "x86_64")]
+    GetDebugRegisters(Sender<DebugRegisters>),
     /// Terminate hypervisor handler thread
     TerminateHandlerThread,
 }
@@ -791,6 +1153,12 @@ impl std::fmt::Debug for HypervisorHandlerAction {
         match self {
             HypervisorHandlerAction::Initialise => write!(f, "Initialise"),
             HypervisorHandlerAction::DispatchCallFromHost(_) => write!(f, "DispatchCallFromHost"),
+            HypervisorHandlerAction::SaveFpuState => write!(f, "SaveFpuState"),
+            HypervisorHandlerAction::DiscardFpuState => write!(f, "DiscardFpuState"),
+            #[cfg(target_arch = "x86_64")]
+            HypervisorHandlerAction::SetDebugRegisters(_) => write!(f, "SetDebugRegisters"),
+            #[cfg(target_arch = "x86_64")]
+            HypervisorHandlerAction::GetDebugRegisters(_) => write!(f, "GetDebugRegisters"),
             HypervisorHandlerAction::TerminateHandlerThread => write!(f, "TerminateHandlerThread"),
         }
     }
@@ -805,15 +1173,60 @@ pub enum HandlerMsg {
     Error(HyperlightError),
 }
 
+/// Call `poll` until it returns a value, for up to `window`, to avoid the
+/// cost of blocking and being woken up when the value is expected soon.
+/// Returns `None` straight away if there is no window.
+pub(crate) fn busy_poll<T>(
+    window: Option<Duration>,
+    mut poll: impl FnMut() -> Option<T>,
+) -> Option<T> {
+    let window = window?;
+    let start = Instant::now();
+    loop {
+        if let Some(value) = poll() {
+            return Some(value);
+        }
+        if start.elapsed() >= window {
+            return None;
+        }
+        std::hint::spin_loop();
+    }
+}
+
+/// Only let the calling thread run on the CPUs in `cpus`
+#[cfg(target_os = "linux")]
+fn set_thread_affinity(cpus: &[usize]) -> Result<()> {
+    let res = unsafe {
+        let mut set: libc::cpu_set_t = std::mem::zeroed();
+        for &cpu in cpus {
+            libc::CPU_SET(cpu, &mut set);
+        }
+        libc::sched_setaffinity(0, std::mem::size_of::<libc::cpu_set_t>(), &set)
+    };
+    if res != 0 {
+        log_then_return!(
+            "Failed to set the CPU affinity of the hypervisor handler thread to {:?}: {}",
+            cpus,
+            std::io::Error::last_os_error()
+        );
+    }
+    Ok(())
+}
+
 fn set_up_hypervisor_partition(
     mgr: &mut SandboxMemoryManager<GuestSharedMemory>,
     #[allow(unused_variables)] // parameter only used for in-process mode
     outb_handler: OutBHandlerWrapper,
+    cpuid_options: Option<&CpuidOptions>,
+    time_options: Option<&TimeOptions>,
+    creation_report: &mut CreationReport,
 ) -> Result<Box<dyn Hypervisor>> {
     let mem_size = u64::try_from(mgr.shared_mem.mem_size())?;
     let mut regions = mgr.layout.get_memory_regions(&mgr.shared_mem)?;
     let rsp_ptr = {
+        let start = Instant::now();
         let rsp_u64 = mgr.set_up_shared_memory(mem_size, &mut regions)?;
+        creation_report.page_tables = start.elapsed();
         let rsp_raw = RawPtr::from(rsp_u64);
         GuestPtr::try_from(rsp_raw)
     }?;
@@ -842,6 +1255,9 @@ fn set_up_hypervisor_partition(
         );
     }
     if mgr.is_in_process() {
+        if cpuid_options.is_some() || time_options.is_some() {
+            log_then_return!("CPUID and time options are not supported in in-process mode");
+        }
         cfg_if::cfg_if! {
             if #[cfg(inprocess)] {
                 // in-process feature + debug build
@@ -868,16 +1284,20 @@ fn set_up_hypervisor_partition(
             }
         }
     } else {
-        match *get_available_hypervisor() {
+        let hv: Box<dyn Hypervisor> = match *get_available_hypervisor() {
             #[cfg(mshv)]
             Some(HypervisorType::Mshv) => {
+                if time_options.is_some() {
+                    log_then_return!("Time options are not supported by mshv");
+                }
                 let hv = crate::hypervisor::hyperv_linux::HypervLinuxDriver::new(
                     regions,
                     entrypoint_ptr,
                     rsp_ptr,
                     pml4_ptr,
+                    cpuid_options,
                 )?;
-                Ok(Box::new(hv))
+                Box::new(hv)
             }
 
             #[cfg(kvm)]
@@ -887,12 +1307,19 @@ fn set_up_hypervisor_partition(
                     pml4_ptr.absolute()?,
                     entrypoint_ptr.absolute()?,
                     rsp_ptr.absolute()?,
+                    cpuid_options,
+                    time_options,
                 )?;
-                Ok(Box::new(hv))
+                Box::new(hv)
             }
 
             #[cfg(target_os = "windows")]
             Some(HypervisorType::Whp) => {
+                if cpuid_options.is_some() {
+                    log_then_return!(
+                        "CPUID options are not supported by Windows Hypervisor Platform"
+                    );
+                }
                 let hv = crate::hypervisor::hyperv_windows::HypervWindowsDriver::new(
                     regions,
                     mgr.shared_mem.raw_mem_size(), // we use raw_* here because windows driver requires 64K aligned addresses,
@@ -901,13 +1328,15 @@ fn set_up_hypervisor_partition(
                     entrypoint_ptr.absolute()?,
                     rsp_ptr.absolute()?,
                 )?;
-                Ok(Box::new(hv))
+                Box::new(hv)
             }
 
             _ => {
                 log_then_return!(NoHypervisorFound());
             }
-        }
+        };
+        mgr.set_outb_transport(hv.outb_transport())?;
+        Ok(hv)
     }
 }
 
@@ -915,6 +1344,7 @@ fn set_up_hypervisor_partition(
 mod tests {
     use std::sync::{Arc, Barrier};
     use std::thread;
+    use std::time::Duration;
 
     use hyperlight_common::flatbuffer_wrappers::function_types::{ParameterValue, ReturnType};
     use hyperlight_testing::simple_guest_as_string;
@@ -994,6 +1424,31 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn sample_spinning_guest() -> Result<()> {
+        let mut sandbox = create_multi_use_sandbox();
+        sandbox.start_sampling_profiler(Duration::from_millis(1), 4)?;
+
+        // the profiler's interrupts don't stop the host from cancelling
+        // execution when it times out
+        let res = sandbox.call_guest_function_by_name("Spin", ReturnType::Void, None);
+        assert!(matches!(
+            res,
+            Err(HyperlightError::ExecutionCanceledByHost())
+        ));
+
+        let profile = sandbox.take_profile()?;
+        assert!(profile.samples > 0);
+        assert!(profile.stacks.keys().all(|stack| !stack.is_empty()));
+        assert_eq!(sandbox.take_profile()?.samples, 0);
+
+        assert!(sandbox.stop_sampling_profiler()?.is_some());
+        assert!(sandbox.take_profile().is_err());
+
+        Ok(())
+    }
+
     #[test]
     fn terminate_execution_then_call_another_function() -> Result<()> {
         let mut sandbox = create_multi_use_sandbox();
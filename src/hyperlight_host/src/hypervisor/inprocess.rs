@@ -117,6 +117,20 @@ impl<'a> Hypervisor for InprocessDriver<'a> {
         unimplemented!("run should not be needed since we are in in-process mode")
     }
 
+    fn save_fpu_state(&mut self) -> crate::Result<()> {
+        // the guest runs on the calling thread, whose FPU state is preserved
+        // across calls by the calling convention
+        Ok(())
+    }
+
+    fn restore_fpu_state(&mut self) -> crate::Result<()> {
+        Ok(())
+    }
+
+    fn discard_fpu_state(&mut self) -> crate::Result<()> {
+        Ok(())
+    }
+
     fn as_mut_hypervisor(&mut self) -> &mut dyn Hypervisor {
         self
     }
@@ -14,19 +14,59 @@ See the License for the specific language governing permissions and
 limitations under the License.
 */
 
+#[cfg(target_arch = "x86_64")]
+use std::arch::x86_64::_rdtsc;
+use std::collections::BTreeMap;
 use std::convert::TryFrom;
 use std::fmt::Debug;
+use std::fs::File;
+#[cfg(target_arch = "aarch64")]
+use std::mem::offset_of;
+use std::os::fd::FromRawFd;
+use std::os::raw::c_ulong;
+use std::os::unix::fs::FileExt;
+use std::sync::Arc;
 
-use kvm_bindings::{kvm_fpu, kvm_regs, kvm_userspace_memory_region, KVM_MEM_READONLY};
+use hyperlight_common::mem::OutBTransport;
+use hyperlight_common::outb::{doorbell_port, MMIO_HALT_PORT};
+#[cfg(target_arch = "x86_64")]
+use kvm_bindings::{
+    kvm_cpuid_entry2, kvm_debugregs, kvm_fpu, kvm_guest_debug, kvm_guest_debug_arch, kvm_msr_entry,
+    kvm_regs, kvm_xsave, CpuId, Msrs, KVM_GUESTDBG_ENABLE, KVM_GUESTDBG_USE_HW_BP,
+    KVM_MAX_CPUID_ENTRIES,
+};
+#[cfg(target_arch = "aarch64")]
+use kvm_bindings::{
+    kvm_regs, kvm_vcpu_init, user_fpsimd_state, user_pt_regs, KVM_ARM_VCPU_PSCI_0_2,
+    KVM_EXIT_UNKNOWN, KVM_REG_ARM64, KVM_REG_ARM64_SYSREG, KVM_REG_ARM_CORE, KVM_REG_SIZE_U128,
+    KVM_REG_SIZE_U32, KVM_REG_SIZE_U64,
+};
+use kvm_bindings::{
+    kvm_userspace_memory_region, KVMIO, KVM_MEM_READONLY, KVM_STATS_TYPE_LINEAR_HIST,
+    KVM_STATS_TYPE_LOG_HIST, KVM_STATS_TYPE_MASK,
+};
 use kvm_ioctls::Cap::UserMemory;
 use kvm_ioctls::{Kvm, VcpuExit, VcpuFd, VmFd};
 use tracing::{instrument, Span};
+use vmm_sys_util::ioctl::ioctl;
 
+#[cfg(target_arch = "x86_64")]
+use super::cpuid::CpuidEntry;
+use super::cpuid::CpuidOptions;
+#[cfg(target_arch = "x86_64")]
+use super::crashdump::walk_frame_pointers;
+#[cfg(target_arch = "x86_64")]
 use super::fpu::{FP_CONTROL_WORD_DEFAULT, FP_TAG_WORD_DEFAULT, MXCSR_DEFAULT};
 use super::handlers::{MemAccessHandlerWrapper, OutBHandlerWrapper};
+use super::time::TimeOptions;
+use super::vcpu_stats::{VcpuStats, VcpuStatsSource};
+#[cfg(target_arch = "x86_64")]
+use super::DebugRegisters;
+use super::{HyperlightExit, Hypervisor, VirtualCPU};
+#[cfg(target_arch = "x86_64")]
 use super::{
-    HyperlightExit, Hypervisor, VirtualCPU, CR0_AM, CR0_ET, CR0_MP, CR0_NE, CR0_PE, CR0_PG, CR0_WP,
-    CR4_OSFXSR, CR4_OSXMMEXCPT, CR4_PAE, EFER_LMA, EFER_LME, EFER_NX, EFER_SCE,
+    CR0_AM, CR0_ET, CR0_MP, CR0_NE, CR0_PE, CR0_PG, CR0_WP, CR4_OSFXSR, CR4_OSXMMEXCPT, CR4_PAE,
+    EFER_LMA, EFER_LME, EFER_NX, EFER_SCE,
 };
 use crate::hypervisor::hypervisor_handler::HypervisorHandler;
 use crate::mem::memory_region::{MemoryRegion, MemoryRegionFlags};
@@ -55,6 +95,198 @@ pub(crate) fn is_hypervisor_present() -> bool {
     }
 }
 
+/// Get the debug registers of the given vCPU
+#[cfg(target_arch = "x86_64")]
+#[instrument(err(Debug), skip_all, parent = Span::current(), level = "Trace")]
+pub fn get_debug_regs(vcpu_fd: &VcpuFd) -> Result<DebugRegisters> {
+    let regs = vcpu_fd.get_debug_regs()?;
+    Ok(DebugRegisters {
+        addresses: regs.db,
+        dr6: regs.dr6,
+        dr7: regs.dr7,
+    })
+}
+
+/// Set the debug registers of the given vCPU.
+///
+/// Breakpoints and watchpoints enabled in `dr7` are trapped by KVM and
+/// reported to the host as debug exits rather than being delivered to
+/// the guest, so they can be set on guest addresses without the guest
+/// having to handle them.
+#[cfg(target_arch = "x86_64")]
+#[instrument(err(Debug), skip_all, parent = Span::current(), level = "Trace")]
+pub fn set_debug_regs(vcpu_fd: &VcpuFd, regs: &DebugRegisters) -> Result<()> {
+    // the local and global enable bits of the four breakpoints
+    const DR7_ENABLE_MASK: u64 = 0xff;
+
+    let mut debugreg = [0; 8];
+    debugreg[..4].copy_from_slice(&regs.addresses);
+    debugreg[6] = regs.dr6;
+    debugreg[7] = regs.dr7;
+    let control = match regs.dr7 & DR7_ENABLE_MASK {
+        0 => 0,
+        _ => KVM_GUESTDBG_ENABLE | KVM_GUESTDBG_USE_HW_BP,
+    };
+    vcpu_fd.set_guest_debug(&kvm_guest_debug {
+        control,
+        arch: kvm_guest_debug_arch { debugreg },
+        ..Default::default()
+    })?;
+
+    vcpu_fd.set_debug_regs(&kvm_debugregs {
+        db: regs.addresses,
+        dr6: regs.dr6,
+        dr7: regs.dr7,
+        ..Default::default()
+    })?;
+    Ok(())
+}
+
+/// Get the guest's TSC
+#[cfg(target_arch = "x86_64")]
+#[instrument(err(Debug), skip_all, parent = Span::current(), level = "Trace")]
+fn get_tsc(vcpu_fd: &VcpuFd) -> Result<u64> {
+    let mut msrs = Msrs::from_entries(&[kvm_msr_entry {
+        index: MSR_IA32_TSC,
+        ..Default::default()
+    }])
+    .map_err(|e| new_error!("Error creating MSR entries: {:?}", e))?;
+    vcpu_fd.get_msrs(&mut msrs)?;
+    Ok(msrs.as_slice()[0].data)
+}
+
+/// Set the guest's TSC
+#[cfg(target_arch = "x86_64")]
+#[instrument(err(Debug), skip_all, parent = Span::current(), level = "Trace")]
+fn set_tsc(vcpu_fd: &VcpuFd, tsc: u64) -> Result<()> {
+    let msrs = Msrs::from_entries(&[kvm_msr_entry {
+        index: MSR_IA32_TSC,
+        data: tsc,
+        ..Default::default()
+    }])
+    .map_err(|e| new_error!("Error creating MSR entries: {:?}", e))?;
+    vcpu_fd.set_msrs(&msrs)?;
+    Ok(())
+}
+
+/// `_IO(KVMIO, 0xce)`, which kvm-ioctls doesn't wrap. The binary stats API
+/// is available from Linux 5.14.
+const KVM_GET_STATS_FD: c_ulong = ((KVMIO as c_ulong) << 8) | 0xce;
+
+/// The description of one statistic in a KVM binary stats file
+struct KvmStatDesc {
+    name: String,
+    flags: u32,
+    /// The number of values, which is the number of buckets for histograms
+    size: usize,
+    /// The offset of the values from the start of the data block
+    offset: u64,
+}
+
+/// The binary stats file of a vCPU, see "KVM_GET_STATS_FD" in the KVM API
+/// documentation. The descriptors never change, so they are read once, and
+/// the data block is read again every time the stats are.
+struct KvmStatsFd {
+    file: File,
+    descriptors: Vec<KvmStatDesc>,
+    data_offset: u64,
+    data_size: usize,
+}
+
+impl KvmStatsFd {
+    /// Open the stats file of `vcpu_fd`, or return `None` if the kernel
+    /// doesn't support binary stats
+    #[instrument(err(Debug), skip_all, parent = Span::current(), level = "Trace")]
+    fn open(vcpu_fd: &VcpuFd) -> Result<Option<Self>> {
+        // SAFETY: KVM_GET_STATS_FD takes no argument, and returns a new fd
+        // that nothing else owns
+        let fd = unsafe { ioctl(vcpu_fd, KVM_GET_STATS_FD) };
+        if fd < 0 {
+            log::info!(
+                "KVM binary stats are not available: {}",
+                std::io::Error::last_os_error()
+            );
+            return Ok(None);
+        }
+        // SAFETY: fd was just returned by the kernel
+        let file = unsafe { File::from_raw_fd(fd) };
+
+        let read_u32 = |buf: &[u8], at: usize| -> u32 {
+            u32::from_ne_bytes([buf[at], buf[at + 1], buf[at + 2], buf[at + 3]])
+        };
+
+        // struct kvm_stats_header
+        let mut header = [0u8; 24];
+        file.read_exact_at(&mut header, 0)?;
+        let name_size = read_u32(&header, 4) as usize;
+        let num_desc = read_u32(&header, 8) as usize;
+        let desc_offset = read_u32(&header, 16) as u64;
+        let data_offset = read_u32(&header, 20) as u64;
+
+        // struct kvm_stats_desc, followed by the name
+        let desc_size = 16 + name_size;
+        let mut buf = vec![0u8; desc_size * num_desc];
+        file.read_exact_at(&mut buf, desc_offset)?;
+        let descriptors: Vec<KvmStatDesc> = buf
+            .chunks_exact(desc_size)
+            .map(|desc| {
+                let name = &desc[16..];
+                let name_len = name.iter().position(|&b| b == 0).unwrap_or(name.len());
+                KvmStatDesc {
+                    name: String::from_utf8_lossy(&name[..name_len]).into_owned(),
+                    flags: read_u32(desc, 0),
+                    size: u16::from_ne_bytes([desc[6], desc[7]]) as usize,
+                    offset: read_u32(desc, 8) as u64,
+                }
+            })
+            .collect();
+        let data_size = descriptors
+            .iter()
+            .map(|desc| desc.offset as usize + desc.size * 8)
+            .max()
+            .unwrap_or(0);
+
+        Ok(Some(Self {
+            file,
+            descriptors,
+            data_offset,
+            data_size,
+        }))
+    }
+}
+
+impl VcpuStatsSource for KvmStatsFd {
+    fn read(&self) -> Result<VcpuStats> {
+        let mut data = vec![0u8; self.data_size];
+        self.file.read_exact_at(&mut data, self.data_offset)?;
+        let values = |desc: &KvmStatDesc| -> Vec<u64> {
+            data[desc.offset as usize..desc.offset as usize + desc.size * 8]
+                .chunks_exact(8)
+                .map(|value| u64::from_ne_bytes(value.try_into().unwrap_or_default()))
+                .collect()
+        };
+
+        let mut counters = BTreeMap::new();
+        let mut histograms = BTreeMap::new();
+        for desc in &self.descriptors {
+            match desc.flags & KVM_STATS_TYPE_MASK {
+                KVM_STATS_TYPE_LINEAR_HIST | KVM_STATS_TYPE_LOG_HIST => {
+                    histograms.insert(desc.name.clone(), values(desc));
+                }
+                _ => {
+                    if let Some(&value) = values(desc).first() {
+                        counters.insert(desc.name.clone(), value);
+                    }
+                }
+            }
+        }
+        Ok(VcpuStats {
+            counters,
+            histograms,
+        })
+    }
+}
+
 /// A Hypervisor driver for KVM on Linux
 pub(super) struct KVMDriver {
     _kvm: Kvm,
@@ -63,8 +295,20 @@ pub(super) struct KVMDriver {
     entrypoint: u64,
     orig_rsp: GuestPtr,
     mem_regions: Vec<MemoryRegion>,
+    saved_fpu_states: Vec<FpuState>,
+    #[cfg(target_arch = "x86_64")]
+    pause_time_in_host: bool,
+    stats: Option<Arc<KvmStatsFd>>,
 }
 
+/// The XSAVE area of the vCPU, which includes the x87 FPU and SSE state
+#[cfg(target_arch = "x86_64")]
+type FpuState = kvm_xsave;
+
+/// The ids and values of the vCPU's FP/SIMD registers
+#[cfg(target_arch = "aarch64")]
+type FpuState = Vec<(u64, Vec<u8>)>;
+
 impl KVMDriver {
     /// Create a new instance of a `KVMDriver`, with only control registers
     /// set. Standard registers will not be set, and `initialise` must
@@ -75,6 +319,8 @@ impl KVMDriver {
         pml4_addr: u64,
         entrypoint: u64,
         rsp: u64,
+        cpuid_options: Option<&CpuidOptions>,
+        time_options: Option<&TimeOptions>,
     ) -> Result<Self> {
         let kvm = Kvm::new()?;
 
@@ -99,21 +345,36 @@ impl KVMDriver {
         })?;
 
         let mut vcpu_fd = vm_fd.create_vcpu(0)?;
-        Self::setup_initial_sregs(&mut vcpu_fd, pml4_addr)?;
+        Self::setup_initial_sregs(&vm_fd, &mut vcpu_fd, pml4_addr)?;
+        if let Some(options) = cpuid_options {
+            Self::setup_cpuid(&kvm, &vcpu_fd, options)?;
+        }
+        if let Some(options) = time_options {
+            Self::setup_time(&vcpu_fd, options)?;
+        }
+        let stats = KvmStatsFd::open(&vcpu_fd)?.map(Arc::new);
 
         let rsp_gp = GuestPtr::try_from(RawPtr::from(rsp))?;
-        Ok(Self {
+        let mut driver = Self {
             _kvm: kvm,
             _vm_fd: vm_fd,
             vcpu_fd,
             entrypoint,
             orig_rsp: rsp_gp,
             mem_regions,
-        })
+            saved_fpu_states: Vec::new(),
+            #[cfg(target_arch = "x86_64")]
+            pause_time_in_host: time_options.is_some_and(|options| options.pause_in_host),
+            stats,
+        };
+        driver.reset_fpu()?;
+        driver.save_fpu_state()?;
+        Ok(driver)
     }
 
+    #[cfg(target_arch = "x86_64")]
     #[instrument(err(Debug), skip_all, parent = Span::current(), level = "Trace")]
-    fn setup_initial_sregs(vcpu_fd: &mut VcpuFd, pml4_addr: u64) -> Result<()> {
+    fn setup_initial_sregs(_vm_fd: &VmFd, vcpu_fd: &mut VcpuFd, pml4_addr: u64) -> Result<()> {
         // setup paging and IA-32e (64-bit) mode
         let mut sregs = vcpu_fd.get_sregs()?;
         sregs.cr3 = pml4_addr;
@@ -124,6 +385,287 @@ impl KVMDriver {
         vcpu_fd.set_sregs(&sregs)?;
         Ok(())
     }
+
+    /// Present the CPUID leaves KVM supports to the vCPU, modified by `options`
+    #[cfg(target_arch = "x86_64")]
+    #[instrument(err(Debug), skip_all, parent = Span::current(), level = "Trace")]
+    fn setup_cpuid(kvm: &Kvm, vcpu_fd: &VcpuFd, options: &CpuidOptions) -> Result<()> {
+        let supported = kvm.get_supported_cpuid(KVM_MAX_CPUID_ENTRIES)?;
+        let mut entries: Vec<CpuidEntry> = supported
+            .as_slice()
+            .iter()
+            .map(|entry| CpuidEntry {
+                function: entry.function,
+                index: entry.index,
+                eax: entry.eax,
+                ebx: entry.ebx,
+                ecx: entry.ecx,
+                edx: entry.edx,
+            })
+            .collect();
+        options.apply(&mut entries);
+
+        let entries: Vec<kvm_cpuid_entry2> = entries
+            .iter()
+            .map(|entry| kvm_cpuid_entry2 {
+                function: entry.function,
+                index: entry.index,
+                // keep whether the subleaf is significant for leaves KVM knows about
+                flags: supported
+                    .as_slice()
+                    .iter()
+                    .find(|s| s.function == entry.function)
+                    .map_or(0, |s| s.flags),
+                eax: entry.eax,
+                ebx: entry.ebx,
+                ecx: entry.ecx,
+                edx: entry.edx,
+                ..Default::default()
+            })
+            .collect();
+        let cpuid = CpuId::from_entries(&entries)
+            .map_err(|e| new_error!("Error creating CPUID entries: {:?}", e))?;
+        vcpu_fd.set_cpuid2(&cpuid)?;
+        Ok(())
+    }
+
+    #[cfg(target_arch = "x86_64")]
+    #[instrument(err(Debug), skip_all, parent = Span::current(), level = "Trace")]
+    fn setup_time(vcpu_fd: &VcpuFd, options: &TimeOptions) -> Result<()> {
+        if let Some(khz) = options.tsc_khz {
+            vcpu_fd.set_tsc_khz(khz)?;
+        }
+        if let Some(offset) = options.tsc_offset {
+            #[allow(unused_unsafe)]
+            let host_tsc = unsafe { _rdtsc() };
+            set_tsc(vcpu_fd, host_tsc.wrapping_add(offset))?;
+        }
+        Ok(())
+    }
+
+    #[cfg(target_arch = "aarch64")]
+    #[instrument(err(Debug), skip_all, parent = Span::current(), level = "Trace")]
+    fn setup_time(_vcpu_fd: &VcpuFd, _options: &TimeOptions) -> Result<()> {
+        log_then_return!("Time options are not supported on aarch64");
+    }
+
+    #[cfg(target_arch = "aarch64")]
+    #[instrument(err(Debug), skip_all, parent = Span::current(), level = "Trace")]
+    fn setup_cpuid(_kvm: &Kvm, _vcpu_fd: &VcpuFd, _options: &CpuidOptions) -> Result<()> {
+        log_then_return!("CPUID options are not supported on aarch64");
+    }
+
+    #[cfg(target_arch = "aarch64")]
+    #[instrument(err(Debug), skip_all, parent = Span::current(), level = "Trace")]
+    fn setup_initial_sregs(vm_fd: &VmFd, vcpu_fd: &mut VcpuFd, pml4_addr: u64) -> Result<()> {
+        let mut kvi = kvm_vcpu_init::default();
+        vm_fd.get_preferred_target(&mut kvi)?;
+        kvi.features[0] |= 1 << KVM_ARM_VCPU_PSCI_0_2;
+        vcpu_fd.vcpu_init(&kvi)?;
+
+        // Enable the MMU at EL1 with 4K pages and 48 bit addresses, using the
+        // same four levels of tables as on x86 (see `set_up_shared_memory`),
+        // with all memory mapped as normal, write-back cacheable memory
+        let tcr_el1 = TCR_T0SZ_48_BIT
+            | TCR_IRGN0_WBWA
+            | TCR_ORGN0_WBWA
+            | TCR_SH0_INNER
+            | TCR_TG0_4K
+            | TCR_EPD1
+            | TCR_IPS_48_BIT;
+        set_one_reg_u64(vcpu_fd, MAIR_EL1, MAIR_NORMAL_WB)?;
+        set_one_reg_u64(vcpu_fd, TCR_EL1, tcr_el1)?;
+        set_one_reg_u64(vcpu_fd, TTBR0_EL1, pml4_addr)?;
+        set_one_reg_u64(vcpu_fd, CPACR_EL1, CPACR_FPEN)?;
+        set_one_reg_u64(vcpu_fd, SCTLR_EL1, SCTLR_RES1 | SCTLR_M | SCTLR_C | SCTLR_I)?;
+        Ok(())
+    }
+
+    /// Reset the general purpose registers, then set the instruction
+    /// pointer to `ip`, the stack pointer to `sp` and the registers for the
+    /// first four function arguments to `args`.
+    #[cfg(target_arch = "x86_64")]
+    #[instrument(err(Debug), skip_all, parent = Span::current(), level = "Trace")]
+    fn set_entry_regs(&mut self, ip: u64, sp: u64, args: [u64; 4]) -> Result<()> {
+        let regs = kvm_regs {
+            rip: ip,
+            rsp: sp,
+
+            // function args
+            rcx: args[0],
+            rdx: args[1],
+            r8: args[2],
+            r9: args[3],
+
+            ..Default::default()
+        };
+        self.vcpu_fd.set_regs(&regs)?;
+        Ok(())
+    }
+
+    /// Reset the general purpose registers, then set the instruction
+    /// pointer to `ip`, the stack pointer to `sp` and the registers for the
+    /// first four function arguments to `args`.
+    #[cfg(target_arch = "aarch64")]
+    #[instrument(err(Debug), skip_all, parent = Span::current(), level = "Trace")]
+    fn set_entry_regs(&mut self, ip: u64, sp: u64, args: [u64; 4]) -> Result<()> {
+        // The guest halts by writing to the MMIO doorbell. KVM completes that
+        // write when the vCPU next runs by stepping over the instruction at
+        // PC, which would now be the first instruction at `ip`, so forget it.
+        self.vcpu_fd.get_kvm_run().exit_reason = KVM_EXIT_UNKNOWN;
+
+        for i in 0..31 {
+            let value = args.get(i).copied().unwrap_or(0);
+            set_one_reg_u64(&self.vcpu_fd, core_reg_x(i), value)?;
+        }
+        set_one_reg_u64(&self.vcpu_fd, core_reg(SP_EL1_OFFSET), sp)?;
+        set_one_reg_u64(&self.vcpu_fd, core_reg(PC_OFFSET), ip)?;
+        set_one_reg_u64(&self.vcpu_fd, core_reg(PSTATE_OFFSET), PSTATE_EL1H_MASKED)?;
+        Ok(())
+    }
+
+    #[cfg(target_arch = "x86_64")]
+    #[instrument(err(Debug), skip_all, parent = Span::current(), level = "Trace")]
+    fn get_stack_pointer(&self) -> Result<u64> {
+        Ok(self.vcpu_fd.get_regs()?.rsp)
+    }
+
+    #[cfg(target_arch = "aarch64")]
+    #[instrument(err(Debug), skip_all, parent = Span::current(), level = "Trace")]
+    fn get_stack_pointer(&self) -> Result<u64> {
+        let mut sp = [0u8; 8];
+        self.vcpu_fd.get_one_reg(core_reg(SP_EL1_OFFSET), &mut sp)?;
+        Ok(u64::from_le_bytes(sp))
+    }
+
+    #[cfg(target_arch = "x86_64")]
+    #[instrument(err(Debug), skip_all, parent = Span::current(), level = "Trace")]
+    fn reset_fpu(&mut self) -> Result<()> {
+        let fpu = kvm_fpu {
+            fcw: FP_CONTROL_WORD_DEFAULT,
+            ftwx: FP_TAG_WORD_DEFAULT,
+            mxcsr: MXCSR_DEFAULT,
+            ..Default::default() // zero out the rest
+        };
+        self.vcpu_fd.set_fpu(&fpu)?;
+        Ok(())
+    }
+
+    #[cfg(target_arch = "aarch64")]
+    #[instrument(err(Debug), skip_all, parent = Span::current(), level = "Trace")]
+    fn reset_fpu(&mut self) -> Result<()> {
+        for offset in [FPSR_OFFSET, FPCR_OFFSET] {
+            let id = KVM_REG_ARM64 | KVM_REG_SIZE_U32 | KVM_REG_ARM_CORE as u64 | offset / 4;
+            self.vcpu_fd.set_one_reg(id, &0u32.to_le_bytes())?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(target_arch = "x86_64")]
+const MSR_IA32_TSC: u32 = 0x10;
+
+#[cfg(target_arch = "aarch64")]
+const SP_EL1_OFFSET: u64 = offset_of!(kvm_regs, sp_el1) as u64;
+#[cfg(target_arch = "aarch64")]
+const PC_OFFSET: u64 = (offset_of!(kvm_regs, regs) + offset_of!(user_pt_regs, pc)) as u64;
+#[cfg(target_arch = "aarch64")]
+const PSTATE_OFFSET: u64 = (offset_of!(kvm_regs, regs) + offset_of!(user_pt_regs, pstate)) as u64;
+#[cfg(target_arch = "aarch64")]
+const FPSR_OFFSET: u64 =
+    (offset_of!(kvm_regs, fp_regs) + offset_of!(user_fpsimd_state, fpsr)) as u64;
+#[cfg(target_arch = "aarch64")]
+const FPCR_OFFSET: u64 =
+    (offset_of!(kvm_regs, fp_regs) + offset_of!(user_fpsimd_state, fpcr)) as u64;
+#[cfg(target_arch = "aarch64")]
+const VREGS_OFFSET: u64 =
+    (offset_of!(kvm_regs, fp_regs) + offset_of!(user_fpsimd_state, vregs)) as u64;
+/// EL1 using SP_EL1, with debug exceptions, SErrors, IRQs and FIQs masked
+#[cfg(target_arch = "aarch64")]
+const PSTATE_EL1H_MASKED: u64 = 0x3c5;
+
+#[cfg(target_arch = "aarch64")]
+const MAIR_EL1: u64 = sys_reg(3, 0, 10, 2, 0);
+#[cfg(target_arch = "aarch64")]
+const TCR_EL1: u64 = sys_reg(3, 0, 2, 0, 2);
+#[cfg(target_arch = "aarch64")]
+const TTBR0_EL1: u64 = sys_reg(3, 0, 2, 0, 0);
+#[cfg(target_arch = "aarch64")]
+const CPACR_EL1: u64 = sys_reg(3, 0, 1, 0, 2);
+#[cfg(target_arch = "aarch64")]
+const SCTLR_EL1: u64 = sys_reg(3, 0, 1, 0, 0);
+
+/// Attribute index 0 is normal, inner and outer write-back, non-transient memory
+#[cfg(target_arch = "aarch64")]
+const MAIR_NORMAL_WB: u64 = 0xff;
+#[cfg(target_arch = "aarch64")]
+const TCR_T0SZ_48_BIT: u64 = 16;
+#[cfg(target_arch = "aarch64")]
+const TCR_IRGN0_WBWA: u64 = 1 << 8;
+#[cfg(target_arch = "aarch64")]
+const TCR_ORGN0_WBWA: u64 = 1 << 10;
+#[cfg(target_arch = "aarch64")]
+const TCR_SH0_INNER: u64 = 3 << 12;
+#[cfg(target_arch = "aarch64")]
+const TCR_TG0_4K: u64 = 0;
+#[cfg(target_arch = "aarch64")]
+const TCR_EPD1: u64 = 1 << 23;
+#[cfg(target_arch = "aarch64")]
+const TCR_IPS_48_BIT: u64 = 5 << 32;
+/// Don't trap FP/SIMD instructions, which Rust code uses freely
+#[cfg(target_arch = "aarch64")]
+const CPACR_FPEN: u64 = 3 << 20;
+#[cfg(target_arch = "aarch64")]
+const SCTLR_RES1: u64 = (1 << 11) | (1 << 20) | (1 << 22) | (1 << 23) | (1 << 28) | (1 << 29);
+#[cfg(target_arch = "aarch64")]
+const SCTLR_M: u64 = 1;
+#[cfg(target_arch = "aarch64")]
+const SCTLR_C: u64 = 1 << 2;
+#[cfg(target_arch = "aarch64")]
+const SCTLR_I: u64 = 1 << 12;
+
+/// The id of the 64 bit core register at `offset` in `kvm_regs`
+#[cfg(target_arch = "aarch64")]
+const fn core_reg(offset: u64) -> u64 {
+    KVM_REG_ARM64 | KVM_REG_SIZE_U64 | KVM_REG_ARM_CORE as u64 | offset / 4
+}
+
+/// The ids and sizes in bytes of the FP/SIMD registers V0-V31, FPSR and FPCR
+#[cfg(target_arch = "aarch64")]
+fn fpsimd_regs() -> impl Iterator<Item = (u64, usize)> {
+    let core = KVM_REG_ARM64 | KVM_REG_ARM_CORE as u64;
+    (0..32)
+        .map(move |n| (core | KVM_REG_SIZE_U128 | (VREGS_OFFSET + n * 16) / 4, 16))
+        .chain(
+            [FPSR_OFFSET, FPCR_OFFSET]
+                .into_iter()
+                .map(move |offset| (core | KVM_REG_SIZE_U32 | offset / 4, 4)),
+        )
+}
+
+/// The id of the general purpose register `Xn`
+#[cfg(target_arch = "aarch64")]
+fn core_reg_x(n: usize) -> u64 {
+    core_reg((offset_of!(kvm_regs, regs) + offset_of!(user_pt_regs, regs) + n * 8) as u64)
+}
+
+/// The id of the 64 bit system register with the given encoding
+#[cfg(target_arch = "aarch64")]
+const fn sys_reg(op0: u64, op1: u64, crn: u64, crm: u64, op2: u64) -> u64 {
+    KVM_REG_ARM64
+        | KVM_REG_SIZE_U64
+        | KVM_REG_ARM64_SYSREG as u64
+        | (op0 << 14)
+        | (op1 << 11)
+        | (crn << 7)
+        | (crm << 3)
+        | op2
+}
+
+#[cfg(target_arch = "aarch64")]
+fn set_one_reg_u64(vcpu_fd: &VcpuFd, id: u64, value: u64) -> Result<()> {
+    vcpu_fd.set_one_reg(id, &value.to_le_bytes())?;
+    Ok(())
 }
 
 impl Debug for KVMDriver {
@@ -134,19 +676,36 @@ impl Debug for KVMDriver {
         for region in &self.mem_regions {
             f.field("Memory Region", &region);
         }
-        let regs = self.vcpu_fd.get_regs();
-        // check that regs is OK and then set field in debug struct
+        #[cfg(target_arch = "x86_64")]
+        {
+            let regs = self.vcpu_fd.get_regs();
+            // check that regs is OK and then set field in debug struct
 
-        if let Ok(regs) = regs {
-            f.field("Registers", &regs);
-        }
+            if let Ok(regs) = regs {
+                f.field("Registers", &regs);
+            }
 
-        let sregs = self.vcpu_fd.get_sregs();
+            let sregs = self.vcpu_fd.get_sregs();
 
-        // check that sregs is OK and then set field in debug struct
+            // check that sregs is OK and then set field in debug struct
 
-        if let Ok(sregs) = sregs {
-            f.field("Special Registers", &sregs);
+            if let Ok(sregs) = sregs {
+                f.field("Special Registers", &sregs);
+            }
+        }
+        #[cfg(target_arch = "aarch64")]
+        {
+            let mut pc = [0u8; 8];
+            if self
+                .vcpu_fd
+                .get_one_reg(core_reg(PC_OFFSET), &mut pc)
+                .is_ok()
+            {
+                f.field("PC", &format_args!("{:#x}", u64::from_le_bytes(pc)));
+            }
+            if let Ok(sp) = self.get_stack_pointer() {
+                f.field("SP", &format_args!("{:#x}", sp));
+            }
         }
 
         f.finish()
@@ -165,19 +724,17 @@ impl Hypervisor for KVMDriver {
         mem_access_hdl: MemAccessHandlerWrapper,
         hv_handler: Option<HypervisorHandler>,
     ) -> Result<()> {
-        let regs = kvm_regs {
-            rip: self.entrypoint,
-            rsp: self.orig_rsp.absolute()?,
-
-            // function args
-            rcx: peb_addr.into(),
-            rdx: seed,
-            r8: page_size.into(),
-            r9: self.get_max_log_level().into(),
-
-            ..Default::default()
-        };
-        self.vcpu_fd.set_regs(&regs)?;
+        self.restore_fpu_state()?;
+        self.set_entry_regs(
+            self.entrypoint,
+            self.orig_rsp.absolute()?,
+            [
+                peb_addr.into(),
+                seed,
+                page_size.into(),
+                self.get_max_log_level().into(),
+            ],
+        )?;
 
         VirtualCPU::run(
             self.as_mut_hypervisor(),
@@ -185,12 +742,10 @@ impl Hypervisor for KVMDriver {
             outb_hdl,
             mem_access_hdl,
         )?;
+        self.save_fpu_state()?;
 
         // reset RSP to what it was before initialise
-        self.vcpu_fd.set_regs(&kvm_regs {
-            rsp: self.orig_rsp.absolute()?,
-            ..Default::default()
-        })?;
+        self.set_entry_regs(0, self.orig_rsp.absolute()?, [0; 4])?;
         Ok(())
     }
 
@@ -203,22 +758,10 @@ impl Hypervisor for KVMDriver {
         hv_handler: Option<HypervisorHandler>,
     ) -> Result<()> {
         // Reset general purpose registers except RSP, then set RIP
-        let rsp_before = self.vcpu_fd.get_regs()?.rsp;
-        let regs = kvm_regs {
-            rip: dispatch_func_addr.into(),
-            rsp: rsp_before,
-            ..Default::default()
-        };
-        self.vcpu_fd.set_regs(&regs)?;
+        let rsp_before = self.get_stack_pointer()?;
+        self.set_entry_regs(dispatch_func_addr.into(), rsp_before, [0; 4])?;
 
-        // reset fpu state
-        let fpu = kvm_fpu {
-            fcw: FP_CONTROL_WORD_DEFAULT,
-            ftwx: FP_TAG_WORD_DEFAULT,
-            mxcsr: MXCSR_DEFAULT,
-            ..Default::default() // zero out the rest
-        };
-        self.vcpu_fd.set_fpu(&fpu)?;
+        self.restore_fpu_state()?;
 
         // run
         VirtualCPU::run(
@@ -229,10 +772,7 @@ impl Hypervisor for KVMDriver {
         )?;
 
         // reset RSP to what it was before function call
-        self.vcpu_fd.set_regs(&kvm_regs {
-            rsp: rsp_before,
-            ..Default::default()
-        })?;
+        self.set_entry_regs(0, rsp_before, [0; 4])?;
         Ok(())
     }
 
@@ -253,11 +793,22 @@ impl Hypervisor for KVMDriver {
         if data.is_empty() {
             log_then_return!("no data was given in IO interrupt");
         } else {
+            #[cfg(target_arch = "x86_64")]
+            let guest_tsc = match self.pause_time_in_host {
+                true => Some(get_tsc(&self.vcpu_fd)?),
+                false => None,
+            };
+
             let payload_u64 = u64::from(data[0]);
             outb_handle_fn
                 .try_lock()
                 .map_err(|e| new_error!("Error locking at {}:{}: {}", file!(), line!(), e))?
                 .call(port, payload_u64)?;
+
+            #[cfg(target_arch = "x86_64")]
+            if let Some(tsc) = guest_tsc {
+                set_tsc(&self.vcpu_fd, tsc)?;
+            }
         }
 
         Ok(())
@@ -289,17 +840,42 @@ impl Hypervisor for KVMDriver {
                     None => HyperlightExit::Mmio(addr),
                 }
             }
-            Ok(VcpuExit::MmioWrite(addr, _)) => {
-                crate::debug!("KVM MMIO Write -Details: Address: {} \n {:#?}", addr, &self);
+            Ok(VcpuExit::MmioWrite(addr, data)) => match doorbell_port(addr) {
+                Some(MMIO_HALT_PORT) => HyperlightExit::Halt(),
+                Some(port) => {
+                    crate::debug!(
+                        "KVM MMIO Doorbell Details : \nPort : {}\nData : {:?}",
+                        port,
+                        data
+                    );
+                    // As with port I/O, KVM completes the write itself so there is no RIP to set
+                    HyperlightExit::IoOut(port, data.to_vec(), 0, 0)
+                }
+                None => {
+                    crate::debug!("KVM MMIO Write -Details: Address: {} \n {:#?}", addr, &self);
 
-                match self.get_memory_access_violation(
-                    addr as usize,
-                    &self.mem_regions,
-                    MemoryRegionFlags::WRITE,
-                ) {
-                    Some(access_violation_exit) => access_violation_exit,
-                    None => HyperlightExit::Mmio(addr),
+                    match self.get_memory_access_violation(
+                        addr as usize,
+                        &self.mem_regions,
+                        MemoryRegionFlags::WRITE,
+                    ) {
+                        Some(access_violation_exit) => access_violation_exit,
+                        None => HyperlightExit::Mmio(addr),
+                    }
                 }
+            },
+            Ok(VcpuExit::Shutdown) => {
+                crate::debug!("KVM - Shutdown Details : {:#?}", &self);
+                HyperlightExit::Shutdown()
+            }
+            Ok(VcpuExit::FailEntry(reason, _)) => {
+                crate::debug!("KVM - Fail Entry Details : {:#?}", &self);
+                HyperlightExit::FailEntry(reason)
+            }
+            #[cfg(target_arch = "x86_64")]
+            Ok(VcpuExit::Debug(debug)) => {
+                crate::debug!("KVM - Debug Details : {:#?}", debug);
+                HyperlightExit::Debug(debug.pc, debug.dr6)
             }
             Err(e) => match e.errno() {
                 // we send a signal to the thread to cancel execution this results in EINTR being returned by KVM so we return Cancelled
@@ -318,6 +894,88 @@ impl Hypervisor for KVMDriver {
         Ok(result)
     }
 
+    #[cfg(target_arch = "x86_64")]
+    #[instrument(err(Debug), skip_all, parent = Span::current(), level = "Trace")]
+    fn save_fpu_state(&mut self) -> Result<()> {
+        self.saved_fpu_states.push(self.vcpu_fd.get_xsave()?);
+        Ok(())
+    }
+
+    #[cfg(target_arch = "aarch64")]
+    #[instrument(err(Debug), skip_all, parent = Span::current(), level = "Trace")]
+    fn save_fpu_state(&mut self) -> Result<()> {
+        let state = fpsimd_regs()
+            .map(|(id, size)| {
+                let mut value = vec![0; size];
+                self.vcpu_fd.get_one_reg(id, &mut value)?;
+                Ok((id, value))
+            })
+            .collect::<Result<_>>()?;
+        self.saved_fpu_states.push(state);
+        Ok(())
+    }
+
+    #[instrument(err(Debug), skip_all, parent = Span::current(), level = "Trace")]
+    fn restore_fpu_state(&mut self) -> Result<()> {
+        let state = self
+            .saved_fpu_states
+            .last()
+            .ok_or_else(|| new_error!("No saved FPU state to restore"))?;
+        #[cfg(target_arch = "x86_64")]
+        self.vcpu_fd.set_xsave(state)?;
+        #[cfg(target_arch = "aarch64")]
+        for (id, value) in state {
+            self.vcpu_fd.set_one_reg(*id, value)?;
+        }
+        Ok(())
+    }
+
+    #[instrument(err(Debug), skip_all, parent = Span::current(), level = "Trace")]
+    fn discard_fpu_state(&mut self) -> Result<()> {
+        if self.saved_fpu_states.len() <= 1 {
+            log_then_return!("No saved FPU state to discard");
+        }
+        self.saved_fpu_states.pop();
+        Ok(())
+    }
+
+    #[instrument(skip_all, parent = Span::current(), level = "Trace")]
+    fn outb_transport(&self) -> OutBTransport {
+        // port I/O only exists on x86
+        if cfg!(target_arch = "x86_64") {
+            OutBTransport::PortIo
+        } else {
+            OutBTransport::Mmio
+        }
+    }
+
+    #[cfg(target_arch = "x86_64")]
+    #[instrument(err(Debug), skip_all, parent = Span::current(), level = "Trace")]
+    fn backtrace(&self) -> Result<Vec<u64>> {
+        let regs = self.vcpu_fd.get_regs()?;
+        let mut backtrace = vec![regs.rip];
+        backtrace.extend(walk_frame_pointers(&self.mem_regions, regs.rbp));
+        Ok(backtrace)
+    }
+
+    #[cfg(target_arch = "x86_64")]
+    #[instrument(err(Debug), skip_all, parent = Span::current(), level = "Trace")]
+    fn set_debug_registers(&mut self, regs: &DebugRegisters) -> Result<()> {
+        set_debug_regs(&self.vcpu_fd, regs)
+    }
+
+    #[cfg(target_arch = "x86_64")]
+    #[instrument(err(Debug), skip_all, parent = Span::current(), level = "Trace")]
+    fn debug_registers(&self) -> Result<DebugRegisters> {
+        get_debug_regs(&self.vcpu_fd)
+    }
+
+    fn vcpu_stats_source(&self) -> Option<Arc<dyn VcpuStatsSource>> {
+        self.stats
+            .clone()
+            .map(|stats| stats as Arc<dyn VcpuStatsSource>)
+    }
+
     #[instrument(skip_all, parent = Span::current(), level = "Trace")]
     fn as_mut_hypervisor(&mut self) -> &mut dyn Hypervisor {
         self as &mut dyn Hypervisor
@@ -385,8 +1043,10 @@ pub(crate) mod test_cfg {
 mod tests {
     use std::sync::{Arc, Mutex};
 
+    use crate::hypervisor::cpuid::CpuidOptions;
     use crate::hypervisor::handlers::{MemAccessHandler, OutBHandler};
     use crate::hypervisor::tests::test_initialise;
+    use crate::hypervisor::time::TimeOptions;
     use crate::{should_run_kvm_linux_test, Result};
 
     #[test]
@@ -403,4 +1063,65 @@ mod tests {
         };
         test_initialise(outb_handler, mem_access_handler).unwrap();
     }
+
+    #[cfg(target_arch = "x86_64")]
+    #[test]
+    fn test_setup_cpuid() {
+        should_run_kvm_linux_test!();
+        let kvm = kvm_ioctls::Kvm::new().unwrap();
+        let vm_fd = kvm.create_vm().unwrap();
+        let vcpu_fd = vm_fd.create_vcpu(0).unwrap();
+
+        let options = CpuidOptions::new().with_hypervisor_signature();
+        super::KVMDriver::setup_cpuid(&kvm, &vcpu_fd, &options).unwrap();
+
+        let cpuid = vcpu_fd
+            .get_cpuid2(kvm_bindings::KVM_MAX_CPUID_ENTRIES)
+            .unwrap();
+        let signature = cpuid
+            .as_slice()
+            .iter()
+            .find(|e| e.function == 0x4000_0000)
+            .unwrap();
+        assert_eq!(signature.ebx.to_le_bytes(), *b"Hype");
+    }
+
+    #[cfg(target_arch = "x86_64")]
+    #[test]
+    fn test_setup_time() {
+        should_run_kvm_linux_test!();
+        let kvm = kvm_ioctls::Kvm::new().unwrap();
+        let vm_fd = kvm.create_vm().unwrap();
+        let vcpu_fd = vm_fd.create_vcpu(0).unwrap();
+
+        // the host's TSC frequency never needs hardware scaling
+        let khz = vcpu_fd.get_tsc_khz().unwrap();
+        let options = TimeOptions::new()
+            .with_tsc_frequency(khz)
+            .with_tsc_offset(1 << 32);
+        super::KVMDriver::setup_time(&vcpu_fd, &options).unwrap();
+        assert_eq!(vcpu_fd.get_tsc_khz().unwrap(), khz);
+        assert!(super::get_tsc(&vcpu_fd).unwrap() > 0);
+    }
+
+    #[cfg(target_arch = "x86_64")]
+    #[test]
+    fn test_debug_regs() {
+        should_run_kvm_linux_test!();
+        let kvm = kvm_ioctls::Kvm::new().unwrap();
+        let vm_fd = kvm.create_vm().unwrap();
+        let vcpu_fd = vm_fd.create_vcpu(0).unwrap();
+
+        let regs = super::DebugRegisters {
+            addresses: [0x1000, 0x2000, 0x3000, 0x4000],
+            // enable DR0 as an execution breakpoint
+            dr7: 0x401,
+            ..Default::default()
+        };
+        super::set_debug_regs(&vcpu_fd, &regs).unwrap();
+
+        let read = super::get_debug_regs(&vcpu_fd).unwrap();
+        assert_eq!(read.addresses, regs.addresses);
+        assert_eq!(read.dr7, regs.dr7);
+    }
 }
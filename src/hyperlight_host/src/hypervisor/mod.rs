@@ -14,21 +14,27 @@ See the License for the specific language governing permissions and
 limitations under the License.
 */
 
+use hyperlight_common::mem::OutBTransport;
 use tracing::{instrument, Span};
 
 use crate::error::HyperlightError::ExecutionCanceledByHost;
 use crate::hypervisor::metrics::HypervisorMetric::NumberOfCancelledGuestExecutions;
+use crate::hypervisor::vcpu_stats::VcpuStatsSource;
 use crate::mem::memory_region::{MemoryRegion, MemoryRegionFlags};
 use crate::{int_counter_inc, log_then_return, new_error, HyperlightError, Result};
 
+/// Probing the features of the hypervisors available on the host
+pub mod capabilities;
+/// Options controlling the CPUID leaves presented to guests
+pub mod cpuid;
 /// Util for handling x87 fpu state
-#[cfg(any(kvm, mshv, target_os = "windows"))]
-pub mod fpu;
+#[cfg(all(target_arch = "x86_64", any(kvm, mshv, target_os = "windows")))]
+pub(crate) mod fpu;
 /// Handlers for Hypervisor custom logic
-pub mod handlers;
+pub(crate) mod handlers;
 /// HyperV-on-linux functionality
 #[cfg(mshv)]
-pub mod hyperv_linux;
+pub(crate) mod hyperv_linux;
 #[cfg(target_os = "windows")]
 /// Hyperv-on-windows functionality
 pub(crate) mod hyperv_windows;
@@ -36,18 +42,24 @@ pub(crate) mod hypervisor_handler;
 
 /// Driver for running in process instead of using hypervisor
 #[cfg(inprocess)]
-pub mod inprocess;
+pub(crate) mod inprocess;
 #[cfg(kvm)]
 /// Functionality to manipulate KVM-based virtual machines
 pub mod kvm;
 /// Metric definitions for Hypervisor module.
 mod metrics;
+/// Statistical profiling of where the guest spends its time
+pub(crate) mod sampling;
 #[cfg(target_os = "windows")]
 /// Hyperlight Surrogate Process
 pub(crate) mod surrogate_process;
 #[cfg(target_os = "windows")]
 /// Hyperlight Surrogate Process
 pub(crate) mod surrogate_process_manager;
+/// Options controlling the guest's time stamp counter
+pub mod time;
+/// Statistics hypervisors keep about vCPUs
+pub mod vcpu_stats;
 /// WindowsHypervisorPlatform utilities
 #[cfg(target_os = "windows")]
 pub(crate) mod windows_hypervisor_platform;
@@ -55,11 +67,16 @@ pub(crate) mod windows_hypervisor_platform;
 #[cfg(target_os = "windows")]
 pub(crate) mod wrappers;
 
-#[cfg(crashdump)]
-pub(crate) mod crashdump;
+/// Reports of where the guest was executing when it failed
+pub mod crashdump;
 
 use std::fmt::Debug;
 use std::sync::{Arc, Mutex};
+use std::time::Instant;
+
+pub use capabilities::{capabilities, HypervisorCapabilities};
+pub use crashdump::CrashDump;
+pub use vcpu_stats::VcpuStats;
 
 use self::handlers::{
     MemAccessHandlerCaller, MemAccessHandlerWrapper, OutBHandlerCaller, OutBHandlerWrapper,
@@ -67,24 +84,51 @@ use self::handlers::{
 use crate::hypervisor::hypervisor_handler::HypervisorHandler;
 use crate::mem::ptr::RawPtr;
 
+#[cfg(target_arch = "x86_64")]
 pub(crate) const CR4_PAE: u64 = 1 << 5;
+#[cfg(target_arch = "x86_64")]
 pub(crate) const CR4_OSFXSR: u64 = 1 << 9;
+#[cfg(target_arch = "x86_64")]
 pub(crate) const CR4_OSXMMEXCPT: u64 = 1 << 10;
+#[cfg(target_arch = "x86_64")]
 pub(crate) const CR0_PE: u64 = 1;
+#[cfg(target_arch = "x86_64")]
 pub(crate) const CR0_MP: u64 = 1 << 1;
+#[cfg(target_arch = "x86_64")]
 pub(crate) const CR0_ET: u64 = 1 << 4;
+#[cfg(target_arch = "x86_64")]
 pub(crate) const CR0_NE: u64 = 1 << 5;
+#[cfg(target_arch = "x86_64")]
 pub(crate) const CR0_WP: u64 = 1 << 16;
+#[cfg(target_arch = "x86_64")]
 pub(crate) const CR0_AM: u64 = 1 << 18;
+#[cfg(target_arch = "x86_64")]
 pub(crate) const CR0_PG: u64 = 1 << 31;
+#[cfg(target_arch = "x86_64")]
 pub(crate) const EFER_LME: u64 = 1 << 8;
+#[cfg(target_arch = "x86_64")]
 pub(crate) const EFER_LMA: u64 = 1 << 10;
+#[cfg(target_arch = "x86_64")]
 pub(crate) const EFER_SCE: u64 = 1;
+#[cfg(target_arch = "x86_64")]
 pub(crate) const EFER_NX: u64 = 1 << 11;
 
+/// The debug registers of a vCPU. `addresses` holds the breakpoint
+/// addresses in DR0-DR3, which are enabled and configured by `dr7`.
+#[cfg(target_arch = "x86_64")]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct DebugRegisters {
+    /// The breakpoint addresses in DR0-DR3
+    pub addresses: [u64; 4],
+    /// The debug status register
+    pub dr6: u64,
+    /// The debug control register
+    pub dr7: u64,
+}
+
 /// These are the generic exit reasons that we can handle from a Hypervisor the Hypervisors run method is responsible for mapping from
 /// the hypervisor specific exit reasons to these generic ones
-pub enum HyperlightExit {
+pub(crate) enum HyperlightExit {
     /// The vCPU has halted
     Halt(),
     /// The vCPU has issued a write to the given port with the given value
@@ -93,6 +137,14 @@ pub enum HyperlightExit {
     Mmio(u64),
     /// The vCPU tried to access memory but was missing the required permissions
     AccessViolation(u64, MemoryRegionFlags, MemoryRegionFlags),
+    /// The vCPU has shut down, for example because the guest triple faulted
+    Shutdown(),
+    /// The hypervisor failed to enter the vCPU, with the given hypervisor specific reason
+    FailEntry(u64),
+    /// The vCPU hit a breakpoint or watchpoint set in its debug registers,
+    /// at the given instruction pointer and with the given DR6
+    #[cfg(target_arch = "x86_64")]
+    Debug(u64, u64),
     /// The vCPU execution has been cancelled
     Cancelled(),
     /// The vCPU has exited for a reason that is not handled by Hyperlight
@@ -101,6 +153,25 @@ pub enum HyperlightExit {
     Retry(),
 }
 
+impl HyperlightExit {
+    /// A short name for the kind of exit, for profiles and metrics
+    pub(crate) fn reason(&self) -> &'static str {
+        match self {
+            HyperlightExit::Halt() => "halt",
+            HyperlightExit::IoOut(..) => "io",
+            HyperlightExit::Mmio(_) => "mmio",
+            HyperlightExit::AccessViolation(..) => "access_violation",
+            HyperlightExit::Shutdown() => "shutdown",
+            HyperlightExit::FailEntry(_) => "fail_entry",
+            #[cfg(target_arch = "x86_64")]
+            HyperlightExit::Debug(..) => "debug",
+            HyperlightExit::Cancelled() => "cancelled",
+            HyperlightExit::Unknown(_) => "unknown",
+            HyperlightExit::Retry() => "retry",
+        }
+    }
+}
+
 /// A common set of hypervisor functionality
 ///
 /// Note: a lot of these structures take in an `Option<HypervisorHandler>`.
@@ -108,7 +179,8 @@ pub enum HyperlightExit {
 /// to account for the fact the Hypervisor was set up beforehand.
 pub(crate) trait Hypervisor: Debug + Sync + Send {
     /// Initialise the internally stored vCPU with the given PEB address and
-    /// random number seed, then run it until a HLT instruction.
+    /// random number seed, then run it until a HLT instruction. Calls start
+    /// from the FPU state the guest is left in, see `restore_fpu_state`.
     #[allow(clippy::too_many_arguments)]
     fn initialise(
         &mut self,
@@ -148,6 +220,33 @@ pub(crate) trait Hypervisor: Debug + Sync + Send {
     /// Run the vCPU
     fn run(&mut self) -> Result<HyperlightExit>;
 
+    /// Save the FPU and SIMD state of the vCPU, including the extended
+    /// state managed by XSAVE, on top of the previously saved states.
+    fn save_fpu_state(&mut self) -> Result<()>;
+
+    /// Restore the FPU and SIMD state of the vCPU to the state saved by
+    /// the last call to `save_fpu_state`.
+    ///
+    /// Drivers save the default state when they are created, and the state
+    /// the guest is left in once it is initialized, and restore the last
+    /// saved state before running any guest code, so that FPU and SIMD
+    /// registers a guest clobbers don't leak into the next call. The saved
+    /// states are kept in step with the sandbox's memory snapshots.
+    fn restore_fpu_state(&mut self) -> Result<()>;
+
+    /// Discard the state saved by the last call to `save_fpu_state`, so
+    /// that the state saved before it is restored from then on. The state
+    /// saved when the driver was created is never discarded.
+    fn discard_fpu_state(&mut self) -> Result<()>;
+
+    /// The transport the guest should use to signal the host. Hypervisors
+    /// that select `OutBTransport::Mmio` must report writes to the MMIO
+    /// doorbell region from `run` as `HyperlightExit::IoOut`, so that the
+    /// rest of the guest/host protocol is the same for both transports.
+    fn outb_transport(&self) -> OutBTransport {
+        OutBTransport::PortIo
+    }
+
     /// Returns a Some(HyperlightExit::AccessViolation(..)) if the given gpa doesn't have
     /// access its corresponding region. Returns None otherwise, or if the region is not found.
     fn get_memory_access_violation(
@@ -180,6 +279,33 @@ pub(crate) trait Hypervisor: Debug + Sync + Send {
         log::max_level() as u32
     }
 
+    /// The vCPU's instruction pointer, followed by the return addresses
+    /// found by unwinding the guest's stack. Drivers that can't read the
+    /// vCPU's registers return an empty backtrace.
+    fn backtrace(&self) -> Result<Vec<u64>> {
+        Ok(Vec::new())
+    }
+
+    /// Set the vCPU's debug registers. Breakpoints and watchpoints they
+    /// enable stop the guest with a `HyperlightExit::Debug` instead of
+    /// being delivered to it.
+    #[cfg(target_arch = "x86_64")]
+    fn set_debug_registers(&mut self, _regs: &DebugRegisters) -> Result<()> {
+        log_then_return!("Hardware breakpoints are not supported by this hypervisor");
+    }
+
+    /// Get the vCPU's debug registers
+    #[cfg(target_arch = "x86_64")]
+    fn debug_registers(&self) -> Result<DebugRegisters> {
+        log_then_return!("Hardware breakpoints are not supported by this hypervisor");
+    }
+
+    /// Where to read the statistics the hypervisor keeps about the vCPU, if
+    /// it keeps any
+    fn vcpu_stats_source(&self) -> Option<Arc<dyn VcpuStatsSource>> {
+        None
+    }
+
     /// get a mutable trait object from self
     fn as_mut_hypervisor(&mut self) -> &mut dyn Hypervisor;
 
@@ -192,7 +318,7 @@ pub(crate) trait Hypervisor: Debug + Sync + Send {
 }
 
 /// A virtual CPU that can be run until an exit occurs
-pub struct VirtualCPU {}
+pub(crate) struct VirtualCPU {}
 
 impl VirtualCPU {
     /// Run the given hypervisor until a halt instruction is reached
@@ -203,17 +329,54 @@ impl VirtualCPU {
         outb_handle_fn: Arc<Mutex<dyn OutBHandlerCaller>>,
         mem_access_fn: Arc<Mutex<dyn MemAccessHandlerCaller>>,
     ) -> Result<()> {
+        #[cfg(crashdump)]
+        let guest_info = hv_handler.as_ref().map(|h| h.guest_info().clone());
+        #[cfg(crashdump)]
+        let redaction = hv_handler
+            .as_ref()
+            .map(|h| h.redaction())
+            .unwrap_or_default();
+
+        #[cfg(feature = "otel")]
+        let otel = hv_handler.as_ref().map(|h| h.events().otel().clone());
+        let profiler = hv_handler.as_ref().map(|h| h.events().profiler().clone());
+
         loop {
-            match hv.run() {
+            let entered = Instant::now();
+            let exit = hv.run();
+            let reason = exit.as_ref().map_or("error", HyperlightExit::reason);
+            #[cfg(feature = "otel")]
+            if let Some(otel) = &otel {
+                otel.record_vm_exit(reason);
+            }
+            // times how long this exit takes to handle, until the end of
+            // this iteration of the loop
+            let _exit_timer = profiler.as_ref().and_then(|profiler| {
+                profiler.record_guest_run(entered.elapsed());
+                profiler.time_exit(reason)
+            });
+            match exit {
                 Ok(HyperlightExit::Halt()) => {
                     break;
                 }
                 Ok(HyperlightExit::IoOut(port, data, rip, instruction_length)) => {
-                    hv.handle_io(port, data, rip, instruction_length, outb_handle_fn.clone())?
+                    match hv.handle_io(port, data, rip, instruction_length, outb_handle_fn.clone())
+                    {
+                        // record where the guest was when it aborted, without
+                        // letting a failure to unwind hide the abort itself
+                        Err(HyperlightError::GuestAborted { code, message, .. }) => {
+                            return Err(HyperlightError::GuestAborted {
+                                code,
+                                message,
+                                backtrace: hv.backtrace().unwrap_or_default(),
+                            });
+                        }
+                        result => result?,
+                    }
                 }
                 Ok(HyperlightExit::Mmio(addr)) => {
                     #[cfg(crashdump)]
-                    crashdump::crashdump_to_tempfile(hv)?;
+                    crashdump::crashdump_to_tempfile(hv, guest_info.as_ref(), redaction)?;
 
                     mem_access_fn
                         .clone()
@@ -225,7 +388,7 @@ impl VirtualCPU {
                 }
                 Ok(HyperlightExit::AccessViolation(addr, tried, region_permission)) => {
                     #[cfg(crashdump)]
-                    crashdump::crashdump_to_tempfile(hv)?;
+                    crashdump::crashdump_to_tempfile(hv, guest_info.as_ref(), redaction)?;
 
                     if region_permission.intersects(MemoryRegionFlags::STACK_GUARD) {
                         return Err(HyperlightError::StackOverflow());
@@ -236,7 +399,35 @@ impl VirtualCPU {
                         region_permission
                     ));
                 }
+                Ok(HyperlightExit::Shutdown()) => {
+                    #[cfg(crashdump)]
+                    crashdump::crashdump_to_tempfile(hv, guest_info.as_ref(), redaction)?;
+
+                    log_then_return!("vCPU shut down unexpectedly");
+                }
+                Ok(HyperlightExit::FailEntry(reason)) => {
+                    #[cfg(crashdump)]
+                    crashdump::crashdump_to_tempfile(hv, guest_info.as_ref(), redaction)?;
+
+                    log_then_return!("Failed to enter vCPU, reason {:#x}", reason);
+                }
+                #[cfg(target_arch = "x86_64")]
+                Ok(HyperlightExit::Debug(rip, dr6)) => {
+                    log_then_return!(HyperlightError::GuestDebugTrap(rip, dr6));
+                }
                 Ok(HyperlightExit::Cancelled()) => {
+                    // the sampling profiler interrupts the vCPU to see where
+                    // the guest is, and then it carries on
+                    #[cfg(target_os = "linux")]
+                    if let Some(sampler) = hv_handler
+                        .as_ref()
+                        .filter(|hvh| !hvh.cancel_requested())
+                        .and_then(HypervisorHandler::sampler)
+                    {
+                        sampler.record(hv.backtrace().unwrap_or_default());
+                        continue;
+                    }
+
                     // Shutdown is returned when the host has cancelled execution
                     // After termination, the main thread will re-initialize the VM
                     if let Some(hvh) = hv_handler {
@@ -251,14 +442,14 @@ impl VirtualCPU {
                 }
                 Ok(HyperlightExit::Unknown(reason)) => {
                     #[cfg(crashdump)]
-                    crashdump::crashdump_to_tempfile(hv)?;
+                    crashdump::crashdump_to_tempfile(hv, guest_info.as_ref(), redaction)?;
 
                     log_then_return!("Unexpected VM Exit {:?}", reason);
                 }
                 Ok(HyperlightExit::Retry()) => continue,
                 Err(e) => {
                     #[cfg(crashdump)]
-                    crashdump::crashdump_to_tempfile(hv)?;
+                    crashdump::crashdump_to_tempfile(hv, guest_info.as_ref(), redaction)?;
 
                     return Err(e);
                 }
@@ -319,6 +510,20 @@ pub(crate) mod tests {
             max_wait_for_cancellation: Duration::from_millis(
                 SandboxConfiguration::DEFAULT_MAX_WAIT_FOR_CANCELLATION as u64,
             ),
+            call_deadline: Arc::new(Mutex::new(None)),
+            busy_poll_window: None,
+            cpuid_options: None,
+            time_options: None,
+            #[cfg(target_os = "linux")]
+            cpu_affinity: Vec::new(),
+            #[cfg(all(feature = "cgroup", target_os = "linux"))]
+            cgroup: None,
+            redaction: sandbox.redaction,
+            guest_info: sandbox.guest_info,
+            sandbox_id: sandbox.registration.id(),
+            sandbox_name: sandbox.registration.name(),
+            events: sandbox.events,
+            creation_report: Arc::new(Mutex::new(sandbox.creation_report)),
         };
 
         let mut hv_handler = HypervisorHandler::new(hv_handler_config);
@@ -0,0 +1,117 @@
+/*
+Copyright 2024 The Hyperlight Authors.
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+    http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::thread;
+use std::time::Duration;
+
+use tracing::{instrument, Span};
+
+use crate::sandbox::GuestProfile;
+use crate::Result;
+
+/// Interrupts the vCPU every `interval` while it is running the guest, so
+/// that `VirtualCPU::run` can record where the guest was.
+///
+/// The interrupts are made from a thread that only holds a weak reference
+/// to the profiler, and stops when the profiler is dropped.
+pub(crate) struct SamplingProfiler {
+    /// The most return addresses recorded for each sample, in addition to
+    /// the instruction pointer
+    stack_depth: usize,
+    profile: Mutex<GuestProfile>,
+}
+
+#[cfg_attr(target_os = "windows", allow(dead_code))]
+impl SamplingProfiler {
+    /// Start interrupting the vCPU every `interval` by calling `interrupt`
+    #[instrument(err(Debug), skip(interrupt), parent = Span::current(), level = "Trace")]
+    pub(crate) fn start(
+        interval: Duration,
+        stack_depth: usize,
+        interrupt: impl Fn() + Send + 'static,
+    ) -> Result<Arc<Self>> {
+        let profiler = Arc::new(Self {
+            stack_depth,
+            profile: Mutex::new(GuestProfile::new(interval)),
+        });
+        let weak = Arc::downgrade(&profiler);
+        thread::Builder::new()
+            .name("hyperlight-sampler".to_string())
+            .spawn(move || {
+                thread::sleep(interval);
+                while weak.strong_count() > 0 {
+                    interrupt();
+                    thread::sleep(interval);
+                }
+            })?;
+        Ok(profiler)
+    }
+
+    /// Record a sample from the backtrace of the vCPU, which starts with
+    /// its instruction pointer
+    pub(crate) fn record(&self, mut backtrace: Vec<u64>) {
+        if backtrace.is_empty() {
+            // the hypervisor can't read the guest's stack
+            return;
+        }
+        backtrace.truncate(self.stack_depth + 1);
+        self.profile().record(backtrace);
+    }
+
+    /// Take the samples recorded since the profile was last taken
+    pub(crate) fn take_profile(&self) -> GuestProfile {
+        let mut profile = self.profile();
+        let interval = profile.interval;
+        std::mem::replace(&mut *profile, GuestProfile::new(interval))
+    }
+
+    fn profile(&self) -> MutexGuard<'_, GuestProfile> {
+        self.profile.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use super::*;
+
+    #[test]
+    fn interrupts_until_dropped() {
+        let interrupts = Arc::new(AtomicUsize::new(0));
+        let counter = interrupts.clone();
+        let profiler = SamplingProfiler::start(Duration::from_millis(1), 1, move || {
+            counter.fetch_add(1, Ordering::Relaxed);
+        })
+        .unwrap();
+        thread::sleep(Duration::from_millis(50));
+        assert!(interrupts.load(Ordering::Relaxed) > 0);
+
+        profiler.record(vec![0x1000, 0x2000, 0x3000]);
+        profiler.record(vec![]);
+        let profile = profiler.take_profile();
+        assert_eq!(profile.samples, 1);
+        assert_eq!(profile.stacks[&vec![0x1000, 0x2000]], 1);
+        assert_eq!(profiler.take_profile().samples, 0);
+
+        drop(profiler);
+        thread::sleep(Duration::from_millis(10));
+        let stopped_at = interrupts.load(Ordering::Relaxed);
+        thread::sleep(Duration::from_millis(10));
+        assert_eq!(interrupts.load(Ordering::Relaxed), stopped_at);
+    }
+}
@@ -0,0 +1,92 @@
+/*
+Copyright 2024 The Hyperlight Authors.
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+    http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+use std::sync::OnceLock;
+use std::time::{Duration, Instant};
+
+use tracing::{instrument, Span};
+
+/// Options controlling the time stamp counter (TSC) seen by the guest, so
+/// that timing sensitive guest code behaves the same way on hosts with
+/// different TSC frequencies, and when replaying recorded calls.
+///
+/// The options are only implemented by the KVM driver on x86-64, and
+/// evolving a sandbox with them set fails on other hypervisors.
+#[derive(Debug, Clone, Default)]
+#[cfg_attr(not(all(kvm, target_arch = "x86_64")), allow(dead_code))]
+pub struct TimeOptions {
+    pub(crate) tsc_khz: Option<u32>,
+    pub(crate) tsc_offset: Option<u64>,
+    pub(crate) pause_in_host: bool,
+}
+
+impl TimeOptions {
+    /// Create options that leave the guest's TSC as the hypervisor sets
+    /// it up.
+    #[instrument(skip_all, parent = Span::current(), level = "Trace")]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Run the guest's TSC at `khz`, scaling it if the host's TSC runs at
+    /// a different frequency. This requires hardware TSC scaling unless
+    /// `khz` is the host's TSC frequency.
+    #[instrument(skip_all, parent = Span::current(), level = "Trace")]
+    pub fn with_tsc_frequency(mut self, khz: u32) -> Self {
+        self.tsc_khz = Some(khz);
+        self
+    }
+
+    /// Set the offset added to the host's TSC to give the guest's TSC.
+    #[instrument(skip_all, parent = Span::current(), level = "Trace")]
+    pub fn with_tsc_offset(mut self, offset: u64) -> Self {
+        self.tsc_offset = Some(offset);
+        self
+    }
+
+    /// Stop the guest's TSC while the host handles the guest's requests,
+    /// such as calls to host functions, so that the time the host takes
+    /// is not visible to the guest.
+    #[instrument(skip_all, parent = Span::current(), level = "Trace")]
+    pub fn pause_during_host_calls(mut self) -> Self {
+        self.pause_in_host = true;
+        self
+    }
+}
+
+/// The frequency of the host's TSC in kHz, which the guest's TSC runs at
+/// unless it is scaled, measured the first time it is needed. This is 0
+/// on hosts without a TSC.
+pub(crate) fn host_tsc_khz() -> u64 {
+    static TSC_KHZ: OnceLock<u64> = OnceLock::new();
+    *TSC_KHZ.get_or_init(|| {
+        #[cfg(target_arch = "x86_64")]
+        {
+            use core::arch::x86_64::_rdtsc;
+
+            // spin rather than sleep, so that the measurement isn't thrown
+            // off by the thread waking up late
+            let start = Instant::now();
+            // SAFETY: reading the TSC has no side effects
+            let start_tsc = unsafe { _rdtsc() };
+            while start.elapsed() < Duration::from_millis(1) {}
+            let ticks = unsafe { _rdtsc() }.wrapping_sub(start_tsc);
+            (ticks as u128 * 1000 / start.elapsed().as_micros()) as u64
+        }
+        #[cfg(not(target_arch = "x86_64"))]
+        0
+    })
+}
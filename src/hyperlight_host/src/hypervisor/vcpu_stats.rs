@@ -0,0 +1,51 @@
+/*
+Copyright 2024 The Hyperlight Authors.
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+    http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+use std::collections::BTreeMap;
+
+use crate::Result;
+
+/// The statistics the hypervisor keeps about the vCPU running a sandbox,
+/// such as how many times it exited and how long it spent halt polling,
+/// see `Sandbox::vcpu_stats`.
+///
+/// Statistics are named and scaled the way the hypervisor reports them, so
+/// on KVM, for example, there are `exits`, `io_exits`, `mmio_exits`,
+/// `halt_exits` and `halt_poll_success_ns`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct VcpuStats {
+    /// The statistics that have a single value, by name
+    pub counters: BTreeMap<String, u64>,
+    /// The statistics that are histograms, by name, with a value for each
+    /// bucket
+    pub histograms: BTreeMap<String, Vec<u64>>,
+}
+
+impl VcpuStats {
+    /// The value of the statistic called `name`, if the hypervisor reports
+    /// one with a single value
+    pub fn get(&self, name: &str) -> Option<u64> {
+        self.counters.get(name).copied()
+    }
+}
+
+/// Reads the statistics of a vCPU. Drivers return one from
+/// `Hypervisor::vcpu_stats_source`, and it may be read from any thread,
+/// while the vCPU is running.
+pub(crate) trait VcpuStatsSource: Send + Sync {
+    /// Read the current values of the vCPU's statistics
+    fn read(&self) -> Result<VcpuStats>;
+}
@@ -499,6 +499,47 @@ impl VMProcessor {
         Ok(())
     }
 
+    #[instrument(err(Debug), skip_all, parent = Span::current(), level= "Trace")]
+    pub(super) fn get_xsave(&self) -> Result<Vec<u8>> {
+        // the first call fails with WHV_E_INSUFFICIENT_BUFFER, but tells us
+        // how big the buffer needs to be
+        let mut buffer_size = 0;
+        let _ = unsafe {
+            WHvGetVirtualProcessorXsaveState(
+                self.get_partition_hdl(),
+                0,
+                std::ptr::null_mut(),
+                0,
+                &mut buffer_size,
+            )
+        };
+
+        let mut xsave = vec![0u8; buffer_size as usize];
+        unsafe {
+            WHvGetVirtualProcessorXsaveState(
+                self.get_partition_hdl(),
+                0,
+                xsave.as_mut_ptr() as *mut c_void,
+                xsave.len() as u32,
+                &mut buffer_size,
+            )?;
+        }
+        Ok(xsave)
+    }
+
+    #[instrument(err(Debug), skip_all, parent = Span::current(), level= "Trace")]
+    pub(super) fn set_xsave(&mut self, xsave: &[u8]) -> Result<()> {
+        unsafe {
+            WHvSetVirtualProcessorXsaveState(
+                self.get_partition_hdl(),
+                0,
+                xsave.as_ptr() as *const c_void,
+                xsave.len() as u32,
+            )?;
+        }
+        Ok(())
+    }
+
     #[instrument(err(Debug), skip_all, parent = Span::current(), level= "Trace")]
     pub(super) fn run(&mut self) -> Result<WHV_RUN_VP_EXIT_CONTEXT> {
         let partition_handle = self.get_partition_hdl();
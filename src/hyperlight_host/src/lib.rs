@@ -67,6 +67,8 @@ pub mod mem;
 /// Metric definitions and helpers
 #[deny(dead_code, missing_docs, unused_mut)]
 pub mod metrics;
+#[deny(dead_code, missing_docs, unused_mut)]
+pub mod prelude;
 /// The main sandbox implementations. Do not use this module directly in code
 /// outside this file. Types from this module needed for public consumption are
 /// re-exported below.
@@ -14,20 +14,34 @@ See the License for the specific language governing permissions and
 limitations under the License.
 */
 
+use std::ops::Range;
+
 #[cfg(target_arch = "aarch64")]
 use goblin::elf::reloc::{R_AARCH64_NONE, R_AARCH64_RELATIVE};
 #[cfg(target_arch = "x86_64")]
 use goblin::elf::reloc::{R_X86_64_NONE, R_X86_64_RELATIVE};
-use goblin::elf::{Elf, ProgramHeaders, Reloc};
-use goblin::elf64::program_header::PT_LOAD;
+use goblin::elf::{Elf, ProgramHeader, ProgramHeaders, Reloc};
+use goblin::elf64::program_header::{PF_W, PT_LOAD};
+use goblin::elf64::section_header::SHT_NOBITS;
+use hyperlight_common::guest_function_metadata::SECTION_NAME as FUNCTION_RECORDS_SECTION;
+use hyperlight_common::mem::PAGE_SIZE_USIZE;
 
 use crate::{log_then_return, new_error, Result};
 
+/// The section LLVM's SanitizerCoverage puts inline 8-bit counters in
+const COVERAGE_COUNTERS_SECTION: &str = "__sancov_cntrs";
+
 pub(crate) struct ElfInfo {
     payload: Vec<u8>,
     phdrs: ProgramHeaders,
     entry: u64,
     relocs: Vec<Reloc>,
+    /// The virtual addresses of the coverage counters, if the binary was
+    /// built with them
+    coverage_counters: Option<Range<u64>>,
+    /// Where in the file the records of the guest's functions are, if it
+    /// has any
+    function_records: Option<Range<usize>>,
 }
 
 impl ElfInfo {
@@ -41,11 +55,49 @@ impl ElfInfo {
         {
             log_then_return!("ELF must have at least one PT_LOAD header");
         }
+        let section = |name| {
+            elf.section_headers
+                .iter()
+                .find(|shdr| elf.shdr_strtab.get_at(shdr.sh_name) == Some(name))
+        };
+        // the headers of untrusted binaries are read by `GuestBinary::inspect`,
+        // so nothing about them can be assumed
+        let coverage_counters = match section(COVERAGE_COUNTERS_SECTION) {
+            Some(shdr) => match shdr.sh_addr.checked_add(shdr.sh_size) {
+                Some(end) => Some(shdr.sh_addr..end),
+                None => {
+                    log_then_return!("ELF section {} is out of bounds", COVERAGE_COUNTERS_SECTION);
+                }
+            },
+            None => None,
+        };
+        let function_records =
+            match section(FUNCTION_RECORDS_SECTION).filter(|shdr| shdr.sh_type != SHT_NOBITS) {
+                Some(shdr) => {
+                    let records = usize::try_from(shdr.sh_offset)
+                        .ok()
+                        .zip(usize::try_from(shdr.sh_size).ok())
+                        .and_then(|(start, len)| Some(start..start.checked_add(len)?))
+                        .filter(|records| records.end <= bytes.len());
+                    match records {
+                        Some(records) => Some(records),
+                        None => {
+                            log_then_return!(
+                                "ELF section {} is out of bounds",
+                                FUNCTION_RECORDS_SECTION
+                            );
+                        }
+                    }
+                }
+                None => None,
+            };
         Ok(ElfInfo {
             payload: bytes.to_vec(),
             phdrs: elf.program_headers,
             entry: elf.entry,
             relocs,
+            coverage_counters,
+            function_records,
         })
     }
     pub(crate) fn entrypoint_va(&self) -> u64 {
@@ -68,6 +120,49 @@ impl ElfInfo {
             .unwrap(); // guaranteed not to panic because of the check in new()
         (max_phdr.p_vaddr + max_phdr.p_memsz - self.get_base_va()) as usize
     }
+    /// The offsets of the coverage counters from the start of the loaded
+    /// image, if the binary was built with them
+    pub(crate) fn get_coverage_counters(&self) -> Option<Range<usize>> {
+        let base_va = self.get_base_va();
+        let counters = self.coverage_counters.as_ref()?;
+        // counters outside the image can't be read
+        let start = counters.start.checked_sub(base_va)? as usize;
+        let end = counters.end.checked_sub(base_va)? as usize;
+        (end <= self.get_va_size()).then_some(start..end)
+    }
+    /// The offsets from the start of the loaded image of the pages that
+    /// only hold segments the guest can't write to. Pages that a writable
+    /// segment shares are left out.
+    pub(crate) fn get_read_only_pages(&self) -> Vec<Range<usize>> {
+        let base_va = self.get_base_va();
+        let pages = |phdr: &ProgramHeader| {
+            let start = (phdr.p_vaddr - base_va) as usize;
+            let end = start + phdr.p_memsz as usize;
+            start / PAGE_SIZE_USIZE * PAGE_SIZE_USIZE..end.next_multiple_of(PAGE_SIZE_USIZE)
+        };
+        let (writable, read_only): (Vec<_>, Vec<_>) = self
+            .phdrs
+            .iter()
+            .filter(|phdr| phdr.p_type == PT_LOAD)
+            .partition(|phdr| phdr.p_flags & PF_W != 0);
+        let writable: Vec<_> = writable.into_iter().map(pages).collect();
+        read_only
+            .into_iter()
+            .map(pages)
+            .filter(|pages| {
+                !writable
+                    .iter()
+                    .any(|w| w.start < pages.end && pages.start < w.end)
+            })
+            .collect()
+    }
+    /// The contents of the section holding the records of the guest's
+    /// functions, if it has one
+    pub(crate) fn get_function_records(&self) -> Option<&[u8]> {
+        self.function_records
+            .clone()
+            .and_then(|records| self.payload.get(records))
+    }
     pub(crate) fn load_at(&self, load_addr: usize, target: &mut [u8]) -> Result<()> {
         let base_va = self.get_base_va();
         for phdr in self.phdrs.iter().filter(|phdr| phdr.p_type == PT_LOAD) {
@@ -111,3 +206,132 @@ impl ElfInfo {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A minimal ELF file with one PT_LOAD segment and a function records
+    /// section at `records_offset` of `records_size` bytes
+    fn elf_with_function_records(records_offset: u64, records_size: u64) -> Vec<u8> {
+        const PHDR_OFFSET: u64 = 64;
+        const SHDR_OFFSET: u64 = PHDR_OFFSET + 56;
+        const STRTAB_OFFSET: u64 = SHDR_OFFSET + 3 * 64;
+        let strtab = b"\0.shstrtab\0.hlfuncs\0";
+
+        let mut elf = Vec::new();
+        elf.extend_from_slice(b"\x7fELF\x02\x01\x01\0\0\0\0\0\0\0\0\0");
+        elf.extend_from_slice(&2u16.to_le_bytes()); // e_type: EXEC
+        elf.extend_from_slice(&62u16.to_le_bytes()); // e_machine: x86_64
+        elf.extend_from_slice(&1u32.to_le_bytes()); // e_version
+        elf.extend_from_slice(&0x1000u64.to_le_bytes()); // e_entry
+        elf.extend_from_slice(&PHDR_OFFSET.to_le_bytes());
+        elf.extend_from_slice(&SHDR_OFFSET.to_le_bytes());
+        elf.extend_from_slice(&0u32.to_le_bytes()); // e_flags
+        for half in [64u16, 56, 1, 64, 3, 1] {
+            // e_ehsize, e_phentsize, e_phnum, e_shentsize, e_shnum, e_shstrndx
+            elf.extend_from_slice(&half.to_le_bytes());
+        }
+
+        // PT_LOAD, R+X, the whole file at 0x1000
+        elf.extend_from_slice(&1u32.to_le_bytes());
+        elf.extend_from_slice(&5u32.to_le_bytes());
+        for word in [0u64, 0x1000, 0x1000, 0x200, 0x200, 0x1000] {
+            elf.extend_from_slice(&word.to_le_bytes());
+        }
+
+        let mut shdr = |name: u32, ty: u32, offset: u64, size: u64| {
+            elf.extend_from_slice(&name.to_le_bytes());
+            elf.extend_from_slice(&ty.to_le_bytes());
+            for word in [0u64, 0, offset, size] {
+                elf.extend_from_slice(&word.to_le_bytes());
+            }
+            elf.extend_from_slice(&[0; 24]);
+        };
+        shdr(0, 0, 0, 0);
+        shdr(1, 3, STRTAB_OFFSET, strtab.len() as u64);
+        shdr(11, 1, records_offset, records_size);
+
+        elf.extend_from_slice(strtab);
+        elf.resize(0x200, 0);
+        elf
+    }
+
+    #[test]
+    fn function_records_out_of_bounds() {
+        let elf = ElfInfo::new(&elf_with_function_records(0x1f0, 0x10)).unwrap();
+        assert_eq!(elf.get_function_records(), Some(&[0u8; 0x10][..]));
+
+        for (offset, size) in [(0x1f0, 0x11), (u64::MAX, 2), (2, u64::MAX)] {
+            assert!(ElfInfo::new(&elf_with_function_records(offset, size)).is_err());
+        }
+    }
+
+    /// Check the guest at `path` has the layout `hyperlight_guest.ld` gives
+    /// guests: code, read-only data and writable data, each in a page
+    /// aligned segment of its own, in that order from address 0, with the
+    /// function records in the read-only data
+    fn assert_hyperlight_guest_layout(path: &std::path::Path) {
+        use goblin::elf64::program_header::{PF_R, PF_W, PF_X};
+
+        let bytes = std::fs::read(path).unwrap();
+        let elf = Elf::parse(&bytes).unwrap();
+        let segments: Vec<_> = elf
+            .program_headers
+            .iter()
+            .filter(|phdr| phdr.p_type == PT_LOAD)
+            .collect();
+        let flags: Vec<_> = segments.iter().map(|phdr| phdr.p_flags).collect();
+        assert_eq!(flags, vec![PF_R | PF_X, PF_R, PF_R | PF_W]);
+        assert_eq!(segments[0].p_vaddr, 0);
+        for pair in segments.windows(2) {
+            assert_eq!(pair[1].p_vaddr % 0x1000, 0);
+            assert!(pair[0].p_vaddr + pair[0].p_memsz <= pair[1].p_vaddr);
+        }
+
+        let records = elf
+            .section_headers
+            .iter()
+            .find(|shdr| elf.shdr_strtab.get_at(shdr.sh_name) == Some(FUNCTION_RECORDS_SECTION))
+            .unwrap();
+        assert!(segments[1].vm_range().contains(&(records.sh_addr as usize)));
+    }
+
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn guests_have_the_hyperlight_guest_layout() {
+        for guest in ["simpleguest", "callbackguest"] {
+            assert_hyperlight_guest_layout(&hyperlight_testing::rust_guest_as_pathbuf(guest));
+        }
+    }
+
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn code_and_read_only_data_are_read_only() {
+        let bytes =
+            std::fs::read(hyperlight_testing::rust_guest_as_pathbuf("simpleguest")).unwrap();
+        let elf = ElfInfo::new(&bytes).unwrap();
+        // the code and read-only data segments, but not the writable data
+        let pages = elf.get_read_only_pages();
+        assert_eq!(pages.len(), 2);
+        assert_eq!(pages[0].start, 0);
+        assert!(pages[1].end < elf.get_va_size());
+    }
+
+    /// Run by `just test-guest-target-spec`, once it has built simpleguest
+    /// for the target spec that ships with `hyperlight_guest`
+    #[test]
+    #[ignore]
+    fn target_spec_guest_has_the_hyperlight_guest_layout() {
+        let profile = if cfg!(debug_assertions) {
+            "debug"
+        } else {
+            "release"
+        };
+        let path = std::path::Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("../tests/rust_guests/simpleguest/target/x86_64-hyperlight-none")
+            .join(profile)
+            .join("simpleguest");
+        assert_hyperlight_guest_layout(&path);
+    }
+}
@@ -16,13 +16,16 @@ limitations under the License.
 
 use std::fs::File;
 use std::io::Read;
+use std::ops::Range;
 use std::vec::Vec;
 
+use hyperlight_common::guest_function_metadata::{decode_section, GuestFunctionSignature};
+
 use super::elf::ElfInfo;
 use super::pe::headers::PEHeaders;
 use super::pe::pe_info::PEInfo;
 use super::ptr_offset::Offset;
-use crate::Result;
+use crate::{new_error, Result};
 
 // This is used extremely infrequently, so being unusually large for PE
 // files _really_ doesn't matter, and probably isn't really worth the
@@ -70,6 +73,40 @@ impl ExeInfo {
             ExeInfo::Elf(elf) => Offset::from(elf.entrypoint_va()),
         }
     }
+    /// The offsets of the SanitizerCoverage counters from the start of the
+    /// loaded image, if the binary was built with them. Only ELF binaries
+    /// are supported.
+    pub fn coverage_counters(&self) -> Option<Range<usize>> {
+        match self {
+            ExeInfo::PE(_) => None,
+            ExeInfo::Elf(elf) => elf.get_coverage_counters(),
+        }
+    }
+    /// The signatures of the guest functions recorded in the binary by
+    /// `register_guest_function!`, or `None` if the guest didn't record
+    /// any
+    pub fn guest_functions(&self) -> Result<Option<Vec<GuestFunctionSignature>>> {
+        let records = match self {
+            ExeInfo::PE(pe) => pe.get_function_records()?,
+            ExeInfo::Elf(elf) => elf.get_function_records(),
+        };
+        records
+            .map(|records| {
+                decode_section(records)
+                    .map_err(|e| new_error!("Failed to read the guest's functions: {}", e))
+            })
+            .transpose()
+    }
+    /// The offsets from the start of the loaded image of the pages that
+    /// hold only code or read-only data, which the guest can't write to.
+    /// Only ELF binaries are split into segments with different
+    /// permissions, so the whole of a PE binary is writable.
+    pub fn read_only_pages(&self) -> Vec<Range<usize>> {
+        match self {
+            ExeInfo::PE(_) => Vec::new(),
+            ExeInfo::Elf(elf) => elf.get_read_only_pages(),
+        }
+    }
     pub fn loaded_size(&self) -> usize {
         match self {
             ExeInfo::PE(pe) => pe.payload.len(),
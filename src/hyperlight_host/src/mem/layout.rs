@@ -17,10 +17,10 @@ limitations under the License.
 use std::fmt::Debug;
 use std::mem::{offset_of, size_of};
 
-use hyperlight_common::mem::{GuestStackData, HyperlightPEB, RunMode, PAGE_SIZE_USIZE};
+use hyperlight_common::mem::{
+    CallDeadlineData, HyperlightPEB, OutBTransport, RunMode, PAGE_SIZE_USIZE,
+};
 use paste::paste;
-use rand::rngs::OsRng;
-use rand::RngCore;
 use tracing::{instrument, Span};
 
 use super::memory_region::MemoryRegionType::{
@@ -29,8 +29,9 @@ use super::memory_region::MemoryRegionType::{
 };
 use super::memory_region::{MemoryRegion, MemoryRegionFlags, MemoryRegionVecBuilder};
 use super::mgr::AMOUNT_OF_MEMORY_PER_PT;
-use super::shared_mem::{ExclusiveSharedMemory, GuestSharedMemory, SharedMemory};
+use super::shared_mem::{ExclusiveSharedMemory, SharedMemory};
 use crate::error::HyperlightError::{GuestOffsetIsInvalid, MemoryRequestTooBig};
+use crate::sandbox::randomness::SandboxRng;
 use crate::sandbox::SandboxConfiguration;
 use crate::{log_then_return, new_error, Result};
 
@@ -125,6 +126,7 @@ pub(crate) struct SandboxMemoryLayout {
     /// The following fields are offsets to the actual PEB struct fields.
     /// They are used when writing the PEB struct itself
     peb_offset: usize,
+    peb_guest_abi_version_offset: usize, // set by guest in guest entrypoint
     peb_security_cookie_seed_offset: usize,
     peb_guest_dispatch_function_ptr_offset: usize, // set by guest in guest entrypoint
     pub(super) peb_host_function_definitions_offset: usize,
@@ -132,11 +134,18 @@ pub(crate) struct SandboxMemoryLayout {
     peb_guest_error_offset: usize,
     peb_code_and_outb_pointer_offset: usize,
     peb_runmode_offset: usize,
+    peb_outb_transport_offset: usize,
     peb_input_data_offset: usize,
     peb_output_data_offset: usize,
+    peb_compression_data_offset: usize,
+    peb_shared_buffer_integrity_offset: usize,
     peb_guest_panic_context_offset: usize,
     peb_heap_data_offset: usize,
     peb_guest_stack_data_offset: usize,
+    peb_guest_env_offset: usize,
+    peb_startup_args_offset: usize,
+    peb_released_memory_offset: usize,
+    peb_call_deadline_offset: usize,
 
     // The following are the actual values
     // that are written to the PEB struct
@@ -177,6 +186,10 @@ impl Debug for SandboxMemoryLayout {
             .field("PEB Address", &format_args!("{:#x}", self.peb_address))
             .field("PEB Offset", &format_args!("{:#x}", self.peb_offset))
             .field("Code Size", &format_args!("{:#x}", self.code_size))
+            .field(
+                "Guest ABI Version Offset",
+                &format_args!("{:#x}", self.peb_guest_abi_version_offset),
+            )
             .field(
                 "Security Cookie Seed Offset",
                 &format_args!("{:#x}", self.peb_security_cookie_seed_offset),
@@ -209,6 +222,14 @@ impl Debug for SandboxMemoryLayout {
                 "Output Data Offset",
                 &format_args!("{:#x}", self.peb_output_data_offset),
             )
+            .field(
+                "Compression Data Offset",
+                &format_args!("{:#x}", self.peb_compression_data_offset),
+            )
+            .field(
+                "Shared Buffer Integrity Offset",
+                &format_args!("{:#x}", self.peb_shared_buffer_integrity_offset),
+            )
             .field(
                 "Guest Panic Context Offset",
                 &format_args!("{:#x}", self.peb_guest_panic_context_offset),
@@ -221,6 +242,22 @@ impl Debug for SandboxMemoryLayout {
                 "Guest Stack Offset",
                 &format_args!("{:#x}", self.peb_guest_stack_data_offset),
             )
+            .field(
+                "Guest Env Offset",
+                &format_args!("{:#x}", self.peb_guest_env_offset),
+            )
+            .field(
+                "Startup Args Offset",
+                &format_args!("{:#x}", self.peb_startup_args_offset),
+            )
+            .field(
+                "Released Memory Offset",
+                &format_args!("{:#x}", self.peb_released_memory_offset),
+            )
+            .field(
+                "Call Deadline Offset",
+                &format_args!("{:#x}", self.peb_call_deadline_offset),
+            )
             .field(
                 "Host Function Definitions Buffer Offset",
                 &format_args!("{:#x}", self.host_function_definitions_buffer_offset),
@@ -329,6 +366,7 @@ impl SandboxMemoryLayout {
         let guest_code_offset = total_page_table_size;
         // The following offsets are to the fields of the PEB struct itself!
         let peb_offset = total_page_table_size + round_up_to(code_size, PAGE_SIZE_USIZE);
+        let peb_guest_abi_version_offset = peb_offset + offset_of!(HyperlightPEB, guestAbiVersion);
         let peb_security_cookie_seed_offset =
             peb_offset + offset_of!(HyperlightPEB, security_cookie_seed);
         let peb_guest_dispatch_function_ptr_offset =
@@ -339,19 +377,27 @@ impl SandboxMemoryLayout {
         let peb_guest_error_offset = peb_offset + offset_of!(HyperlightPEB, guestErrorData);
         let peb_code_and_outb_pointer_offset = peb_offset + offset_of!(HyperlightPEB, pCode);
         let peb_runmode_offset = peb_offset + offset_of!(HyperlightPEB, runMode);
+        let peb_outb_transport_offset = peb_offset + offset_of!(HyperlightPEB, outbTransport);
         let peb_input_data_offset = peb_offset + offset_of!(HyperlightPEB, inputdata);
         let peb_output_data_offset = peb_offset + offset_of!(HyperlightPEB, outputdata);
+        let peb_compression_data_offset = peb_offset + offset_of!(HyperlightPEB, compressionData);
+        let peb_shared_buffer_integrity_offset =
+            peb_offset + offset_of!(HyperlightPEB, sharedBufferIntegrity);
         let peb_guest_panic_context_offset =
             peb_offset + offset_of!(HyperlightPEB, guestPanicContextData);
         let peb_heap_data_offset = peb_offset + offset_of!(HyperlightPEB, guestheapData);
         let peb_guest_stack_data_offset = peb_offset + offset_of!(HyperlightPEB, gueststackData);
+        let peb_guest_env_offset = peb_offset + offset_of!(HyperlightPEB, guestEnvData);
+        let peb_startup_args_offset = peb_offset + offset_of!(HyperlightPEB, startupArgsData);
+        let peb_released_memory_offset = peb_offset + offset_of!(HyperlightPEB, releasedMemoryData);
+        let peb_call_deadline_offset = peb_offset + offset_of!(HyperlightPEB, callDeadlineData);
 
         // The following offsets are the actual values that relate to memory layout,
         // which are written to PEB struct
         let peb_address = Self::BASE_ADDRESS + peb_offset;
         // make sure host function definitions buffer starts at 4K boundary
         let host_function_definitions_buffer_offset = round_up_to(
-            peb_guest_stack_data_offset + size_of::<GuestStackData>(),
+            peb_call_deadline_offset + size_of::<CallDeadlineData>(),
             PAGE_SIZE_USIZE,
         );
         // make sure host exception buffer starts at 4K boundary
@@ -368,11 +414,11 @@ impl SandboxMemoryLayout {
             PAGE_SIZE_USIZE,
         );
         let output_data_buffer_offset = round_up_to(
-            input_data_buffer_offset + cfg.get_input_data_size(),
+            input_data_buffer_offset + cfg.get_max_input_data_size(),
             PAGE_SIZE_USIZE,
         );
         let guest_panic_context_buffer_offset = round_up_to(
-            output_data_buffer_offset + cfg.get_output_data_size(),
+            output_data_buffer_offset + cfg.get_max_output_data_size(),
             PAGE_SIZE_USIZE,
         );
         // make sure heap buffer starts at 4K boundary
@@ -396,6 +442,7 @@ impl SandboxMemoryLayout {
             peb_offset,
             stack_size: stack_size_rounded,
             heap_size,
+            peb_guest_abi_version_offset,
             peb_security_cookie_seed_offset,
             peb_guest_dispatch_function_ptr_offset,
             peb_host_function_definitions_offset,
@@ -403,11 +450,18 @@ impl SandboxMemoryLayout {
             peb_guest_error_offset,
             peb_code_and_outb_pointer_offset,
             peb_runmode_offset,
+            peb_outb_transport_offset,
             peb_input_data_offset,
             peb_output_data_offset,
+            peb_compression_data_offset,
+            peb_shared_buffer_integrity_offset,
             peb_guest_panic_context_offset,
             peb_heap_data_offset,
             peb_guest_stack_data_offset,
+            peb_guest_env_offset,
+            peb_startup_args_offset,
+            peb_released_memory_offset,
+            peb_call_deadline_offset,
             guest_error_buffer_offset,
             sandbox_memory_config: cfg,
             code_size,
@@ -435,6 +489,11 @@ impl SandboxMemoryLayout {
         self.peb_runmode_offset
     }
 
+    /// Gets the offset in guest memory to the OutBTransport field in the PEB struct.
+    pub(super) fn get_outb_transport_offset(&self) -> usize {
+        self.peb_outb_transport_offset
+    }
+
     /// Get the offset in guest memory to the size field in the
     /// `HostExceptionData` structure.
     #[instrument(skip_all, parent = Span::current(), level= "Trace")]
@@ -473,7 +532,7 @@ impl SandboxMemoryLayout {
     /// Get the offset in guest memory to the host function definitions
     /// pointer.
     #[instrument(skip_all, parent = Span::current(), level= "Trace")]
-    fn get_host_function_definitions_pointer_offset(&self) -> usize {
+    pub(super) fn get_host_function_definitions_pointer_offset(&self) -> usize {
         // The size field is the field after the size field in the `HostFunctions` struct which is a u64
         self.peb_host_function_definitions_offset + size_of::<u64>()
     }
@@ -520,6 +579,38 @@ impl SandboxMemoryLayout {
         self.get_output_data_size_offset() + size_of::<u64>()
     }
 
+    /// Get the offset in guest memory to the size the output data buffer
+    /// can grow to.
+    #[instrument(skip_all, parent = Span::current(), level= "Trace")]
+    fn get_output_data_max_size_offset(&self) -> usize {
+        // This field is immediately after the output data pointer, which
+        // is a `u64`.
+        self.get_output_data_pointer_offset() + size_of::<u64>()
+    }
+
+    /// Get the offset in guest memory to the size above which `VecBytes`
+    /// values may be compressed.
+    #[instrument(skip_all, parent = Span::current(), level= "Trace")]
+    fn get_compression_threshold_offset(&self) -> usize {
+        self.peb_compression_data_offset
+    }
+
+    /// Get the offset in guest memory to the compression the guest says
+    /// it can decode.
+    #[instrument(skip_all, parent = Span::current(), level= "Trace")]
+    pub(super) fn get_guest_compression_support_offset(&self) -> usize {
+        // This field is immediately after the compression threshold,
+        // which is a `u64`.
+        self.get_compression_threshold_offset() + size_of::<u64>()
+    }
+
+    /// Get the offset in guest memory to the flag that turns integrity
+    /// checks on.
+    #[instrument(skip_all, parent = Span::current(), level= "Trace")]
+    fn get_integrity_enabled_offset(&self) -> usize {
+        self.peb_shared_buffer_integrity_offset
+    }
+
     /// Get the offset in guest memory to the start of output data.
     ///
     /// This function exists to accommodate the macro that generates C API
@@ -552,6 +643,13 @@ impl SandboxMemoryLayout {
         self.peb_code_and_outb_pointer_offset
     }
 
+    /// Get the offset in guest memory to where the guest writes the ABI
+    /// version it was built against
+    #[instrument(skip_all, parent = Span::current(), level= "Trace")]
+    pub(super) fn get_guest_abi_version_offset(&self) -> usize {
+        self.peb_guest_abi_version_offset
+    }
+
     /// Get the offset in guest memory to where the guest dispatch function
     /// pointer is written
     #[instrument(skip_all, parent = Span::current(), level= "Trace")]
@@ -630,6 +728,76 @@ impl SandboxMemoryLayout {
         self.get_guest_panic_context_size_offset() + size_of::<u64>()
     }
 
+    /// Get the offset to the size of the guest's environment
+    #[instrument(skip_all, parent = Span::current(), level= "Trace")]
+    pub(super) fn get_guest_env_size_offset(&self) -> usize {
+        // The size field is the first field in the `GuestEnvData` data
+        self.peb_guest_env_offset
+    }
+
+    /// Get the offset to the pointer to the guest's environment
+    #[instrument(skip_all, parent = Span::current(), level= "Trace")]
+    pub(super) fn get_guest_env_pointer_offset(&self) -> usize {
+        // The pointer is immediately after the size field in the
+        // `GuestEnvData` data which is a `u64`
+        self.get_guest_env_size_offset() + size_of::<u64>()
+    }
+
+    /// Get the offset to the size of the arguments to `hyperlight_main`
+    #[instrument(skip_all, parent = Span::current(), level= "Trace")]
+    pub(super) fn get_startup_args_size_offset(&self) -> usize {
+        // The size field is the first field in the `StartupArgsData` data
+        self.peb_startup_args_offset
+    }
+
+    /// Get the offset to the pointer to the arguments to `hyperlight_main`
+    #[instrument(skip_all, parent = Span::current(), level= "Trace")]
+    pub(super) fn get_startup_args_pointer_offset(&self) -> usize {
+        // The pointer is immediately after the size field in the
+        // `StartupArgsData` data which is a `u64`
+        self.get_startup_args_size_offset() + size_of::<u64>()
+    }
+
+    /// Get the offset to the offset into the heap of the pages the guest
+    /// wants the host to reclaim
+    #[instrument(skip_all, parent = Span::current(), level= "Trace")]
+    pub(super) fn get_released_memory_offset_offset(&self) -> usize {
+        // The offset field is the first field in the `ReleasedMemoryData`
+        // data
+        self.peb_released_memory_offset
+    }
+
+    /// Get the offset to the size of the pages the guest wants the host to
+    /// reclaim
+    #[instrument(skip_all, parent = Span::current(), level= "Trace")]
+    pub(super) fn get_released_memory_size_offset(&self) -> usize {
+        // The size field is immediately after the offset field in the
+        // `ReleasedMemoryData` data which is a `u64`
+        self.get_released_memory_offset_offset() + size_of::<u64>()
+    }
+
+    /// Get the offset to the nanoseconds the host gives the guest function
+    /// call it is dispatching
+    #[instrument(skip_all, parent = Span::current(), level= "Trace")]
+    pub(super) fn get_call_deadline_nanos_offset(&self) -> usize {
+        // The deadline is the first field in the `CallDeadlineData` data
+        self.peb_call_deadline_offset
+    }
+
+    /// Get the offset to the frequency of the guest's time stamp counter
+    #[instrument(skip_all, parent = Span::current(), level= "Trace")]
+    pub(super) fn get_guest_tsc_khz_offset(&self) -> usize {
+        // The frequency is immediately after the deadline field in the
+        // `CallDeadlineData` data which is a `u64`
+        self.get_call_deadline_nanos_offset() + size_of::<u64>()
+    }
+
+    /// Get the offset of the guest heap in shared memory
+    #[instrument(skip_all, parent = Span::current(), level= "Trace")]
+    pub(super) fn get_guest_heap_buffer_offset(&self) -> usize {
+        self.guest_heap_buffer_offset
+    }
+
     /// Get the offset to the guest panic context buffer pointer
     #[instrument(skip_all, parent = Span::current(), level= "Trace")]
     pub(crate) fn get_guest_panic_context_buffer_offset(&self) -> usize {
@@ -724,8 +892,8 @@ impl SandboxMemoryLayout {
         total_mapped_memory_size +=
             round_up_to(cfg.get_host_function_definition_size(), PAGE_SIZE_USIZE);
         total_mapped_memory_size += round_up_to(cfg.get_guest_error_buffer_size(), PAGE_SIZE_USIZE);
-        total_mapped_memory_size += round_up_to(cfg.get_input_data_size(), PAGE_SIZE_USIZE);
-        total_mapped_memory_size += round_up_to(cfg.get_output_data_size(), PAGE_SIZE_USIZE);
+        total_mapped_memory_size += round_up_to(cfg.get_max_input_data_size(), PAGE_SIZE_USIZE);
+        total_mapped_memory_size += round_up_to(cfg.get_max_output_data_size(), PAGE_SIZE_USIZE);
         total_mapped_memory_size +=
             round_up_to(cfg.get_guest_panic_context_buffer_size(), PAGE_SIZE_USIZE);
         total_mapped_memory_size += round_up_to(size_of::<HyperlightPEB>(), PAGE_SIZE_USIZE);
@@ -772,7 +940,7 @@ impl SandboxMemoryLayout {
 
     /// Returns the memory regions associated with this memory layout,
     /// suitable for passing to a hypervisor for mapping into memory
-    pub fn get_memory_regions(&self, shared_mem: &GuestSharedMemory) -> Result<Vec<MemoryRegion>> {
+    pub fn get_memory_regions<S: SharedMemory>(&self, shared_mem: &S) -> Result<Vec<MemoryRegion>> {
         let mut builder = MemoryRegionVecBuilder::new(Self::BASE_ADDRESS, shared_mem.base_addr());
 
         // PML4, PDPT, PD
@@ -881,7 +1049,7 @@ impl SandboxMemoryLayout {
 
         // guest input data
         let output_data_offset = builder.push_page_aligned(
-            self.sandbox_memory_config.get_input_data_size(),
+            self.sandbox_memory_config.get_max_input_data_size(),
             MemoryRegionFlags::READ | MemoryRegionFlags::WRITE,
             InputData,
         );
@@ -899,7 +1067,7 @@ impl SandboxMemoryLayout {
 
         // guest output data
         let guest_panic_context_offset = builder.push_page_aligned(
-            self.sandbox_memory_config.get_output_data_size(),
+            self.sandbox_memory_config.get_max_output_data_size(),
             MemoryRegionFlags::READ | MemoryRegionFlags::WRITE,
             OutputData,
         );
@@ -1075,6 +1243,7 @@ impl SandboxMemoryLayout {
         guest_offset: usize,
         size: usize,
         run_inprocess: bool,
+        rng: &mut SandboxRng,
     ) -> Result<()> {
         macro_rules! get_address {
             ($something:ident) => {
@@ -1099,8 +1268,7 @@ impl SandboxMemoryLayout {
         // Start of setting up the PEB. The following are in the order of the PEB fields
 
         // Set up the security cookie seed
-        let mut security_cookie_seed = [0u8; 8];
-        OsRng.fill_bytes(&mut security_cookie_seed);
+        let security_cookie_seed: [u8; 8] = rng.bytes();
         shared_mem.copy_from_slice(&security_cookie_seed, self.peb_security_cookie_seed_offset)?;
 
         // Skip guest_dispatch_function_ptr_offset because it is set by the guest
@@ -1134,7 +1302,12 @@ impl SandboxMemoryLayout {
         )?;
 
         // Skip code, is set when loading binary
-        // skip outb and outb context, is set when running in_proc
+        // The outb pointer and context are host addresses, set when running
+        // in_proc. Clear them otherwise, so a guest in a VM never sees them
+        if !run_inprocess {
+            shared_mem.write_u64(self.get_outb_pointer_offset(), 0)?;
+            shared_mem.write_u64(self.get_outb_context_offset(), 0)?;
+        }
 
         // Set RunMode in PEB
         shared_mem.write_u64(
@@ -1151,6 +1324,13 @@ impl SandboxMemoryLayout {
             },
         )?;
 
+        // Guests signal the host with port I/O unless the hypervisor says
+        // otherwise, see `SandboxMemoryManager::set_outb_transport`
+        shared_mem.write_u64(
+            self.get_outb_transport_offset(),
+            OutBTransport::PortIo as u64,
+        )?;
+
         // Set up input buffer pointer
         shared_mem.write_u64(
             self.get_input_data_size_offset(),
@@ -1170,6 +1350,31 @@ impl SandboxMemoryLayout {
         )?;
         let addr = get_address!(output_data_buffer);
         shared_mem.write_u64(self.get_output_data_pointer_offset(), addr)?;
+        shared_mem.write_u64(
+            self.get_output_data_max_size_offset(),
+            self.sandbox_memory_config
+                .get_max_output_data_size()
+                .try_into()?,
+        )?;
+
+        // Let the guest know how large a value must be before it is
+        // compressed. The guest says what it can decompress when it starts.
+        shared_mem.write_u64(
+            self.get_compression_threshold_offset(),
+            self.sandbox_memory_config
+                .get_compression_threshold()
+                .try_into()?,
+        )?;
+        shared_mem.write_u64(self.get_guest_compression_support_offset(), 0)?;
+
+        // Tell the guest whether to tag and check the elements on the
+        // shared buffers
+        shared_mem.write_u64(
+            self.get_integrity_enabled_offset(),
+            self.sandbox_memory_config
+                .get_shared_buffer_integrity()
+                .into(),
+        )?;
 
         // Set up the guest panic context buffer
         let addr = get_address!(guest_panic_context_buffer);
@@ -1237,6 +1442,17 @@ impl SandboxMemoryLayout {
 
         shared_mem.write_u64(self.get_boot_stack_pointer_offset(), start_of_boot_stack)?;
 
+        // The guest has no environment or startup arguments until the host
+        // gives it some, see `SandboxMemoryManager::write_host_data`
+        shared_mem.write_u64(self.get_guest_env_size_offset(), 0)?;
+        shared_mem.write_u64(self.get_guest_env_pointer_offset(), 0)?;
+        shared_mem.write_u64(self.get_startup_args_size_offset(), 0)?;
+        shared_mem.write_u64(self.get_startup_args_pointer_offset(), 0)?;
+        shared_mem.write_u64(self.get_released_memory_offset_offset(), 0)?;
+        shared_mem.write_u64(self.get_released_memory_size_offset(), 0)?;
+        shared_mem.write_u64(self.get_call_deadline_nanos_offset(), 0)?;
+        shared_mem.write_u64(self.get_guest_tsc_khz_offset(), 0)?;
+
         // End of setting up the PEB
 
         // Initialize the stack pointers of input data and output data
@@ -1299,9 +1515,9 @@ mod tests {
 
         expected_size += round_up_to(cfg.get_guest_error_buffer_size(), PAGE_SIZE_USIZE);
 
-        expected_size += round_up_to(cfg.get_input_data_size(), PAGE_SIZE_USIZE);
+        expected_size += round_up_to(cfg.get_max_input_data_size(), PAGE_SIZE_USIZE);
 
-        expected_size += round_up_to(cfg.get_output_data_size(), PAGE_SIZE_USIZE);
+        expected_size += round_up_to(cfg.get_max_output_data_size(), PAGE_SIZE_USIZE);
 
         expected_size += round_up_to(cfg.get_guest_panic_context_buffer_size(), PAGE_SIZE_USIZE);
 
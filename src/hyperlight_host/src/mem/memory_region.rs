@@ -154,6 +154,28 @@ pub struct MemoryRegion {
     pub(crate) region_type: MemoryRegionType,
 }
 
+impl MemoryRegion {
+    /// The range of guest physical addresses the region is mapped at
+    pub fn guest_region(&self) -> Range<usize> {
+        self.guest_region.clone()
+    }
+
+    /// The range of host virtual addresses backing the region
+    pub fn host_region(&self) -> Range<usize> {
+        self.host_region.clone()
+    }
+
+    /// The permissions the guest has on the region
+    pub fn flags(&self) -> MemoryRegionFlags {
+        self.flags
+    }
+
+    /// What the region is used for
+    pub fn region_type(&self) -> MemoryRegionType {
+        self.region_type
+    }
+}
+
 pub(crate) struct MemoryRegionVecBuilder {
     guest_base_phys_addr: usize,
     host_base_virt_addr: usize,
@@ -15,18 +15,30 @@ limitations under the License.
 */
 
 use core::mem::size_of;
-use std::cmp::Ordering;
+use std::cmp::{max, min, Ordering};
+use std::collections::HashMap;
+use std::ops::Range;
 use std::str::from_utf8;
 use std::sync::{Arc, Mutex};
+use std::time::Duration;
 
+use flatbuffers::FlatBufferBuilder;
+use hmac::{Hmac, Mac};
+use hyperlight_common::flatbuffer_wrappers::compression::LZ4_SUPPORTED;
 use hyperlight_common::flatbuffer_wrappers::function_call::{
     validate_guest_function_call_buffer, FunctionCall,
 };
 use hyperlight_common::flatbuffer_wrappers::function_types::ReturnValue;
 use hyperlight_common::flatbuffer_wrappers::guest_error::{ErrorCode, GuestError};
+use hyperlight_common::flatbuffer_wrappers::guest_function_details::GuestFunctionDetails;
 use hyperlight_common::flatbuffer_wrappers::guest_log_data::GuestLogData;
 use hyperlight_common::flatbuffer_wrappers::host_function_details::HostFunctionDetails;
+use hyperlight_common::integrity::{verify_integrity_tag, INTEGRITY_TAG_LEN};
+use hyperlight_common::mem::{OutBTransport, CHUNK_FLAG, GUEST_ABI_VERSION, PAGE_SIZE_USIZE};
+use hyperlight_common::outb::doorbell_port;
+use hyperlight_common::secret::zeroize;
 use serde_json::from_str;
+use sha2::Sha256;
 use tracing::{instrument, Span};
 
 use super::exe::ExeInfo;
@@ -39,10 +51,12 @@ use super::ptr_offset::Offset;
 use super::shared_mem::{ExclusiveSharedMemory, GuestSharedMemory, HostSharedMemory, SharedMemory};
 use super::shared_mem_snapshot::SharedMemorySnapshot;
 use crate::error::HyperlightError::{
-    ExceptionDataLengthIncorrect, ExceptionMessageTooBig, JsonConversionFailure, NoMemorySnapshot,
+    ExceptionDataLengthIncorrect, ExceptionMessageTooBig, GuestAbiMismatch,
+    GuestInitializationFailed, JsonConversionFailure, NoMemorySnapshot, SharedBufferIntegrity,
     UTF8SliceConversionFailure,
 };
 use crate::error::HyperlightHostError;
+use crate::func::GuestFunctionId;
 use crate::sandbox::SandboxConfiguration;
 use crate::{log_then_return, new_error, HyperlightError, Result};
 
@@ -57,16 +71,60 @@ const PAGE_RW: u64 = 1 << 1; // Page is Read/Write (if not set page is read only
 const PAGE_USER: u64 = 1 << 2; // User/Supervisor (if this bit is set then the page is accessible by user mode code)
 const PAGE_NX: u64 = 1 << 63; // Execute Disable (if this bit is set then data in the page cannot be executed)
 
+/// Convert a page table entry for a 4K page from the x86 format that
+/// `set_up_shared_memory` builds the tables in to the format of the
+/// architecture the guest runs on.
+///
+/// The other levels of the tables need no conversion, as an entry that is
+/// present and writable on x86 is also a valid table descriptor on aarch64.
+#[cfg(target_arch = "x86_64")]
+fn leaf_page_table_entry(entry: u64) -> u64 {
+    entry
+}
+
+/// Convert a page table entry for a 4K page from the x86 format that
+/// `set_up_shared_memory` builds the tables in to the format of the
+/// architecture the guest runs on.
+///
+/// The other levels of the tables need no conversion, as an entry that is
+/// present and writable on x86 is also a valid table descriptor on aarch64.
+#[cfg(target_arch = "aarch64")]
+fn leaf_page_table_entry(entry: u64) -> u64 {
+    const PAGE_DESCRIPTOR: u64 = 0b11;
+    const INNER_SHAREABLE: u64 = 3 << 8;
+    const ACCESS_FLAG: u64 = 1 << 10;
+    const READ_ONLY: u64 = 1 << 7;
+    const EXECUTE_NEVER: u64 = 3 << 53; // both PXN and UXN
+    const ADDRESS_MASK: u64 = 0x0000_ffff_ffff_f000;
+
+    if entry & PAGE_PRESENT == 0 {
+        return 0;
+    }
+    // PAGE_USER has no equivalent: the guest runs at EL1, and aarch64 never
+    // lets EL1 execute pages that EL0 can write to. Memory attribute index 0
+    // is normal memory, see `setup_initial_sregs` in the KVM driver.
+    let mut descriptor = (entry & ADDRESS_MASK) | PAGE_DESCRIPTOR | INNER_SHAREABLE | ACCESS_FLAG;
+    if entry & PAGE_RW == 0 {
+        descriptor |= READ_ONLY;
+    }
+    if entry & PAGE_NX != 0 {
+        descriptor |= EXECUTE_NEVER;
+    }
+    descriptor
+}
+
 // The amount of memory that can be mapped per page table
 pub(super) const AMOUNT_OF_MEMORY_PER_PT: usize = 0x200000;
 /// Read/write permissions flag for the 64-bit PDE
 /// The page size for the 64-bit PDE
 /// The size of stack guard cookies
 pub(crate) const STACK_COOKIE_LEN: usize = 16;
+/// The size of the key the host tags the elements it pushes onto the
+/// input buffer with
+pub(crate) const INTEGRITY_KEY_LEN: usize = 32;
 
 /// A struct that is responsible for laying out and managing the memory
 /// for a given `Sandbox`.
-#[derive(Clone)]
 pub(crate) struct SandboxMemoryManager<S> {
     /// Shared memory for the Sandbox
     pub(crate) shared_mem: S,
@@ -81,6 +139,44 @@ pub(crate) struct SandboxMemoryManager<S> {
     /// A vector of memory snapshots that can be used to save and  restore the state of the memory
     /// This is used by the Rust Sandbox implementation (rather than the mem_snapshot field above which only exists to support current C API)
     snapshots: Arc<Mutex<Vec<SharedMemorySnapshot>>>,
+    /// Scratch buffer that elements popped off the shared output buffer
+    /// are copied into, kept around so it is only allocated once
+    scratch_buffer: Vec<u8>,
+    /// Flatbuffer builder reused to serialize values written to the
+    /// shared input buffer
+    fb_builder: FlatBufferBuilder<'static>,
+    /// The segments of a value the guest is sending in segments, because
+    /// it is too large for the output buffer, received so far
+    output_segments: Vec<u8>,
+    /// A value being sent to the guest in segments, because it is too
+    /// large for the input buffer
+    input_segments: PendingInput,
+    /// The names of the functions the guest assigned ids to during
+    /// initialisation, the function with id `n` being at index `n - 1`
+    guest_function_names: Vec<Arc<str>>,
+    /// The ids in `guest_function_names`, by name
+    guest_function_ids: HashMap<Arc<str>, GuestFunctionId>,
+    /// Set when the guest aborted part way through a call, which leaves
+    /// its memory in an unknown state until it is restored from a snapshot
+    poisoned: bool,
+    /// The offsets in shared memory of the guest's coverage counters, if
+    /// it was built with them
+    coverage_counters: Option<Range<usize>>,
+    /// The guest addresses of the pages of the guest binary that the guest
+    /// must not write to, which are mapped read-only
+    read_only_code: Vec<Range<usize>>,
+    /// The coverage counters collected since they were last taken
+    coverage: Vec<u8>,
+    /// The LZ4 compressed contents of shared memory while the sandbox is
+    /// hibernating, see `hibernate`
+    hibernated: Option<Vec<u8>>,
+    /// Whether the protected regions of memory are checked after every
+    /// guest function call, see `MultiUseSandbox::set_isolation_audit`
+    isolation_audit: bool,
+    /// The key the host tags the elements it pushes onto the input buffer
+    /// with, if integrity checks are on. It is never written to shared
+    /// memory, so the guest can't forge the tags.
+    integrity_key: [u8; INTEGRITY_KEY_LEN],
     /// This field must be present, even though it's not read,
     /// so that its underlying resources are properly dropped at
     /// the right time.
@@ -88,6 +184,36 @@ pub(crate) struct SandboxMemoryManager<S> {
     _lib: Option<LoadedLib>,
 }
 
+impl<S: Clone> Clone for SandboxMemoryManager<S> {
+    // The scratch buffer and flatbuffer builder are not shared between
+    // clones; each clone lazily grows its own.
+    fn clone(&self) -> Self {
+        Self {
+            shared_mem: self.shared_mem.clone(),
+            layout: self.layout,
+            inprocess: self.inprocess,
+            load_addr: self.load_addr.clone(),
+            entrypoint_offset: self.entrypoint_offset,
+            snapshots: self.snapshots.clone(),
+            scratch_buffer: Vec::new(),
+            fb_builder: FlatBufferBuilder::new(),
+            output_segments: Vec::new(),
+            input_segments: PendingInput::default(),
+            guest_function_names: self.guest_function_names.clone(),
+            guest_function_ids: self.guest_function_ids.clone(),
+            poisoned: self.poisoned,
+            coverage_counters: self.coverage_counters.clone(),
+            read_only_code: self.read_only_code.clone(),
+            coverage: self.coverage.clone(),
+            hibernated: self.hibernated.clone(),
+            isolation_audit: self.isolation_audit,
+            integrity_key: self.integrity_key,
+            #[cfg(target_os = "windows")]
+            _lib: self._lib.clone(),
+        }
+    }
+}
+
 impl<S> SandboxMemoryManager<S>
 where
     S: SharedMemory,
@@ -109,6 +235,19 @@ where
             load_addr,
             entrypoint_offset,
             snapshots: Arc::new(Mutex::new(Vec::new())),
+            scratch_buffer: Vec::new(),
+            fb_builder: FlatBufferBuilder::new(),
+            output_segments: Vec::new(),
+            input_segments: PendingInput::default(),
+            guest_function_names: Vec::new(),
+            guest_function_ids: HashMap::new(),
+            poisoned: false,
+            coverage_counters: None,
+            read_only_code: Vec::new(),
+            coverage: Vec::new(),
+            hibernated: None,
+            isolation_audit: false,
+            integrity_key: [0; INTEGRITY_KEY_LEN],
             #[cfg(target_os = "windows")]
             _lib: lib,
         }
@@ -152,6 +291,7 @@ where
             + self.layout.stack_size as u64
             - 0x28;
 
+        let read_only_code = &self.read_only_code;
         self.shared_mem.with_exclusivity(|shared_mem| {
             // Create PDL4 table with only 1 PML4E
             shared_mem.write_u64(
@@ -190,12 +330,25 @@ where
                     let offset = SandboxMemoryLayout::PT_OFFSET + (p * 4096) + (i * 8);
                     // Each PTE maps a 4KB page
                     let val_to_write = if p == 0 {
-                        (p << 21) as u64 | (i << 12) as u64
+                        let addr = (p << 21) as u64 | (i << 12) as u64;
+                        // Nothing is mapped into the guest below 0x200000, except for the
+                        // MMIO doorbell region, which has no memory behind it so that
+                        // every write to it exits to the host
+                        match doorbell_port(addr) {
+                            Some(_) => addr | PAGE_PRESENT | PAGE_RW | PAGE_NX,
+                            None => addr,
+                        }
                     } else {
+                        let addr = (p << 21) + (i << 12);
                         let flags = match Self::get_page_flags(p, i, regions) {
                             Ok(region_type) => match region_type {
-                                // TODO: We parse and load the exe according to its sections and then
-                                // have the correct flags set rather than just marking the entire binary as executable
+                                // The guest binary's code and read-only data are readonly in
+                                // the guest, if they are in pages of their own
+                                MemoryRegionType::Code
+                                    if read_only_code.iter().any(|pages| pages.contains(&addr)) =>
+                                {
+                                    PAGE_PRESENT | PAGE_USER
+                                }
                                 MemoryRegionType::Code => PAGE_PRESENT | PAGE_RW | PAGE_USER,
                                 MemoryRegionType::Stack => {
                                     PAGE_PRESENT | PAGE_RW | PAGE_USER | PAGE_NX
@@ -231,7 +384,7 @@ where
                         };
                         ((p << 21) as u64 | (i << 12) as u64) | flags
                     };
-                    shared_mem.write_u64(offset, val_to_write)?;
+                    shared_mem.write_u64(offset, leaf_page_table_entry(val_to_write))?;
                 }
             }
             Ok::<(), HyperlightError>(())
@@ -301,7 +454,49 @@ where
             log_then_return!(NoMemorySnapshot);
         }
         let snapshot = last.unwrap();
-        snapshot.restore_from_snapshot(&mut self.shared_mem)
+        collect_coverage(
+            &mut self.shared_mem,
+            self.coverage_counters.as_ref(),
+            &mut self.coverage,
+        )?;
+        snapshot.restore_from_snapshot(&mut self.shared_mem)?;
+        // the memory a hibernating sandbox would have been woken with is
+        // stale now
+        self.hibernated = None;
+        self.poisoned = false;
+        self.output_segments.clear();
+        self.input_segments = PendingInput::default();
+        Ok(())
+    }
+
+    /// Take the coverage counters collected since they were last taken,
+    /// or `None` if the guest wasn't built with coverage counters.
+    ///
+    /// Restoring memory from a snapshot resets the guest's counters, so
+    /// they are collected before each restore. Each collected counter is
+    /// the highest value it reached in any call.
+    pub(crate) fn take_coverage(&mut self) -> Result<Option<Vec<u8>>> {
+        if self.coverage_counters.is_none() {
+            return Ok(None);
+        }
+        collect_coverage(
+            &mut self.shared_mem,
+            self.coverage_counters.as_ref(),
+            &mut self.coverage,
+        )?;
+        Ok(Some(std::mem::take(&mut self.coverage)))
+    }
+
+    /// Mark the memory as being in an unknown state, after the guest
+    /// aborted part way through a call.
+    pub(crate) fn set_poisoned(&mut self) {
+        self.poisoned = true;
+    }
+
+    /// Whether the guest aborted part way through a call since the memory
+    /// was last restored from a snapshot.
+    pub(crate) fn is_poisoned(&self) -> bool {
+        self.poisoned
     }
 
     /// this function pops the last snapshot off the stack and restores the memory to the previous state
@@ -324,6 +519,11 @@ where
     /// for calling outb function
     #[instrument(err(Debug), skip_all, parent = Span::current(), level= "Trace")]
     pub(crate) fn set_outb_address_and_context(&mut self, addr: u64, context: u64) -> Result<()> {
+        // these are host addresses, which a guest in a VM has no use for
+        // and must not learn
+        if !self.inprocess {
+            log_then_return!("The outb pointer and context are only set in in-process mode");
+        }
         let pointer_offset = self.layout.get_outb_pointer_offset();
         let context_offset = self.layout.get_outb_context_offset();
         self.shared_mem.with_exclusivity(|excl| -> Result<()> {
@@ -332,6 +532,289 @@ where
             Ok(())
         })?
     }
+
+    /// Tell the guest which transport to use to signal the host, as
+    /// chosen by the hypervisor the guest runs on
+    #[instrument(err(Debug), skip_all, parent = Span::current(), level= "Trace")]
+    pub(crate) fn set_outb_transport(&mut self, transport: OutBTransport) -> Result<()> {
+        let offset = self.layout.get_outb_transport_offset();
+        self.shared_mem
+            .with_exclusivity(|excl| excl.write_u64(offset, transport as u64))?
+    }
+}
+
+/// Merge the guest's coverage counters at `counters` in `shared_mem`
+/// into `coverage`, keeping the highest value of each counter
+fn collect_coverage<S: SharedMemory>(
+    shared_mem: &mut S,
+    counters: Option<&Range<usize>>,
+    coverage: &mut Vec<u8>,
+) -> Result<()> {
+    let Some(counters) = counters else {
+        return Ok(());
+    };
+    let current = shared_mem.with_exclusivity(|e| e.as_slice()[counters.clone()].to_vec())?;
+    coverage.resize(current.len(), 0);
+    for (collected, value) in coverage.iter_mut().zip(current) {
+        *collected = (*collected).max(value);
+    }
+    Ok(())
+}
+
+/// The size of the length prefix and stack pointer that each segment of
+/// a value sent in segments takes up in a buffer besides its data
+const SEGMENT_OVERHEAD: usize = size_of::<u32>() + size_of::<u64>();
+
+/// A value being sent to the guest in segments, see `CHUNK_FLAG`
+#[derive(Default)]
+struct PendingInput {
+    data: Vec<u8>,
+    /// How much of `data` has been pushed to the guest
+    sent: usize,
+}
+
+/// Push `data` onto the input buffer, first growing the buffer, up to
+/// the size reserved for it, if `data` doesn't fit. If it doesn't fit even
+/// then, and isn't larger than `SandboxConfiguration::set_max_segmented_data_size`
+/// allows, push the first segment of it and keep the rest in `pending`
+/// for the guest to ask for.
+fn push_input_data(
+    shared_mem: &mut HostSharedMemory,
+    layout: &SandboxMemoryLayout,
+    integrity_key: &[u8; INTEGRITY_KEY_LEN],
+    pending: &mut PendingInput,
+    data: &[u8],
+) -> Result<()> {
+    *pending = PendingInput::default();
+    check_input_integrity(shared_mem, layout, integrity_key)?;
+    let size_required = data.len() + 8 + integrity_tag_len(layout);
+    let (size, stack_pointer_rel) = input_data_space(shared_mem, layout, size_required)?;
+    if stack_pointer_rel.saturating_add(size_required) <= size {
+        return push_input_element(
+            shared_mem,
+            layout,
+            integrity_key,
+            size,
+            stack_pointer_rel,
+            data,
+        );
+    }
+    if data.len() > layout.sandbox_memory_config.get_max_segmented_data_size() {
+        log_then_return!(
+            "Input data of {} bytes doesn't fit in the input buffer and is too large to send in segments",
+            data.len()
+        );
+    }
+    pending.data.extend_from_slice(data);
+    push_input_segment(shared_mem, layout, integrity_key, pending)
+}
+
+/// Push as much of the rest of `pending` as fits onto the input buffer as
+/// one segment, with `CHUNK_FLAG` set in its length prefix if there is
+/// more to follow.
+fn push_input_segment(
+    shared_mem: &mut HostSharedMemory,
+    layout: &SandboxMemoryLayout,
+    integrity_key: &[u8; INTEGRITY_KEY_LEN],
+    pending: &mut PendingInput,
+) -> Result<()> {
+    let remaining = &pending.data[pending.sent..];
+    if remaining.is_empty() {
+        log_then_return!("The guest asked for a segment of input data, but there are none left");
+    }
+    check_input_integrity(shared_mem, layout, integrity_key)?;
+    let (size, stack_pointer_rel) = input_data_space(shared_mem, layout, 0)?;
+    let available = size
+        .saturating_sub(stack_pointer_rel)
+        .saturating_sub(SEGMENT_OVERHEAD + integrity_tag_len(layout));
+    if available == 0 {
+        log_then_return!("Not enough space in the input buffer for a segment of input data");
+    }
+    let len = min(remaining.len(), available);
+    let mut prefix = u32::try_from(len)?;
+    if len < remaining.len() {
+        prefix |= CHUNK_FLAG;
+    }
+    let mut segment = Vec::with_capacity(size_of::<u32>() + len);
+    segment.extend_from_slice(&prefix.to_le_bytes());
+    segment.extend_from_slice(&remaining[..len]);
+    pending.sent += len;
+    // zeroed whether or not it holds secrets, see `CHUNK_FLAG`
+    if pending.sent == pending.data.len() {
+        zeroize(&mut pending.data);
+        *pending = PendingInput::default();
+    }
+    let res = push_input_element(
+        shared_mem,
+        layout,
+        integrity_key,
+        size,
+        stack_pointer_rel,
+        &segment,
+    );
+    zeroize(&mut segment);
+    res
+}
+
+/// The length of the tag appended to each element pushed onto a shared
+/// buffer, which is 0 unless integrity checks are on
+fn integrity_tag_len(layout: &SandboxMemoryLayout) -> usize {
+    if layout.sandbox_memory_config.get_shared_buffer_integrity() {
+        INTEGRITY_TAG_LEN
+    } else {
+        0
+    }
+}
+
+/// The keyed tag for `element`, which starts `element_offset_rel` bytes
+/// into the input buffer
+fn input_integrity_mac(
+    integrity_key: &[u8; INTEGRITY_KEY_LEN],
+    element_offset_rel: usize,
+    element: &[u8],
+) -> Hmac<Sha256> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(integrity_key.as_slice())
+        .expect("HMAC accepts any key length");
+    mac.update(&(element_offset_rel as u64).to_le_bytes());
+    mac.update(element);
+    mac
+}
+
+/// Push `element` onto the input buffer, whose next free address is
+/// `stack_pointer_rel`, followed by its tag if integrity checks are on.
+/// The host checks the tag for as long as the element is on the buffer,
+/// see `check_input_integrity`.
+fn push_input_element(
+    shared_mem: &mut HostSharedMemory,
+    layout: &SandboxMemoryLayout,
+    integrity_key: &[u8; INTEGRITY_KEY_LEN],
+    size: usize,
+    stack_pointer_rel: usize,
+    element: &[u8],
+) -> Result<()> {
+    if integrity_tag_len(layout) == 0 {
+        return shared_mem.push_buffer(layout.input_data_buffer_offset, size, element);
+    }
+    let mut tagged = Vec::with_capacity(element.len() + INTEGRITY_TAG_LEN);
+    tagged.extend_from_slice(element);
+    tagged.extend_from_slice(
+        &input_integrity_mac(integrity_key, stack_pointer_rel, element)
+            .finalize()
+            .into_bytes(),
+    );
+    let res = shared_mem.push_buffer(layout.input_data_buffer_offset, size, &tagged);
+    zeroize(&mut tagged);
+    res
+}
+
+/// Check the tags of the elements the host pushed onto the input buffer
+/// that the guest hasn't popped yet, if integrity checks are on.
+///
+/// Only the host has the key the tags are computed with, so a guest that
+/// rewrites the data or framing of those elements, or moves the buffer's
+/// stack pointer anywhere but the start of one of them, can't make them
+/// pass the check.
+fn check_input_integrity(
+    shared_mem: &HostSharedMemory,
+    layout: &SandboxMemoryLayout,
+    integrity_key: &[u8; INTEGRITY_KEY_LEN],
+) -> Result<()> {
+    if integrity_tag_len(layout) == 0 {
+        return Ok(());
+    }
+    let offset = layout.input_data_buffer_offset;
+    let size = layout.sandbox_memory_config.get_max_input_data_size();
+    let mut stack_pointer_rel = usize::try_from(shared_mem.read::<u64>(offset)?)?;
+    if !(8..=size).contains(&stack_pointer_rel) {
+        return Err(SharedBufferIntegrity(format!(
+            "input buffer stack pointer {} is out of bounds",
+            stack_pointer_rel
+        )));
+    }
+    // each element is followed by its tag and the offset it starts at, so
+    // the elements are walked from the top of the buffer down to its start
+    while stack_pointer_rel > 8 {
+        let element_end = stack_pointer_rel.saturating_sub(8);
+        let element_offset_rel = usize::try_from(shared_mem.read::<u64>(offset + element_end)?)?;
+        // the guest can write the offset, so adding to it may overflow
+        if element_offset_rel < 8
+            || element_offset_rel
+                .checked_add(size_of::<u32>() + INTEGRITY_TAG_LEN)
+                .map_or(true, |min_end| min_end > element_end)
+        {
+            return Err(SharedBufferIntegrity(format!(
+                "input buffer element offset {} is out of bounds",
+                element_offset_rel
+            )));
+        }
+
+        let mut element = vec![0; element_end - element_offset_rel];
+        shared_mem.copy_to_slice(&mut element, offset + element_offset_rel)?;
+        let (data, tag) = element.split_at(element.len() - INTEGRITY_TAG_LEN);
+        let tag_matches = input_integrity_mac(integrity_key, element_offset_rel, data)
+            .verify_slice(tag)
+            .is_ok();
+        let framing_matches =
+            (segment_prefix(data)? & !CHUNK_FLAG) as usize + size_of::<u32>() == data.len();
+        // the element may hold secrets
+        zeroize(&mut element);
+        if !tag_matches {
+            return Err(SharedBufferIntegrity(format!(
+                "input buffer element at {} does not match its tag",
+                element_offset_rel
+            )));
+        }
+        if !framing_matches {
+            return Err(SharedBufferIntegrity(format!(
+                "input buffer element at {} has the wrong length prefix",
+                element_offset_rel
+            )));
+        }
+        stack_pointer_rel = element_offset_rel;
+    }
+    Ok(())
+}
+
+/// Get the size of the input buffer and the offset of the next free
+/// address in it, first growing the buffer, up to the size reserved for
+/// it, if fewer than `size_required` bytes are free.
+///
+/// The guest reads the size of the input buffer from the PEB whenever it
+/// uses it, so growing it is just a matter of updating the PEB.
+fn input_data_space(
+    shared_mem: &mut HostSharedMemory,
+    layout: &SandboxMemoryLayout,
+    size_required: usize,
+) -> Result<(usize, usize)> {
+    let max_size = layout.sandbox_memory_config.get_max_input_data_size();
+    let size_offset = layout.get_input_data_size_offset();
+    // the guest can write to the PEB, so don't trust the size to be
+    // within the buffer
+    let mut size = min(
+        usize::try_from(shared_mem.read::<u64>(size_offset)?)?,
+        max_size,
+    );
+    let stack_pointer_rel =
+        usize::try_from(shared_mem.read::<u64>(layout.input_data_buffer_offset)?)?;
+    let size_required = stack_pointer_rel.saturating_add(size_required);
+    if size_required > size && size < max_size {
+        size = min(
+            max(size * 2, size_required.next_multiple_of(PAGE_SIZE_USIZE)),
+            max_size,
+        );
+        shared_mem.write::<u64>(size_offset, size.try_into()?)?;
+    }
+    Ok((size, stack_pointer_rel))
+}
+
+/// Read the length prefix of a segment popped off a buffer
+fn segment_prefix(segment: &[u8]) -> Result<u32> {
+    Ok(u32::from_le_bytes(
+        segment
+            .get(..size_of::<u32>())
+            .ok_or_else(|| new_error!("Segment is too short for its length prefix"))?
+            .try_into()?,
+    ))
 }
 
 /// Common setup functionality for the
@@ -356,6 +839,15 @@ where
         usize::try_from(cfg.get_heap_size(exe_info))?,
     )?;
     let mut shared_mem = ExclusiveSharedMemory::new(layout.get_memory_size()?)?;
+    // bind the memory before anything is written to it, so that its pages
+    // are allocated on the right node in the first place
+    #[cfg(target_os = "linux")]
+    if let Some(node) = cfg.get_numa_node() {
+        shared_mem.bind_to_numa_node(node)?;
+    }
+    if cfg.get_prefault_memory() {
+        shared_mem.prefault()?;
+    }
 
     let load_addr: RawPtr = load_addr_fn(&shared_mem, &layout)?;
 
@@ -422,7 +914,7 @@ impl SandboxMemoryManager<ExclusiveSharedMemory> {
             &mut shared_mem.as_mut_slice()[layout.get_guest_code_offset()..],
         )?;
 
-        Ok(Self::new(
+        let mut mgr = Self::new(
             layout,
             shared_mem,
             inprocess,
@@ -430,7 +922,20 @@ impl SandboxMemoryManager<ExclusiveSharedMemory> {
             entrypoint_offset,
             #[cfg(target_os = "windows")]
             None,
-        ))
+        );
+        mgr.coverage_counters = exe_info.coverage_counters().map(|counters| {
+            let code_offset = layout.get_guest_code_offset();
+            code_offset + counters.start..code_offset + counters.end
+        });
+        if !inprocess {
+            let code_address = layout.get_guest_code_address();
+            mgr.read_only_code = exe_info
+                .read_only_pages()
+                .into_iter()
+                .map(|pages| code_address + pages.start..code_address + pages.end)
+                .collect();
+        }
+        Ok(mgr)
     }
 
     /// Similar to load_guest_binary_into_memory, except only works on Windows
@@ -508,6 +1013,69 @@ impl SandboxMemoryManager<ExclusiveSharedMemory> {
         Ok(())
     }
 
+    /// Write `startup_args`, the `FunctionCall` flatbuffer holding the
+    /// arguments to `hyperlight_main`, and `env`, a guest environment
+    /// encoded with `hyperlight_common::guest_env::encode`, where the guest
+    /// can read but not write them, replacing any written before. Either
+    /// may be empty.
+    ///
+    /// Both take up the end of the host function definitions buffer, so
+    /// `host_function_details_len` bytes of host function details must
+    /// still fit in what is left of it.
+    #[instrument(err(Debug), skip_all, parent = Span::current(), level= "Trace")]
+    pub(crate) fn write_host_data(
+        &mut self,
+        startup_args: &[u8],
+        env: &[u8],
+        host_function_details_len: usize,
+    ) -> Result<()> {
+        let available = self
+            .layout
+            .sandbox_memory_config
+            .get_host_function_definition_size();
+        let startup_args_size = startup_args.len().next_multiple_of(size_of::<u64>());
+        let env_size = env.len().next_multiple_of(size_of::<u64>());
+        if startup_args_size + env_size + host_function_details_len > available {
+            log_then_return!(
+                "The startup arguments and guest environment are {} bytes, but only {} bytes of the host function definitions buffer are free",
+                startup_args.len() + env.len(),
+                available.saturating_sub(host_function_details_len)
+            );
+        }
+
+        let host_function_definitions_size = available - startup_args_size - env_size;
+        let startup_args_offset =
+            self.layout.host_function_definitions_buffer_offset + host_function_definitions_size;
+        self.shared_mem
+            .copy_from_slice(startup_args, startup_args_offset)?;
+        self.shared_mem
+            .copy_from_slice(env, startup_args_offset + startup_args_size)?;
+
+        let host_function_definitions_addr = self
+            .shared_mem
+            .read_u64(self.layout.get_host_function_definitions_pointer_offset())?;
+        let startup_args_addr =
+            host_function_definitions_addr + host_function_definitions_size as u64;
+        self.shared_mem.write_u64(
+            self.layout.get_host_function_definitions_size_offset(),
+            host_function_definitions_size as u64,
+        )?;
+        self.shared_mem.write_u64(
+            self.layout.get_startup_args_size_offset(),
+            startup_args.len() as u64,
+        )?;
+        self.shared_mem.write_u64(
+            self.layout.get_startup_args_pointer_offset(),
+            startup_args_addr,
+        )?;
+        self.shared_mem
+            .write_u64(self.layout.get_guest_env_size_offset(), env.len() as u64)?;
+        self.shared_mem.write_u64(
+            self.layout.get_guest_env_pointer_offset(),
+            startup_args_addr + startup_args_size as u64,
+        )
+    }
+
     /// Set the stack guard to `cookie` using `layout` to calculate
     /// its location and `shared_mem` to write it.
     #[instrument(err(Debug), skip_all, parent = Span::current(), level= "Trace")]
@@ -516,6 +1084,12 @@ impl SandboxMemoryManager<ExclusiveSharedMemory> {
         self.shared_mem.copy_from_slice(cookie, stack_offset)
     }
 
+    /// Set the key the host tags the elements it pushes onto the input
+    /// buffer with, see `SandboxConfiguration::set_shared_buffer_integrity`
+    pub(crate) fn set_integrity_key(&mut self, key: [u8; INTEGRITY_KEY_LEN]) {
+        self.integrity_key = key;
+    }
+
     /// Wraps ExclusiveSharedMemory::build
     pub fn build(
         self,
@@ -532,6 +1106,19 @@ impl SandboxMemoryManager<ExclusiveSharedMemory> {
                 load_addr: self.load_addr.clone(),
                 entrypoint_offset: self.entrypoint_offset,
                 snapshots: Arc::new(Mutex::new(Vec::new())),
+                scratch_buffer: self.scratch_buffer,
+                fb_builder: self.fb_builder,
+                output_segments: Vec::new(),
+                input_segments: PendingInput::default(),
+                guest_function_names: Vec::new(),
+                guest_function_ids: HashMap::new(),
+                poisoned: false,
+                coverage_counters: self.coverage_counters.clone(),
+                read_only_code: self.read_only_code.clone(),
+                coverage: Vec::new(),
+                hibernated: None,
+                isolation_audit: self.isolation_audit,
+                integrity_key: self.integrity_key,
                 #[cfg(target_os = "windows")]
                 _lib: self._lib,
             },
@@ -542,6 +1129,19 @@ impl SandboxMemoryManager<ExclusiveSharedMemory> {
                 load_addr: self.load_addr.clone(),
                 entrypoint_offset: self.entrypoint_offset,
                 snapshots: Arc::new(Mutex::new(Vec::new())),
+                scratch_buffer: Vec::new(),
+                fb_builder: FlatBufferBuilder::new(),
+                output_segments: Vec::new(),
+                input_segments: PendingInput::default(),
+                guest_function_names: Vec::new(),
+                guest_function_ids: HashMap::new(),
+                poisoned: false,
+                coverage_counters: self.coverage_counters,
+                read_only_code: self.read_only_code,
+                coverage: Vec::new(),
+                hibernated: None,
+                isolation_audit: self.isolation_audit,
+                integrity_key: self.integrity_key,
                 #[cfg(target_os = "windows")]
                 _lib: None,
             },
@@ -550,6 +1150,92 @@ impl SandboxMemoryManager<ExclusiveSharedMemory> {
 }
 
 impl SandboxMemoryManager<HostSharedMemory> {
+    /// Replace the host function details the guest reads with `buffer`,
+    /// both in shared memory and in the snapshots it is restored from, so
+    /// that changes to the host functions of an initialized sandbox last
+    /// past the end of the next guest call
+    #[instrument(err(Debug), skip_all, parent = Span::current(), level= "Trace")]
+    pub(crate) fn update_host_function_details(&mut self, buffer: &[u8]) -> Result<()> {
+        let buffer_size = usize::try_from(
+            self.shared_mem
+                .read::<u64>(self.layout.get_host_function_definitions_size_offset())?,
+        )?;
+        if buffer.len() > buffer_size {
+            log_then_return!(
+                "Host Function Details buffer is too big for the host_function_definitions buffer"
+            );
+        }
+
+        let offset = self.layout.host_function_definitions_buffer_offset;
+        self.shared_mem.copy_from_slice(buffer, offset)?;
+        for snapshot in self
+            .snapshots
+            .try_lock()
+            .map_err(|e| new_error!("Error locking at {}:{}: {}", file!(), line!(), e))?
+            .iter_mut()
+        {
+            snapshot.copy_from_slice(buffer, offset)?;
+        }
+        Ok(())
+    }
+
+    /// Compress shared memory, and the snapshots of it, into host memory
+    /// and give the pages of shared memory back to the OS, until `wake` is
+    /// called. Does nothing if the sandbox is already hibernating.
+    #[instrument(err(Debug), skip_all, parent = Span::current(), level= "Trace")]
+    pub(crate) fn hibernate(&mut self) -> Result<()> {
+        if self.hibernated.is_some() {
+            return Ok(());
+        }
+        let memory = self.shared_mem.with_exclusivity(|e| -> Result<Vec<u8>> {
+            let memory = lz4_flex::block::compress_prepend_size(e.as_slice());
+            e.discard_pages()?;
+            Ok(memory)
+        })??;
+        self.hibernated = Some(memory);
+        for snapshot in self
+            .snapshots
+            .try_lock()
+            .map_err(|e| new_error!("Error locking at {}:{}: {}", file!(), line!(), e))?
+            .iter_mut()
+        {
+            snapshot.compress();
+        }
+        Ok(())
+    }
+
+    /// Put back the contents of shared memory `hibernate` compressed. The
+    /// snapshots are decompressed as they are restored from.
+    #[instrument(err(Debug), skip_all, parent = Span::current(), level= "Trace")]
+    pub(crate) fn wake(&mut self) -> Result<()> {
+        let Some(memory) = &self.hibernated else {
+            return Ok(());
+        };
+        let memory = lz4_flex::block::decompress_size_prepended(memory)
+            .map_err(|e| new_error!("Failed to decompress hibernated memory: {}", e))?;
+        self.shared_mem
+            .with_exclusivity(|e| e.copy_from_slice(&memory, 0))??;
+        self.hibernated = None;
+        Ok(())
+    }
+
+    /// Whether the sandbox is hibernating, see `hibernate`
+    pub(crate) fn is_hibernating(&self) -> bool {
+        self.hibernated.is_some()
+    }
+
+    /// Turn checking the protected regions of memory after every guest
+    /// function call on or off
+    pub(crate) fn set_isolation_audit(&mut self, enabled: bool) {
+        self.isolation_audit = enabled;
+    }
+
+    /// Whether the protected regions of memory are checked after every
+    /// guest function call
+    pub(crate) fn isolation_audit(&self) -> bool {
+        self.isolation_audit
+    }
+
     /// Check the stack guard of the memory in `shared_mem`, using
     /// `layout` to calculate its location.
     ///
@@ -570,6 +1256,36 @@ impl SandboxMemoryManager<HostSharedMemory> {
         Ok(cmp_res == Ordering::Equal)
     }
 
+    /// Check that the guest was built against the same version of the
+    /// host-guest ABI as the host, once it has initialised
+    #[instrument(err(Debug), skip_all, parent = Span::current(), level= "Trace")]
+    pub(crate) fn check_guest_abi_version(&self) -> Result<()> {
+        let guest = self
+            .shared_mem
+            .read::<u64>(self.layout.get_guest_abi_version_offset())?;
+        if guest != GUEST_ABI_VERSION {
+            log_then_return!(GuestAbiMismatch {
+                guest,
+                host: GUEST_ABI_VERSION
+            });
+        }
+        Ok(())
+    }
+
+    /// Return an error if `hyperlight_main` reported that the guest failed
+    /// to initialize, see `hyperlight_guest::entrypoint::fail_initialization`
+    #[instrument(err(Debug), skip_all, parent = Span::current(), level= "Trace")]
+    pub(crate) fn check_guest_initialization(&self) -> Result<()> {
+        let guest_error = self.get_guest_error()?;
+        if guest_error.code != ErrorCode::NoError {
+            log_then_return!(GuestInitializationFailed {
+                code: guest_error.code.clone(),
+                message: guest_error.message.clone()
+            });
+        }
+        Ok(())
+    }
+
     /// Get the address of the dispatch function in memory
     #[instrument(err(Debug), skip_all, parent = Span::current(), level= "Trace")]
     pub(crate) fn get_pointer_to_dispatch_function(&self) -> Result<u64> {
@@ -593,30 +1309,36 @@ impl SandboxMemoryManager<HostSharedMemory> {
     /// Reads a host function call from memory
     #[instrument(err(Debug), skip_all, parent = Span::current(), level= "Trace")]
     pub(crate) fn get_host_function_call(&mut self) -> Result<FunctionCall> {
-        self.shared_mem.try_pop_buffer_into::<FunctionCall>(
-            self.layout.output_data_buffer_offset,
-            self.layout.sandbox_memory_config.get_output_data_size(),
-        )
+        let function_call = self.pop_output_data::<FunctionCall>()?;
+        // the guest zeroes its copies of the call, and popping it zeroed
+        // the one in shared memory
+        if function_call.has_secrets() {
+            zeroize(&mut self.scratch_buffer);
+        }
+        Ok(function_call)
     }
 
     /// Writes a function call result to memory
     #[instrument(err(Debug), skip_all, parent = Span::current(), level= "Trace")]
     pub(crate) fn write_response_from_host_method_call(&mut self, res: &ReturnValue) -> Result<()> {
-        let function_call_ret_val_buffer = Vec::<u8>::try_from(res).map_err(|_| {
-            new_error!(
-                "write_response_from_host_method_call: failed to convert ReturnValue to Vec<u8>"
-            )
-        })?;
-        self.shared_mem.push_buffer(
-            self.layout.input_data_buffer_offset,
-            self.layout.sandbox_memory_config.get_input_data_size(),
-            function_call_ret_val_buffer.as_slice(),
+        let compression_threshold = self.guest_compression_threshold()?;
+        let function_call_ret_val_buffer =
+            res.encode_with_compression(&mut self.fb_builder, compression_threshold);
+        push_input_data(
+            &mut self.shared_mem,
+            &self.layout,
+            &self.integrity_key,
+            &mut self.input_segments,
+            function_call_ret_val_buffer,
         )
     }
 
     /// Writes a guest function call to memory
     #[instrument(err(Debug), skip_all, parent = Span::current(), level= "Trace")]
-    pub(crate) fn write_guest_function_call(&mut self, buffer: &[u8]) -> Result<()> {
+    pub(crate) fn write_guest_function_call(&mut self, function_call: &FunctionCall) -> Result<()> {
+        let compression_threshold = self.guest_compression_threshold()?;
+        let buffer =
+            function_call.encode_with_compression(&mut self.fb_builder, compression_threshold);
         validate_guest_function_call_buffer(buffer).map_err(|e| {
             new_error!(
                 "Guest function call buffer validation failed: {}",
@@ -624,29 +1346,107 @@ impl SandboxMemoryManager<HostSharedMemory> {
             )
         })?;
 
-        self.shared_mem.push_buffer(
-            self.layout.input_data_buffer_offset,
-            self.layout.sandbox_memory_config.get_input_data_size(),
+        let res = push_input_data(
+            &mut self.shared_mem,
+            &self.layout,
+            &self.integrity_key,
+            &mut self.input_segments,
+            buffer,
+        );
+        // the guest zeroes the copy in shared memory when it pops it
+        if function_call.has_secrets() {
+            zeroize(self.fb_builder.mut_finished_buffer().0);
+        }
+        res
+    }
+
+    /// Push `buffer` onto the input buffer as is, so tests can check how
+    /// the guest handles function calls that aren't valid flatbuffers
+    #[cfg(test)]
+    pub(crate) fn write_raw_guest_function_call(&mut self, buffer: &[u8]) -> Result<()> {
+        push_input_data(
+            &mut self.shared_mem,
+            &self.layout,
+            &self.integrity_key,
+            &mut self.input_segments,
             buffer,
         )
     }
 
+    /// The size at which values sent to the guest are compressed, if
+    /// compression is turned on and the guest can decompress them
+    #[instrument(err(Debug), skip_all, parent = Span::current(), level= "Trace")]
+    fn guest_compression_threshold(&self) -> Result<Option<usize>> {
+        let threshold = self
+            .layout
+            .sandbox_memory_config
+            .get_compression_threshold();
+        if threshold == 0 {
+            return Ok(None);
+        }
+        let support = self
+            .shared_mem
+            .read::<u64>(self.layout.get_guest_compression_support_offset())?;
+        Ok((support & LZ4_SUPPORTED != 0).then_some(threshold))
+    }
+
+    /// Read the table of guest function ids that the guest leaves in the
+    /// output buffer at the end of initialisation, for
+    /// `guest_function_id` to resolve function names with.
+    ///
+    /// Guests that don't publish a table can only be called by name.
+    #[instrument(err(Debug), skip_all, parent = Span::current(), level= "Trace")]
+    pub(crate) fn read_guest_function_details(&mut self) -> Result<()> {
+        let stack_pointer_rel = self
+            .shared_mem
+            .read::<u64>(self.layout.output_data_buffer_offset)?;
+        // an empty buffer's stack pointer is 8
+        if stack_pointer_rel <= 8 {
+            return Ok(());
+        }
+
+        let details = self.pop_output_data::<GuestFunctionDetails>()?;
+        // ids are handed out in table order, starting at 1
+        self.guest_function_names = details.ids().map(|(_, name)| name.into()).collect();
+        self.guest_function_ids = details
+            .ids()
+            .zip(&self.guest_function_names)
+            .map(|((id, _), name)| (name.clone(), GuestFunctionId(id)))
+            .collect();
+        Ok(())
+    }
+
+    /// The names of the functions in the guest's table of function ids,
+    /// in id order
+    pub(crate) fn guest_function_names(&self) -> Vec<String> {
+        self.guest_function_names
+            .iter()
+            .map(|name| name.to_string())
+            .collect()
+    }
+
+    /// The id the guest assigned to its function `name`, if it published
+    /// one
+    pub(crate) fn guest_function_id(&self, name: &str) -> Option<GuestFunctionId> {
+        self.guest_function_ids.get(name).copied()
+    }
+
+    /// The name of the function the guest assigned `id` to
+    pub(crate) fn guest_function_name(&self, id: GuestFunctionId) -> Option<Arc<str>> {
+        let index = usize::try_from(id.0).ok()?.checked_sub(1)?;
+        self.guest_function_names.get(index).cloned()
+    }
+
     /// Reads a function call result from memory
     #[instrument(err(Debug), skip_all, parent = Span::current(), level= "Trace")]
     pub(crate) fn get_guest_function_call_result(&mut self) -> Result<ReturnValue> {
-        self.shared_mem.try_pop_buffer_into::<ReturnValue>(
-            self.layout.output_data_buffer_offset,
-            self.layout.sandbox_memory_config.get_output_data_size(),
-        )
+        self.pop_output_data::<ReturnValue>()
     }
 
     /// Read guest log data from the `SharedMemory` contained within `self`
     #[instrument(err(Debug), skip_all, parent = Span::current(), level= "Trace")]
     pub(crate) fn read_guest_log_data(&mut self) -> Result<GuestLogData> {
-        self.shared_mem.try_pop_buffer_into::<GuestLogData>(
-            self.layout.output_data_buffer_offset,
-            self.layout.sandbox_memory_config.get_output_data_size(),
-        )
+        self.pop_output_data::<GuestLogData>()
     }
 
     /// Get the length of the host exception
@@ -788,6 +1588,191 @@ impl SandboxMemoryManager<HostSharedMemory> {
         Ok(())
     }
 
+    /// Pop the value on top of the output buffer, appending it to the
+    /// segments received before it if the guest sent it in segments.
+    fn pop_output_data<T>(&mut self) -> Result<T>
+    where
+        T: for<'b> TryFrom<&'b [u8]>,
+    {
+        self.check_output_integrity()?;
+        let offset = self.layout.output_data_buffer_offset;
+        let size = self.layout.sandbox_memory_config.get_max_output_data_size();
+        if self.output_segments.is_empty() {
+            return self.shared_mem.try_pop_buffer_into_with_scratch(
+                offset,
+                size,
+                &mut self.scratch_buffer,
+            );
+        }
+
+        self.shared_mem
+            .pop_buffer_into_scratch(offset, size, &mut self.scratch_buffer)?;
+        if segment_prefix(&self.scratch_buffer)? & CHUNK_FLAG != 0 {
+            log_then_return!("Expected the last segment of output data, but more follow");
+        }
+        self.check_output_segments_size(self.scratch_buffer.len() - size_of::<u32>())?;
+        self.output_segments
+            .extend_from_slice(&self.scratch_buffer[size_of::<u32>()..]);
+        let mut data = std::mem::take(&mut self.output_segments);
+        let res = T::try_from(data.as_slice()).map_err(|_e| {
+            new_error!(
+                "pop_output_data: failed to convert buffer to {}",
+                std::any::type_name::<T>()
+            )
+        });
+        // zeroed whether or not it holds secrets, see `CHUNK_FLAG`
+        zeroize(&mut data);
+        res
+    }
+
+    /// Pop a segment of a value the guest is sending in segments off the
+    /// output buffer, and hold on to it until the rest arrive.
+    #[instrument(err(Debug), skip_all, parent = Span::current(), level= "Trace")]
+    pub(crate) fn collect_output_segment(&mut self) -> Result<()> {
+        self.check_output_integrity()?;
+        self.shared_mem.pop_buffer_into_scratch(
+            self.layout.output_data_buffer_offset,
+            self.layout.sandbox_memory_config.get_max_output_data_size(),
+            &mut self.scratch_buffer,
+        )?;
+        if segment_prefix(&self.scratch_buffer)? & CHUNK_FLAG == 0 {
+            log_then_return!("Expected a segment of output data with more to follow");
+        }
+        self.check_output_segments_size(self.scratch_buffer.len() - size_of::<u32>())?;
+        self.output_segments
+            .extend_from_slice(&self.scratch_buffer[size_of::<u32>()..]);
+        Ok(())
+    }
+
+    /// Check that `len` more bytes of a value the guest is sending in
+    /// segments keep it within `SandboxConfiguration::set_max_segmented_data_size`,
+    /// dropping the segments received so far if not
+    fn check_output_segments_size(&mut self, len: usize) -> Result<()> {
+        // flatbuffers can't be larger than 2GB
+        let max_size = min(
+            self.layout
+                .sandbox_memory_config
+                .get_max_segmented_data_size(),
+            CHUNK_FLAG as usize,
+        );
+        if self.output_segments.len().saturating_add(len) > max_size {
+            zeroize(&mut self.output_segments);
+            self.output_segments.clear();
+            log_then_return!(
+                "The guest sent more than {} bytes of output data in segments",
+                max_size
+            );
+        }
+        Ok(())
+    }
+
+    /// Check the tag the guest appended to the element on top of the
+    /// output buffer, if integrity checks are on, along with the tags of
+    /// the elements the host left on the input buffer.
+    ///
+    /// The tag covers the element's data and its offset in the buffer, and
+    /// the element's length prefix must account for all of the data, so
+    /// an element whose framing has been corrupted fails the check too.
+    /// The guest computes the tag itself, so unlike the keyed tags on the
+    /// input buffer it doesn't stop a guest forging an element, only
+    /// catches one that was corrupted.
+    fn check_output_integrity(&self) -> Result<()> {
+        if integrity_tag_len(&self.layout) == 0 {
+            return Ok(());
+        }
+        check_input_integrity(&self.shared_mem, &self.layout, &self.integrity_key)?;
+        let offset = self.layout.output_data_buffer_offset;
+        let size = self.layout.sandbox_memory_config.get_max_output_data_size();
+        let stack_pointer_rel = usize::try_from(self.shared_mem.read::<u64>(offset)?)?;
+        if !(16..=size).contains(&stack_pointer_rel) {
+            return Err(SharedBufferIntegrity(format!(
+                "output buffer stack pointer {} is out of bounds",
+                stack_pointer_rel
+            )));
+        }
+        let element_end = stack_pointer_rel - 8;
+        let element_offset_rel =
+            usize::try_from(self.shared_mem.read::<u64>(offset + element_end)?)?;
+        // the guest wrote the offset, so adding to it may overflow
+        if element_offset_rel < 8
+            || element_offset_rel
+                .checked_add(size_of::<u32>() + INTEGRITY_TAG_LEN)
+                .map_or(true, |min_end| min_end > element_end)
+        {
+            return Err(SharedBufferIntegrity(format!(
+                "output buffer element offset {} is out of bounds",
+                element_offset_rel
+            )));
+        }
+
+        let mut element = vec![0; element_end - element_offset_rel];
+        self.shared_mem
+            .copy_to_slice(&mut element, offset + element_offset_rel)?;
+        let (data, tag) = element.split_at(element.len() - INTEGRITY_TAG_LEN);
+        if !verify_integrity_tag(element_offset_rel as u64, data, tag) {
+            return Err(SharedBufferIntegrity(format!(
+                "output buffer element at {} does not match its tag",
+                element_offset_rel
+            )));
+        }
+        if (segment_prefix(data)? & !CHUNK_FLAG) as usize + size_of::<u32>() != data.len() {
+            return Err(SharedBufferIntegrity(format!(
+                "output buffer element at {} has the wrong length prefix",
+                element_offset_rel
+            )));
+        }
+        Ok(())
+    }
+
+    /// Push the next segment of a value being sent to the guest in
+    /// segments onto the input buffer, which the guest has emptied.
+    #[instrument(err(Debug), skip_all, parent = Span::current(), level= "Trace")]
+    pub(crate) fn push_input_segment(&mut self) -> Result<()> {
+        push_input_segment(
+            &mut self.shared_mem,
+            &self.layout,
+            &self.integrity_key,
+            &mut self.input_segments,
+        )
+    }
+
+    /// Tell the guest it has `remaining` to run the call the host is about
+    /// to dispatch, and that its time stamp counter runs at `tsc_khz`
+    #[instrument(err(Debug), skip_all, parent = Span::current(), level= "Trace")]
+    pub(crate) fn write_call_deadline(&mut self, remaining: Duration, tsc_khz: u64) -> Result<()> {
+        let nanos = u64::try_from(remaining.as_nanos()).unwrap_or(u64::MAX);
+        self.shared_mem
+            .write::<u64>(self.layout.get_call_deadline_nanos_offset(), nanos)?;
+        self.shared_mem
+            .write::<u64>(self.layout.get_guest_tsc_khz_offset(), tsc_khz)
+    }
+
+    /// Give back to the OS the heap pages the guest has said it freed,
+    /// failing if they aren't all whole pages of the guest heap
+    #[instrument(err(Debug), skip_all, parent = Span::current(), level= "Trace")]
+    pub(crate) fn release_guest_memory(&mut self) -> Result<()> {
+        let offset = usize::try_from(
+            self.shared_mem
+                .read::<u64>(self.layout.get_released_memory_offset_offset())?,
+        )?;
+        let size = usize::try_from(
+            self.shared_mem
+                .read::<u64>(self.layout.get_released_memory_size_offset())?,
+        )?;
+        if !matches!(offset.checked_add(size), Some(end) if end <= self.layout.heap_size) {
+            log_then_return!(
+                "The guest released {:#x} bytes at heap offset {:#x}, which is outside of its heap of {:#x} bytes",
+                size,
+                offset,
+                self.layout.heap_size
+            );
+        }
+        // the heap starts at a page boundary, so the pages are whole if
+        // `offset` and `size` are multiples of the page size
+        self.shared_mem
+            .discard(self.layout.get_guest_heap_buffer_offset() + offset, size)
+    }
+
     /// Read guest panic data from the `SharedMemory` contained within `self`
     #[instrument(err(Debug), skip_all, parent = Span::current(), level= "Trace")]
     pub fn read_guest_panic_context_data(&self) -> Result<Vec<u8>> {
@@ -803,25 +1788,180 @@ impl SandboxMemoryManager<HostSharedMemory> {
             .copy_to_slice(vec_out.as_mut_slice(), offset)?;
         Ok(vec_out)
     }
+
+    /// Read `len` bytes of guest memory starting at the guest address
+    /// `addr`, failing if any of them are outside of guest memory.
+    #[cfg(feature = "unsafe_memory_access")]
+    #[instrument(err(Debug), skip_all, parent = Span::current(), level= "Trace")]
+    pub(crate) fn read_guest_memory(&self, addr: u64, len: usize) -> Result<Vec<u8>> {
+        let offset = self.guest_memory_offset(addr)?;
+        // check before allocating, so that a huge `len` is an error too
+        let mem_size = self.shared_mem.mem_size();
+        if offset.checked_add(len).map_or(true, |end| end > mem_size) {
+            log_then_return!(HyperlightError::BoundsCheckFailed(addr, mem_size));
+        }
+        let mut vec_out = vec![0; len];
+        self.shared_mem
+            .copy_to_slice(vec_out.as_mut_slice(), offset)?;
+        Ok(vec_out)
+    }
+
+    /// Write `data` to guest memory starting at the guest address `addr`,
+    /// failing if any of it is outside of guest memory or would overwrite
+    /// the guest's page tables.
+    #[cfg(feature = "unsafe_memory_access")]
+    #[instrument(err(Debug), skip_all, parent = Span::current(), level= "Trace")]
+    pub(crate) fn write_guest_memory(&mut self, addr: u64, data: &[u8]) -> Result<()> {
+        let offset = self.guest_memory_offset(addr)?;
+        if offset < self.layout.get_guest_code_offset() {
+            log_then_return!("Guest address {:#x} is in the guest page tables", addr);
+        }
+        self.shared_mem.copy_from_slice(data, offset)
+    }
+
+    /// Translate the guest address `addr` into an offset in `shared_mem`
+    #[cfg(feature = "unsafe_memory_access")]
+    fn guest_memory_offset(&self, addr: u64) -> Result<usize> {
+        let guest_ptr = GuestPtr::try_from(RawPtr::from(addr))?;
+        usize::try_from(guest_ptr.offset())
+    }
+
+    /// Check that no 8-byte aligned value in guest memory is the address
+    /// of something mapped into the host process, which would tell the
+    /// guest where the host keeps its code and data. Guests running
+    /// in-process share the host's address space, so they aren't checked.
+    #[cfg(pointer_audit)]
+    #[instrument(err(Debug), skip_all, parent = Span::current(), level= "Trace")]
+    pub(crate) fn audit_host_pointers(&mut self) -> Result<()> {
+        if self.inprocess {
+            return Ok(());
+        }
+        let mut mappings = host_mappings()?;
+        let base = self.shared_mem.raw_ptr() as u64;
+        mappings.push(base..base + self.shared_mem.raw_mem_size() as u64);
+        mappings.sort_by_key(|mapping| mapping.start);
+        let guest_addresses = SandboxMemoryLayout::BASE_ADDRESS as u64
+            ..(SandboxMemoryLayout::BASE_ADDRESS + self.shared_mem.mem_size()) as u64;
+        let found = self
+            .shared_mem
+            .with_exclusivity(|e| find_host_pointer(e.as_slice(), &mappings, &guest_addresses))?;
+        if let Some((offset, value)) = found {
+            log_then_return!(
+                "Guest memory at offset {:#x} holds the host address {:#x}",
+                offset,
+                value
+            );
+        }
+        Ok(())
+    }
+}
+
+/// The address ranges mapped into the host process
+#[cfg(all(pointer_audit, target_os = "linux"))]
+fn host_mappings() -> Result<Vec<Range<u64>>> {
+    let maps = std::fs::read_to_string("/proc/self/maps")?;
+    maps.lines()
+        .map(|line| {
+            let range = line
+                .split_once(' ')
+                .and_then(|(range, _)| range.split_once('-'))
+                .and_then(|(start, end)| {
+                    Some(u64::from_str_radix(start, 16).ok()?..u64::from_str_radix(end, 16).ok()?)
+                });
+            range.ok_or_else(|| new_error!("Unexpected line in /proc/self/maps: {}", line))
+        })
+        .collect()
+}
+
+/// The address ranges mapped into the host process. Only the shared
+/// memory mapping, which `audit_host_pointers` adds itself, is checked
+/// for on Windows.
+#[cfg(all(pointer_audit, not(target_os = "linux")))]
+fn host_mappings() -> Result<Vec<Range<u64>>> {
+    Ok(Vec::new())
+}
+
+/// Find the first 8-byte aligned value in `memory` that is in one of
+/// `mappings`, which must be sorted by their start, returning its offset
+/// and the value. Values that are also guest addresses are ignored, as
+/// guest memory is full of those.
+#[cfg(pointer_audit)]
+fn find_host_pointer(
+    memory: &[u8],
+    mappings: &[Range<u64>],
+    guest_addresses: &Range<u64>,
+) -> Option<(usize, u64)> {
+    memory
+        .chunks_exact(size_of::<u64>())
+        .enumerate()
+        .find_map(|(i, word)| {
+            let value = u64::from_le_bytes(word.try_into().ok()?);
+            // most of guest memory is zero, which is never mapped
+            if value == 0 || guest_addresses.contains(&value) {
+                return None;
+            }
+            let next = mappings.partition_point(|mapping| mapping.start <= value);
+            let mapped = next > 0 && mappings[next - 1].contains(&value);
+            mapped.then_some((i * size_of::<u64>(), value))
+        })
 }
 
 #[cfg(test)]
 mod tests {
+    use hmac::Mac;
+    use hyperlight_common::flatbuffer_wrappers::function_types::ReturnValue;
+    use hyperlight_common::integrity::{integrity_tag, INTEGRITY_TAG_LEN};
+    use hyperlight_common::mem::GUEST_ABI_VERSION;
     use hyperlight_testing::rust_guest_as_pathbuf;
     use serde_json::to_string;
     #[cfg(target_os = "windows")]
     use serial_test::serial;
 
-    use super::SandboxMemoryManager;
-    use crate::error::HyperlightHostError;
+    use super::{input_integrity_mac, SandboxMemoryManager, INTEGRITY_KEY_LEN};
+    use crate::error::{HyperlightError, HyperlightHostError};
     use crate::mem::exe::ExeInfo;
     use crate::mem::layout::SandboxMemoryLayout;
     use crate::mem::ptr::RawPtr;
     use crate::mem::ptr_offset::Offset;
-    use crate::mem::shared_mem::{ExclusiveSharedMemory, SharedMemory};
+    use crate::mem::shared_mem::{ExclusiveSharedMemory, HostSharedMemory, SharedMemory};
+    use crate::sandbox::randomness::SandboxRng;
     use crate::sandbox::SandboxConfiguration;
     use crate::testing::bytes_for_path;
 
+    /// Lay out memory for `cfg` as the host would, and build a manager for
+    /// it, using `integrity_key` to tag shared buffer elements if given
+    fn new_test_mgr(
+        cfg: SandboxConfiguration,
+        integrity_key: Option<[u8; INTEGRITY_KEY_LEN]>,
+    ) -> (SandboxMemoryManager<HostSharedMemory>, SandboxMemoryLayout) {
+        let layout = SandboxMemoryLayout::new(cfg, 0x10000, 0x10000, 0x10000).unwrap();
+        let mut eshm = ExclusiveSharedMemory::new(layout.get_memory_size().unwrap()).unwrap();
+        let mem_size = eshm.mem_size();
+        layout
+            .write(
+                &mut eshm,
+                SandboxMemoryLayout::BASE_ADDRESS,
+                mem_size,
+                false,
+                &mut SandboxRng::default(),
+            )
+            .unwrap();
+        let mut emgr = SandboxMemoryManager::new(
+            layout,
+            eshm,
+            false,
+            RawPtr::from(0),
+            Offset::from(0),
+            #[cfg(target_os = "windows")]
+            None,
+        );
+        if let Some(key) = integrity_key {
+            emgr.set_integrity_key(key);
+        }
+        let (hmgr, _) = emgr.build();
+        (hmgr, layout)
+    }
+
     #[test]
     fn load_guest_binary_common() {
         let guests = vec![
@@ -893,57 +2033,38 @@ mod tests {
     /// successfully do the read but get no error back
     #[test]
     fn get_host_error_none() {
-        let cfg = SandboxConfiguration::default();
-        let layout = SandboxMemoryLayout::new(cfg, 0x10000, 0x10000, 0x10000).unwrap();
-        let mut eshm = ExclusiveSharedMemory::new(layout.get_memory_size().unwrap()).unwrap();
-        let mem_size = eshm.mem_size();
-        layout
-            .write(
-                &mut eshm,
-                SandboxMemoryLayout::BASE_ADDRESS,
-                mem_size,
-                false,
-            )
-            .unwrap();
-        let emgr = SandboxMemoryManager::new(
-            layout,
-            eshm,
-            false,
-            RawPtr::from(0),
-            Offset::from(0),
-            #[cfg(target_os = "windows")]
-            None,
-        );
-        let (hmgr, _) = emgr.build();
+        let (hmgr, _) = new_test_mgr(SandboxConfiguration::default(), None);
         assert_eq!(None, hmgr.get_host_error().unwrap());
     }
 
+    /// Check that guests that haven't written the ABI version the host
+    /// expects are rejected
+    #[test]
+    fn guest_abi_version_is_checked() {
+        let (hmgr, layout) = new_test_mgr(SandboxConfiguration::default(), None);
+        let offset = layout.get_guest_abi_version_offset();
+
+        for guest in [0, GUEST_ABI_VERSION + 1] {
+            hmgr.shared_mem.write::<u64>(offset, guest).unwrap();
+            match hmgr.check_guest_abi_version() {
+                Err(HyperlightError::GuestAbiMismatch { guest: g, host }) => {
+                    assert_eq!(g, guest);
+                    assert_eq!(host, GUEST_ABI_VERSION);
+                }
+                other => panic!("Expected GuestAbiMismatch but got {:?}", other),
+            }
+        }
+
+        hmgr.shared_mem
+            .write::<u64>(offset, GUEST_ABI_VERSION)
+            .unwrap();
+        hmgr.check_guest_abi_version().unwrap();
+    }
+
     /// write a host error to shared memory, then try to read it back out
     #[test]
     fn round_trip_host_error() {
-        let cfg = SandboxConfiguration::default();
-        let layout = SandboxMemoryLayout::new(cfg, 0x10000, 0x10000, 0x10000).unwrap();
-        let mem_size = layout.get_memory_size().unwrap();
-        // write a host error and then try to read it back
-        let mut eshm = ExclusiveSharedMemory::new(mem_size).unwrap();
-        layout
-            .write(
-                &mut eshm,
-                SandboxMemoryLayout::BASE_ADDRESS,
-                mem_size,
-                false,
-            )
-            .unwrap();
-        let emgr = SandboxMemoryManager::new(
-            layout,
-            eshm,
-            false,
-            RawPtr::from(0),
-            Offset::from(0),
-            #[cfg(target_os = "windows")]
-            None,
-        );
-        let (mut hmgr, _) = emgr.build();
+        let (mut hmgr, _) = new_test_mgr(SandboxConfiguration::default(), None);
         let err = HyperlightHostError {
             message: "test message".to_string(),
             source: "rust test".to_string(),
@@ -962,4 +2083,192 @@ mod tests {
         assert!(host_err_opt.is_some());
         assert_eq!(err, host_err_opt.unwrap());
     }
+
+    /// Push tagged elements onto the output buffer as the guest would, and
+    /// check that the host only pops them if they haven't been tampered with
+    #[test]
+    fn output_integrity_is_checked() {
+        let mut cfg = SandboxConfiguration::default();
+        cfg.set_shared_buffer_integrity(true);
+        let (mut hmgr, layout) = new_test_mgr(cfg, None);
+
+        let offset = layout.output_data_buffer_offset;
+        let size = cfg.get_output_data_size();
+        let value: Vec<u8> = (&ReturnValue::Int(42)).try_into().unwrap();
+        let mut element = value.clone();
+        // an empty buffer's stack pointer is 8
+        element.extend_from_slice(&integrity_tag(8, &value));
+
+        hmgr.shared_mem.push_buffer(offset, size, &element).unwrap();
+        assert_eq!(
+            hmgr.get_guest_function_call_result().unwrap(),
+            ReturnValue::Int(42)
+        );
+
+        hmgr.shared_mem.push_buffer(offset, size, &element).unwrap();
+        hmgr.shared_mem.write::<u8>(offset + 8 + 4, 0xff).unwrap();
+        assert!(matches!(
+            hmgr.get_guest_function_call_result(),
+            Err(HyperlightError::SharedBufferIntegrity(_))
+        ));
+
+        // an untagged element is rejected too
+        hmgr.shared_mem.write::<u64>(offset, 8).unwrap();
+        hmgr.shared_mem.push_buffer(offset, size, &value).unwrap();
+        assert!(matches!(
+            hmgr.get_guest_function_call_result(),
+            Err(HyperlightError::SharedBufferIntegrity(_))
+        ));
+
+        // and so is an element offset that overflows when the tag is added
+        hmgr.shared_mem.write::<u64>(offset, 8).unwrap();
+        hmgr.shared_mem.push_buffer(offset, size, &element).unwrap();
+        let stack_pointer_rel = hmgr.shared_mem.read::<u64>(offset).unwrap() as usize;
+        hmgr.shared_mem
+            .write::<u64>(offset + stack_pointer_rel - 8, u64::MAX - 1)
+            .unwrap();
+        assert!(matches!(
+            hmgr.get_guest_function_call_result(),
+            Err(HyperlightError::SharedBufferIntegrity(_))
+        ));
+    }
+
+    /// Check that the host tags the elements it pushes onto the input
+    /// buffer with its key, and stops using the buffer once they have been
+    /// tampered with
+    #[test]
+    fn input_integrity_is_checked() {
+        let mut cfg = SandboxConfiguration::default();
+        cfg.set_shared_buffer_integrity(true);
+        let key = [7; INTEGRITY_KEY_LEN];
+        let (mut hmgr, layout) = new_test_mgr(cfg, Some(key));
+
+        hmgr.write_response_from_host_method_call(&ReturnValue::Int(42))
+            .unwrap();
+        let offset = layout.input_data_buffer_offset;
+        let stack_pointer_rel = hmgr.shared_mem.read::<u64>(offset).unwrap() as usize;
+        // an empty buffer's stack pointer is 8
+        let mut element = vec![0; stack_pointer_rel - 8 - 8];
+        hmgr.shared_mem
+            .copy_to_slice(&mut element, offset + 8)
+            .unwrap();
+        let (value, tag) = element.split_at(element.len() - INTEGRITY_TAG_LEN);
+        assert!(input_integrity_mac(&key, 8, value)
+            .verify_slice(tag)
+            .is_ok());
+        assert_eq!(ReturnValue::try_from(value).unwrap(), ReturnValue::Int(42));
+
+        // the element's framing being rewritten is caught the next time the
+        // host pushes onto the buffer or pops what the guest returned
+        hmgr.shared_mem.write::<u8>(offset + 8, 0xff).unwrap();
+        assert!(matches!(
+            hmgr.write_response_from_host_method_call(&ReturnValue::Int(43)),
+            Err(HyperlightError::SharedBufferIntegrity(_))
+        ));
+        assert!(matches!(
+            hmgr.get_guest_function_call_result(),
+            Err(HyperlightError::SharedBufferIntegrity(_))
+        ));
+
+        // and so is the element being tagged as the guest tags elements,
+        // without the key
+        hmgr.shared_mem.copy_from_slice(value, offset + 8).unwrap();
+        hmgr.shared_mem
+            .copy_from_slice(&integrity_tag(8, value), offset + 8 + value.len())
+            .unwrap();
+        assert!(matches!(
+            hmgr.write_response_from_host_method_call(&ReturnValue::Int(43)),
+            Err(HyperlightError::SharedBufferIntegrity(_))
+        ));
+
+        // an untouched element passes
+        hmgr.shared_mem
+            .copy_from_slice(&element, offset + 8)
+            .unwrap();
+        hmgr.write_response_from_host_method_call(&ReturnValue::Int(43))
+            .unwrap();
+    }
+
+    /// Lay out memory as for a guest in a VM, and check that the audit
+    /// passes until a host address is written into it
+    #[cfg(pointer_audit)]
+    #[test]
+    fn host_pointers_are_found() {
+        let (mut hmgr, layout) = new_test_mgr(SandboxConfiguration::default(), None);
+        hmgr.audit_host_pointers().unwrap();
+
+        let offset = layout.input_data_buffer_offset;
+        let base = hmgr.shared_mem.base_addr() as u64;
+        hmgr.shared_mem.write::<u64>(offset, base + 0x10).unwrap();
+        assert!(hmgr.audit_host_pointers().is_err());
+
+        // the address of something on the host's stack is found too
+        let local = 0u64;
+        hmgr.shared_mem
+            .write::<u64>(offset, &local as *const u64 as u64)
+            .unwrap();
+        assert_eq!(
+            cfg!(target_os = "linux"),
+            hmgr.audit_host_pointers().is_err()
+        );
+    }
+
+    /// Check that an isolation audit finds the writes to the regions it
+    /// protects, and only those
+    #[test]
+    fn isolation_audit() {
+        use crate::mem::memory_region::MemoryRegionType;
+        use crate::sandbox::isolation_audit::{IsolationAudit, IsolationViolation};
+
+        let (hmgr, layout) = new_test_mgr(SandboxConfiguration::default(), None);
+        let base = hmgr.shared_mem.base_addr();
+        let regions = layout.get_memory_regions(&hmgr.shared_mem).unwrap();
+        // the offset of the second byte of the first region of `region_type`
+        let offset_in = |region_type| {
+            let region = regions
+                .iter()
+                .find(|region| region.region_type == region_type)
+                .unwrap();
+            (
+                region.host_region.start - base + 1,
+                region.guest_region.start as u64 + 1,
+            )
+        };
+
+        let audit = IsolationAudit::start(&hmgr).unwrap();
+        assert_eq!(audit.finish(&hmgr).unwrap(), vec![]);
+
+        // the CPU setting the Accessed and Dirty bits of a page table
+        // entry as the guest runs doesn't count
+        let audit = IsolationAudit::start(&hmgr).unwrap();
+        let (offset, _) = offset_in(MemoryRegionType::PageTables);
+        let entry = hmgr.shared_mem.read::<u64>(offset - 1).unwrap();
+        hmgr.shared_mem
+            .write::<u64>(offset - 1, entry | 1 << 5 | 1 << 6)
+            .unwrap();
+        assert_eq!(audit.finish(&hmgr).unwrap(), vec![]);
+
+        let audit = IsolationAudit::start(&hmgr).unwrap();
+        let mut expected = vec![];
+        for region_type in [
+            MemoryRegionType::PageTables,
+            MemoryRegionType::HostFunctionDefinitions,
+            MemoryRegionType::Heap,
+            MemoryRegionType::GuardPage,
+        ] {
+            let (offset, address) = offset_in(region_type);
+            hmgr.shared_mem.write::<u8>(offset, 0xcc).unwrap();
+            expected.extend(match region_type {
+                MemoryRegionType::PageTables => {
+                    Some(IsolationViolation::PageTablesModified { address })
+                }
+                MemoryRegionType::GuardPage => {
+                    Some(IsolationViolation::GuardPageWritten { address })
+                }
+                MemoryRegionType::Heap => None,
+                region => Some(IsolationViolation::ReadOnlyRegionWritten { region, address }),
+            });
+        }
+        assert_eq!(audit.finish(&hmgr).unwrap(), expected);
+    }
 }
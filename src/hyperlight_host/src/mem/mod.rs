@@ -22,7 +22,7 @@ pub(crate) mod elf;
 /// A generic wrapper for executable files (PE, ELF, etc)
 pub(crate) mod exe;
 /// Functionality to establish a sandbox's memory layout.
-pub mod layout;
+pub(crate) mod layout;
 /// Safe wrapper around an HINSTANCE created by the windows
 /// `LoadLibrary` call
 #[cfg(target_os = "windows")]
@@ -31,22 +31,25 @@ pub(super) mod loaded_lib;
 pub mod memory_region;
 /// Functionality that wraps a `SandboxMemoryLayout` and a
 /// `SandboxMemoryConfig` to mutate a sandbox's memory as necessary.
-pub mod mgr;
+pub(crate) mod mgr;
 /// Functionality to read and mutate a PE file in a structured manner.
 pub(crate) mod pe;
 /// Structures to represent pointers into guest and host memory
-pub mod ptr;
+pub(crate) mod ptr;
 /// Structures to represent memory address spaces into which pointers
 /// point.
 pub(super) mod ptr_addr_space;
 /// Structures to represent an offset into a memory space
-pub mod ptr_offset;
+pub(crate) mod ptr_offset;
 /// A wrapper around unsafe functionality to create and initialize
 /// a memory region for a guest running in a sandbox.
-pub mod shared_mem;
+pub(crate) mod shared_mem;
 /// A wrapper around a `SharedMemory` and a snapshot in time
 /// of the memory therein
-pub mod shared_mem_snapshot;
+pub(crate) mod shared_mem_snapshot;
 /// Utilities for writing shared memory tests
 #[cfg(test)]
 pub(crate) mod shared_mem_tests;
+/// Symbol tables of guest binaries, to translate guest addresses into
+/// function names
+pub mod symbols;
@@ -20,6 +20,7 @@ use std::{iter, mem};
 use goblin::pe::optional_header::OptionalHeader;
 use goblin::pe::section_table::SectionTable;
 use goblin::pe::PE;
+use hyperlight_common::guest_function_metadata::SECTION_NAME as FUNCTION_RECORDS_SECTION;
 use tracing::{instrument, Span};
 
 use crate::mem::pe::base_relocations;
@@ -40,6 +41,7 @@ pub(crate) struct PEInfo {
     pub(crate) payload: Vec<u8>,
     optional_header: OptionalHeader,
     reloc_section: Option<SectionTable>,
+    function_records_section: Option<SectionTable>,
 }
 
 impl PEInfo {
@@ -177,11 +179,14 @@ impl PEInfo {
             }
         }
 
-        let reloc_section = pe
-            .sections
-            .iter()
-            .find(|section| section.name().unwrap_or_default() == ".reloc")
-            .cloned();
+        let section = |name| {
+            pe.sections
+                .iter()
+                .find(|section| section.name().unwrap_or_default() == name)
+                .cloned()
+        };
+        let reloc_section = section(".reloc");
+        let function_records_section = section(FUNCTION_RECORDS_SECTION);
 
         // extend the .data section to match the virtual size in the payload.
         // We insert `data_section_additional_bytes` number of zeroes starting at `end_of_data_index`
@@ -194,6 +199,7 @@ impl PEInfo {
             payload: pe_bytes,
             optional_header,
             reloc_section,
+            function_records_section,
         })
     }
 
@@ -210,6 +216,23 @@ impl PEInfo {
         self.optional_header.windows_fields.image_base
     }
 
+    /// The contents of the section holding the records of the guest's
+    /// functions, if it has one
+    pub(crate) fn get_function_records(&self) -> Result<Option<&[u8]>> {
+        let Some(section) = &self.function_records_section else {
+            return Ok(None);
+        };
+        let start = section.pointer_to_raw_data as usize;
+        // the raw data is padded to the file alignment
+        let len = section.virtual_size.min(section.size_of_raw_data) as usize;
+        match self.payload.get(start..start + len) {
+            Some(records) => Ok(Some(records)),
+            None => {
+                log_then_return!("PE section {} is out of bounds", FUNCTION_RECORDS_SECTION);
+            }
+        }
+    }
+
     /// Return the stack reserve field from the optional COFF header.
     #[instrument(skip_all, parent = Span::current(), level= "Trace")]
     pub(crate) fn stack_reserve(&self) -> u64 {
@@ -20,7 +20,7 @@ use std::io::Error;
 use std::ptr::null_mut;
 use std::sync::{Arc, RwLock};
 
-use hyperlight_common::mem::PAGE_SIZE_USIZE;
+use hyperlight_common::mem::{CHUNK_FLAG, PAGE_SIZE_USIZE};
 use tracing::{instrument, Span};
 #[cfg(target_os = "windows")]
 use windows::Win32::System::Memory::{VirtualAlloc, MEM_COMMIT, PAGE_EXECUTE_READWRITE};
@@ -527,6 +527,92 @@ impl ExclusiveSharedMemory {
         Ok(())
     }
 
+    /// Give the pages backing `self` back to the OS, leaving `self`
+    /// zero-filled (on Linux) or holding unspecified contents (on Windows)
+    /// until it is written to again, so that memory the host has saved a
+    /// copy of elsewhere stops counting towards the resident size of the
+    /// process.
+    #[instrument(err(Debug), skip_all, parent = Span::current(), level= "Trace")]
+    pub(crate) fn discard_pages(&mut self) -> Result<()> {
+        discard_range(self.base_ptr(), self.mem_size())
+    }
+
+    /// Have the OS allocate every page backing `self` now, rather than
+    /// when it is first touched, so that the guest doesn't take a page
+    /// fault the first time it uses each page
+    #[instrument(err(Debug), skip_all, parent = Span::current(), level= "Trace")]
+    pub(crate) fn prefault(&mut self) -> Result<()> {
+        #[cfg(target_os = "linux")]
+        {
+            // from linux/mman.h, which libc doesn't have
+            const MADV_POPULATE_WRITE: libc::c_int = 23;
+
+            let res = unsafe {
+                libc::madvise(
+                    self.base_ptr() as *mut c_void,
+                    self.mem_size(),
+                    MADV_POPULATE_WRITE,
+                )
+            };
+            if res == 0 {
+                return Ok(());
+            }
+            let error = Error::last_os_error();
+            // kernels before 5.14 don't know about MADV_POPULATE_WRITE,
+            // so touch the pages instead
+            if error.raw_os_error() != Some(libc::EINVAL) {
+                log_then_return!("Failed to prefault shared memory: {}", error);
+            }
+        }
+        let base = self.base_ptr();
+        for offset in (0..self.mem_size()).step_by(PAGE_SIZE_USIZE) {
+            // SAFETY: the page is inside the mapping, which `self` has the
+            // only reference to, and writing back what was read leaves it
+            // as it was
+            unsafe {
+                let byte = base.add(offset);
+                byte.write_volatile(byte.read_volatile());
+            }
+        }
+        Ok(())
+    }
+
+    /// Have the OS take the pages backing `self` from the NUMA node
+    /// `node`, moving any it has already allocated elsewhere
+    #[cfg(target_os = "linux")]
+    #[instrument(err(Debug), skip_all, parent = Span::current(), level= "Trace")]
+    pub(crate) fn bind_to_numa_node(&mut self, node: u32) -> Result<()> {
+        // from linux/mempolicy.h, which libc doesn't have
+        const MPOL_BIND: libc::c_long = 2;
+        const MPOL_MF_MOVE: libc::c_ulong = 1 << 1;
+
+        let node = node as usize;
+        let mut node_mask = vec![0 as libc::c_ulong; node / libc::c_ulong::BITS as usize + 1];
+        node_mask[node / libc::c_ulong::BITS as usize] |=
+            1 << (node % libc::c_ulong::BITS as usize);
+        // the kernel reads one bit fewer than it is told to
+        let max_node = node_mask.len() * libc::c_ulong::BITS as usize + 1;
+        let res = unsafe {
+            libc::syscall(
+                libc::SYS_mbind,
+                self.base_ptr(),
+                self.mem_size(),
+                MPOL_BIND,
+                node_mask.as_ptr(),
+                max_node,
+                MPOL_MF_MOVE,
+            )
+        };
+        if res != 0 {
+            log_then_return!(
+                "Failed to bind shared memory to NUMA node {}: {}",
+                node,
+                Error::last_os_error()
+            );
+        }
+        Ok(())
+    }
+
     /// Return the address of memory at an offset to this `SharedMemory` checking
     /// that the memory is within the bounds of the `SharedMemory`.
     #[instrument(err(Debug), skip_all, parent = Span::current(), level= "Trace")]
@@ -612,12 +698,14 @@ pub trait SharedMemory {
 
     /// Return the raw base address of the host mapping, including the
     /// guard pages.
+    #[cfg_attr(not(target_os = "windows"), allow(dead_code))]
     fn raw_ptr(&self) -> *mut u8 {
         self.region().ptr
     }
 
     /// Return the raw size of the host mapping, including the guard
     /// pages.
+    #[cfg_attr(not(target_os = "windows"), allow(dead_code))]
     fn raw_mem_size(&self) -> usize {
         self.region().size
     }
@@ -686,6 +774,38 @@ unsafe impl AllValid for i32 {}
 unsafe impl AllValid for i64 {}
 unsafe impl AllValid for [u8; 16] {}
 
+/// Give the pages backing `[base, base + len)` back to the OS, see
+/// `ExclusiveSharedMemory::discard_pages`
+fn discard_range(base: *mut u8, len: usize) -> Result<()> {
+    // the mapping is shared, so only MADV_REMOVE frees its backing pages
+    #[cfg(target_os = "linux")]
+    {
+        use libc::{madvise, MADV_REMOVE};
+
+        let res = unsafe { madvise(base as *mut c_void, len, MADV_REMOVE) };
+        if res != 0 {
+            log_then_return!(
+                "Failed to discard shared memory pages: {:#?}",
+                Error::last_os_error().raw_os_error()
+            );
+        }
+    }
+    #[cfg(target_os = "windows")]
+    {
+        use windows::Win32::System::Memory::{MEM_RESET, PAGE_READWRITE};
+
+        let addr =
+            unsafe { VirtualAlloc(Some(base as *const c_void), len, MEM_RESET, PAGE_READWRITE) };
+        if addr.is_null() {
+            log_then_return!(
+                "Failed to discard shared memory pages: {:#?}",
+                Error::last_os_error().raw_os_error()
+            );
+        }
+    }
+    Ok(())
+}
+
 impl HostSharedMemory {
     /// Read a value of type T, whose representation is the same
     /// between the sandbox and the host, and which has no invalid bit
@@ -759,6 +879,32 @@ impl HostSharedMemory {
         Ok(())
     }
 
+    /// Give the pages backing the range `[offset, offset + len)` back to
+    /// the OS, as `ExclusiveSharedMemory::discard_pages` does for the whole
+    /// region. `offset` and `len` must be multiples of the page size.
+    #[instrument(err(Debug), skip_all, parent = Span::current(), level= "Trace")]
+    pub(crate) fn discard(&mut self, offset: usize, len: usize) -> Result<()> {
+        bounds_check!(offset, len, self.mem_size());
+        if offset % PAGE_SIZE_USIZE != 0 || len % PAGE_SIZE_USIZE != 0 {
+            log_then_return!(
+                "Cannot discard {:#x} bytes at offset {:#x}, they are not whole pages",
+                len,
+                offset
+            );
+        }
+        if len == 0 {
+            return Ok(());
+        }
+        // like `fill`, this only changes the contents of the memory
+        let guard = self
+            .lock
+            .try_read()
+            .map_err(|e| new_error!("Error locking at {}:{}: {}", file!(), line!(), e))?;
+        discard_range(self.base_ptr().wrapping_add(offset), len)?;
+        drop(guard);
+        Ok(())
+    }
+
     /// Fill the memory in the range `[offset, offset + len)` with `value`
     #[instrument(err(Debug), skip_all, parent = Span::current(), level= "Trace")]
     pub fn fill(&mut self, value: u8, offset: usize, len: usize) -> Result<()> {
@@ -828,15 +974,41 @@ impl HostSharedMemory {
     /// Pops the given given buffer into a `T` and returns it.
     /// NOTE! the data must be a size-prefixed flatbuffer, and
     /// buffer_start_offset must point to the beginning of the buffer
+    ///
+    /// The popped element is copied into `scratch` rather than a freshly
+    /// allocated buffer. `scratch` is resized as needed and only
+    /// reallocates when it has to grow, so passing the same buffer on every
+    /// call keeps steady-state pops allocation free.
     #[instrument(err(Debug), skip_all, parent = Span::current(), level= "Trace")]
-    pub fn try_pop_buffer_into<T>(
+    pub fn try_pop_buffer_into_with_scratch<T>(
         &mut self,
         buffer_start_offset: usize,
         buffer_size: usize,
+        scratch: &mut Vec<u8>,
     ) -> Result<T>
     where
         T: for<'b> TryFrom<&'b [u8]>,
     {
+        self.pop_buffer_into_scratch(buffer_start_offset, buffer_size, scratch)?;
+        T::try_from(scratch.as_slice()).map_err(|_e| {
+            new_error!(
+                "pop_buffer_into: failed to convert buffer to {}",
+                type_name::<T>()
+            )
+        })
+    }
+
+    /// Pops the element on top of the given buffer into `scratch`, without
+    /// converting it. The element must start with a 4 byte length prefix,
+    /// which is copied into `scratch` along with the data, and which may
+    /// have `CHUNK_FLAG` set.
+    #[instrument(err(Debug), skip_all, parent = Span::current(), level= "Trace")]
+    pub fn pop_buffer_into_scratch(
+        &mut self,
+        buffer_start_offset: usize,
+        buffer_size: usize,
+        scratch: &mut Vec<u8>,
+    ) -> Result<()> {
         // get the stackpointer
         let stack_pointer_rel = self.read::<u64>(buffer_start_offset)? as usize;
 
@@ -860,22 +1032,17 @@ impl HostSharedMemory {
 
         // Get the size of the flatbuffer buffer from memory
         let fb_buffer_size = {
-            let size_i32 = self.read::<u32>(last_element_offset_abs)? + 4;
+            let size_i32 = (self.read::<u32>(last_element_offset_abs)? & !CHUNK_FLAG) + 4;
             // ^^^ flatbuffer byte arrays are prefixed by 4 bytes
             // indicating its size, so, to get the actual size, we need
             // to add 4.
             usize::try_from(size_i32)
         }?;
 
-        let mut result_buffer = vec![0; fb_buffer_size];
+        scratch.clear();
+        scratch.resize(fb_buffer_size, 0);
 
-        self.copy_to_slice(&mut result_buffer, last_element_offset_abs)?;
-        let to_return = T::try_from(result_buffer.as_slice()).map_err(|_e| {
-            new_error!(
-                "pop_buffer_into: failed to convert buffer to {}",
-                type_name::<T>()
-            )
-        })?;
+        self.copy_to_slice(scratch.as_mut_slice(), last_element_offset_abs)?;
 
         // update the stack pointer to point to the element we just popped off since that is now free
         self.write::<u64>(buffer_start_offset, last_element_offset_rel as u64)?;
@@ -884,7 +1051,7 @@ impl HostSharedMemory {
         let num_bytes_to_zero = stack_pointer_rel - last_element_offset_rel;
         self.fill(0, last_element_offset_abs, num_bytes_to_zero)?;
 
-        Ok(to_return)
+        Ok(())
     }
 }
 
@@ -1085,6 +1252,33 @@ mod tests {
         assert_eq!(data, ret_vec);
     }
 
+    #[test]
+    fn prefault() {
+        let mut eshm = ExclusiveSharedMemory::new(PAGE_SIZE_USIZE * 4).unwrap();
+        eshm.copy_from_slice(&[1, 2, 3], PAGE_SIZE_USIZE).unwrap();
+        eshm.prefault().unwrap();
+        // prefaulting leaves the contents as they were
+        assert_eq!(
+            &eshm.as_slice()[PAGE_SIZE_USIZE..PAGE_SIZE_USIZE + 3],
+            &[1, 2, 3]
+        );
+        assert!(eshm.as_slice()[..PAGE_SIZE_USIZE].iter().all(|&b| b == 0));
+    }
+
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn bind_to_numa_node() {
+        let mut eshm = ExclusiveSharedMemory::new(PAGE_SIZE_USIZE * 4).unwrap();
+        // every Linux host has node 0
+        eshm.bind_to_numa_node(0).unwrap();
+        eshm.copy_from_slice(&[1, 2, 3], PAGE_SIZE_USIZE).unwrap();
+        assert_eq!(
+            &eshm.as_slice()[PAGE_SIZE_USIZE..PAGE_SIZE_USIZE + 3],
+            &[1, 2, 3]
+        );
+        assert!(eshm.bind_to_numa_node(4095).is_err());
+    }
+
     /// A test to ensure that, if a `SharedMem` instance is cloned
     /// and _all_ clones are dropped, the memory region will no longer
     /// be valid.
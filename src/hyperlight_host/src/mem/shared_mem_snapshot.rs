@@ -17,13 +17,15 @@ limitations under the License.
 use tracing::{instrument, Span};
 
 use super::shared_mem::SharedMemory;
-use crate::Result;
+use crate::{new_error, Result};
 
 /// A wrapper around a `SharedMemory` reference and a snapshot
 /// of the memory therein
 #[derive(Clone)]
 pub(super) struct SharedMemorySnapshot {
     snapshot: Vec<u8>,
+    /// Whether `snapshot` holds the memory LZ4 compressed, see `compress`
+    compressed: bool,
 }
 
 impl SharedMemorySnapshot {
@@ -33,7 +35,20 @@ impl SharedMemorySnapshot {
     pub(super) fn new<S: SharedMemory>(shared_mem: &mut S) -> Result<Self> {
         // TODO: Track dirty pages instead of copying entire memory
         let snapshot = shared_mem.with_exclusivity(|e| e.copy_all_to_vec())??;
-        Ok(Self { snapshot })
+        Ok(Self {
+            snapshot,
+            compressed: false,
+        })
+    }
+
+    /// Compress the snapshot, to take up less memory while it isn't being
+    /// restored from. It is decompressed the next time it is restored.
+    #[instrument(skip_all, parent = Span::current(), level= "Trace")]
+    pub(super) fn compress(&mut self) {
+        if !self.compressed {
+            self.snapshot = lz4_flex::block::compress_prepend_size(&self.snapshot);
+            self.compressed = true;
+        }
     }
 
     /// Take another snapshot of the internally-stored `SharedMemory`,
@@ -42,6 +57,30 @@ impl SharedMemorySnapshot {
 
     pub(super) fn replace_snapshot<S: SharedMemory>(&mut self, shared_mem: &mut S) -> Result<()> {
         self.snapshot = shared_mem.with_exclusivity(|e| e.copy_all_to_vec())??;
+        self.compressed = false;
+        Ok(())
+    }
+
+    /// Copy `src` into the snapshot at `offset`, so that it is there the
+    /// next time the snapshot is restored
+    #[instrument(err(Debug), skip_all, parent = Span::current(), level= "Trace")]
+    pub(super) fn copy_from_slice(&mut self, src: &[u8], offset: usize) -> Result<()> {
+        let compressed = self.compressed;
+        if compressed {
+            self.snapshot = lz4_flex::block::decompress_size_prepended(&self.snapshot)
+                .map_err(|e| new_error!("Failed to decompress memory snapshot: {}", e))?;
+            self.compressed = false;
+        }
+        let end = offset
+            .checked_add(src.len())
+            .filter(|end| *end <= self.snapshot.len())
+            .ok_or_else(|| {
+                new_error!("Copying into the snapshot at {} is out of bounds", offset)
+            })?;
+        self.snapshot[offset..end].copy_from_slice(src);
+        if compressed {
+            self.compress();
+        }
         Ok(())
     }
 
@@ -52,6 +91,11 @@ impl SharedMemorySnapshot {
         &mut self,
         shared_mem: &mut S,
     ) -> Result<()> {
+        if self.compressed {
+            self.snapshot = lz4_flex::block::decompress_size_prepended(&self.snapshot)
+                .map_err(|e| new_error!("Failed to decompress memory snapshot: {}", e))?;
+            self.compressed = false;
+        }
         shared_mem.with_exclusivity(|e| e.copy_from_slice(self.snapshot.as_slice(), 0))?
     }
 }
@@ -94,5 +138,28 @@ mod tests {
             snap.restore_from_snapshot(&mut gm).unwrap();
             assert_eq!(data2, gm.copy_all_to_vec().unwrap());
         }
+        {
+            // a compressed snapshot restores the same memory
+            gm.copy_from_slice(data1.as_slice(), 0).unwrap();
+            snap.compress();
+            assert!(snap.snapshot.len() < data2.len());
+            snap.restore_from_snapshot(&mut gm).unwrap();
+            assert_eq!(data2, gm.copy_all_to_vec().unwrap());
+        }
+    }
+
+    #[test]
+    fn copy_into_snapshot() {
+        let mut gm = ExclusiveSharedMemory::new(PAGE_SIZE_USIZE).unwrap();
+        let mut snap = super::SharedMemorySnapshot::new(&mut gm).unwrap();
+        snap.compress();
+        snap.copy_from_slice(b"abc", 8).unwrap();
+        // the snapshot stays compressed
+        assert!(snap.compressed);
+        assert!(snap.copy_from_slice(b"abc", PAGE_SIZE_USIZE - 2).is_err());
+
+        snap.restore_from_snapshot(&mut gm).unwrap();
+        assert_eq!(&gm.as_slice()[8..11], b"abc");
+        assert!(gm.as_slice()[..8].iter().all(|&b| b == 0));
     }
 }
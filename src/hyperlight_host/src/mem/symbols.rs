@@ -0,0 +1,194 @@
+/*
+Copyright 2024 The Hyperlight Authors.
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+    http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+use goblin::elf::sym::STT_FUNC;
+use goblin::elf64::program_header::PT_LOAD;
+use goblin::Object;
+use tracing::{instrument, Span};
+
+use crate::sandbox::uninitialized::GuestBinary;
+use crate::{log_then_return, Result};
+
+#[derive(Debug, Clone)]
+struct Symbol {
+    /// The offset of the symbol from the start of the loaded image
+    offset: u64,
+    /// The size of the symbol, or 0 if the binary doesn't record it
+    size: u64,
+    name: String,
+}
+
+/// The function symbols of a guest binary, used to translate guest
+/// addresses, such as the RIP reported when a guest takes an exception,
+/// into `function+offset`.
+///
+/// Symbols are read from the `.symtab` of ELF guests, and from the export
+/// table of PE guests, whose full symbols are kept in a separate PDB.
+#[derive(Debug, Clone, Default)]
+pub struct GuestSymbols {
+    /// Sorted by `offset`
+    symbols: Vec<Symbol>,
+    load_address: u64,
+}
+
+impl GuestSymbols {
+    /// Read the symbols of the given guest binary.
+    ///
+    /// The symbols are relative to a load address of 0 until
+    /// `with_load_address` is called.
+    #[instrument(err(Debug), skip_all, parent = Span::current(), level = "Trace")]
+    pub fn from_binary(binary: &GuestBinary) -> Result<Self> {
+        match binary {
+            GuestBinary::Buffer(bytes) => Self::from_bytes(bytes),
+            GuestBinary::FilePath(path) => Self::from_bytes(&std::fs::read(path)?),
+        }
+    }
+
+    /// Read the symbols of the guest binary in `bytes`.
+    #[instrument(err(Debug), skip_all, parent = Span::current(), level = "Trace")]
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        let mut symbols = match Object::parse(bytes)? {
+            Object::Elf(elf) => {
+                let base_va = elf
+                    .program_headers
+                    .iter()
+                    .find(|phdr| phdr.p_type == PT_LOAD)
+                    .map_or(0, |phdr| phdr.p_vaddr);
+                elf.syms
+                    .iter()
+                    .filter(|sym| sym.st_type() == STT_FUNC && sym.st_value >= base_va)
+                    .filter_map(|sym| {
+                        let name = elf.strtab.get_at(sym.st_name)?;
+                        Some(Symbol {
+                            offset: sym.st_value - base_va,
+                            size: sym.st_size,
+                            name: rustc_demangle::demangle(name).to_string(),
+                        })
+                    })
+                    .collect::<Vec<_>>()
+            }
+            Object::PE(pe) => pe
+                .exports
+                .iter()
+                .filter_map(|export| {
+                    Some(Symbol {
+                        offset: export.rva as u64,
+                        size: export.size as u64,
+                        name: rustc_demangle::demangle(export.name?).to_string(),
+                    })
+                })
+                .collect(),
+            _ => {
+                log_then_return!("Guest binary is neither an ELF nor a PE file");
+            }
+        };
+        symbols.sort_by_key(|symbol| symbol.offset);
+        Ok(Self {
+            symbols,
+            load_address: 0,
+        })
+    }
+
+    /// Relocate the symbols to the address the guest binary was loaded
+    /// at, as returned by `UninitializedSandbox::guest_load_address`.
+    #[instrument(skip_all, parent = Span::current(), level = "Trace")]
+    pub fn with_load_address(mut self, load_address: u64) -> Self {
+        self.load_address = load_address;
+        self
+    }
+
+    /// Find the function containing `address`, returning its name and the
+    /// offset of `address` from its start.
+    #[instrument(skip_all, parent = Span::current(), level = "Trace")]
+    pub fn lookup(&self, address: u64) -> Option<(&str, u64)> {
+        let offset = address.checked_sub(self.load_address)?;
+        let index = self
+            .symbols
+            .partition_point(|symbol| symbol.offset <= offset)
+            .checked_sub(1)?;
+        let symbol = &self.symbols[index];
+        let delta = offset - symbol.offset;
+        if symbol.size != 0 && delta >= symbol.size {
+            return None;
+        }
+        Some((&symbol.name, delta))
+    }
+
+    /// Format `address` as `function+0xoffset`, or as a plain hex address
+    /// if it isn't in a known function.
+    #[instrument(skip_all, parent = Span::current(), level = "Trace")]
+    pub fn symbolize(&self, address: u64) -> String {
+        match self.lookup(address) {
+            Some((name, 0)) => name.to_string(),
+            Some((name, delta)) => format!("{}+{:#x}", name, delta),
+            None => format!("{:#x}", address),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use hyperlight_testing::simple_guest_as_string;
+
+    use super::{GuestSymbols, Symbol};
+    use crate::sandbox::uninitialized::GuestBinary;
+
+    #[test]
+    fn lookup() {
+        let symbols = GuestSymbols {
+            symbols: vec![
+                Symbol {
+                    offset: 0x100,
+                    size: 0x10,
+                    name: "sized".to_string(),
+                },
+                Symbol {
+                    offset: 0x200,
+                    size: 0,
+                    name: "unsized".to_string(),
+                },
+            ],
+            load_address: 0,
+        }
+        .with_load_address(0x1000);
+
+        assert_eq!(symbols.lookup(0x1100), Some(("sized", 0)));
+        assert_eq!(symbols.lookup(0x110f), Some(("sized", 0xf)));
+        assert_eq!(symbols.lookup(0x1110), None);
+        assert_eq!(symbols.lookup(0x1280), Some(("unsized", 0x80)));
+        assert_eq!(symbols.lookup(0x10ff), None);
+        assert_eq!(symbols.lookup(0x100), None);
+        assert_eq!(symbols.symbolize(0x1104), "sized+0x4");
+    }
+
+    #[test]
+    fn symbolize_simple_guest() {
+        let binary = GuestBinary::FilePath(simple_guest_as_string().unwrap());
+        let symbols = GuestSymbols::from_binary(&binary)
+            .unwrap()
+            .with_load_address(0x20_0000);
+
+        let (name, offset) = symbols
+            .symbols
+            .iter()
+            .find(|symbol| symbol.name == "entrypoint")
+            .map(|symbol| (symbol.name.clone(), symbol.offset))
+            .unwrap();
+        assert_eq!(symbols.symbolize(0x20_0000 + offset), name);
+        assert_eq!(symbols.symbolize(0x20_0000 + offset + 1), "entrypoint+0x1");
+        assert_eq!(symbols.symbolize(0x1000), "0x1000");
+    }
+}
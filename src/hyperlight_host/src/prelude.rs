@@ -0,0 +1,69 @@
+/*
+Copyright 2024 The Hyperlight Authors.
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+    http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+//! The types most hosts need to create sandboxes and call the functions of
+//! their guests, for importing with `use hyperlight_host::prelude::*`.
+//!
+//! Everything exported here is part of the stable API of this crate, and
+//! only changes in a semver-compatible way. The other public modules expose
+//! more specialised functionality, such as hypervisor capabilities, crash
+//! dumps and guest symbols, which may change more often. The
+//! implementation of sandboxes, their memory and the hypervisor drivers is
+//! not public at all, so it can be refactored without breaking hosts.
+//!
+//! ```no_run
+//! use hyperlight_host::prelude::*;
+//!
+//! fn main() -> Result<()> {
+//!     let mut config = SandboxConfiguration::default();
+//!     config.set_input_data_size(0x4000);
+//!     let sandbox = UninitializedSandbox::new(
+//!         GuestBinary::FilePath("simpleguest".to_string()),
+//!         Some(config),
+//!         None, // default run options
+//!         None, // default host print function
+//!     )?;
+//!     let mut sandbox: MultiUseSandbox = sandbox.evolve(Noop::default())?;
+//!     let message = sandbox.call_guest_function_by_name(
+//!         "Echo",
+//!         ReturnType::String,
+//!         Some(vec![ParameterValue::String("Hello".to_string())]),
+//!     )?;
+//!     assert_eq!(message, ReturnValue::String("Hello".to_string()));
+//!     Ok(())
+//! }
+//! ```
+
+pub use crate::error::HyperlightError;
+pub use crate::func::call_ctx::MultiUseGuestCallContext;
+#[cfg(feature = "async_host_functions")]
+pub use crate::func::{
+    AsyncHostFunction0, AsyncHostFunction1, AsyncHostFunction10, AsyncHostFunction2,
+    AsyncHostFunction3, AsyncHostFunction4, AsyncHostFunction5, AsyncHostFunction6,
+    AsyncHostFunction7, AsyncHostFunction8, AsyncHostFunction9,
+};
+pub use crate::func::{
+    HostFunction0, HostFunction1, HostFunction10, HostFunction2, HostFunction3, HostFunction4,
+    HostFunction5, HostFunction6, HostFunction7, HostFunction8, HostFunction9, ParameterValue,
+    ReturnType, ReturnValue,
+};
+pub use crate::sandbox::{
+    is_hypervisor_present, GuestBinary, MultiUseSandbox, ResultCachePolicy, SandboxConfiguration,
+    SandboxRunOptions, SingleUseSandbox, UninitializedSandbox,
+};
+pub use crate::sandbox_state::sandbox::{DevolvableSandbox, EvolvableSandbox, Sandbox};
+pub use crate::sandbox_state::transition::{MultiUseContextCallback, Noop};
+pub use crate::{new_error, Result};
@@ -0,0 +1,653 @@
+/*
+Copyright 2024 The Hyperlight Authors.
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+    http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, Mutex};
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+
+#[cfg(feature = "http_service")]
+use hyperlight_common::builtin_services::HTTP_REQUEST;
+use hyperlight_common::builtin_services::{
+    BUS_PUBLISH, BUS_RECEIVE, BUS_SUBSCRIBE, ENTROPY_RANDOM_BYTES, FS_CLOSE, FS_LIST, FS_OPEN,
+    FS_READ, FS_SEEK, FS_WRITE, KV_DELETE, KV_GET, KV_SET, METRICS_INCREMENT, PRINT,
+    TIME_MONOTONIC_NANOS, TIME_UNIX_NANOS,
+};
+use rand::RngCore;
+use tracing::{instrument, Span};
+
+use super::event_bus::{BusAccess, EventBus};
+use super::host_funcs::default_writer_func;
+#[cfg(feature = "http_service")]
+use super::http::HttpPolicy;
+use super::metrics::SandboxMetric::BuiltinServiceCounters;
+use super::vfs::{VfsState, VirtualFs};
+use super::UninitializedSandbox;
+#[cfg(feature = "http_service")]
+use crate::func::HostFunction4;
+use crate::func::{HostFunction0, HostFunction1, HostFunction2};
+use crate::{int_counter_vec_inc_by, log_then_return, new_error, Result};
+
+/// One of the services `BuiltinServices` provides to guests
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum BuiltinService {
+    /// `hyperlight::print`, which writes to the host's standard output
+    Print,
+    /// `hyperlight::time::unix_nanos` and
+    /// `hyperlight::time::monotonic_nanos`, which read the host's clocks
+    Time,
+    /// `hyperlight::entropy::random_bytes`, which returns random bytes
+    Entropy,
+    /// `hyperlight::metrics::increment`, which adds to the host's
+    /// `builtin_service_counters` metric for the names the host declared
+    Metrics,
+    /// `hyperlight::kv::get`, `hyperlight::kv::set` and
+    /// `hyperlight::kv::delete`, which keep a key-value store for the
+    /// sandbox on the host
+    KeyValue,
+}
+
+/// Which of the built-in services `BuiltinServices::register` provides,
+/// and the limits it puts on them. Every service is enabled by default.
+#[derive(Debug, Clone)]
+pub struct BuiltinServicesPolicy {
+    disabled: HashSet<BuiltinService>,
+    max_random_bytes: usize,
+    counter_names: HashSet<String>,
+    max_kv_keys: usize,
+    max_kv_bytes: usize,
+    vfs: Option<VirtualFs>,
+    bus: Option<(EventBus, BusAccess)>,
+    #[cfg(feature = "http_service")]
+    http: Option<HttpPolicy>,
+}
+
+impl Default for BuiltinServicesPolicy {
+    fn default() -> Self {
+        Self {
+            disabled: HashSet::new(),
+            max_random_bytes: 4096,
+            counter_names: HashSet::new(),
+            max_kv_keys: 256,
+            max_kv_bytes: 64 * 1024,
+            vfs: None,
+            bus: None,
+            #[cfg(feature = "http_service")]
+            http: None,
+        }
+    }
+}
+
+impl BuiltinServicesPolicy {
+    /// Create a policy with every service enabled
+    #[instrument(skip_all, parent = Span::current(), level = "Trace")]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Enable or disable `service`
+    #[instrument(skip(self), parent = Span::current(), level = "Trace")]
+    pub fn with_service(mut self, service: BuiltinService, enabled: bool) -> Self {
+        if enabled {
+            self.disabled.remove(&service);
+        } else {
+            self.disabled.insert(service);
+        }
+        self
+    }
+
+    /// Limit the bytes the guest can ask for in one call to
+    /// `hyperlight::entropy::random_bytes`. The default is 4096.
+    #[instrument(skip(self), parent = Span::current(), level = "Trace")]
+    pub fn with_max_random_bytes(mut self, max: usize) -> Self {
+        self.max_random_bytes = max;
+        self
+    }
+
+    /// Let the guest increment the counters called `names` with
+    /// `hyperlight::metrics::increment`, which fails for any other name.
+    /// Each counter is a series of the `builtin_service_counters` metric
+    /// that every sandbox in the process shares, so only the host chooses
+    /// the names, keeping the number of series bounded. By default there
+    /// are none.
+    #[instrument(skip_all, parent = Span::current(), level = "Trace")]
+    pub fn with_counter_names<I, S>(mut self, names: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.counter_names = names.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// Limit the key-value store of the sandbox to `max_keys` keys and
+    /// `max_bytes` bytes of keys and values together. The defaults are 256
+    /// keys and 64KiB.
+    #[instrument(skip(self), parent = Span::current(), level = "Trace")]
+    pub fn with_kv_quotas(mut self, max_keys: usize, max_bytes: usize) -> Self {
+        self.max_kv_keys = max_keys;
+        self.max_kv_bytes = max_bytes;
+        self
+    }
+
+    /// Give the guest `fs` through the `hyperlight::fs::*` services, which
+    /// aren't registered otherwise
+    #[instrument(skip_all, parent = Span::current(), level = "Trace")]
+    pub fn with_vfs(mut self, fs: VirtualFs) -> Self {
+        self.vfs = Some(fs);
+        self
+    }
+
+    /// Join the sandbox to `bus` through the `hyperlight::bus::*` services,
+    /// which aren't registered otherwise, publishing and subscribing to
+    /// the topics `access` allows
+    #[instrument(skip_all, parent = Span::current(), level = "Trace")]
+    pub fn with_event_bus(mut self, bus: EventBus, access: BusAccess) -> Self {
+        self.bus = Some((bus, access));
+        self
+    }
+
+    /// Let the guest make the HTTP requests `http` allows through the
+    /// `hyperlight::http::request` service, which isn't registered
+    /// otherwise
+    #[cfg(feature = "http_service")]
+    #[instrument(skip_all, parent = Span::current(), level = "Trace")]
+    pub fn with_http(mut self, http: HttpPolicy) -> Self {
+        self.http = Some(http);
+        self
+    }
+
+    /// Whether `service` is enabled
+    pub fn is_enabled(&self, service: BuiltinService) -> bool {
+        !self.disabled.contains(&service)
+    }
+}
+
+/// The host functions every host would otherwise write for itself, for
+/// printing, reading the time, getting random bytes, recording metrics,
+/// keeping state between calls, reading files, passing events between
+/// sandboxes and making HTTP requests.
+///
+/// They are registered under names starting with `hyperlight::`, which
+/// hosts can't use for their own functions, and guests find the names in
+/// `hyperlight_common::builtin_services`.
+pub struct BuiltinServices;
+
+/// Register `$func` as `$name`, allowing `$syscall`s under seccomp
+macro_rules! register {
+    ($func:expr, $sandbox:expr, $name:expr $(, $syscall:expr)*) => {{
+        #[cfg(all(feature = "seccomp", target_os = "linux"))]
+        let res = $func.register_with_extra_allowed_syscalls($sandbox, $name, vec![$($syscall),*]);
+        #[cfg(not(all(feature = "seccomp", target_os = "linux")))]
+        let res = $func.register($sandbox, $name);
+        res
+    }};
+}
+
+impl BuiltinServices {
+    /// Register the services `policy` enables with `sandbox`
+    #[instrument(err(Debug), skip_all, parent = Span::current(), level = "Trace")]
+    pub fn register(
+        sandbox: &mut UninitializedSandbox,
+        policy: BuiltinServicesPolicy,
+    ) -> Result<()> {
+        sandbox
+            .host_funcs
+            .try_lock()
+            .map_err(|e| new_error!("Error locking at {}:{}: {}", file!(), line!(), e))?
+            .set_allow_reserved_names(true);
+        let res = Self::register_enabled(sandbox, &policy);
+        sandbox
+            .host_funcs
+            .try_lock()
+            .map_err(|e| new_error!("Error locking at {}:{}: {}", file!(), line!(), e))?
+            .set_allow_reserved_names(false);
+        res
+    }
+
+    fn register_enabled(
+        sandbox: &mut UninitializedSandbox,
+        policy: &BuiltinServicesPolicy,
+    ) -> Result<()> {
+        if policy.is_enabled(BuiltinService::Print) {
+            let print = Arc::new(Mutex::new(default_writer_func));
+            register!(
+                print,
+                sandbox,
+                PRINT,
+                libc::SYS_mmap,
+                libc::SYS_brk,
+                libc::SYS_mprotect
+            )?;
+        }
+
+        if policy.is_enabled(BuiltinService::Time) {
+            let unix_nanos = Arc::new(Mutex::new(|| {
+                let since_epoch = SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .map_err(|e| new_error!("The host's clock is before 1970: {}", e))?;
+                Ok(since_epoch.as_nanos() as u64)
+            }));
+            register!(
+                unix_nanos,
+                sandbox,
+                TIME_UNIX_NANOS,
+                libc::SYS_clock_gettime
+            )?;
+
+            let start = Instant::now();
+            let monotonic_nanos =
+                Arc::new(Mutex::new(move || Ok(start.elapsed().as_nanos() as u64)));
+            register!(
+                monotonic_nanos,
+                sandbox,
+                TIME_MONOTONIC_NANOS,
+                libc::SYS_clock_gettime
+            )?;
+        }
+
+        if policy.is_enabled(BuiltinService::Entropy) {
+            let max = policy.max_random_bytes;
+            let random_bytes = Arc::new(Mutex::new(move |len: i32| {
+                let len = match usize::try_from(len) {
+                    Ok(len) if len <= max => len,
+                    _ => {
+                        log_then_return!(
+                            "The guest asked for {} random bytes, but at most {} are allowed",
+                            len,
+                            max
+                        );
+                    }
+                };
+                let mut bytes = vec![0; len];
+                rand::rngs::OsRng.fill_bytes(&mut bytes);
+                Ok(bytes)
+            }));
+            register!(
+                random_bytes,
+                sandbox,
+                ENTROPY_RANDOM_BYTES,
+                libc::SYS_getrandom
+            )?;
+        }
+
+        if policy.is_enabled(BuiltinService::Metrics) {
+            let names = policy.counter_names.clone();
+            let increment = Arc::new(Mutex::new(move |name: String, value: u64| {
+                if !names.contains(&name) {
+                    log_then_return!("The host didn't declare a counter called {}", name);
+                }
+                int_counter_vec_inc_by!(&BuiltinServiceCounters, &[name.as_str()], value);
+                Ok(())
+            }));
+            register!(increment, sandbox, METRICS_INCREMENT)?;
+        }
+
+        if policy.is_enabled(BuiltinService::KeyValue) {
+            let store = Arc::new(Mutex::new(KvStore::new(
+                policy.max_kv_keys,
+                policy.max_kv_bytes,
+            )));
+
+            let get_store = store.clone();
+            let get = Arc::new(Mutex::new(move |key: String| {
+                Ok(lock_state(&get_store)?.get(&key))
+            }));
+            register!(get, sandbox, KV_GET, libc::SYS_mmap, libc::SYS_brk)?;
+
+            let set_store = store.clone();
+            let set = Arc::new(Mutex::new(move |key: String, value: Vec<u8>| {
+                lock_state(&set_store)?.set(key, value)
+            }));
+            register!(set, sandbox, KV_SET, libc::SYS_mmap, libc::SYS_brk)?;
+
+            let delete = Arc::new(Mutex::new(move |key: String| {
+                lock_state(&store)?.delete(&key);
+                Ok(())
+            }));
+            register!(delete, sandbox, KV_DELETE)?;
+        }
+
+        if let Some(fs) = &policy.vfs {
+            Self::register_vfs(sandbox, fs.clone())?;
+        }
+
+        if let Some((bus, access)) = &policy.bus {
+            let member = Arc::new(bus.join(access.clone())?);
+
+            let publish_member = member.clone();
+            let publish = Arc::new(Mutex::new(move |topic: String, payload: Vec<u8>| {
+                publish_member.publish(&topic, payload)
+            }));
+            register!(publish, sandbox, BUS_PUBLISH, libc::SYS_mmap, libc::SYS_brk)?;
+
+            let subscribe_member = member.clone();
+            let subscribe = Arc::new(Mutex::new(move |topic: String| {
+                subscribe_member.subscribe(&topic)
+            }));
+            register!(
+                subscribe,
+                sandbox,
+                BUS_SUBSCRIBE,
+                libc::SYS_mmap,
+                libc::SYS_brk
+            )?;
+
+            let receive = Arc::new(Mutex::new(move |topic: String| member.receive(&topic)));
+            register!(receive, sandbox, BUS_RECEIVE)?;
+        }
+
+        #[cfg(feature = "http_service")]
+        if let Some(http) = &policy.http {
+            let http = http.clone();
+            let request = Arc::new(Mutex::new(
+                move |method: String, url: String, headers: String, body: Vec<u8>| {
+                    http.request(&method, &url, &headers, body)
+                },
+            ));
+            register!(
+                request,
+                sandbox,
+                HTTP_REQUEST,
+                libc::SYS_socket,
+                libc::SYS_connect,
+                libc::SYS_bind,
+                libc::SYS_sendto,
+                libc::SYS_recvfrom,
+                libc::SYS_sendmsg,
+                libc::SYS_recvmsg,
+                libc::SYS_sendmmsg,
+                libc::SYS_poll,
+                libc::SYS_ppoll,
+                libc::SYS_setsockopt,
+                libc::SYS_getsockopt,
+                libc::SYS_getsockname,
+                libc::SYS_getpeername,
+                libc::SYS_shutdown,
+                libc::SYS_read,
+                libc::SYS_write,
+                libc::SYS_close,
+                libc::SYS_fcntl,
+                libc::SYS_ioctl,
+                libc::SYS_openat,
+                libc::SYS_newfstatat,
+                libc::SYS_fstat,
+                libc::SYS_lseek,
+                libc::SYS_uname,
+                libc::SYS_getrandom,
+                libc::SYS_futex,
+                libc::SYS_mmap,
+                libc::SYS_munmap,
+                libc::SYS_brk,
+                libc::SYS_mprotect
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+impl BuiltinServices {
+    fn register_vfs(sandbox: &mut UninitializedSandbox, fs: VirtualFs) -> Result<()> {
+        let state = Arc::new(Mutex::new(VfsState::new(fs)?));
+        let open_state = state.clone();
+        let open = Arc::new(Mutex::new(move |path: String, mode: i32| {
+            lock_state(&open_state)?.open(&path, mode)
+        }));
+        register!(
+            open,
+            sandbox,
+            FS_OPEN,
+            libc::SYS_openat,
+            libc::SYS_newfstatat,
+            libc::SYS_statx,
+            libc::SYS_readlink,
+            libc::SYS_mmap,
+            libc::SYS_brk
+        )?;
+
+        let read_state = state.clone();
+        let read = Arc::new(Mutex::new(move |handle: i32, len: i32| {
+            lock_state(&read_state)?.read(handle, len)
+        }));
+        register!(
+            read,
+            sandbox,
+            FS_READ,
+            libc::SYS_read,
+            libc::SYS_mmap,
+            libc::SYS_brk
+        )?;
+
+        let write_state = state.clone();
+        let write = Arc::new(Mutex::new(move |handle: i32, bytes: Vec<u8>| {
+            lock_state(&write_state)?.write(handle, bytes)
+        }));
+        register!(
+            write,
+            sandbox,
+            FS_WRITE,
+            libc::SYS_write,
+            libc::SYS_lseek,
+            libc::SYS_fstat,
+            libc::SYS_statx,
+            libc::SYS_mmap,
+            libc::SYS_brk
+        )?;
+
+        let seek_state = state.clone();
+        let seek = Arc::new(Mutex::new(move |handle: i32, offset: u64| {
+            lock_state(&seek_state)?.seek(handle, offset)
+        }));
+        register!(seek, sandbox, FS_SEEK, libc::SYS_lseek)?;
+
+        let close_state = state.clone();
+        let close = Arc::new(Mutex::new(move |handle: i32| {
+            lock_state(&close_state)?.close(handle)
+        }));
+        register!(close, sandbox, FS_CLOSE, libc::SYS_close)?;
+
+        let list = Arc::new(Mutex::new(move |path: String| {
+            lock_state(&state)?.list(&path)
+        }));
+        register!(
+            list,
+            sandbox,
+            FS_LIST,
+            libc::SYS_openat,
+            libc::SYS_getdents64,
+            libc::SYS_newfstatat,
+            libc::SYS_statx,
+            libc::SYS_readlink,
+            libc::SYS_fstat,
+            libc::SYS_close,
+            libc::SYS_mmap,
+            libc::SYS_brk
+        )
+    }
+}
+
+/// The state `hyperlight::kv::*` keeps for a sandbox
+struct KvStore {
+    entries: HashMap<String, Vec<u8>>,
+    /// The bytes of the keys and values in `entries`
+    bytes: usize,
+    max_keys: usize,
+    max_bytes: usize,
+}
+
+impl KvStore {
+    fn new(max_keys: usize, max_bytes: usize) -> Self {
+        Self {
+            entries: HashMap::new(),
+            bytes: 0,
+            max_keys,
+            max_bytes,
+        }
+    }
+
+    /// The value of `key`, which is empty if it isn't set
+    fn get(&self, key: &str) -> Vec<u8> {
+        self.entries.get(key).cloned().unwrap_or_default()
+    }
+
+    /// Set `key` to `value`, or delete it if `value` is empty
+    fn set(&mut self, key: String, value: Vec<u8>) -> Result<()> {
+        if value.is_empty() {
+            self.delete(&key);
+            return Ok(());
+        }
+        let (keys, bytes) = match self.entries.get(&key) {
+            Some(old) => (self.entries.len(), self.bytes - old.len() + value.len()),
+            None => (self.entries.len() + 1, self.bytes + key.len() + value.len()),
+        };
+        if keys > self.max_keys {
+            log_then_return!(
+                "The guest's key-value store can hold at most {} keys",
+                self.max_keys
+            );
+        }
+        if bytes > self.max_bytes {
+            log_then_return!(
+                "The guest's key-value store can hold at most {} bytes",
+                self.max_bytes
+            );
+        }
+        self.bytes = bytes;
+        self.entries.insert(key, value);
+        Ok(())
+    }
+
+    fn delete(&mut self, key: &str) {
+        if let Some(value) = self.entries.remove(key) {
+            self.bytes -= key.len() + value.len();
+        }
+    }
+}
+
+/// Lock the state shared by the functions of a service
+fn lock_state<T>(state: &Mutex<T>) -> Result<std::sync::MutexGuard<'_, T>> {
+    state
+        .try_lock()
+        .map_err(|e| new_error!("Error locking at {}:{}: {}", file!(), line!(), e))
+}
+
+#[cfg(test)]
+mod tests {
+    use hyperlight_common::flatbuffer_wrappers::function_types::{ParameterValue, ReturnValue};
+    use hyperlight_testing::simple_guest_as_string;
+
+    use super::*;
+    use crate::sandbox::uninitialized::GuestBinary;
+
+    fn sandbox() -> UninitializedSandbox {
+        UninitializedSandbox::new(
+            GuestBinary::FilePath(simple_guest_as_string().unwrap()),
+            None,
+            None,
+            None,
+        )
+        .unwrap()
+    }
+
+    fn call(
+        sandbox: &UninitializedSandbox,
+        name: &str,
+        args: Vec<ParameterValue>,
+    ) -> Result<ReturnValue> {
+        sandbox
+            .host_funcs
+            .try_lock()
+            .unwrap()
+            .call_host_function(name, args)
+    }
+
+    #[test]
+    fn register() {
+        let mut sandbox = sandbox();
+        let policy = BuiltinServicesPolicy::new()
+            .with_service(BuiltinService::Print, false)
+            .with_max_random_bytes(16);
+        BuiltinServices::register(&mut sandbox, policy).unwrap();
+
+        assert!(call(&sandbox, PRINT, vec![ParameterValue::String("x".into())]).is_err());
+        assert!(matches!(
+            call(&sandbox, TIME_UNIX_NANOS, vec![]),
+            Ok(ReturnValue::ULong(nanos)) if nanos > 0
+        ));
+        assert!(matches!(
+            call(&sandbox, ENTROPY_RANDOM_BYTES, vec![ParameterValue::Int(16)]),
+            Ok(ReturnValue::VecBytes(bytes)) if bytes.len() == 16
+        ));
+        assert!(call(
+            &sandbox,
+            ENTROPY_RANDOM_BYTES,
+            vec![ParameterValue::Int(17)]
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn counter_names() {
+        let mut sandbox = sandbox();
+        let policy = BuiltinServicesPolicy::new().with_counter_names(["requests"]);
+        BuiltinServices::register(&mut sandbox, policy).unwrap();
+
+        let increment = |name: &str| {
+            call(
+                &sandbox,
+                METRICS_INCREMENT,
+                vec![
+                    ParameterValue::String(name.to_string()),
+                    ParameterValue::ULong(1),
+                ],
+            )
+        };
+        assert!(matches!(increment("requests"), Ok(ReturnValue::Void)));
+        assert!(increment("requests-from-the-guest").is_err());
+    }
+
+    #[test]
+    fn reserved_names() {
+        let mut sandbox = sandbox();
+        let func = Arc::new(Mutex::new(|| -> Result<i32> { Ok(0) }));
+        assert!(func.register(&mut sandbox, "hyperlight::mine").is_err());
+        assert!(func.register(&mut sandbox, "mine").is_ok());
+    }
+
+    #[test]
+    fn kv_store_quotas() {
+        let mut store = KvStore::new(2, 10);
+        store.set("a".to_string(), vec![1, 2, 3]).unwrap();
+        assert_eq!(store.get("a"), vec![1, 2, 3]);
+        assert_eq!(store.get("b"), Vec::<u8>::new());
+
+        // 1 + 3 + 1 + 6 bytes is too many
+        assert!(store.set("b".to_string(), vec![0; 6]).is_err());
+        store.set("b".to_string(), vec![0; 5]).unwrap();
+        assert!(store.set("c".to_string(), vec![0]).is_err());
+
+        // replacing a value only counts the difference
+        store.set("a".to_string(), vec![0; 3]).unwrap();
+        assert!(store.set("a".to_string(), vec![0; 4]).is_err());
+
+        store.set("b".to_string(), vec![]).unwrap();
+        assert_eq!(store.get("b"), Vec::<u8>::new());
+        store.set("c".to_string(), vec![0; 5]).unwrap();
+        store.delete("a");
+        store.delete("c");
+        assert_eq!(store.bytes, 0);
+    }
+}
@@ -0,0 +1,240 @@
+/*
+Copyright 2024 The Hyperlight Authors.
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+    http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+use std::fs;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use tracing::{instrument, Span};
+
+use super::SandboxId;
+use crate::{log_then_return, new_error, Result};
+
+/// The cgroup v2 a sandbox's vCPU thread is placed in, and the limits the
+/// kernel enforces on it.
+///
+/// The cgroup is created as `hyperlight-<sandbox id>` under `parent`, which
+/// must be the cgroup the process is in or a threaded cgroup below it,
+/// and is made a threaded cgroup so that it can hold single threads.
+/// Threads the vCPU thread starts, such as the ones host functions run on
+/// when the `seccomp` feature is on, are placed in it too. The cgroup is
+/// removed when the sandbox is dropped.
+///
+/// The `memory` controller isn't a threaded controller, so the memory of
+/// a sandbox can't be limited this way. Memory is shared by all the threads
+/// of a process, so limit it on the cgroup the whole process is in instead.
+#[derive(Debug, Clone)]
+pub struct CgroupOptions {
+    parent: PathBuf,
+    cpu_max: Option<(Duration, Duration)>,
+}
+
+impl CgroupOptions {
+    /// Create the cgroups of sandboxes under `parent`, such as
+    /// `/sys/fs/cgroup/my-service`, without any limits
+    #[instrument(skip_all, parent = Span::current(), level = "Trace")]
+    pub fn new(parent: impl Into<PathBuf>) -> Self {
+        Self {
+            parent: parent.into(),
+            cpu_max: None,
+        }
+    }
+
+    /// Let the vCPU thread run for at most `quota` of every `period`,
+    /// through the cgroup's `cpu.max`. The `cpu` controller is enabled in
+    /// `parent` for this.
+    #[instrument(skip_all, parent = Span::current(), level = "Trace")]
+    pub fn with_cpu_max(mut self, quota: Duration, period: Duration) -> Self {
+        self.cpu_max = Some((quota, period));
+        self
+    }
+}
+
+/// What the threads in a sandbox's cgroup have used, read back from the
+/// cgroup's statistics
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CgroupUsage {
+    /// The CPU time used, from `cpu.stat`
+    pub cpu_time: Duration,
+    /// The time the threads were throttled for by `cpu.max`, from
+    /// `cpu.stat`, or zero if the `cpu` controller isn't enabled
+    pub throttled_time: Duration,
+    /// The memory in use, from `memory.current`, or `None` if the `memory`
+    /// controller isn't enabled
+    pub memory: Option<u64>,
+}
+
+/// A cgroup created for a sandbox, removed when dropped
+#[derive(Debug)]
+pub(crate) struct SandboxCgroup {
+    path: PathBuf,
+}
+
+impl SandboxCgroup {
+    /// Create the cgroup of the sandbox `id` with the limits in `options`
+    #[instrument(err(Debug), skip_all, parent = Span::current(), level = "Trace")]
+    pub(crate) fn new(options: &CgroupOptions, id: SandboxId) -> Result<Self> {
+        let path = options.parent.join(format!("hyperlight-{}", id));
+        fs::create_dir(&path)
+            .map_err(|e| new_error!("Failed to create cgroup {}: {}", path.display(), e))?;
+        // from here on the cgroup is removed if setting it up fails
+        let cgroup = Self { path };
+
+        write_file(&cgroup.path.join("cgroup.type"), "threaded")?;
+        if let Some((quota, period)) = options.cpu_max {
+            write_file(&options.parent.join("cgroup.subtree_control"), "+cpu")?;
+            write_file(
+                &cgroup.path.join("cpu.max"),
+                &format!("{} {}", quota.as_micros(), period.as_micros()),
+            )?;
+        }
+        Ok(cgroup)
+    }
+
+    /// Move the calling thread into the cgroup
+    #[instrument(err(Debug), skip_all, parent = Span::current(), level = "Trace")]
+    pub(crate) fn add_current_thread(&self) -> Result<()> {
+        // 0 stands for the thread doing the write
+        write_file(&self.path.join("cgroup.threads"), "0")
+    }
+
+    /// Read what the threads in the cgroup have used
+    #[instrument(err(Debug), skip_all, parent = Span::current(), level = "Trace")]
+    pub(crate) fn usage(&self) -> Result<CgroupUsage> {
+        let cpu_stat = read_file(&self.path.join("cpu.stat"))?;
+        let stat = |name: &str| {
+            cpu_stat
+                .lines()
+                .filter_map(|line| line.split_once(' '))
+                .find(|(key, _)| *key == name)
+                .and_then(|(_, value)| value.trim().parse::<u64>().ok())
+                .map(Duration::from_micros)
+        };
+        let memory = match fs::read_to_string(self.path.join("memory.current")) {
+            Ok(current) => Some(current.trim().parse::<u64>().map_err(|e| {
+                new_error!(
+                    "Failed to parse memory.current of cgroup {}: {}",
+                    self.path.display(),
+                    e
+                )
+            })?),
+            Err(e) if e.kind() == ErrorKind::NotFound => None,
+            Err(e) => {
+                log_then_return!(
+                    "Failed to read memory.current of cgroup {}: {}",
+                    self.path.display(),
+                    e
+                );
+            }
+        };
+        Ok(CgroupUsage {
+            cpu_time: stat("usage_usec")
+                .ok_or_else(|| new_error!("cgroup {} has no CPU usage", self.path.display()))?,
+            throttled_time: stat("throttled_usec").unwrap_or_default(),
+            memory,
+        })
+    }
+}
+
+impl Drop for SandboxCgroup {
+    fn drop(&mut self) {
+        // a thread that has just been joined can take a moment to leave
+        // the cgroup
+        for _ in 0..100 {
+            match fs::remove_dir(&self.path) {
+                Err(e) if e.raw_os_error() == Some(libc::EBUSY) => {
+                    std::thread::sleep(Duration::from_millis(1))
+                }
+                Err(e) => {
+                    log::error!("Failed to remove cgroup {}: {}", self.path.display(), e);
+                    return;
+                }
+                Ok(()) => return,
+            }
+        }
+        log::error!(
+            "Failed to remove cgroup {}, it still has threads in it",
+            self.path.display()
+        );
+    }
+}
+
+fn write_file(path: &Path, contents: &str) -> Result<()> {
+    fs::write(path, contents).map_err(|e| {
+        new_error!(
+            "Failed to write {:?} to {}: {}",
+            contents,
+            path.display(),
+            e
+        )
+    })
+}
+
+fn read_file(path: &Path) -> Result<String> {
+    fs::read_to_string(path).map_err(|e| new_error!("Failed to read {}: {}", path.display(), e))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::time::Duration;
+
+    use super::{CgroupOptions, CgroupUsage, SandboxCgroup};
+    use crate::sandbox::randomness::SandboxRng;
+    use crate::sandbox::SandboxId;
+
+    // a plain directory stands in for the cgroup file system, which only
+    // root can change
+    #[test]
+    fn writes_limits_and_reads_usage() {
+        let parent = tempfile::tempdir().unwrap();
+        let options = CgroupOptions::new(parent.path())
+            .with_cpu_max(Duration::from_millis(50), Duration::from_millis(100));
+        let id = SandboxId::new(&mut SandboxRng::default());
+        let cgroup = SandboxCgroup::new(&options, id).unwrap();
+
+        let path = parent.path().join(format!("hyperlight-{}", id));
+        assert_eq!(
+            fs::read_to_string(path.join("cgroup.type")).unwrap(),
+            "threaded"
+        );
+        assert_eq!(
+            fs::read_to_string(path.join("cpu.max")).unwrap(),
+            "50000 100000"
+        );
+        assert_eq!(
+            fs::read_to_string(parent.path().join("cgroup.subtree_control")).unwrap(),
+            "+cpu"
+        );
+
+        fs::write(
+            path.join("cpu.stat"),
+            "usage_usec 1500\nuser_usec 1000\nsystem_usec 500\nthrottled_usec 20\n",
+        )
+        .unwrap();
+        assert_eq!(
+            cgroup.usage().unwrap(),
+            CgroupUsage {
+                cpu_time: Duration::from_micros(1500),
+                throttled_time: Duration::from_micros(20),
+                memory: None,
+            }
+        );
+        fs::write(path.join("memory.current"), "4096\n").unwrap();
+        assert_eq!(cgroup.usage().unwrap().memory, Some(4096));
+    }
+}
@@ -17,9 +17,32 @@ limitations under the License.
 use std::cmp::{max, min};
 use std::time::Duration;
 
+use serde::Deserialize;
 use tracing::{instrument, Span};
 
 use crate::mem::exe::ExeInfo;
+use crate::sandbox::redaction::RedactionPolicy;
+use crate::{log_then_return, Result};
+
+/// The length of the Ed25519 public keys guests are verified with
+pub const GUEST_PUBLIC_KEY_LEN: usize = 32;
+
+/// How a sandbox trades CPU time for latency while the host waits for the
+/// guest and the guest waits for the host
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+#[repr(C)]
+pub enum LatencyProfile {
+    /// Threads that have to wait, such as the thread calling a guest
+    /// function while the guest runs, block straight away
+    #[default]
+    Balanced,
+    /// Threads that have to wait spin for up to the busy poll window
+    /// before they block, so that short guest calls and host function
+    /// calls return without the thread waiting on them being woken up.
+    /// This takes a CPU for the window each time.
+    LowLatency,
+}
 
 /// The complete set of configuration needed to create a Sandbox
 #[derive(Copy, Clone, Debug, Eq, PartialEq)]
@@ -39,6 +62,33 @@ pub struct SandboxConfiguration {
     /// The size of the memory buffer that is made available for input to the
     /// Guest Binary
     output_data_size: usize,
+    /// The size the input buffer can grow to when a function call or host
+    /// function return value doesn't fit in it. If this is not greater
+    /// than `input_data_size`, the input buffer doesn't grow.
+    max_input_data_size: usize,
+    /// The size the output buffer can grow to when a return value or host
+    /// function call doesn't fit in it. If this is not greater than
+    /// `output_data_size`, the output buffer doesn't grow.
+    max_output_data_size: usize,
+    /// The largest value that can be sent between the host and the guest
+    /// in segments when it doesn't fit in the grown input or output
+    /// buffer. If set to 0, values that don't fit aren't sent.
+    max_segmented_data_size: usize,
+    /// `VecBytes` parameters and return values at least this many bytes
+    /// long are compressed before they are copied between the host and
+    /// the guest. If set to 0, values are never compressed.
+    compression_threshold: usize,
+    /// Whether the elements pushed onto the input and output buffers are
+    /// tagged with a digest, which is checked before they are popped
+    shared_buffer_integrity: bool,
+    /// Whether `UninitializedSandbox::new` refuses guest binaries that
+    /// aren't signed by one of the trusted guest keys
+    require_signed_guests: bool,
+    /// The public keys guest binaries may be signed with. Only the first
+    /// `trusted_guest_key_count` are in use.
+    trusted_guest_keys: [[u8; GUEST_PUBLIC_KEY_LEN]; Self::MAX_TRUSTED_GUEST_KEYS],
+    /// The number of keys in `trusted_guest_keys` that are in use
+    trusted_guest_key_count: usize,
     /// The stack size to use in the guest sandbox. If set to 0, the stack
     /// size will be determined from the PE file header.
     ///
@@ -85,6 +135,40 @@ pub struct SandboxConfiguration {
     /// The size of the memory buffer that is made available for serializing
     /// guest panic context
     guest_panic_context_buffer_size: usize,
+    /// The NUMA node to allocate the sandbox's memory on. If negative, the
+    /// memory is allocated wherever the OS chooses.
+    ///
+    /// Note: this is a C-compatible struct, so even though this optional
+    /// field should be represented as an `Option`, that type is not
+    /// FFI-safe, so it cannot be.
+    numa_node: i64,
+    /// A bitmask of the CPUs the thread running the sandbox's vCPU may run
+    /// on, bit `n % 64` of element `n / 64` being CPU `n`. If no bit is
+    /// set, the thread may run on any CPU.
+    cpu_affinity: [u64; Self::MAX_CPUS / 64],
+    /// Whether crash dumps leave out the contents of the input and output
+    /// buffers
+    exclude_io_buffers_from_crashdumps: bool,
+    /// Whether the audit log records the value of each guest function
+    /// call parameter rather than only its type
+    log_audited_parameter_values: bool,
+    /// Whether the audit log records a hash of each guest function call
+    /// parameter rather than the parameter itself
+    hash_audited_parameters: bool,
+    /// Whether the sandbox's random values come from `deterministic_seed`
+    /// rather than the OS
+    use_deterministic_seed: bool,
+    /// The seed the sandbox's random values come from, if
+    /// `use_deterministic_seed` is set
+    deterministic_seed: u64,
+    /// Whether threads waiting on the guest or host spin before they block
+    latency_profile: LatencyProfile,
+    /// How long threads spin for before they block, in microseconds, when
+    /// `latency_profile` is `LowLatency`
+    busy_poll_window_us: u32,
+    /// Whether the pages of guest memory are allocated when the sandbox
+    /// is created rather than when they are first touched
+    prefault_memory: bool,
 }
 
 impl SandboxConfiguration {
@@ -136,6 +220,13 @@ impl SandboxConfiguration {
     pub const MIN_KERNEL_STACK_SIZE: usize = 0x1000;
     /// The default value for kernel stack size
     pub const DEFAULT_KERNEL_STACK_SIZE: usize = Self::MIN_KERNEL_STACK_SIZE;
+    /// The maximum number of keys guest binaries can be trusted to be
+    /// signed with
+    pub const MAX_TRUSTED_GUEST_KEYS: usize = 8;
+    /// The number of CPUs that can be named in a CPU affinity
+    pub const MAX_CPUS: usize = 1024;
+    /// The default busy poll window (in microseconds)
+    pub const DEFAULT_BUSY_POLL_WINDOW_US: u32 = 50;
 
     #[allow(clippy::too_many_arguments)]
     /// Create a new configuration for a sandbox with the given sizes.
@@ -157,6 +248,24 @@ impl SandboxConfiguration {
         Self {
             input_data_size: max(input_data_size, Self::MIN_INPUT_SIZE),
             output_data_size: max(output_data_size, Self::MIN_OUTPUT_SIZE),
+            max_input_data_size: 0,
+            max_output_data_size: 0,
+            max_segmented_data_size: 0,
+            compression_threshold: 0,
+            shared_buffer_integrity: false,
+            require_signed_guests: false,
+            trusted_guest_keys: [[0; GUEST_PUBLIC_KEY_LEN]; Self::MAX_TRUSTED_GUEST_KEYS],
+            trusted_guest_key_count: 0,
+            numa_node: -1,
+            cpu_affinity: [0; Self::MAX_CPUS / 64],
+            exclude_io_buffers_from_crashdumps: false,
+            log_audited_parameter_values: false,
+            hash_audited_parameters: false,
+            use_deterministic_seed: false,
+            deterministic_seed: 0,
+            latency_profile: LatencyProfile::Balanced,
+            busy_poll_window_us: Self::DEFAULT_BUSY_POLL_WINDOW_US,
+            prefault_memory: false,
             host_function_definition_size: max(
                 function_definition_size,
                 Self::MIN_HOST_FUNCTION_DEFINITION_SIZE,
@@ -237,6 +346,209 @@ impl SandboxConfiguration {
         self.output_data_size = max(output_data_size, Self::MIN_OUTPUT_SIZE);
     }
 
+    /// Set the size the input buffer can grow to when a function call or host
+    /// function return value doesn't fit in it.
+    ///
+    /// The memory for the largest buffer is reserved when the sandbox is
+    /// created, but the guest only sees the buffer grow when the host needs
+    /// more space, and it shrinks back when the sandbox is restored after
+    /// the call.
+    #[instrument(skip_all, parent = Span::current(), level= "Trace")]
+    pub fn set_max_input_data_size(&mut self, max_input_data_size: usize) {
+        self.max_input_data_size = max_input_data_size;
+    }
+
+    /// Set the size the output buffer can grow to when a return value or
+    /// host function call doesn't fit in it.
+    ///
+    /// As with `set_max_input_data_size`, the memory is reserved up front
+    /// and the buffer only grows when the guest needs more space.
+    #[instrument(skip_all, parent = Span::current(), level= "Trace")]
+    pub fn set_max_output_data_size(&mut self, max_output_data_size: usize) {
+        self.max_output_data_size = max_output_data_size;
+    }
+
+    /// Set the size of the largest function call, return value or host
+    /// function call that is sent between the host and the guest in
+    /// segments when it doesn't fit in the input or output buffer, even
+    /// grown to its maximum size. The receiving side holds on to the
+    /// segments until the whole value has arrived, so this bounds the
+    /// memory a guest can make the host allocate.
+    ///
+    /// Values that don't fit aren't sent in segments by default.
+    #[instrument(skip_all, parent = Span::current(), level= "Trace")]
+    pub fn set_max_segmented_data_size(&mut self, max_segmented_data_size: usize) {
+        self.max_segmented_data_size = max_segmented_data_size;
+    }
+
+    /// Compress `VecBytes` parameters and return values that are at least
+    /// `compression_threshold` bytes long, trading some CPU time for
+    /// copying less data between the host and the guest. 0, the default,
+    /// turns compression off.
+    ///
+    /// The host only compresses values it sends to guests that report they
+    /// can decompress them.
+    #[instrument(skip_all, parent = Span::current(), level= "Trace")]
+    pub fn set_compression_threshold(&mut self, compression_threshold: usize) {
+        self.compression_threshold = compression_threshold;
+    }
+
+    /// Tag every element pushed onto the input and output buffers with a
+    /// tag covering it and where it is in the buffer, so that a guest that
+    /// corrupts the data or framing of the values passed through them
+    /// fails with `HyperlightError::SharedBufferIntegrity` rather than
+    /// being deserialized.
+    ///
+    /// The elements the host sends are tagged with an HMAC-SHA256 keyed
+    /// with a key the host generates when the sandbox is created and keeps
+    /// out of guest memory, and are checked for as long as they are on the
+    /// input buffer. The guest has no key to tag the elements it returns
+    /// with, so they are tagged with a SHA-256 digest, which catches ones
+    /// that were corrupted but not ones the guest forged.
+    #[instrument(skip_all, parent = Span::current(), level= "Trace")]
+    pub fn set_shared_buffer_integrity(&mut self, enabled: bool) {
+        self.shared_buffer_integrity = enabled;
+    }
+
+    /// Refuse to create sandboxes for guest binaries that aren't signed by
+    /// one of the keys added with `add_trusted_guest_key`, failing with
+    /// `HyperlightError::GuestSignatureInvalid`. See
+    /// `hyperlight_host::sandbox::signing` for how guests are signed.
+    ///
+    /// Checking signatures needs the `signed_guests` feature, without it
+    /// requiring signed guests is an error.
+    #[instrument(err(Debug), skip_all, parent = Span::current(), level= "Trace")]
+    pub fn set_require_signed_guests(&mut self, require_signed_guests: bool) -> Result<()> {
+        if require_signed_guests && cfg!(not(feature = "signed_guests")) {
+            log_then_return!("Requiring signed guests needs the signed_guests feature");
+        }
+        self.require_signed_guests = require_signed_guests;
+        Ok(())
+    }
+
+    /// Trust guest binaries signed with the Ed25519 key whose public key
+    /// is `public_key`. Up to `MAX_TRUSTED_GUEST_KEYS` keys can be trusted.
+    #[instrument(err(Debug), skip_all, parent = Span::current(), level= "Trace")]
+    pub fn add_trusted_guest_key(&mut self, public_key: [u8; GUEST_PUBLIC_KEY_LEN]) -> Result<()> {
+        if self.trusted_guest_key_count == Self::MAX_TRUSTED_GUEST_KEYS {
+            log_then_return!(
+                "At most {} guest keys can be trusted",
+                Self::MAX_TRUSTED_GUEST_KEYS
+            );
+        }
+        self.trusted_guest_keys[self.trusted_guest_key_count] = public_key;
+        self.trusted_guest_key_count += 1;
+        Ok(())
+    }
+
+    /// Allocate the memory of sandboxes on the NUMA node `numa_node`, so
+    /// that a sandbox whose vCPU is pinned to the CPUs of that node with
+    /// `set_cpu_affinity` doesn't reach across sockets for its memory.
+    ///
+    /// This is only supported on Linux, creating a sandbox elsewhere fails
+    /// if it is set.
+    #[instrument(skip_all, parent = Span::current(), level= "Trace")]
+    pub fn set_numa_node(&mut self, numa_node: u32) {
+        self.numa_node = numa_node.into();
+    }
+
+    /// Only run the vCPUs of sandboxes on the CPUs in `cpus`, numbered as
+    /// the OS numbers them. An empty list lets them run on any CPU, as they
+    /// do by default. CPUs from `MAX_CPUS` up can't be named.
+    ///
+    /// This is only supported on Linux, creating a sandbox elsewhere fails
+    /// if it is set.
+    #[instrument(err(Debug), skip_all, parent = Span::current(), level= "Trace")]
+    pub fn set_cpu_affinity(&mut self, cpus: &[usize]) -> Result<()> {
+        let mut cpu_affinity = [0; Self::MAX_CPUS / 64];
+        for &cpu in cpus {
+            if cpu >= Self::MAX_CPUS {
+                log_then_return!(
+                    "CPU {} is out of range, at most {} CPUs can be named",
+                    cpu,
+                    Self::MAX_CPUS
+                );
+            }
+            cpu_affinity[cpu / 64] |= 1 << (cpu % 64);
+        }
+        self.cpu_affinity = cpu_affinity;
+        Ok(())
+    }
+
+    /// Write zeros in place of the input and output buffers in crash
+    /// dumps, so that the parameters and return values of the calls in
+    /// flight when the guest crashed aren't written to disk. The rest of
+    /// the dump keeps its layout.
+    #[instrument(skip_all, parent = Span::current(), level= "Trace")]
+    pub fn set_exclude_io_buffers_from_crashdumps(&mut self, exclude: bool) {
+        self.exclude_io_buffers_from_crashdumps = exclude;
+    }
+
+    /// Record the value of each parameter of a guest function call in the
+    /// `hyperlight_host::audit` log, which otherwise only has the types of
+    /// the parameters. `Secret` parameters are never recorded.
+    #[instrument(skip_all, parent = Span::current(), level= "Trace")]
+    pub fn set_log_audited_parameter_values(&mut self, log: bool) {
+        self.log_audited_parameter_values = log;
+    }
+
+    /// Record an HMAC-SHA256 of each parameter of a guest function call in
+    /// the `hyperlight_host::audit` log rather than only its type, so that
+    /// calls with the same arguments can be matched up without the
+    /// arguments being kept. The key is random and only lives as long as
+    /// the process, so the hashes can only be matched up within a process.
+    /// This takes precedence over `set_log_audited_parameter_values`.
+    /// `Secret` parameters are never recorded.
+    #[instrument(skip_all, parent = Span::current(), level= "Trace")]
+    pub fn set_hash_audited_parameters(&mut self, hash: bool) {
+        self.hash_audited_parameters = hash;
+    }
+
+    /// Generate the sandbox's id, the cookies that guard its stack, the
+    /// seed its guest's entrypoint is given and the key its shared buffers
+    /// are checked with from `seed` rather than getting them from the OS,
+    /// so that a test that depends on them, or on where they end up in
+    /// guest memory, runs the same way every time.
+    ///
+    /// Sandboxes created with the same seed get the same id, so give
+    /// sandboxes that are alive at the same time different seeds. This
+    /// makes the values guessable, so it's only available in debug builds
+    /// with the `deterministic` feature.
+    #[cfg(deterministic)]
+    #[instrument(skip_all, parent = Span::current(), level= "Trace")]
+    pub fn set_deterministic_seed(&mut self, seed: u64) {
+        self.use_deterministic_seed = true;
+        self.deterministic_seed = seed;
+    }
+
+    /// Set how the sandbox trades CPU time for latency. With
+    /// `LatencyProfile::LowLatency`, the thread calling a guest function,
+    /// the thread running the vCPU while it waits for the next call and,
+    /// with the `seccomp` feature, while it waits for a host function to
+    /// return, spin for up to the busy poll window before they block.
+    #[instrument(skip_all, parent = Span::current(), level= "Trace")]
+    pub fn set_latency_profile(&mut self, latency_profile: LatencyProfile) {
+        self.latency_profile = latency_profile;
+    }
+
+    /// Set how long threads spin for before they block when the latency
+    /// profile is `LatencyProfile::LowLatency`. The window is rounded down
+    /// to a microsecond, and the default is DEFAULT_BUSY_POLL_WINDOW_US.
+    #[instrument(skip_all, parent = Span::current(), level= "Trace")]
+    pub fn set_busy_poll_window(&mut self, window: Duration) {
+        self.busy_poll_window_us = min(window.as_micros(), u32::MAX.into()) as u32;
+    }
+
+    /// Allocate every page of a sandbox's memory when the sandbox is
+    /// created, rather than when the guest or host first touches it, so
+    /// that the first guest calls don't take page faults. This makes
+    /// creating a sandbox slower, and means a sandbox holds on to all of
+    /// its memory from the start, even the parts its guest never uses.
+    #[instrument(skip_all, parent = Span::current(), level= "Trace")]
+    pub fn set_prefault_memory(&mut self, prefault: bool) {
+        self.prefault_memory = prefault;
+    }
+
     /// Set the size of the memory buffer that is made available for serialising host function definitions
     /// the minimum value is MIN_HOST_FUNCTION_DEFINITION_SIZE
     #[instrument(skip_all, parent = Span::current(), level= "Trace")]
@@ -346,6 +658,84 @@ impl SandboxConfiguration {
         );
     }
 
+    /// Create a configuration from a TOML document. Settings that are left
+    /// out keep their default values, and settings that aren't recognised
+    /// are an error. See `ConfigFile` for the settings, which are named
+    /// after the setters without the `set_` prefix.
+    ///
+    /// ```toml
+    /// input_data_size = 65536
+    /// heap_size = 1048576
+    /// max_execution_time_ms = 500
+    /// require_signed_guests = true
+    /// trusted_guest_keys = ["d75a980182b10ab7d54bfed3c964073a0ee172f3daa62325af021a68f707511a"]
+    /// ```
+    #[instrument(err(Debug), skip_all, parent = Span::current(), level= "Trace")]
+    #[cfg(feature = "toml_config")]
+    pub fn from_toml(toml: &str) -> Result<Self> {
+        let mut config = Self::default();
+        toml::from_str::<ConfigFile>(toml)?.apply(&mut config)?;
+        Ok(config)
+    }
+
+    /// Create a configuration from a JSON object, with the same settings
+    /// as `from_toml`
+    #[instrument(err(Debug), skip_all, parent = Span::current(), level= "Trace")]
+    pub fn from_json(json: &str) -> Result<Self> {
+        let mut config = Self::default();
+        serde_json::from_str::<ConfigFile>(json)?.apply(&mut config)?;
+        Ok(config)
+    }
+
+    /// Override settings with the environment variables named after them
+    /// in upper case with a `HYPERLIGHT_` prefix, such as
+    /// `HYPERLIGHT_HEAP_SIZE=1048576` or
+    /// `HYPERLIGHT_SHARED_BUFFER_INTEGRITY=true`. Trusted guest keys in
+    /// `HYPERLIGHT_TRUSTED_GUEST_KEYS` are separated by commas and are
+    /// added to the ones already trusted, CPUs in
+    /// `HYPERLIGHT_CPU_AFFINITY` are separated by commas and replace the
+    /// ones already set.
+    ///
+    /// This is meant to be called after `from_toml` or `from_json`, so that
+    /// a deployment can change a setting without changing the file.
+    #[instrument(err(Debug), skip_all, parent = Span::current(), level= "Trace")]
+    pub fn apply_env_overrides(&mut self) -> Result<()> {
+        self.apply_overrides(std::env::vars())
+    }
+
+    fn apply_overrides(&mut self, vars: impl Iterator<Item = (String, String)>) -> Result<()> {
+        let mut settings = serde_json::Map::new();
+        for (name, value) in vars {
+            let Some(setting) = name.strip_prefix(ENV_PREFIX) else {
+                continue;
+            };
+            let setting = setting.to_lowercase();
+            if !ConfigFile::SETTINGS.contains(&setting.as_str()) {
+                continue;
+            }
+            let value = if setting == "trusted_guest_keys" {
+                value
+                    .split(',')
+                    .map(|key| serde_json::Value::String(key.trim().to_string()))
+                    .collect()
+            } else if setting == "cpu_affinity" {
+                value
+                    .split(',')
+                    .map(|cpu| {
+                        serde_json::from_str(cpu.trim())
+                            .unwrap_or(serde_json::Value::String(cpu.to_string()))
+                    })
+                    .collect()
+            } else {
+                // numbers and booleans parse as JSON, anything else is left
+                // for the setting's type to reject
+                serde_json::from_str(&value).unwrap_or(serde_json::Value::String(value))
+            };
+            settings.insert(setting, value);
+        }
+        serde_json::from_value::<ConfigFile>(settings.into())?.apply(self)
+    }
+
     #[instrument(skip_all, parent = Span::current(), level= "Trace")]
     pub(crate) fn get_guest_error_buffer_size(&self) -> usize {
         self.guest_error_buffer_size
@@ -371,6 +761,88 @@ impl SandboxConfiguration {
         self.output_data_size
     }
 
+    /// The size of memory reserved for the input buffer, which it can grow
+    /// to fill
+    #[instrument(skip_all, parent = Span::current(), level= "Trace")]
+    pub(crate) fn get_max_input_data_size(&self) -> usize {
+        max(self.input_data_size, self.max_input_data_size)
+    }
+
+    /// The size of memory reserved for the output buffer, which it can grow
+    /// to fill
+    #[instrument(skip_all, parent = Span::current(), level= "Trace")]
+    pub(crate) fn get_max_output_data_size(&self) -> usize {
+        max(self.output_data_size, self.max_output_data_size)
+    }
+
+    #[instrument(skip_all, parent = Span::current(), level= "Trace")]
+    pub(crate) fn get_max_segmented_data_size(&self) -> usize {
+        self.max_segmented_data_size
+    }
+
+    #[instrument(skip_all, parent = Span::current(), level= "Trace")]
+    pub(crate) fn get_compression_threshold(&self) -> usize {
+        self.compression_threshold
+    }
+
+    #[instrument(skip_all, parent = Span::current(), level= "Trace")]
+    pub(crate) fn get_shared_buffer_integrity(&self) -> bool {
+        self.shared_buffer_integrity
+    }
+
+    #[instrument(skip_all, parent = Span::current(), level= "Trace")]
+    #[cfg_attr(not(feature = "signed_guests"), allow(dead_code))]
+    pub(crate) fn get_require_signed_guests(&self) -> bool {
+        self.require_signed_guests
+    }
+
+    #[instrument(skip_all, parent = Span::current(), level= "Trace")]
+    #[cfg_attr(not(feature = "signed_guests"), allow(dead_code))]
+    pub(crate) fn get_trusted_guest_keys(&self) -> &[[u8; GUEST_PUBLIC_KEY_LEN]] {
+        &self.trusted_guest_keys[..self.trusted_guest_key_count]
+    }
+
+    #[instrument(skip_all, parent = Span::current(), level= "Trace")]
+    pub(crate) fn get_numa_node(&self) -> Option<u32> {
+        u32::try_from(self.numa_node).ok()
+    }
+
+    /// The CPUs the vCPU thread may run on, empty if it may run on any
+    #[instrument(skip_all, parent = Span::current(), level= "Trace")]
+    pub(crate) fn get_cpu_affinity(&self) -> Vec<usize> {
+        (0..Self::MAX_CPUS)
+            .filter(|cpu| self.cpu_affinity[cpu / 64] & (1 << (cpu % 64)) != 0)
+            .collect()
+    }
+
+    #[instrument(skip_all, parent = Span::current(), level= "Trace")]
+    pub(crate) fn get_redaction_policy(&self) -> RedactionPolicy {
+        RedactionPolicy {
+            exclude_io_buffers: self.exclude_io_buffers_from_crashdumps,
+            log_parameter_values: self.log_audited_parameter_values,
+            hash_parameters: self.hash_audited_parameters,
+        }
+    }
+
+    #[instrument(skip_all, parent = Span::current(), level= "Trace")]
+    pub(crate) fn get_deterministic_seed(&self) -> Option<u64> {
+        self.use_deterministic_seed
+            .then_some(self.deterministic_seed)
+    }
+
+    /// How long threads spin for before they block, or `None` if they
+    /// block straight away
+    #[instrument(skip_all, parent = Span::current(), level= "Trace")]
+    pub(crate) fn get_busy_poll_window(&self) -> Option<Duration> {
+        (self.latency_profile == LatencyProfile::LowLatency)
+            .then(|| Duration::from_micros(self.busy_poll_window_us.into()))
+    }
+
+    #[instrument(skip_all, parent = Span::current(), level= "Trace")]
+    pub(crate) fn get_prefault_memory(&self) -> bool {
+        self.prefault_memory
+    }
+
     #[instrument(skip_all, parent = Span::current(), level="Trace")]
     pub(crate) fn get_guest_panic_context_buffer_size(&self) -> usize {
         self.guest_panic_context_buffer_size
@@ -442,6 +914,187 @@ impl Default for SandboxConfiguration {
     }
 }
 
+/// The prefix of the environment variables read by
+/// `SandboxConfiguration::apply_env_overrides`
+const ENV_PREFIX: &str = "HYPERLIGHT_";
+
+/// The settings that can be read from a configuration file or the
+/// environment. Sizes are in bytes and times in milliseconds, and each is
+/// applied with the setter of the same name, so out of range values are
+/// clamped the same way.
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ConfigFile {
+    input_data_size: Option<usize>,
+    output_data_size: Option<usize>,
+    max_input_data_size: Option<usize>,
+    max_output_data_size: Option<usize>,
+    max_segmented_data_size: Option<usize>,
+    compression_threshold: Option<usize>,
+    host_function_definition_size: Option<usize>,
+    host_exception_size: Option<usize>,
+    guest_error_buffer_size: Option<usize>,
+    guest_panic_context_buffer_size: Option<usize>,
+    stack_size: Option<u64>,
+    heap_size: Option<u64>,
+    kernel_stack_size: Option<usize>,
+    max_execution_time_ms: Option<u64>,
+    max_execution_cancel_wait_time_ms: Option<u64>,
+    max_initialization_time_ms: Option<u64>,
+    shared_buffer_integrity: Option<bool>,
+    require_signed_guests: Option<bool>,
+    /// Ed25519 public keys as hex strings
+    trusted_guest_keys: Option<Vec<String>>,
+    numa_node: Option<u32>,
+    cpu_affinity: Option<Vec<usize>>,
+    exclude_io_buffers_from_crashdumps: Option<bool>,
+    log_audited_parameter_values: Option<bool>,
+    hash_audited_parameters: Option<bool>,
+    latency_profile: Option<LatencyProfile>,
+    busy_poll_window_us: Option<u64>,
+    prefault_memory: Option<bool>,
+}
+
+impl ConfigFile {
+    /// The names of the settings, which are also the environment variables
+    /// without their prefix
+    const SETTINGS: &'static [&'static str] = &[
+        "input_data_size",
+        "output_data_size",
+        "max_input_data_size",
+        "max_output_data_size",
+        "max_segmented_data_size",
+        "compression_threshold",
+        "host_function_definition_size",
+        "host_exception_size",
+        "guest_error_buffer_size",
+        "guest_panic_context_buffer_size",
+        "stack_size",
+        "heap_size",
+        "kernel_stack_size",
+        "max_execution_time_ms",
+        "max_execution_cancel_wait_time_ms",
+        "max_initialization_time_ms",
+        "shared_buffer_integrity",
+        "require_signed_guests",
+        "trusted_guest_keys",
+        "numa_node",
+        "cpu_affinity",
+        "exclude_io_buffers_from_crashdumps",
+        "log_audited_parameter_values",
+        "hash_audited_parameters",
+        "latency_profile",
+        "busy_poll_window_us",
+        "prefault_memory",
+    ];
+
+    fn apply(self, config: &mut SandboxConfiguration) -> Result<()> {
+        if let Some(size) = self.input_data_size {
+            config.set_input_data_size(size);
+        }
+        if let Some(size) = self.output_data_size {
+            config.set_output_data_size(size);
+        }
+        if let Some(size) = self.max_input_data_size {
+            config.set_max_input_data_size(size);
+        }
+        if let Some(size) = self.max_output_data_size {
+            config.set_max_output_data_size(size);
+        }
+        if let Some(size) = self.max_segmented_data_size {
+            config.set_max_segmented_data_size(size);
+        }
+        if let Some(threshold) = self.compression_threshold {
+            config.set_compression_threshold(threshold);
+        }
+        if let Some(size) = self.host_function_definition_size {
+            config.set_host_function_definition_size(size);
+        }
+        if let Some(size) = self.host_exception_size {
+            config.set_host_exception_size(size);
+        }
+        if let Some(size) = self.guest_error_buffer_size {
+            config.set_guest_error_buffer_size(size);
+        }
+        if let Some(size) = self.guest_panic_context_buffer_size {
+            config.set_guest_panic_context_buffer_size(size);
+        }
+        if let Some(size) = self.stack_size {
+            config.set_stack_size(size);
+        }
+        if let Some(size) = self.heap_size {
+            config.set_heap_size(size);
+        }
+        if let Some(size) = self.kernel_stack_size {
+            config.set_kernel_stack_size(size);
+        }
+        if let Some(ms) = self.max_execution_time_ms {
+            config.set_max_execution_time(Duration::from_millis(ms));
+        }
+        if let Some(ms) = self.max_execution_cancel_wait_time_ms {
+            config.set_max_execution_cancel_wait_time(Duration::from_millis(ms));
+        }
+        if let Some(ms) = self.max_initialization_time_ms {
+            config.set_max_initialization_time(Duration::from_millis(ms));
+        }
+        if let Some(enabled) = self.shared_buffer_integrity {
+            config.set_shared_buffer_integrity(enabled);
+        }
+        if let Some(required) = self.require_signed_guests {
+            config.set_require_signed_guests(required)?;
+        }
+        for key in self.trusted_guest_keys.unwrap_or_default() {
+            config.add_trusted_guest_key(parse_public_key(&key)?)?;
+        }
+        if let Some(node) = self.numa_node {
+            config.set_numa_node(node);
+        }
+        if let Some(cpus) = self.cpu_affinity {
+            config.set_cpu_affinity(&cpus)?;
+        }
+        if let Some(exclude) = self.exclude_io_buffers_from_crashdumps {
+            config.set_exclude_io_buffers_from_crashdumps(exclude);
+        }
+        if let Some(log) = self.log_audited_parameter_values {
+            config.set_log_audited_parameter_values(log);
+        }
+        if let Some(hash) = self.hash_audited_parameters {
+            config.set_hash_audited_parameters(hash);
+        }
+        if let Some(latency_profile) = self.latency_profile {
+            config.set_latency_profile(latency_profile);
+        }
+        if let Some(us) = self.busy_poll_window_us {
+            config.set_busy_poll_window(Duration::from_micros(us));
+        }
+        if let Some(prefault) = self.prefault_memory {
+            config.set_prefault_memory(prefault);
+        }
+        Ok(())
+    }
+}
+
+/// Parse a guest public key written as hex
+fn parse_public_key(hex: &str) -> Result<[u8; GUEST_PUBLIC_KEY_LEN]> {
+    let digits: Vec<u8> = hex
+        .chars()
+        .filter_map(|c| c.to_digit(16))
+        .map(|digit| digit as u8)
+        .collect();
+    if digits.len() != hex.len() || digits.len() != GUEST_PUBLIC_KEY_LEN * 2 {
+        log_then_return!(
+            "Guest public key {:?} is not {} hex digits",
+            hex,
+            GUEST_PUBLIC_KEY_LEN * 2
+        );
+    }
+    let mut key = [0; GUEST_PUBLIC_KEY_LEN];
+    for (byte, pair) in key.iter_mut().zip(digits.chunks_exact(2)) {
+        *byte = pair[0] << 4 | pair[1];
+    }
+    Ok(key)
+}
+
 #[cfg(test)]
 mod tests {
     use std::time::Duration;
@@ -629,7 +1282,123 @@ mod tests {
         );
     }
 
+    const KEY: &str = "d75a980182b10ab7d54bfed3c964073a0ee172f3daa62325af021a68f707511a";
+
+    #[test]
+    #[cfg(feature = "toml_config")]
+    fn from_toml() {
+        let cfg = SandboxConfiguration::from_toml(&format!(
+            r#"
+            input_data_size = 0x10000
+            heap_size = 1048576
+            max_execution_time_ms = 500
+            trusted_guest_keys = ["{KEY}"]
+            numa_node = 1
+            cpu_affinity = [0, 65]
+            hash_audited_parameters = true
+            latency_profile = "low_latency"
+            busy_poll_window_us = 20
+            prefault_memory = true
+            "#
+        ))
+        .unwrap();
+        assert_eq!(0x10000, cfg.input_data_size);
+        assert_eq!(1048576, cfg.heap_size_override);
+        assert_eq!(500, cfg.max_execution_time);
+        assert_eq!(1, cfg.trusted_guest_key_count);
+        assert_eq!(0xd7, cfg.trusted_guest_keys[0][0]);
+        assert_eq!(0x1a, cfg.trusted_guest_keys[0][31]);
+        assert_eq!(Some(1), cfg.get_numa_node());
+        assert_eq!(vec![0, 65], cfg.get_cpu_affinity());
+        assert!(cfg.get_redaction_policy().hash_parameters);
+        assert!(!cfg.get_redaction_policy().exclude_io_buffers);
+        assert_eq!(Some(Duration::from_micros(20)), cfg.get_busy_poll_window());
+        assert!(cfg.get_prefault_memory());
+        assert_eq!(
+            SandboxConfiguration::DEFAULT_OUTPUT_SIZE,
+            cfg.output_data_size
+        );
+
+        // values are clamped the same way as by the setters
+        let cfg = SandboxConfiguration::from_toml("input_data_size = 1").unwrap();
+        assert_eq!(SandboxConfiguration::MIN_INPUT_SIZE, cfg.input_data_size);
+
+        assert!(SandboxConfiguration::from_toml("input_size = 0x10000").is_err());
+        assert!(SandboxConfiguration::from_toml("heap_size = \"big\"").is_err());
+        assert!(SandboxConfiguration::from_toml("trusted_guest_keys = [\"d75a\"]").is_err());
+        assert!(SandboxConfiguration::from_toml("cpu_affinity = [1024]").is_err());
+        assert!(SandboxConfiguration::from_toml("latency_profile = \"fast\"").is_err());
+    }
+
+    #[test]
+    fn from_json() {
+        let cfg = SandboxConfiguration::from_json(
+            r#"{ "output_data_size": 65536, "shared_buffer_integrity": true }"#,
+        )
+        .unwrap();
+        assert_eq!(65536, cfg.output_data_size);
+        assert!(cfg.shared_buffer_integrity);
+        assert_eq!(
+            SandboxConfiguration::default(),
+            SandboxConfiguration::from_json("{}").unwrap()
+        );
+        assert!(SandboxConfiguration::from_json(r#"{ "stack": 1 }"#).is_err());
+        assert_eq!(
+            cfg!(feature = "signed_guests"),
+            SandboxConfiguration::from_json(r#"{ "require_signed_guests": true }"#).is_ok()
+        );
+        assert_eq!(None, SandboxConfiguration::default().get_busy_poll_window());
+    }
+
+    #[test]
+    fn env_overrides() {
+        let vars = [
+            ("HYPERLIGHT_STACK_SIZE", "0x10000"),
+            ("HYPERLIGHT_HEAP_SIZE", "65536"),
+            ("HYPERLIGHT_MAX_INITIALIZATION_TIME_MS", "3000"),
+            ("HYPERLIGHT_REQUIRE_SIGNED_GUESTS", "false"),
+            ("HYPERLIGHT_TRUSTED_GUEST_KEYS", &format!("{KEY}, {KEY}")),
+            ("HYPERLIGHT_CPU_AFFINITY", "2, 3"),
+            ("HYPERLIGHT_LATENCY_PROFILE", "low_latency"),
+            ("HYPERLIGHT_UNRELATED", "1"),
+            ("HEAP_SIZE", "1"),
+        ];
+        let vars = || {
+            vars.iter()
+                .map(|(name, value)| (name.to_string(), value.to_string()))
+        };
+
+        let mut cfg = SandboxConfiguration::from_json(r#"{ "heap_size": 1024 }"#).unwrap();
+        // hex isn't JSON, so the stack size is rejected as a string
+        assert!(cfg.apply_overrides(vars()).is_err());
+        let mut cfg = SandboxConfiguration::from_json(r#"{ "heap_size": 1024 }"#).unwrap();
+        cfg.apply_overrides(vars().skip(1)).unwrap();
+        assert_eq!(65536, cfg.heap_size_override);
+        assert_eq!(3000, cfg.max_initialization_time);
+        assert!(!cfg.require_signed_guests);
+        assert_eq!(2, cfg.trusted_guest_key_count);
+        assert_eq!(vec![2, 3], cfg.get_cpu_affinity());
+        assert_eq!(None, cfg.get_numa_node());
+        assert_eq!(
+            Some(Duration::from_micros(
+                SandboxConfiguration::DEFAULT_BUSY_POLL_WINDOW_US.into()
+            )),
+            cfg.get_busy_poll_window()
+        );
+    }
+
+    #[test]
+    fn settings_are_named_after_fields() {
+        let settings: serde_json::Map<String, serde_json::Value> = super::ConfigFile::SETTINGS
+            .iter()
+            .map(|setting| (setting.to_string(), serde_json::Value::Null))
+            .collect();
+        serde_json::from_value::<super::ConfigFile>(settings.into()).unwrap();
+    }
+
     mod proptests {
+        use std::cmp::max;
+
         use proptest::prelude::*;
 
         use super::SandboxConfiguration;
@@ -711,6 +1480,20 @@ mod tests {
                 cfg.set_heap_size(size);
                 prop_assert_eq!(size, cfg.heap_size_override);
             }
+
+            #[test]
+            fn max_input_data_size(size in 0..=SandboxConfiguration::DEFAULT_INPUT_SIZE * 4) {
+                let mut cfg = SandboxConfiguration::default();
+                cfg.set_max_input_data_size(size);
+                prop_assert_eq!(max(size, cfg.get_input_data_size()), cfg.get_max_input_data_size());
+            }
+
+            #[test]
+            fn max_output_data_size(size in 0..=SandboxConfiguration::DEFAULT_OUTPUT_SIZE * 4) {
+                let mut cfg = SandboxConfiguration::default();
+                cfg.set_max_output_data_size(size);
+                prop_assert_eq!(max(size, cfg.get_output_data_size()), cfg.get_max_output_data_size());
+            }
         }
     }
 }
@@ -0,0 +1,89 @@
+/*
+Copyright 2024 The Hyperlight Authors.
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+    http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+/// The coverage counters of a guest built with SanitizerCoverage inline
+/// 8-bit counters, as returned by `MultiUseSandbox::take_coverage`.
+///
+/// There is one counter per edge of the guest's control flow graph, in
+/// the order the compiler emitted them, so maps taken from sandboxes
+/// running the same guest binary can be compared and merged.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CoverageMap {
+    counters: Vec<u8>,
+}
+
+impl CoverageMap {
+    pub(crate) fn new(counters: Vec<u8>) -> Self {
+        Self { counters }
+    }
+
+    /// The counters, each of which is the highest number of times (up to
+    /// 255) its edge was hit in a single guest call.
+    pub fn counters(&self) -> &[u8] {
+        &self.counters
+    }
+
+    /// The number of edges hit at least once
+    pub fn covered(&self) -> usize {
+        self.counters.iter().filter(|&&count| count != 0).count()
+    }
+
+    /// The fraction of edges hit at least once, between 0 and 1
+    pub fn ratio(&self) -> f64 {
+        match self.counters.len() {
+            0 => 0.0,
+            len => self.covered() as f64 / len as f64,
+        }
+    }
+
+    /// Whether this map hits any edge that `other` doesn't, which is how
+    /// a coverage-guided fuzzer decides an input is interesting.
+    pub fn has_new_coverage(&self, other: &CoverageMap) -> bool {
+        self.counters
+            .iter()
+            .enumerate()
+            .any(|(i, &count)| count != 0 && other.counters.get(i).copied().unwrap_or(0) == 0)
+    }
+
+    /// Merge `other` into this map, keeping the highest value of each
+    /// counter.
+    pub fn merge(&mut self, other: &CoverageMap) {
+        if self.counters.len() < other.counters.len() {
+            self.counters.resize(other.counters.len(), 0);
+        }
+        for (count, &other_count) in self.counters.iter_mut().zip(&other.counters) {
+            *count = (*count).max(other_count);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::CoverageMap;
+
+    #[test]
+    fn merge_and_compare() {
+        let mut seen = CoverageMap::new(vec![1, 0, 0, 3]);
+        let run = CoverageMap::new(vec![2, 0, 1, 0]);
+
+        assert!(run.has_new_coverage(&seen));
+        seen.merge(&run);
+        assert_eq!(seen.counters(), &[2, 0, 1, 3]);
+        assert_eq!(seen.covered(), 3);
+        assert_eq!(seen.ratio(), 0.75);
+        assert!(!run.has_new_coverage(&seen));
+    }
+}
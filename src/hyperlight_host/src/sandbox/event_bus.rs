@@ -0,0 +1,342 @@
+/*
+Copyright 2024 The Hyperlight Authors.
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+    http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use tracing::{instrument, Span};
+
+use crate::{log_then_return, new_error, Result};
+
+/// A bus that carries events between the sandboxes it is given to through
+/// the `hyperlight::bus::*` built-in services, so that plugins running in
+/// different sandboxes can work together without knowing about each
+/// other.
+///
+/// Guests publish an event, a payload of bytes, to a topic, and every
+/// other sandbox subscribed to the topic gets a copy, which its guest
+/// receives the next time it asks for one. What each sandbox may publish
+/// and subscribe to is set by the `BusAccess` it joins the bus with.
+///
+/// Cloning a bus gives another handle to the same bus.
+#[derive(Debug, Clone, Default)]
+pub struct EventBus {
+    state: Arc<Mutex<BusState>>,
+}
+
+#[derive(Debug, Default)]
+struct BusState {
+    members: HashMap<u64, Member>,
+    next_member: u64,
+}
+
+#[derive(Debug)]
+struct Member {
+    /// The events published to each topic the member subscribes to, which
+    /// its guest hasn't received yet
+    queues: HashMap<String, VecDeque<Vec<u8>>>,
+    max_queued: usize,
+}
+
+impl EventBus {
+    /// Create a bus no sandbox has joined
+    #[instrument(skip_all, parent = Span::current(), level = "Trace")]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Publish `payload` to `topic` from the host, which isn't limited by
+    /// any `BusAccess`
+    #[instrument(err(Debug), skip(self, payload), parent = Span::current(), level = "Trace")]
+    pub fn publish(&self, topic: &str, payload: Vec<u8>) -> Result<()> {
+        if payload.is_empty() {
+            log_then_return!("Events published to {} can't be empty", topic);
+        }
+        self.state()?.deliver(None, topic, payload);
+        Ok(())
+    }
+
+    /// Add a sandbox to the bus, which leaves it when the returned member
+    /// is dropped
+    pub(crate) fn join(&self, access: BusAccess) -> Result<BusMember> {
+        let mut state = self.state()?;
+        let id = state.next_member;
+        state.next_member += 1;
+        state.members.insert(
+            id,
+            Member {
+                queues: HashMap::new(),
+                max_queued: access.max_queued,
+            },
+        );
+        Ok(BusMember {
+            bus: self.clone(),
+            id,
+            access,
+        })
+    }
+
+    fn state(&self) -> Result<MutexGuard<'_, BusState>> {
+        self.state
+            .lock()
+            .map_err(|e| new_error!("Error locking at {}:{}: {}", file!(), line!(), e))
+    }
+}
+
+impl BusState {
+    /// Queue `payload` for every member subscribed to `topic`, other than
+    /// `from`, dropping the oldest event queued for the topic if a member
+    /// has as many as it can hold
+    fn deliver(&mut self, from: Option<u64>, topic: &str, payload: Vec<u8>) {
+        for (id, member) in &mut self.members {
+            if Some(*id) == from || member.max_queued == 0 {
+                continue;
+            }
+            if let Some(queue) = member.queues.get_mut(topic) {
+                while queue.len() >= member.max_queued {
+                    queue.pop_front();
+                }
+                queue.push_back(payload.clone());
+            }
+        }
+    }
+}
+
+/// The topics a sandbox can publish and subscribe to on an `EventBus`.
+/// Nothing is allowed until it is added with `allow_publish` or
+/// `allow_subscribe`.
+///
+/// Topics are allowed by name, or by a prefix followed by `*`, so that
+/// `orders.*` allows `orders.created` and `orders.cancelled`.
+#[derive(Debug, Clone)]
+pub struct BusAccess {
+    publish: Vec<String>,
+    subscribe: Vec<String>,
+    max_queued: usize,
+    max_topics: usize,
+    max_payload_size: usize,
+}
+
+impl Default for BusAccess {
+    fn default() -> Self {
+        Self {
+            publish: Vec::new(),
+            subscribe: Vec::new(),
+            max_queued: 64,
+            max_topics: 16,
+            max_payload_size: 64 * 1024,
+        }
+    }
+}
+
+impl BusAccess {
+    /// Create access that allows nothing
+    #[instrument(skip_all, parent = Span::current(), level = "Trace")]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Allow the guest to publish to the topics `topic` matches
+    #[instrument(skip(self), parent = Span::current(), level = "Trace")]
+    pub fn allow_publish(mut self, topic: &str) -> Self {
+        self.publish.push(topic.to_string());
+        self
+    }
+
+    /// Allow the guest to subscribe to the topics `topic` matches
+    #[instrument(skip(self), parent = Span::current(), level = "Trace")]
+    pub fn allow_subscribe(mut self, topic: &str) -> Self {
+        self.subscribe.push(topic.to_string());
+        self
+    }
+
+    /// Limit the events queued for the guest on each topic it subscribes
+    /// to, past which the oldest are dropped. The default is 64. With a
+    /// limit of 0 nothing is queued, so the guest receives no events.
+    #[instrument(skip(self), parent = Span::current(), level = "Trace")]
+    pub fn with_max_queued(mut self, max: usize) -> Self {
+        self.max_queued = max;
+        self
+    }
+
+    /// Limit the number of topics the guest can subscribe to, each of
+    /// which has a queue of its own. The default is 16.
+    #[instrument(skip(self), parent = Span::current(), level = "Trace")]
+    pub fn with_max_topics(mut self, max: usize) -> Self {
+        self.max_topics = max;
+        self
+    }
+
+    /// Limit the size of the events the guest can publish, each of which
+    /// is copied for every sandbox subscribed to its topic. The default
+    /// is 64 KiB.
+    #[instrument(skip(self), parent = Span::current(), level = "Trace")]
+    pub fn with_max_payload_size(mut self, max: usize) -> Self {
+        self.max_payload_size = max;
+        self
+    }
+}
+
+/// Whether one of `patterns` matches `topic`
+fn allowed(patterns: &[String], topic: &str) -> bool {
+    patterns
+        .iter()
+        .any(|pattern| match pattern.strip_suffix('*') {
+            Some(prefix) => topic.starts_with(prefix),
+            None => pattern == topic,
+        })
+}
+
+/// A sandbox's place on an `EventBus`, which the `hyperlight::bus::*`
+/// services of the sandbox share
+pub(crate) struct BusMember {
+    bus: EventBus,
+    id: u64,
+    access: BusAccess,
+}
+
+impl BusMember {
+    /// Publish `payload` to `topic` for the other sandboxes subscribed to
+    /// it
+    pub(crate) fn publish(&self, topic: &str, payload: Vec<u8>) -> Result<()> {
+        if !allowed(&self.access.publish, topic) {
+            log_then_return!("The guest isn't allowed to publish to {}", topic);
+        }
+        if payload.is_empty() {
+            log_then_return!("Events published to {} can't be empty", topic);
+        }
+        if payload.len() > self.access.max_payload_size {
+            log_then_return!(
+                "The guest can only publish events of at most {} bytes",
+                self.access.max_payload_size
+            );
+        }
+        self.bus.state()?.deliver(Some(self.id), topic, payload);
+        Ok(())
+    }
+
+    /// Start queueing the events published to `topic` for the guest
+    pub(crate) fn subscribe(&self, topic: &str) -> Result<()> {
+        if !allowed(&self.access.subscribe, topic) {
+            log_then_return!("The guest isn't allowed to subscribe to {}", topic);
+        }
+        if let Some(member) = self.bus.state()?.members.get_mut(&self.id) {
+            if !member.queues.contains_key(topic) && member.queues.len() >= self.access.max_topics {
+                log_then_return!(
+                    "The guest can subscribe to at most {} topics",
+                    self.access.max_topics
+                );
+            }
+            member.queues.entry(topic.to_string()).or_default();
+        }
+        Ok(())
+    }
+
+    /// The oldest event queued for the guest on `topic`, which is empty if
+    /// there is none
+    pub(crate) fn receive(&self, topic: &str) -> Result<Vec<u8>> {
+        let mut state = self.bus.state()?;
+        let Some(queue) = state
+            .members
+            .get_mut(&self.id)
+            .and_then(|member| member.queues.get_mut(topic))
+        else {
+            log_then_return!("The guest isn't subscribed to {}", topic);
+        };
+        Ok(queue.pop_front().unwrap_or_default())
+    }
+}
+
+impl Drop for BusMember {
+    fn drop(&mut self) {
+        if let Ok(mut state) = self.bus.state() {
+            state.members.remove(&self.id);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn publish_and_receive() {
+        let bus = EventBus::new();
+        let publisher = bus
+            .join(BusAccess::new().allow_publish("orders.*"))
+            .unwrap();
+        let subscriber = bus
+            .join(
+                BusAccess::new()
+                    .allow_subscribe("orders.created")
+                    .with_max_queued(2),
+            )
+            .unwrap();
+
+        assert!(subscriber.subscribe("orders.cancelled").is_err());
+        assert!(subscriber.receive("orders.created").is_err());
+        subscriber.subscribe("orders.created").unwrap();
+        assert_eq!(subscriber.receive("orders.created").unwrap(), b"");
+
+        assert!(publisher.publish("payments.made", b"1".to_vec()).is_err());
+        assert!(publisher.publish("orders.created", vec![]).is_err());
+        assert!(subscriber.publish("orders.created", b"1".to_vec()).is_err());
+        for payload in [b"1", b"2", b"3"] {
+            publisher
+                .publish("orders.created", payload.to_vec())
+                .unwrap();
+        }
+        publisher
+            .publish("orders.cancelled", b"4".to_vec())
+            .unwrap();
+        bus.publish("orders.created", b"5".to_vec()).unwrap();
+
+        // the oldest events were dropped
+        assert_eq!(subscriber.receive("orders.created").unwrap(), b"3");
+        assert_eq!(subscriber.receive("orders.created").unwrap(), b"5");
+        assert_eq!(subscriber.receive("orders.created").unwrap(), b"");
+
+        drop(subscriber);
+        assert!(bus.state().unwrap().members.len() == 1);
+    }
+
+    #[test]
+    fn limits() {
+        let bus = EventBus::new();
+        let publisher = bus
+            .join(BusAccess::new().allow_publish("*").with_max_payload_size(4))
+            .unwrap();
+        let subscriber = bus
+            .join(
+                BusAccess::new()
+                    .allow_subscribe("*")
+                    .with_max_topics(2)
+                    .with_max_queued(0),
+            )
+            .unwrap();
+
+        subscriber.subscribe("a").unwrap();
+        subscriber.subscribe("b").unwrap();
+        // subscribing again takes no more room
+        subscriber.subscribe("a").unwrap();
+        assert!(subscriber.subscribe("c").is_err());
+
+        assert!(publisher.publish("a", b"12345".to_vec()).is_err());
+        publisher.publish("a", b"1234".to_vec()).unwrap();
+        // nothing is queued for a subscriber that can't hold any events
+        assert_eq!(subscriber.receive("a").unwrap(), b"");
+    }
+}
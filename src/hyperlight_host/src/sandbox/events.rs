@@ -0,0 +1,156 @@
+/*
+Copyright 2024 The Hyperlight Authors.
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+    http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+
+use hyperlight_common::heap_profile::HeapProfile;
+
+use crate::sandbox::isolation_audit::IsolationViolation;
+#[cfg(feature = "otel")]
+use crate::sandbox::otel::OtelSubscriber;
+use crate::sandbox::profile::CallProfiler;
+use crate::sandbox::SandboxId;
+use crate::HyperlightError;
+
+/// Callbacks for things that happen during a sandbox's life, for hosts
+/// that want to feed them into their own telemetry.
+///
+/// Subscribers are added with `add_event_subscriber` on
+/// `UninitializedSandbox`, `MultiUseSandbox` or `SingleUseSandbox`, and are
+/// kept when the sandbox is evolved. Every method does nothing by default,
+/// so subscribers only need to implement the ones they are interested in.
+///
+/// The callbacks are made synchronously, some of them on the thread that
+/// runs the guest, so they should return quickly and must not call back
+/// into the sandbox.
+pub trait EventSubscriber: Send + Sync {
+    /// The sandbox has been initialised and is ready to have guest
+    /// functions called
+    fn on_sandbox_created(&self, _sandbox_id: SandboxId) {}
+
+    /// The host is about to call `function_name` in the guest
+    fn on_guest_call_started(&self, _sandbox_id: SandboxId, _function_name: &str) {}
+
+    /// A call to `function_name` in the guest has returned, successfully or
+    /// not, after `elapsed`
+    fn on_guest_call_finished(
+        &self,
+        _sandbox_id: SandboxId,
+        _function_name: &str,
+        _elapsed: Duration,
+    ) {
+    }
+
+    /// The guest is calling the host function `function_name`
+    fn on_host_function_invoked(&self, _sandbox_id: SandboxId, _function_name: &str) {}
+
+    /// A call from the guest to the host function `function_name` has
+    /// returned, successfully or not, after `elapsed`
+    fn on_host_function_finished(
+        &self,
+        _sandbox_id: SandboxId,
+        _function_name: &str,
+        _elapsed: Duration,
+    ) {
+    }
+
+    /// A call to `function_name` in the guest failed with `error`. This is
+    /// called before `on_guest_call_finished`.
+    fn on_guest_error(
+        &self,
+        _sandbox_id: SandboxId,
+        _function_name: &str,
+        _error: &HyperlightError,
+    ) {
+    }
+
+    /// A call to `function_name` in the guest, made while isolation
+    /// auditing is on, left `violation` behind. This is called before
+    /// `on_guest_call_finished`, once for each violation found.
+    fn on_isolation_violation(
+        &self,
+        _sandbox_id: SandboxId,
+        _function_name: &str,
+        _violation: &IsolationViolation,
+    ) {
+    }
+
+    /// The guest, built with the `heap_profiling` feature of
+    /// `hyperlight-guest`, sent the host its heap profile
+    fn on_heap_profile(&self, _sandbox_id: SandboxId, _profile: &HeapProfile) {}
+
+    /// The sandbox has been dropped
+    fn on_sandbox_destroyed(&self, _sandbox_id: SandboxId) {}
+}
+
+/// The subscribers to a sandbox's events, shared between the sandbox and
+/// the hypervisor handler so that subscribers added after the sandbox is
+/// initialised still see every event from then on.
+#[derive(Clone)]
+pub(crate) struct SandboxEvents {
+    sandbox_id: SandboxId,
+    subscribers: Arc<RwLock<Vec<Arc<dyn EventSubscriber>>>>,
+    /// Always subscribed, and also told about the sandbox's vCPU runs and
+    /// exits
+    profiler: Arc<CallProfiler>,
+    /// Always subscribed, and also counts the sandbox's VM exits
+    #[cfg(feature = "otel")]
+    otel: Arc<OtelSubscriber>,
+}
+
+impl SandboxEvents {
+    pub(crate) fn new(sandbox_id: SandboxId) -> Self {
+        let profiler = Arc::new(CallProfiler::default());
+        let events = Self {
+            sandbox_id,
+            subscribers: Arc::new(RwLock::new(Vec::new())),
+            profiler: profiler.clone(),
+            #[cfg(feature = "otel")]
+            otel: Arc::new(OtelSubscriber::new(sandbox_id)),
+        };
+        events.add(profiler);
+        #[cfg(feature = "otel")]
+        events.add(events.otel.clone());
+        events
+    }
+
+    pub(crate) fn profiler(&self) -> &Arc<CallProfiler> {
+        &self.profiler
+    }
+
+    #[cfg(feature = "otel")]
+    pub(crate) fn otel(&self) -> &Arc<OtelSubscriber> {
+        &self.otel
+    }
+
+    pub(crate) fn add(&self, subscriber: Arc<dyn EventSubscriber>) {
+        self.subscribers
+            .write()
+            .unwrap_or_else(|e| e.into_inner())
+            .push(subscriber);
+    }
+
+    /// Call `event` on every subscriber with the id of the sandbox
+    pub(crate) fn emit(&self, event: impl Fn(&dyn EventSubscriber, SandboxId)) {
+        // a subscriber that panicked doesn't stop the others from being
+        // told about later events
+        let subscribers = self.subscribers.read().unwrap_or_else(|e| e.into_inner());
+        for subscriber in subscribers.iter() {
+            event(subscriber.as_ref(), self.sandbox_id);
+        }
+    }
+}
@@ -0,0 +1,129 @@
+/*
+Copyright 2024 The Hyperlight Authors.
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+    http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+use std::fmt::{Display, Formatter};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use hyperlight_common::guest_function_metadata::GuestFunctionSignature;
+use sha2::{Digest, Sha256};
+use tracing::{instrument, Span};
+
+use crate::mem::exe::ExeInfo;
+use crate::Result;
+
+/// What a guest binary says about itself, read without running it, see
+/// `GuestBinary::inspect`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GuestBinaryInfo {
+    /// The signatures of the functions the guest registered with
+    /// `register_guest_function!`, in the order they are in the binary, or
+    /// `None` if the guest didn't register any that way
+    pub functions: Option<Vec<GuestFunctionSignature>>,
+}
+
+impl GuestBinaryInfo {
+    /// Read what the guest binary `exe_info` says about itself
+    pub(crate) fn new(exe_info: &ExeInfo) -> Result<Self> {
+        Ok(Self {
+            functions: exe_info.guest_functions()?,
+        })
+    }
+}
+
+/// Where the guest binary running in a sandbox came from, so that what
+/// happens in the sandbox can be tied back to the exact binary.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GuestInfo {
+    /// The SHA-256 digest of the guest binary
+    pub digest: [u8; 32],
+    /// The path the guest binary was loaded from, or `None` if it was
+    /// loaded from a buffer
+    pub path: Option<String>,
+    /// When the guest binary was loaded
+    pub loaded_at: SystemTime,
+    /// The signatures of the functions recorded in the guest binary, see
+    /// `GuestBinaryInfo::functions`
+    pub functions: Option<Vec<GuestFunctionSignature>>,
+}
+
+impl GuestInfo {
+    /// Record that `image`, the guest binary read from `path` if it was
+    /// loaded from a file, was loaded now. `functions` are the signatures
+    /// recorded in it, see `ExeInfo::guest_functions`.
+    #[instrument(skip_all, parent = Span::current(), level = "Trace")]
+    pub(crate) fn new(
+        image: &[u8],
+        path: Option<String>,
+        functions: Option<Vec<GuestFunctionSignature>>,
+    ) -> Self {
+        Self {
+            digest: Sha256::digest(image).into(),
+            path,
+            loaded_at: SystemTime::now(),
+            functions,
+        }
+    }
+
+    /// The digest of the guest binary as a lowercase hex string
+    pub fn digest_hex(&self) -> String {
+        self.digest.iter().map(|b| format!("{:02x}", b)).collect()
+    }
+}
+
+impl Display for GuestInfo {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "sha256:{}", self.digest_hex())?;
+        if let Some(path) = &self.path {
+            write!(f, " from {}", path)?;
+        }
+        // the unix timestamp, since formatting dates needs a dependency
+        let loaded_at = self
+            .loaded_at
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default();
+        write!(
+            f,
+            " loaded at {}.{:03}",
+            loaded_at.as_secs(),
+            loaded_at.subsec_millis()
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    #[test]
+    fn digest_and_display() {
+        let mut info = GuestInfo::new(b"abc", None, None);
+        assert_eq!(
+            info.digest_hex(),
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+        assert_eq!(info.path, None);
+        assert_eq!(info.functions, None);
+
+        info.path = Some("/guests/abc".to_string());
+        info.loaded_at = UNIX_EPOCH + Duration::from_millis(1_700_000_000_123);
+        assert_eq!(
+            info.to_string(),
+            "sha256:ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad from /guests/abc loaded at 1700000000.123"
+        );
+    }
+}
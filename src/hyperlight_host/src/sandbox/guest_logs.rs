@@ -0,0 +1,313 @@
+/*
+Copyright 2024 The Hyperlight Authors.
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+    http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+use std::collections::VecDeque;
+use std::sync::{Arc, Condvar, Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+
+use hyperlight_common::flatbuffer_wrappers::guest_log_data::GuestLogData;
+use tracing::{instrument, Span};
+
+use crate::sandbox::metrics::SandboxMetric::GuestLogQueueOverflows;
+use crate::{int_counter_vec_inc, new_error, Result};
+
+/// What a `GuestLogQueue` does with a record the guest logs while it is
+/// full.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LogOverflowPolicy {
+    /// Discard the oldest record in the queue to make room for the new
+    /// one, keeping the records that led up to whatever the guest is doing
+    /// now.
+    DropOldest,
+    /// Discard the new record, keeping the records that led up to the
+    /// queue filling up.
+    DropNewest,
+    /// Suspend the guest until the host takes a record out of the queue,
+    /// so that nothing is lost while the host keeps up. The host must
+    /// consume the queue on another thread than the one calling the guest.
+    /// The guest waits no later than the deadline of the call it is making,
+    /// after which the new record is discarded so that the call can time
+    /// out as usual.
+    BlockGuest,
+}
+
+impl LogOverflowPolicy {
+    fn name(self) -> &'static str {
+        match self {
+            Self::DropOldest => "drop_oldest",
+            Self::DropNewest => "drop_newest",
+            Self::BlockGuest => "block_guest",
+        }
+    }
+}
+
+/// A bounded queue of the records a guest logs, for hosts that consume
+/// them at their own pace rather than as they are logged.
+///
+/// A sandbox given a queue with `set_guest_log_queue` puts the records
+/// its guest logs into it instead of passing them to the `log` or
+/// `tracing` subscriber. Clones of a queue share the same records, so one
+/// clone can be given to the sandbox while another is read from. When the
+/// queue is full, records are dropped or the guest is suspended as the
+/// `LogOverflowPolicy` says, and the `guest_log_queue_overflows` metric is
+/// incremented.
+#[derive(Debug, Clone)]
+pub struct GuestLogQueue {
+    inner: Arc<Inner>,
+}
+
+#[derive(Debug)]
+struct Inner {
+    state: Mutex<State>,
+    not_empty: Condvar,
+    not_full: Condvar,
+    capacity: usize,
+    policy: LogOverflowPolicy,
+}
+
+#[derive(Debug, Default)]
+struct State {
+    records: VecDeque<GuestLogData>,
+    dropped: u64,
+}
+
+impl GuestLogQueue {
+    /// Create a queue that holds at most `capacity` records
+    #[instrument(err(Debug), skip_all, parent = Span::current(), level = "Trace")]
+    pub fn new(capacity: usize, policy: LogOverflowPolicy) -> Result<Self> {
+        if capacity == 0 {
+            return Err(new_error!(
+                "A guest log queue must hold at least one record"
+            ));
+        }
+        Ok(Self {
+            inner: Arc::new(Inner {
+                state: Mutex::new(State::default()),
+                not_empty: Condvar::new(),
+                not_full: Condvar::new(),
+                capacity,
+                policy,
+            }),
+        })
+    }
+
+    /// The most records the queue holds
+    pub fn capacity(&self) -> usize {
+        self.inner.capacity
+    }
+
+    /// What the queue does with records logged while it is full
+    pub fn policy(&self) -> LogOverflowPolicy {
+        self.inner.policy
+    }
+
+    /// The number of records in the queue
+    pub fn len(&self) -> usize {
+        self.lock().records.len()
+    }
+
+    /// Whether there are no records in the queue
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// The number of records that have been dropped because the queue was
+    /// full
+    pub fn dropped(&self) -> u64 {
+        self.lock().dropped
+    }
+
+    /// Take the oldest record out of the queue, if there is one
+    pub fn try_recv(&self) -> Option<GuestLogData> {
+        let record = self.lock().records.pop_front();
+        if record.is_some() {
+            self.inner.not_full.notify_one();
+        }
+        record
+    }
+
+    /// Take the oldest record out of the queue, waiting up to `timeout`
+    /// for the guest to log one if there is none
+    pub fn recv_timeout(&self, timeout: Duration) -> Option<GuestLogData> {
+        let deadline = Instant::now() + timeout;
+        let mut state = self.lock();
+        loop {
+            if let Some(record) = state.records.pop_front() {
+                self.inner.not_full.notify_one();
+                return Some(record);
+            }
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            if remaining.is_zero() {
+                return None;
+            }
+            state = self
+                .inner
+                .not_empty
+                .wait_timeout(state, remaining)
+                .unwrap_or_else(|e| e.into_inner())
+                .0;
+        }
+    }
+
+    /// Take all the records out of the queue, oldest first
+    pub fn drain(&self) -> Vec<GuestLogData> {
+        let records: Vec<_> = self.lock().records.drain(..).collect();
+        if !records.is_empty() {
+            self.inner.not_full.notify_all();
+        }
+        records
+    }
+
+    /// Add a record the guest logged, applying the overflow policy if the
+    /// queue is full. With `LogOverflowPolicy::BlockGuest` this blocks the
+    /// calling thread, which is the one running the guest, until there is
+    /// room or `deadline` passes, in which case the record is dropped.
+    pub(crate) fn push(&self, record: GuestLogData, deadline: Option<Instant>) {
+        let mut state = self.lock();
+        if state.records.len() >= self.inner.capacity {
+            int_counter_vec_inc!(&GuestLogQueueOverflows, &[self.inner.policy.name()]);
+            match self.inner.policy {
+                LogOverflowPolicy::DropOldest => {
+                    state.records.pop_front();
+                    state.dropped += 1;
+                }
+                LogOverflowPolicy::DropNewest => {
+                    state.dropped += 1;
+                    return;
+                }
+                LogOverflowPolicy::BlockGuest => {
+                    while state.records.len() >= self.inner.capacity {
+                        state = match deadline {
+                            Some(deadline) => {
+                                let remaining = deadline.saturating_duration_since(Instant::now());
+                                if remaining.is_zero() {
+                                    state.dropped += 1;
+                                    return;
+                                }
+                                self.inner
+                                    .not_full
+                                    .wait_timeout(state, remaining)
+                                    .unwrap_or_else(|e| e.into_inner())
+                                    .0
+                            }
+                            None => self
+                                .inner
+                                .not_full
+                                .wait(state)
+                                .unwrap_or_else(|e| e.into_inner()),
+                        };
+                    }
+                }
+            }
+        }
+        state.records.push_back(record);
+        self.inner.not_empty.notify_one();
+    }
+
+    fn lock(&self) -> MutexGuard<'_, State> {
+        // the state is consistent after every operation, so it is safe to
+        // carry on after a panic while it was locked
+        self.inner.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::thread;
+
+    use hyperlight_common::flatbuffer_wrappers::guest_log_level::LogLevel;
+
+    use super::*;
+
+    fn record(message: &str) -> GuestLogData {
+        GuestLogData::new(
+            message.to_string(),
+            "source".to_string(),
+            LogLevel::Information,
+            "caller".to_string(),
+            "source_file".to_string(),
+            1,
+        )
+    }
+
+    fn messages(records: Vec<GuestLogData>) -> Vec<String> {
+        records.into_iter().map(|record| record.message).collect()
+    }
+
+    #[test]
+    fn drop_oldest() {
+        let queue = GuestLogQueue::new(2, LogOverflowPolicy::DropOldest).unwrap();
+        for message in ["a", "b", "c"] {
+            queue.push(record(message), None);
+        }
+        assert_eq!(queue.dropped(), 1);
+        assert_eq!(messages(queue.drain()), ["b", "c"]);
+        assert!(queue.is_empty());
+    }
+
+    #[test]
+    fn drop_newest() {
+        let queue = GuestLogQueue::new(2, LogOverflowPolicy::DropNewest).unwrap();
+        for message in ["a", "b", "c"] {
+            queue.push(record(message), None);
+        }
+        assert_eq!(queue.dropped(), 1);
+        assert_eq!(queue.try_recv().unwrap().message, "a");
+        queue.push(record("d"), None);
+        assert_eq!(messages(queue.drain()), ["b", "d"]);
+    }
+
+    #[test]
+    fn block_guest() {
+        let queue = GuestLogQueue::new(1, LogOverflowPolicy::BlockGuest).unwrap();
+        let guest = {
+            let queue = queue.clone();
+            thread::spawn(move || {
+                for message in ["a", "b", "c"] {
+                    queue.push(record(message), None);
+                }
+            })
+        };
+        let mut received = Vec::new();
+        while received.len() < 3 {
+            received.extend(queue.recv_timeout(Duration::from_secs(10)));
+            assert!(queue.len() <= 1);
+        }
+        guest.join().unwrap();
+        assert_eq!(messages(received), ["a", "b", "c"]);
+        assert_eq!(queue.dropped(), 0);
+        assert!(queue.recv_timeout(Duration::from_millis(1)).is_none());
+    }
+
+    #[test]
+    fn block_guest_until_deadline() {
+        let queue = GuestLogQueue::new(1, LogOverflowPolicy::BlockGuest).unwrap();
+        queue.push(record("a"), None);
+        let start = Instant::now();
+        queue.push(record("b"), Some(start + Duration::from_millis(50)));
+        assert!(start.elapsed() >= Duration::from_millis(50));
+        assert_eq!(queue.dropped(), 1);
+        assert_eq!(messages(queue.drain()), ["a"]);
+    }
+
+    #[test]
+    fn capacity() {
+        assert!(GuestLogQueue::new(0, LogOverflowPolicy::DropOldest).is_err());
+        let queue = GuestLogQueue::new(5, LogOverflowPolicy::BlockGuest).unwrap();
+        assert_eq!(queue.capacity(), 5);
+        assert_eq!(queue.policy(), LogOverflowPolicy::BlockGuest);
+    }
+}
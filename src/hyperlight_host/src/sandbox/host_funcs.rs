@@ -15,28 +15,69 @@ limitations under the License.
 */
 
 use std::io::{IsTerminal, Write};
+use std::time::Duration;
 
-use hyperlight_common::flatbuffer_wrappers::function_types::{ParameterValue, ReturnValue};
+use crossbeam_channel::Sender;
+use hyperlight_common::builtin_services::RESERVED_PREFIX;
+use hyperlight_common::flatbuffer_wrappers::function_types::{
+    ParameterType, ParameterValue, ReturnType, ReturnValue,
+};
 use hyperlight_common::flatbuffer_wrappers::host_function_definition::HostFunctionDefinition;
 use hyperlight_common::flatbuffer_wrappers::host_function_details::HostFunctionDetails;
+use hyperlight_common::progress::REPORT_PROGRESS_HOST_FUNCTION;
 use termcolor::{Color, ColorChoice, ColorSpec, StandardStream, WriteColor};
 use tracing::{instrument, Span};
 
-use super::{ExtraAllowedSyscall, FunctionsMap};
-use crate::func::HyperlightFunction;
+use super::{ExtraAllowedSyscall, FunctionsMap, HostPrintOptions, SandboxId};
+use crate::func::call_options::{with_call_options, CallOptions, ProgressReport};
+use crate::func::{HyperlightFallbackFunction, HyperlightFunction};
 use crate::mem::mgr::SandboxMemoryManager;
-use crate::mem::shared_mem::ExclusiveSharedMemory;
-use crate::HyperlightError::HostFunctionNotFound;
-use crate::{new_error, Result};
+use crate::mem::shared_mem::{ExclusiveSharedMemory, HostSharedMemory};
+use crate::HyperlightError::{
+    HostFunctionNotFound, ParameterValueConversionFailure, UnexpectedNoOfArguments,
+};
+use crate::{log_then_return, new_error, Result};
+
+/// The label the durations of calls to the fallback host function are
+/// recorded under, whatever function the guest called
+#[cfg(feature = "function_call_metrics")]
+const FALLBACK_METRIC_LABEL: &str = "<fallback>";
 
 #[derive(Default, Clone)]
 /// A Wrapper around details of functions exposed by the Host
 pub struct HostFuncsWrapper {
     functions_map: FunctionsMap,
     function_details: HostFunctionDetails,
+    /// Whether functions can be registered under `RESERVED_PREFIX`, which
+    /// only `BuiltinServices` does
+    allow_reserved_names: bool,
+    /// How long the vCPU thread spins for while it waits for a host
+    /// function running on a thread of its own, see
+    /// `SandboxConfiguration::set_latency_profile`
+    busy_poll_window: Option<Duration>,
+    /// The options of the guest function call in progress, which the host
+    /// functions it calls see, such as `CallOptions::context`
+    call_options: Option<CallOptions>,
+    /// Where the progress the guest reports goes, to be passed to
+    /// `CallOptions::on_progress` by the thread that called the guest
+    progress: Option<Sender<ProgressReport>>,
 }
 
 impl HostFuncsWrapper {
+    #[instrument(skip_all, parent = Span::current(), level = "Trace")]
+    pub(super) fn set_busy_poll_window(&mut self, busy_poll_window: Option<Duration>) {
+        self.busy_poll_window = busy_poll_window;
+    }
+    /// Set the options the host functions called during the next guest
+    /// function call see, until they are set again
+    pub(crate) fn set_call_options(&mut self, options: Option<CallOptions>) {
+        self.call_options = options;
+    }
+    /// Set where the progress the guest reports goes, see
+    /// `HypervisorHandler::progress_sender`
+    pub(crate) fn set_progress_sender(&mut self, progress: Sender<ProgressReport>) {
+        self.progress = Some(progress);
+    }
     #[instrument(skip_all, parent = Span::current(), level = "Trace")]
     fn get_host_funcs(&self) -> &FunctionsMap {
         &self.functions_map
@@ -54,6 +95,37 @@ impl HostFuncsWrapper {
         &mut self.function_details
     }
 
+    /// The host function details, serialized as the guest reads them
+    pub(super) fn serialize_host_func_details(&self) -> Result<Vec<u8>> {
+        serialize_host_func_details(self.get_host_func_details())
+    }
+
+    /// Allow or disallow registering functions under `RESERVED_PREFIX`
+    pub(super) fn set_allow_reserved_names(&mut self, allow: bool) {
+        self.allow_reserved_names = allow;
+    }
+
+    /// Let the guest report the progress of the call it is running with
+    /// `REPORT_PROGRESS_HOST_FUNCTION`. Reports are handled on the vCPU
+    /// thread by `call_host_function`, rather than by a function running
+    /// on a thread of its own, so only the definition the guest checks its
+    /// calls against is registered.
+    #[instrument(err(Debug), skip_all, parent = Span::current(), level = "Trace")]
+    pub(super) fn register_report_progress(
+        &mut self,
+        mgr: &mut SandboxMemoryManager<ExclusiveSharedMemory>,
+    ) -> Result<()> {
+        self.get_host_func_details_mut()
+            .insert_host_function(HostFunctionDefinition::new(
+                REPORT_PROGRESS_HOST_FUNCTION.to_string(),
+                Some(vec![ParameterType::UInt, ParameterType::String]),
+                ReturnType::Void,
+            ));
+        self.get_host_func_details_mut()
+            .sort_host_functions_by_name();
+        write_host_function_details(self, mgr)
+    }
+
     /// Register a host function with the sandbox.
     #[instrument(err(Debug), skip_all, parent = Span::current(), level = "Trace")]
     pub(crate) fn register_host_function(
@@ -79,6 +151,110 @@ impl HostFuncsWrapper {
         register_host_function_helper(self, mgr, hfd, func, Some(extra_allowed_syscalls))
     }
 
+    /// Register a host function with a sandbox whose guest has been
+    /// initialized. The definitions the guest sees are updated before the
+    /// function is added, so nothing changes if they don't fit in the host
+    /// function definitions buffer.
+    #[instrument(err(Debug), skip_all, parent = Span::current(), level = "Trace")]
+    pub(crate) fn register_host_function_after_init(
+        &mut self,
+        mgr: &mut SandboxMemoryManager<HostSharedMemory>,
+        hfd: &HostFunctionDefinition,
+        func: HyperlightFunction,
+    ) -> Result<()> {
+        check_host_function_name(self, &hfd.function_name)?;
+        let mut details = self.get_host_func_details().clone();
+        details.insert_host_function(hfd.clone());
+        details.sort_host_functions_by_name();
+        mgr.update_host_function_details(&serialize_host_func_details(&details)?)?;
+
+        self.get_host_funcs_mut().insert(
+            hfd.function_name.to_string(),
+            hfd.parameter_types.clone().unwrap_or_default(),
+            func,
+            None,
+        );
+        self.function_details = details;
+        Ok(())
+    }
+
+    /// Remove every overload of the host function `name` from a sandbox
+    /// whose guest has been initialized, so that calls to it go to the
+    /// fallback, if there is one.
+    #[instrument(err(Debug), skip_all, parent = Span::current(), level = "Trace")]
+    pub(crate) fn unregister_host_function(
+        &mut self,
+        mgr: &mut SandboxMemoryManager<HostSharedMemory>,
+        name: &str,
+    ) -> Result<()> {
+        check_host_function_name(self, name)?;
+        let mut details = self.get_host_func_details().clone();
+        if !details.remove_host_function(name) {
+            return Err(HostFunctionNotFound(name.to_string()));
+        }
+        mgr.update_host_function_details(&serialize_host_func_details(&details)?)?;
+
+        self.get_host_funcs_mut().remove(name);
+        self.function_details = details;
+        Ok(())
+    }
+
+    /// Register the handler for calls to host functions that have not been
+    /// registered with the sandbox, with a list of extra syscalls that it is
+    /// allowed to make, replacing any existing one.
+    #[instrument(err(Debug), skip_all, parent = Span::current(), level = "Trace")]
+    pub(crate) fn register_fallback_host_function(
+        &mut self,
+        mgr: &mut SandboxMemoryManager<ExclusiveSharedMemory>,
+        func: HyperlightFallbackFunction,
+        extra_allowed_syscalls: Vec<ExtraAllowedSyscall>,
+    ) -> Result<()> {
+        self.get_host_funcs_mut()
+            .set_fallback(func, Some(extra_allowed_syscalls));
+        // let the guest know that it can call functions the host has not
+        // told it about
+        self.get_host_func_details_mut().has_fallback = true;
+        write_host_function_details(self, mgr)
+    }
+
+    /// Apply `options` to every message the sandbox with the id
+    /// `sandbox_id` passes to the `"HostPrint"` host function that is
+    /// currently registered, keeping its extra allowed syscalls.
+    #[instrument(err(Debug), skip_all, parent = Span::current(), level = "Trace")]
+    pub(crate) fn set_host_print_options(
+        &mut self,
+        sandbox_id: SandboxId,
+        mut options: HostPrintOptions,
+    ) -> Result<()> {
+        let (writer, extra_allowed_syscalls) = self
+            .get_host_funcs()
+            .get("HostPrint", &[ParameterType::String])
+            .map(|(func, syscalls)| (func.clone(), syscalls.clone()))
+            .ok_or_else(|| HostFunctionNotFound("HostPrint".to_string()))?;
+
+        let func = HyperlightFunction::new(move |args: Vec<ParameterValue>| {
+            let msg = match <[ParameterValue; 1]>::try_from(args) {
+                Ok([ParameterValue::String(msg)]) => msg,
+                Ok([other]) => return Err(ParameterValueConversionFailure(other, "String")),
+                Err(args) => return Err(UnexpectedNoOfArguments(args.len(), 1)),
+            };
+            match options.filter(sandbox_id, msg) {
+                Some(msg) => writer.call(vec![ParameterValue::String(msg)]),
+                // nothing was written
+                None => Ok(ReturnValue::Int(0)),
+            }
+        });
+
+        self.get_host_funcs_mut().insert(
+            "HostPrint".to_string(),
+            vec![ParameterType::String],
+            func,
+            extra_allowed_syscalls,
+        );
+
+        Ok(())
+    }
+
     /// Assuming a host function called `"HostPrint"` exists, and takes a
     /// single string parameter, call it with the given `msg` parameter.
     ///
@@ -90,6 +266,8 @@ impl HostFuncsWrapper {
             self.get_host_funcs(),
             "HostPrint",
             vec![ParameterValue::String(msg)],
+            self.busy_poll_window,
+            self.call_options.clone(),
         )?;
         res.try_into()
             .map_err(|_| HostFunctionNotFound("HostPrint".to_string()))
@@ -107,7 +285,40 @@ impl HostFuncsWrapper {
         name: &str,
         args: Vec<ParameterValue>,
     ) -> Result<ReturnValue> {
-        call_host_func_impl(self.get_host_funcs(), name, args)
+        if name == REPORT_PROGRESS_HOST_FUNCTION {
+            return self.report_progress(args);
+        }
+        call_host_func_impl(
+            self.get_host_funcs(),
+            name,
+            args,
+            self.busy_poll_window,
+            self.call_options.clone(),
+        )
+    }
+
+    /// Send the progress the guest reported to the thread that called it,
+    /// if the call was given a progress callback
+    fn report_progress(&self, args: Vec<ParameterValue>) -> Result<ReturnValue> {
+        let (percent, message) = match <[ParameterValue; 2]>::try_from(args) {
+            Ok([ParameterValue::UInt(percent), ParameterValue::String(message)]) => {
+                (percent, message)
+            }
+            Ok([ParameterValue::UInt(_), other]) => {
+                return Err(ParameterValueConversionFailure(other, "String"))
+            }
+            Ok([other, _]) => return Err(ParameterValueConversionFailure(other, "UInt")),
+            Err(args) => return Err(UnexpectedNoOfArguments(args.len(), 2)),
+        };
+        let on_progress = self
+            .call_options
+            .as_ref()
+            .is_some_and(|options| options.on_progress.is_some());
+        if let Some(progress) = self.progress.as_ref().filter(|_| on_progress) {
+            // the handler the receiver belongs to outlives the call
+            let _ = progress.send((percent.min(100), message));
+        }
+        Ok(ReturnValue::Void)
     }
 }
 
@@ -118,20 +329,28 @@ fn register_host_function_helper(
     func: HyperlightFunction,
     extra_allowed_syscalls: Option<Vec<ExtraAllowedSyscall>>,
 ) -> Result<()> {
+    check_host_function_name(self_, &hfd.function_name)?;
+    let parameter_types = hfd.parameter_types.clone().unwrap_or_default();
     if let Some(_syscalls) = extra_allowed_syscalls {
         #[cfg(all(feature = "seccomp", target_os = "linux"))]
-        self_
-            .get_host_funcs_mut()
-            .insert(hfd.function_name.to_string(), func, Some(_syscalls));
+        self_.get_host_funcs_mut().insert(
+            hfd.function_name.to_string(),
+            parameter_types,
+            func,
+            Some(_syscalls),
+        );
 
         #[cfg(not(all(feature = "seccomp", target_os = "linux")))]
         return Err(new_error!(
             "Extra syscalls are only supported on Linux with seccomp"
         ));
     } else {
-        self_
-            .get_host_funcs_mut()
-            .insert(hfd.function_name.to_string(), func, None);
+        self_.get_host_funcs_mut().insert(
+            hfd.function_name.to_string(),
+            parameter_types,
+            func,
+            None,
+        );
     }
     self_
         .get_host_func_details_mut()
@@ -142,38 +361,83 @@ fn register_host_function_helper(
     self_
         .get_host_func_details_mut()
         .sort_host_functions_by_name();
-    let buffer: Vec<u8> = self_.get_host_func_details().try_into().map_err(|e| {
+    write_host_function_details(self_, mgr)
+}
+
+/// Fail if `name` is reserved for the built-in services, unless they are
+/// the ones registering it
+fn check_host_function_name(self_: &HostFuncsWrapper, name: &str) -> Result<()> {
+    if name.starts_with(RESERVED_PREFIX) && !self_.allow_reserved_names {
+        log_then_return!(
+            "Host function names starting with {} are reserved for the built-in services",
+            RESERVED_PREFIX
+        );
+    }
+    Ok(())
+}
+
+fn serialize_host_func_details(details: &HostFunctionDetails) -> Result<Vec<u8>> {
+    details.try_into().map_err(|e| {
         new_error!(
             "Error serializing host function details to flatbuffer: {}",
             e
         )
-    })?;
+    })
+}
+
+fn write_host_function_details(
+    self_: &HostFuncsWrapper,
+    mgr: &mut SandboxMemoryManager<ExclusiveSharedMemory>,
+) -> Result<()> {
+    let buffer = self_.serialize_host_func_details()?;
     mgr.write_buffer_host_function_details(&buffer)?;
 
     Ok(())
 }
 
 #[instrument(err(Debug), skip_all, parent = Span::current(), level = "Trace")]
+#[cfg_attr(
+    not(all(feature = "seccomp", target_os = "linux")),
+    allow(unused_variables)
+)]
 fn call_host_func_impl(
     host_funcs: &FunctionsMap,
     name: &str,
     args: Vec<ParameterValue>,
+    busy_poll_window: Option<Duration>,
+    call_options: Option<CallOptions>,
 ) -> Result<ReturnValue> {
     // Inner function containing the common logic
     fn call_func(
         host_funcs: &FunctionsMap,
         name: &str,
         args: Vec<ParameterValue>,
+        call_options: Option<CallOptions>,
     ) -> Result<ReturnValue> {
-        let func_with_syscalls = host_funcs
-            .get(name)
-            .ok_or_else(|| HostFunctionNotFound(name.to_string()))?;
+        // pick the overload matching the types of the arguments
+        let parameter_types = args.iter().map(ParameterType::from).collect::<Vec<_>>();
+        let func_with_syscalls = match host_funcs.get(name, &parameter_types) {
+            Some((func, syscalls)) => (func.clone(), syscalls.clone()),
+            // only calls to functions that were never registered go to the
+            // fallback, a known function called with the wrong arguments is
+            // still an error
+            None if !host_funcs.contains_key(name) => {
+                let (fallback, syscalls) = host_funcs
+                    .fallback()
+                    .ok_or_else(|| HostFunctionNotFound(name.to_string()))?;
+                let fallback = fallback.clone();
+                let name = name.to_string();
+                let func = HyperlightFunction::new(move |args| fallback.call(&name, args));
+                (func, syscalls.clone())
+            }
+            None => return Err(HostFunctionNotFound(name.to_string())),
+        };
 
-        let func = func_with_syscalls.0.clone();
+        let func = func_with_syscalls.0;
 
         #[cfg(all(feature = "seccomp", target_os = "linux"))]
         {
-            let syscalls = func_with_syscalls.1.clone();
+            let syscalls = func_with_syscalls.1;
             let seccomp_filter =
                 crate::seccomp::guest::get_seccomp_filter_for_host_function_worker_thread(
                     syscalls,
@@ -184,17 +448,24 @@ fn call_host_func_impl(
         #[cfg(feature = "function_call_metrics")]
         {
             let start = std::time::Instant::now();
-            let result = func.call(args.clone());
+            let result = with_call_options(call_options, || func.call(args.clone()));
+            // the guest chooses the names of the functions it calls through
+            // the fallback, so they share one label
+            let label = if host_funcs.contains_key(name) {
+                name
+            } else {
+                FALLBACK_METRIC_LABEL
+            };
             crate::histogram_vec_observe!(
                 &crate::sandbox::metrics::SandboxMetric::HostFunctionCallsDurationMicroseconds,
-                &[name],
+                &[label],
                 start.elapsed().as_micros() as f64
             );
             result
         }
 
         #[cfg(not(feature = "function_call_metrics"))]
-        func.call(args)
+        with_call_options(call_options, || func.call(args))
     }
 
     cfg_if::cfg_if! {
@@ -215,7 +486,9 @@ fn call_host_func_impl(
                     // execution after trapping the disallowed syscall can lead to UB (e.g., try
                     // running a host function that attempts to sleep without `SYS_clock_nanosleep`,
                     // you'll block the syscall but panic in the aftermath).
-                    match std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| call_func(&host_funcs_cloned, &name_cloned, args_cloned))) {
+                    match std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+                        call_func(&host_funcs_cloned, &name_cloned, args_cloned, call_options)
+                    })) {
                         Ok(val) => val,
                         Err(err) => {
                             if let Some(crate::HyperlightError::DisallowedSyscall) = err.downcast_ref::<crate::HyperlightError>() {
@@ -227,10 +500,15 @@ fn call_host_func_impl(
                     }
                 })?;
 
+            // the host function may well be done before the thread would be
+            // woken up by it finishing
+            crate::hypervisor::hypervisor_handler::busy_poll(busy_poll_window, || {
+                join_handle.is_finished().then_some(())
+            });
             join_handle.join().map_err(|_| new_error!("Error joining thread executing host function"))?
         } else {
             // Directly call the function without creating a new thread
-            call_func(host_funcs, name, args)
+            call_func(host_funcs, name, args, call_options)
         }
     }
 }
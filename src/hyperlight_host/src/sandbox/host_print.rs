@@ -0,0 +1,188 @@
+/*
+Copyright 2024 The Hyperlight Authors.
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+    http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+use std::time::{Duration, Instant};
+
+use tracing::{instrument, Span};
+
+use super::SandboxId;
+
+/// What should happen to a message the guest printed, as decided by a
+/// host print hook.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum HostPrintAction {
+    /// Pass the message on to the `HostPrint` writer.
+    Print,
+    /// Discard the message. Hooks that want to buffer output should keep
+    /// their own copy of the message and return this.
+    Drop,
+}
+
+type HostPrintHook = Box<dyn FnMut(SandboxId, &str) -> HostPrintAction + Send>;
+
+/// Options controlling how messages printed by the guest are handed to
+/// the `HostPrint` writer of a sandbox.
+///
+/// The options wrap the writer that is registered when
+/// `UninitializedSandbox::set_host_print_options` is called, so messages
+/// that get through are still written by it.
+pub struct HostPrintOptions {
+    sandbox_name: String,
+    prefix: bool,
+    rate_limit: Option<(u32, Duration)>,
+    hook: Option<HostPrintHook>,
+    window_start: Option<Instant>,
+    printed_in_window: u32,
+}
+
+impl HostPrintOptions {
+    /// Create options for the sandbox called `sandbox_name`, which pass
+    /// every message on to the writer unchanged.
+    #[instrument(skip_all, parent = Span::current(), level = "Trace")]
+    pub fn new(sandbox_name: impl Into<String>) -> Self {
+        Self {
+            sandbox_name: sandbox_name.into(),
+            prefix: false,
+            rate_limit: None,
+            hook: None,
+            window_start: None,
+            printed_in_window: 0,
+        }
+    }
+
+    /// Prefix every message with `[<sandbox name>] ` before it is written.
+    #[instrument(skip_all, parent = Span::current(), level = "Trace")]
+    pub fn with_prefix(mut self) -> Self {
+        self.prefix = true;
+        self
+    }
+
+    /// Write at most `max_messages` messages in any `period`, dropping the
+    /// rest, so that a noisy guest can't flood the host's output.
+    #[instrument(skip_all, parent = Span::current(), level = "Trace")]
+    pub fn with_rate_limit(mut self, max_messages: u32, period: Duration) -> Self {
+        self.rate_limit = Some((max_messages, period));
+        self
+    }
+
+    /// Call `hook` with the id of the sandbox and each message before it is
+    /// written, and only write the message if the hook returns
+    /// `HostPrintAction::Print`.
+    #[instrument(skip_all, parent = Span::current(), level = "Trace")]
+    pub fn with_hook<F>(mut self, hook: F) -> Self
+    where
+        F: FnMut(SandboxId, &str) -> HostPrintAction + Send + 'static,
+    {
+        self.hook = Some(Box::new(hook));
+        self
+    }
+
+    /// Apply the options to `msg`, printed by the sandbox with the id
+    /// `sandbox_id`, returning the message that should be written, or
+    /// `None` if it should be dropped.
+    #[instrument(skip_all, parent = Span::current(), level = "Trace")]
+    pub(super) fn filter(&mut self, sandbox_id: SandboxId, msg: String) -> Option<String> {
+        if let Some(hook) = self.hook.as_mut() {
+            if hook(sandbox_id, &msg) == HostPrintAction::Drop {
+                return None;
+            }
+        }
+
+        if let Some((max_messages, period)) = self.rate_limit {
+            let now = Instant::now();
+            match self.window_start {
+                Some(start) if now.duration_since(start) < period => {}
+                _ => {
+                    self.window_start = Some(now);
+                    self.printed_in_window = 0;
+                }
+            }
+            if self.printed_in_window >= max_messages {
+                return None;
+            }
+            self.printed_in_window += 1;
+        }
+
+        match self.prefix {
+            true => Some(format!("[{}] {}", self.sandbox_name, msg)),
+            false => Some(msg),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+    use std::time::Duration;
+
+    use super::{HostPrintAction, HostPrintOptions};
+    use crate::sandbox::randomness::SandboxRng;
+    use crate::sandbox::SandboxId;
+
+    fn id() -> SandboxId {
+        SandboxId::new(&mut SandboxRng::default())
+    }
+
+    #[test]
+    fn prefix() {
+        let id = id();
+        let mut options = HostPrintOptions::new("sandbox");
+        assert_eq!(options.filter(id, "hello".to_string()).unwrap(), "hello");
+
+        let mut options = HostPrintOptions::new("sandbox").with_prefix();
+        assert_eq!(
+            options.filter(id, "hello".to_string()).unwrap(),
+            "[sandbox] hello"
+        );
+    }
+
+    #[test]
+    fn hook() {
+        let id = id();
+        let buffered = Arc::new(Mutex::new(Vec::new()));
+        let buffered_clone = buffered.clone();
+        let mut options = HostPrintOptions::new("sandbox").with_hook(move |sandbox_id, msg| {
+            assert_eq!(sandbox_id, id);
+            if msg.starts_with("debug") {
+                buffered_clone.lock().unwrap().push(msg.to_string());
+                return HostPrintAction::Drop;
+            }
+            HostPrintAction::Print
+        });
+
+        assert!(options.filter(id, "debug: buffered".to_string()).is_none());
+        assert_eq!(
+            options.filter(id, "printed".to_string()).unwrap(),
+            "printed"
+        );
+        assert_eq!(*buffered.lock().unwrap(), vec!["debug: buffered"]);
+    }
+
+    #[test]
+    fn rate_limit() {
+        let id = id();
+        let period = Duration::from_millis(100);
+        let mut options = HostPrintOptions::new("sandbox").with_rate_limit(2, period);
+
+        assert!(options.filter(id, "1".to_string()).is_some());
+        assert!(options.filter(id, "2".to_string()).is_some());
+        assert!(options.filter(id, "3".to_string()).is_none());
+
+        // the limit resets once the period has passed
+        std::thread::sleep(period);
+        assert!(options.filter(id, "4".to_string()).is_some());
+    }
+}
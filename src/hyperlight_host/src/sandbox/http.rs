@@ -0,0 +1,267 @@
+/*
+Copyright 2024 The Hyperlight Authors.
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+    http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+use std::io::Read;
+use std::time::Duration;
+
+use attohttpc::header::{HeaderName, HeaderValue};
+use attohttpc::{Method, RequestBuilder};
+use hyperlight_common::http::HttpResponse;
+use tracing::{instrument, Span};
+use url::Url;
+
+use crate::{log_then_return, new_error, Result};
+
+/// Headers the guest can't set, as they decide where a request goes or how
+/// its body is framed
+const FORBIDDEN_HEADERS: &[&str] = &["host", "content-length", "transfer-encoding"];
+
+/// The URLs a guest can request with the `hyperlight::http::request`
+/// built-in service, and the limits on its requests.
+///
+/// No URL is allowed until it is added with `with_allowed_url`. Redirects
+/// are never followed, so that a server can't send the guest somewhere it
+/// isn't allowed to go.
+#[derive(Debug, Clone)]
+pub struct HttpPolicy {
+    allowed_urls: Vec<Url>,
+    max_request_bytes: usize,
+    max_response_bytes: usize,
+    timeout: Duration,
+}
+
+impl Default for HttpPolicy {
+    fn default() -> Self {
+        Self {
+            allowed_urls: Vec::new(),
+            max_request_bytes: 1024 * 1024,
+            max_response_bytes: 1024 * 1024,
+            timeout: Duration::from_secs(30),
+        }
+    }
+}
+
+impl HttpPolicy {
+    /// Create a policy that allows no URLs
+    #[instrument(skip_all, parent = Span::current(), level = "Trace")]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Allow requests to `url` and, if its path ends with `/`, to every
+    /// path under it. The scheme, host and port of requests must match
+    /// `url` exactly.
+    #[instrument(err(Debug), skip(self), parent = Span::current(), level = "Trace")]
+    pub fn with_allowed_url(mut self, url: &str) -> Result<Self> {
+        let url = Url::parse(url).map_err(|e| new_error!("{} is not a valid URL: {}", url, e))?;
+        if !matches!(url.scheme(), "http" | "https") || url.host_str().is_none() {
+            log_then_return!("{} is not an HTTP URL", url);
+        }
+        self.allowed_urls.push(url);
+        Ok(self)
+    }
+
+    /// Limit the body of the requests the guest makes to `max` bytes. The
+    /// default is 1MiB.
+    #[instrument(skip(self), parent = Span::current(), level = "Trace")]
+    pub fn with_max_request_bytes(mut self, max: usize) -> Self {
+        self.max_request_bytes = max;
+        self
+    }
+
+    /// Fail requests whose response has a body of more than `max` bytes.
+    /// The default is 1MiB.
+    #[instrument(skip(self), parent = Span::current(), level = "Trace")]
+    pub fn with_max_response_bytes(mut self, max: usize) -> Self {
+        self.max_response_bytes = max;
+        self
+    }
+
+    /// Fail requests that take longer than `timeout`, from connecting to
+    /// reading the whole response. The default is 30 seconds.
+    #[instrument(skip(self), parent = Span::current(), level = "Trace")]
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    fn is_allowed(&self, url: &Url) -> bool {
+        if !url.username().is_empty() || url.password().is_some() {
+            return false;
+        }
+        self.allowed_urls.iter().any(|allowed| {
+            allowed.scheme() == url.scheme()
+                && allowed.host_str() == url.host_str()
+                && allowed.port_or_known_default() == url.port_or_known_default()
+                && if allowed.path().ends_with('/') {
+                    url.path().starts_with(allowed.path())
+                } else {
+                    url.path() == allowed.path()
+                }
+        })
+    }
+
+    /// Make the request the guest asked for, returning the encoded
+    /// `HttpResponse`
+    pub(crate) fn request(
+        &self,
+        method: &str,
+        url: &str,
+        headers: &str,
+        body: Vec<u8>,
+    ) -> Result<Vec<u8>> {
+        let method = Method::from_bytes(method.as_bytes())
+            .map_err(|_| new_error!("{} is not an HTTP method", method))?;
+        let url = Url::parse(url).map_err(|e| new_error!("{} is not a valid URL: {}", url, e))?;
+        if !self.is_allowed(&url) {
+            log_then_return!("The guest is not allowed to request {}", url);
+        }
+        if body.len() > self.max_request_bytes {
+            log_then_return!(
+                "The guest's request has {} bytes, but at most {} are allowed",
+                body.len(),
+                self.max_request_bytes
+            );
+        }
+
+        let mut request = RequestBuilder::try_new(method, url.as_str())
+            .map_err(|e| new_error!("HTTP request failed: {}", e))?
+            .follow_redirects(false)
+            .timeout(self.timeout);
+        for line in headers.lines().filter(|line| !line.trim().is_empty()) {
+            let Some((name, value)) = line.split_once(':') else {
+                log_then_return!("{} is not a header", line);
+            };
+            let name = name.trim();
+            if FORBIDDEN_HEADERS
+                .iter()
+                .any(|forbidden| name.eq_ignore_ascii_case(forbidden))
+            {
+                log_then_return!("The guest can't set the {} header", name);
+            }
+            let name = HeaderName::from_bytes(name.as_bytes())
+                .map_err(|e| new_error!("{} is not a valid header: {}", line, e))?;
+            let value = HeaderValue::from_str(value.trim())
+                .map_err(|e| new_error!("{} is not a valid header: {}", line, e))?;
+            request = request.header_append(name, value);
+        }
+
+        let response = request
+            .bytes(body)
+            .send()
+            .map_err(|e| new_error!("HTTP request failed: {}", e))?;
+        let (status, headers, reader) = response.split();
+        let mut body = Vec::new();
+        reader
+            .take(self.max_response_bytes as u64 + 1)
+            .read_to_end(&mut body)?;
+        if body.len() > self.max_response_bytes {
+            log_then_return!(
+                "The response to the guest's request has more than {} bytes",
+                self.max_response_bytes
+            );
+        }
+
+        let response = HttpResponse {
+            status: status.as_u16(),
+            headers: headers
+                .iter()
+                .map(|(name, value)| {
+                    (
+                        name.to_string(),
+                        String::from_utf8_lossy(value.as_bytes()).into_owned(),
+                    )
+                })
+                .collect(),
+            body,
+        };
+        Ok(Vec::from(&response))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+    use std::net::TcpListener;
+    use std::thread;
+
+    use super::*;
+
+    #[test]
+    fn allowed_urls() {
+        let policy = HttpPolicy::new()
+            .with_allowed_url("https://api.example.com/v1/")
+            .unwrap()
+            .with_allowed_url("http://example.com/status")
+            .unwrap();
+        let allowed = |url: &str| policy.is_allowed(&Url::parse(url).unwrap());
+
+        assert!(allowed("https://API.example.com:443/v1/items?id=1"));
+        assert!(allowed("http://example.com/status"));
+        assert!(!allowed("https://api.example.com/v1"));
+        assert!(!allowed("https://api.example.com/v1/../admin"));
+        assert!(!allowed("http://api.example.com/v1/items"));
+        assert!(!allowed("https://api.example.com:8443/v1/items"));
+        assert!(!allowed("https://api.example.com.evil.com/v1/items"));
+        assert!(!allowed("https://user@api.example.com/v1/items"));
+        assert!(!allowed("http://example.com/status/more"));
+        assert!(HttpPolicy::new().with_allowed_url("file:///etc").is_err());
+    }
+
+    #[test]
+    fn request() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let server = thread::spawn(move || {
+            for body in ["hello", "a longer body"] {
+                let (mut stream, _) = listener.accept().unwrap();
+                let mut request = [0; 1024];
+                let _ = stream.read(&mut request).unwrap();
+                write!(
+                    stream,
+                    "HTTP/1.1 200 OK\r\nContent-Length: {}\r\nX-Test: yes\r\nConnection: close\r\n\r\n{}",
+                    body.len(),
+                    body
+                )
+                .unwrap();
+            }
+        });
+
+        let url = format!("http://127.0.0.1:{}/", port);
+        let policy = HttpPolicy::new()
+            .with_allowed_url(&url)
+            .unwrap()
+            .with_max_request_bytes(4)
+            .with_max_response_bytes(5);
+        assert!(policy.request("GET", &url, "", vec![0; 5]).is_err());
+        assert!(policy
+            .request("GET", &url, "Host: evil.com", vec![])
+            .is_err());
+        assert!(policy
+            .request("GET", "http://127.0.0.1:1/", "", vec![])
+            .is_err());
+
+        let response = policy
+            .request("GET", &url, "Accept: text/plain\n", vec![])
+            .unwrap();
+        let response = HttpResponse::try_from(response.as_slice()).unwrap();
+        assert_eq!(response.status, 200);
+        assert_eq!(response.header("x-test"), Some("yes"));
+        assert_eq!(response.body, b"hello");
+        assert!(policy.request("POST", &url, "", vec![1]).is_err());
+        server.join().unwrap();
+    }
+}
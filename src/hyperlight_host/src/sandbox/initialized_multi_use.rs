@@ -15,21 +15,43 @@ limitations under the License.
 */
 
 use std::sync::{Arc, Mutex};
+use std::time::Duration;
 
 use hyperlight_common::flatbuffer_wrappers::function_types::{
-    ParameterValue, ReturnType, ReturnValue,
+    ParameterType, ParameterValue, ReturnType, ReturnValue,
 };
+use hyperlight_common::flatbuffer_wrappers::host_function_definition::HostFunctionDefinition;
 use tracing::{instrument, Span};
 
+#[cfg(all(feature = "cgroup", target_os = "linux"))]
+use super::cgroup::CgroupUsage;
+use super::coverage::CoverageMap;
 use super::host_funcs::HostFuncsWrapper;
-use super::{MemMgrWrapper, WrapperGetter};
+use super::registry::SandboxRegistration;
+use super::result_cache::{ResultCache, ResultCachePolicy, ResultCacheStats};
+use super::uninitialized_evolve::initialise_guest;
+use super::{
+    CallProfile, CreationReport, EventSubscriber, GuestInfo, GuestProfile, MemMgrWrapper,
+    SandboxId, WrapperGetter,
+};
 use crate::func::call_ctx::MultiUseGuestCallContext;
-use crate::func::guest_dispatch::call_function_on_guest;
-use crate::hypervisor::hypervisor_handler::HypervisorHandler;
+use crate::func::call_options::CallOptions;
+use crate::func::guest_caller::{timed, CallStats};
+use crate::func::guest_dispatch::{call_function_on_guest, GuestFunctionId};
+use crate::func::HyperlightFunction;
+use crate::hypervisor::hypervisor_handler::{HypervisorHandler, HypervisorHandlerAction};
+#[cfg(target_arch = "x86_64")]
+use crate::hypervisor::DebugRegisters;
+use crate::hypervisor::VcpuStats;
+use crate::mem::memory_region::MemoryRegion;
 use crate::mem::shared_mem::HostSharedMemory;
 use crate::sandbox_state::sandbox::{DevolvableSandbox, EvolvableSandbox, Sandbox};
 use crate::sandbox_state::transition::{MultiUseContextCallback, Noop};
-use crate::Result;
+use crate::{log_then_return, new_error, HyperlightError, Result};
+
+/// The guest function `MultiUseSandbox::shutdown` calls, if the guest
+/// registered one, before tearing the sandbox down
+const ON_SHUTDOWN_FUNCTION: &str = "hyperlight_on_shutdown";
 
 /// A sandbox that supports being used Multiple times.
 /// The implication of being used multiple times is two-fold:
@@ -40,10 +62,17 @@ use crate::Result;
 /// 2. A MultiUseGuestCallContext can be created from the sandbox and used to make multiple guest function calls to the Sandbox.
 ///    in this case the state of the sandbox is not reset until the context is finished and the `MultiUseSandbox` is returned.
 pub struct MultiUseSandbox {
-    // We need to keep a reference to the host functions, even if the compiler marks it as unused. The compiler cannot detect our dynamic usages of the host function in `HyperlightFunction::call`.
-    pub(super) _host_funcs: Arc<Mutex<HostFuncsWrapper>>,
+    /// The host functions the guest can call, which can still be changed
+    /// with `register_host_function` and `unregister_host_function`
+    pub(super) host_funcs: Arc<Mutex<HostFuncsWrapper>>,
     pub(crate) mem_mgr: MemMgrWrapper<HostSharedMemory>,
     hv_handler: HypervisorHandler,
+    registration: SandboxRegistration,
+    result_cache: Option<ResultCache>,
+    call_stats: CallStats,
+    /// Whether the guest's entrypoint has run, see
+    /// `UninitializedSandbox::set_lazy_initialization`
+    initialized: bool,
 }
 
 // We need to implement drop to join the
@@ -62,6 +91,9 @@ impl Drop for MultiUseSandbox {
                 log::error!("[POTENTIAL THREAD LEAK] Potentially failed to kill hypervisor handler thread when dropping MultiUseSandbox: {:?}", e);
             }
         }
+        self.hv_handler
+            .events()
+            .emit(|subscriber, id| subscriber.on_sandbox_destroyed(id));
     }
 }
 
@@ -76,12 +108,163 @@ impl MultiUseSandbox {
         host_funcs: Arc<Mutex<HostFuncsWrapper>>,
         mgr: MemMgrWrapper<HostSharedMemory>,
         hv_handler: HypervisorHandler,
+        registration: SandboxRegistration,
+        initialized: bool,
     ) -> MultiUseSandbox {
         Self {
-            _host_funcs: host_funcs,
+            host_funcs,
             mem_mgr: mgr,
             hv_handler,
+            registration,
+            result_cache: None,
+            call_stats: CallStats::default(),
+            initialized,
+        }
+    }
+
+    /// Run the guest's entrypoint now, if the sandbox was created with
+    /// `UninitializedSandbox::set_lazy_initialization` and no guest
+    /// function has been called yet, returning any error initialization
+    /// fails with. Does nothing if the guest is already initialized.
+    #[instrument(err(Debug), skip_all, parent = Span::current(), level = "Trace")]
+    pub fn ensure_initialized(&mut self) -> Result<()> {
+        if self.initialized {
+            return Ok(());
         }
+        initialise_guest(&mut self.mem_mgr, &mut self.hv_handler)?;
+        // guest calls start from the state the guest is in once initialized
+        self.mem_mgr.as_mut().push_state()?;
+        self.initialized = true;
+        Ok(())
+    }
+
+    /// Whether the guest's entrypoint has run. This is only `false` for
+    /// lazily initialized sandboxes that haven't been used yet.
+    #[instrument(skip_all, parent = Span::current(), level = "Trace")]
+    pub fn is_initialized(&self) -> bool {
+        self.initialized
+    }
+
+    /// Compress the guest's memory, and the snapshots the sandbox restores
+    /// it from, into host memory, and give the pages of guest memory back
+    /// to the OS. Guest memory is mostly untouched zeroes, so pools of
+    /// idle sandboxes take up a fraction of the memory they otherwise
+    /// would.
+    ///
+    /// The sandbox wakes up by itself on the next guest call, taking the
+    /// time to decompress its memory, or can be woken ahead of time with
+    /// `wake`. Hibernating a hibernating sandbox does nothing.
+    #[instrument(err(Debug), skip_all, parent = Span::current(), level = "Trace")]
+    pub fn hibernate(&mut self) -> Result<()> {
+        self.mem_mgr.as_mut().hibernate()
+    }
+
+    /// Put back the guest memory `hibernate` compressed. Does nothing if
+    /// the sandbox isn't hibernating.
+    #[instrument(err(Debug), skip_all, parent = Span::current(), level = "Trace")]
+    pub fn wake(&mut self) -> Result<()> {
+        self.mem_mgr.as_mut().wake()
+    }
+
+    /// Whether the sandbox is hibernating, see `hibernate`
+    #[instrument(skip_all, parent = Span::current(), level = "Trace")]
+    pub fn is_hibernating(&self) -> bool {
+        self.mem_mgr.as_ref().is_hibernating()
+    }
+
+    /// Get the sandbox ready for a guest call, waking it and initializing
+    /// the guest if need be
+    pub(crate) fn prepare_for_call(&mut self) -> Result<()> {
+        self.wake()?;
+        self.ensure_initialized()
+    }
+
+    /// Remember the results of the guest functions `policy` declares pure,
+    /// so that calling one of them through `call_guest_function_by_name`
+    /// with the same arguments and return type as an earlier successful
+    /// call returns the earlier result without entering the guest.
+    ///
+    /// Calls made through a `MultiUseGuestCallContext` are never cached, as
+    /// the guest's state carries over between them. The cache is emptied
+    /// whenever the sandbox is evolved or devolved, since that changes the
+    /// state guest functions start from.
+    #[instrument(skip_all, parent = Span::current(), level = "Trace")]
+    pub fn with_result_cache(mut self, policy: ResultCachePolicy) -> Self {
+        self.result_cache = Some(ResultCache::new(policy));
+        self
+    }
+
+    /// How many calls the result cache has answered, if there is one
+    #[instrument(skip_all, parent = Span::current(), level = "Trace")]
+    pub fn result_cache_stats(&self) -> Option<ResultCacheStats> {
+        self.result_cache.as_ref().map(ResultCache::stats)
+    }
+
+    /// Forget the results in the result cache, for example after changing
+    /// something a pure function depends on, such as a host function it
+    /// calls
+    #[instrument(skip_all, parent = Span::current(), level = "Trace")]
+    pub fn clear_result_cache(&mut self) {
+        if let Some(cache) = &mut self.result_cache {
+            cache.clear();
+        }
+    }
+
+    /// Register `func` as the host function `name`, which the guest can
+    /// call from its next guest function call on, for long-lived sandboxes
+    /// that gain capabilities after they have been initialized.
+    ///
+    /// As with host functions registered before the sandbox was evolved,
+    /// registering a function with the same name and parameter types as an
+    /// existing one replaces it, and the function stays registered when the
+    /// sandbox's memory is restored after a call.
+    ///
+    /// The host functions can't change while a guest call is in flight, so
+    /// the table of them needs no version for a call to check: this takes
+    /// `&mut self`, as every way of calling the guest does, so the borrow
+    /// checker keeps the two apart.
+    ///
+    /// The result cache, if there is one, is emptied, since pure functions
+    /// may call the function.
+    #[instrument(err(Debug), skip(self, func), parent = Span::current(), level = "Trace")]
+    pub fn register_host_function<F>(
+        &mut self,
+        name: &str,
+        parameter_types: Vec<ParameterType>,
+        return_type: ReturnType,
+        func: F,
+    ) -> Result<()>
+    where
+        F: FnMut(Vec<ParameterValue>) -> Result<ReturnValue> + Send + 'static,
+    {
+        // the definitions the guest reads are in guest memory
+        self.wake()?;
+        let hfd = HostFunctionDefinition::new(name.to_string(), Some(parameter_types), return_type);
+        self.host_funcs
+            .try_lock()
+            .map_err(|e| new_error!("Error locking at {}:{}: {}", file!(), line!(), e))?
+            .register_host_function_after_init(
+                self.mem_mgr.as_mut(),
+                &hfd,
+                HyperlightFunction::new(func),
+            )?;
+        self.clear_result_cache();
+        Ok(())
+    }
+
+    /// Remove every overload of the host function `name`, so that the
+    /// guest's calls to it fail with `HostFunctionNotFound`, or go to the
+    /// fallback host function if one was registered, from its next guest
+    /// function call on. See `register_host_function`.
+    #[instrument(err(Debug), skip(self), parent = Span::current(), level = "Trace")]
+    pub fn unregister_host_function(&mut self, name: &str) -> Result<()> {
+        self.wake()?;
+        self.host_funcs
+            .try_lock()
+            .map_err(|e| new_error!("Error locking at {}:{}: {}", file!(), line!(), e))?
+            .unregister_host_function(self.mem_mgr.as_mut(), name)?;
+        self.clear_result_cache();
+        Ok(())
     }
 
     /// Create a new `MultiUseCallContext` suitable for making 0 or more
@@ -156,21 +339,399 @@ impl MultiUseSandbox {
     }
 
     /// Call a guest function by name, with the given return type and arguments.
-    #[instrument(err(Debug), skip(self, args), parent = Span::current())]
+    #[instrument(err(Debug), skip(self, args), fields(sandbox_id = %self.id()), parent = Span::current())]
     pub fn call_guest_function_by_name(
         &mut self,
         func_name: &str,
         func_ret_type: ReturnType,
         args: Option<Vec<ParameterValue>>,
     ) -> Result<ReturnValue> {
-        let res = call_function_on_guest(self, func_name, func_ret_type, args)?;
+        self.call_guest_function(func_name, None, func_ret_type, args)
+    }
+
+    /// Resolve the guest function `func_name` to the id the guest assigned
+    /// it during initialisation, for `call_guest_function_by_id`. Calling
+    /// a function by id saves sending its name to the guest and looking it
+    /// up there on every call.
+    ///
+    /// Fails if the guest didn't publish an id for the function. Lazily
+    /// initialized sandboxes only know the ids once the guest is
+    /// initialized.
+    #[instrument(err(Debug), skip(self), parent = Span::current(), level = "Trace")]
+    pub fn guest_function_id(&self, func_name: &str) -> Result<GuestFunctionId> {
+        match self.mem_mgr.unwrap_mgr().guest_function_id(func_name) {
+            Some(id) => Ok(id),
+            None => {
+                log_then_return!("The guest published no id for function {}", func_name);
+            }
+        }
+    }
+
+    /// Call the guest function `function`, resolved with
+    /// `guest_function_id`, as `call_guest_function_by_name` calls it by
+    /// name.
+    #[instrument(err(Debug), skip(self, args), fields(sandbox_id = %self.id()), parent = Span::current())]
+    pub fn call_guest_function_by_id(
+        &mut self,
+        function: GuestFunctionId,
+        func_ret_type: ReturnType,
+        args: Option<Vec<ParameterValue>>,
+    ) -> Result<ReturnValue> {
+        let Some(func_name) = self.mem_mgr.unwrap_mgr().guest_function_name(function) else {
+            log_then_return!("The guest has no function with id {}", function.0);
+        };
+        self.call_guest_function(&func_name, Some(function), func_ret_type, args)
+    }
+
+    fn call_guest_function(
+        &mut self,
+        func_name: &str,
+        function_id: Option<GuestFunctionId>,
+        func_ret_type: ReturnType,
+        args: Option<Vec<ParameterValue>>,
+    ) -> Result<ReturnValue> {
+        let mut stats = self.call_stats;
+        let res = timed(&mut stats, || match self.result_cache.take() {
+            Some(mut cache) => {
+                let res = cache.get_or_call(func_name, func_ret_type, args, |args| {
+                    self.call_guest_function_uncached(func_name, function_id, func_ret_type, args)
+                });
+                self.result_cache = Some(cache);
+                res
+            }
+            None => self.call_guest_function_uncached(func_name, function_id, func_ret_type, args),
+        });
+        self.call_stats = stats;
+        res
+    }
+
+    /// Call a guest function by name, as `call_guest_function_by_name`
+    /// does, with `options` for this call alone.
+    ///
+    /// The host functions the guest calls can read the call's
+    /// `CallOptions::context` with `call_context`, and the progress the
+    /// guest reports is passed to `CallOptions::on_progress`. Calls given
+    /// either bypass the result cache, as what the host functions return
+    /// may depend on the context, and a cached call reports no progress.
+    #[instrument(err(Debug), skip(self, args, options), fields(sandbox_id = %self.id()), parent = Span::current())]
+    pub fn call_guest_function_with_options(
+        &mut self,
+        func_name: &str,
+        func_ret_type: ReturnType,
+        args: Option<Vec<ParameterValue>>,
+        options: CallOptions,
+    ) -> Result<ReturnValue> {
+        if options.is_empty() {
+            return self.call_guest_function_by_name(func_name, func_ret_type, args);
+        }
+        let on_progress = options.on_progress.clone();
+        self.host_funcs
+            .try_lock()
+            .map_err(|e| new_error!("Error locking at {}:{}: {}", file!(), line!(), e))?
+            .set_call_options(Some(options));
+        self.hv_handler.set_progress_callback(on_progress);
+        let mut stats = self.call_stats;
+        let res = timed(&mut stats, || {
+            self.call_guest_function_uncached(func_name, None, func_ret_type, args)
+        });
+        self.call_stats = stats;
+        // the options must not be seen by later calls, whatever happened to
+        // this one, and nothing else holds the lock once the call is over
+        self.host_funcs
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .set_call_options(None);
+        self.hv_handler.set_progress_callback(None);
+        res
+    }
+
+    /// The calls made through `call_guest_function_by_name`, including
+    /// ones the result cache answered
+    #[instrument(skip_all, parent = Span::current(), level = "Trace")]
+    pub fn call_stats(&self) -> CallStats {
+        self.call_stats
+    }
+
+    fn call_guest_function_uncached(
+        &mut self,
+        func_name: &str,
+        function_id: Option<GuestFunctionId>,
+        func_ret_type: ReturnType,
+        args: Option<Vec<ParameterValue>>,
+    ) -> Result<ReturnValue> {
+        self.prepare_for_call()?;
+        let res = call_function_on_guest(self, func_name, function_id, func_ret_type, args)?;
         self.restore_state()?;
         Ok(res)
     }
 
+    /// Returns `true` if the guest aborted part way through a call. Calls to
+    /// a poisoned sandbox fail with `HyperlightError::PoisonedSandbox` until
+    /// `clear_poison` is called.
+    #[instrument(skip_all, parent = Span::current(), level = "Trace")]
+    pub fn is_poisoned(&self) -> bool {
+        self.mem_mgr.as_ref().is_poisoned()
+    }
+
+    /// Restore the sandbox to the state it was in before the call in which
+    /// the guest aborted, so that it can be called again.
+    #[instrument(err(Debug), skip_all, parent = Span::current(), level = "Trace")]
+    pub fn clear_poison(&mut self) -> Result<()> {
+        self.restore_state()
+    }
+
+    /// Take the coverage the guest collected since coverage was last
+    /// taken. The guest must be an ELF binary built with SanitizerCoverage
+    /// inline 8-bit counters, see `docs/how-to-build-a-hyperlight-guest-binary.md`.
+    #[instrument(err(Debug), skip_all, parent = Span::current(), level = "Trace")]
+    pub fn take_coverage(&mut self) -> Result<CoverageMap> {
+        self.wake()?;
+        match self.mem_mgr.unwrap_mgr_mut().take_coverage()? {
+            Some(counters) => Ok(CoverageMap::new(counters)),
+            None => {
+                log_then_return!("The guest binary was not built with coverage counters");
+            }
+        }
+    }
+
+    /// Read `len` bytes of guest memory starting at the guest address
+    /// `addr`.
+    ///
+    /// Fails with `HyperlightError::BoundsCheckFailed` if the range isn't
+    /// entirely within guest memory, and fails if the sandbox is
+    /// hibernating, see `hibernate`.
+    #[cfg(feature = "unsafe_memory_access")]
+    #[instrument(err(Debug), skip_all, parent = Span::current(), level = "Trace")]
+    pub fn read_guest_memory(&self, addr: u64, len: usize) -> Result<Vec<u8>> {
+        if self.is_hibernating() {
+            log_then_return!("Cannot read the memory of a hibernating sandbox, wake it first");
+        }
+        self.mem_mgr.unwrap_mgr().read_guest_memory(addr, len)
+    }
+
+    /// Write `data` to guest memory starting at the guest address `addr`.
+    ///
+    /// Fails if the range isn't entirely within guest memory, or if it
+    /// overlaps the guest's page tables, which the host never modifies
+    /// once the sandbox is created. As with any other change to guest
+    /// memory, the write is undone when the sandbox is restored after the
+    /// next guest function call.
+    ///
+    /// Although this can't corrupt host memory, the guest trusts its
+    /// memory to only change when it changes it, and writing to memory
+    /// the guest is using can make it misbehave in arbitrary ways.
+    #[cfg(feature = "unsafe_memory_access")]
+    #[instrument(err(Debug), skip_all, parent = Span::current(), level = "Trace")]
+    pub fn write_guest_memory(&mut self, addr: u64, data: &[u8]) -> Result<()> {
+        self.wake()?;
+        self.mem_mgr.unwrap_mgr_mut().write_guest_memory(addr, data)
+    }
+
+    /// The `GuestInfo` of the guest binary, carried over from the
+    /// `UninitializedSandbox` this sandbox was evolved from
+    #[instrument(skip_all, parent = Span::current(), level = "Trace")]
+    pub fn guest_info(&self) -> &GuestInfo {
+        self.hv_handler.guest_info()
+    }
+
+    /// The id of this sandbox, which is the id of the
+    /// `UninitializedSandbox` it was evolved from.
+    #[instrument(skip_all, parent = Span::current(), level = "Trace")]
+    pub fn id(&self) -> SandboxId {
+        self.registration.id()
+    }
+
+    /// The name the `UninitializedSandbox` this sandbox was evolved from
+    /// was given, if any.
+    #[instrument(skip_all, parent = Span::current(), level = "Trace")]
+    pub fn name(&self) -> Option<String> {
+        self.registration.name()
+    }
+
+    /// Add a subscriber to be told about the events in this sandbox's life
+    /// from now on.
+    #[instrument(skip_all, parent = Span::current(), level = "Trace")]
+    pub fn add_event_subscriber(&mut self, subscriber: Arc<dyn EventSubscriber>) {
+        self.hv_handler.events().add(subscriber);
+    }
+
+    /// Turn recording a `CallProfile` for each guest function call on or
+    /// off. Profiling is off by default, since it adds to the cost of every
+    /// exit from the vCPU.
+    #[instrument(skip(self), parent = Span::current(), level = "Trace")]
+    pub fn set_call_profiling(&mut self, enabled: bool) {
+        self.hv_handler.events().profiler().set_enabled(enabled);
+    }
+
+    /// Turn isolation auditing on or off. While it is on, the page tables,
+    /// the stack's guard page and cookie and the regions of memory the
+    /// guest should only read from are checked after every guest function
+    /// call, and anything the call changed in them is reported to the
+    /// `EventSubscriber`s' `on_isolation_violation` and logged to the
+    /// `hyperlight_host::audit` target. The call itself still returns as
+    /// normal. Auditing is off by default, since it copies those regions
+    /// on every call.
+    #[instrument(skip(self), parent = Span::current(), level = "Trace")]
+    pub fn set_isolation_audit(&mut self, enabled: bool) {
+        self.mem_mgr.unwrap_mgr_mut().set_isolation_audit(enabled);
+    }
+
+    /// The profile of the last guest function call made while call
+    /// profiling was on, if there has been one.
+    #[instrument(skip_all, parent = Span::current(), level = "Trace")]
+    pub fn last_call_profile(&self) -> Option<CallProfile> {
+        self.hv_handler.events().profiler().last_profile()
+    }
+
+    /// Start interrupting the guest every `interval` while it runs to
+    /// record where it is, with up to `stack_depth` return addresses found
+    /// by following the guest's frame pointers, for `take_profile` to
+    /// return. Starting the profiler again replaces the samples recorded so
+    /// far.
+    ///
+    /// The profiler is only supported on Linux, where it interrupts the
+    /// vCPU with the same signal used to cancel guest execution, so host
+    /// functions may see system calls fail with `EINTR` while it runs.
+    #[instrument(err(Debug), skip(self), parent = Span::current(), level = "Trace")]
+    pub fn start_sampling_profiler(
+        &mut self,
+        interval: Duration,
+        stack_depth: usize,
+    ) -> Result<()> {
+        self.hv_handler.start_sampling(interval, stack_depth)
+    }
+
+    /// Stop the sampling profiler, returning the samples recorded since the
+    /// profile was last taken, or `None` if it wasn't running.
+    #[instrument(err(Debug), skip_all, parent = Span::current(), level = "Trace")]
+    pub fn stop_sampling_profiler(&mut self) -> Result<Option<GuestProfile>> {
+        self.hv_handler.stop_sampling()
+    }
+
+    /// Take the samples the sampling profiler has recorded since the
+    /// profile was last taken. `GuestProfile::to_folded_stacks` turns them
+    /// into input for a flamegraph, with the guest's `GuestSymbols`.
+    #[instrument(err(Debug), skip_all, parent = Span::current(), level = "Trace")]
+    pub fn take_profile(&mut self) -> Result<GuestProfile> {
+        match self.hv_handler.sampler() {
+            Some(sampler) => Ok(sampler.take_profile()),
+            None => {
+                log_then_return!("The sampling profiler is not running");
+            }
+        }
+    }
+
+    /// The names of the functions the guest registered.
+    ///
+    /// The functions recorded in the guest binary by
+    /// `register_guest_function!` come first, in the order they are in the
+    /// binary, and are known without running the guest, see
+    /// `GuestBinary::inspect`. They are followed by the other functions the
+    /// guest published to the host during initialisation, in the order it
+    /// registered them, which lazily initialized sandboxes only know once
+    /// the guest is initialized.
+    #[instrument(skip_all, parent = Span::current(), level = "Trace")]
+    pub fn guest_function_names(&self) -> Vec<String> {
+        let mut names: Vec<String> = self
+            .guest_info()
+            .functions
+            .iter()
+            .flatten()
+            .map(|function| function.name.clone())
+            .collect();
+        for name in self.mem_mgr.unwrap_mgr().guest_function_names() {
+            if !names.contains(&name) {
+                names.push(name);
+            }
+        }
+        names
+    }
+
+    /// Get the regions of guest memory, in order of guest address, with
+    /// the host addresses they are mapped from and the permissions the
+    /// guest has on them.
+    #[instrument(err(Debug), skip_all, parent = Span::current(), level = "Trace")]
+    pub fn memory_layout(&self) -> Result<Vec<MemoryRegion>> {
+        let mgr = self.mem_mgr.unwrap_mgr();
+        mgr.layout.get_memory_regions(&mgr.shared_mem)
+    }
+
+    /// Set the debug registers of the sandbox's vCPU, initializing the
+    /// guest first if it is lazily initialized. A guest call that hits a
+    /// breakpoint or watchpoint they enable fails with
+    /// `HyperlightError::GuestDebugTrap`, and they stay set until they
+    /// are set again, for example to `DebugRegisters::default()`.
+    ///
+    /// Only KVM supports this, the other hypervisors return an error.
+    #[cfg(target_arch = "x86_64")]
+    #[instrument(err(Debug), skip_all, parent = Span::current(), level = "Trace")]
+    pub fn set_debug_registers(&mut self, regs: DebugRegisters) -> Result<()> {
+        self.prepare_for_call()?;
+        self.hv_handler
+            .execute_hypervisor_handler_action(HypervisorHandlerAction::SetDebugRegisters(regs))
+    }
+
+    /// Get the debug registers of the sandbox's vCPU, initializing the
+    /// guest first if it is lazily initialized.
+    ///
+    /// Only KVM supports this, the other hypervisors return an error.
+    #[cfg(target_arch = "x86_64")]
+    #[instrument(err(Debug), skip_all, parent = Span::current(), level = "Trace")]
+    pub fn debug_registers(&mut self) -> Result<DebugRegisters> {
+        self.prepare_for_call()?;
+        let (regs_tx, regs_rx) = crossbeam_channel::bounded(1);
+        self.hv_handler.execute_hypervisor_handler_action(
+            HypervisorHandlerAction::GetDebugRegisters(regs_tx),
+        )?;
+        regs_rx
+            .try_recv()
+            .map_err(|_| HyperlightError::HypervisorHandlerCommunicationFailure())
+    }
+
+    /// Tear the sandbox down, first calling the guest's
+    /// `hyperlight_on_shutdown` function, if it registered one, so that it
+    /// can flush its state through host function calls. The call is
+    /// cancelled if it is still running after `timeout`.
+    ///
+    /// The sandbox is torn down whether or not the call succeeds, and the
+    /// error it failed with, if any, is returned. A lazily initialized
+    /// sandbox whose guest was never initialized has nothing to flush, so
+    /// no function is called.
+    #[instrument(err(Debug), skip(self), fields(sandbox_id = %self.id()), parent = Span::current())]
+    pub fn shutdown(mut self, timeout: Duration) -> Result<()> {
+        if !self.initialized
+            || !self
+                .guest_function_names()
+                .iter()
+                .any(|name| name == ON_SHUTDOWN_FUNCTION)
+        {
+            return Ok(());
+        }
+        self.wake()?;
+        // the guest sees the shutdown's deadline as the call's, see
+        // `hyperlight_guest::time::remaining`
+        self.hv_handler.set_max_exec_time(timeout);
+        let function_id = self
+            .mem_mgr
+            .unwrap_mgr()
+            .guest_function_id(ON_SHUTDOWN_FUNCTION);
+        call_function_on_guest(
+            &mut self,
+            ON_SHUTDOWN_FUNCTION,
+            function_id,
+            ReturnType::Void,
+            None,
+        )?;
+        Ok(())
+    }
+
     /// Restore the Sandbox's state
     #[instrument(err(Debug), skip_all, parent = Span::current(), level = "Trace")]
     pub(crate) fn restore_state(&mut self) -> Result<()> {
+        // there is no state to restore before the guest is initialized
+        if !self.initialized {
+            return Ok(());
+        }
         let mem_mgr = self.mem_mgr.unwrap_mgr_mut();
         mem_mgr.restore_state_from_last_snapshot()
     }
@@ -195,11 +756,28 @@ impl Sandbox for MultiUseSandbox {
     fn check_stack_guard(&self) -> Result<bool> {
         self.mem_mgr.check_stack_guard()
     }
+
+    fn vcpu_stats(&self) -> Result<Option<VcpuStats>> {
+        self.hv_handler.vcpu_stats()
+    }
+
+    #[cfg(all(feature = "cgroup", target_os = "linux"))]
+    #[instrument(err(Debug), skip_all, parent = Span::current(), level = "Trace")]
+    fn cgroup_usage(&self) -> Result<Option<CgroupUsage>> {
+        self.hv_handler.cgroup_usage()
+    }
+
+    #[instrument(err(Debug), skip_all, parent = Span::current(), level = "Trace")]
+    fn creation_report(&self) -> Result<Option<CreationReport>> {
+        self.hv_handler.creation_report().map(Some)
+    }
 }
 
 impl std::fmt::Debug for MultiUseSandbox {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         f.debug_struct("MultiUseSandbox")
+            .field("id", &self.id())
+            .field("name", &self.name())
             .field("stack_guard", &self.mem_mgr.get_stack_cookie())
             .finish()
     }
@@ -217,9 +795,17 @@ impl DevolvableSandbox<MultiUseSandbox, MultiUseSandbox, Noop<MultiUseSandbox, M
     /// The devolve can be used to return the MultiUseSandbox to the state before the code was loaded. Thus avoiding initialisation overhead
     #[instrument(err(Debug), skip_all, parent = Span::current(), level = "Trace")]
     fn devolve(mut self, _tsn: Noop<MultiUseSandbox, MultiUseSandbox>) -> Result<MultiUseSandbox> {
+        // a guest that was never initialized has no snapshots, nor a vCPU
+        // whose FPU state could have been saved
+        if !self.initialized {
+            return Ok(self);
+        }
         self.mem_mgr
             .unwrap_mgr_mut()
             .pop_and_restore_state_from_snapshot()?;
+        self.hv_handler
+            .execute_hypervisor_handler_action(HypervisorHandlerAction::DiscardFpuState)?;
+        self.clear_result_cache();
         Ok(self)
     }
 }
@@ -241,16 +827,20 @@ where
     /// The evolve function creates a new MultiUseCallContext which is then passed to a callback function  allowing the
     /// callback function to call guest functions as part of the evolve process, once the callback function  is complete
     /// the context is finished using a crate internal method that does not restore the prior state of the Sanbbox.
-    /// It then creates a mew  memory snapshot on the snapshot stack and returns the MultiUseSandbox
+    /// It then creates a mew  memory snapshot on the snapshot stack, saving the vCPU's FPU state along with it, and returns the MultiUseSandbox
     #[instrument(err(Debug), skip_all, parent = Span::current(), level = "Trace")]
     fn evolve(
-        self,
+        mut self,
         transition_func: MultiUseContextCallback<'a, MultiUseSandbox, F>,
     ) -> Result<MultiUseSandbox> {
+        self.prepare_for_call()?;
         let mut ctx = self.new_call_context();
         transition_func.call(&mut ctx)?;
         let mut sbox = ctx.finish_no_reset();
         sbox.mem_mgr.unwrap_mgr_mut().push_state()?;
+        sbox.hv_handler
+            .execute_hypervisor_handler_action(HypervisorHandlerAction::SaveFpuState)?;
+        sbox.clear_result_cache();
         Ok(sbox)
     }
 }
@@ -260,11 +850,12 @@ mod tests {
     use hyperlight_common::flatbuffer_wrappers::function_types::{
         ParameterValue, ReturnType, ReturnValue,
     };
-    use hyperlight_testing::simple_guest_as_string;
+    use hyperlight_testing::{callback_guest_as_string, simple_guest_as_string};
 
     use crate::func::call_ctx::MultiUseGuestCallContext;
+    use crate::func::GuestFunctionId;
     use crate::sandbox::SandboxConfiguration;
-    use crate::sandbox_state::sandbox::{DevolvableSandbox, EvolvableSandbox};
+    use crate::sandbox_state::sandbox::{DevolvableSandbox, EvolvableSandbox, Sandbox};
     use crate::sandbox_state::transition::{MultiUseContextCallback, Noop};
     use crate::{GuestBinary, MultiUseSandbox, UninitializedSandbox};
 
@@ -351,4 +942,480 @@ mod tests {
             .unwrap();
         assert_eq!(res, ReturnValue::Int(0));
     }
+
+    #[test]
+    fn hibernate_and_wake() {
+        let sbox1: MultiUseSandbox = {
+            let path = simple_guest_as_string().unwrap();
+            let u_sbox =
+                UninitializedSandbox::new(GuestBinary::FilePath(path), None, None, None).unwrap();
+            u_sbox.evolve(Noop::default())
+        }
+        .unwrap();
+        let func = Box::new(|call_ctx: &mut MultiUseGuestCallContext| {
+            call_ctx.call(
+                "AddToStatic",
+                ReturnType::Int,
+                Some(vec![ParameterValue::Int(5)]),
+            )?;
+            Ok(())
+        });
+        let mut sbox2 = sbox1.evolve(MultiUseContextCallback::from(func)).unwrap();
+
+        sbox2.hibernate().unwrap();
+        assert!(sbox2.is_hibernating());
+        let res = sbox2
+            .call_guest_function_by_name("GetStatic", ReturnType::Int, None)
+            .unwrap();
+        assert_eq!(res, ReturnValue::Int(5));
+        assert!(!sbox2.is_hibernating());
+
+        // the older snapshot is compressed too and still restores
+        sbox2.hibernate().unwrap();
+        let mut sbox3: MultiUseSandbox = sbox2.devolve(Noop::default()).unwrap();
+        assert!(!sbox3.is_hibernating());
+        let res = sbox3
+            .call_guest_function_by_name("GetStatic", ReturnType::Int, None)
+            .unwrap();
+        assert_eq!(res, ReturnValue::Int(0));
+    }
+
+    #[test]
+    #[cfg(feature = "unsafe_memory_access")]
+    fn read_and_write_guest_memory() {
+        use crate::mem::layout::SandboxMemoryLayout;
+        use crate::mem::shared_mem::SharedMemory;
+        use crate::HyperlightError;
+
+        let mut sbox: MultiUseSandbox = {
+            let path = simple_guest_as_string().unwrap();
+            let u_sbox =
+                UninitializedSandbox::new(GuestBinary::FilePath(path), None, None, None).unwrap();
+            u_sbox.evolve(Noop::default())
+        }
+        .unwrap();
+
+        let base = SandboxMemoryLayout::BASE_ADDRESS as u64;
+        let end = base + sbox.mem_mgr.unwrap_mgr().shared_mem.mem_size() as u64;
+        let data = [0xde, 0xad, 0xbe, 0xef];
+
+        sbox.write_guest_memory(end - 4, &data).unwrap();
+        assert_eq!(sbox.read_guest_memory(end - 4, 4).unwrap(), data);
+
+        assert!(matches!(
+            sbox.read_guest_memory(end - 4, 5),
+            Err(HyperlightError::BoundsCheckFailed(..))
+        ));
+        assert!(matches!(
+            sbox.read_guest_memory(base, usize::MAX),
+            Err(HyperlightError::BoundsCheckFailed(..))
+        ));
+        assert!(sbox.read_guest_memory(base - 1, 1).is_err());
+        assert!(sbox.write_guest_memory(end - 2, &data).is_err());
+        // the page tables are at the start of guest memory
+        assert!(sbox.write_guest_memory(base, &data).is_err());
+        assert!(sbox.read_guest_memory(base, 4).is_ok());
+    }
+
+    #[test]
+    fn fpu_state_is_snapshotted() {
+        // the default MXCSR, with all exceptions masked
+        const DEFAULT: i32 = 0x1f80;
+        // round towards zero
+        const EVOLVED: i32 = DEFAULT | 0x6000;
+
+        let sbox1: MultiUseSandbox = {
+            let path = simple_guest_as_string().unwrap();
+            let u_sbox =
+                UninitializedSandbox::new(GuestBinary::FilePath(path), None, None, None).unwrap();
+            u_sbox.evolve(Noop::default())
+        }
+        .unwrap();
+
+        let func = Box::new(|call_ctx: &mut MultiUseGuestCallContext| {
+            call_ctx.call(
+                "SetMxcsr",
+                ReturnType::Void,
+                Some(vec![ParameterValue::Int(EVOLVED)]),
+            )?;
+            Ok(())
+        });
+        let mut sbox2 = sbox1.evolve(MultiUseContextCallback::from(func)).unwrap();
+        let res = sbox2
+            .call_guest_function_by_name("GetMxcsr", ReturnType::Int, None)
+            .unwrap();
+        assert_eq!(res, ReturnValue::Int(EVOLVED));
+
+        // a call's changes don't carry over to the next call
+        sbox2
+            .call_guest_function_by_name(
+                "SetMxcsr",
+                ReturnType::Void,
+                Some(vec![ParameterValue::Int(DEFAULT)]),
+            )
+            .unwrap();
+        let res = sbox2
+            .call_guest_function_by_name("GetMxcsr", ReturnType::Int, None)
+            .unwrap();
+        assert_eq!(res, ReturnValue::Int(EVOLVED));
+
+        let mut sbox3: MultiUseSandbox = sbox2.devolve(Noop::default()).unwrap();
+        let res = sbox3
+            .call_guest_function_by_name("GetMxcsr", ReturnType::Int, None)
+            .unwrap();
+        assert_eq!(res, ReturnValue::Int(DEFAULT));
+    }
+
+    #[test]
+    fn memory_layout() {
+        use crate::mem::layout::SandboxMemoryLayout;
+        use crate::mem::memory_region::{MemoryRegionFlags, MemoryRegionType};
+        use crate::mem::shared_mem::SharedMemory;
+
+        let sbox: MultiUseSandbox = {
+            let path = simple_guest_as_string().unwrap();
+            let u_sbox =
+                UninitializedSandbox::new(GuestBinary::FilePath(path), None, None, None).unwrap();
+            u_sbox.evolve(Noop::default())
+        }
+        .unwrap();
+
+        let regions = sbox.memory_layout().unwrap();
+        let mem_size = sbox.mem_mgr.unwrap_mgr().shared_mem.mem_size();
+        assert_eq!(regions[0].region_type(), MemoryRegionType::PageTables);
+        assert_eq!(
+            regions[0].guest_region().start,
+            SandboxMemoryLayout::BASE_ADDRESS
+        );
+        assert_eq!(
+            regions.last().unwrap().guest_region().end,
+            SandboxMemoryLayout::BASE_ADDRESS + mem_size
+        );
+        for pair in regions.windows(2) {
+            assert_eq!(pair[0].guest_region().end, pair[1].guest_region().start);
+            assert_eq!(pair[0].host_region().end, pair[1].host_region().start);
+        }
+
+        let code = regions
+            .iter()
+            .find(|region| region.region_type() == MemoryRegionType::Code)
+            .unwrap();
+        assert!(code.flags().contains(MemoryRegionFlags::EXECUTE));
+        for region_type in [
+            MemoryRegionType::Peb,
+            MemoryRegionType::InputData,
+            MemoryRegionType::OutputData,
+            MemoryRegionType::Heap,
+            MemoryRegionType::GuardPage,
+            MemoryRegionType::Stack,
+        ] {
+            assert!(regions
+                .iter()
+                .any(|region| region.region_type() == region_type));
+        }
+    }
+
+    #[test]
+    fn guest_function_names() {
+        let sbox: MultiUseSandbox = {
+            let path = simple_guest_as_string().unwrap();
+            let u_sbox =
+                UninitializedSandbox::new(GuestBinary::FilePath(path), None, None, None).unwrap();
+            u_sbox.evolve(Noop::default())
+        }
+        .unwrap();
+
+        let names = sbox.guest_function_names();
+        assert!(names.iter().any(|name| name == "PrintOutput"));
+        assert!(names.iter().any(|name| name == "Echo"));
+
+        // callbackguest records its functions in its binary, so they are
+        // known before it is initialized
+        let mut u_sbox = UninitializedSandbox::new(
+            GuestBinary::FilePath(callback_guest_as_string().unwrap()),
+            None,
+            None,
+            None,
+        )
+        .unwrap();
+        u_sbox.set_lazy_initialization(true);
+        let sbox: MultiUseSandbox = u_sbox.evolve(Noop::default()).unwrap();
+        assert!(!sbox.is_initialized());
+        assert!(sbox
+            .guest_function_names()
+            .iter()
+            .any(|name| name == "LogMessage"));
+    }
+
+    #[test]
+    fn call_guest_function_by_id() {
+        let mut sbox: MultiUseSandbox = {
+            let path = simple_guest_as_string().unwrap();
+            let u_sbox =
+                UninitializedSandbox::new(GuestBinary::FilePath(path), None, None, None).unwrap();
+            u_sbox.evolve(Noop::default())
+        }
+        .unwrap();
+
+        let echo = sbox.guest_function_id("Echo").unwrap();
+        for message in ["hello", "world"] {
+            let res = sbox
+                .call_guest_function_by_id(
+                    echo,
+                    ReturnType::String,
+                    Some(vec![ParameterValue::String(message.to_string())]),
+                )
+                .unwrap();
+            assert_eq!(res, ReturnValue::String(message.to_string()));
+        }
+
+        assert!(sbox.guest_function_id("NoSuchFunction").is_err());
+        let res = sbox.call_guest_function_by_id(GuestFunctionId(u32::MAX), ReturnType::Int, None);
+        assert!(res.is_err());
+    }
+
+    #[test]
+    fn event_subscribers() {
+        use std::sync::{Arc, Mutex};
+        use std::time::Duration;
+
+        use crate::sandbox::{EventSubscriber, SandboxId};
+        use crate::HyperlightError;
+
+        #[derive(Default)]
+        struct Recorder(Mutex<Vec<String>>);
+
+        impl Recorder {
+            fn record(&self, event: String) {
+                self.0.lock().unwrap().push(event);
+            }
+        }
+
+        impl EventSubscriber for Recorder {
+            fn on_sandbox_created(&self, sandbox_id: SandboxId) {
+                self.record(format!("created {}", sandbox_id));
+            }
+            fn on_guest_call_started(&self, _: SandboxId, function_name: &str) {
+                self.record(format!("started {}", function_name));
+            }
+            fn on_guest_call_finished(&self, _: SandboxId, function_name: &str, _: Duration) {
+                self.record(format!("finished {}", function_name));
+            }
+            fn on_host_function_invoked(&self, _: SandboxId, function_name: &str) {
+                self.record(format!("host {}", function_name));
+            }
+            fn on_guest_error(&self, _: SandboxId, function_name: &str, _: &HyperlightError) {
+                self.record(format!("error {}", function_name));
+            }
+            fn on_sandbox_destroyed(&self, sandbox_id: SandboxId) {
+                self.record(format!("destroyed {}", sandbox_id));
+            }
+        }
+
+        let recorder = Arc::new(Recorder::default());
+        let path = simple_guest_as_string().unwrap();
+        let mut u_sbox =
+            UninitializedSandbox::new(GuestBinary::FilePath(path), None, None, None).unwrap();
+        u_sbox.add_event_subscriber(recorder.clone());
+        let id = u_sbox.id();
+
+        let mut sbox: MultiUseSandbox = u_sbox.evolve(Noop::default()).unwrap();
+        sbox.call_guest_function_by_name(
+            "PrintOutput",
+            ReturnType::Int,
+            Some(vec![ParameterValue::String("hello".to_string())]),
+        )
+        .unwrap();
+        assert!(sbox
+            .call_guest_function_by_name("NoSuchFunction", ReturnType::Int, None)
+            .is_err());
+        drop(sbox);
+
+        assert_eq!(
+            *recorder.0.lock().unwrap(),
+            vec![
+                format!("created {}", id),
+                "started PrintOutput".to_string(),
+                "host HostPrint".to_string(),
+                "finished PrintOutput".to_string(),
+                "started NoSuchFunction".to_string(),
+                "error NoSuchFunction".to_string(),
+                "finished NoSuchFunction".to_string(),
+                format!("destroyed {}", id),
+            ]
+        );
+    }
+
+    #[test]
+    fn call_profiling() {
+        let mut sbox: MultiUseSandbox = {
+            let path = simple_guest_as_string().unwrap();
+            let u_sbox =
+                UninitializedSandbox::new(GuestBinary::FilePath(path), None, None, None).unwrap();
+            u_sbox.evolve(Noop::default())
+        }
+        .unwrap();
+        let print = |sbox: &mut MultiUseSandbox| {
+            sbox.call_guest_function_by_name(
+                "PrintOutput",
+                ReturnType::Int,
+                Some(vec![ParameterValue::String("hello".to_string())]),
+            )
+            .unwrap();
+        };
+
+        print(&mut sbox);
+        assert_eq!(sbox.last_call_profile(), None);
+
+        sbox.set_call_profiling(true);
+        print(&mut sbox);
+        let profile = sbox.last_call_profile().unwrap();
+        assert_eq!(profile.function_name, "PrintOutput");
+        assert!(profile.guest.count > 0);
+        assert!(profile.exits["io"].count > 0);
+        assert_eq!(profile.exits["halt"].count, 1);
+        assert_eq!(profile.host_functions["HostPrint"].count, 1);
+        // the call is written in and its result read out, and the same for
+        // the call to HostPrint
+        assert_eq!(profile.serialization.count, 2);
+        assert_eq!(profile.host_function_serialization.count, 2);
+        assert!(profile.duration >= profile.guest.duration);
+        let breakdown = profile.breakdown();
+        assert_eq!(
+            breakdown.guest + breakdown.host_functions + breakdown.serialization + breakdown.other,
+            profile.duration
+        );
+        assert!(profile
+            .to_folded_stacks()
+            .contains("guest_call:PrintOutput;exit:io;host_function:HostPrint "));
+
+        sbox.set_call_profiling(false);
+        sbox.call_guest_function_by_name("GetStatic", ReturnType::Int, None)
+            .unwrap();
+        assert_eq!(
+            sbox.last_call_profile().unwrap().function_name,
+            "PrintOutput"
+        );
+    }
+
+    #[test]
+    fn isolation_audit() {
+        use std::sync::{Arc, Mutex};
+
+        use crate::sandbox::{EventSubscriber, IsolationViolation, SandboxId};
+
+        #[derive(Default)]
+        struct Violations(Mutex<Vec<IsolationViolation>>);
+
+        impl EventSubscriber for Violations {
+            fn on_isolation_violation(
+                &self,
+                _: SandboxId,
+                _: &str,
+                violation: &IsolationViolation,
+            ) {
+                self.0.lock().unwrap().push(violation.clone());
+            }
+        }
+
+        let mut sbox: MultiUseSandbox = {
+            let path = simple_guest_as_string().unwrap();
+            let u_sbox =
+                UninitializedSandbox::new(GuestBinary::FilePath(path), None, None, None).unwrap();
+            u_sbox.evolve(Noop::default())
+        }
+        .unwrap();
+        let violations = Arc::new(Violations::default());
+        sbox.add_event_subscriber(violations.clone());
+        sbox.set_isolation_audit(true);
+
+        // guests that stay within their own memory leave nothing behind
+        for _ in 0..2 {
+            let res = sbox
+                .call_guest_function_by_name(
+                    "Echo",
+                    ReturnType::String,
+                    Some(vec![ParameterValue::String("hello".to_string())]),
+                )
+                .unwrap();
+            assert_eq!(res, ReturnValue::String("hello".to_string()));
+        }
+        sbox.call_guest_function_by_name("GetStatic", ReturnType::Int, None)
+            .unwrap();
+        assert!(violations.0.lock().unwrap().is_empty());
+    }
+
+    #[cfg(all(kvm, target_arch = "x86_64"))]
+    #[test]
+    fn debug_registers() {
+        use crate::hypervisor::DebugRegisters;
+        use crate::mem::memory_region::MemoryRegionType;
+        use crate::sandbox::hypervisor::{get_available_hypervisor, HypervisorType};
+        use crate::HyperlightError;
+
+        if !matches!(get_available_hypervisor(), Some(HypervisorType::Kvm)) {
+            return;
+        }
+        let mut sbox: MultiUseSandbox = {
+            let path = simple_guest_as_string().unwrap();
+            let u_sbox =
+                UninitializedSandbox::new(GuestBinary::FilePath(path), None, None, None).unwrap();
+            u_sbox.evolve(Noop::default())
+        }
+        .unwrap();
+
+        // watch the size of the input buffer, which the guest reads on
+        // every call
+        let input = sbox
+            .memory_layout()
+            .unwrap()
+            .into_iter()
+            .find(|region| region.region_type() == MemoryRegionType::InputData)
+            .unwrap();
+        let regs = DebugRegisters {
+            addresses: [input.guest_region().start as u64, 0, 0, 0],
+            // enable DR0 as an 8 byte read/write watchpoint
+            dr7: 0xb0001,
+            ..Default::default()
+        };
+        sbox.set_debug_registers(regs).unwrap();
+        assert_eq!(sbox.debug_registers().unwrap().addresses, regs.addresses);
+        let res = sbox.call_guest_function_by_name("GetStatic", ReturnType::Int, None);
+        assert!(
+            matches!(res, Err(HyperlightError::GuestDebugTrap(..))),
+            "{:?}",
+            res
+        );
+
+        sbox.set_debug_registers(DebugRegisters::default()).unwrap();
+        let res = sbox
+            .call_guest_function_by_name("GetStatic", ReturnType::Int, None)
+            .unwrap();
+        assert_eq!(res, ReturnValue::Int(0));
+    }
+
+    #[test]
+    fn vcpu_stats() {
+        let mut sbox: MultiUseSandbox = {
+            let path = simple_guest_as_string().unwrap();
+            let u_sbox =
+                UninitializedSandbox::new(GuestBinary::FilePath(path), None, None, None).unwrap();
+            u_sbox.evolve(Noop::default())
+        }
+        .unwrap();
+
+        // only KVM on recent kernels provides stats
+        let Some(before) = sbox.vcpu_stats().unwrap() else {
+            return;
+        };
+        sbox.call_guest_function_by_name(
+            "PrintOutput",
+            ReturnType::Int,
+            Some(vec![ParameterValue::String("hello".to_string())]),
+        )
+        .unwrap();
+        let after = sbox.vcpu_stats().unwrap().unwrap();
+        assert!(after.get("exits").unwrap() > before.get("exits").unwrap());
+        assert!(after.get("io_exits").unwrap() > before.get("io_exits").unwrap());
+    }
 }
@@ -14,14 +14,21 @@ See the License for the specific language governing permissions and
 limitations under the License.
 */
 
+use std::sync::Arc;
+
 use hyperlight_common::flatbuffer_wrappers::function_types::{
     ParameterValue, ReturnType, ReturnValue,
 };
 use tracing::{instrument, Span};
 
-use super::{MemMgrWrapper, WrapperGetter};
+#[cfg(all(feature = "cgroup", target_os = "linux"))]
+use super::cgroup::CgroupUsage;
+use super::registry::SandboxRegistration;
+use super::uninitialized_evolve::initialise_guest;
+use super::{CreationReport, EventSubscriber, GuestInfo, MemMgrWrapper, SandboxId, WrapperGetter};
 use crate::func::call_ctx::SingleUseGuestCallContext;
 use crate::hypervisor::hypervisor_handler::HypervisorHandler;
+use crate::hypervisor::VcpuStats;
 use crate::mem::shared_mem::HostSharedMemory;
 use crate::sandbox_state::sandbox::Sandbox;
 use crate::Result;
@@ -31,6 +38,10 @@ use crate::Result;
 pub struct SingleUseSandbox {
     pub(super) mem_mgr: MemMgrWrapper<HostSharedMemory>,
     hv_handler: HypervisorHandler,
+    registration: SandboxRegistration,
+    /// Whether the guest's entrypoint has run, see
+    /// `UninitializedSandbox::set_lazy_initialization`
+    initialized: bool,
 }
 
 // We need to implement drop to join the
@@ -49,6 +60,9 @@ impl Drop for SingleUseSandbox {
                 log::error!("[POTENTIAL THREAD LEAK] Potentially failed to kill hypervisor handler thread when dropping MultiUseSandbox: {:?}", e);
             }
         }
+        self.hv_handler
+            .events()
+            .emit(|subscriber, id| subscriber.on_sandbox_destroyed(id));
     }
 }
 
@@ -68,11 +82,28 @@ impl SingleUseSandbox {
     pub(super) fn from_uninit(
         mgr: MemMgrWrapper<HostSharedMemory>,
         hv_handler: HypervisorHandler,
+        registration: SandboxRegistration,
+        initialized: bool,
     ) -> SingleUseSandbox {
         Self {
             mem_mgr: mgr,
             hv_handler,
+            registration,
+            initialized,
+        }
+    }
+
+    /// Run the guest's entrypoint now, if the sandbox was created with
+    /// `UninitializedSandbox::set_lazy_initialization`, returning any error
+    /// initialization fails with. Does nothing if the guest is already
+    /// initialized.
+    #[instrument(err(Debug), skip_all, parent = Span::current(), level = "Trace")]
+    pub fn ensure_initialized(&mut self) -> Result<()> {
+        if !self.initialized {
+            initialise_guest(&mut self.mem_mgr, &mut self.hv_handler)?;
+            self.initialized = true;
         }
+        Ok(())
     }
 
     /// Create a new `SingleUseCallContext` . The main purpose of the
@@ -164,7 +195,7 @@ impl SingleUseSandbox {
     /// Convenience for the following:
     ///
     /// `self.new_call_context().call(name, ret, args)`
-    #[instrument(err(Debug), skip(self, args), parent = Span::current())]
+    #[instrument(err(Debug), skip(self, args), fields(sandbox_id = %self.id()), parent = Span::current())]
     pub fn call_guest_function_by_name(
         self,
         name: &str,
@@ -173,6 +204,34 @@ impl SingleUseSandbox {
     ) -> Result<ReturnValue> {
         self.new_call_context().call(name, ret, args)
     }
+
+    /// The `GuestInfo` of the guest binary this single-use sandbox was
+    /// evolved with
+    #[instrument(skip_all, parent = Span::current(), level = "Trace")]
+    pub fn guest_info(&self) -> &GuestInfo {
+        self.hv_handler.guest_info()
+    }
+
+    /// The id of this sandbox, which is the id of the
+    /// `UninitializedSandbox` it was evolved from.
+    #[instrument(skip_all, parent = Span::current(), level = "Trace")]
+    pub fn id(&self) -> SandboxId {
+        self.registration.id()
+    }
+
+    /// The name the `UninitializedSandbox` this sandbox was evolved from
+    /// was given, if any.
+    #[instrument(skip_all, parent = Span::current(), level = "Trace")]
+    pub fn name(&self) -> Option<String> {
+        self.registration.name()
+    }
+
+    /// Add a subscriber to be told about the events in this sandbox's life
+    /// from now on.
+    #[instrument(skip_all, parent = Span::current(), level = "Trace")]
+    pub fn add_event_subscriber(&mut self, subscriber: Arc<dyn EventSubscriber>) {
+        self.hv_handler.events().add(subscriber);
+    }
 }
 
 impl WrapperGetter for SingleUseSandbox {
@@ -195,11 +254,29 @@ impl Sandbox for SingleUseSandbox {
     fn check_stack_guard(&self) -> Result<bool> {
         self.mem_mgr.check_stack_guard()
     }
+
+    #[instrument(err(Debug), skip_all, parent = Span::current(), level = "Trace")]
+    fn vcpu_stats(&self) -> Result<Option<VcpuStats>> {
+        self.hv_handler.vcpu_stats()
+    }
+
+    #[cfg(all(feature = "cgroup", target_os = "linux"))]
+    #[instrument(err(Debug), skip_all, parent = Span::current(), level = "Trace")]
+    fn cgroup_usage(&self) -> Result<Option<CgroupUsage>> {
+        self.hv_handler.cgroup_usage()
+    }
+
+    #[instrument(err(Debug), skip_all, parent = Span::current(), level = "Trace")]
+    fn creation_report(&self) -> Result<Option<CreationReport>> {
+        self.hv_handler.creation_report().map(Some)
+    }
 }
 
 impl std::fmt::Debug for SingleUseSandbox {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         f.debug_struct("SingleUseSandbox")
+            .field("id", &self.id())
+            .field("name", &self.name())
             .field("stack_guard", &self.mem_mgr.get_stack_cookie())
             .finish()
     }
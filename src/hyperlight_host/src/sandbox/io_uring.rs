@@ -0,0 +1,320 @@
+/*
+Copyright 2024 The Hyperlight Authors.
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+    http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+use std::collections::HashMap;
+use std::os::fd::RawFd;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::{io, thread};
+
+use crossbeam_channel::{bounded, unbounded, Receiver, Sender};
+use io_uring::{opcode, squeue, types, IoUring};
+use tracing::{instrument, Span};
+
+use crate::{log_then_return, new_error, Result};
+
+/// The size of the submission queue, which is the most operations the
+/// submission thread queues before submitting them
+const RING_ENTRIES: u32 = 256;
+
+/// The most a read grows its buffer by at a time
+const READ_CHUNK: usize = 64 * 1024;
+
+/// The ring shared by every sandbox in the process, created the first time
+/// a sandbox needs it, and again if its threads have stopped
+static SHARED: Mutex<Option<Arc<IoUringExecutor>>> = Mutex::new(None);
+
+/// An operation a host call wants done, and where to send its result
+struct Request {
+    entry: squeue::Entry,
+    result: Sender<i32>,
+}
+
+/// An io_uring the built-in services of every sandbox in the process do
+/// their file I/O through.
+///
+/// Host calls hand their operations to a submission thread, which queues
+/// the operations of every sandbox waiting at the time on the ring and
+/// submits them together, and wait for a completion thread to hand the
+/// results back. Each host call still waits for its own operation, but the
+/// threads running host calls make no I/O system calls of their own, and
+/// the kernel works on the I/O of many sandboxes at once.
+pub(crate) struct IoUringExecutor {
+    requests: Sender<Request>,
+    shared: Arc<Shared>,
+}
+
+/// What the submission and completion threads share
+struct Shared {
+    ring: IoUring,
+    /// The channels the results of the operations in flight are sent back
+    /// on, by the operations' user data, or `None` once the completion
+    /// thread has stopped
+    pending: Mutex<Option<HashMap<u64, Sender<i32>>>>,
+    /// Whether either thread has stopped, after which the ring can't be
+    /// used any more
+    stopped: AtomicBool,
+}
+
+impl IoUringExecutor {
+    /// The ring shared by every sandbox in the process
+    #[instrument(err(Debug), skip_all, parent = Span::current(), level = "Trace")]
+    pub(crate) fn shared() -> Result<Arc<Self>> {
+        let mut shared = lock(&SHARED)?;
+        if let Some(executor) = &*shared {
+            if !executor.shared.stopped.load(Ordering::Acquire) {
+                return Ok(executor.clone());
+            }
+            log::warn!("The io_uring threads have stopped, so the ring is being replaced");
+        }
+
+        let ring = IoUring::new(RING_ENTRIES)?;
+        if !ring.params().is_feature_rw_cur_pos() {
+            log_then_return!(
+                "The host's kernel can't read and write files at their position through io_uring"
+            );
+        }
+        let (requests, received) = unbounded();
+        let executor = Arc::new(Self {
+            requests,
+            shared: Arc::new(Shared {
+                ring,
+                pending: Mutex::new(Some(HashMap::new())),
+                stopped: AtomicBool::new(false),
+            }),
+        });
+        let submitter = executor.shared.clone();
+        thread::Builder::new()
+            .name("hyperlight-io-uring-submit".to_string())
+            .spawn(move || submitter.submit(received))?;
+        let reaper = executor.shared.clone();
+        thread::Builder::new()
+            .name("hyperlight-io-uring".to_string())
+            .spawn(move || reaper.reap())?;
+        *shared = Some(executor.clone());
+        Ok(executor)
+    }
+
+    /// Read at most `len` bytes from `fd` at its position, advancing it,
+    /// returning fewer bytes only at the end of the file
+    pub(crate) fn read(&self, fd: RawFd, len: usize) -> Result<Vec<u8>> {
+        let mut bytes = Vec::new();
+        while bytes.len() < len {
+            // grow the buffer as the file turns out to be long enough,
+            // rather than trusting the guest with the allocation
+            let remaining = len - bytes.len();
+            bytes.reserve(remaining.min(READ_CHUNK));
+            let spare = bytes.spare_capacity_mut();
+            let entry = opcode::Read::new(
+                types::Fd(fd),
+                spare.as_mut_ptr().cast(),
+                spare.len().min(remaining) as u32,
+            )
+            .offset(u64::MAX)
+            .build();
+            let (returned, read) = self.complete(entry, bytes)?;
+            bytes = returned;
+            if read == 0 {
+                break;
+            }
+            // SAFETY: the kernel initialised the `read` bytes after the
+            // end of `bytes`
+            unsafe { bytes.set_len(bytes.len() + read) };
+        }
+        Ok(bytes)
+    }
+
+    /// Write all of `bytes` to `fd` at its position, advancing it
+    pub(crate) fn write_all(&self, fd: RawFd, mut bytes: Vec<u8>) -> Result<()> {
+        let mut written = 0;
+        while written < bytes.len() {
+            let remaining = &bytes[written..];
+            let entry = opcode::Write::new(
+                types::Fd(fd),
+                remaining.as_ptr(),
+                remaining.len().min(u32::MAX as usize) as u32,
+            )
+            .offset(u64::MAX)
+            .build();
+            let (returned, count) = self.complete(entry, bytes)?;
+            bytes = returned;
+            if count == 0 {
+                return Err(io::Error::from(io::ErrorKind::WriteZero).into());
+            }
+            written += count;
+        }
+        Ok(())
+    }
+
+    /// Have `entry`, which reads into or writes from `buffer`, done, and
+    /// wait for the number of bytes it read or wrote. If `entry` could
+    /// still be using `buffer` when this fails, `buffer` is leaked rather
+    /// than freed under the kernel.
+    fn complete(&self, entry: squeue::Entry, buffer: Vec<u8>) -> Result<(Vec<u8>, usize)> {
+        let (result, receiver) = bounded(1);
+        if self.requests.send(Request { entry, result }).is_err() {
+            log_then_return!("The io_uring submission thread has stopped");
+        }
+        match receiver.recv() {
+            Ok(result) if result < 0 => Err(io::Error::from_raw_os_error(-result).into()),
+            Ok(result) => Ok((buffer, result as usize)),
+            Err(e) => {
+                std::mem::forget(buffer);
+                Err(new_error!("The io_uring threads have stopped: {}", e))
+            }
+        }
+    }
+}
+
+impl Shared {
+    /// Queue the operations host calls ask for on the ring, submitting
+    /// each batch of them that arrives together, until every sender of
+    /// requests is gone or the completion thread stops
+    fn submit(&self, requests: Receiver<Request>) {
+        let mut next_id: u64 = 0;
+        while let Ok(request) = requests.recv() {
+            let mut batch = vec![request];
+            batch.extend(requests.try_iter().take(RING_ENTRIES as usize - 1));
+
+            let Ok(mut guard) = lock(&self.pending) else {
+                break;
+            };
+            let Some(pending) = &mut *guard else {
+                // dropping the batch wakes the host calls waiting on it
+                break;
+            };
+            // SAFETY: this is the only thread using the submission queue
+            let mut queue = unsafe { self.ring.submission_shared() };
+            for Request { entry, result } in batch {
+                let id = next_id;
+                next_id = next_id.wrapping_add(1);
+                let entry = entry.user_data(id);
+                // SAFETY: the host call waiting for the result keeps the
+                // buffer of the operation alive until the kernel is done
+                // with it
+                if unsafe { queue.push(&entry) }.is_err() {
+                    // the kernel hasn't taken everything queued before,
+                    // so give it another chance to before giving up
+                    queue.sync();
+                    let _ = self.ring.submit();
+                    queue.sync();
+                    if unsafe { queue.push(&entry) }.is_err() {
+                        let _ = result.send(-libc::EBUSY);
+                        continue;
+                    }
+                }
+                pending.insert(id, result);
+            }
+            queue.sync();
+            drop(queue);
+            drop(guard);
+
+            match self.ring.submit() {
+                Ok(_) => {}
+                // the operations are on the queue, so the next submission,
+                // or the completion thread waiting, will start them
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+                Err(e) if e.raw_os_error() == Some(libc::EBUSY) => {}
+                Err(e) => {
+                    log::error!("Submitting io_uring operations failed: {}", e);
+                    break;
+                }
+            }
+        }
+        self.stopped.store(true, Ordering::Release);
+    }
+
+    /// Wait for operations to complete, sending their results to the host
+    /// calls waiting for them, for as long as the ring is in use
+    fn reap(&self) {
+        loop {
+            match self.ring.submitter().submit_and_wait(1) {
+                Ok(_) => {}
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(e) => {
+                    log::error!("Waiting for io_uring completions failed: {}", e);
+                    break;
+                }
+            }
+
+            // SAFETY: this is the only thread using the completion queue
+            let completed: Vec<_> = unsafe { self.ring.completion_shared() }
+                .map(|cqe| (cqe.user_data(), cqe.result()))
+                .collect();
+            let Ok(mut pending) = lock(&self.pending) else {
+                break;
+            };
+            let Some(pending) = &mut *pending else {
+                break;
+            };
+            for (id, result) in completed {
+                if let Some(sender) = pending.remove(&id) {
+                    // the receiver is only dropped once its host call has
+                    // given up on the result
+                    let _ = sender.send(result);
+                }
+            }
+        }
+        // dropping the senders wakes everything waiting
+        if let Ok(mut pending) = lock(&self.pending) {
+            *pending = None;
+        }
+        self.stopped.store(true, Ordering::Release);
+    }
+}
+
+fn lock<T>(mutex: &Mutex<T>) -> Result<MutexGuard<'_, T>> {
+    mutex
+        .lock()
+        .map_err(|e| new_error!("Error locking at {}:{}: {}", file!(), line!(), e))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{Seek, SeekFrom};
+    use std::os::fd::AsRawFd;
+
+    use super::*;
+
+    #[test]
+    fn read_and_write() {
+        let executor = IoUringExecutor::shared().unwrap();
+        assert!(Arc::ptr_eq(&executor, &IoUringExecutor::shared().unwrap()));
+
+        let mut file = tempfile::tempfile().unwrap();
+        executor
+            .write_all(file.as_raw_fd(), b"hello world".to_vec())
+            .unwrap();
+        file.seek(SeekFrom::Start(6)).unwrap();
+        assert_eq!(executor.read(file.as_raw_fd(), 3).unwrap(), b"wor");
+        assert_eq!(executor.read(file.as_raw_fd(), 100).unwrap(), b"ld");
+        assert_eq!(executor.read(file.as_raw_fd(), 100).unwrap(), b"");
+        assert!(executor.read(-1, 1).is_err());
+    }
+
+    #[test]
+    fn replaced_once_stopped() {
+        let executor = IoUringExecutor::shared().unwrap();
+        executor.shared.stopped.store(true, Ordering::Release);
+        let replacement = IoUringExecutor::shared().unwrap();
+        assert!(!Arc::ptr_eq(&executor, &replacement));
+
+        let file = tempfile::tempfile().unwrap();
+        replacement
+            .write_all(file.as_raw_fd(), b"hello".to_vec())
+            .unwrap();
+    }
+}
@@ -0,0 +1,166 @@
+/*
+Copyright 2024 The Hyperlight Authors.
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+    http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+use std::fmt;
+
+use tracing::{instrument, Span};
+
+use crate::mem::memory_region::{MemoryRegion, MemoryRegionType};
+use crate::mem::mgr::SandboxMemoryManager;
+use crate::mem::shared_mem::{HostSharedMemory, SharedMemory};
+use crate::Result;
+
+/// The regions of guest memory the guest has no reason to write to while
+/// it runs a function: the page tables, which only the host sets up, the
+/// guard page below the stack and the host function definitions, which
+/// only the host writes, before the guest is initialised or between guest
+/// function calls as host functions are registered
+const PROTECTED_REGIONS: [MemoryRegionType; 3] = [
+    MemoryRegionType::PageTables,
+    MemoryRegionType::GuardPage,
+    MemoryRegionType::HostFunctionDefinitions,
+];
+
+/// The Accessed and Dirty bits of a page table entry, which the CPU sets
+/// itself as the guest reaches the memory the entry maps, so they aren't
+/// changes to the page tables
+const PAGE_ACCESSED_DIRTY: u64 = 1 << 5 | 1 << 6;
+
+/// Something found after a guest function call, while isolation auditing
+/// is on, that shows the guest reached outside the memory it is meant to
+/// use. See `MultiUseSandbox::set_isolation_audit`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum IsolationViolation {
+    /// The cookie at the top of the guest's stack, which the guest never
+    /// touches, has been overwritten, so the stack has overflowed or been
+    /// written past
+    StackCookieOverwritten,
+    /// The guard page below the guest's stack has been written to, at the
+    /// guest physical `address`
+    GuardPageWritten {
+        /// The first address in the page that was written to
+        address: u64,
+    },
+    /// The guest's page tables have been changed, at the guest physical
+    /// `address`, which can change which memory the guest can reach and
+    /// how
+    PageTablesModified {
+        /// The first address in the page tables that was changed
+        address: u64,
+    },
+    /// A region the guest should only read from has been written to, at
+    /// the guest physical `address`
+    ReadOnlyRegionWritten {
+        /// The region that was written to
+        region: MemoryRegionType,
+        /// The first address in the region that was written to
+        address: u64,
+    },
+}
+
+impl fmt::Display for IsolationViolation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::StackCookieOverwritten => write!(f, "the stack cookie was overwritten"),
+            Self::GuardPageWritten { address } => {
+                write!(f, "the stack guard page was written at {:#x}", address)
+            }
+            Self::PageTablesModified { address } => {
+                write!(f, "the page tables were modified at {:#x}", address)
+            }
+            Self::ReadOnlyRegionWritten { region, address } => {
+                write!(f, "the {:?} region was written at {:#x}", region, address)
+            }
+        }
+    }
+}
+
+/// The contents of the protected regions of a sandbox's memory taken
+/// before a guest function call, to compare them with once the call has
+/// returned
+pub(crate) struct IsolationAudit {
+    regions: Vec<(MemoryRegion, Vec<u8>)>,
+}
+
+impl IsolationAudit {
+    /// Copy the protected regions of the memory `mgr` manages
+    #[instrument(err(Debug), skip_all, parent = Span::current(), level = "Trace")]
+    pub(crate) fn start(mgr: &SandboxMemoryManager<HostSharedMemory>) -> Result<Self> {
+        let base = mgr.shared_mem.base_addr();
+        let regions = mgr
+            .layout
+            .get_memory_regions(&mgr.shared_mem)?
+            .into_iter()
+            .filter(|region| PROTECTED_REGIONS.contains(&region.region_type))
+            .map(|region| {
+                let mut contents = vec![0; region.host_region.len()];
+                mgr.shared_mem
+                    .copy_to_slice(&mut contents, region.host_region.start - base)?;
+                Ok((region, contents))
+            })
+            .collect::<Result<_>>()?;
+        Ok(Self { regions })
+    }
+
+    /// Compare the protected regions of the memory `mgr` manages with the
+    /// copies `start` took, returning a violation for each region that has
+    /// changed
+    #[instrument(err(Debug), skip_all, parent = Span::current(), level = "Trace")]
+    pub(crate) fn finish(
+        self,
+        mgr: &SandboxMemoryManager<HostSharedMemory>,
+    ) -> Result<Vec<IsolationViolation>> {
+        let base = mgr.shared_mem.base_addr();
+        let mut violations = Vec::new();
+        let mut contents = Vec::new();
+        for (region, before) in self.regions {
+            contents.resize(before.len(), 0);
+            mgr.shared_mem
+                .copy_to_slice(&mut contents, region.host_region.start - base)?;
+            let Some(changed) = first_change(region.region_type, &before, &contents) else {
+                continue;
+            };
+            let address = (region.guest_region.start + changed) as u64;
+            violations.push(match region.region_type {
+                MemoryRegionType::GuardPage => IsolationViolation::GuardPageWritten { address },
+                MemoryRegionType::PageTables => IsolationViolation::PageTablesModified { address },
+                region => IsolationViolation::ReadOnlyRegionWritten { region, address },
+            });
+        }
+        Ok(violations)
+    }
+}
+
+/// The offset of the first byte of a region of `region_type` that differs
+/// between `before` and `after`, ignoring the bits of page table entries
+/// the CPU sets
+fn first_change(region_type: MemoryRegionType, before: &[u8], after: &[u8]) -> Option<usize> {
+    if region_type != MemoryRegionType::PageTables {
+        return before.iter().zip(after).position(|(a, b)| a != b);
+    }
+    before
+        .chunks_exact(8)
+        .zip(after.chunks_exact(8))
+        .enumerate()
+        .find_map(|(entry, (a, b))| {
+            let a = u64::from_le_bytes(a.try_into().ok()?);
+            let b = u64::from_le_bytes(b.try_into().ok()?);
+            match (a ^ b) & !PAGE_ACCESSED_DIRTY {
+                0 => None,
+                changed => Some(entry * 8 + changed.trailing_zeros() as usize / 8),
+            }
+        })
+}
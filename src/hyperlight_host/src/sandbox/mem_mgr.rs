@@ -21,6 +21,7 @@ use crate::mem::mgr::{SandboxMemoryManager, STACK_COOKIE_LEN};
 use crate::mem::shared_mem::{
     ExclusiveSharedMemory, GuestSharedMemory, HostSharedMemory, SharedMemory,
 };
+use crate::sandbox::randomness::SandboxRng;
 use crate::Result;
 
 /// StackCookie
@@ -79,7 +80,11 @@ impl MemMgrWrapper<ExclusiveSharedMemory> {
     }
 
     #[instrument(err(Debug), skip_all, parent = Span::current(), level= "Trace")]
-    pub(super) fn write_memory_layout(&mut self, run_inprocess: bool) -> Result<()> {
+    pub(super) fn write_memory_layout(
+        &mut self,
+        run_inprocess: bool,
+        rng: &mut SandboxRng,
+    ) -> Result<()> {
         let mgr = self.unwrap_mgr_mut();
         let layout = mgr.layout;
         let shared_mem = mgr.get_shared_mem_mut();
@@ -89,7 +94,7 @@ impl MemMgrWrapper<ExclusiveSharedMemory> {
         } else {
             SandboxMemoryLayout::BASE_ADDRESS
         };
-        layout.write(shared_mem, guest_offset, mem_size, run_inprocess)
+        layout.write(shared_mem, guest_offset, mem_size, run_inprocess, rng)
     }
 }
 
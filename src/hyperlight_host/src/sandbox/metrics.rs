@@ -42,6 +42,20 @@ static SANDBOX_METRIC_DEFINITIONS: &[HyperlightMetricDefinition] = &[
         labels: &["error_code", "error_message"],
         buckets: &[],
     },
+    HyperlightMetricDefinition {
+        name: "guest_log_queue_overflows",
+        help: "Number of records guests logged while their log queue was full",
+        metric_type: HyperlightMetricType::IntCounterVec,
+        labels: &["overflow_policy"],
+        buckets: &[],
+    },
+    HyperlightMetricDefinition {
+        name: "builtin_service_counters",
+        help: "Counters guests increment with the hyperlight::metrics::increment built-in service",
+        metric_type: HyperlightMetricType::IntCounterVec,
+        labels: &["counter_name"],
+        buckets: &[],
+    },
     #[cfg(feature = "function_call_metrics")]
     HyperlightMetricDefinition {
         name: "guest_function_call_duration_microseconds",
@@ -91,6 +105,8 @@ static SANDBOX_METRIC_DEFINITIONS: &[HyperlightMetricDefinition] = &[
 #[strum(serialize_all = "snake_case")]
 pub(crate) enum SandboxMetric {
     GuestErrorCount,
+    GuestLogQueueOverflows,
+    BuiltinServiceCounters,
     #[cfg(feature = "function_call_metrics")]
     GuestFunctionCallDurationMicroseconds,
     #[cfg(feature = "function_call_metrics")]
@@ -186,7 +202,12 @@ mod tests {
                         );
                         assert!(counter.is_ok());
                         let counter = counter.unwrap();
-                        let label_vals = ["test", "test2"];
+                        let labels = SANDBOX_METRIC_DEFINITIONS
+                            .iter()
+                            .find(|definition| definition.name == int_counter_vec.name)
+                            .unwrap()
+                            .labels;
+                        let label_vals = vec!["test"; labels.len()];
                         int_counter_vec_reset!(&sandbox_metric, &label_vals);
                         let value = counter.get(&label_vals);
                         assert!(value.is_ok());
@@ -252,8 +273,8 @@ mod tests {
         let registry = get_metrics_registry();
         let result = registry.gather();
         #[cfg(feature = "function_call_metrics")]
-        assert_eq!(result.len(), 3);
+        assert_eq!(result.len(), 5);
         #[cfg(not(feature = "function_call_metrics"))]
-        assert_eq!(result.len(), 1);
+        assert_eq!(result.len(), 3);
     }
 }
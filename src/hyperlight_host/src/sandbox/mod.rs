@@ -14,10 +14,33 @@ See the License for the specific language governing permissions and
 limitations under the License.
 */
 
+/// The built-in host services, such as time and entropy, that hosts can
+/// give guests
+pub mod builtin_services;
+/// Placing sandboxes in cgroups of their own
+#[cfg(all(feature = "cgroup", target_os = "linux"))]
+pub mod cgroup;
 /// Configuration needed to establish a sandbox.
 pub mod config;
+/// Coverage collected from guests built with coverage counters
+pub mod coverage;
+/// The bus that carries events between sandboxes through the built-in
+/// services
+pub mod event_bus;
+/// Callbacks for the events in a sandbox's life
+pub mod events;
+/// Recording which guest binary a sandbox runs
+pub mod guest_info;
+/// Queueing the records guests log for hosts to consume
+pub mod guest_logs;
 /// Functionality for reading, but not modifying host functions
 mod host_funcs;
+/// Options controlling how output printed by the guest is written
+pub mod host_print;
+/// The HTTP requests hosts can let guests make through the built-in
+/// services
+#[cfg(feature = "http_service")]
+pub mod http;
 /// Functionality for dealing with `Sandbox`es that contain Hypervisors
 pub(crate) mod hypervisor;
 /// Functionality for dealing with initialized sandboxes that can
@@ -26,6 +49,12 @@ pub mod initialized_multi_use;
 /// Functionality for dealing with initialized sandboxes that can
 /// call 0 or 1 guest functions, but no more
 pub mod initialized_single_use;
+/// The io_uring the built-in file system services of every sandbox share
+#[cfg(all(feature = "io_uring", target_os = "linux"))]
+pub(crate) mod io_uring;
+/// Checking that guest function calls stay within the memory they are
+/// allowed to use
+pub mod isolation_audit;
 /// A container to leak, store and manage outb handlers for in-process
 /// executions. On non-in-process executions (e.g. windows without
 /// in-process mode turned on, or linux), the same container is just
@@ -38,27 +67,100 @@ pub(crate) mod mem_access;
 /// Functionality for interacting with a sandbox's internally-stored
 /// `SandboxMemoryManager`
 pub(crate) mod mem_mgr;
+/// Reporting sandbox activity to OpenTelemetry
+#[cfg(feature = "otel")]
+pub(crate) mod otel;
 pub(crate) mod outb;
+/// Profiling where the time of creating sandboxes and of guest function
+/// calls goes
+pub mod profile;
+/// The source of a sandbox's random values
+pub(crate) mod randomness;
+/// Redaction of crash dumps and the audit log
+pub(crate) mod redaction;
+/// The process-wide registry of live sandboxes
+pub mod registry;
+/// Caching the results of pure guest functions
+pub mod result_cache;
 /// Options for configuring a sandbox
 mod run_options;
+/// Signing guest binaries and checking their signatures
+#[cfg(feature = "signed_guests")]
+pub mod signing;
 /// Functionality for creating uninitialized sandboxes, manipulating them,
 /// and converting them to initialized sandboxes.
 pub mod uninitialized;
 /// Functionality for properly converting `UninitializedSandbox`es to
 /// initialized `Sandbox`es.
 pub(crate) mod uninitialized_evolve;
+/// The file systems hosts can give guests through the built-in services
+pub mod vfs;
 
 /// Metric definitions for Sandbox module.
 pub(crate) mod metrics;
 
 use std::collections::HashMap;
 
+/// Re-export for `BuiltinService` type
+pub use builtin_services::BuiltinService;
+/// Re-export for `BuiltinServices` type
+pub use builtin_services::BuiltinServices;
+/// Re-export for `BuiltinServicesPolicy` type
+pub use builtin_services::BuiltinServicesPolicy;
+/// Re-export for `CgroupOptions` type
+#[cfg(all(feature = "cgroup", target_os = "linux"))]
+pub use cgroup::CgroupOptions;
+/// Re-export for `CgroupUsage` type
+#[cfg(all(feature = "cgroup", target_os = "linux"))]
+pub use cgroup::CgroupUsage;
+/// Re-export for `LatencyProfile` type
+pub use config::LatencyProfile;
 /// Re-export for `SandboxConfiguration` type
 pub use config::SandboxConfiguration;
+/// Re-export for `CoverageMap` type
+pub use coverage::CoverageMap;
+/// Re-export for `BusAccess` type
+pub use event_bus::BusAccess;
+/// Re-export for `EventBus` type
+pub use event_bus::EventBus;
+/// Re-export for `EventSubscriber` trait
+pub use events::EventSubscriber;
+/// Re-export for `GuestBinaryInfo` type
+pub use guest_info::GuestBinaryInfo;
+/// Re-export for `GuestInfo` type
+pub use guest_info::GuestInfo;
+/// Re-export for `GuestLogQueue` type
+pub use guest_logs::GuestLogQueue;
+/// Re-export for `LogOverflowPolicy` type
+pub use guest_logs::LogOverflowPolicy;
+/// Re-export for `HostPrintAction` type
+pub use host_print::HostPrintAction;
+/// Re-export for `HostPrintOptions` type
+pub use host_print::HostPrintOptions;
+/// Re-export for `HttpPolicy` type
+#[cfg(feature = "http_service")]
+pub use http::HttpPolicy;
+use hyperlight_common::flatbuffer_wrappers::function_types::ParameterType;
 /// Re-export for the `MultiUseSandbox` type
 pub use initialized_multi_use::MultiUseSandbox;
 /// Re-export for `SingleUseSandbox` type
 pub use initialized_single_use::SingleUseSandbox;
+/// Re-export for `IsolationViolation` type
+pub use isolation_audit::IsolationViolation;
+/// Re-export for `UnknownOutbPolicy` type
+pub use outb::UnknownOutbPolicy;
+/// Re-export for `CallProfile` type
+pub use profile::{CallProfile, CallTimeBreakdown, CreationReport, GuestProfile};
+/// Re-export for `SandboxId` type
+pub use registry::SandboxId;
+/// Re-export for `SandboxInfo` type
+pub use registry::SandboxInfo;
+/// Re-export for `SandboxRegistry` type
+pub use registry::SandboxRegistry;
+/// Re-export for `ResultCachePolicy` type
+pub use result_cache::ResultCachePolicy;
+/// Re-export for `ResultCacheStats` type
+pub use result_cache::ResultCacheStats;
 /// Re-export for `SandboxRunOptions` type
 pub use run_options::SandboxRunOptions;
 use tracing::{instrument, Span};
@@ -66,9 +168,11 @@ use tracing::{instrument, Span};
 pub use uninitialized::GuestBinary;
 /// Re-export for `UninitializedSandbox` type
 pub use uninitialized::UninitializedSandbox;
+/// Re-export for `VirtualFs` type
+pub use vfs::VirtualFs;
 
 use self::mem_mgr::MemMgrWrapper;
-use crate::func::HyperlightFunction;
+use crate::func::{HyperlightFallbackFunction, HyperlightFunction};
 use crate::hypervisor::hypervisor_handler::HypervisorHandler;
 #[cfg(target_os = "windows")]
 use crate::hypervisor::windows_hypervisor_platform;
@@ -96,42 +200,115 @@ pub type ExtraAllowedSyscall = i64;
 
 /// A `HashMap` to map function names to `HyperlightFunction`s and their extra allowed syscalls.
 ///
+/// Host functions can be overloaded by parameter types, so each name maps to
+/// every signature registered under it.
+///
+/// It also holds the optional fallback that handles calls to functions that
+/// have not been registered.
+///
 /// Note: you cannot add extra syscalls on Windows, but the field is still present to avoid a funky
 /// conditional compilation setup. This isn't a big deal as this struct isn't public facing.
 #[derive(Clone, Default)]
-pub(super) struct FunctionsMap(
-    HashMap<String, (HyperlightFunction, Option<Vec<ExtraAllowedSyscall>>)>,
-);
+pub(super) struct FunctionsMap {
+    functions: HashMap<String, Vec<HostFunctionOverload>>,
+    fallback: Option<(HyperlightFallbackFunction, Option<Vec<ExtraAllowedSyscall>>)>,
+}
+
+/// A single signature of a (possibly overloaded) host function.
+#[derive(Clone)]
+pub(super) struct HostFunctionOverload {
+    parameter_types: Vec<ParameterType>,
+    function: HyperlightFunction,
+    extra_allowed_syscalls: Option<Vec<ExtraAllowedSyscall>>,
+}
 
 impl FunctionsMap {
-    /// Insert a new entry into the map
+    /// Insert a new entry into the map, replacing any existing entry with
+    /// the same name and parameter types.
     pub(super) fn insert(
         &mut self,
         key: String,
+        parameter_types: Vec<ParameterType>,
         value: HyperlightFunction,
         extra_syscalls: Option<Vec<ExtraAllowedSyscall>>,
     ) {
-        self.0.insert(key, (value, extra_syscalls));
+        let overload = HostFunctionOverload {
+            parameter_types,
+            function: value,
+            extra_allowed_syscalls: extra_syscalls,
+        };
+        let overloads = self.functions.entry(key).or_default();
+        match overloads
+            .iter_mut()
+            .find(|o| o.parameter_types == overload.parameter_types)
+        {
+            Some(existing) => *existing = overload,
+            None => overloads.push(overload),
+        }
     }
 
-    /// Get the value associated with the given key, if it exists.
+    /// Get the function and extra allowed syscalls for the overload of
+    /// `key` that takes `parameter_types`, if it exists.
     pub(super) fn get(
         &self,
         key: &str,
-    ) -> Option<&(HyperlightFunction, Option<Vec<ExtraAllowedSyscall>>)> {
-        self.0.get(key)
+        parameter_types: &[ParameterType],
+    ) -> Option<(&HyperlightFunction, &Option<Vec<ExtraAllowedSyscall>>)> {
+        self.functions
+            .get(key)?
+            .iter()
+            .find(|o| o.parameter_types == parameter_types)
+            .map(|o| (&o.function, &o.extra_allowed_syscalls))
+    }
+
+    /// Remove every overload of `key`, returning whether there were any.
+    pub(super) fn remove(&mut self, key: &str) -> bool {
+        self.functions.remove(key).is_some()
+    }
+
+    /// Returns `true` if any overload of `key` has been registered.
+    pub(super) fn contains_key(&self, key: &str) -> bool {
+        self.functions.contains_key(key)
+    }
+
+    /// Set the handler for calls to functions that have not been registered,
+    /// and the extra syscalls it may make, replacing any existing one.
+    pub(super) fn set_fallback(
+        &mut self,
+        fallback: HyperlightFallbackFunction,
+        extra_syscalls: Option<Vec<ExtraAllowedSyscall>>,
+    ) {
+        self.fallback = Some((fallback, extra_syscalls));
+    }
+
+    /// Get the handler for calls to functions that have not been registered,
+    /// and the extra syscalls it may make, if one has been set.
+    pub(super) fn fallback(
+        &self,
+    ) -> Option<(
+        &HyperlightFallbackFunction,
+        &Option<Vec<ExtraAllowedSyscall>>,
+    )> {
+        self.fallback
+            .as_ref()
+            .map(|(fallback, syscalls)| (fallback, syscalls))
     }
 
     /// Get the length of the map.
     fn len(&self) -> usize {
-        self.0.len()
+        self.functions.len()
     }
 }
 
 impl PartialEq for FunctionsMap {
     #[instrument(skip_all, parent = Span::current(), level= "Trace")]
     fn eq(&self, other: &Self) -> bool {
-        self.len() == other.len() && self.0.keys().all(|k| other.0.contains_key(k))
+        self.len() == other.len()
+            && self
+                .functions
+                .keys()
+                .all(|k| other.functions.contains_key(k))
+            && self.fallback.is_some() == other.fallback.is_some()
     }
 }
 
@@ -272,7 +449,7 @@ mod tests {
                         .pop()
                         .unwrap_or_else(|| panic!("Failed to pop Sandbox thread {}", i));
                     let host_funcs = sandbox
-                        ._host_funcs
+                        .host_funcs
                         .try_lock()
                         .map_err(|_| new_error!("Error locking"));
 
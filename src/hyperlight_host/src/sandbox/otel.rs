@@ -0,0 +1,177 @@
+/*
+Copyright 2024 The Hyperlight Authors.
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+    http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+//! Reports sandbox activity to OpenTelemetry, using the global tracer and
+//! meter providers, so hosts that have set those up get spans for guest
+//! function calls and host function calls, and metrics for their latency
+//! and for VM exits, without doing anything else.
+//!
+//! Every span and measurement has the `hyperlight.sandbox.id` attribute,
+//! and `hyperlight.sandbox.name` if the sandbox was given a name.
+
+use std::sync::{Mutex, RwLock};
+use std::time::Duration;
+
+use opentelemetry::metrics::{Counter, Histogram};
+use opentelemetry::trace::{Status, TraceContextExt, Tracer};
+use opentelemetry::{global, Context, KeyValue};
+
+use crate::sandbox::{EventSubscriber, SandboxId, SandboxRegistry};
+use crate::HyperlightError;
+
+/// The name of the tracer and meter sandboxes report to
+const INSTRUMENTATION_SCOPE: &str = "hyperlight_host";
+
+/// Turns the events of one sandbox into OpenTelemetry spans and metrics
+pub(crate) struct OtelSubscriber {
+    attributes: RwLock<Vec<KeyValue>>,
+    /// The spans of the calls in progress, innermost last. Host function
+    /// calls are made from guest function calls, so their spans are
+    /// children of the guest call's span.
+    calls: Mutex<Vec<Context>>,
+    guest_call_duration: Histogram<f64>,
+    host_function_duration: Histogram<f64>,
+    vm_exits: Counter<u64>,
+}
+
+impl OtelSubscriber {
+    pub(crate) fn new(sandbox_id: SandboxId) -> Self {
+        let meter = global::meter(INSTRUMENTATION_SCOPE);
+        Self {
+            attributes: RwLock::new(vec![KeyValue::new(
+                "hyperlight.sandbox.id",
+                sandbox_id.to_string(),
+            )]),
+            calls: Mutex::new(Vec::new()),
+            guest_call_duration: meter
+                .f64_histogram("hyperlight.guest_call.duration")
+                .with_unit("s")
+                .with_description("Duration of guest function calls")
+                .build(),
+            host_function_duration: meter
+                .f64_histogram("hyperlight.host_function.duration")
+                .with_unit("s")
+                .with_description("Duration of host function calls made by guests")
+                .build(),
+            vm_exits: meter
+                .u64_counter("hyperlight.vm_exits")
+                .with_description("Number of times the vCPU exited to the host")
+                .build(),
+        }
+    }
+
+    /// Count an exit from the vCPU running the sandbox
+    pub(crate) fn record_vm_exit(&self, reason: &'static str) {
+        let mut attributes = self.attributes();
+        attributes.push(KeyValue::new("hyperlight.vm_exit.reason", reason));
+        self.vm_exits.add(1, &attributes);
+    }
+
+    fn attributes(&self) -> Vec<KeyValue> {
+        self.attributes
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone()
+    }
+
+    fn start_span(&self, name: String, function_name: &str) {
+        let mut calls = self.calls.lock().unwrap_or_else(|e| e.into_inner());
+        // guest calls are children of whatever the host was doing
+        let parent = calls.last().cloned().unwrap_or_else(Context::current);
+        let mut attributes = self.attributes();
+        attributes.push(KeyValue::new(
+            "hyperlight.function.name",
+            function_name.to_string(),
+        ));
+        let tracer = global::tracer(INSTRUMENTATION_SCOPE);
+        let span = tracer
+            .span_builder(name)
+            .with_attributes(attributes)
+            .start_with_context(&tracer, &parent);
+        calls.push(parent.with_span(span));
+    }
+
+    fn end_span(&self) {
+        let mut calls = self.calls.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(call) = calls.pop() {
+            call.span().end();
+        }
+    }
+}
+
+impl EventSubscriber for OtelSubscriber {
+    fn on_sandbox_created(&self, sandbox_id: SandboxId) {
+        // the sandbox can't be renamed once it has been initialised
+        if let Some(name) = SandboxRegistry::get(sandbox_id).and_then(|info| info.name) {
+            self.attributes
+                .write()
+                .unwrap_or_else(|e| e.into_inner())
+                .push(KeyValue::new("hyperlight.sandbox.name", name));
+        }
+    }
+
+    fn on_guest_call_started(&self, _sandbox_id: SandboxId, function_name: &str) {
+        self.start_span(format!("guest_call {}", function_name), function_name);
+    }
+
+    fn on_guest_call_finished(
+        &self,
+        _sandbox_id: SandboxId,
+        function_name: &str,
+        elapsed: Duration,
+    ) {
+        self.end_span();
+        let mut attributes = self.attributes();
+        attributes.push(KeyValue::new(
+            "hyperlight.function.name",
+            function_name.to_string(),
+        ));
+        self.guest_call_duration
+            .record(elapsed.as_secs_f64(), &attributes);
+    }
+
+    fn on_host_function_invoked(&self, _sandbox_id: SandboxId, function_name: &str) {
+        self.start_span(format!("host_function {}", function_name), function_name);
+    }
+
+    fn on_host_function_finished(
+        &self,
+        _sandbox_id: SandboxId,
+        function_name: &str,
+        elapsed: Duration,
+    ) {
+        self.end_span();
+        let mut attributes = self.attributes();
+        attributes.push(KeyValue::new(
+            "hyperlight.function.name",
+            function_name.to_string(),
+        ));
+        self.host_function_duration
+            .record(elapsed.as_secs_f64(), &attributes);
+    }
+
+    fn on_guest_error(
+        &self,
+        _sandbox_id: SandboxId,
+        _function_name: &str,
+        error: &HyperlightError,
+    ) {
+        let calls = self.calls.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(call) = calls.last() {
+            call.span().set_status(Status::error(error.to_string()));
+        }
+    }
+}
@@ -14,7 +14,9 @@ See the License for the specific language governing permissions and
 limitations under the License.
 */
 
+use std::collections::HashMap;
 use std::sync::{Arc, Mutex};
+use std::time::Instant;
 
 use hyperlight_common::flatbuffer_wrappers::function_types::ParameterValue;
 use hyperlight_common::flatbuffer_wrappers::guest_error::ErrorCode;
@@ -23,6 +25,8 @@ use log::{Level, Record};
 use tracing::{instrument, Span};
 use tracing_log::format_trace;
 
+use super::events::SandboxEvents;
+use super::guest_logs::GuestLogQueue;
 use super::host_funcs::HostFuncsWrapper;
 use super::mem_mgr::MemMgrWrapper;
 use crate::hypervisor::handlers::{OutBHandler, OutBHandlerFunction, OutBHandlerWrapper};
@@ -30,10 +34,68 @@ use crate::mem::mgr::SandboxMemoryManager;
 use crate::mem::shared_mem::HostSharedMemory;
 use crate::{new_error, HyperlightError, Result};
 
+/// A handler for writes to one of the outb ports reserved for
+/// user-defined channels.
+pub(crate) type PortHandler = Box<dyn FnMut(u8) -> Result<()> + Send>;
+
+type UnknownOutbCallback = Arc<Mutex<Box<dyn FnMut(u16, u64) -> Result<()> + Send>>>;
+
+/// What the host does when the guest writes to an outb port that
+/// Hyperlight doesn't use itself.
+#[derive(Clone, Default)]
+pub enum UnknownOutbPolicy {
+    /// Fail the current guest call with an error. This is the default.
+    #[default]
+    Error,
+    /// Silently ignore the write and resume the guest.
+    Ignore,
+    /// Pass the port and value to a host callback, which decides whether
+    /// the guest may continue by returning `Ok` or an error.
+    Callback(UnknownOutbCallback),
+}
+
+impl UnknownOutbPolicy {
+    /// Create an `UnknownOutbPolicy::Callback` that calls `handler` with
+    /// the port and value of every write to an unknown port.
+    #[instrument(skip_all, parent = Span::current(), level = "Trace")]
+    pub fn callback<F>(handler: F) -> Self
+    where
+        F: FnMut(u16, u64) -> Result<()> + Send + 'static,
+    {
+        Self::Callback(Arc::new(Mutex::new(Box::new(handler))))
+    }
+
+    #[instrument(err(Debug), skip_all, parent = Span::current(), level = "Trace")]
+    fn handle(&self, port: u16, byte: u64) -> Result<()> {
+        match self {
+            Self::Error => Err(new_error!("Invalid OutB value: {}", port)),
+            Self::Ignore => Ok(()),
+            Self::Callback(handler) => (handler
+                .try_lock()
+                .map_err(|e| new_error!("Error locking at {}:{}: {}", file!(), line!(), e))?)(
+                port, byte,
+            ),
+        }
+    }
+}
+
+impl std::fmt::Debug for UnknownOutbPolicy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Error => write!(f, "Error"),
+            Self::Ignore => write!(f, "Ignore"),
+            Self::Callback(_) => write!(f, "Callback"),
+        }
+    }
+}
+
 pub(super) enum OutBAction {
     Log,
     CallFunction,
     Abort,
+    PushChunk,
+    PopChunk,
+    ReleaseMemory,
 }
 
 impl TryFrom<u16> for OutBAction {
@@ -44,6 +106,9 @@ impl TryFrom<u16> for OutBAction {
             99 => Ok(OutBAction::Log),
             101 => Ok(OutBAction::CallFunction),
             102 => Ok(OutBAction::Abort),
+            104 => Ok(OutBAction::PushChunk),
+            105 => Ok(OutBAction::PopChunk),
+            106 => Ok(OutBAction::ReleaseMemory),
             _ => Err(new_error!("Invalid OutB value: {}", val)),
         }
     }
@@ -75,6 +140,13 @@ pub(super) fn outb_log(mgr: &mut SandboxMemoryManager<HostSharedMemory>) -> Resu
     let source_file = Some(log_data.source_file.as_str());
     let line = Some(log_data.line);
     let source = Some(log_data.source.as_str());
+    // Messages the guest logged to a target of its own are logged under
+    // `hyperlight-guest::<target>`, so that they can still be filtered as
+    // guest messages.
+    let target = match &log_data.target {
+        Some(target) => format!("hyperlight-guest::{}", target),
+        None => "hyperlight-guest".to_string(),
+    };
 
     // See https://github.com/rust-lang/rust/issues/42253 for the reason this has to be done this way
 
@@ -88,7 +160,7 @@ pub(super) fn outb_log(mgr: &mut SandboxMemoryManager<HostSharedMemory>) -> Resu
             &Record::builder()
                 .args(format_args!("{}", log_data.message))
                 .level(record_level)
-                .target("hyperlight-guest")
+                .target(&target)
                 .file(source_file)
                 .line(line)
                 .module_path(source)
@@ -100,7 +172,7 @@ pub(super) fn outb_log(mgr: &mut SandboxMemoryManager<HostSharedMemory>) -> Resu
             &Record::builder()
                 .args(format_args!("{}", log_data.message))
                 .level(record_level)
-                .target("hyperlight-guest")
+                .target(&target)
                 .file(Some(&log_data.source_file))
                 .line(Some(log_data.line))
                 .module_path(Some(&log_data.source))
@@ -113,31 +185,75 @@ pub(super) fn outb_log(mgr: &mut SandboxMemoryManager<HostSharedMemory>) -> Resu
 
 /// Handles OutB operations from the guest.
 #[instrument(err(Debug), skip_all, parent = Span::current(), level= "Trace")]
+#[allow(clippy::too_many_arguments)]
 fn handle_outb_impl(
     mem_mgr: &mut MemMgrWrapper<HostSharedMemory>,
     host_funcs: Arc<Mutex<HostFuncsWrapper>>,
+    port_handlers: &mut HashMap<u16, PortHandler>,
+    unknown_outb_policy: &UnknownOutbPolicy,
+    guest_log_queue: Option<&GuestLogQueue>,
+    call_deadline: &Mutex<Option<Instant>>,
+    events: &SandboxEvents,
     port: u16,
     byte: u64,
 ) -> Result<()> {
-    match port.try_into()? {
-        OutBAction::Log => outb_log(mem_mgr.as_mut()),
+    if let Some(handler) = port_handlers.get_mut(&port) {
+        return handler(byte as u8);
+    }
+    let action = match OutBAction::try_from(port) {
+        Ok(action) => action,
+        Err(_) => return unknown_outb_policy.handle(port, byte),
+    };
+    match action {
+        OutBAction::Log => match guest_log_queue {
+            Some(queue) => {
+                let deadline = *call_deadline
+                    .lock()
+                    .map_err(|e| new_error!("Error locking at {}:{}: {}", file!(), line!(), e))?;
+                queue.push(mem_mgr.as_mut().read_guest_log_data()?, deadline);
+                Ok(())
+            }
+            None => outb_log(mem_mgr.as_mut()),
+        },
         OutBAction::CallFunction => {
+            let start = Instant::now();
             let call = mem_mgr.as_mut().get_host_function_call()?; // pop output buffer
+            events
+                .profiler()
+                .record_serialization(start.elapsed(), true);
             let name = call.function_name.clone();
             let args: Vec<ParameterValue> = call.parameters.unwrap_or(vec![]);
+            events.emit(|subscriber, id| subscriber.on_host_function_invoked(id, &name));
+            let start = Instant::now();
             let res = host_funcs
                 .try_lock()
-                .map_err(|e| new_error!("Error locking at {}:{}: {}", file!(), line!(), e))?
-                .call_host_function(&name, args)?;
+                .map_err(|e| new_error!("Error locking at {}:{}: {}", file!(), line!(), e))
+                .and_then(|host_funcs| host_funcs.call_host_function(&name, args));
+            events.emit(|subscriber, id| {
+                subscriber.on_host_function_finished(id, &name, start.elapsed())
+            });
+            let res = res?;
+            let start = Instant::now();
             mem_mgr
                 .as_mut()
                 .write_response_from_host_method_call(&res)?; // push input buffers
+            events
+                .profiler()
+                .record_serialization(start.elapsed(), true);
 
             Ok(())
         }
+        // the guest is sending a value that doesn't fit in the output buffer
+        OutBAction::PushChunk => mem_mgr.as_mut().collect_output_segment(),
+        // the guest wants the next part of a value that doesn't fit in the
+        // input buffer
+        OutBAction::PopChunk => mem_mgr.as_mut().push_input_segment(),
+        // the guest freed part of its heap that it doesn't expect to need
+        // again soon
+        OutBAction::ReleaseMemory => mem_mgr.as_mut().release_guest_memory(),
         OutBAction::Abort => {
             let guest_error = ErrorCode::from(byte);
-            let panic_context = mem_mgr.as_mut().read_guest_panic_context_data().unwrap();
+            let panic_context = mem_mgr.as_mut().read_guest_panic_context_data()?;
             // trim off trailing \0 bytes if they exist
             let index_opt = panic_context.iter().position(|&x| x == 0x00);
             let trimmed = match index_opt {
@@ -147,10 +263,13 @@ fn handle_outb_impl(
             let s = String::from_utf8_lossy(trimmed);
             match guest_error {
                 ErrorCode::StackOverflow => Err(HyperlightError::StackOverflow()),
-                _ => Err(HyperlightError::GuestAborted(
-                    byte as u8,
-                    s.trim().to_string(),
-                )),
+                // the guest's exception handler describes the exception in
+                // the panic context too, as it can't rely on the heap
+                _ => Err(HyperlightError::GuestAborted {
+                    code: byte as u8,
+                    message: s.trim().to_string(),
+                    backtrace: Vec::new(),
+                }),
             }
         }
     }
@@ -158,17 +277,32 @@ fn handle_outb_impl(
 
 /// Given a `MemMgrWrapper` and ` HostFuncsWrapper` -- both passed by _value_
 ///  -- return an `OutBHandlerWrapper` wrapping the core OUTB handler logic.
+/// Writes to user-defined ports are passed to the matching entry in
+/// `port_handlers`, and writes to any other port the handler doesn't know
+/// about are dealt with according to `unknown_outb_policy`. The records
+/// the guest logs go into `guest_log_queue` if there is one, and `events`
+/// are told about every host function the guest calls.
 ///
 /// TODO: pass at least the `host_funcs_wrapper` param by reference.
 #[instrument(skip_all, parent = Span::current(), level= "Trace")]
 pub(crate) fn outb_handler_wrapper(
     mut mem_mgr_wrapper: MemMgrWrapper<HostSharedMemory>,
     host_funcs_wrapper: Arc<Mutex<HostFuncsWrapper>>,
+    mut port_handlers: HashMap<u16, PortHandler>,
+    unknown_outb_policy: UnknownOutbPolicy,
+    guest_log_queue: Option<GuestLogQueue>,
+    call_deadline: Arc<Mutex<Option<Instant>>>,
+    events: SandboxEvents,
 ) -> OutBHandlerWrapper {
     let outb_func: OutBHandlerFunction = Box::new(move |port, payload| {
         handle_outb_impl(
             &mut mem_mgr_wrapper,
             host_funcs_wrapper.clone(),
+            &mut port_handlers,
+            &unknown_outb_policy,
+            guest_log_queue.as_ref(),
+            &call_deadline,
+            &events,
             port,
             payload,
         )
@@ -179,20 +313,43 @@ pub(crate) fn outb_handler_wrapper(
 
 #[cfg(test)]
 mod tests {
+    use std::sync::{Arc, Mutex};
+
     use hyperlight_common::flatbuffer_wrappers::guest_log_level::LogLevel;
     use hyperlight_testing::logger::{Logger, LOGGER};
     use log::Level;
     use tracing_core::callsite::rebuild_interest_cache;
 
-    use super::outb_log;
+    use super::{outb_log, UnknownOutbPolicy};
     use crate::mem::layout::SandboxMemoryLayout;
     use crate::mem::mgr::SandboxMemoryManager;
     use crate::mem::shared_mem::SharedMemory;
+    use crate::new_error;
     use crate::sandbox::outb::GuestLogData;
+    use crate::sandbox::randomness::SandboxRng;
     use crate::sandbox::SandboxConfiguration;
     use crate::testing::log_values::test_value_as_str;
     use crate::testing::simple_guest_exe_info;
 
+    #[test]
+    fn unknown_outb_policy() {
+        assert!(UnknownOutbPolicy::default().handle(200, 1).is_err());
+        assert!(UnknownOutbPolicy::Ignore.handle(200, 1).is_ok());
+
+        let writes = Arc::new(Mutex::new(Vec::new()));
+        let writes_clone = writes.clone();
+        let policy = UnknownOutbPolicy::callback(move |port, byte| {
+            writes_clone.lock().unwrap().push((port, byte));
+            match byte {
+                0 => Err(new_error!("rejected")),
+                _ => Ok(()),
+            }
+        });
+        assert!(policy.handle(200, 1).is_ok());
+        assert!(policy.handle(201, 0).is_err());
+        assert_eq!(*writes.lock().unwrap(), vec![(200, 1), (201, 0)]);
+    }
+
     fn new_guest_log_data(level: LogLevel) -> GuestLogData {
         GuestLogData::new(
             "test log".to_string(),
@@ -229,6 +386,7 @@ mod tests {
                     SandboxMemoryLayout::BASE_ADDRESS,
                     mem_size,
                     false,
+                    &mut SandboxRng::default(),
                 )
                 .unwrap();
             let (hmgr, _) = mgr.build();
@@ -316,6 +474,33 @@ mod tests {
                 });
             }
         }
+        {
+            // messages logged to a target of the guest's own are logged
+            // under that target
+            LOGGER.set_max_level(log::LevelFilter::Trace);
+            let mut mgr = new_mgr();
+            LOGGER.clear_log_calls();
+
+            let layout = mgr.layout;
+            let log_data =
+                new_guest_log_data(LogLevel::Information).with_target("audit".to_string());
+            let guest_log_data_buffer: Vec<u8> = log_data.try_into().unwrap();
+            mgr.get_shared_mem_mut()
+                .push_buffer(
+                    layout.get_output_data_offset(),
+                    sandbox_cfg.get_output_data_size(),
+                    guest_log_data_buffer.as_slice(),
+                )
+                .unwrap();
+
+            outb_log(&mut mgr).unwrap();
+
+            LOGGER.test_log_records(|log_calls| {
+                assert!(log_calls
+                    .iter()
+                    .any(|log_call| log_call.target == "hyperlight-guest::audit"));
+            });
+        }
     }
 
     // Tests that outb_log emits traces when a trace subscriber is set
@@ -349,6 +534,7 @@ mod tests {
                         SandboxMemoryLayout::BASE_ADDRESS,
                         mem_size,
                         false,
+                        &mut SandboxRng::default(),
                     )
                     .unwrap();
                 let (hmgr, _) = mgr.build();
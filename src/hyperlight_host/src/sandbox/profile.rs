@@ -0,0 +1,432 @@
+/*
+Copyright 2024 The Hyperlight Authors.
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+    http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use crate::mem::symbols::GuestSymbols;
+use crate::sandbox::{EventSubscriber, SandboxId};
+
+/// How many times something happened during a guest function call, and how
+/// long it took in total
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ProfileEntry {
+    /// The number of times it happened
+    pub count: u64,
+    /// The total time it took
+    pub duration: Duration,
+}
+
+impl ProfileEntry {
+    fn add(&mut self, duration: Duration) {
+        self.count += 1;
+        self.duration += duration;
+    }
+}
+
+/// Where the time of a guest function call went, recorded when call
+/// profiling is turned on with `MultiUseSandbox::set_call_profiling`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CallProfile {
+    /// The guest function that was called
+    pub function_name: String,
+    /// How long the whole call took
+    pub duration: Duration,
+    /// How many times the vCPU was run, and how long it ran for
+    pub guest: ProfileEntry,
+    /// The exits from the vCPU by reason, such as `io`, `mmio` and `halt`,
+    /// and how long the host took to handle them, including the host
+    /// functions they called
+    pub exits: BTreeMap<String, ProfileEntry>,
+    /// The host functions the guest called, and how long they took
+    pub host_functions: BTreeMap<String, ProfileEntry>,
+    /// How long the host took to write the call into the sandbox and read
+    /// its result back
+    pub serialization: ProfileEntry,
+    /// How long the host took to read the calls to host functions the
+    /// guest made and write their results back, which is part of the time
+    /// taken by `io` exits
+    pub host_function_serialization: ProfileEntry,
+}
+
+/// Where the time of a guest function call went, split between the guest,
+/// the host functions it called and moving values in and out of the
+/// sandbox. The four parts add up to the duration of the call.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CallTimeBreakdown {
+    /// The time the vCPU spent running the guest
+    pub guest: Duration,
+    /// The time spent in the host functions the guest called
+    pub host_functions: Duration,
+    /// The time spent serializing and deserializing the call, the host
+    /// function calls, and their parameters and results
+    pub serialization: Duration,
+    /// The rest, such as handling other exits from the vCPU and getting
+    /// the vCPU to run
+    pub other: Duration,
+}
+
+impl CallProfile {
+    fn new(function_name: &str) -> Self {
+        Self {
+            function_name: function_name.to_string(),
+            duration: Duration::ZERO,
+            guest: ProfileEntry::default(),
+            exits: BTreeMap::new(),
+            host_functions: BTreeMap::new(),
+            serialization: ProfileEntry::default(),
+            host_function_serialization: ProfileEntry::default(),
+        }
+    }
+
+    /// Split the duration of the call between the guest, host functions,
+    /// serialization and everything else
+    pub fn breakdown(&self) -> CallTimeBreakdown {
+        let guest = self.guest.duration;
+        let host_functions = self.host_functions.values().map(|e| e.duration).sum();
+        let serialization = self.serialization.duration + self.host_function_serialization.duration;
+        CallTimeBreakdown {
+            guest,
+            host_functions,
+            serialization,
+            other: self
+                .duration
+                .saturating_sub(guest + host_functions + serialization),
+        }
+    }
+
+    /// The profile in the folded stack format used by flamegraph tools such
+    /// as `inferno-flamegraph`, one line per stack with the time spent in
+    /// that frame itself, in microseconds.
+    ///
+    /// Host functions are called from `io` exits, so their stacks, and
+    /// the time spent serializing their calls, are under the `exit:io`
+    /// frame, and time in the call that wasn't spent in the guest, handling
+    /// exits or serializing the call is in the call's own frame.
+    pub fn to_folded_stacks(&self) -> String {
+        let root = format!("guest_call:{}", self.function_name);
+        let host_functions: Duration = self.host_functions.values().map(|e| e.duration).sum();
+        let exits: Duration = self.exits.values().map(|e| e.duration).sum();
+
+        let mut folded = String::new();
+        let mut line = |stack: &str, duration: Duration| {
+            if !duration.is_zero() {
+                // writing to a String can't fail
+                let _ = writeln!(folded, "{} {}", stack, duration.as_micros());
+            }
+        };
+        line(
+            &root,
+            self.duration
+                .saturating_sub(self.guest.duration + exits + self.serialization.duration),
+        );
+        line(&format!("{};guest", root), self.guest.duration);
+        line(
+            &format!("{};serialization", root),
+            self.serialization.duration,
+        );
+        for (reason, exit) in &self.exits {
+            let duration = match reason.as_str() {
+                "io" => exit
+                    .duration
+                    .saturating_sub(host_functions + self.host_function_serialization.duration),
+                _ => exit.duration,
+            };
+            line(&format!("{};exit:{}", root, reason), duration);
+        }
+        line(
+            &format!("{};exit:io;serialization", root),
+            self.host_function_serialization.duration,
+        );
+        for (name, host_function) in &self.host_functions {
+            line(
+                &format!("{};exit:io;host_function:{}", root, name),
+                host_function.duration,
+            );
+        }
+        folded
+    }
+}
+
+/// Where the guest was executing each time the sampling profiler started
+/// with `MultiUseSandbox::start_sampling_profiler` interrupted it, as
+/// returned by `MultiUseSandbox::take_profile`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct GuestProfile {
+    /// How often the vCPU was interrupted
+    pub interval: Duration,
+    /// The number of samples taken
+    pub samples: u64,
+    /// The number of samples taken at each stack, which is the guest's
+    /// instruction pointer followed by the return addresses of the calls
+    /// in progress, innermost first
+    pub stacks: BTreeMap<Vec<u64>, u64>,
+}
+
+impl GuestProfile {
+    pub(crate) fn new(interval: Duration) -> Self {
+        Self {
+            interval,
+            ..Default::default()
+        }
+    }
+
+    pub(crate) fn record(&mut self, stack: Vec<u64>) {
+        self.samples += 1;
+        *self.stacks.entry(stack).or_default() += 1;
+    }
+
+    /// The profile in the folded stack format used by flamegraph tools such
+    /// as `inferno-flamegraph`, one line per stack, outermost frame first,
+    /// with the number of samples taken there. Addresses are translated to
+    /// `function+offset` with `symbols`.
+    pub fn to_folded_stacks(&self, symbols: &GuestSymbols) -> String {
+        let mut folded = String::new();
+        for (stack, count) in &self.stacks {
+            let frames: Vec<String> = stack
+                .iter()
+                .rev()
+                .map(|address| symbols.symbolize(*address))
+                .collect();
+            // writing to a String can't fail
+            let _ = writeln!(folded, "{} {}", frames.join(";"), count);
+        }
+        folded
+    }
+}
+
+/// Where the time it took to create a sandbox went, as returned by
+/// `Sandbox::creation_report`. The stages that run when the sandbox is
+/// evolved take no time until it is, or, if it is lazily initialized, until
+/// its guest is initialized.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CreationReport {
+    /// Reading the guest binary, checking its signature and parsing it
+    pub binary_load: Duration,
+    /// Mapping the sandbox's memory and loading the guest binary into it
+    pub memory_map: Duration,
+    /// Building the guest's page tables
+    pub page_tables: Duration,
+    /// Creating the hypervisor partition, or VM, and its vCPU
+    pub partition: Duration,
+    /// Running the guest's entrypoint
+    pub entrypoint: Duration,
+}
+
+impl CreationReport {
+    /// The time all the stages took
+    pub fn total(&self) -> Duration {
+        self.binary_load + self.memory_map + self.page_tables + self.partition + self.entrypoint
+    }
+}
+
+#[derive(Default)]
+struct ProfilerState {
+    /// The call being profiled
+    call: Option<CallProfile>,
+    last: Option<CallProfile>,
+}
+
+/// Records a `CallProfile` for each guest function call while call
+/// profiling is turned on. It is always subscribed to the sandbox's events,
+/// and is told about vCPU runs and exits by `VirtualCPU::run`.
+#[derive(Default)]
+pub(crate) struct CallProfiler {
+    enabled: AtomicBool,
+    state: Mutex<ProfilerState>,
+}
+
+impl CallProfiler {
+    pub(crate) fn set_enabled(&self, enabled: bool) {
+        self.enabled.store(enabled, Ordering::Relaxed);
+        if !enabled {
+            self.state().call = None;
+        }
+    }
+
+    /// The profile of the last guest function call that finished while
+    /// profiling was turned on
+    pub(crate) fn last_profile(&self) -> Option<CallProfile> {
+        self.state().last.clone()
+    }
+
+    /// Record that the vCPU ran for `duration`
+    pub(crate) fn record_guest_run(&self, duration: Duration) {
+        if !self.enabled.load(Ordering::Relaxed) {
+            return;
+        }
+        if let Some(call) = &mut self.state().call {
+            call.guest.add(duration);
+        }
+    }
+
+    /// Record that the host spent `duration` serializing or deserializing
+    /// the call, or a call the guest made to a host function if
+    /// `host_function` is set
+    pub(crate) fn record_serialization(&self, duration: Duration, host_function: bool) {
+        if !self.enabled.load(Ordering::Relaxed) {
+            return;
+        }
+        if let Some(call) = &mut self.state().call {
+            if host_function {
+                call.host_function_serialization.add(duration);
+            } else {
+                call.serialization.add(duration);
+            }
+        }
+    }
+
+    /// Start timing the handling of an exit from the vCPU, which is recorded
+    /// when the returned timer is dropped
+    pub(crate) fn time_exit(self: &Arc<Self>, reason: &'static str) -> Option<ExitTimer> {
+        if !self.enabled.load(Ordering::Relaxed) {
+            return None;
+        }
+        Some(ExitTimer {
+            profiler: self.clone(),
+            reason,
+            start: Instant::now(),
+        })
+    }
+
+    fn state(&self) -> std::sync::MutexGuard<'_, ProfilerState> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// Records how long the host took to handle an exit from the vCPU when it
+/// is dropped
+pub(crate) struct ExitTimer {
+    profiler: Arc<CallProfiler>,
+    reason: &'static str,
+    start: Instant,
+}
+
+impl Drop for ExitTimer {
+    fn drop(&mut self) {
+        if let Some(call) = &mut self.profiler.state().call {
+            call.exits
+                .entry(self.reason.to_string())
+                .or_default()
+                .add(self.start.elapsed());
+        }
+    }
+}
+
+impl EventSubscriber for CallProfiler {
+    fn on_guest_call_started(&self, _sandbox_id: SandboxId, function_name: &str) {
+        if self.enabled.load(Ordering::Relaxed) {
+            self.state().call = Some(CallProfile::new(function_name));
+        }
+    }
+
+    fn on_guest_call_finished(
+        &self,
+        _sandbox_id: SandboxId,
+        _function_name: &str,
+        elapsed: Duration,
+    ) {
+        let mut state = self.state();
+        if let Some(mut call) = state.call.take() {
+            call.duration = elapsed;
+            state.last = Some(call);
+        }
+    }
+
+    fn on_host_function_finished(
+        &self,
+        _sandbox_id: SandboxId,
+        function_name: &str,
+        elapsed: Duration,
+    ) {
+        if let Some(call) = &mut self.state().call {
+            call.host_functions
+                .entry(function_name.to_string())
+                .or_default()
+                .add(elapsed);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sampled_folded_stacks() {
+        let mut profile = GuestProfile::new(Duration::from_millis(1));
+        profile.record(vec![0x1010, 0x2020]);
+        profile.record(vec![0x1010, 0x2020]);
+        profile.record(vec![0x3000]);
+        assert_eq!(profile.samples, 3);
+
+        assert_eq!(
+            profile.to_folded_stacks(&GuestSymbols::default()),
+            "0x2020;0x1010 2\n0x3000 1\n"
+        );
+    }
+
+    #[test]
+    fn folded_stacks() {
+        let mut profile = CallProfile::new("Echo");
+        profile.duration = Duration::from_micros(1000);
+        profile.guest.add(Duration::from_micros(300));
+        profile.guest.add(Duration::from_micros(200));
+        profile
+            .exits
+            .entry("io".to_string())
+            .or_default()
+            .add(Duration::from_micros(350));
+        profile
+            .exits
+            .entry("halt".to_string())
+            .or_default()
+            .add(Duration::from_micros(50));
+        profile
+            .host_functions
+            .entry("HostPrint".to_string())
+            .or_default()
+            .add(Duration::from_micros(250));
+
+        profile.serialization.add(Duration::from_micros(40));
+        profile
+            .host_function_serialization
+            .add(Duration::from_micros(30));
+
+        assert_eq!(
+            profile.to_folded_stacks(),
+            "guest_call:Echo 60\n\
+             guest_call:Echo;guest 500\n\
+             guest_call:Echo;serialization 40\n\
+             guest_call:Echo;exit:halt 50\n\
+             guest_call:Echo;exit:io 70\n\
+             guest_call:Echo;exit:io;serialization 30\n\
+             guest_call:Echo;exit:io;host_function:HostPrint 250\n"
+        );
+        assert_eq!(
+            profile.breakdown(),
+            CallTimeBreakdown {
+                guest: Duration::from_micros(500),
+                host_functions: Duration::from_micros(250),
+                serialization: Duration::from_micros(70),
+                other: Duration::from_micros(180),
+            }
+        );
+    }
+}
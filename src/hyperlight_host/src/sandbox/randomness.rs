@@ -0,0 +1,70 @@
+/*
+Copyright 2024 The Hyperlight Authors.
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+    http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+use rand::rngs::{OsRng, StdRng};
+use rand::{RngCore, SeedableRng};
+
+/// Where a sandbox's random values, such as its id and the cookies that
+/// guard its stack, come from.
+///
+/// They come from the OS unless the sandbox was configured with
+/// `SandboxConfiguration::set_deterministic_seed`, in which case they come
+/// from a generator seeded with that seed, and so are the same every time
+/// a sandbox is created with it by the same build of Hyperlight.
+#[derive(Debug, Default)]
+pub(crate) struct SandboxRng(Option<StdRng>);
+
+impl SandboxRng {
+    /// Generate values from `seed`, or from the OS if it's `None`
+    pub(crate) fn new(seed: Option<u64>) -> Self {
+        Self(seed.map(StdRng::seed_from_u64))
+    }
+
+    pub(crate) fn fill_bytes(&mut self, bytes: &mut [u8]) {
+        match &mut self.0 {
+            Some(rng) => rng.fill_bytes(bytes),
+            None => OsRng.fill_bytes(bytes),
+        }
+    }
+
+    pub(crate) fn bytes<const N: usize>(&mut self) -> [u8; N] {
+        let mut bytes = [0; N];
+        self.fill_bytes(&mut bytes);
+        bytes
+    }
+
+    pub(crate) fn next_u64(&mut self) -> u64 {
+        u64::from_le_bytes(self.bytes())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::SandboxRng;
+
+    #[test]
+    fn seeded_values_repeat() {
+        let mut first = SandboxRng::new(Some(42));
+        let mut second = SandboxRng::new(Some(42));
+        assert_eq!(first.bytes::<32>(), second.bytes::<32>());
+        assert_eq!(first.next_u64(), second.next_u64());
+        assert_ne!(
+            SandboxRng::new(Some(42)).next_u64(),
+            SandboxRng::new(Some(43)).next_u64()
+        );
+        assert_ne!(SandboxRng::new(None).bytes::<32>(), [0; 32]);
+    }
+}
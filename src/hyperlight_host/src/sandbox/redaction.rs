@@ -0,0 +1,139 @@
+/*
+Copyright 2024 The Hyperlight Authors.
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+    http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+use hmac::{Hmac, Mac};
+use hyperlight_common::flatbuffer_wrappers::function_types::{ParameterType, ParameterValue};
+use once_cell::sync::Lazy;
+use rand::rngs::OsRng;
+use rand::RngCore;
+use sha2::Sha256;
+
+#[cfg(crashdump)]
+use crate::mem::memory_region::MemoryRegionType;
+
+/// The key parameters are hashed with for the audit log. It's random and
+/// never leaves the process, so the hashes of guessable parameters, such
+/// as small numbers, can't be reversed by hashing every guess.
+static AUDIT_KEY: Lazy<[u8; 32]> = Lazy::new(|| {
+    let mut key = [0; 32];
+    OsRng.fill_bytes(&mut key);
+    key
+});
+
+/// What crash dumps and the audit log leave out, as set with
+/// `SandboxConfiguration::set_exclude_io_buffers_from_crashdumps`,
+/// `SandboxConfiguration::set_log_audited_parameter_values` and
+/// `SandboxConfiguration::set_hash_audited_parameters`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub(crate) struct RedactionPolicy {
+    /// Whether crash dumps have zeros in place of the input and output
+    /// buffers
+    pub(crate) exclude_io_buffers: bool,
+    /// Whether the audit log has the value of each parameter rather than
+    /// only its type
+    pub(crate) log_parameter_values: bool,
+    /// Whether the audit log has a keyed hash of each parameter in place
+    /// of its value
+    pub(crate) hash_parameters: bool,
+}
+
+impl RedactionPolicy {
+    /// Whether crash dumps leave out the contents of regions of
+    /// `region_type`
+    #[cfg(crashdump)]
+    pub(crate) fn excludes_region(&self, region_type: MemoryRegionType) -> bool {
+        self.exclude_io_buffers
+            && matches!(
+                region_type,
+                MemoryRegionType::InputData | MemoryRegionType::OutputData
+            )
+    }
+
+    /// Describe the parameters of a function call for the audit log, by
+    /// default only by their types
+    pub(crate) fn describe_parameters(&self, args: &[ParameterValue]) -> String {
+        let described: Vec<String> = args
+            .iter()
+            .map(|arg| match arg {
+                _ if !self.log_parameter_values && !self.hash_parameters => {
+                    format!("{:?}", ParameterType::from(arg))
+                }
+                // every secret would hash the same, which would give away
+                // nothing but is no use either
+                ParameterValue::Secret(_) => format!("{:?}", arg),
+                _ if self.hash_parameters => {
+                    let mut mac = Hmac::<Sha256>::new_from_slice(AUDIT_KEY.as_slice())
+                        .expect("HMAC accepts any key length");
+                    mac.update(format!("{:?}", arg).as_bytes());
+                    let digest = mac.finalize().into_bytes();
+                    let hex: String = digest.iter().map(|b| format!("{:02x}", b)).collect();
+                    format!("hmac-sha256:{}", hex)
+                }
+                _ => format!("{:?}", arg),
+            })
+            .collect();
+        format!("[{}]", described.join(", "))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use hyperlight_common::flatbuffer_wrappers::function_types::ParameterValue;
+    use hyperlight_common::secret::Secret;
+    use sha2::Digest;
+
+    use super::RedactionPolicy;
+
+    #[test]
+    fn describe_parameters() {
+        let args = [
+            ParameterValue::String("alice@example.com".to_string()),
+            ParameterValue::Int(42),
+            ParameterValue::Secret(Secret::new(b"hunter2".to_vec())),
+        ];
+
+        let policy = RedactionPolicy::default();
+        assert_eq!(policy.describe_parameters(&args), "[String, Int, Secret]");
+
+        let policy = RedactionPolicy {
+            log_parameter_values: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            policy.describe_parameters(&args),
+            r#"[String("alice@example.com"), Int(42), Secret(Secret(<redacted>))]"#
+        );
+
+        let policy = RedactionPolicy {
+            hash_parameters: true,
+            ..Default::default()
+        };
+        let described = policy.describe_parameters(&args);
+        assert!(!described.contains("alice"));
+        assert!(!described.contains("Int("));
+        // keyed, so a plain hash of a guessed value doesn't match it
+        let unkeyed = sha2::Sha256::digest(r#"String("alice@example.com")"#);
+        let unkeyed: String = unkeyed.iter().map(|b| format!("{:02x}", b)).collect();
+        assert!(!described.contains(&unkeyed));
+        assert!(described.ends_with("Secret(Secret(<redacted>))]"));
+        // the same arguments always hash the same
+        assert_eq!(described, policy.describe_parameters(&args));
+        assert_ne!(
+            described,
+            policy.describe_parameters(&[ParameterValue::Int(43)])
+        );
+    }
+}
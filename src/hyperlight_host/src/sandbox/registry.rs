@@ -0,0 +1,185 @@
+/*
+Copyright 2024 The Hyperlight Authors.
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+    http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+use std::collections::HashMap;
+use std::fmt::{Display, Formatter};
+use std::sync::{Mutex, MutexGuard};
+use std::time::SystemTime;
+
+use once_cell::sync::Lazy;
+use uuid::Uuid;
+
+use crate::sandbox::randomness::SandboxRng;
+use crate::sandbox::GuestInfo;
+
+/// The sandboxes that are alive in this process
+static SANDBOXES: Lazy<Mutex<HashMap<SandboxId, SandboxInfo>>> =
+    Lazy::new(|| Mutex::new(HashMap::new()));
+
+/// Identifies a sandbox. A sandbox keeps its id from when it is created
+/// until it is dropped, through `evolve` and `devolve`, and ids are never
+/// reused.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct SandboxId(Uuid);
+
+impl SandboxId {
+    pub(crate) fn new(rng: &mut SandboxRng) -> Self {
+        Self(uuid::Builder::from_random_bytes(rng.bytes()).into_uuid())
+    }
+
+    /// The id as a UUID
+    pub fn as_uuid(&self) -> Uuid {
+        self.0
+    }
+}
+
+impl Display for SandboxId {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+/// What the `SandboxRegistry` knows about a live sandbox
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SandboxInfo {
+    /// The sandbox's id
+    pub id: SandboxId,
+    /// The name the sandbox was given with `UninitializedSandbox::set_name`,
+    /// if any
+    pub name: Option<String>,
+    /// The `GuestInfo` of the sandbox's guest binary
+    pub guest_info: GuestInfo,
+    /// When the sandbox was created
+    pub created_at: SystemTime,
+}
+
+/// The process-wide list of live sandboxes, for tools that need to
+/// enumerate or look up the sandboxes a process is running.
+///
+/// Sandboxes are added to the registry when they are created and removed
+/// when they are dropped.
+pub struct SandboxRegistry;
+
+impl SandboxRegistry {
+    /// All the live sandboxes, oldest first
+    pub fn live() -> Vec<SandboxInfo> {
+        let mut sandboxes: Vec<SandboxInfo> = Self::lock().values().cloned().collect();
+        sandboxes.sort_by_key(|info| (info.created_at, info.id));
+        sandboxes
+    }
+
+    /// The sandbox with the given id, if it is still alive
+    pub fn get(id: SandboxId) -> Option<SandboxInfo> {
+        Self::lock().get(&id).cloned()
+    }
+
+    /// The live sandboxes with the given name, oldest first
+    pub fn find_by_name(name: &str) -> Vec<SandboxInfo> {
+        let mut sandboxes = Self::live();
+        sandboxes.retain(|info| info.name.as_deref() == Some(name));
+        sandboxes
+    }
+
+    /// The number of live sandboxes
+    pub fn len() -> usize {
+        Self::lock().len()
+    }
+
+    fn lock() -> MutexGuard<'static, HashMap<SandboxId, SandboxInfo>> {
+        // the map is always left consistent, so a panic while it was
+        // locked doesn't matter
+        SANDBOXES.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// Keeps a sandbox in the `SandboxRegistry` until it is dropped. It is
+/// handed from one sandbox type to the next as the sandbox evolves.
+#[derive(Debug)]
+pub(crate) struct SandboxRegistration {
+    id: SandboxId,
+}
+
+impl SandboxRegistration {
+    /// Add a new sandbox running the guest described by `guest_info` to
+    /// the registry, with an id generated by `rng`
+    pub(crate) fn new(guest_info: GuestInfo, rng: &mut SandboxRng) -> Self {
+        let id = SandboxId::new(rng);
+        let info = SandboxInfo {
+            id,
+            name: None,
+            guest_info,
+            created_at: SystemTime::now(),
+        };
+        SandboxRegistry::lock().insert(id, info);
+        Self { id }
+    }
+
+    pub(crate) fn id(&self) -> SandboxId {
+        self.id
+    }
+
+    pub(crate) fn name(&self) -> Option<String> {
+        SandboxRegistry::lock()
+            .get(&self.id)
+            .and_then(|info| info.name.clone())
+    }
+
+    pub(crate) fn set_name(&self, name: String) {
+        log::info!(target: "hyperlight_host::audit", "Named sandbox {} {:?}", self.id, name);
+        if let Some(info) = SandboxRegistry::lock().get_mut(&self.id) {
+            info.name = Some(name);
+        }
+    }
+}
+
+impl Drop for SandboxRegistration {
+    fn drop(&mut self) {
+        SandboxRegistry::lock().remove(&self.id);
+        log::info!(target: "hyperlight_host::audit", "Dropped sandbox {}", self.id);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn registrations_are_live_until_dropped() {
+        let guest_info = GuestInfo::new(&[1, 2, 3], None, None);
+        let mut rng = SandboxRng::default();
+        let first = SandboxRegistration::new(guest_info.clone(), &mut rng);
+        let second = SandboxRegistration::new(guest_info, &mut rng);
+        assert_ne!(first.id(), second.id());
+
+        let name = format!("registry-test-{}", first.id());
+        first.set_name(name.clone());
+        assert_eq!(first.name(), Some(name.clone()));
+        assert_eq!(second.name(), None);
+
+        let named = SandboxRegistry::find_by_name(&name);
+        assert_eq!(named.len(), 1);
+        assert_eq!(named[0].id, first.id());
+
+        let live: Vec<SandboxId> = SandboxRegistry::live().iter().map(|i| i.id).collect();
+        assert!(live.contains(&first.id()) && live.contains(&second.id()));
+
+        let id = first.id();
+        drop(first);
+        assert_eq!(SandboxRegistry::get(id), None);
+        assert!(SandboxRegistry::get(second.id()).is_some());
+        assert!(SandboxRegistry::find_by_name(&name).is_empty());
+    }
+}
@@ -0,0 +1,403 @@
+/*
+Copyright 2024 The Hyperlight Authors.
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+    http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+use std::collections::{HashMap, HashSet};
+use std::time::{Duration, Instant};
+
+use hyperlight_common::flatbuffer_wrappers::function_types::{
+    ParameterValue, ReturnType, ReturnValue,
+};
+use tracing::{instrument, Span};
+
+/// Which guest functions a `MultiUseSandbox` remembers the results of, so
+/// that calling one of them again with the same arguments returns the
+/// remembered result without entering the guest.
+///
+/// Only functions declared pure are cached: their result must depend on
+/// nothing but their arguments, and they must not call host functions
+/// whose effects matter, as those calls are skipped when the result is
+/// cached. Failed calls are never cached.
+#[derive(Debug, Clone)]
+pub struct ResultCachePolicy {
+    capacity: usize,
+    pure_functions: HashSet<String>,
+    all_pure: bool,
+    ttl: Option<Duration>,
+}
+
+impl ResultCachePolicy {
+    /// Create a policy that remembers at most `capacity` results, dropping
+    /// the least recently used when it is full. No function is cached
+    /// until it is declared pure.
+    #[instrument(skip_all, parent = Span::current(), level = "Trace")]
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            pure_functions: HashSet::new(),
+            all_pure: false,
+            ttl: None,
+        }
+    }
+
+    /// Declare the guest function `name` pure, so that its results are
+    /// cached
+    #[instrument(skip_all, parent = Span::current(), level = "Trace")]
+    pub fn with_pure_function(mut self, name: impl Into<String>) -> Self {
+        self.pure_functions.insert(name.into());
+        self
+    }
+
+    /// Declare every function of the guest pure
+    #[instrument(skip_all, parent = Span::current(), level = "Trace")]
+    pub fn with_all_functions_pure(mut self) -> Self {
+        self.all_pure = true;
+        self
+    }
+
+    /// Forget results `ttl` after they were cached
+    #[instrument(skip_all, parent = Span::current(), level = "Trace")]
+    pub fn with_ttl(mut self, ttl: Duration) -> Self {
+        self.ttl = Some(ttl);
+        self
+    }
+
+    fn is_pure(&self, name: &str) -> bool {
+        self.all_pure || self.pure_functions.contains(name)
+    }
+}
+
+/// How well the result cache of a sandbox has done
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ResultCacheStats {
+    /// Calls answered from the cache
+    pub hits: u64,
+    /// Calls to pure functions that had to enter the guest
+    pub misses: u64,
+    /// The number of results in the cache
+    pub entries: usize,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct Key {
+    function: String,
+    return_type: u8,
+    /// The arguments, written out so that every distinct list of
+    /// arguments has distinct bytes
+    args: Vec<u8>,
+}
+
+#[derive(Debug)]
+struct Entry {
+    value: ReturnValue,
+    cached_at: Instant,
+    last_used: u64,
+}
+
+/// The results a sandbox has remembered under a `ResultCachePolicy`
+#[derive(Debug)]
+pub(crate) struct ResultCache {
+    policy: ResultCachePolicy,
+    entries: HashMap<Key, Entry>,
+    /// Incremented on every lookup, to tell which entry was used least
+    /// recently
+    clock: u64,
+    hits: u64,
+    misses: u64,
+}
+
+impl ResultCache {
+    pub(crate) fn new(policy: ResultCachePolicy) -> Self {
+        Self {
+            policy,
+            entries: HashMap::new(),
+            clock: 0,
+            hits: 0,
+            misses: 0,
+        }
+    }
+
+    /// The key to cache a call under, or `None` if the function isn't pure
+    fn key(
+        &self,
+        function: &str,
+        return_type: ReturnType,
+        args: &Option<Vec<ParameterValue>>,
+    ) -> Option<Key> {
+        if self.policy.capacity == 0 || !self.policy.is_pure(function) {
+            return None;
+        }
+        // a secret would outlive the call in the key, so calls passing
+        // one are never cached
+        if args
+            .iter()
+            .flatten()
+            .any(|arg| matches!(arg, ParameterValue::Secret(_)))
+        {
+            return None;
+        }
+        let mut bytes = Vec::new();
+        for arg in args.iter().flatten() {
+            encode(arg, &mut bytes);
+        }
+        Some(Key {
+            function: function.to_string(),
+            return_type: return_type as u8,
+            args: bytes,
+        })
+    }
+
+    /// Return the cached result of calling `function` with `args`, or call
+    /// it with `call` and cache what it returns
+    pub(crate) fn get_or_call(
+        &mut self,
+        function: &str,
+        return_type: ReturnType,
+        args: Option<Vec<ParameterValue>>,
+        call: impl FnOnce(Option<Vec<ParameterValue>>) -> crate::Result<ReturnValue>,
+    ) -> crate::Result<ReturnValue> {
+        let Some(key) = self.key(function, return_type, &args) else {
+            return call(args);
+        };
+
+        self.clock += 1;
+        if let Some(entry) = self.entries.get_mut(&key) {
+            let expired = self
+                .policy
+                .ttl
+                .is_some_and(|ttl| entry.cached_at.elapsed() >= ttl);
+            if !expired {
+                entry.last_used = self.clock;
+                self.hits += 1;
+                return Ok(entry.value.clone());
+            }
+            self.entries.remove(&key);
+        }
+
+        self.misses += 1;
+        let value = call(args)?;
+        if self.entries.len() >= self.policy.capacity {
+            let least_recently_used = self
+                .entries
+                .iter()
+                .min_by_key(|(_, entry)| entry.last_used)
+                .map(|(key, _)| key.clone());
+            if let Some(key) = least_recently_used {
+                self.entries.remove(&key);
+            }
+        }
+        self.entries.insert(
+            key,
+            Entry {
+                value: value.clone(),
+                cached_at: Instant::now(),
+                last_used: self.clock,
+            },
+        );
+        Ok(value)
+    }
+
+    pub(crate) fn clear(&mut self) {
+        self.entries.clear();
+    }
+
+    pub(crate) fn stats(&self) -> ResultCacheStats {
+        ResultCacheStats {
+            hits: self.hits,
+            misses: self.misses,
+            entries: self.entries.len(),
+        }
+    }
+}
+
+/// Append `arg` to `bytes`, tagged with its type and, for strings and
+/// byte vectors, its length
+fn encode(arg: &ParameterValue, bytes: &mut Vec<u8>) {
+    match arg {
+        ParameterValue::Int(v) => {
+            bytes.push(0);
+            bytes.extend_from_slice(&v.to_le_bytes());
+        }
+        ParameterValue::UInt(v) => {
+            bytes.push(1);
+            bytes.extend_from_slice(&v.to_le_bytes());
+        }
+        ParameterValue::Long(v) => {
+            bytes.push(2);
+            bytes.extend_from_slice(&v.to_le_bytes());
+        }
+        ParameterValue::ULong(v) => {
+            bytes.push(3);
+            bytes.extend_from_slice(&v.to_le_bytes());
+        }
+        ParameterValue::Float(v) => {
+            bytes.push(4);
+            bytes.extend_from_slice(&v.to_bits().to_le_bytes());
+        }
+        ParameterValue::Double(v) => {
+            bytes.push(5);
+            bytes.extend_from_slice(&v.to_bits().to_le_bytes());
+        }
+        ParameterValue::String(v) => {
+            bytes.push(6);
+            bytes.extend_from_slice(&(v.len() as u64).to_le_bytes());
+            bytes.extend_from_slice(v.as_bytes());
+        }
+        ParameterValue::Bool(v) => {
+            bytes.push(7);
+            bytes.push(*v as u8);
+        }
+        ParameterValue::VecBytes(v) => {
+            bytes.push(8);
+            bytes.extend_from_slice(&(v.len() as u64).to_le_bytes());
+            bytes.extend_from_slice(v);
+        }
+        // calls passing secrets aren't cached, see `ResultCache::key`
+        ParameterValue::Secret(_) => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::Cell;
+
+    use hyperlight_common::secret::Secret;
+
+    use super::*;
+    use crate::new_error;
+
+    fn call(
+        cache: &mut ResultCache,
+        calls: &Cell<u32>,
+        function: &str,
+        args: Vec<ParameterValue>,
+    ) -> crate::Result<ReturnValue> {
+        cache.get_or_call(function, ReturnType::Int, Some(args), |_| {
+            calls.set(calls.get() + 1);
+            Ok(ReturnValue::Int(calls.get() as i32))
+        })
+    }
+
+    #[test]
+    fn caches_pure_functions() {
+        let mut cache = ResultCache::new(ResultCachePolicy::new(8).with_pure_function("Pure"));
+        let calls = Cell::new(0);
+        let args = || vec![ParameterValue::String("a".to_string())];
+
+        let first = call(&mut cache, &calls, "Pure", args()).unwrap();
+        assert_eq!(call(&mut cache, &calls, "Pure", args()).unwrap(), first);
+        assert_eq!(calls.get(), 1);
+        call(&mut cache, &calls, "Impure", args()).unwrap();
+        call(&mut cache, &calls, "Impure", args()).unwrap();
+        assert_eq!(calls.get(), 3);
+        assert_eq!(
+            cache.stats(),
+            ResultCacheStats {
+                hits: 1,
+                misses: 1,
+                entries: 1
+            }
+        );
+
+        cache.clear();
+        call(&mut cache, &calls, "Pure", args()).unwrap();
+        assert_eq!(calls.get(), 4);
+    }
+
+    #[test]
+    fn distinguishes_arguments() {
+        let mut cache = ResultCache::new(ResultCachePolicy::new(8).with_all_functions_pure());
+        let calls = Cell::new(0);
+        // the same bytes, split differently between strings
+        call(
+            &mut cache,
+            &calls,
+            "F",
+            vec![
+                ParameterValue::String("ab".to_string()),
+                ParameterValue::String("c".to_string()),
+            ],
+        )
+        .unwrap();
+        call(
+            &mut cache,
+            &calls,
+            "F",
+            vec![
+                ParameterValue::String("a".to_string()),
+                ParameterValue::String("bc".to_string()),
+            ],
+        )
+        .unwrap();
+        call(&mut cache, &calls, "F", vec![ParameterValue::Int(1)]).unwrap();
+        call(&mut cache, &calls, "F", vec![ParameterValue::UInt(1)]).unwrap();
+        assert_eq!(calls.get(), 4);
+
+        let res = cache.get_or_call(
+            "F",
+            ReturnType::UInt,
+            Some(vec![ParameterValue::Int(1)]),
+            |_| Ok(ReturnValue::UInt(1)),
+        );
+        assert_eq!(res.unwrap(), ReturnValue::UInt(1));
+    }
+
+    #[test]
+    fn does_not_cache_secrets() {
+        let mut cache = ResultCache::new(ResultCachePolicy::new(8).with_all_functions_pure());
+        let calls = Cell::new(0);
+        let args = || vec![ParameterValue::Secret(Secret::new(b"key".to_vec()))];
+        call(&mut cache, &calls, "F", args()).unwrap();
+        call(&mut cache, &calls, "F", args()).unwrap();
+        assert_eq!(calls.get(), 2);
+        assert_eq!(cache.stats().entries, 0);
+    }
+
+    #[test]
+    fn evicts_least_recently_used() {
+        let mut cache = ResultCache::new(ResultCachePolicy::new(2).with_all_functions_pure());
+        let calls = Cell::new(0);
+        call(&mut cache, &calls, "A", vec![]).unwrap();
+        call(&mut cache, &calls, "B", vec![]).unwrap();
+        call(&mut cache, &calls, "A", vec![]).unwrap();
+        call(&mut cache, &calls, "C", vec![]).unwrap();
+        assert_eq!(calls.get(), 3);
+        assert_eq!(cache.stats().entries, 2);
+        call(&mut cache, &calls, "A", vec![]).unwrap();
+        assert_eq!(calls.get(), 3);
+        call(&mut cache, &calls, "B", vec![]).unwrap();
+        assert_eq!(calls.get(), 4);
+    }
+
+    #[test]
+    fn does_not_cache_errors_or_expired_results() {
+        let mut cache = ResultCache::new(
+            ResultCachePolicy::new(8)
+                .with_all_functions_pure()
+                .with_ttl(Duration::ZERO),
+        );
+        let res = cache.get_or_call("F", ReturnType::Int, None, |_| {
+            Err(new_error!("the guest function failed"))
+        });
+        assert!(res.is_err());
+        assert_eq!(cache.stats().entries, 0);
+
+        let calls = Cell::new(0);
+        call(&mut cache, &calls, "F", vec![]).unwrap();
+        call(&mut cache, &calls, "F", vec![]).unwrap();
+        assert_eq!(calls.get(), 2);
+        assert_eq!(cache.stats().hits, 0);
+    }
+}
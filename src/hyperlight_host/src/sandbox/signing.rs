@@ -0,0 +1,182 @@
+/*
+Copyright 2024 The Hyperlight Authors.
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+    http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+//! Signing guest binaries, so that hosts can refuse to run guests that
+//! weren't published by someone they trust, see
+//! `SandboxConfiguration::set_require_signed_guests`.
+//!
+//! Guest binaries are signed with Ed25519 over the whole image. The
+//! signature is either embedded, appended to the image followed by
+//! `SIGNATURE_MAGIC`, or detached, in a file next to the binary with
+//! `.sig` added to its name.
+
+use ed25519_dalek::{Signature, Signer, SigningKey, Verifier, VerifyingKey};
+use tracing::{instrument, Span};
+
+use crate::error::HyperlightError::GuestSignatureInvalid;
+pub use crate::sandbox::config::GUEST_PUBLIC_KEY_LEN;
+use crate::{log_then_return, Result};
+
+/// The length of the Ed25519 secret keys guests are signed with
+pub const GUEST_SIGNING_KEY_LEN: usize = 32;
+
+/// The length of a guest signature
+pub const GUEST_SIGNATURE_LEN: usize = 64;
+
+/// Follows the signature at the end of a guest binary with an embedded
+/// signature
+pub const SIGNATURE_MAGIC: &[u8; 8] = b"HLGSIG01";
+
+/// The extension added to the path of a guest binary to find its detached
+/// signature
+pub const DETACHED_SIGNATURE_EXTENSION: &str = "sig";
+
+/// The public key that guests signed with `signing_key` are verified with
+pub fn guest_public_key(signing_key: &[u8; GUEST_SIGNING_KEY_LEN]) -> [u8; GUEST_PUBLIC_KEY_LEN] {
+    SigningKey::from_bytes(signing_key)
+        .verifying_key()
+        .to_bytes()
+}
+
+/// Sign `image` with `signing_key`, returning the signature to store in a
+/// detached signature file
+pub fn sign_guest_binary_detached(
+    image: &[u8],
+    signing_key: &[u8; GUEST_SIGNING_KEY_LEN],
+) -> [u8; GUEST_SIGNATURE_LEN] {
+    SigningKey::from_bytes(signing_key).sign(image).to_bytes()
+}
+
+/// Sign `image` with `signing_key`, returning the image with the signature
+/// embedded in it
+pub fn sign_guest_binary(image: &[u8], signing_key: &[u8; GUEST_SIGNING_KEY_LEN]) -> Vec<u8> {
+    let mut signed = Vec::with_capacity(image.len() + GUEST_SIGNATURE_LEN + SIGNATURE_MAGIC.len());
+    signed.extend_from_slice(image);
+    signed.extend_from_slice(&sign_guest_binary_detached(image, signing_key));
+    signed.extend_from_slice(SIGNATURE_MAGIC);
+    signed
+}
+
+/// Split a signed image into the image that was signed and its embedded
+/// signature, if it has one
+fn split_embedded_signature(image: &[u8]) -> Option<(&[u8], &[u8])> {
+    let rest = image.strip_suffix(SIGNATURE_MAGIC)?;
+    let split = rest.len().checked_sub(GUEST_SIGNATURE_LEN)?;
+    Some(rest.split_at(split))
+}
+
+/// Check that `image` was signed by one of `trusted_keys`, either with a
+/// signature embedded in the image or with `detached_signature`
+#[instrument(err(Debug), skip_all, parent = Span::current(), level = "Trace")]
+pub(crate) fn verify_guest_image(
+    image: &[u8],
+    detached_signature: Option<&[u8]>,
+    trusted_keys: &[[u8; GUEST_PUBLIC_KEY_LEN]],
+) -> Result<()> {
+    let (signed, signature) = match (split_embedded_signature(image), detached_signature) {
+        (Some(embedded), _) => embedded,
+        (None, Some(detached)) => (image, detached),
+        (None, None) => {
+            log_then_return!(GuestSignatureInvalid(
+                "the guest binary is not signed".into()
+            ));
+        }
+    };
+    let signature = Signature::from_slice(signature)
+        .map_err(|e| GuestSignatureInvalid(format!("malformed signature: {}", e)))?;
+
+    for key in trusted_keys {
+        // keys that aren't valid points can't have signed anything
+        let Ok(key) = VerifyingKey::from_bytes(key) else {
+            continue;
+        };
+        if key.verify(signed, &signature).is_ok() {
+            return Ok(());
+        }
+    }
+
+    log_then_return!(GuestSignatureInvalid(
+        "the guest binary is not signed by a trusted key".into()
+    ));
+}
+
+/// Check that `image`, the guest binary read from `path` if it was loaded
+/// from a file, was signed by one of `trusted_keys`, looking for a detached
+/// signature next to the file if it doesn't have one embedded.
+///
+/// The bytes that were checked are what must be loaded, as the file could
+/// be replaced between being checked and being read again.
+#[instrument(err(Debug), skip_all, parent = Span::current(), level = "Trace")]
+pub(crate) fn verify_guest_binary(
+    image: &[u8],
+    path: Option<&str>,
+    trusted_keys: &[[u8; GUEST_PUBLIC_KEY_LEN]],
+) -> Result<()> {
+    let detached_signature = path
+        .and_then(|path| std::fs::read(format!("{}.{}", path, DETACHED_SIGNATURE_EXTENSION)).ok());
+    verify_guest_image(image, detached_signature.as_deref(), trusted_keys)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::HyperlightError;
+
+    const KEY: [u8; GUEST_SIGNING_KEY_LEN] = [1; GUEST_SIGNING_KEY_LEN];
+    const OTHER_KEY: [u8; GUEST_SIGNING_KEY_LEN] = [2; GUEST_SIGNING_KEY_LEN];
+
+    fn assert_invalid(result: Result<()>) {
+        match result {
+            Err(HyperlightError::GuestSignatureInvalid(_)) => {}
+            other => panic!("Expected GuestSignatureInvalid but got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn embedded_signatures() {
+        let image = b"not really an elf file".to_vec();
+        let signed = sign_guest_binary(&image, &KEY);
+        let trusted = [guest_public_key(&OTHER_KEY), guest_public_key(&KEY)];
+
+        verify_guest_image(&signed, None, &trusted).unwrap();
+        assert_invalid(verify_guest_image(&signed, None, &trusted[..1]));
+        assert_invalid(verify_guest_image(&image, None, &trusted));
+
+        let mut tampered = signed.clone();
+        tampered[0] ^= 1;
+        assert_invalid(verify_guest_image(&tampered, None, &trusted));
+    }
+
+    #[test]
+    fn detached_signatures() {
+        let image = b"not really an elf file".to_vec();
+        let signature = sign_guest_binary_detached(&image, &KEY);
+        let trusted = [guest_public_key(&KEY)];
+
+        verify_guest_image(&image, Some(&signature), &trusted).unwrap();
+        assert_invalid(verify_guest_image(&image[1..], Some(&signature), &trusted));
+        assert_invalid(verify_guest_image(&image, Some(&signature[1..]), &trusted));
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("guest");
+        std::fs::write(&path, &image).unwrap();
+        let path = path.to_str();
+        assert_invalid(verify_guest_binary(&image, path, &trusted));
+        std::fs::write(dir.path().join("guest.sig"), signature).unwrap();
+        verify_guest_binary(&image, path, &trusted).unwrap();
+        assert_invalid(verify_guest_binary(&image, None, &trusted));
+    }
+}
@@ -14,24 +14,46 @@ See the License for the specific language governing permissions and
 limitations under the License.
 */
 
+use std::collections::HashMap;
 use std::fmt::Debug;
 use std::option::Option;
 use std::path::Path;
 use std::sync::{Arc, Mutex};
-use std::time::Duration;
-
+use std::time::{Duration, Instant};
+
+use hyperlight_common::flatbuffer_wrappers::function_call::{FunctionCall, FunctionCallType};
+use hyperlight_common::flatbuffer_wrappers::function_types::ReturnType;
+use hyperlight_common::flatbuffer_wrappers::host_function_definition::HostFunctionDefinition;
+use hyperlight_common::guest_env;
+use hyperlight_common::heap_profile::{HeapProfile, HEAP_PROFILE_HOST_FUNCTION};
+use hyperlight_common::outb::{is_user_port, USER_PORT_BASE, USER_PORT_COUNT};
 use tracing::{instrument, Span};
 
+#[cfg(all(feature = "cgroup", target_os = "linux"))]
+use super::cgroup::{CgroupOptions, SandboxCgroup};
+use super::events::SandboxEvents;
 use super::host_funcs::{default_writer_func, HostFuncsWrapper};
 use super::mem_mgr::MemMgrWrapper;
+use super::outb::PortHandler;
+use super::randomness::SandboxRng;
+use super::registry::SandboxRegistration;
 use super::run_options::SandboxRunOptions;
+#[cfg(feature = "signed_guests")]
+use super::signing::verify_guest_binary;
 use super::uninitialized_evolve::{evolve_impl_multi_use, evolve_impl_single_use};
 use crate::error::HyperlightError::GuestBinaryShouldBeAFile;
 use crate::func::host_functions::HostFunction1;
+use crate::func::{HyperlightFallbackFunction, HyperlightFunction, ParameterValue, ReturnValue};
+use crate::hypervisor::cpuid::CpuidOptions;
+use crate::hypervisor::time::TimeOptions;
 use crate::mem::exe::ExeInfo;
 use crate::mem::mgr::{SandboxMemoryManager, STACK_COOKIE_LEN};
 use crate::mem::shared_mem::ExclusiveSharedMemory;
-use crate::sandbox::SandboxConfiguration;
+use crate::sandbox::redaction::RedactionPolicy;
+use crate::sandbox::{
+    CreationReport, EventSubscriber, ExtraAllowedSyscall, GuestBinaryInfo, GuestInfo,
+    GuestLogQueue, HostPrintOptions, SandboxConfiguration, SandboxId, UnknownOutbPolicy,
+};
 use crate::sandbox_state::sandbox::EvolvableSandbox;
 use crate::sandbox_state::transition::Noop;
 use crate::{
@@ -54,6 +76,36 @@ pub struct UninitializedSandbox {
     pub(crate) max_initialization_time: Duration,
     pub(crate) max_execution_time: Duration,
     pub(crate) max_wait_for_cancellation: Duration,
+    /// How long threads waiting on the guest or host spin for before they
+    /// block
+    pub(crate) busy_poll_window: Option<Duration>,
+    pub(crate) port_handlers: HashMap<u16, PortHandler>,
+    pub(crate) unknown_outb_policy: UnknownOutbPolicy,
+    pub(crate) guest_log_queue: Option<GuestLogQueue>,
+    pub(crate) cpuid_options: Option<CpuidOptions>,
+    pub(crate) time_options: Option<TimeOptions>,
+    /// The CPUs the vCPU thread may run on, empty if it may run on any
+    #[cfg(target_os = "linux")]
+    pub(crate) cpu_affinity: Vec<usize>,
+    /// The cgroup the vCPU thread is placed in, if any
+    #[cfg(all(feature = "cgroup", target_os = "linux"))]
+    pub(crate) cgroup: Option<Arc<SandboxCgroup>>,
+    /// Passed on to the hypervisor handler when the sandbox is evolved
+    pub(crate) redaction: RedactionPolicy,
+    pub(crate) guest_info: GuestInfo,
+    /// The arguments to `hyperlight_main`, as a `FunctionCall` flatbuffer,
+    /// or empty if the host gave none
+    pub(crate) startup_args: Vec<u8>,
+    /// The guest environment, encoded with `guest_env::encode`
+    pub(crate) guest_env: Vec<u8>,
+    pub(crate) lazy_initialization: bool,
+    /// Generates the seed the guest's entrypoint is given
+    pub(crate) rng: SandboxRng,
+    pub(crate) registration: SandboxRegistration,
+    pub(crate) events: SandboxEvents,
+    /// How long loading the guest binary took, which the rest of the
+    /// report is added to when the sandbox is evolved
+    pub(crate) creation_report: CreationReport,
 }
 
 impl crate::sandbox_state::sandbox::UninitializedSandbox for UninitializedSandbox {
@@ -71,6 +123,8 @@ impl crate::sandbox_state::sandbox::UninitializedSandbox for UninitializedSandbo
 impl Debug for UninitializedSandbox {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         f.debug_struct("UninitializedSandbox")
+            .field("id", &self.id())
+            .field("name", &self.name())
             .field("memory_layout", &self.mgr.unwrap_mgr().layout)
             .finish()
     }
@@ -82,6 +136,11 @@ impl crate::sandbox_state::sandbox::Sandbox for UninitializedSandbox {
             "Checking the stack cookie before the sandbox is initialized is unsupported"
         );
     }
+
+    #[instrument(err(Debug), skip_all, parent = Span::current(), level = "Trace")]
+    fn creation_report(&self) -> Result<Option<CreationReport>> {
+        Ok(Some(self.creation_report))
+    }
 }
 
 impl
@@ -92,7 +151,7 @@ impl
     > for UninitializedSandbox
 {
     /// Evolve `self` to a `SingleUseSandbox` without any additional metadata.
-    #[instrument(err(Debug), skip_all, parent = Span::current(), level = "Trace")]
+    #[instrument(err(Debug), skip_all, fields(sandbox_id = %self.id()), parent = Span::current(), level = "Trace")]
     fn evolve(self, _: Noop<UninitializedSandbox, SingleUseSandbox>) -> Result<SingleUseSandbox> {
         evolve_impl_single_use(self)
     }
@@ -106,7 +165,7 @@ impl
     > for UninitializedSandbox
 {
     /// Evolve `self` to a `MultiUseSandbox` without any additional metadata.
-    #[instrument(err(Debug), skip_all, parent = Span::current(), level = "Trace")]
+    #[instrument(err(Debug), skip_all, fields(sandbox_id = %self.id()), parent = Span::current(), level = "Trace")]
     fn evolve(self, _: Noop<UninitializedSandbox, MultiUseSandbox>) -> Result<MultiUseSandbox> {
         evolve_impl_multi_use(self)
     }
@@ -121,6 +180,19 @@ pub enum GuestBinary {
     FilePath(String),
 }
 
+impl GuestBinary {
+    /// Read what the guest binary says about itself, such as the functions
+    /// it exposes to the host, without running it
+    #[instrument(err(Debug), skip_all, parent = Span::current(), level = "Trace")]
+    pub fn inspect(&self) -> Result<GuestBinaryInfo> {
+        let exe_info = match self {
+            GuestBinary::FilePath(bin_path_str) => ExeInfo::from_file(bin_path_str)?,
+            GuestBinary::Buffer(buffer) => ExeInfo::from_buf(buffer)?,
+        };
+        GuestBinaryInfo::new(&exe_info)
+    }
+}
+
 impl UninitializedSandbox {
     /// Create a new sandbox configured to run the binary at path
     /// `bin_path`.
@@ -141,31 +213,67 @@ impl UninitializedSandbox {
         host_print_writer: Option<&dyn HostFunction1<String, i32>>,
     ) -> Result<Self> {
         log_build_details();
+        let start = Instant::now();
 
         // hyperlight is only supported on Windows 11 and Windows Server 2022 and later
         #[cfg(target_os = "windows")]
         check_windows_version()?;
 
-        // If the guest binary is a file make sure it exists
-        let guest_binary = match guest_binary {
+        // Read the guest binary once, so that the bytes that are hashed,
+        // checked against their signature and loaded are the same, even if
+        // the file is replaced in the meantime
+        let (image, path) = match guest_binary {
             GuestBinary::FilePath(binary_path) => {
                 let path = Path::new(&binary_path)
                     .canonicalize()
-                    .map_err(|e| new_error!("GuestBinary not found: '{}': {}", binary_path, e))?;
-                GuestBinary::FilePath(
-                    path.into_os_string()
-                        .into_string()
-                        .map_err(|e| new_error!("Error converting OsString to String: {:?}", e))?,
-                )
+                    .map_err(|e| new_error!("GuestBinary not found: '{}': {}", binary_path, e))?
+                    .into_os_string()
+                    .into_string()
+                    .map_err(|e| new_error!("Error converting OsString to String: {:?}", e))?;
+                let image = std::fs::read(&path)
+                    .map_err(|e| new_error!("Failed to read guest binary '{}': {}", path, e))?;
+                (image, Some(path))
             }
-            buffer @ GuestBinary::Buffer(_) => buffer,
+            GuestBinary::Buffer(image) => (image, None),
         };
 
+        let sandbox_cfg = cfg.unwrap_or_default();
         let run_opts = sandbox_run_options.unwrap_or_default();
 
         let run_inprocess = run_opts.in_process();
         let use_loadlib = run_opts.use_loadlib();
 
+        // Nothing parses the image until its signature has been checked
+        #[cfg(feature = "signed_guests")]
+        if sandbox_cfg.get_require_signed_guests() {
+            // LoadLibrary reads the file itself, after it has been checked
+            if use_loadlib {
+                log_then_return!("Signed guests can't be loaded with LoadLibrary");
+            }
+            verify_guest_binary(
+                &image,
+                path.as_deref(),
+                sandbox_cfg.get_trusted_guest_keys(),
+            )?;
+        }
+        let exe_info = ExeInfo::from_buf(&image)?;
+        let guest_info = GuestInfo::new(&image, path.clone(), exe_info.guest_functions()?);
+        // LoadLibrary loads the file itself
+        let loadlib_path = match (use_loadlib, path) {
+            (false, _) => None,
+            (true, Some(path)) => Some(path),
+            (true, None) => {
+                log_then_return!(GuestBinaryShouldBeAFile());
+            }
+        };
+        let mut creation_report = CreationReport {
+            binary_load: start.elapsed(),
+            ..Default::default()
+        };
+        let mut rng = SandboxRng::new(sandbox_cfg.get_deterministic_seed());
+        let registration = SandboxRegistration::new(guest_info.clone(), &mut rng);
+        log::info!(target: "hyperlight_host::audit", "Loading guest {} into sandbox {}", guest_info, registration.id());
+
         if run_inprocess && cfg!(not(inprocess)) {
             log_then_return!(
                 "Inprocess mode is only available in debug builds, and also requires cargo feature 'inprocess'"
@@ -176,22 +284,34 @@ impl UninitializedSandbox {
             log_then_return!("Inprocess mode with LoadLibrary is only available on Windows")
         }
 
-        let sandbox_cfg = cfg.unwrap_or_default();
+        #[cfg(not(target_os = "linux"))]
+        if sandbox_cfg.get_numa_node().is_some() || !sandbox_cfg.get_cpu_affinity().is_empty() {
+            log_then_return!("NUMA nodes and CPU affinity can only be set on Linux");
+        }
+
         let mut mem_mgr_wrapper = {
-            let mut mgr = UninitializedSandbox::load_guest_binary(
+            let mut mgr = UninitializedSandbox::load_exe(
                 sandbox_cfg,
-                &guest_binary,
+                exe_info,
+                loadlib_path.as_deref(),
                 run_inprocess,
-                use_loadlib,
+                &mut creation_report,
             )?;
-            let stack_guard = Self::create_stack_guard();
+            let stack_guard = Self::create_stack_guard(&mut rng);
             mgr.set_stack_guard(&stack_guard)?;
+            if sandbox_cfg.get_shared_buffer_integrity() {
+                mgr.set_integrity_key(rng.bytes());
+            }
             MemMgrWrapper::new(mgr, stack_guard)
         };
 
-        mem_mgr_wrapper.write_memory_layout(run_inprocess)?;
+        let start = Instant::now();
+        mem_mgr_wrapper.write_memory_layout(run_inprocess, &mut rng)?;
+        creation_report.memory_map += start.elapsed();
 
-        let host_funcs = Arc::new(Mutex::new(HostFuncsWrapper::default()));
+        let mut host_funcs = HostFuncsWrapper::default();
+        host_funcs.set_busy_poll_window(sandbox_cfg.get_busy_poll_window());
+        let host_funcs = Arc::new(Mutex::new(host_funcs));
 
         let mut sandbox = Self {
             host_funcs,
@@ -204,6 +324,25 @@ impl UninitializedSandbox {
             max_wait_for_cancellation: Duration::from_millis(
                 sandbox_cfg.get_max_wait_for_cancellation() as u64,
             ),
+            busy_poll_window: sandbox_cfg.get_busy_poll_window(),
+            port_handlers: HashMap::new(),
+            unknown_outb_policy: UnknownOutbPolicy::default(),
+            guest_log_queue: None,
+            cpuid_options: None,
+            time_options: None,
+            #[cfg(target_os = "linux")]
+            cpu_affinity: sandbox_cfg.get_cpu_affinity(),
+            #[cfg(all(feature = "cgroup", target_os = "linux"))]
+            cgroup: None,
+            redaction: sandbox_cfg.get_redaction_policy(),
+            guest_info,
+            startup_args: Vec::new(),
+            guest_env: Vec::new(),
+            lazy_initialization: false,
+            rng,
+            events: SandboxEvents::new(registration.id()),
+            registration,
+            creation_report,
         };
 
         // TODO: These only here to accommodate some writer functions.
@@ -225,6 +364,33 @@ impl UninitializedSandbox {
             libc::SYS_close,
         ];
 
+        // Guests built with heap profiling send their profiles here, to be
+        // passed on to the event subscribers.
+        let events = sandbox.events.clone();
+        let heap_profile_func = Arc::new(Mutex::new(move |profile: Vec<u8>| {
+            let profile = HeapProfile::try_from(profile.as_slice())?;
+            events.emit(|subscriber, id| subscriber.on_heap_profile(id, &profile));
+            Ok(())
+        }));
+
+        #[cfg(any(target_os = "windows", not(feature = "seccomp")))]
+        heap_profile_func.register(&mut sandbox, HEAP_PROFILE_HOST_FUNCTION)?;
+
+        #[cfg(all(target_os = "linux", feature = "seccomp"))]
+        heap_profile_func.register_with_extra_allowed_syscalls(
+            &mut sandbox,
+            HEAP_PROFILE_HOST_FUNCTION,
+            extra_allowed_syscalls_for_writer_func.clone(),
+        )?;
+
+        // Guests report the progress of long-running calls here, to be
+        // passed on to the progress callback of the call.
+        sandbox
+            .host_funcs
+            .try_lock()
+            .map_err(|e| new_error!("Error locking at {}:{}: {}", file!(), line!(), e))?
+            .register_report_progress(sandbox.mgr.as_mut())?;
+
         // If we were passed a writer for host print register it otherwise use the default.
         match host_print_writer {
             Some(writer_func) => {
@@ -267,44 +433,333 @@ impl UninitializedSandbox {
         Ok(sandbox)
     }
 
-    #[instrument(skip_all, parent = Span::current(), level = "Trace")]
-    fn create_stack_guard() -> [u8; STACK_COOKIE_LEN] {
-        rand::random::<[u8; STACK_COOKIE_LEN]>()
+    /// Register `handler` to be called when the guest calls a host function
+    /// that has not been registered with this sandbox, instead of the call
+    /// failing with `HostFunctionNotFound`.
+    ///
+    /// The handler receives the name of the function the guest called and
+    /// its arguments, which allows hosts to forward calls they don't know
+    /// about ahead of time, for example to another service. Registering a
+    /// second handler replaces the first.
+    ///
+    /// Like host functions registered with
+    /// `register_with_extra_allowed_syscalls`, the handler may make
+    /// `extra_allowed_syscalls` as well as the syscalls every host function
+    /// is allowed, such as those needed to open a socket. They are ignored
+    /// without the `seccomp` feature. Calls to the handler are timed under
+    /// one `"<fallback>"` label in the host function call metrics, rather
+    /// than by the name the guest called.
+    #[instrument(
+        err(Debug), skip(self, handler, extra_allowed_syscalls),
+        parent = Span::current(), level = "Trace"
+    )]
+    pub fn register_fallback_host_function<F>(
+        &mut self,
+        handler: F,
+        extra_allowed_syscalls: Vec<ExtraAllowedSyscall>,
+    ) -> Result<()>
+    where
+        F: FnMut(String, Vec<ParameterValue>) -> Result<ReturnValue> + Send + 'static,
+    {
+        self.host_funcs
+            .try_lock()
+            .map_err(|e| new_error!("Error locking at {}:{}: {}", file!(), line!(), e))?
+            .register_fallback_host_function(
+                self.mgr.as_mut(),
+                HyperlightFallbackFunction::new(handler),
+                extra_allowed_syscalls,
+            )
     }
 
-    /// Load the file at `bin_path_str` into a PE file, then attempt to
-    /// load the PE file into a `SandboxMemoryManager` and return it.
+    /// Apply `options` to everything the guest prints before it is passed to
+    /// the `HostPrint` writer, for example to prefix output with the sandbox
+    /// name, rate-limit a noisy guest, or intercept messages with a hook.
     ///
-    /// If `run_from_guest_binary` is passed as `true`, and this code is
-    /// running on windows, this function will call
-    /// `SandboxMemoryManager::load_guest_binary_using_load_library` to
-    /// create the new `SandboxMemoryManager`. If `run_from_guest_binary` is
-    /// passed as `true` and we're not running on windows, this function will
-    /// return an `Err`. Otherwise, if `run_from_guest_binary` is passed
-    /// as `false`, this function calls `SandboxMemoryManager::load_guest_binary_into_memory`.
-    #[instrument(err(Debug), skip_all, parent = Span::current(), level = "Trace")]
-    pub(super) fn load_guest_binary(
-        cfg: SandboxConfiguration,
-        guest_binary: &GuestBinary,
-        inprocess: bool,
-        use_loadlib: bool,
-    ) -> Result<SandboxMemoryManager<ExclusiveSharedMemory>> {
-        let mut exe_info = match guest_binary {
-            GuestBinary::FilePath(bin_path_str) => ExeInfo::from_file(bin_path_str)?,
-            GuestBinary::Buffer(buffer) => ExeInfo::from_buf(buffer)?,
+    /// The options wrap the writer registered at the time of the call.
+    #[instrument(err(Debug), skip(self, options), parent = Span::current(), level = "Trace")]
+    pub fn set_host_print_options(&mut self, options: HostPrintOptions) -> Result<()> {
+        let id = self.id();
+        self.host_funcs
+            .try_lock()
+            .map_err(|e| new_error!("Error locking at {}:{}: {}", file!(), line!(), e))?
+            .set_host_print_options(id, options)
+    }
+
+    /// Register `handler` to be called with the value the guest passes to
+    /// `signal_host` for `port`, giving the guest a lightweight way to
+    /// notify the host without calling a host function. `port` must be in
+    /// the range reserved for user-defined channels, see
+    /// `hyperlight_common::outb`. Registering a second handler for the same
+    /// port replaces the first.
+    #[instrument(err(Debug), skip(self, handler), parent = Span::current(), level = "Trace")]
+    pub fn register_port_handler<F>(&mut self, port: u16, handler: F) -> Result<()>
+    where
+        F: FnMut(u8) -> Result<()> + Send + 'static,
+    {
+        if !is_user_port(port) {
+            log_then_return!(
+                "Port {} is outside the user-defined range {}..{}",
+                port,
+                USER_PORT_BASE,
+                USER_PORT_BASE + USER_PORT_COUNT
+            );
+        }
+        self.port_handlers.insert(port, Box::new(handler));
+        Ok(())
+    }
+
+    /// Let the guest call `functions` of the guest in `other` as if they
+    /// were host functions of the same names. The host copies the
+    /// arguments of each call out of this sandbox's memory and into
+    /// `other`'s, and the result back, so the two guests stay as isolated
+    /// from each other as they are from the host. Linking sandboxes this
+    /// way chains plugins into pipelines.
+    ///
+    /// The guest in `other` must record the signatures of `functions` in
+    /// its binary with `register_guest_function!`, see
+    /// `GuestInfo::functions`. Calls into `other`, whether from the host or
+    /// from the sandboxes linked to it, run one at a time. As `other` is
+    /// already initialised, links can't form a cycle.
+    #[instrument(err(Debug), skip(self, other), parent = Span::current(), level = "Trace")]
+    pub fn link(&mut self, other: Arc<Mutex<MultiUseSandbox>>, functions: &[&str]) -> Result<()> {
+        let (other_id, signatures) = {
+            let other = other
+                .lock()
+                .map_err(|e| new_error!("Error locking at {}:{}: {}", file!(), line!(), e))?;
+            (other.id(), other.guest_info().functions.clone())
+        };
+        let Some(signatures) = signatures else {
+            log_then_return!(
+                "The guest of sandbox {} doesn't record the signatures of its functions",
+                other_id
+            );
         };
 
-        if use_loadlib {
-            let path = match guest_binary {
-                GuestBinary::FilePath(bin_path_str) => bin_path_str,
-                GuestBinary::Buffer(_) => {
-                    log_then_return!(GuestBinaryShouldBeAFile());
-                }
+        for name in functions {
+            let Some(signature) = signatures.iter().find(|signature| signature.name == *name)
+            else {
+                log_then_return!("The guest of sandbox {} has no function {}", other_id, name);
             };
-            SandboxMemoryManager::load_guest_binary_using_load_library(cfg, path, &mut exe_info)
-        } else {
-            SandboxMemoryManager::load_guest_binary_into_memory(cfg, &mut exe_info, inprocess)
+            let hfd = HostFunctionDefinition::new(
+                signature.name.clone(),
+                Some(signature.parameter_types.clone()),
+                signature.return_type,
+            );
+            let other = other.clone();
+            let func_name = signature.name.clone();
+            let return_type = signature.return_type;
+            let func = HyperlightFunction::new(move |args: Vec<ParameterValue>| {
+                other
+                    .lock()
+                    .map_err(|e| new_error!("Error locking at {}:{}: {}", file!(), line!(), e))?
+                    .call_guest_function_by_name(&func_name, return_type, Some(args))
+            });
+
+            let mut host_funcs = self
+                .host_funcs
+                .try_lock()
+                .map_err(|e| new_error!("Error locking at {}:{}: {}", file!(), line!(), e))?;
+            // the call into `other` allocates, and waits for and may
+            // interrupt `other`'s vCPU thread
+            #[cfg(all(feature = "seccomp", target_os = "linux"))]
+            host_funcs.register_host_function_with_syscalls(
+                self.mgr.as_mut(),
+                &hfd,
+                func,
+                vec![
+                    libc::SYS_mmap,
+                    libc::SYS_brk,
+                    libc::SYS_mprotect,
+                    libc::SYS_tgkill,
+                    libc::SYS_clock_nanosleep,
+                ],
+            )?;
+            #[cfg(not(all(feature = "seccomp", target_os = "linux")))]
+            host_funcs.register_host_function(self.mgr.as_mut(), &hfd, func)?;
         }
+        Ok(())
+    }
+
+    /// Set what happens when the guest writes to an outb port that
+    /// Hyperlight doesn't use. By default such writes fail the guest call;
+    /// guests that signal the host on extra ports of their own can use
+    /// `UnknownOutbPolicy::Ignore`, or handle the writes with
+    /// `UnknownOutbPolicy::callback`.
+    #[instrument(skip(self), parent = Span::current(), level = "Trace")]
+    pub fn set_unknown_outb_policy(&mut self, policy: UnknownOutbPolicy) {
+        self.unknown_outb_policy = policy;
+    }
+
+    /// Give the guest `env`, which it reads with
+    /// `hyperlight_guest::env::get` without calling the host. Setting the
+    /// environment again replaces it.
+    ///
+    /// The environment is stored, read-only to the guest, at the end of the
+    /// host function definitions buffer, so it must fit in the space host
+    /// functions don't use, see
+    /// `SandboxConfiguration::set_host_function_definition_size`.
+    #[instrument(err(Debug), skip(self, env), parent = Span::current(), level = "Trace")]
+    pub fn set_guest_env(&mut self, env: HashMap<String, String>) -> Result<()> {
+        let env = guest_env::encode(
+            env.iter()
+                .map(|(name, value)| (name.as_str(), value.as_str())),
+        );
+        self.write_host_data(&self.startup_args.clone(), &env)?;
+        self.guest_env = env;
+        Ok(())
+    }
+
+    /// Pass `args` to `hyperlight_main`, which reads them with
+    /// `hyperlight_guest::entrypoint::startup_args`, so that one guest
+    /// binary can be initialized differently by different sandboxes.
+    /// Setting the arguments again replaces them.
+    ///
+    /// Like the guest environment, the arguments are stored read-only to
+    /// the guest in the space host functions don't use in the host function
+    /// definitions buffer.
+    #[instrument(err(Debug), skip(self, args), parent = Span::current(), level = "Trace")]
+    pub fn set_startup_args(&mut self, args: Vec<ParameterValue>) -> Result<()> {
+        let startup_args: Vec<u8> = FunctionCall::new(
+            "hyperlight_main".to_string(),
+            Some(args),
+            FunctionCallType::Guest,
+            ReturnType::Void,
+        )
+        .try_into()?;
+        self.write_host_data(&startup_args, &self.guest_env.clone())?;
+        self.startup_args = startup_args;
+        Ok(())
+    }
+
+    fn write_host_data(&mut self, startup_args: &[u8], env: &[u8]) -> Result<()> {
+        let host_func_details = self
+            .host_funcs
+            .try_lock()
+            .map_err(|e| new_error!("Error locking at {}:{}: {}", file!(), line!(), e))?
+            .serialize_host_func_details()?;
+        self.mgr
+            .as_mut()
+            .write_host_data(startup_args, env, host_func_details.len())
+    }
+
+    /// Defer setting up the VM and running the guest's entrypoint from
+    /// `evolve` until the first guest call, or until `ensure_initialized`
+    /// is called on the evolved sandbox. Hosts that create sandboxes ahead
+    /// of time, not all of which get used, then only pay for initializing
+    /// the ones that do.
+    ///
+    /// The errors initialization can fail with, such as
+    /// `HyperlightError::GuestInitializationFailed`, are then returned by
+    /// that first call instead of by `evolve`.
+    #[instrument(skip(self), parent = Span::current(), level = "Trace")]
+    pub fn set_lazy_initialization(&mut self, lazy: bool) {
+        self.lazy_initialization = lazy;
+    }
+
+    /// Create a cgroup v2 for this sandbox as described by `options`, and
+    /// place the thread that runs its vCPU in it when it is evolved, so
+    /// that the kernel enforces the limits in `options` on the sandbox.
+    /// Calling this again replaces the cgroup.
+    ///
+    /// Use `Sandbox::cgroup_usage` on the evolved sandbox to read back what
+    /// it has used.
+    #[cfg(all(feature = "cgroup", target_os = "linux"))]
+    #[instrument(err(Debug), skip_all, parent = Span::current(), level = "Trace")]
+    pub fn set_cgroup(&mut self, options: &CgroupOptions) -> Result<()> {
+        // remove any cgroup set before, so that its name can be reused
+        self.cgroup = None;
+        self.cgroup = Some(Arc::new(SandboxCgroup::new(options, self.id())?));
+        Ok(())
+    }
+
+    /// Put the records the guest logs into `queue`, for the host to take
+    /// out when it is ready, instead of passing them to the `log` or
+    /// `tracing` subscriber as they are logged.
+    #[instrument(skip(self), parent = Span::current(), level = "Trace")]
+    pub fn set_guest_log_queue(&mut self, queue: GuestLogQueue) {
+        self.guest_log_queue = Some(queue);
+    }
+
+    /// Control the CPUID leaves presented to the guest. By default the
+    /// guest sees whatever the hypervisor presents.
+    #[instrument(skip(self), parent = Span::current(), level = "Trace")]
+    pub fn set_cpuid_options(&mut self, options: CpuidOptions) {
+        self.cpuid_options = Some(options);
+    }
+
+    /// Control the guest's time stamp counter. By default the guest sees
+    /// whatever the hypervisor presents.
+    #[instrument(skip(self), parent = Span::current(), level = "Trace")]
+    pub fn set_time_options(&mut self, options: TimeOptions) {
+        self.time_options = Some(options);
+    }
+
+    /// The `GuestInfo` recorded when the guest binary was loaded, which the
+    /// sandbox keeps when it is evolved
+    #[instrument(skip(self), parent = Span::current(), level = "Trace")]
+    pub fn guest_info(&self) -> &GuestInfo {
+        &self.guest_info
+    }
+
+    /// The id of this sandbox, which it keeps when it is evolved.
+    #[instrument(skip(self), parent = Span::current(), level = "Trace")]
+    pub fn id(&self) -> SandboxId {
+        self.registration.id()
+    }
+
+    /// The name this sandbox was given with `set_name`, if any.
+    #[instrument(skip(self), parent = Span::current(), level = "Trace")]
+    pub fn name(&self) -> Option<String> {
+        self.registration.name()
+    }
+
+    /// Give this sandbox a name, which it keeps when it is evolved and which
+    /// is added to its metrics, logs and `SandboxRegistry` entry. Names
+    /// don't have to be unique.
+    #[instrument(skip(self), parent = Span::current(), level = "Trace")]
+    pub fn set_name(&mut self, name: impl Into<String> + Debug) {
+        self.registration.set_name(name.into());
+    }
+
+    /// Add a subscriber to be told about the events in this sandbox's life,
+    /// which it stays subscribed to when the sandbox is evolved.
+    #[instrument(skip_all, parent = Span::current(), level = "Trace")]
+    pub fn add_event_subscriber(&mut self, subscriber: Arc<dyn EventSubscriber>) {
+        self.events.add(subscriber);
+    }
+
+    /// The address in the guest's address space that the guest binary was
+    /// loaded at, for use with `GuestSymbols::with_load_address`.
+    #[instrument(skip(self), parent = Span::current(), level = "Trace")]
+    pub fn guest_load_address(&self) -> u64 {
+        self.mgr.unwrap_mgr().load_addr.clone().into()
+    }
+
+    #[instrument(skip_all, parent = Span::current(), level = "Trace")]
+    fn create_stack_guard(rng: &mut SandboxRng) -> [u8; STACK_COOKIE_LEN] {
+        rng.bytes()
+    }
+
+    /// Load the parsed guest binary `exe_info`, with LoadLibrary from
+    /// `loadlib_path` if it is given
+    fn load_exe(
+        cfg: SandboxConfiguration,
+        mut exe_info: ExeInfo,
+        loadlib_path: Option<&str>,
+        inprocess: bool,
+        creation_report: &mut CreationReport,
+    ) -> Result<SandboxMemoryManager<ExclusiveSharedMemory>> {
+        let start = Instant::now();
+        let mgr = match loadlib_path {
+            Some(path) => {
+                SandboxMemoryManager::load_guest_binary_using_load_library(cfg, path, &mut exe_info)
+            }
+            None => {
+                SandboxMemoryManager::load_guest_binary_into_memory(cfg, &mut exe_info, inprocess)
+            }
+        }?;
+        creation_report.memory_map += start.elapsed();
+        Ok(mgr)
     }
 }
 // Check to see if the current version of Windows is supported
@@ -340,10 +795,15 @@ mod tests {
     use std::{fs, thread};
 
     use crossbeam_queue::ArrayQueue;
-    use hyperlight_common::flatbuffer_wrappers::function_types::{ParameterValue, ReturnValue};
+    use hyperlight_common::flatbuffer_wrappers::function_types::{
+        ParameterType, ParameterValue, ReturnType, ReturnValue,
+    };
+    use hyperlight_common::progress::REPORT_PROGRESS_HOST_FUNCTION;
     use hyperlight_testing::logger::{Logger as TestLogger, LOGGER as TEST_LOGGER};
     use hyperlight_testing::tracing_subscriber::TracingSubscriber as TestSubcriber;
-    use hyperlight_testing::{simple_guest_as_string, simple_guest_exe_as_string};
+    use hyperlight_testing::{
+        callback_guest_as_string, simple_guest_as_string, simple_guest_exe_as_string,
+    };
     use log::Level;
     use serde_json::{Map, Value};
     use serial_test::serial;
@@ -352,12 +812,20 @@ mod tests {
     use tracing_core::Subscriber;
     use uuid::Uuid;
 
-    use crate::func::{HostFunction1, HostFunction2};
+    use crate::func::{CallOptions, GuestFunctionSignature, HostFunction1, HostFunction2};
+    use crate::mem::exe::ExeInfo;
+    use crate::sandbox::host_funcs::HostFuncsWrapper;
+    #[cfg(feature = "signed_guests")]
+    use crate::sandbox::signing::{guest_public_key, sign_guest_binary, GUEST_SIGNING_KEY_LEN};
     use crate::sandbox::uninitialized::GuestBinary;
-    use crate::sandbox::SandboxConfiguration;
+    use crate::sandbox::{
+        CreationReport, HostPrintAction, HostPrintOptions, SandboxConfiguration, SandboxRegistry,
+    };
     use crate::sandbox_state::sandbox::EvolvableSandbox;
     use crate::sandbox_state::transition::Noop;
     use crate::testing::log_values::{test_value_as_str, try_to_strings};
+    #[cfg(feature = "signed_guests")]
+    use crate::HyperlightError;
     use crate::{new_error, MultiUseSandbox, Result, SandboxRunOptions, UninitializedSandbox};
 
     #[test]
@@ -470,14 +938,220 @@ mod tests {
         let cfg = SandboxConfiguration::default();
 
         let simple_guest_path = simple_guest_as_string().unwrap();
+        let exe_info = ExeInfo::from_file(&simple_guest_path).unwrap();
 
-        UninitializedSandbox::load_guest_binary(
-            cfg,
-            &GuestBinary::FilePath(simple_guest_path),
-            false,
-            false,
+        UninitializedSandbox::load_exe(cfg, exe_info, None, false, &mut CreationReport::default())
+            .unwrap();
+    }
+
+    #[test]
+    #[cfg(deterministic)]
+    fn deterministic_seed_repeats_random_values() {
+        let sandbox = |seed: u64| {
+            let mut cfg = SandboxConfiguration::default();
+            cfg.set_deterministic_seed(seed);
+            let mut sbox = UninitializedSandbox::new(
+                GuestBinary::FilePath(simple_guest_as_string().unwrap()),
+                Some(cfg),
+                None,
+                None,
+            )
+            .unwrap();
+            // sandboxes with the same seed get the same id, so only one is
+            // alive at a time
+            (sbox.id(), *sbox.mgr.get_stack_cookie(), sbox.rng.next_u64())
+        };
+        assert_eq!(sandbox(1), sandbox(1));
+        assert_ne!(sandbox(1), sandbox(2));
+    }
+
+    #[test]
+    #[cfg(feature = "signed_guests")]
+    fn test_require_signed_guests() {
+        let signing_key = [7; GUEST_SIGNING_KEY_LEN];
+        let mut cfg = SandboxConfiguration::default();
+        cfg.set_require_signed_guests(true).unwrap();
+        cfg.add_trusted_guest_key(guest_public_key(&signing_key))
+            .unwrap();
+
+        let simple_guest_path = simple_guest_as_string().unwrap();
+        let unsigned = UninitializedSandbox::new(
+            GuestBinary::FilePath(simple_guest_path.clone()),
+            Some(cfg),
+            None,
+            None,
+        );
+        assert!(matches!(
+            unsigned,
+            Err(HyperlightError::GuestSignatureInvalid(_))
+        ));
+
+        let image = fs::read(simple_guest_path).unwrap();
+        let signed = sign_guest_binary(&image, &signing_key);
+        UninitializedSandbox::new(GuestBinary::Buffer(signed), Some(cfg), None, None).unwrap();
+
+        let signed_by_someone_else = sign_guest_binary(&image, &[8; GUEST_SIGNING_KEY_LEN]);
+        let untrusted = UninitializedSandbox::new(
+            GuestBinary::Buffer(signed_by_someone_else),
+            Some(cfg),
+            None,
+            None,
+        );
+        assert!(matches!(
+            untrusted,
+            Err(HyperlightError::GuestSignatureInvalid(_))
+        ));
+    }
+
+    #[test]
+    fn test_guest_info() {
+        let simple_guest_path = simple_guest_as_string().unwrap();
+        let image = fs::read(&simple_guest_path).unwrap();
+
+        let from_file = UninitializedSandbox::new(
+            GuestBinary::FilePath(simple_guest_path.clone()),
+            None,
+            None,
+            None,
         )
         .unwrap();
+        let from_buffer =
+            UninitializedSandbox::new(GuestBinary::Buffer(image), None, None, None).unwrap();
+
+        assert_eq!(
+            from_file.guest_info().digest,
+            from_buffer.guest_info().digest
+        );
+        assert_eq!(
+            from_file.guest_info().path.as_deref(),
+            Some(
+                PathBuf::from(&simple_guest_path)
+                    .canonicalize()
+                    .unwrap()
+                    .to_str()
+                    .unwrap()
+            )
+        );
+        assert_eq!(from_buffer.guest_info().path, None);
+
+        let sbox: MultiUseSandbox = from_file.evolve(Noop::default()).unwrap();
+        assert_eq!(sbox.guest_info().digest, from_buffer.guest_info().digest);
+    }
+
+    #[test]
+    fn test_inspect_guest_binary() {
+        // callbackguest registers its functions with register_guest_function!
+        let callback_guest = GuestBinary::FilePath(callback_guest_as_string().unwrap());
+        let functions = callback_guest.inspect().unwrap().functions.unwrap();
+        assert_eq!(functions.len(), 9);
+        assert!(functions.contains(&GuestFunctionSignature {
+            name: "LogMessage".to_string(),
+            parameter_types: vec![
+                ParameterType::String,
+                ParameterType::String,
+                ParameterType::Int
+            ],
+            return_type: ReturnType::Int,
+        }));
+
+        let sbox = UninitializedSandbox::new(callback_guest, None, None, None).unwrap();
+        assert_eq!(sbox.guest_info().functions, Some(functions));
+
+        // simpleguest doesn't
+        let simple_guest = GuestBinary::FilePath(simple_guest_as_string().unwrap());
+        assert_eq!(simple_guest.inspect().unwrap().functions, None);
+        assert!(GuestBinary::Buffer(vec![1, 2, 3]).inspect().is_err());
+    }
+
+    #[test]
+    fn test_link() {
+        let callback_guest = GuestBinary::FilePath(callback_guest_as_string().unwrap());
+        let other: MultiUseSandbox = UninitializedSandbox::new(callback_guest, None, None, None)
+            .unwrap()
+            .evolve(Noop::default())
+            .unwrap();
+        let other = Arc::new(Mutex::new(other));
+
+        let simple_guest = GuestBinary::FilePath(simple_guest_as_string().unwrap());
+        let mut sbox = UninitializedSandbox::new(simple_guest, None, None, None).unwrap();
+        assert!(sbox.link(other.clone(), &["Missing"]).is_err());
+        sbox.link(other.clone(), &["PrintOutput"]).unwrap();
+        let res = sbox.host_funcs.try_lock().unwrap().call_host_function(
+            "PrintOutput",
+            vec![ParameterValue::String("linked\n".to_string())],
+        );
+        assert!(matches!(res, Ok(ReturnValue::Int(7))));
+
+        // simpleguest doesn't record its functions' signatures
+        let simple_guest = GuestBinary::FilePath(simple_guest_as_string().unwrap());
+        let simple: MultiUseSandbox = UninitializedSandbox::new(simple_guest, None, None, None)
+            .unwrap()
+            .evolve(Noop::default())
+            .unwrap();
+        assert!(sbox
+            .link(Arc::new(Mutex::new(simple)), &["PrintOutput"])
+            .is_err());
+    }
+
+    #[test]
+    fn report_progress() {
+        let simple_guest = GuestBinary::FilePath(simple_guest_as_string().unwrap());
+        let sbox = UninitializedSandbox::new(simple_guest, None, None, None).unwrap();
+        let (progress_tx, progress_rx) = crossbeam_channel::unbounded();
+        let mut host_funcs = sbox.host_funcs.try_lock().unwrap();
+        host_funcs.set_progress_sender(progress_tx);
+        let report = |host_funcs: &HostFuncsWrapper, percent| {
+            host_funcs.call_host_function(
+                REPORT_PROGRESS_HOST_FUNCTION,
+                vec![
+                    ParameterValue::UInt(percent),
+                    ParameterValue::String("working".to_string()),
+                ],
+            )
+        };
+
+        // calls without a progress callback report nothing
+        assert!(matches!(report(&host_funcs, 10), Ok(ReturnValue::Void)));
+        assert!(progress_rx.try_recv().is_err());
+
+        host_funcs.set_call_options(Some(CallOptions::new().with_progress_callback(|_, _| {})));
+        assert!(matches!(report(&host_funcs, 150), Ok(ReturnValue::Void)));
+        assert_eq!(
+            progress_rx.try_recv().unwrap(),
+            (100, "working".to_string())
+        );
+
+        // the built-in can't be replaced
+        drop(host_funcs);
+        let mut sbox = sbox;
+        let func = Arc::new(Mutex::new(|_: u32, _: String| Ok(())));
+        assert!(func
+            .register(&mut sbox, REPORT_PROGRESS_HOST_FUNCTION)
+            .is_err());
+    }
+
+    #[test]
+    fn test_sandbox_id_and_name() {
+        let simple_guest_path = simple_guest_as_string().unwrap();
+        let mut u_sbox =
+            UninitializedSandbox::new(GuestBinary::FilePath(simple_guest_path), None, None, None)
+                .unwrap();
+        let id = u_sbox.id();
+        assert_eq!(u_sbox.name(), None);
+
+        let name = format!("test_sandbox_id_and_name-{}", id);
+        u_sbox.set_name(name.clone());
+        assert_eq!(SandboxRegistry::get(id).unwrap().name, Some(name.clone()));
+
+        let sbox: MultiUseSandbox = u_sbox.evolve(Noop::default()).unwrap();
+        assert_eq!(sbox.id(), id);
+        assert_eq!(sbox.name(), Some(name.clone()));
+        let found = SandboxRegistry::find_by_name(&name);
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].id, id);
+
+        drop(sbox);
+        assert_eq!(SandboxRegistry::get(id), None);
     }
 
     #[test]
@@ -504,7 +1178,7 @@ mod tests {
             let sandbox = sandbox.unwrap();
 
             let host_funcs = sandbox
-                ._host_funcs
+                .host_funcs
                 .try_lock()
                 .map_err(|_| new_error!("Error locking"));
 
@@ -530,7 +1204,7 @@ mod tests {
             let sandbox = sandbox.unwrap();
 
             let host_funcs = sandbox
-                ._host_funcs
+                .host_funcs
                 .try_lock()
                 .map_err(|_| new_error!("Error locking"));
 
@@ -562,7 +1236,7 @@ mod tests {
             let sandbox = sandbox.unwrap();
 
             let host_funcs = sandbox
-                ._host_funcs
+                .host_funcs
                 .try_lock()
                 .map_err(|_| new_error!("Error locking"));
 
@@ -580,7 +1254,7 @@ mod tests {
             let sandbox = sandbox.unwrap();
 
             let host_funcs = sandbox
-                ._host_funcs
+                .host_funcs
                 .try_lock()
                 .map_err(|_| new_error!("Error locking"));
 
@@ -589,6 +1263,86 @@ mod tests {
             let res = host_funcs.unwrap().call_host_function("test4", vec![]);
             assert!(res.is_err());
         }
+
+        // overloads of the same name are dispatched by parameter types
+        {
+            let mut usbox = uninitialized_sandbox();
+            let add_one = |arg: i32| -> Result<i32> { Ok(arg + 1) };
+            Arc::new(Mutex::new(add_one))
+                .register(&mut usbox, "overloaded")
+                .unwrap();
+            let add = |arg1: i32, arg2: i32| -> Result<i32> { Ok(arg1 + arg2) };
+            Arc::new(Mutex::new(add))
+                .register(&mut usbox, "overloaded")
+                .unwrap();
+            let len = |arg: String| -> Result<i32> { Ok(arg.len() as i32) };
+            Arc::new(Mutex::new(len))
+                .register(&mut usbox, "overloaded")
+                .unwrap();
+
+            let sandbox: MultiUseSandbox = usbox.evolve(Noop::default()).unwrap();
+            let host_funcs = sandbox.host_funcs.try_lock().unwrap();
+
+            let res = host_funcs
+                .call_host_function("overloaded", vec![ParameterValue::Int(1)])
+                .unwrap();
+            assert_eq!(res, ReturnValue::Int(2));
+
+            let res = host_funcs
+                .call_host_function(
+                    "overloaded",
+                    vec![ParameterValue::Int(1), ParameterValue::Int(2)],
+                )
+                .unwrap();
+            assert_eq!(res, ReturnValue::Int(3));
+
+            let res = host_funcs
+                .call_host_function(
+                    "overloaded",
+                    vec![ParameterValue::String("abcd".to_string())],
+                )
+                .unwrap();
+            assert_eq!(res, ReturnValue::Int(4));
+
+            let res = host_funcs.call_host_function("overloaded", vec![ParameterValue::Bool(true)]);
+            assert!(res.is_err());
+        }
+
+        // calling a function that doesn't exist goes to the fallback
+        {
+            let mut usbox = uninitialized_sandbox();
+            let known = |arg: i32| -> Result<i32> { Ok(arg) };
+            Arc::new(Mutex::new(known))
+                .register(&mut usbox, "known")
+                .unwrap();
+            usbox
+                .register_fallback_host_function(
+                    |name, args| Ok(ReturnValue::String(format!("{}:{}", name, args.len()))),
+                    vec![],
+                )
+                .unwrap();
+
+            let sandbox: MultiUseSandbox = usbox.evolve(Noop::default()).unwrap();
+            let host_funcs = sandbox.host_funcs.try_lock().unwrap();
+
+            let res = host_funcs
+                .call_host_function(
+                    "unknown",
+                    vec![ParameterValue::Int(1), ParameterValue::Bool(true)],
+                )
+                .unwrap();
+            assert_eq!(res, ReturnValue::String("unknown:2".to_string()));
+
+            // registered functions are still called directly
+            let res = host_funcs
+                .call_host_function("known", vec![ParameterValue::Int(5)])
+                .unwrap();
+            assert_eq!(res, ReturnValue::Int(5));
+
+            // and calling them with the wrong arguments is still an error
+            let res = host_funcs.call_host_function("known", vec![]);
+            assert!(res.is_err());
+        }
     }
 
     #[test]
@@ -596,11 +1350,13 @@ mod tests {
     fn test_load_guest_binary_load_lib() {
         let cfg = SandboxConfiguration::default();
         let simple_guest_path = simple_guest_exe_as_string().unwrap();
-        let mgr_res = UninitializedSandbox::load_guest_binary(
+        let exe_info = ExeInfo::from_file(&simple_guest_path).unwrap();
+        let mgr_res = UninitializedSandbox::load_exe(
             cfg,
-            &GuestBinary::FilePath(simple_guest_path),
-            true,
+            exe_info,
+            Some(&simple_guest_path),
             true,
+            &mut CreationReport::default(),
         );
         #[cfg(target_os = "linux")]
         {
@@ -784,6 +1540,48 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_host_print_options() {
+        let received_msgs = Arc::new(Mutex::new(Vec::new()));
+        let received_msgs_clone = received_msgs.clone();
+        let writer = move |msg: String| {
+            let len = msg.len() as i32;
+            received_msgs_clone.lock().unwrap().push(msg);
+            Ok(len)
+        };
+        let writer_func = Arc::new(Mutex::new(writer));
+
+        let mut sandbox = UninitializedSandbox::new(
+            GuestBinary::FilePath(simple_guest_as_string().expect("Guest Binary Missing")),
+            None,
+            None,
+            Some(&writer_func),
+        )
+        .expect("Failed to create sandbox");
+
+        let id = sandbox.id();
+        let options = HostPrintOptions::new("test-sandbox")
+            .with_prefix()
+            .with_hook(move |sandbox_id, msg| {
+                assert_eq!(sandbox_id, id);
+                match msg {
+                    "dropped" => HostPrintAction::Drop,
+                    _ => HostPrintAction::Print,
+                }
+            });
+        sandbox.set_host_print_options(options).unwrap();
+
+        let mut host_funcs = sandbox.host_funcs.try_lock().unwrap();
+        assert_eq!(host_funcs.host_print("printed".to_string()).unwrap(), 22);
+        assert_eq!(host_funcs.host_print("dropped".to_string()).unwrap(), 0);
+        drop(host_funcs);
+
+        assert_eq!(
+            *received_msgs.lock().unwrap(),
+            vec!["[test-sandbox] printed".to_string()]
+        );
+    }
+
     #[test]
     fn check_create_and_use_sandbox_on_different_threads() {
         let unintializedsandbox_queue = Arc::new(ArrayQueue::<UninitializedSandbox>::new(10));
@@ -860,7 +1658,7 @@ mod tests {
                         .unwrap_or_else(|| panic!("Failed to pop Sandbox thread {}", i));
 
                     let host_funcs = sandbox
-                        ._host_funcs
+                        .host_funcs
                         .try_lock()
                         .map_err(|_| new_error!("Error locking"));
 
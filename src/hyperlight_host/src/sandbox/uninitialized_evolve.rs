@@ -15,21 +15,29 @@ limitations under the License.
 */
 
 use core::time::Duration;
+use std::collections::HashMap;
 use std::sync::{Arc, Mutex};
 
-use rand::Rng;
 use tracing::{instrument, Span};
 
+use crate::hypervisor::cpuid::CpuidOptions;
 use crate::hypervisor::hypervisor_handler::{
     HvHandlerConfig, HypervisorHandler, HypervisorHandlerAction,
 };
+use crate::hypervisor::time::TimeOptions;
 use crate::mem::mgr::SandboxMemoryManager;
 use crate::mem::ptr::RawPtr;
 use crate::mem::shared_mem::GuestSharedMemory;
+#[cfg(all(feature = "cgroup", target_os = "linux"))]
+use crate::sandbox::cgroup::SandboxCgroup;
+use crate::sandbox::events::SandboxEvents;
+use crate::sandbox::guest_logs::GuestLogQueue;
 use crate::sandbox::host_funcs::HostFuncsWrapper;
 use crate::sandbox::mem_access::mem_access_handler_wrapper;
-use crate::sandbox::outb::outb_handler_wrapper;
-use crate::sandbox::{HostSharedMemory, MemMgrWrapper};
+use crate::sandbox::outb::{outb_handler_wrapper, PortHandler, UnknownOutbPolicy};
+use crate::sandbox::redaction::RedactionPolicy;
+use crate::sandbox::registry::SandboxRegistration;
+use crate::sandbox::{CreationReport, GuestInfo, HostSharedMemory, MemMgrWrapper, SandboxId};
 use crate::sandbox_state::sandbox::Sandbox;
 use crate::{new_error, MultiUseSandbox, Result, SingleUseSandbox, UninitializedSandbox};
 
@@ -46,7 +54,7 @@ use crate::{new_error, MultiUseSandbox, Result, SingleUseSandbox, UninitializedS
 /// please reach out to a Hyperlight developer before making the change.
 #[instrument(err(Debug), skip_all, , parent = Span::current(), level = "Trace")]
 fn evolve_impl<TransformFunc, ResSandbox: Sandbox>(
-    u_sbox: UninitializedSandbox,
+    mut u_sbox: UninitializedSandbox,
     transform: TransformFunc,
 ) -> Result<ResSandbox>
 where
@@ -54,67 +62,135 @@ where
         Arc<Mutex<HostFuncsWrapper>>,
         MemMgrWrapper<HostSharedMemory>,
         HypervisorHandler,
+        SandboxRegistration,
+        bool,
     ) -> Result<ResSandbox>,
 {
-    let (hshm, gshm) = u_sbox.mgr.build();
-
-    let hv_handler = {
-        let mut hv_handler = hv_init(
-            &hshm,
-            gshm,
-            u_sbox.host_funcs.clone(),
-            u_sbox.max_initialization_time,
-            u_sbox.max_execution_time,
-            u_sbox.max_wait_for_cancellation,
-        )?;
-
-        {
-            let dispatch_function_addr = hshm.as_ref().get_pointer_to_dispatch_function()?;
-            assert_ne!(dispatch_function_addr, 0);
-            hv_handler.set_dispatch_function_addr(RawPtr::from(dispatch_function_addr))?;
-        }
+    let (mut hshm, gshm) = u_sbox.mgr.build();
 
-        hv_handler
-    };
+    let mut hv_handler = hv_init(
+        &hshm,
+        gshm,
+        u_sbox.host_funcs.clone(),
+        u_sbox.port_handlers,
+        u_sbox.unknown_outb_policy,
+        u_sbox.guest_log_queue,
+        u_sbox.cpuid_options,
+        u_sbox.time_options,
+        #[cfg(target_os = "linux")]
+        u_sbox.cpu_affinity,
+        #[cfg(all(feature = "cgroup", target_os = "linux"))]
+        u_sbox.cgroup,
+        u_sbox.redaction,
+        u_sbox.rng.next_u64(),
+        u_sbox.max_initialization_time,
+        u_sbox.max_execution_time,
+        u_sbox.max_wait_for_cancellation,
+        u_sbox.busy_poll_window,
+        u_sbox.guest_info,
+        u_sbox.registration.id(),
+        u_sbox.registration.name(),
+        u_sbox.events.clone(),
+        u_sbox.creation_report,
+    )?;
+
+    if !u_sbox.lazy_initialization {
+        initialise_guest(&mut hshm, &mut hv_handler).map_err(|init_e| {
+            match hv_handler.kill_hypervisor_handler_thread() {
+                Ok(_) => init_e,
+                Err(kill_e) => new_error!("{}", format!("{}, {}", init_e, kill_e)),
+            }
+        })?;
+    }
 
-    transform(u_sbox.host_funcs, hshm, hv_handler)
+    let sandbox = transform(
+        u_sbox.host_funcs,
+        hshm,
+        hv_handler,
+        u_sbox.registration,
+        !u_sbox.lazy_initialization,
+    )?;
+    u_sbox
+        .events
+        .emit(|subscriber, id| subscriber.on_sandbox_created(id));
+    Ok(sandbox)
 }
 
 #[instrument(err(Debug), skip_all, parent = Span::current(), level = "Trace")]
 pub(super) fn evolve_impl_multi_use(u_sbox: UninitializedSandbox) -> Result<MultiUseSandbox> {
-    evolve_impl(u_sbox, |hf, mut hshm, hv_handler| {
-        {
-            hshm.as_mut().push_state()?;
-        }
-        Ok(MultiUseSandbox::from_uninit(hf, hshm, hv_handler))
-    })
+    evolve_impl(
+        u_sbox,
+        |hf, mut hshm, hv_handler, registration, initialized| {
+            // a lazily initialized sandbox takes its first snapshot once the
+            // guest has been initialized, see `MultiUseSandbox::ensure_initialized`
+            if initialized {
+                hshm.as_mut().push_state()?;
+            }
+            Ok(MultiUseSandbox::from_uninit(
+                hf,
+                hshm,
+                hv_handler,
+                registration,
+                initialized,
+            ))
+        },
+    )
 }
 
 #[instrument(err(Debug), skip_all, parent = Span::current(), level = "Trace")]
 pub(super) fn evolve_impl_single_use(u_sbox: UninitializedSandbox) -> Result<SingleUseSandbox> {
-    evolve_impl(u_sbox, |_hf, hshm, hv_handler| {
-        // Its intentional not to snapshot state here. This is because
-        // single use sandboxes are not reusable and so there is no need
-        // to snapshot state as they cannot be devolved back to an uninitialized sandbox.
-        Ok(SingleUseSandbox::from_uninit(hshm, hv_handler))
-    })
+    evolve_impl(
+        u_sbox,
+        |_hf, hshm, hv_handler, registration, initialized| {
+            // Its intentional not to snapshot state here. This is because
+            // single use sandboxes are not reusable and so there is no need
+            // to snapshot state as they cannot be devolved back to an uninitialized sandbox.
+            Ok(SingleUseSandbox::from_uninit(
+                hshm,
+                hv_handler,
+                registration,
+                initialized,
+            ))
+        },
+    )
 }
 
 #[instrument(err(Debug), skip_all, parent = Span::current(), level = "Trace")]
+#[allow(clippy::too_many_arguments)]
 fn hv_init(
     hshm: &MemMgrWrapper<HostSharedMemory>,
     gshm: SandboxMemoryManager<GuestSharedMemory>,
     host_funcs: Arc<Mutex<HostFuncsWrapper>>,
+    port_handlers: HashMap<u16, PortHandler>,
+    unknown_outb_policy: UnknownOutbPolicy,
+    guest_log_queue: Option<GuestLogQueue>,
+    cpuid_options: Option<CpuidOptions>,
+    time_options: Option<TimeOptions>,
+    #[cfg(target_os = "linux")] cpu_affinity: Vec<usize>,
+    #[cfg(all(feature = "cgroup", target_os = "linux"))] cgroup: Option<Arc<SandboxCgroup>>,
+    redaction: RedactionPolicy,
+    seed: u64,
     max_init_time: Duration,
     max_exec_time: Duration,
     max_wait_for_cancellation: Duration,
+    busy_poll_window: Option<Duration>,
+    guest_info: GuestInfo,
+    sandbox_id: SandboxId,
+    sandbox_name: Option<String>,
+    events: SandboxEvents,
+    creation_report: CreationReport,
 ) -> Result<HypervisorHandler> {
-    let outb_hdl = outb_handler_wrapper(hshm.clone(), host_funcs);
+    let call_deadline = Arc::new(Mutex::new(None));
+    let outb_hdl = outb_handler_wrapper(
+        hshm.clone(),
+        host_funcs.clone(),
+        port_handlers,
+        unknown_outb_policy,
+        guest_log_queue,
+        call_deadline.clone(),
+        events.clone(),
+    );
     let mem_access_hdl = mem_access_handler_wrapper(hshm.clone());
-    let seed = {
-        let mut rng = rand::thread_rng();
-        rng.gen::<u64>()
-    };
     let peb_addr = {
         let peb_u64 = u64::try_from(gshm.layout.peb_address)?;
         RawPtr::from(peb_u64)
@@ -130,24 +206,63 @@ fn hv_init(
         max_init_time,
         max_exec_time,
         max_wait_for_cancellation,
+        call_deadline,
+        busy_poll_window,
+        cpuid_options,
+        time_options,
+        #[cfg(target_os = "linux")]
+        cpu_affinity,
+        #[cfg(all(feature = "cgroup", target_os = "linux"))]
+        cgroup,
+        redaction,
+        guest_info,
+        sandbox_id,
+        sandbox_name,
+        events,
+        creation_report: Arc::new(Mutex::new(creation_report)),
     };
     // Note: `dispatch_function_addr` is set by the Hyperlight guest library, and so it isn't in
-    // shared memory at this point in time. We will set it after the execution of `hv_init`.
+    // shared memory at this point in time. We will set it after the execution of `initialise_guest`.
 
     let mut hv_handler = HypervisorHandler::new(hv_handler_config);
+    host_funcs
+        .try_lock()
+        .map_err(|e| new_error!("Error locking at {}:{}: {}", file!(), line!(), e))?
+        .set_progress_sender(hv_handler.progress_sender());
 
     hv_handler.start_hypervisor_handler(gshm)?;
 
-    hv_handler
-        .execute_hypervisor_handler_action(HypervisorHandlerAction::Initialise)
-        .map_err(|exec_e| match hv_handler.kill_hypervisor_handler_thread() {
-            Ok(_) => exec_e,
-            Err(kill_e) => new_error!("{}", format!("{}, {}", exec_e, kill_e)),
-        })?;
-
     Ok(hv_handler)
 }
 
+/// Set up the VM and run the guest's entrypoint in it, then read what the
+/// guest left for the host. This happens when the sandbox is evolved, or,
+/// for lazily initialized sandboxes, before the first guest call.
+#[instrument(err(Debug), skip_all, parent = Span::current(), level = "Trace")]
+pub(super) fn initialise_guest(
+    hshm: &mut MemMgrWrapper<HostSharedMemory>,
+    hv_handler: &mut HypervisorHandler,
+) -> Result<()> {
+    #[cfg(pointer_audit)]
+    hshm.as_mut().audit_host_pointers()?;
+    hv_handler.execute_hypervisor_handler_action(HypervisorHandlerAction::Initialise)?;
+
+    // nothing else the guest wrote to the PEB can be trusted to be
+    // where the host expects it unless the guest has the same ABI
+    hshm.as_ref().check_guest_abi_version()?;
+    // fail here with the guest's own error rather than on the first
+    // call into a guest that never finished setting itself up
+    hshm.as_ref().check_guest_initialization()?;
+
+    {
+        let dispatch_function_addr = hshm.as_ref().get_pointer_to_dispatch_function()?;
+        assert_ne!(dispatch_function_addr, 0);
+        hv_handler.set_dispatch_function_addr(RawPtr::from(dispatch_function_addr))?;
+    }
+
+    hshm.as_mut().read_guest_function_details()
+}
+
 #[cfg(test)]
 mod tests {
     use hyperlight_testing::{callback_guest_as_string, simple_guest_as_string};
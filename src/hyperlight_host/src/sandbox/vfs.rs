@@ -0,0 +1,607 @@
+/*
+Copyright 2024 The Hyperlight Authors.
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+    http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+use std::collections::{BTreeMap, HashMap};
+use std::fs::{File, OpenOptions};
+use std::io::{Read, Seek, SeekFrom, Write};
+#[cfg(all(feature = "io_uring", target_os = "linux"))]
+use std::os::fd::AsRawFd;
+use std::path::{Path, PathBuf};
+#[cfg(all(feature = "io_uring", target_os = "linux"))]
+use std::sync::Arc;
+
+use hyperlight_common::builtin_services::{FS_OPEN_APPEND, FS_OPEN_READ, FS_OPEN_WRITE};
+use tracing::{instrument, Span};
+
+#[cfg(all(feature = "io_uring", target_os = "linux"))]
+use super::io_uring::IoUringExecutor;
+use crate::{log_then_return, Result};
+
+/// A file system the host gives a guest through the `hyperlight::fs::*`
+/// built-in services, either a directory of the host's or a tree of files
+/// in memory.
+///
+/// The guest sees the root of the file system as `/`, and can't reach any
+/// path outside it, whether through `..` or symbolic links. It can only
+/// read files unless writes are allowed with `with_writes`.
+#[derive(Debug, Clone)]
+pub struct VirtualFs {
+    root: VfsRoot,
+    writable: bool,
+    max_open_files: usize,
+    max_file_size: u64,
+    max_memory_size: u64,
+    #[cfg(all(feature = "io_uring", target_os = "linux"))]
+    io_uring: bool,
+}
+
+#[derive(Debug, Clone)]
+enum VfsRoot {
+    Directory(PathBuf),
+    /// Files by their normalized path. Directories are implied by the
+    /// paths of the files in them.
+    Memory(BTreeMap<String, Vec<u8>>),
+}
+
+impl VirtualFs {
+    /// A file system rooted at the host directory `path`
+    #[instrument(err(Debug), skip_all, parent = Span::current(), level = "Trace")]
+    pub fn directory(path: impl AsRef<Path>) -> Result<Self> {
+        let root = path.as_ref().canonicalize()?;
+        if !root.is_dir() {
+            log_then_return!("{} is not a directory", root.display());
+        }
+        Ok(Self::new(VfsRoot::Directory(root)))
+    }
+
+    /// A file system holding `files`, by their paths. Each sandbox the
+    /// file system is given to gets its own copy of the files.
+    #[instrument(err(Debug), skip_all, parent = Span::current(), level = "Trace")]
+    pub fn in_memory(
+        files: impl IntoIterator<Item = (impl AsRef<str>, impl Into<Vec<u8>>)>,
+    ) -> Result<Self> {
+        let mut tree = BTreeMap::new();
+        for (path, contents) in files {
+            tree.insert(normalize(path.as_ref())?, contents.into());
+        }
+        Ok(Self::new(VfsRoot::Memory(tree)))
+    }
+
+    fn new(root: VfsRoot) -> Self {
+        Self {
+            root,
+            writable: false,
+            max_open_files: 64,
+            max_file_size: 64 << 20,
+            max_memory_size: 256 << 20,
+            #[cfg(all(feature = "io_uring", target_os = "linux"))]
+            io_uring: false,
+        }
+    }
+
+    /// Allow or disallow the guest creating and writing files
+    #[instrument(skip(self), parent = Span::current(), level = "Trace")]
+    pub fn with_writes(mut self, writable: bool) -> Self {
+        self.writable = writable;
+        self
+    }
+
+    /// Limit the number of files the guest can have open at once. The
+    /// default is 64.
+    #[instrument(skip(self), parent = Span::current(), level = "Trace")]
+    pub fn with_max_open_files(mut self, max: usize) -> Self {
+        self.max_open_files = max;
+        self
+    }
+
+    /// Limit the size the guest can make any file by writing to it. The
+    /// default is 64 MiB.
+    #[instrument(skip(self), parent = Span::current(), level = "Trace")]
+    pub fn with_max_file_size(mut self, max: u64) -> Self {
+        self.max_file_size = max;
+        self
+    }
+
+    /// Limit the combined size of the files of a file system in memory,
+    /// which writes can't grow beyond it. The default is 256 MiB. It makes
+    /// no difference to file systems rooted at a host directory, which
+    /// are only limited by `with_max_file_size`.
+    #[instrument(skip(self), parent = Span::current(), level = "Trace")]
+    pub fn with_max_memory_size(mut self, max: u64) -> Self {
+        self.max_memory_size = max;
+        self
+    }
+
+    /// Read and write the files of a file system rooted at a host
+    /// directory through an io_uring shared by every sandbox in the
+    /// process, rather than with a system call on the thread of each host
+    /// call. Host calls still wait for their own reads and writes, but the
+    /// ring's threads submit the reads and writes of every sandbox waiting
+    /// on files together, so the kernel does them at once. It makes no
+    /// difference to file systems in memory.
+    ///
+    /// The ring is set up when the file system is registered with a
+    /// sandbox, which fails if the host's kernel doesn't support io_uring
+    /// or doesn't allow the process to use it.
+    #[cfg(all(feature = "io_uring", target_os = "linux"))]
+    #[instrument(skip(self), parent = Span::current(), level = "Trace")]
+    pub fn with_io_uring(mut self, io_uring: bool) -> Self {
+        self.io_uring = io_uring;
+        self
+    }
+
+    /// Whether the file system's files are read and written through
+    /// io_uring
+    #[cfg(all(feature = "io_uring", target_os = "linux"))]
+    pub(crate) fn uses_io_uring(&self) -> bool {
+        self.io_uring && matches!(self.root, VfsRoot::Directory(_))
+    }
+
+    /// The host path the guest path `path` refers to, which must be in the
+    /// directory the file system is rooted at. If `must_exist` is false,
+    /// the file doesn't need to exist but its directory does.
+    fn resolve(root: &Path, path: &str, must_exist: bool) -> Result<PathBuf> {
+        let full = root.join(normalize(path)?);
+        let resolved = match full.canonicalize() {
+            Ok(resolved) => resolved,
+            Err(_) if !must_exist => match (full.parent(), full.file_name()) {
+                (Some(parent), Some(name)) => parent.canonicalize()?.join(name),
+                _ => {
+                    log_then_return!("The guest can't create {}", path);
+                }
+            },
+            Err(e) => return Err(e.into()),
+        };
+        if !resolved.starts_with(root) {
+            log_then_return!("{} is outside the guest's file system", path);
+        }
+        Ok(resolved)
+    }
+}
+
+/// A file the guest has open
+enum OpenFile {
+    Directory {
+        file: File,
+        append: bool,
+    },
+    Memory {
+        path: String,
+        position: usize,
+        writable: bool,
+    },
+}
+
+/// The state `hyperlight::fs::*` keeps for a sandbox
+pub(crate) struct VfsState {
+    fs: VirtualFs,
+    open_files: HashMap<i32, OpenFile>,
+    next_handle: i32,
+    #[cfg(all(feature = "io_uring", target_os = "linux"))]
+    io_uring: Option<Arc<IoUringExecutor>>,
+}
+
+impl VfsState {
+    pub(crate) fn new(fs: VirtualFs) -> Result<Self> {
+        Ok(Self {
+            #[cfg(all(feature = "io_uring", target_os = "linux"))]
+            io_uring: match fs.uses_io_uring() {
+                true => Some(IoUringExecutor::shared()?),
+                false => None,
+            },
+            fs,
+            open_files: HashMap::new(),
+            next_handle: 1,
+        })
+    }
+
+    /// Open `path` with one of the `FS_OPEN_*` modes, returning the handle
+    /// of the file
+    pub(crate) fn open(&mut self, path: &str, mode: i32) -> Result<i32> {
+        let writing = match mode {
+            FS_OPEN_READ => false,
+            FS_OPEN_WRITE | FS_OPEN_APPEND => true,
+            _ => {
+                log_then_return!("{} is not a mode files can be opened with", mode);
+            }
+        };
+        if writing && !self.fs.writable {
+            log_then_return!("The guest's file system is read-only");
+        }
+        if self.open_files.len() >= self.fs.max_open_files {
+            log_then_return!(
+                "The guest can have at most {} files open",
+                self.fs.max_open_files
+            );
+        }
+
+        let file = match &mut self.fs.root {
+            VfsRoot::Directory(root) => {
+                let path = VirtualFs::resolve(root, path, !writing)?;
+                let mut options = OpenOptions::new();
+                // `resolve` checked the file isn't a link out of the root,
+                // unless it's a link to a file that doesn't exist yet
+                #[cfg(unix)]
+                std::os::unix::fs::OpenOptionsExt::custom_flags(&mut options, libc::O_NOFOLLOW);
+                let file = options
+                    .read(!writing)
+                    .write(mode == FS_OPEN_WRITE)
+                    .append(mode == FS_OPEN_APPEND)
+                    .create(writing)
+                    .truncate(mode == FS_OPEN_WRITE)
+                    .open(path)?;
+                OpenFile::Directory {
+                    file,
+                    append: mode == FS_OPEN_APPEND,
+                }
+            }
+            VfsRoot::Memory(tree) => {
+                let path = normalize(path)?;
+                let position = match mode {
+                    FS_OPEN_READ => {
+                        if !tree.contains_key(&path) {
+                            log_then_return!("{} does not exist", path);
+                        }
+                        0
+                    }
+                    FS_OPEN_WRITE => {
+                        tree.insert(path.clone(), Vec::new());
+                        0
+                    }
+                    _ => tree.entry(path.clone()).or_default().len(),
+                };
+                OpenFile::Memory {
+                    path,
+                    position,
+                    writable: writing,
+                }
+            }
+        };
+
+        let handle = self.next_handle;
+        self.next_handle = self.next_handle.wrapping_add(1).max(1);
+        self.open_files.insert(handle, file);
+        Ok(handle)
+    }
+
+    /// Read at most `len` bytes from the file `handle`, returning an empty
+    /// vector at the end of the file
+    pub(crate) fn read(&mut self, handle: i32, len: i32) -> Result<Vec<u8>> {
+        let len = match usize::try_from(len) {
+            Ok(len) => len,
+            Err(_) => {
+                log_then_return!("Can't read {} bytes", len);
+            }
+        };
+        match self.open_files.get_mut(&handle) {
+            Some(OpenFile::Directory { file, .. }) => {
+                #[cfg(all(feature = "io_uring", target_os = "linux"))]
+                if let Some(io_uring) = &self.io_uring {
+                    return io_uring.read(file.as_raw_fd(), len);
+                }
+                let mut bytes = Vec::new();
+                file.take(len as u64).read_to_end(&mut bytes)?;
+                Ok(bytes)
+            }
+            Some(OpenFile::Memory { path, position, .. }) => {
+                let VfsRoot::Memory(tree) = &self.fs.root else {
+                    unreachable!("memory files are only opened in memory file systems")
+                };
+                let contents = tree.get(path.as_str()).map(Vec::as_slice).unwrap_or(&[]);
+                let start = (*position).min(contents.len());
+                let end = start.saturating_add(len).min(contents.len());
+                *position = end;
+                Ok(contents[start..end].to_vec())
+            }
+            None => {
+                log_then_return!("{} is not an open file", handle);
+            }
+        }
+    }
+
+    /// Write `bytes` to the file `handle`, returning the number of bytes
+    /// written
+    pub(crate) fn write(&mut self, handle: i32, bytes: Vec<u8>) -> Result<i32> {
+        let len = bytes.len() as i32;
+        match self.open_files.get_mut(&handle) {
+            Some(OpenFile::Directory { file, append }) => {
+                let start = match append {
+                    true => file.metadata()?.len(),
+                    false => file.stream_position()?,
+                };
+                check_file_size(self.fs.max_file_size, start.checked_add(bytes.len() as u64))?;
+                #[cfg(all(feature = "io_uring", target_os = "linux"))]
+                if let Some(io_uring) = &self.io_uring {
+                    io_uring.write_all(file.as_raw_fd(), bytes)?;
+                    return Ok(len);
+                }
+                file.write_all(&bytes)?;
+            }
+            Some(OpenFile::Memory {
+                path,
+                position,
+                writable,
+            }) => {
+                if !*writable {
+                    log_then_return!("{} was opened for reading", path);
+                }
+                let max_memory_size = self.fs.max_memory_size;
+                let VfsRoot::Memory(tree) = &mut self.fs.root else {
+                    unreachable!("memory files are only opened in memory file systems")
+                };
+                let end = position.checked_add(bytes.len()).map(|end| end as u64);
+                let end = check_file_size(self.fs.max_file_size, end)? as usize;
+                let size = tree.get(path.as_str()).map_or(0, Vec::len);
+                let memory_size = tree
+                    .values()
+                    .map(|contents| contents.len() as u64)
+                    .sum::<u64>();
+                if end > size && memory_size + (end - size) as u64 > max_memory_size {
+                    log_then_return!(
+                        "The guest's files can be at most {} bytes in all",
+                        max_memory_size
+                    );
+                }
+                let contents = tree.entry(path.clone()).or_default();
+                if contents.len() < end {
+                    contents.resize(end, 0);
+                }
+                contents[*position..end].copy_from_slice(&bytes);
+                *position = end;
+            }
+            None => {
+                log_then_return!("{} is not an open file", handle);
+            }
+        }
+        Ok(len)
+    }
+
+    /// Move the position of the file `handle` to `offset` bytes from its
+    /// start
+    pub(crate) fn seek(&mut self, handle: i32, offset: u64) -> Result<()> {
+        match self.open_files.get_mut(&handle) {
+            Some(OpenFile::Directory { file, .. }) => {
+                file.seek(SeekFrom::Start(offset))?;
+            }
+            Some(OpenFile::Memory { position, .. }) => {
+                *position = usize::try_from(offset).unwrap_or(usize::MAX);
+            }
+            None => {
+                log_then_return!("{} is not an open file", handle);
+            }
+        }
+        Ok(())
+    }
+
+    pub(crate) fn close(&mut self, handle: i32) -> Result<()> {
+        match self.open_files.remove(&handle) {
+            Some(_) => Ok(()),
+            None => {
+                log_then_return!("{} is not an open file", handle);
+            }
+        }
+    }
+
+    /// The names of the entries in the directory `path`, with a `/` after
+    /// the names of directories, one per line
+    pub(crate) fn list(&self, path: &str) -> Result<String> {
+        let mut names = Vec::new();
+        match &self.fs.root {
+            VfsRoot::Directory(root) => {
+                for entry in std::fs::read_dir(VirtualFs::resolve(root, path, true)?)? {
+                    let entry = entry?;
+                    let mut name = entry.file_name().to_string_lossy().into_owned();
+                    if entry.file_type()?.is_dir() {
+                        name.push('/');
+                    }
+                    names.push(name);
+                }
+                names.sort();
+            }
+            VfsRoot::Memory(tree) => {
+                let dir = normalize(path)?;
+                let prefix = if dir.is_empty() {
+                    String::new()
+                } else {
+                    format!("{}/", dir)
+                };
+                for file in tree.keys().filter(|file| file.starts_with(&prefix)) {
+                    let name = match file[prefix.len()..].split_once('/') {
+                        Some((subdir, _)) => format!("{}/", subdir),
+                        None => file[prefix.len()..].to_string(),
+                    };
+                    if names.last() != Some(&name) {
+                        names.push(name);
+                    }
+                }
+                if names.is_empty() && !dir.is_empty() {
+                    log_then_return!("{} is not a directory", path);
+                }
+            }
+        }
+        Ok(names.join("\n"))
+    }
+}
+
+/// Check a write that leaves a file `end` bytes long, or longer than the
+/// host can represent if `end` is `None`, keeps it within the file size
+/// limit, returning `end`
+fn check_file_size(max: u64, end: Option<u64>) -> Result<u64> {
+    match end {
+        Some(end) if end <= max => Ok(end),
+        _ => {
+            log_then_return!("The guest's files can be at most {} bytes", max);
+        }
+    }
+}
+
+/// `path` relative to the root of the file system, without `.` or `..`.
+/// Paths that would leave the root are rejected, as are components the
+/// host's platform could treat as a root of their own.
+fn normalize(path: &str) -> Result<String> {
+    let mut components: Vec<&str> = Vec::new();
+    for component in path.split('/') {
+        match component {
+            "" | "." => {}
+            ".." => {
+                if components.pop().is_none() {
+                    log_then_return!("{} is outside the guest's file system", path);
+                }
+            }
+            _ if component.contains(['\\', ':', '\0']) => {
+                log_then_return!("{} is not a valid path", path);
+            }
+            _ => components.push(component),
+        }
+    }
+    Ok(components.join("/"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn normalize_paths() {
+        assert_eq!(normalize("/a/./b//c/../d").unwrap(), "a/b/d");
+        assert_eq!(normalize("/").unwrap(), "");
+        assert!(normalize("a/../..").is_err());
+        assert!(normalize("C:/Windows").is_err());
+        assert!(normalize("..\\secret").is_err());
+    }
+
+    #[test]
+    fn in_memory() {
+        let fs = VirtualFs::in_memory([("/etc/config", "abc"), ("/data/x/y", "")]).unwrap();
+        let mut state = VfsState::new(fs.clone()).unwrap();
+        assert_eq!(state.list("/").unwrap(), "data/\netc/");
+        assert_eq!(state.list("/data").unwrap(), "x/");
+        assert!(state.list("/missing").is_err());
+
+        let file = state.open("/etc/../etc/config", FS_OPEN_READ).unwrap();
+        assert_eq!(state.read(file, 2).unwrap(), b"ab");
+        assert_eq!(state.read(file, 2).unwrap(), b"c");
+        assert_eq!(state.read(file, 2).unwrap(), b"");
+        assert!(state.write(file, b"x".to_vec()).is_err());
+        state.close(file).unwrap();
+        assert!(state.read(file, 1).is_err());
+
+        assert!(state.open("/etc/config", FS_OPEN_WRITE).is_err());
+        let mut state = VfsState::new(fs.with_writes(true)).unwrap();
+        let file = state.open("/etc/config", FS_OPEN_APPEND).unwrap();
+        state.write(file, b"de".to_vec()).unwrap();
+        state.seek(file, 1).unwrap();
+        state.write(file, b"X".to_vec()).unwrap();
+        let file = state.open("/etc/config", FS_OPEN_READ).unwrap();
+        assert_eq!(state.read(file, 10).unwrap(), b"aXcde");
+    }
+
+    #[test]
+    fn size_limits() {
+        let fs = VirtualFs::in_memory([("/a", "abc")])
+            .unwrap()
+            .with_writes(true)
+            .with_max_file_size(8)
+            .with_max_memory_size(12);
+        let mut state = VfsState::new(fs).unwrap();
+        let file = state.open("/a", FS_OPEN_APPEND).unwrap();
+        state.seek(file, u64::MAX).unwrap();
+        assert!(state.write(file, b"x".to_vec()).is_err());
+        state.seek(file, 8).unwrap();
+        assert!(state.write(file, b"x".to_vec()).is_err());
+        state.seek(file, 3).unwrap();
+        state.write(file, b"defgh".to_vec()).unwrap();
+
+        let other = state.open("/b", FS_OPEN_WRITE).unwrap();
+        state.write(other, b"1234".to_vec()).unwrap();
+        assert!(state.write(other, b"5".to_vec()).is_err());
+        let file = state.open("/a", FS_OPEN_READ).unwrap();
+        assert_eq!(state.read(file, 10).unwrap(), b"abcdefgh");
+
+        let dir = tempfile::tempdir().unwrap();
+        let fs = VirtualFs::directory(dir.path())
+            .unwrap()
+            .with_writes(true)
+            .with_max_file_size(4);
+        let mut state = VfsState::new(fs).unwrap();
+        let file = state.open("/file", FS_OPEN_WRITE).unwrap();
+        state.write(file, b"abc".to_vec()).unwrap();
+        state.seek(file, 1 << 40).unwrap();
+        assert!(state.write(file, b"x".to_vec()).is_err());
+        let file = state.open("/file", FS_OPEN_APPEND).unwrap();
+        state.write(file, b"d".to_vec()).unwrap();
+        assert!(state.write(file, b"e".to_vec()).is_err());
+        assert_eq!(std::fs::read(dir.path().join("file")).unwrap(), b"abcd");
+    }
+
+    #[test]
+    fn directory() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::create_dir(dir.path().join("sub")).unwrap();
+        std::fs::write(dir.path().join("sub/file"), "hello").unwrap();
+        let outside = tempfile::NamedTempFile::new_in(dir.path().parent().unwrap()).unwrap();
+        #[cfg(unix)]
+        std::os::unix::fs::symlink(outside.path(), dir.path().join("link")).unwrap();
+
+        let fs = VirtualFs::directory(dir.path().join("sub/..")).unwrap();
+        let mut state = VfsState::new(fs.clone()).unwrap();
+        let file = state.open("/sub/file", FS_OPEN_READ).unwrap();
+        assert_eq!(state.read(file, 100).unwrap(), b"hello");
+        assert!(state.open("/sub/other", FS_OPEN_WRITE).is_err());
+        assert!(state.open("/link", FS_OPEN_READ).is_err());
+        assert!(state.open("/../etc/passwd", FS_OPEN_READ).is_err());
+
+        let mut state = VfsState::new(fs.with_writes(true).with_max_open_files(1)).unwrap();
+        let file = state.open("/sub/other", FS_OPEN_WRITE).unwrap();
+        assert!(state.open("/sub/file", FS_OPEN_READ).is_err());
+        state.write(file, b"written".to_vec()).unwrap();
+        state.close(file).unwrap();
+        assert!(state.open("/missing/file", FS_OPEN_WRITE).is_err());
+        assert_eq!(
+            std::fs::read(dir.path().join("sub/other")).unwrap(),
+            b"written"
+        );
+        assert_eq!(state.list("/sub").unwrap(), "file\nother");
+    }
+
+    #[test]
+    #[cfg(all(feature = "io_uring", target_os = "linux"))]
+    fn directory_through_io_uring() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("file"), "hello").unwrap();
+
+        let fs = VirtualFs::directory(dir.path())
+            .unwrap()
+            .with_writes(true)
+            .with_io_uring(true);
+        let mut state = VfsState::new(fs).unwrap();
+        let file = state.open("/file", FS_OPEN_READ).unwrap();
+        assert_eq!(state.read(file, 2).unwrap(), b"he");
+        state.seek(file, 4).unwrap();
+        assert_eq!(state.read(file, 100).unwrap(), b"o");
+        assert_eq!(state.read(file, 100).unwrap(), b"");
+
+        let file = state.open("/file", FS_OPEN_APPEND).unwrap();
+        state.write(file, b" world".to_vec()).unwrap();
+        let file = state.open("/other", FS_OPEN_WRITE).unwrap();
+        state.write(file, b"new".to_vec()).unwrap();
+        assert_eq!(
+            std::fs::read(dir.path().join("file")).unwrap(),
+            b"hello world"
+        );
+        assert_eq!(std::fs::read(dir.path().join("other")).unwrap(), b"new");
+    }
+}
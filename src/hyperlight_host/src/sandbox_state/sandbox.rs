@@ -20,6 +20,10 @@ use std::panic;
 use tracing::{instrument, Span};
 
 use super::transition::TransitionMetadata;
+use crate::hypervisor::VcpuStats;
+#[cfg(all(feature = "cgroup", target_os = "linux"))]
+use crate::sandbox::CgroupUsage;
+use crate::sandbox::CreationReport;
 use crate::Result;
 
 /// The minimal functionality of a Hyperlight sandbox. Most of the types
@@ -50,6 +54,31 @@ pub trait Sandbox: Sized + Debug {
     fn check_stack_guard(&self) -> Result<bool> {
         panic!("check_stack_guard not implemented for this type");
     }
+
+    /// The statistics the hypervisor keeps about the vCPU running the
+    /// sandbox, such as the number of exits and the time spent halt
+    /// polling, or `None` if the sandbox has no vCPU or the hypervisor
+    /// doesn't provide any. Only KVM on Linux 5.14 or later provides them.
+    #[instrument(err(Debug), skip_all, parent = Span::current(), level= "Trace")]
+    fn vcpu_stats(&self) -> Result<Option<VcpuStats>> {
+        Ok(None)
+    }
+
+    /// What the threads in the cgroup the sandbox was placed in with
+    /// `UninitializedSandbox::set_cgroup` have used, or `None` if it wasn't
+    /// placed in one
+    #[cfg(all(feature = "cgroup", target_os = "linux"))]
+    #[instrument(err(Debug), skip_all, parent = Span::current(), level= "Trace")]
+    fn cgroup_usage(&self) -> Result<Option<CgroupUsage>> {
+        Ok(None)
+    }
+
+    /// How long each stage of creating the sandbox took, so far, or `None`
+    /// if the sandbox doesn't keep track
+    #[instrument(err(Debug), skip_all, parent = Span::current(), level= "Trace")]
+    fn creation_report(&self) -> Result<Option<CreationReport>> {
+        Ok(None)
+    }
 }
 
 /// A utility trait to recognize a Sandbox that has not yet been initialized.
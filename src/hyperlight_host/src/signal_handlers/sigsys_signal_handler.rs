@@ -20,7 +20,7 @@ pub(super) extern "C" fn handle_sigsys(
     info: *mut libc::siginfo_t,
     context: *mut libc::c_void,
 ) {
-    #[cfg(target_arch = "x86_64")]
+    #[cfg(any(target_arch = "x86_64", target_arch = "aarch64"))]
     {
         unsafe {
             // si_code contains the reason for the SIGSYS signal.
@@ -77,7 +77,10 @@ pub(super) extern "C" fn handle_sigsys(
             let mcontext = &mut (*ucontext).uc_mcontext;
 
             if syscall == libc::SYS_ioctl as usize {
+                #[cfg(target_arch = "x86_64")]
                 let ioctl_param = mcontext.gregs[libc::REG_EBRACE as usize] as usize;
+                #[cfg(target_arch = "aarch64")]
+                let ioctl_param = mcontext.regs[1] as usize;
                 let ioctl_param_bytes =
                     raw_format(b"[ERROR][HYPERLIGHT] IOCTL Param: ", ioctl_param);
                 libc::write(
@@ -88,14 +91,21 @@ pub(super) extern "C" fn handle_sigsys(
             }
 
             // We don't want to return execution to the offending host function, so
-            // we alter the RIP register to point to a function that will panic out of
+            // we alter the instruction pointer to point to a function that will panic out of
             // the host function call.
-            mcontext.gregs[libc::REG_RIP as usize] =
-                after_syscall_violation as usize as libc::greg_t;
+            #[cfg(target_arch = "x86_64")]
+            {
+                mcontext.gregs[libc::REG_RIP as usize] =
+                    after_syscall_violation as usize as libc::greg_t;
+            }
+            #[cfg(target_arch = "aarch64")]
+            {
+                mcontext.pc = after_syscall_violation as usize as u64;
+            }
         }
     }
 
-    #[cfg(not(target_arch = "x86_64"))]
+    #[cfg(not(any(target_arch = "x86_64", target_arch = "aarch64")))]
     {
         compile_error!("Unsupported architecture for seccomp feature");
     }
@@ -0,0 +1,308 @@
+/*
+Copyright 2024 The Hyperlight Authors.
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+    http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+//! Runs the same guest function calls on every backend available in this
+//! process, and fails if any backend's result differs from the others'.
+//!
+//! KVM and mshv are never available on the same machine, so to compare
+//! them, set `HYPERLIGHT_DIFFERENTIAL_OUT` to a file on one machine, to
+//! write the results there as JSON, then set
+//! `HYPERLIGHT_DIFFERENTIAL_BASELINE` to that file on the other.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::sync::{Arc, Mutex};
+
+use hyperlight_common::flatbuffer_wrappers::guest_error::ErrorCode;
+use hyperlight_host::func::{HostFunction2, ParameterValue, ReturnType, ReturnValue};
+use hyperlight_host::hypervisor::capabilities;
+use hyperlight_host::sandbox_state::sandbox::EvolvableSandbox;
+use hyperlight_host::sandbox_state::transition::Noop;
+use hyperlight_host::{
+    GuestBinary, HyperlightError, MultiUseSandbox, Result, SandboxRunOptions, UninitializedSandbox,
+};
+
+pub mod common; // pub to disable dead_code warning
+use crate::common::get_c_or_rust_simpleguest_path;
+
+/// A guest function call to make on every backend
+struct Case {
+    function: &'static str,
+    return_type: ReturnType,
+    args: Vec<ParameterValue>,
+    /// Whether the call faults or aborts, which takes the whole process
+    /// down when the guest runs in-process
+    faults: bool,
+}
+
+impl Case {
+    fn new(function: &'static str, return_type: ReturnType, args: Vec<ParameterValue>) -> Self {
+        Self {
+            function,
+            return_type,
+            args,
+            faults: false,
+        }
+    }
+
+    fn faulting(self) -> Self {
+        Self {
+            faults: true,
+            ..self
+        }
+    }
+
+    /// The name the case's results are reported under
+    fn name(&self) -> String {
+        format!("{}{:?} -> {:?}", self.function, self.args, self.return_type)
+    }
+}
+
+fn cases() -> Vec<Case> {
+    use ParameterValue::*;
+    vec![
+        Case::new("Echo", ReturnType::String, vec![String("hello".into())]),
+        Case::new(
+            "SetByteArrayToZero",
+            ReturnType::VecBytes,
+            vec![VecBytes(vec![1; 100])],
+        ),
+        Case::new("EchoDouble", ReturnType::Double, vec![Double(-1.5)]),
+        Case::new("EchoFloat", ReturnType::Float, vec![Float(f32::MIN)]),
+        Case::new("Add", ReturnType::Int, vec![Int(40), Int(2)]),
+        Case::new("SmallVar", ReturnType::Int, vec![]),
+        Case::new("StackAllocate", ReturnType::Int, vec![Int(1024)]),
+        Case::new("CallMalloc", ReturnType::Int, vec![Int(4096)]),
+        Case::new("MallocAndFree", ReturnType::Int, vec![Int(4096)]),
+        Case::new("AddToStatic", ReturnType::Int, vec![Int(5)]),
+        Case::new("GuestAssert", ReturnType::Int, vec![Int(1)]),
+        Case::new(
+            "CustomError",
+            ReturnType::Void,
+            vec![UInt(7), String("custom".into())],
+        ),
+        Case::new("NoSuchFunction", ReturnType::Int, vec![]),
+        Case::new("Echo", ReturnType::Int, vec![String("hello".into())]),
+        Case::new("Echo", ReturnType::String, vec![Int(1)]),
+        Case::new("GuestAbortWithCode", ReturnType::Void, vec![Int(13)]).faulting(),
+        Case::new(
+            "GuestAbortWithMessage",
+            ReturnType::Void,
+            vec![Int(25), String("message".into())],
+        )
+        .faulting(),
+        Case::new(
+            "guest_panic",
+            ReturnType::Void,
+            vec![String("panic".into())],
+        )
+        .faulting(),
+        Case::new("StackOverflow", ReturnType::Int, vec![Int(10)]).faulting(),
+        Case::new("InfiniteRecursion", ReturnType::Void, vec![]).faulting(),
+        Case::new("TriggerException", ReturnType::ULong, vec![]).faulting(),
+        Case::new("ExecuteOnStack", ReturnType::String, vec![]).faulting(),
+        Case::new("WriteToHostFunctionDefinitions", ReturnType::String, vec![]).faulting(),
+    ]
+}
+
+/// A way of running the guest
+struct Backend {
+    name: String,
+    path: String,
+    run_options: Option<SandboxRunOptions>,
+}
+
+impl Backend {
+    fn in_process(&self) -> bool {
+        matches!(self.run_options, Some(SandboxRunOptions::RunInProcess(_)))
+    }
+
+    fn sandbox(&self) -> Result<MultiUseSandbox> {
+        let mut sandbox = UninitializedSandbox::new(
+            GuestBinary::FilePath(self.path.clone()),
+            None,
+            self.run_options.clone(),
+            None,
+        )?;
+        let host_add = Arc::new(Mutex::new(|a: i32, b: i32| Ok(a + b)));
+        host_add.register(&mut sandbox, "HostAdd")?;
+        sandbox.evolve(Noop::default())
+    }
+}
+
+/// The backends available in this process, each with the guest as an ELF
+/// and as a PE file
+fn backends() -> Vec<Backend> {
+    let elf_path = get_c_or_rust_simpleguest_path();
+    let exe_path = format!("{elf_path}.exe");
+    let hypervisor = match capabilities() {
+        c if c.mshv_present => "mshv",
+        c if c.kvm_api_version > 0 => "kvm",
+        _ if cfg!(target_os = "windows") => "whp",
+        _ => "hypervisor",
+    };
+
+    let mut backends = Vec::new();
+    for (format, path) in [("elf", &elf_path), ("exe", &exe_path)] {
+        if hyperlight_host::is_hypervisor_present() {
+            backends.push(Backend {
+                name: format!("{} ({})", hypervisor, format),
+                path: path.clone(),
+                run_options: None,
+            });
+        }
+        #[cfg(inprocess)]
+        backends.push(Backend {
+            name: format!("in-process ({})", format),
+            path: path.clone(),
+            run_options: Some(SandboxRunOptions::RunInProcess(false)),
+        });
+    }
+    #[cfg(all(target_os = "windows", inprocess))]
+    backends.push(Backend {
+        name: "loadlib (exe)".to_string(),
+        path: exe_path,
+        run_options: Some(SandboxRunOptions::RunInProcess(true)),
+    });
+    backends
+}
+
+/// Describe the result of a call without the details that depend on where
+/// the guest was loaded, such as addresses
+fn outcome(result: Result<ReturnValue>) -> String {
+    match result {
+        Ok(value) => format!("returned {:?}", value),
+        Err(HyperlightError::GuestAborted { code, message, .. })
+            if code == ErrorCode::GuestException as u8 =>
+        {
+            let exception = message.split(" at RIP").next().unwrap_or_default();
+            match message.rsplit_once("error code ") {
+                Some((_, error_code)) => {
+                    format!("aborted with {} error code {}", exception, error_code)
+                }
+                None => format!("aborted with {}", exception),
+            }
+        }
+        Err(HyperlightError::GuestAborted { code, .. }) => format!("aborted with code {}", code),
+        Err(HyperlightError::GuestError(code, ..)) => format!("guest error {:?}", code),
+        Err(HyperlightError::GuestCustomError { code, .. }) => {
+            format!("guest custom error {}", code)
+        }
+        Err(e) => {
+            let debug = format!("{:?}", e);
+            let variant = debug.split(['(', ' ', '{']).next().unwrap_or_default();
+            format!("failed with {}", variant)
+        }
+    }
+}
+
+/// The outcome of each case on each backend it ran on
+type Results = BTreeMap<String, BTreeMap<String, String>>;
+
+fn run_cases(backends: &[Backend]) -> Results {
+    let mut results = Results::new();
+    for case in cases() {
+        let outcomes = results.entry(case.name()).or_default();
+        for backend in backends {
+            if case.faults && backend.in_process() {
+                continue;
+            }
+            // a fresh sandbox for each call, so that a call that poisons the
+            // sandbox doesn't change the outcome of the next
+            let outcome = backend.sandbox().map_or_else(
+                |e| format!("sandbox creation {}", outcome(Err(e))),
+                |mut sandbox| {
+                    outcome(sandbox.call_guest_function_by_name(
+                        case.function,
+                        case.return_type,
+                        Some(case.args.clone()),
+                    ))
+                },
+            );
+            outcomes.insert(backend.name.clone(), outcome);
+        }
+    }
+    results
+}
+
+/// Describe each case whose outcomes differ, listing the outcome on each
+/// backend
+fn divergences(results: &Results) -> Vec<String> {
+    results
+        .iter()
+        .filter(|(_, outcomes)| outcomes.values().collect::<BTreeSet<_>>().len() > 1)
+        .map(|(case, outcomes)| {
+            let lines: Vec<String> = outcomes
+                .iter()
+                .map(|(backend, outcome)| format!("    {}: {}", backend, outcome))
+                .collect();
+            format!("{}\n{}", case, lines.join("\n"))
+        })
+        .collect()
+}
+
+#[test]
+fn backends_agree() {
+    let backends = backends();
+    if backends.is_empty() {
+        println!("No backends available, skipping");
+        return;
+    }
+    let mut results = run_cases(&backends);
+
+    if let Ok(path) = std::env::var("HYPERLIGHT_DIFFERENTIAL_OUT") {
+        let json = serde_json::to_string_pretty(&results).unwrap();
+        std::fs::write(&path, json).unwrap();
+    }
+    if let Ok(path) = std::env::var("HYPERLIGHT_DIFFERENTIAL_BASELINE") {
+        let baseline: Results =
+            serde_json::from_str(&std::fs::read_to_string(&path).unwrap()).unwrap();
+        for (case, outcomes) in baseline {
+            for (backend, outcome) in outcomes {
+                results
+                    .entry(case.clone())
+                    .or_default()
+                    .entry(format!("{} from {}", backend, path))
+                    .or_insert(outcome);
+            }
+        }
+    }
+
+    let divergences = divergences(&results);
+    assert!(
+        divergences.is_empty(),
+        "backends disagree on {} cases:\n{}",
+        divergences.len(),
+        divergences.join("\n")
+    );
+}
+
+#[test]
+fn divergences_are_reported() {
+    let mut results = Results::new();
+    results.entry("Same".to_string()).or_default().extend([
+        ("kvm".to_string(), "returned Int(1)".to_string()),
+        ("in-process".to_string(), "returned Int(1)".to_string()),
+    ]);
+    results.entry("Different".to_string()).or_default().extend([
+        ("kvm".to_string(), "guest error GuestError".to_string()),
+        ("mshv".to_string(), "failed with Error".to_string()),
+    ]);
+
+    assert_eq!(
+        divergences(&results),
+        vec!["Different\n    kvm: guest error GuestError\n    mshv: failed with Error"]
+    );
+}
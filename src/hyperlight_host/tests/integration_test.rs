@@ -14,16 +14,23 @@ See the License for the specific language governing permissions and
 limitations under the License.
 */
 
+use std::sync::{Arc, Mutex};
+
 use hyperlight_common::flatbuffer_wrappers::guest_error::ErrorCode;
+use hyperlight_common::heap_profile::{HeapProfile, CALL_STACK_DEPTH};
 use hyperlight_common::mem::PAGE_SIZE;
+use hyperlight_common::outb::{USER_PORT_BASE, USER_PORT_COUNT};
 use hyperlight_host::func::{ParameterValue, ReturnType, ReturnValue};
-#[cfg(not(feature = "executable_heap"))]
-use hyperlight_host::mem::memory_region::MemoryRegionFlags;
-use hyperlight_host::sandbox::SandboxConfiguration;
+use hyperlight_host::sandbox::{EventSubscriber, SandboxConfiguration, SandboxId};
 use hyperlight_host::sandbox_state::sandbox::EvolvableSandbox;
 use hyperlight_host::sandbox_state::transition::Noop;
-use hyperlight_host::{GuestBinary, HyperlightError, SingleUseSandbox, UninitializedSandbox};
-use hyperlight_testing::{c_simple_guest_as_string, simple_guest_as_string};
+use hyperlight_host::{
+    GuestBinary, HyperlightError, MultiUseSandbox, SingleUseSandbox, UninitializedSandbox,
+};
+use hyperlight_testing::sandbox::assert_guest_aborted;
+use hyperlight_testing::{
+    c_simple_guest_as_string, compute_guest_as_string, simple_guest_as_string,
+};
 
 pub mod common; // pub to disable dead_code warning
 use crate::common::{new_uninit, new_uninit_rust};
@@ -63,8 +70,76 @@ fn guest_abort() {
         .unwrap_err();
     println!("{:?}", res);
     assert!(
-        matches!(res, HyperlightError::GuestAborted(code, message) if (code == error_code && message.is_empty()) )
+        matches!(res, HyperlightError::GuestAborted { code, message, .. } if (code == error_code && message.is_empty()) )
+    );
+}
+
+// Checks that a guest abort poisons a multi-use sandbox until it is restored.
+#[test]
+fn guest_abort_poisons_sandbox() {
+    let mut sbox1: MultiUseSandbox = new_uninit().unwrap().evolve(Noop::default()).unwrap();
+    let res = sbox1.call_guest_function_by_name(
+        "GuestAbortWithCode",
+        ReturnType::Void,
+        Some(vec![ParameterValue::Int(13)]),
+    );
+    assert_guest_aborted(res, 13);
+    assert!(sbox1.is_poisoned());
+
+    let res = sbox1
+        .call_guest_function_by_name(
+            "Echo",
+            ReturnType::String,
+            Some(vec![ParameterValue::String("hello".to_string())]),
+        )
+        .unwrap_err();
+    assert!(matches!(res, HyperlightError::PoisonedSandbox(id) if id == sbox1.id()));
+
+    sbox1.clear_poison().unwrap();
+    assert!(!sbox1.is_poisoned());
+    let res = sbox1
+        .call_guest_function_by_name(
+            "Echo",
+            ReturnType::String,
+            Some(vec![ParameterValue::String("hello".to_string())]),
+        )
+        .unwrap();
+    assert_eq!(res, ReturnValue::String("hello".to_string()));
+}
+
+#[test]
+fn stack_overflow_abort_poisons_sandbox() {
+    let mut sbox1: MultiUseSandbox = new_uninit_rust().unwrap().evolve(Noop::default()).unwrap();
+    // the guest's stack probe aborts with ErrorCode::StackOverflow
+    let res = sbox1
+        .call_guest_function_by_name(
+            "StackAllocate",
+            ReturnType::Int,
+            Some(vec![ParameterValue::Int(10 * 1024 * 1024)]),
+        )
+        .unwrap_err();
+    assert!(matches!(res, HyperlightError::StackOverflow()));
+    assert!(sbox1.is_poisoned());
+
+    let res = sbox1
+        .call_guest_function_by_name("GetStatic", ReturnType::Int, None)
+        .unwrap_err();
+    assert!(matches!(res, HyperlightError::PoisonedSandbox(id) if id == sbox1.id()));
+}
+
+// Checks that a CPU exception in the guest is reported rather than failing the vCPU.
+#[test]
+#[cfg(target_arch = "x86_64")]
+fn guest_exception() {
+    let mut sbox1: MultiUseSandbox = new_uninit().unwrap().evolve(Noop::default()).unwrap();
+    let res = sbox1
+        .call_guest_function_by_name("TriggerException", ReturnType::ULong, None)
+        .unwrap_err();
+    println!("{:?}", res);
+    assert!(
+        matches!(res, HyperlightError::GuestAborted { code, message, .. } if (code == ErrorCode::GuestException as u8 && message.starts_with("Page fault") && message.contains("addr 0x100000000")))
     );
+    assert!(sbox1.is_poisoned());
 }
 
 #[test]
@@ -83,7 +158,7 @@ fn guest_abort_with_context1() {
         .unwrap_err();
     println!("{:?}", res);
     assert!(
-        matches!(res, HyperlightError::GuestAborted(code, context) if (code == 25 && context == "Oh no"))
+        matches!(res, HyperlightError::GuestAborted { code, message: context, .. } if (code == 25 && context == "Oh no"))
     );
 }
 
@@ -135,7 +210,7 @@ fn guest_abort_with_context2() {
         .unwrap_err();
     println!("{:?}", res);
     assert!(
-        matches!(res, HyperlightError::GuestAborted(_, context) if context.contains(&abort_message[..400]))
+        matches!(res, HyperlightError::GuestAborted { message: context, .. } if context.contains(&abort_message[..400]))
     );
 }
 
@@ -161,7 +236,34 @@ fn guest_abort_c_guest() {
         .unwrap_err();
     println!("{:?}", res);
     assert!(
-        matches!(res, HyperlightError::GuestAborted(code, message) if (code == 75 && message == "This is a test error message"))
+        matches!(res, HyperlightError::GuestAborted { code, message, .. } if (code == 75 && message == "This is a test error message"))
+    );
+}
+
+#[test]
+fn guest_assert() {
+    // this test is rust-specific
+    let mut sbox1: MultiUseSandbox = new_uninit_rust().unwrap().evolve(Noop::default()).unwrap();
+
+    let res = sbox1
+        .call_guest_function_by_name(
+            "GuestAssert",
+            ReturnType::Int,
+            Some(vec![ParameterValue::Int(0)]),
+        )
+        .unwrap();
+    assert_eq!(res, ReturnValue::Int(0));
+
+    let res = sbox1
+        .call_guest_function_by_name(
+            "GuestAssert",
+            ReturnType::Int,
+            Some(vec![ParameterValue::Int(5)]),
+        )
+        .unwrap_err();
+    println!("{:?}", res);
+    assert!(
+        matches!(res, HyperlightError::GuestAborted { code, message, .. } if code == ErrorCode::GuestError as u8 && message.starts_with("src/main.rs:") && message.ends_with(": expected 0 but got 5"))
     );
 }
 
@@ -181,10 +283,194 @@ fn guest_panic() {
         .unwrap_err();
     println!("{:?}", res);
     assert!(
-        matches!(res, HyperlightError::GuestAborted(code, context) if code == ErrorCode::UnknownError as u8 && context.contains("\nError... error..."))
+        matches!(res, HyperlightError::GuestAborted { code, message: context, .. } if code == ErrorCode::UnknownError as u8 && context.contains("\nError... error..."))
     )
 }
 
+/// Run by `just test-guest-panic-recovery`, once it has built simpleguest
+/// with the `unwind_to_error` feature
+#[test]
+#[ignore]
+fn guest_panic_is_recovered() {
+    let profile = if cfg!(debug_assertions) {
+        "debug"
+    } else {
+        "release"
+    };
+    let path = std::path::Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("../tests/rust_guests/simpleguest/target/unwind_to_error/x86_64-unknown-none")
+        .join(profile)
+        .join("simpleguest");
+    let mut sbox: MultiUseSandbox = UninitializedSandbox::new(
+        GuestBinary::FilePath(path.to_string_lossy().into_owned()),
+        None,
+        None,
+        None,
+    )
+    .unwrap()
+    .evolve(Noop::default())
+    .unwrap();
+
+    for message in ["Error... error...".to_string(), "a".repeat(4096)] {
+        let res = sbox
+            .call_guest_function_by_name(
+                "guest_panic",
+                ReturnType::Void,
+                Some(vec![ParameterValue::String(message.clone())]),
+            )
+            .unwrap_err();
+        println!("{:?}", res);
+        // long messages are cut short
+        assert!(
+            matches!(res, HyperlightError::GuestError(ErrorCode::GuestError, ref context, _)
+                if context.starts_with("Guest function panicked: ")
+                    && context.contains(&message[..16])
+                    && context.len() < 1024)
+        );
+
+        // the guest can still be called
+        let res = sbox
+            .call_guest_function_by_name(
+                "Echo",
+                ReturnType::String,
+                Some(vec![ParameterValue::String("hello".to_string())]),
+            )
+            .unwrap();
+        assert_eq!(res, ReturnValue::String("hello".to_string()));
+    }
+}
+
+/// Run by `just test-guest-heap-profiling`, once it has built simpleguest
+/// with the `heap_profiling` feature
+#[test]
+#[ignore]
+fn heap_profile_survives_corrupted_frame_pointer() {
+    #[derive(Default)]
+    struct Profiles(Mutex<Vec<HeapProfile>>);
+    impl EventSubscriber for Profiles {
+        fn on_heap_profile(&self, _sandbox_id: SandboxId, profile: &HeapProfile) {
+            self.0.lock().unwrap().push(profile.clone());
+        }
+    }
+
+    let profile = if cfg!(debug_assertions) {
+        "debug"
+    } else {
+        "release"
+    };
+    let path = std::path::Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("../tests/rust_guests/simpleguest/target/heap_profiling/x86_64-unknown-none")
+        .join(profile)
+        .join("simpleguest");
+    let mut sbox = UninitializedSandbox::new(
+        GuestBinary::FilePath(path.to_string_lossy().into_owned()),
+        None,
+        None,
+        None,
+    )
+    .unwrap();
+    let profiles = Arc::new(Profiles::default());
+    sbox.add_event_subscriber(profiles.clone());
+    let mut sbox: MultiUseSandbox = sbox.evolve(Noop::default()).unwrap();
+
+    let size = 12345;
+    sbox.call_guest_function_by_name(
+        "AllocateWithCorruptedFramePointer",
+        ReturnType::Void,
+        Some(vec![ParameterValue::Int(size)]),
+    )
+    .unwrap();
+
+    let profiles = profiles.0.lock().unwrap();
+    assert_eq!(profiles.len(), 1);
+    let site = profiles[0]
+        .call_sites
+        .iter()
+        .find(|site| site.bytes >= size as u64)
+        .expect("the allocation should be recorded");
+    assert!(!site.stack.is_empty() && site.stack.len() <= CALL_STACK_DEPTH);
+}
+
+#[test]
+fn guest_custom_error() {
+    // this test is rust-specific
+    let sbox1: SingleUseSandbox = new_uninit_rust().unwrap().evolve(Noop::default()).unwrap();
+
+    let res = sbox1
+        .call_guest_function_by_name(
+            "CustomError",
+            ReturnType::Void,
+            Some(vec![
+                ParameterValue::UInt(42),
+                ParameterValue::String("out of widgets".to_string()),
+            ]),
+        )
+        .unwrap_err();
+    assert!(
+        matches!(res, HyperlightError::GuestCustomError { code, message, .. } if code == 42 && message == "out of widgets")
+    );
+}
+
+#[test]
+fn guest_error_with_payload() {
+    // this test is rust-specific
+    let sbox1: SingleUseSandbox = new_uninit_rust().unwrap().evolve(Noop::default()).unwrap();
+
+    let res = sbox1
+        .call_guest_function_by_name(
+            "ErrorWithPayload",
+            ReturnType::Void,
+            Some(vec![ParameterValue::String("diagnostics".to_string())]),
+        )
+        .unwrap_err();
+    assert!(
+        matches!(res, HyperlightError::GuestError(ErrorCode::GuestError, message, Some(payload)) if message == "error with payload" && payload == b"diagnostics")
+    );
+}
+
+#[test]
+fn guest_signals_registered_port() {
+    // this test is rust-specific
+    let signals = Arc::new(Mutex::new(Vec::new()));
+    let signals_clone = signals.clone();
+    let mut uninit = new_uninit_rust().unwrap();
+    uninit
+        .register_port_handler(USER_PORT_BASE, move |value| {
+            signals_clone.lock().unwrap().push(value);
+            Ok(())
+        })
+        .unwrap();
+    assert!(uninit
+        .register_port_handler(USER_PORT_BASE + USER_PORT_COUNT, |_| Ok(()))
+        .is_err());
+    let mut sbox1: MultiUseSandbox = uninit.evolve(Noop::default()).unwrap();
+
+    for value in [1, 2] {
+        sbox1
+            .call_guest_function_by_name(
+                "SignalHost",
+                ReturnType::Void,
+                Some(vec![
+                    ParameterValue::UInt(USER_PORT_BASE as u32),
+                    ParameterValue::UInt(value),
+                ]),
+            )
+            .unwrap();
+    }
+    assert_eq!(*signals.lock().unwrap(), vec![1, 2]);
+
+    // a user port without a handler falls back to the unknown outb policy
+    let res = sbox1.call_guest_function_by_name(
+        "SignalHost",
+        ReturnType::Void,
+        Some(vec![
+            ParameterValue::UInt(USER_PORT_BASE as u32 + 1),
+            ParameterValue::UInt(1),
+        ]),
+    );
+    assert!(res.is_err());
+}
+
 #[test]
 fn guest_malloc() {
     // this test is rust-only
@@ -234,7 +520,7 @@ fn guest_malloc_abort() {
         .unwrap_err();
     println!("{:?}", res);
     assert!(
-        matches!(res, HyperlightError::GuestAborted(code, _) if code == ErrorCode::MallocFailed as u8)
+        matches!(res, HyperlightError::GuestAborted { code, .. } if code == ErrorCode::MallocFailed as u8)
     );
 
     // allocate a vector (on heap) that is bigger than the heap
@@ -262,7 +548,7 @@ fn guest_malloc_abort() {
     assert!(matches!(
         res.unwrap_err(),
         // OOM memory errors in rust allocator are panics. Our panic handler returns ErrorCode::UnknownError on panic
-        HyperlightError::GuestAborted(code, msg) if code == ErrorCode::UnknownError as u8 && msg.contains("memory allocation of ")
+        HyperlightError::GuestAborted { code, message: msg, .. } if code == ErrorCode::UnknownError as u8 && msg.contains("memory allocation of ")
     ));
 }
 
@@ -426,7 +712,35 @@ fn guard_page_check_2() {
     assert!(matches!(result, HyperlightError::StackOverflow()));
 }
 
+/// Check that `err` reports a page fault in the guest with the given error
+/// code, whose bit 0 is set when the page was present, bit 1 when the access
+/// was a write and bit 4 when it was an instruction fetch
+#[cfg(target_arch = "x86_64")]
+fn assert_page_fault(err: HyperlightError, error_code: u64) {
+    println!("{:?}", err);
+    let HyperlightError::GuestAborted { code, message, .. } = err else {
+        panic!("expected the guest to abort");
+    };
+    assert_eq!(code, ErrorCode::GuestException as u8);
+    assert!(message.starts_with("Page fault"));
+    assert!(message.ends_with(&format!("error code {:#x}", error_code)));
+
+    // an instruction fetch faults on the address it fetches from
+    if error_code & 0x10 != 0 {
+        let field = |name: &str| message.split_once(name).unwrap().1.split(' ').next();
+        assert_eq!(field("RIP "), field("addr "));
+    }
+}
+
+/// Instruction fetch from a present page
+#[cfg(target_arch = "x86_64")]
+const PF_PRESENT_FETCH: u64 = 0x11;
+/// Write to a present page
+#[cfg(target_arch = "x86_64")]
+const PF_PRESENT_WRITE: u64 = 0x3;
+
 #[test]
+#[cfg(target_arch = "x86_64")]
 fn execute_on_stack() {
     let sbox1: SingleUseSandbox = new_uninit().unwrap().evolve(Noop::default()).unwrap();
 
@@ -434,20 +748,8 @@ fn execute_on_stack() {
         .call_guest_function_by_name("ExecuteOnStack", ReturnType::String, Some(vec![]))
         .unwrap_err();
 
-    // TODO: because we set the stack as NX in the guest PTE we get a generic error, once we handle the exception correctly in the guest we can make this more specific
-    if let HyperlightError::Error(message) = result {
-        cfg_if::cfg_if! {
-            if #[cfg(target_os = "linux")] {
-                assert!(message.starts_with("Unexpected VM Exit") || message.starts_with("unknown Hyper-V run message type"));
-            } else if #[cfg(target_os = "windows")] {
-                assert!(message.starts_with("Unexpected VM Exit \"Did not receive a halt from Hypervisor as expected - Received WHV_RUN_VP_EXIT_REASON(4)"));
-            } else {
-                panic!("Unexpected");
-            }
-        }
-    } else {
-        panic!("Unexpected error type");
-    }
+    // the stack is mapped NX in the guest's page tables
+    assert_page_fault(result, PF_PRESENT_FETCH);
 }
 
 #[test]
@@ -461,18 +763,39 @@ fn execute_on_heap() {
     #[cfg(feature = "executable_heap")]
     assert!(result.is_ok());
 
-    #[cfg(not(feature = "executable_heap"))]
-    {
-        assert!(result.is_err());
-        let err = result.unwrap_err();
-        assert!(
-            matches!(
-                err,
-                HyperlightError::MemoryAccessViolation(_, MemoryRegionFlags::EXECUTE, _)
-            ) || matches!(err, HyperlightError::Error(ref s) if s.starts_with("Unexpected VM Exit"))
-                || matches!(err, HyperlightError::Error(ref s) if s.starts_with("unknown Hyper-V run message type")) // Because the memory is set as NX in the guest PTE we get a generic error, once we handle the exception correctly in the guest we can make this more specific
-        );
-    }
+    // the heap is mapped NX in the guest's page tables
+    #[cfg(all(not(feature = "executable_heap"), target_arch = "x86_64"))]
+    assert_page_fault(result.unwrap_err(), PF_PRESENT_FETCH);
+}
+
+// The host function definitions are mapped read-only in the guest
+#[test]
+#[cfg(target_arch = "x86_64")]
+fn write_to_host_function_definitions() {
+    let mut sbox1: MultiUseSandbox = new_uninit_rust().unwrap().evolve(Noop::default()).unwrap();
+
+    let result = sbox1
+        .call_guest_function_by_name(
+            "WriteToHostFunctionDefinitions",
+            ReturnType::String,
+            Some(vec![]),
+        )
+        .unwrap_err();
+
+    assert_page_fault(result, PF_PRESENT_WRITE);
+    assert!(sbox1.is_poisoned());
+}
+
+#[test]
+#[cfg(target_arch = "x86_64")]
+fn write_to_code() {
+    let sbox1: SingleUseSandbox = new_uninit_rust().unwrap().evolve(Noop::default()).unwrap();
+
+    let result = sbox1
+        .call_guest_function_by_name("WriteToCode", ReturnType::String, Some(vec![]))
+        .unwrap_err();
+
+    assert_page_fault(result, PF_PRESENT_WRITE);
 }
 
 // checks that a recursive function with stack allocation eventually fails with stackoverflow
@@ -493,6 +816,48 @@ fn recursive_stack_allocate_overflow() {
     assert!(matches!(res, HyperlightError::StackOverflow()));
 }
 
+#[test]
+fn compute_guest() {
+    let path = compute_guest_as_string().unwrap();
+    let mut sbox: MultiUseSandbox =
+        UninitializedSandbox::new(GuestBinary::FilePath(path), None, None, None)
+            .unwrap()
+            .evolve(Noop::default())
+            .unwrap();
+
+    let res = sbox
+        .call_guest_function_by_name(
+            "Fibonacci",
+            ReturnType::ULong,
+            Some(vec![ParameterValue::Int(20)]),
+        )
+        .unwrap();
+    assert_eq!(res, ReturnValue::ULong(6765));
+
+    let res = sbox
+        .call_guest_function_by_name(
+            "MatrixMultiply",
+            ReturnType::ULong,
+            Some(vec![ParameterValue::Int(3)]),
+        )
+        .unwrap();
+    assert_eq!(res, ReturnValue::ULong(72));
+
+    let res = sbox
+        .call_guest_function_by_name(
+            "Sha256",
+            ReturnType::VecBytes,
+            Some(vec![ParameterValue::VecBytes(b"abc".to_vec())]),
+        )
+        .unwrap();
+    let expected = [
+        0xba, 0x78, 0x16, 0xbf, 0x8f, 0x01, 0xcf, 0xea, 0x41, 0x41, 0x40, 0xde, 0x5d, 0xae, 0x22,
+        0x23, 0xb0, 0x03, 0x61, 0xa3, 0x96, 0x17, 0x7a, 0x9c, 0xb4, 0x10, 0xff, 0x61, 0xf2, 0x00,
+        0x15, 0xad,
+    ];
+    assert_eq!(res, ReturnValue::VecBytes(expected.to_vec()));
+}
+
 // Check that log messages are emitted correctly from the guest
 // This test is ignored as it sets a logger and therefore maybe impacted by other tests running concurrently
 // or it may impact other tests.
@@ -559,6 +924,25 @@ fn log_message() {
     log_test_messages();
     assert_eq!(0, LOGGER.num_log_calls());
     assert_eq!(LOGGER.num_log_calls(), LOGGER.num_enabled_calls());
+
+    // test that a target chosen by the guest flows through to the host
+    log::set_max_level(log::LevelFilter::Info);
+    LOGGER.clear_log_calls();
+    let sbox: SingleUseSandbox = new_uninit_rust().unwrap().evolve(Noop::default()).unwrap();
+    sbox.call_guest_function_by_name(
+        "LogMessage",
+        ReturnType::Void,
+        Some(vec![
+            ParameterValue::String("Hello from a target".to_string()),
+            ParameterValue::String("audit".to_string()),
+            ParameterValue::Int(log::LevelFilter::Info as i32),
+        ]),
+    )
+    .unwrap();
+    assert_eq!(1, LOGGER.num_log_calls());
+    let log_call = LOGGER.get_log_call(0).unwrap();
+    assert_eq!(log_call.target, "hyperlight-guest::audit");
+    assert_eq!(log_call.args, "Hello from a target");
 }
 
 fn log_test_messages() {
@@ -572,6 +956,7 @@ fn log_test_messages() {
                 ReturnType::Void,
                 Some(vec![
                     ParameterValue::String(message.to_string()),
+                    ParameterValue::String(String::new()),
                     ParameterValue::Int(level as i32),
                 ]),
             )
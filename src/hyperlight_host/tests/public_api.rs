@@ -0,0 +1,26 @@
+/*
+Copyright 2024 The Hyperlight Authors.
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+    http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+//! Checks the boundary of the public API: hosts written against
+//! `hyperlight_host::prelude` keep compiling, and the implementation
+//! details of sandboxes stay private to the crate.
+
+#[test]
+fn public_api() {
+    let t = trybuild::TestCases::new();
+    t.pass("tests/public_api/pass/*.rs");
+    t.compile_fail("tests/public_api/fail/*.rs");
+}
@@ -0,0 +1,57 @@
+/*
+Copyright 2024 The Hyperlight Authors.
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+    http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+// A host using only the prelude. It is compiled and run, but as it can't
+// rely on a hypervisor being present it only creates the sandbox when
+// asked to.
+
+use std::sync::{Arc, Mutex};
+
+use hyperlight_host::prelude::*;
+
+fn run(guest: String) -> Result<ReturnValue> {
+    let mut config = SandboxConfiguration::default();
+    config.set_input_data_size(0x4000);
+    let mut sandbox =
+        UninitializedSandbox::new(GuestBinary::FilePath(guest), Some(config), None, None)?;
+
+    fn add(a: i32, b: i32) -> Result<i32> {
+        a.checked_add(b)
+            .ok_or_else(|| new_error!("{} + {} overflows", a, b))
+    }
+    Arc::new(Mutex::new(add)).register(&mut sandbox, "Add")?;
+
+    let sandbox: MultiUseSandbox = sandbox.evolve(Noop::default())?;
+    let mut ctx = MultiUseGuestCallContext::start(sandbox);
+    let value = ctx.call(
+        "Echo",
+        ReturnType::String,
+        Some(vec![ParameterValue::String("Hello".to_string())]),
+    )?;
+    let _sandbox: MultiUseSandbox = ctx.finish()?;
+    Ok(value)
+}
+
+fn main() {
+    if let Some(guest) = std::env::args().nth(1) {
+        match run(guest) {
+            Ok(value) => println!("{:?}", value),
+            Err(HyperlightError::Error(message)) => eprintln!("{}", message),
+            Err(e) => eprintln!("{}", e),
+        }
+    }
+    let _ = is_hypervisor_present();
+}
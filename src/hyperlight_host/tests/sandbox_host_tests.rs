@@ -15,17 +15,30 @@ limitations under the License.
 */
 
 use core::f64;
+use std::collections::HashMap;
 use std::sync::{Arc, Mutex};
+use std::time::Duration;
 
-use common::new_uninit;
-use hyperlight_host::func::{HostFunction1, ParameterValue, ReturnType, ReturnValue};
-use hyperlight_host::sandbox::SandboxConfiguration;
-use hyperlight_host::sandbox_state::sandbox::EvolvableSandbox;
+use common::{new_uninit, new_uninit_rust};
+use hyperlight_common::flatbuffer_wrappers::function_types::ParameterType;
+use hyperlight_common::flatbuffer_wrappers::guest_error::ErrorCode;
+use hyperlight_host::func::{
+    call_context, CallOptions, HostFunction1, ParameterValue, ReturnType, ReturnValue, Secret,
+};
+use hyperlight_host::sandbox::{HostPrintOptions, SandboxConfiguration};
+use hyperlight_host::sandbox_state::sandbox::{DevolvableSandbox, EvolvableSandbox, Sandbox};
 use hyperlight_host::sandbox_state::transition::Noop;
 use hyperlight_host::{
     new_error, GuestBinary, HyperlightError, MultiUseSandbox, Result, UninitializedSandbox,
 };
-use hyperlight_testing::simple_guest_as_string;
+use hyperlight_testing::mock::{Expectation, MockHostFunctions};
+use hyperlight_testing::sandbox::{assert_guest_error, new_sandbox, new_uninit_sandbox};
+use hyperlight_testing::strategies::parameter_value_of;
+use hyperlight_testing::{
+    callback_guest_as_string, chatty_guest_as_string, locate_or_build_rust_guest,
+    simple_guest_as_string,
+};
+use proptest::prelude::*;
 #[cfg(target_os = "windows")]
 use serial_test::serial; // using LoadLibrary requires serial tests
 
@@ -136,9 +149,7 @@ fn invalid_guest_function_name() {
         let fn_name = "FunctionDoesntExist";
         let res = sandbox.call_guest_function_by_name(fn_name, ReturnType::Int, None);
         println!("{:?}", res);
-        assert!(
-            matches!(res.unwrap_err(), HyperlightError::GuestError(hyperlight_common::flatbuffer_wrappers::guest_error::ErrorCode::GuestFunctionNotFound, error_name) if error_name == fn_name)
-        );
+        assert_guest_error(res, ErrorCode::GuestFunctionNotFound, fn_name);
     }
 }
 
@@ -357,7 +368,8 @@ fn incorrect_parameter_type() {
             res.unwrap_err(),
             HyperlightError::GuestError(
                 hyperlight_common::flatbuffer_wrappers::guest_error::ErrorCode::GuestFunctionParameterTypeMismatch,
-                msg
+                msg,
+                _
             ) if msg == "Expected parameter type String for parameter index 0 of function Echo but got Int."
         ));
     }
@@ -379,12 +391,27 @@ fn incorrect_parameter_num() {
             res.unwrap_err(),
             HyperlightError::GuestError(
                 hyperlight_common::flatbuffer_wrappers::guest_error::ErrorCode::GuestFunctionIncorrecNoOfParameters,
-                msg
+                msg,
+                _
             ) if msg == "Called function Echo with 2 parameters but it takes 1."
         ));
     }
 }
 
+proptest! {
+    #![proptest_config(ProptestConfig::with_cases(32))]
+
+    #[test]
+    fn echo_roundtrip(value in parameter_value_of(ParameterType::String)) {
+        let mut sandbox: MultiUseSandbox = new_uninit_rust().unwrap().evolve(Noop::default()).unwrap();
+        let ParameterValue::String(expected) = value.clone() else {
+            unreachable!()
+        };
+        let res = sandbox.call_guest_function_by_name("Echo", ReturnType::String, Some(vec![value]));
+        prop_assert_eq!(res.unwrap(), ReturnValue::String(expected));
+    }
+}
+
 #[test]
 fn max_memory_sandbox() {
     let mut cfg = SandboxConfiguration::default();
@@ -402,6 +429,495 @@ fn max_memory_sandbox() {
     ));
 }
 
+#[test]
+fn io_buffers_grow_to_fit() {
+    let guest = locate_or_build_rust_guest("simpleguest").unwrap();
+    let new_sandbox = |max_data_size: usize| -> MultiUseSandbox {
+        hyperlight_testing::sandbox::new_sandbox(&guest, |cfg| {
+            cfg.set_heap_size(0x100000);
+            cfg.set_max_input_data_size(max_data_size);
+            cfg.set_max_output_data_size(max_data_size);
+        })
+        .unwrap()
+    };
+    let message = "a".repeat(SandboxConfiguration::DEFAULT_INPUT_SIZE * 3);
+    let echo = |sandbox: &mut MultiUseSandbox| {
+        sandbox.call_guest_function_by_name(
+            "Echo",
+            ReturnType::String,
+            Some(vec![ParameterValue::String(message.clone())]),
+        )
+    };
+
+    // the buffers don't grow by default
+    let mut sandbox = new_sandbox(0);
+    assert!(echo(&mut sandbox).is_err());
+
+    let mut sandbox = new_sandbox(SandboxConfiguration::DEFAULT_INPUT_SIZE * 4);
+    for _ in 0..2 {
+        assert_eq!(
+            echo(&mut sandbox).unwrap(),
+            ReturnValue::String(message.clone())
+        );
+    }
+
+    // but not beyond the maximum
+    let mut sandbox = new_sandbox(SandboxConfiguration::DEFAULT_INPUT_SIZE * 2);
+    assert!(echo(&mut sandbox).is_err());
+}
+
+#[test]
+fn oversized_values_are_sent_in_segments() {
+    let new_sandbox = |max_segmented_data_size: usize, max_input_data_size: usize| {
+        let sandbox: MultiUseSandbox =
+            new_uninit_sandbox(simple_guest_as_string().unwrap(), |cfg| {
+                cfg.set_heap_size(0x1000000);
+                cfg.set_max_segmented_data_size(max_segmented_data_size);
+                cfg.set_max_input_data_size(max_input_data_size);
+            })
+            .unwrap()
+            .evolve(Noop::default())
+            .unwrap();
+        sandbox
+    };
+    let mut sandbox = new_sandbox(SandboxConfiguration::DEFAULT_INPUT_SIZE * 32, 0);
+
+    let bytes = vec![0xab; SandboxConfiguration::DEFAULT_INPUT_SIZE * 16 + 1];
+    for _ in 0..2 {
+        let res = sandbox
+            .call_guest_function_by_name(
+                "SetByteArrayToZero",
+                ReturnType::VecBytes,
+                Some(vec![ParameterValue::VecBytes(bytes.clone())]),
+            )
+            .unwrap();
+        assert_eq!(res, ReturnValue::VecBytes(vec![0; bytes.len()]));
+    }
+
+    let message = "a".repeat(SandboxConfiguration::DEFAULT_OUTPUT_SIZE * 16);
+    let res = sandbox
+        .call_guest_function_by_name(
+            "Echo",
+            ReturnType::String,
+            Some(vec![ParameterValue::String(message.clone())]),
+        )
+        .unwrap();
+    assert_eq!(res, ReturnValue::String(message));
+
+    // values larger than the maximum aren't sent in either direction
+    let mut sandbox = new_sandbox(SandboxConfiguration::DEFAULT_INPUT_SIZE * 8, 0);
+    let res = sandbox.call_guest_function_by_name(
+        "SetByteArrayToZero",
+        ReturnType::VecBytes,
+        Some(vec![ParameterValue::VecBytes(bytes)]),
+    );
+    assert!(res.is_err());
+    // the message fits in the grown input buffer, but the result has to be
+    // sent in segments
+    let mut sandbox = new_sandbox(
+        SandboxConfiguration::DEFAULT_INPUT_SIZE * 8,
+        SandboxConfiguration::DEFAULT_INPUT_SIZE * 32,
+    );
+    let res = sandbox.call_guest_function_by_name(
+        "Echo",
+        ReturnType::String,
+        Some(vec![ParameterValue::String(
+            "a".repeat(SandboxConfiguration::DEFAULT_OUTPUT_SIZE * 16),
+        )]),
+    );
+    assert!(res.is_err());
+}
+
+#[test]
+fn large_byte_arrays_are_compressed() {
+    let mut sandbox = new_sandbox(simple_guest_as_string().unwrap(), |cfg| {
+        cfg.set_compression_threshold(0x1000);
+    })
+    .unwrap();
+
+    for len in [0x800, SandboxConfiguration::DEFAULT_INPUT_SIZE * 4] {
+        let res = sandbox
+            .call_guest_function_by_name(
+                "SetByteArrayToZero",
+                ReturnType::VecBytes,
+                Some(vec![ParameterValue::VecBytes(vec![0xab; len])]),
+            )
+            .unwrap();
+        assert_eq!(res, ReturnValue::VecBytes(vec![0; len]));
+    }
+}
+
+#[test]
+fn shared_buffer_integrity() {
+    let mut sandbox = new_sandbox(simple_guest_as_string().unwrap(), |cfg| {
+        cfg.set_heap_size(0x1000000);
+        cfg.set_shared_buffer_integrity(true);
+        cfg.set_max_segmented_data_size(SandboxConfiguration::DEFAULT_OUTPUT_SIZE * 8);
+    })
+    .unwrap();
+
+    let res = sandbox
+        .call_guest_function_by_name(
+            "PrintOutput",
+            ReturnType::Int,
+            Some(vec![ParameterValue::String("tagged\n".to_string())]),
+        )
+        .unwrap();
+    assert_eq!(res, ReturnValue::Int(7));
+
+    // large enough to be sent in segments, each of which is tagged
+    let message = "a".repeat(SandboxConfiguration::DEFAULT_OUTPUT_SIZE * 4);
+    let res = sandbox
+        .call_guest_function_by_name(
+            "Echo",
+            ReturnType::String,
+            Some(vec![ParameterValue::String(message.clone())]),
+        )
+        .unwrap();
+    assert_eq!(res, ReturnValue::String(message));
+}
+
+#[test]
+fn guest_env() {
+    let mut uninit = new_uninit_sandbox(simple_guest_as_string().unwrap(), |cfg| {
+        cfg.set_shared_buffer_integrity(true);
+    })
+    .unwrap();
+
+    let too_big = HashMap::from([(
+        "TOO_BIG".to_string(),
+        "a".repeat(SandboxConfiguration::DEFAULT_HOST_FUNCTION_DEFINITION_SIZE),
+    )]);
+    assert!(uninit.set_guest_env(too_big).is_err());
+    uninit
+        .set_guest_env(HashMap::from([
+            ("GREETING".to_string(), "hello".to_string()),
+            ("EMPTY".to_string(), String::new()),
+        ]))
+        .unwrap();
+    let mut sandbox: MultiUseSandbox = uninit.evolve(Noop::default()).unwrap();
+
+    for (name, value) in [("GREETING", "hello"), ("EMPTY", "")] {
+        let res = sandbox.call_guest_function_by_name(
+            "GetEnv",
+            ReturnType::String,
+            Some(vec![ParameterValue::String(name.to_string())]),
+        );
+        assert_eq!(res.unwrap(), ReturnValue::String(value.to_string()));
+    }
+    let res = sandbox.call_guest_function_by_name(
+        "GetEnv",
+        ReturnType::String,
+        Some(vec![ParameterValue::String("MISSING".to_string())]),
+    );
+    assert!(res.is_err());
+
+    // host functions still work with the environment taking up part of
+    // their buffer, and integrity checks still pass
+    let res = sandbox
+        .call_guest_function_by_name(
+            "PrintOutput",
+            ReturnType::Int,
+            Some(vec![ParameterValue::String("env\n".to_string())]),
+        )
+        .unwrap();
+    assert_eq!(res, ReturnValue::Int(4));
+}
+
+#[test]
+fn startup_args() {
+    let mut uninit = new_uninit_sandbox(simple_guest_as_string().unwrap(), |_| {}).unwrap();
+    uninit
+        .set_guest_env(HashMap::from([(
+            "GREETING".to_string(),
+            "hello".to_string(),
+        )]))
+        .unwrap();
+    uninit
+        .set_startup_args(vec![
+            ParameterValue::String("first".to_string()),
+            ParameterValue::Int(2),
+        ])
+        .unwrap();
+    let mut sandbox: MultiUseSandbox = uninit.evolve(Noop::default()).unwrap();
+
+    let res = sandbox.call_guest_function_by_name(
+        "GetStartupArg",
+        ReturnType::String,
+        Some(vec![ParameterValue::Int(0)]),
+    );
+    assert_eq!(res.unwrap(), ReturnValue::String("first".to_string()));
+    let res = sandbox.call_guest_function_by_name(
+        "GetStartupArg",
+        ReturnType::String,
+        Some(vec![ParameterValue::Int(1)]),
+    );
+    assert!(res.is_err());
+
+    // the environment set before the arguments is still there
+    let res = sandbox.call_guest_function_by_name(
+        "GetEnv",
+        ReturnType::String,
+        Some(vec![ParameterValue::String("GREETING".to_string())]),
+    );
+    assert_eq!(res.unwrap(), ReturnValue::String("hello".to_string()));
+}
+
+#[test]
+fn guest_initialization_failure() {
+    let mut uninit = new_uninit_sandbox(simple_guest_as_string().unwrap(), |_| {}).unwrap();
+    uninit
+        .set_startup_args(vec![
+            ParameterValue::String("FailInitialization".to_string()),
+            ParameterValue::String("missing configuration".to_string()),
+        ])
+        .unwrap();
+    let res: Result<MultiUseSandbox> = uninit.evolve(Noop::default());
+    match res {
+        Err(HyperlightError::GuestInitializationFailed { code, message }) => {
+            assert_eq!(code, ErrorCode::GuestError);
+            assert_eq!(message, "missing configuration");
+        }
+        other => panic!("Expected GuestInitializationFailed but got {:?}", other),
+    }
+}
+
+#[test]
+fn freed_heap_pages_are_released() {
+    let mut sandbox: MultiUseSandbox = new_uninit_rust().unwrap().evolve(Noop::default()).unwrap();
+
+    // the pages of a large block are given back to the host when it is
+    // freed, so reusing them finds zeros rather than what was written
+    let res = sandbox.call_guest_function_by_name(
+        "ReuseFreedMemory",
+        ReturnType::Int,
+        Some(vec![ParameterValue::Int(256 * 1024)]),
+    );
+    #[cfg(target_os = "linux")]
+    assert_eq!(res.unwrap(), ReturnValue::Int(0));
+    // on Windows their contents are unspecified
+    #[cfg(target_os = "windows")]
+    assert!(res.is_ok());
+
+    // small blocks are kept as they are
+    let res = sandbox.call_guest_function_by_name(
+        "ReuseFreedMemory",
+        ReturnType::Int,
+        Some(vec![ParameterValue::Int(4 * 4096)]),
+    );
+    assert_eq!(res.unwrap(), ReturnValue::Int(0xAA));
+}
+
+#[test]
+fn creation_report_covers_each_stage() -> Result<()> {
+    let uninit = new_uninit_rust()?;
+    let loaded = uninit.creation_report()?.unwrap();
+    assert!(loaded.binary_load > Duration::ZERO);
+    assert!(loaded.memory_map > Duration::ZERO);
+    // the rest happens when the sandbox is evolved
+    assert_eq!(loaded.total(), loaded.binary_load + loaded.memory_map);
+
+    let sandbox: MultiUseSandbox = uninit.evolve(Noop::default())?;
+    let created = sandbox.creation_report()?.unwrap();
+    assert_eq!(created.binary_load, loaded.binary_load);
+    assert_eq!(created.memory_map, loaded.memory_map);
+    assert!(created.page_tables > Duration::ZERO);
+    assert!(created.partition > Duration::ZERO);
+    assert!(created.entrypoint > Duration::ZERO);
+    Ok(())
+}
+
+#[test]
+fn shutdown_calls_guest_hook() -> Result<()> {
+    let messages = Arc::new(Mutex::new(Vec::new()));
+    let messages_clone = messages.clone();
+    let writer = move |msg: String| {
+        let len = msg.len();
+        messages_clone
+            .try_lock()
+            .map_err(|e| new_error!("Error locking at {}:{}: {}", file!(), line!(), e))?
+            .push(msg);
+        Ok(len as i32)
+    };
+    let writer_func = Arc::new(Mutex::new(writer));
+
+    let sandbox: MultiUseSandbox = UninitializedSandbox::new(
+        GuestBinary::FilePath(simple_guest_as_string().unwrap()),
+        None,
+        None,
+        Some(&writer_func),
+    )?
+    .evolve(Noop::default())?;
+    sandbox.shutdown(Duration::from_secs(1))?;
+    assert_eq!(*messages.lock().unwrap(), vec!["flushed on shutdown"]);
+
+    // a guest that was never initialized has nothing to flush
+    let mut uninit = new_uninit_rust()?;
+    uninit.set_lazy_initialization(true);
+    let sandbox: MultiUseSandbox = uninit.evolve(Noop::default())?;
+    sandbox.shutdown(Duration::from_secs(1))?;
+
+    // nor is there anything to call in a guest without the hook
+    for uninit in get_callbackguest_uninit_sandboxes(None) {
+        let sandbox: MultiUseSandbox = uninit.evolve(Noop::default())?;
+        sandbox.shutdown(Duration::from_secs(1))?;
+    }
+    Ok(())
+}
+
+#[test]
+fn guest_sees_remaining_call_time() {
+    let mut sandbox: MultiUseSandbox = new_uninit_rust().unwrap().evolve(Noop::default()).unwrap();
+
+    // the guest is told how long it has left of the default maximum
+    // execution time of a second
+    let res = sandbox.call_guest_function_by_name("GetRemainingTime", ReturnType::ULong, None);
+    match res.unwrap() {
+        ReturnValue::ULong(nanos) => assert!(nanos > 0 && nanos <= 1_000_000_000),
+        other => panic!("Expected ULong but got {:?}", other),
+    }
+}
+
+#[test]
+fn guest_reports_progress() {
+    let mut sandbox: MultiUseSandbox = new_uninit_rust().unwrap().evolve(Noop::default()).unwrap();
+    let reports = Arc::new(Mutex::new(Vec::new()));
+    let reports_clone = reports.clone();
+    let options = CallOptions::new().with_progress_callback(move |percent, message| {
+        reports_clone
+            .lock()
+            .unwrap()
+            .push((percent, message.to_string()))
+    });
+
+    let res = sandbox.call_guest_function_with_options(
+        "ReportProgressSteps",
+        ReturnType::Int,
+        Some(vec![ParameterValue::Int(4)]),
+        options,
+    );
+    assert_eq!(res.unwrap(), ReturnValue::Int(4));
+    assert_eq!(
+        *reports.lock().unwrap(),
+        vec![
+            (25, "step 1".to_string()),
+            (50, "step 2".to_string()),
+            (75, "step 3".to_string()),
+            (100, "step 4".to_string()),
+        ]
+    );
+
+    // progress reported by calls without a callback goes nowhere
+    let res = sandbox.call_guest_function_by_name(
+        "ReportProgressSteps",
+        ReturnType::Int,
+        Some(vec![ParameterValue::Int(2)]),
+    );
+    assert_eq!(res.unwrap(), ReturnValue::Int(2));
+    assert_eq!(reports.lock().unwrap().len(), 4);
+}
+
+#[test]
+fn secrets_pass_through_guest_to_host() {
+    let host_func = Arc::new(Mutex::new(|secret: Secret| {
+        // the secret doesn't show up in errors about it
+        assert_eq!(format!("{:?}", secret), "Secret(<redacted>)");
+        Ok(secret.ct_eq(b"hunter2") as i32)
+    }));
+    let mut sandbox = new_uninit_rust().unwrap();
+    host_func.register(&mut sandbox, "HostCheckSecret").unwrap();
+    let mut sandbox: MultiUseSandbox = sandbox.evolve(Noop::default()).unwrap();
+
+    for (secret, expected) in [(&b"hunter2"[..], 1), (&b"hunter3"[..], 0)] {
+        let res = sandbox.call_guest_function_by_name(
+            "ForwardSecret",
+            ReturnType::Int,
+            Some(vec![ParameterValue::Secret(Secret::new(secret.to_vec()))]),
+        );
+        assert_eq!(res.unwrap(), ReturnValue::Int(expected));
+    }
+
+    // nor in errors about passing one where it isn't expected
+    let err = sandbox
+        .call_guest_function_by_name(
+            "SetByteArrayToZero",
+            ReturnType::VecBytes,
+            Some(vec![ParameterValue::Secret(Secret::new(
+                b"hunter2".to_vec(),
+            ))]),
+        )
+        .unwrap_err();
+    assert!(!format!("{:?}", err).contains("hunter2"));
+}
+
+#[test]
+fn numa_node_and_cpu_affinity() {
+    let res = new_uninit_sandbox(simple_guest_as_string().unwrap(), |cfg| {
+        cfg.set_numa_node(0);
+        cfg.set_cpu_affinity(&[0]).unwrap();
+    });
+
+    #[cfg(target_os = "linux")]
+    {
+        let mut sandbox: MultiUseSandbox = res.unwrap().evolve(Noop::default()).unwrap();
+        let res = sandbox.call_guest_function_by_name(
+            "Echo",
+            ReturnType::String,
+            Some(vec![ParameterValue::String("pinned".to_string())]),
+        );
+        assert_eq!(res.unwrap(), ReturnValue::String("pinned".to_string()));
+    }
+    #[cfg(not(target_os = "linux"))]
+    assert!(res.is_err());
+}
+
+#[test]
+fn lazy_initialization() {
+    let new_lazy_uninit = || {
+        let mut uninit = new_uninit_sandbox(simple_guest_as_string().unwrap(), |_| {}).unwrap();
+        uninit.set_lazy_initialization(true);
+        uninit
+    };
+
+    let mut sandbox: MultiUseSandbox = new_lazy_uninit().evolve(Noop::default()).unwrap();
+    assert!(!sandbox.is_initialized());
+    assert!(sandbox.guest_function_names().is_empty());
+    let res = sandbox.call_guest_function_by_name(
+        "Echo",
+        ReturnType::String,
+        Some(vec![ParameterValue::String("lazy".to_string())]),
+    );
+    assert_eq!(res.unwrap(), ReturnValue::String("lazy".to_string()));
+    assert!(sandbox.is_initialized());
+    assert!(!sandbox.guest_function_names().is_empty());
+
+    // a sandbox that was never used has nothing to devolve to
+    let sandbox: MultiUseSandbox = new_lazy_uninit().evolve(Noop::default()).unwrap();
+    let mut sandbox = sandbox.devolve(Noop::default()).unwrap();
+    assert!(!sandbox.is_initialized());
+    let res = sandbox.call_guest_function_by_name(
+        "Echo",
+        ReturnType::String,
+        Some(vec![ParameterValue::String("devolved".to_string())]),
+    );
+    assert_eq!(res.unwrap(), ReturnValue::String("devolved".to_string()));
+
+    // initialization errors are returned by the first call instead of evolve
+    let mut uninit = new_lazy_uninit();
+    uninit
+        .set_startup_args(vec![
+            ParameterValue::String("FailInitialization".to_string()),
+            ParameterValue::String("missing configuration".to_string()),
+        ])
+        .unwrap();
+    let mut sandbox: MultiUseSandbox = uninit.evolve(Noop::default()).unwrap();
+    let res = sandbox.ensure_initialized();
+    assert!(matches!(
+        res,
+        Err(HyperlightError::GuestInitializationFailed { .. })
+    ));
+}
+
 #[test]
 #[cfg_attr(target_os = "windows", serial)] // using LoadLibrary requires serial tests
 fn iostack_is_working() {
@@ -629,3 +1145,383 @@ fn host_function_error() -> Result<()> {
     }
     Ok(())
 }
+
+#[test]
+fn linked_sandbox_answers_guest_calls() -> Result<()> {
+    let printed = Arc::new(Mutex::new(Vec::new()));
+    let printed_clone = printed.clone();
+    let writer = Arc::new(Mutex::new(move |msg: String| {
+        let len = msg.len();
+        printed_clone
+            .try_lock()
+            .map_err(|e| new_error!("Error locking at {}:{}: {}", file!(), line!(), e))?
+            .push(msg);
+        Ok(len as i32)
+    }));
+    let callback_guest = || GuestBinary::FilePath(callback_guest_as_string().unwrap());
+    let other: MultiUseSandbox =
+        UninitializedSandbox::new(callback_guest(), None, None, Some(&writer))?
+            .evolve(Noop::default())?;
+
+    let mut sandbox = UninitializedSandbox::new(callback_guest(), None, None, None)?;
+    sandbox.link(Arc::new(Mutex::new(other)), &["PrintOutput"])?;
+    let mut sandbox: MultiUseSandbox = sandbox.evolve(Noop::default())?;
+
+    // the guest's call to the host function `PrintOutput` is answered by
+    // the guest function of the same name in `other`
+    let res = sandbox.call_guest_function_by_name(
+        "CallHostMethod",
+        ReturnType::Int,
+        Some(vec![
+            ParameterValue::String("PrintOutput".to_string()),
+            ParameterValue::String("linked\n".to_string()),
+        ]),
+    )?;
+    assert_eq!(res, ReturnValue::Int(7));
+    let printed = printed
+        .try_lock()
+        .map_err(|e| new_error!("Error locking at {}:{}: {}", file!(), line!(), e))?;
+    assert_eq!(*printed, vec!["linked\n".to_string()]);
+    Ok(())
+}
+
+fn new_chatty_sandbox(
+    writer: Option<&dyn HostFunction1<String, i32>>,
+) -> Result<UninitializedSandbox> {
+    UninitializedSandbox::new(
+        GuestBinary::FilePath(chatty_guest_as_string().unwrap()),
+        None,
+        None,
+        writer,
+    )
+}
+
+#[test]
+fn chatty_guest_print_storm_is_rate_limited() -> Result<()> {
+    let messages = Arc::new(Mutex::new(Vec::new()));
+    let messages_clone = messages.clone();
+    let writer = move |msg: String| {
+        let len = msg.len();
+        messages_clone
+            .try_lock()
+            .map_err(|e| new_error!("Error locking at {}:{}: {}", file!(), line!(), e))?
+            .push(msg);
+        Ok(len as i32)
+    };
+    let writer_func = Arc::new(Mutex::new(writer));
+
+    let mut sandbox = new_chatty_sandbox(Some(&writer_func))?;
+    sandbox.set_host_print_options(
+        HostPrintOptions::new("chatty").with_rate_limit(10, Duration::from_secs(3600)),
+    )?;
+    let mut sandbox: MultiUseSandbox = sandbox.evolve(Noop::default())?;
+
+    sandbox.call_guest_function_by_name(
+        "PrintMany",
+        ReturnType::Int,
+        Some(vec![
+            ParameterValue::String("hello".to_string()),
+            ParameterValue::Int(1000),
+        ]),
+    )?;
+
+    let messages = messages
+        .try_lock()
+        .map_err(|e| new_error!("Error locking at {}:{}: {}", file!(), line!(), e))?;
+    assert_eq!(messages.len(), 10);
+    assert!(messages.iter().all(|msg| msg == "hello"));
+    Ok(())
+}
+
+#[test]
+fn chatty_guest_log_storm() -> Result<()> {
+    let mut sandbox: MultiUseSandbox = new_chatty_sandbox(None)?.evolve(Noop::default())?;
+
+    let res = sandbox.call_guest_function_by_name(
+        "LogMany",
+        ReturnType::Int,
+        Some(vec![ParameterValue::Int(1000), ParameterValue::Int(2)]),
+    )?;
+    assert_eq!(res, ReturnValue::Int(1000));
+    Ok(())
+}
+
+#[test]
+fn chatty_guest_many_host_calls() -> Result<()> {
+    let calls = Arc::new(Mutex::new(0));
+    let calls_clone = calls.clone();
+    let host_func = Arc::new(Mutex::new(move |i: i32| {
+        *calls_clone
+            .try_lock()
+            .map_err(|e| new_error!("Error locking at {}:{}: {}", file!(), line!(), e))? += 1;
+        Ok(i * 2)
+    }));
+
+    let mut sandbox = new_chatty_sandbox(None)?;
+    host_func.register(&mut sandbox, "HostDouble")?;
+    let mut sandbox: MultiUseSandbox = sandbox.evolve(Noop::default())?;
+
+    let res = sandbox.call_guest_function_by_name(
+        "CallHostMany",
+        ReturnType::Int,
+        Some(vec![
+            ParameterValue::String("HostDouble".to_string()),
+            ParameterValue::Int(1000),
+        ]),
+    )?;
+    assert_eq!(res, ReturnValue::Int((0..1000).map(|i| i * 2).sum()));
+    assert_eq!(
+        *calls.try_lock().map_err(|e| new_error!(
+            "Error locking at {}:{}: {}",
+            file!(),
+            line!(),
+            e
+        ))?,
+        1000
+    );
+    Ok(())
+}
+
+#[test]
+fn chatty_guest_interleaved_host_calls() -> Result<()> {
+    let host_func = Arc::new(Mutex::new(|depth: i32| Ok(depth)));
+
+    let mut sandbox = new_chatty_sandbox(None)?;
+    host_func.register(&mut sandbox, "HostPing")?;
+    let mut sandbox: MultiUseSandbox = sandbox.evolve(Noop::default())?;
+
+    let res = sandbox.call_guest_function_by_name(
+        "PingPong",
+        ReturnType::Int,
+        Some(vec![ParameterValue::Int(50)]),
+    )?;
+    assert_eq!(res, ReturnValue::Int((1..=50).sum()));
+    Ok(())
+}
+
+#[test]
+fn host_functions_read_call_context() -> Result<()> {
+    // adds the offset the call was given, if any
+    let host_func = Arc::new(Mutex::new(|i: i32| {
+        Ok(i + call_context::<i32>().map_or(0, |offset| *offset))
+    }));
+
+    let mut sandbox = new_chatty_sandbox(None)?;
+    host_func.register(&mut sandbox, "HostOffset")?;
+    let mut sandbox: MultiUseSandbox = sandbox.evolve(Noop::default())?;
+
+    let args = || {
+        Some(vec![
+            ParameterValue::String("HostOffset".to_string()),
+            ParameterValue::Int(10),
+        ])
+    };
+    let res = sandbox.call_guest_function_with_options(
+        "CallHostMany",
+        ReturnType::Int,
+        args(),
+        CallOptions::new().with_context(100i32),
+    )?;
+    assert_eq!(res, ReturnValue::Int((0..10).sum::<i32>() + 1000));
+
+    // the context doesn't outlive the call
+    let res = sandbox.call_guest_function_by_name("CallHostMany", ReturnType::Int, args())?;
+    assert_eq!(res, ReturnValue::Int((0..10).sum()));
+    Ok(())
+}
+
+#[test]
+fn register_host_function_after_init() -> Result<()> {
+    let mut sandbox: MultiUseSandbox = new_chatty_sandbox(None)?.evolve(Noop::default())?;
+    let call = |sandbox: &mut MultiUseSandbox| {
+        sandbox.call_guest_function_by_name(
+            "CallHostMany",
+            ReturnType::Int,
+            Some(vec![
+                ParameterValue::String("HostDouble".to_string()),
+                ParameterValue::Int(10),
+            ]),
+        )
+    };
+    assert!(call(&mut sandbox).is_err());
+
+    sandbox.register_host_function(
+        "HostDouble",
+        vec![ParameterType::Int],
+        ReturnType::Int,
+        |args| match args[..] {
+            [ParameterValue::Int(i)] => Ok(ReturnValue::Int(i * 2)),
+            _ => Err(new_error!("Unexpected arguments {:?}", args)),
+        },
+    )?;
+    // the function outlives the restore after each call
+    for _ in 0..2 {
+        assert_eq!(
+            call(&mut sandbox)?,
+            ReturnValue::Int((0..10).map(|i| i * 2).sum())
+        );
+    }
+
+    sandbox.unregister_host_function("HostDouble")?;
+    assert!(call(&mut sandbox).is_err());
+    assert!(matches!(
+        sandbox.unregister_host_function("HostDouble"),
+        Err(HyperlightError::HostFunctionNotFound(_))
+    ));
+    Ok(())
+}
+
+#[test]
+#[cfg(feature = "async_host_functions")]
+fn chatty_guest_async_host_calls() -> Result<()> {
+    use hyperlight_host::func::AsyncHostFunction1;
+
+    let runtime = tokio::runtime::Builder::new_multi_thread()
+        .worker_threads(2)
+        .enable_time()
+        .build()
+        .unwrap();
+    let host_func = Arc::new(Mutex::new(|i: i32| async move {
+        tokio::time::sleep(Duration::from_micros(10)).await;
+        // make sure the future runs on the runtime rather than the thread
+        // handling the guest's call
+        tokio::task::yield_now().await;
+        Ok(i * 2)
+    }));
+
+    let mut sandbox = new_chatty_sandbox(None)?;
+    host_func.register_async(&mut sandbox, "HostDouble", runtime.handle())?;
+    let mut sandbox: MultiUseSandbox = sandbox.evolve(Noop::default())?;
+
+    let res = sandbox.call_guest_function_by_name(
+        "CallHostMany",
+        ReturnType::Int,
+        Some(vec![
+            ParameterValue::String("HostDouble".to_string()),
+            ParameterValue::Int(100),
+        ]),
+    )?;
+    assert_eq!(res, ReturnValue::Int((0..100).map(|i| i * 2).sum()));
+
+    // an error from the future fails the call like that of any host function
+    let failing = Arc::new(Mutex::new(|i: i32| async move {
+        Err::<i32, _>(new_error!("HostDouble failed for {}", i))
+    }));
+    let mut sandbox = new_chatty_sandbox(None)?;
+    failing.register_async(&mut sandbox, "HostDouble", runtime.handle())?;
+    let mut sandbox: MultiUseSandbox = sandbox.evolve(Noop::default())?;
+    let res = sandbox.call_guest_function_by_name(
+        "CallHostMany",
+        ReturnType::Int,
+        Some(vec![
+            ParameterValue::String("HostDouble".to_string()),
+            ParameterValue::Int(1),
+        ]),
+    );
+    assert!(res.is_err());
+    Ok(())
+}
+
+#[test]
+fn event_bus_between_sandboxes() -> Result<()> {
+    use hyperlight_host::sandbox::{BuiltinServices, BuiltinServicesPolicy, BusAccess, EventBus};
+
+    let bus = EventBus::new();
+    let join = |access: BusAccess| -> Result<MultiUseSandbox> {
+        let mut sandbox = new_uninit_rust()?;
+        let policy = BuiltinServicesPolicy::new().with_event_bus(bus.clone(), access);
+        BuiltinServices::register(&mut sandbox, policy)?;
+        sandbox.evolve(Noop::default())
+    };
+    let mut publisher = join(
+        BusAccess::new()
+            .allow_publish("orders.*")
+            .with_max_payload_size(8),
+    )?;
+    let mut subscriber = join(
+        BusAccess::new()
+            .allow_subscribe("orders.*")
+            .with_max_topics(1),
+    )?;
+    let mut deaf = join(
+        BusAccess::new()
+            .allow_subscribe("orders.*")
+            .with_max_queued(0),
+    )?;
+
+    let subscribe = |sandbox: &mut MultiUseSandbox, topic: &str| {
+        sandbox.call_guest_function_by_name(
+            "BusSubscribe",
+            ReturnType::Void,
+            Some(vec![ParameterValue::String(topic.to_string())]),
+        )
+    };
+    let publish = |sandbox: &mut MultiUseSandbox, payload: &[u8]| {
+        sandbox.call_guest_function_by_name(
+            "BusPublish",
+            ReturnType::Void,
+            Some(vec![
+                ParameterValue::String("orders.created".to_string()),
+                ParameterValue::VecBytes(payload.to_vec()),
+            ]),
+        )
+    };
+    let receive = |sandbox: &mut MultiUseSandbox| {
+        sandbox.call_guest_function_by_name(
+            "BusReceive",
+            ReturnType::VecBytes,
+            Some(vec![ParameterValue::String("orders.created".to_string())]),
+        )
+    };
+
+    subscribe(&mut subscriber, "orders.created")?;
+    assert!(subscribe(&mut subscriber, "orders.cancelled").is_err());
+    subscribe(&mut deaf, "orders.created")?;
+
+    publish(&mut publisher, b"order 1")?;
+    assert!(publish(&mut publisher, b"too large").is_err());
+    bus.publish("orders.created", b"order 2".to_vec())?;
+
+    // the subscription outlives the restore after each call
+    assert_eq!(
+        receive(&mut subscriber)?,
+        ReturnValue::VecBytes(b"order 1".to_vec())
+    );
+    assert_eq!(
+        receive(&mut subscriber)?,
+        ReturnValue::VecBytes(b"order 2".to_vec())
+    );
+    assert_eq!(receive(&mut subscriber)?, ReturnValue::VecBytes(vec![]));
+    assert_eq!(receive(&mut deaf)?, ReturnValue::VecBytes(vec![]));
+    Ok(())
+}
+
+#[test]
+fn mocked_host_functions_answer_guest_calls() -> Result<()> {
+    let mocks = MockHostFunctions::new();
+    mocks
+        .expect(
+            Expectation::call("HostAdd")
+                .with_args(vec![ParameterValue::Int(40), ParameterValue::Int(2)])
+                .returning(ReturnValue::Int(42))
+                .times(1),
+        )
+        .expect(Expectation::call("HostAdd").failing("overflow").times(1));
+
+    let mut sandbox = new_uninit_sandbox(simple_guest_as_string().unwrap(), |_| {})?;
+    mocks.register(&mut sandbox)?;
+    let mut sandbox: MultiUseSandbox = sandbox.evolve(Noop::default())?;
+    let add = |sandbox: &mut MultiUseSandbox, a: i32, b: i32| {
+        sandbox.call_guest_function_by_name(
+            "Add",
+            ReturnType::Int,
+            Some(vec![ParameterValue::Int(a), ParameterValue::Int(b)]),
+        )
+    };
+
+    assert_eq!(add(&mut sandbox, 40, 2)?, ReturnValue::Int(42));
+    assert!(add(&mut sandbox, i32::MAX, 1).is_err());
+    assert_eq!(mocks.calls("HostAdd"), 2);
+    mocks.verify();
+    Ok(())
+}
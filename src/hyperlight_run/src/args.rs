@@ -0,0 +1,177 @@
+/*
+Copyright 2024 The Hyperlight Authors.
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+    http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+//! Converts between command line arguments and the values passed to and
+//! returned from guest functions.
+
+use hyperlight_host::func::{ParameterValue, ReturnType, ReturnValue};
+
+/// Parse a command line argument into a guest function parameter.
+///
+/// The type can be given with a prefix, as in `int:5`, `uint:5`, `long:5`,
+/// `ulong:5`, `float:1.5`, `double:1.5`, `bool:true`, `string:5` or
+/// `bytes:deadbeef`, where bytes are written as hex. Without a prefix,
+/// `true` and `false` are `Bool`s, integers are `Int`s, or `Long`s if they
+/// don't fit, other numbers are `Double`s, and anything else is a
+/// `String`.
+pub(crate) fn parse_parameter(arg: &str) -> Result<ParameterValue, String> {
+    if let Some((ty, value)) = arg.split_once(':') {
+        let parsed = match ty {
+            "int" => value.parse().map(ParameterValue::Int).ok(),
+            "uint" => value.parse().map(ParameterValue::UInt).ok(),
+            "long" => value.parse().map(ParameterValue::Long).ok(),
+            "ulong" => value.parse().map(ParameterValue::ULong).ok(),
+            "float" => value.parse().map(ParameterValue::Float).ok(),
+            "double" => value.parse().map(ParameterValue::Double).ok(),
+            "bool" => value.parse().map(ParameterValue::Bool).ok(),
+            "string" => Some(ParameterValue::String(value.to_string())),
+            "bytes" => parse_hex(value).map(ParameterValue::VecBytes),
+            // the colon is part of an untyped string, such as a URL
+            _ => return Ok(infer_parameter(arg)),
+        };
+        return parsed.ok_or_else(|| format!("{:?} is not a valid {}", value, ty));
+    }
+    Ok(infer_parameter(arg))
+}
+
+fn infer_parameter(arg: &str) -> ParameterValue {
+    if let Ok(value) = arg.parse() {
+        ParameterValue::Bool(value)
+    } else if let Ok(value) = arg.parse() {
+        ParameterValue::Int(value)
+    } else if let Ok(value) = arg.parse() {
+        ParameterValue::Long(value)
+    } else if let (Ok(value), true) = (arg.parse(), looks_numeric(arg)) {
+        ParameterValue::Double(value)
+    } else {
+        ParameterValue::String(arg.to_string())
+    }
+}
+
+/// Whether `arg` starts like a number, since "inf" and "nan" parse as
+/// floats but are more likely meant as strings
+fn looks_numeric(arg: &str) -> bool {
+    arg.starts_with(|c: char| c.is_ascii_digit() || c == '-' || c == '.')
+}
+
+/// Parse the name of a return type, which is one of the prefixes accepted
+/// by `parse_parameter` or `void`
+pub(crate) fn parse_return_type(name: &str) -> Result<ReturnType, String> {
+    match name {
+        "int" => Ok(ReturnType::Int),
+        "uint" => Ok(ReturnType::UInt),
+        "long" => Ok(ReturnType::Long),
+        "ulong" => Ok(ReturnType::ULong),
+        "float" => Ok(ReturnType::Float),
+        "double" => Ok(ReturnType::Double),
+        "bool" => Ok(ReturnType::Bool),
+        "string" => Ok(ReturnType::String),
+        "bytes" => Ok(ReturnType::VecBytes),
+        "void" => Ok(ReturnType::Void),
+        _ => Err(format!(
+            "{:?} is not one of int, uint, long, ulong, float, double, bool, string, bytes or void",
+            name
+        )),
+    }
+}
+
+/// Format a value returned from the guest for printing, with bytes written
+/// as hex so they can be passed back with `bytes:`
+pub(crate) fn format_return_value(value: &ReturnValue) -> String {
+    match value {
+        ReturnValue::Int(value) => value.to_string(),
+        ReturnValue::UInt(value) => value.to_string(),
+        ReturnValue::Long(value) => value.to_string(),
+        ReturnValue::ULong(value) => value.to_string(),
+        ReturnValue::Float(value) => value.to_string(),
+        ReturnValue::Double(value) => value.to_string(),
+        ReturnValue::String(value) => value.clone(),
+        ReturnValue::Bool(value) => value.to_string(),
+        ReturnValue::Void => String::new(),
+        ReturnValue::VecBytes(value) => value.iter().map(|b| format!("{:02x}", b)).collect(),
+    }
+}
+
+fn parse_hex(hex: &str) -> Option<Vec<u8>> {
+    if hex.len() % 2 != 0 || !hex.bytes().all(|b| b.is_ascii_hexdigit()) {
+        return None;
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).ok())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn typed_parameters() {
+        assert_eq!(parse_parameter("int:-5"), Ok(ParameterValue::Int(-5)));
+        assert_eq!(parse_parameter("uint:5"), Ok(ParameterValue::UInt(5)));
+        assert_eq!(parse_parameter("long:5"), Ok(ParameterValue::Long(5)));
+        assert_eq!(parse_parameter("ulong:5"), Ok(ParameterValue::ULong(5)));
+        assert_eq!(parse_parameter("float:1.5"), Ok(ParameterValue::Float(1.5)));
+        assert_eq!(
+            parse_parameter("double:1.5"),
+            Ok(ParameterValue::Double(1.5))
+        );
+        assert_eq!(parse_parameter("bool:true"), Ok(ParameterValue::Bool(true)));
+        assert_eq!(
+            parse_parameter("string:5"),
+            Ok(ParameterValue::String("5".to_string()))
+        );
+        assert_eq!(
+            parse_parameter("bytes:00ff10"),
+            Ok(ParameterValue::VecBytes(vec![0x00, 0xff, 0x10]))
+        );
+        assert!(parse_parameter("int:five").is_err());
+        assert!(parse_parameter("uint:-5").is_err());
+        assert!(parse_parameter("bytes:abc").is_err());
+        assert!(parse_parameter("bytes:zz").is_err());
+    }
+
+    #[test]
+    fn inferred_parameters() {
+        assert_eq!(parse_parameter("false"), Ok(ParameterValue::Bool(false)));
+        assert_eq!(parse_parameter("-42"), Ok(ParameterValue::Int(-42)));
+        assert_eq!(
+            parse_parameter("4294967296"),
+            Ok(ParameterValue::Long(4294967296))
+        );
+        assert_eq!(parse_parameter("0.25"), Ok(ParameterValue::Double(0.25)));
+        for arg in ["hello", "nan", "inf", "http://example.com"] {
+            assert_eq!(
+                parse_parameter(arg),
+                Ok(ParameterValue::String(arg.to_string()))
+            );
+        }
+    }
+
+    #[test]
+    fn return_types() {
+        assert_eq!(parse_return_type("void"), Ok(ReturnType::Void));
+        assert_eq!(parse_return_type("bytes"), Ok(ReturnType::VecBytes));
+        assert!(parse_return_type("i32").is_err());
+        assert_eq!(format_return_value(&ReturnValue::Int(-1)), "-1");
+        assert_eq!(format_return_value(&ReturnValue::Void), "");
+        assert_eq!(
+            format_return_value(&ReturnValue::VecBytes(vec![0xde, 0xad])),
+            "dead"
+        );
+    }
+}
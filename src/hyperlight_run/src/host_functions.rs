@@ -0,0 +1,60 @@
+/*
+Copyright 2024 The Hyperlight Authors.
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+    http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+use std::sync::{Arc, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use hyperlight_host::func::{HostFunction0, HostFunction1};
+use hyperlight_host::{new_error, Result, UninitializedSandbox};
+use rand::rngs::StdRng;
+use rand::{RngCore, SeedableRng};
+
+/// The most bytes `GetRandomBytes` returns at once
+const MAX_RANDOM_BYTES: i32 = 0x1000;
+
+/// Register the host functions that guests run with `hyperlight-run` can
+/// call, in addition to `HostPrint`, which every sandbox has:
+///
+/// - `GetTimeSinceUnixEpochMicros() -> i64`, the wall clock time
+/// - `GetRandomBytes(len: i32) -> Vec<u8>`, up to 4096 random bytes
+pub(crate) fn register(sandbox: &mut UninitializedSandbox) -> Result<()> {
+    let time = Arc::new(Mutex::new(|| {
+        let since_epoch = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_err(|e| new_error!("The clock is before the unix epoch: {}", e))?;
+        Ok(since_epoch.as_micros() as i64)
+    }));
+    time.register(sandbox, "GetTimeSinceUnixEpochMicros")?;
+
+    // seeded here, since the host functions run on a thread that may not be
+    // allowed to ask the OS for entropy
+    let mut rng = StdRng::from_entropy();
+    let random_bytes = Arc::new(Mutex::new(move |len: i32| {
+        if !(0..=MAX_RANDOM_BYTES).contains(&len) {
+            return Err(new_error!(
+                "GetRandomBytes can return between 0 and {} bytes, not {}",
+                MAX_RANDOM_BYTES,
+                len
+            ));
+        }
+        let mut bytes = vec![0; len as usize];
+        rng.fill_bytes(&mut bytes);
+        Ok(bytes)
+    }));
+    random_bytes.register(sandbox, "GetRandomBytes")?;
+
+    Ok(())
+}
@@ -0,0 +1,136 @@
+/*
+Copyright 2024 The Hyperlight Authors.
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+    http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+//! `hyperlight-run` loads a guest binary into a sandbox, calls one of its
+//! functions and prints what it returns, so that guests can be tried out
+//! without writing a host:
+//!
+//! ```text
+//! hyperlight-run simpleguest PrintOutput "Hello, World!"
+//! hyperlight-run --returns string simpleguest Echo string:42
+//! ```
+//!
+//! With `--interactive`, it instead reads commands that call the guest's
+//! functions, and shows how long each call took and where the guest was
+//! if it crashed.
+
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+
+use clap::Parser;
+use hyperlight_host::func::{ParameterValue, ReturnType, ReturnValue};
+use hyperlight_host::mem::symbols::GuestSymbols;
+use hyperlight_host::sandbox::SandboxConfiguration;
+use hyperlight_host::sandbox_state::sandbox::EvolvableSandbox;
+use hyperlight_host::sandbox_state::transition::Noop;
+use hyperlight_host::{new_error, GuestBinary, MultiUseSandbox, Result, UninitializedSandbox};
+
+mod args;
+mod host_functions;
+mod repl;
+
+/// Call a function in a Hyperlight guest binary and print what it returns
+#[derive(Parser)]
+#[command(name = "hyperlight-run", version)]
+struct Cli {
+    /// The type the function returns: int, uint, long, ulong, float,
+    /// double, bool, string, bytes or void
+    #[arg(short, long, default_value = "int", value_parser = args::parse_return_type)]
+    returns: ReturnType,
+
+    /// A TOML file of sandbox settings, as read by
+    /// `SandboxConfiguration::from_toml`. `HYPERLIGHT_` environment
+    /// variables override the settings in the file.
+    #[arg(short, long)]
+    config: Option<PathBuf>,
+
+    /// Read commands that call the guest's functions from stdin, instead
+    /// of calling a single function
+    #[arg(short, long, conflicts_with_all = ["function", "args"])]
+    interactive: bool,
+
+    /// The guest binary to load
+    guest: PathBuf,
+
+    /// The name of the guest function to call
+    #[arg(required_unless_present = "interactive")]
+    function: Option<String>,
+
+    /// The arguments to pass to the function. The type of an argument can
+    /// be given with a prefix, as in `int:5`, `string:5` or
+    /// `bytes:deadbeef`, and is otherwise inferred from what it looks like.
+    #[arg(value_parser = args::parse_parameter, allow_hyphen_values = true)]
+    args: Vec<ParameterValue>,
+}
+
+fn main() -> ExitCode {
+    let cli = Cli::parse();
+    match run(cli) {
+        Ok(value) => {
+            if value != ReturnValue::Void {
+                println!("{}", args::format_return_value(&value));
+            }
+            ExitCode::SUCCESS
+        }
+        Err(e) => {
+            eprintln!("error: {}", e);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn run(cli: Cli) -> Result<ReturnValue> {
+    let (mut sandbox, symbols) = create_sandbox(&cli.guest, cli.config.as_deref())?;
+    let Some(function) = cli.function else {
+        repl::Repl::new(sandbox, symbols, cli.returns).run()?;
+        return Ok(ReturnValue::Void);
+    };
+    let args = match cli.args.is_empty() {
+        true => None,
+        false => Some(cli.args),
+    };
+    sandbox.call_guest_function_by_name(&function, cli.returns, args)
+}
+
+/// Load `guest` into a sandbox with the standard host functions, configured
+/// from `config` and the environment, and read its symbols. Guests without
+/// symbols get an empty table.
+fn create_sandbox(guest: &Path, config: Option<&Path>) -> Result<(MultiUseSandbox, GuestSymbols)> {
+    let mut cfg = match config {
+        Some(path) => {
+            let toml = std::fs::read_to_string(path)
+                .map_err(|e| new_error!("Failed to read {}: {}", path.display(), e))?;
+            SandboxConfiguration::from_toml(&toml)?
+        }
+        None => SandboxConfiguration::default(),
+    };
+    cfg.apply_env_overrides()?;
+
+    let guest = guest
+        .to_str()
+        .ok_or_else(|| new_error!("{} is not a valid path", guest.display()))?;
+    let binary = GuestBinary::FilePath(guest.to_string());
+    let symbols = GuestSymbols::from_binary(&binary).unwrap_or_default();
+    let mut sandbox = UninitializedSandbox::new(
+        binary,
+        Some(cfg),
+        None, // default run options
+        None, // default host print function
+    )?;
+    host_functions::register(&mut sandbox)?;
+    let symbols = symbols.with_load_address(sandbox.guest_load_address());
+    Ok((sandbox.evolve(Noop::default())?, symbols))
+}
@@ -0,0 +1,279 @@
+/*
+Copyright 2024 The Hyperlight Authors.
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+    http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+//! The interactive mode of `hyperlight-run`, which keeps one sandbox and
+//! calls its functions as they are typed in.
+
+use std::io::{self, BufRead, Write};
+use std::time::Instant;
+
+use hyperlight_host::func::{ReturnType, ReturnValue};
+use hyperlight_host::hypervisor::CrashDump;
+use hyperlight_host::mem::memory_region::MemoryRegionType;
+use hyperlight_host::mem::symbols::GuestSymbols;
+use hyperlight_host::sandbox_state::sandbox::Sandbox;
+use hyperlight_host::MultiUseSandbox;
+
+use crate::args;
+
+const HELP: &str = "\
+functions                   list the functions the guest registered
+call <function> [args...]   call a function, with arguments written as on the command line
+returns <type>              set the type returned by the functions called from now on
+memory                      show the guest's memory regions
+stats                       show the statistics the hypervisor keeps for the vCPU
+crash                       show where the guest was when it last crashed
+help                        show this message
+quit                        leave";
+
+pub(crate) struct Repl {
+    sandbox: MultiUseSandbox,
+    symbols: GuestSymbols,
+    returns: ReturnType,
+    last_crash: Option<CrashDump>,
+}
+
+impl Repl {
+    /// `symbols` are used to show where the guest crashed, and should be
+    /// relocated to where the guest was loaded
+    pub(crate) fn new(
+        mut sandbox: MultiUseSandbox,
+        symbols: GuestSymbols,
+        returns: ReturnType,
+    ) -> Self {
+        sandbox.set_call_profiling(true);
+        Self {
+            sandbox,
+            symbols,
+            returns,
+            last_crash: None,
+        }
+    }
+
+    /// Read commands from stdin until it is closed or `quit` is entered
+    pub(crate) fn run(&mut self) -> io::Result<()> {
+        println!("Type `help` for the list of commands");
+        let mut lines = io::stdin().lock().lines();
+        loop {
+            print!("> ");
+            io::stdout().flush()?;
+            let Some(line) = lines.next().transpose()? else {
+                println!();
+                return Ok(());
+            };
+            let words = match split_words(&line) {
+                Ok(words) => words,
+                Err(e) => {
+                    println!("error: {}", e);
+                    continue;
+                }
+            };
+            let Some((command, words)) = words.split_first() else {
+                continue;
+            };
+            match (command.as_str(), words) {
+                ("quit" | "exit", []) => return Ok(()),
+                ("help", []) => println!("{}", HELP),
+                ("functions", []) => self.functions(),
+                ("call", [function, args @ ..]) => self.call(function, args),
+                ("returns", [name]) => match args::parse_return_type(name) {
+                    Ok(returns) => self.returns = returns,
+                    Err(e) => println!("error: {}", e),
+                },
+                ("memory", []) => self.memory(),
+                ("stats", []) => self.stats(),
+                ("crash", []) => match &self.last_crash {
+                    Some(crash) => print!("{}", crash.symbolize(&self.symbols)),
+                    None => println!("The guest hasn't crashed"),
+                },
+                _ => println!("error: {:?} is not a command, see `help`", line.trim()),
+            }
+        }
+    }
+
+    fn functions(&self) {
+        let names = self.sandbox.guest_function_names();
+        if names.is_empty() {
+            println!("The guest didn't tell the host what its functions are");
+        }
+        for name in names {
+            println!("{}", name);
+        }
+    }
+
+    fn call(&mut self, function: &str, words: &[String]) {
+        let args = match words
+            .iter()
+            .map(|word| args::parse_parameter(word))
+            .collect::<Result<Vec<_>, _>>()
+        {
+            Ok(args) => args,
+            Err(e) => {
+                println!("error: {}", e);
+                return;
+            }
+        };
+        let args = (!args.is_empty()).then_some(args);
+
+        let start = Instant::now();
+        let result = self
+            .sandbox
+            .call_guest_function_by_name(function, self.returns, args);
+        let elapsed = start.elapsed();
+
+        match result {
+            Ok(ReturnValue::Void) => {}
+            Ok(value) => println!("{}", args::format_return_value(&value)),
+            Err(e) => {
+                println!("error: {}", e);
+                if let Some(crash) = CrashDump::from_error(&e) {
+                    println!("The guest crashed, see `crash` for where");
+                    self.last_crash = Some(crash);
+                }
+            }
+        }
+        if self.sandbox.is_poisoned() {
+            match self.sandbox.clear_poison() {
+                Ok(()) => println!("The sandbox was restored to its state before the call"),
+                Err(e) => println!("error: failed to restore the sandbox: {}", e),
+            }
+        }
+
+        match self.sandbox.last_call_profile() {
+            Some(profile) => {
+                println!(
+                    "took {:?}: {} vCPU runs taking {:?}, {} exits, {} host function calls",
+                    elapsed,
+                    profile.guest.count,
+                    profile.guest.duration,
+                    profile.exits.values().map(|exit| exit.count).sum::<u64>(),
+                    profile
+                        .host_functions
+                        .values()
+                        .map(|call| call.count)
+                        .sum::<u64>(),
+                );
+                let breakdown = profile.breakdown();
+                println!(
+                    "time: guest {:?}, host functions {:?}, serialization {:?}, other {:?}",
+                    breakdown.guest,
+                    breakdown.host_functions,
+                    breakdown.serialization,
+                    breakdown.other,
+                );
+            }
+            None => println!("took {:?}", elapsed),
+        }
+        if let Ok(regions) = self.sandbox.memory_layout() {
+            let size_of = |region_type| {
+                regions
+                    .iter()
+                    .filter(|region| region.region_type() == region_type)
+                    .map(|region| region.guest_region().len())
+                    .sum::<usize>()
+            };
+            let total: usize = regions.iter().map(|r| r.guest_region().len()).sum();
+            println!(
+                "memory: {} KiB, of which heap {} KiB, stack {} KiB, input {} KiB, output {} KiB",
+                total / 1024,
+                size_of(MemoryRegionType::Heap) / 1024,
+                size_of(MemoryRegionType::Stack) / 1024,
+                size_of(MemoryRegionType::InputData) / 1024,
+                size_of(MemoryRegionType::OutputData) / 1024,
+            );
+        }
+    }
+
+    fn memory(&self) {
+        match self.sandbox.memory_layout() {
+            Ok(regions) => {
+                for region in regions {
+                    let guest = region.guest_region();
+                    println!(
+                        "{:#012x}-{:#012x} {:>8} KiB {:?} {}",
+                        guest.start,
+                        guest.end,
+                        guest.len() / 1024,
+                        region.region_type(),
+                        region.flags()
+                    );
+                }
+            }
+            Err(e) => println!("error: {}", e),
+        }
+    }
+
+    fn stats(&self) {
+        match self.sandbox.vcpu_stats() {
+            Ok(Some(stats)) => {
+                for (name, value) in &stats.counters {
+                    println!("{} {}", name, value);
+                }
+            }
+            Ok(None) => println!("The hypervisor doesn't report vCPU statistics"),
+            Err(e) => println!("error: {}", e),
+        }
+    }
+}
+
+/// Split a line into words at whitespace, except inside double quotes,
+/// where `\"` and `\\` stand for `"` and `\`
+fn split_words(line: &str) -> Result<Vec<String>, String> {
+    let mut words = Vec::new();
+    let mut word: Option<String> = None;
+    let mut chars = line.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' => {
+                let word = word.get_or_insert_with(String::new);
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        Some('\\') => match chars.next() {
+                            Some(escaped) => word.push(escaped),
+                            None => return Err("unterminated quote".to_string()),
+                        },
+                        Some(c) => word.push(c),
+                        None => return Err("unterminated quote".to_string()),
+                    }
+                }
+            }
+            c if c.is_whitespace() => words.extend(word.take()),
+            c => word.get_or_insert_with(String::new).push(c),
+        }
+    }
+    words.extend(word);
+    Ok(words)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn words() {
+        assert_eq!(
+            split_words("  call Echo  hello ").unwrap(),
+            ["call", "Echo", "hello"]
+        );
+        assert_eq!(
+            split_words(r#"call Echo "hello, \"world\"" string:"a b" """#).unwrap(),
+            ["call", "Echo", "hello, \"world\"", "string:a b", ""]
+        );
+        assert!(split_words(r#"call Echo "hello"#).is_err());
+        assert!(split_words("").unwrap().is_empty());
+    }
+}
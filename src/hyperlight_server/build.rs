@@ -0,0 +1,52 @@
+/*
+Copyright 2024 The Hyperlight Authors.
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+    http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+use tonic_build::manual::{Builder, Method, Service};
+
+fn main() {
+    println!("cargo:rerun-if-changed=build.rs");
+
+    // The messages are declared with prost in src/proto.rs, and described
+    // for clients in proto/hyperlight_server.proto, which must be kept in
+    // step with them. Only the service is generated here.
+    let method = |name: &str, route_name: &str, input_type: &str, output_type: &str| {
+        Method::builder()
+            .name(name)
+            .route_name(route_name)
+            .input_type(input_type)
+            .output_type(output_type)
+            .codec_path("tonic::codec::ProstCodec")
+            .build()
+    };
+    let service = Service::builder()
+        .name("GuestFunctions")
+        .package("hyperlight.server")
+        .comment("Calls the functions of the guests in the server's sandbox pools")
+        .method(method(
+            "call",
+            "Call",
+            "super::CallRequest",
+            "super::CallResponse",
+        ))
+        .method(method(
+            "list_functions",
+            "ListFunctions",
+            "super::ListFunctionsRequest",
+            "super::ListFunctionsResponse",
+        ))
+        .build();
+    Builder::new().compile(&[service]);
+}
@@ -0,0 +1,39 @@
+/*
+Copyright 2024 The Hyperlight Authors.
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+    http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+//! Serves the functions of Hyperlight guests over gRPC, so that Hyperlight
+//! can be deployed as a standalone sandboxing service rather than embedded
+//! in each application that needs it.
+//!
+//! Each guest is loaded into a `SandboxPool`, and a `GuestService` serves
+//! the `hyperlight.server.GuestFunctions` service for a set of named
+//! pools. Requests name the pool and function to call, and carry the
+//! arguments as `Value`s, which become the `ParameterValue`s the guest
+//! function is called with. See `proto/hyperlight_server.proto` for the
+//! service definition to generate clients from.
+#![deny(missing_docs)]
+
+/// The messages of the service, and its generated server and client
+pub mod proto;
+
+/// Pools of sandboxes running the same guest
+pub mod pool;
+
+/// The implementation of the service
+pub mod service;
+
+pub use pool::{PooledSandbox, SandboxPool};
+pub use service::GuestService;
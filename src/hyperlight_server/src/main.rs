@@ -0,0 +1,95 @@
+/*
+Copyright 2024 The Hyperlight Authors.
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+    http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+//! `hyperlight-server` serves the functions of Hyperlight guests over gRPC:
+//!
+//! ```text
+//! hyperlight-server --listen 127.0.0.1:50051 --pool simple=simpleguest --pool-size 8
+//! ```
+
+use std::error::Error;
+use std::net::SocketAddr;
+use std::path::PathBuf;
+
+use clap::Parser;
+use hyperlight_host::sandbox::SandboxConfiguration;
+use hyperlight_server::{GuestService, SandboxPool};
+use tonic::transport::Server;
+
+/// Serve the functions of Hyperlight guests over gRPC
+#[derive(Parser)]
+#[command(name = "hyperlight-server", version)]
+struct Cli {
+    /// The address to listen on
+    #[arg(short, long, default_value = "127.0.0.1:50051")]
+    listen: SocketAddr,
+
+    /// A pool of sandboxes to serve, as `name=guest`, where requests for
+    /// the pool `name` call functions in the guest binary `guest`. This can
+    /// be given more than once.
+    #[arg(short, long = "pool", required = true, value_parser = parse_pool)]
+    pools: Vec<(String, String)>,
+
+    /// The number of sandboxes in each pool, which is the number of calls
+    /// to the pool that can run at once
+    #[arg(long, default_value_t = 4)]
+    pool_size: usize,
+
+    /// A TOML file of sandbox settings, as read by
+    /// `SandboxConfiguration::from_toml`. `HYPERLIGHT_` environment
+    /// variables override the settings in the file.
+    #[arg(short, long)]
+    config: Option<PathBuf>,
+}
+
+fn parse_pool(pool: &str) -> Result<(String, String), String> {
+    match pool.split_once('=') {
+        Some((name, guest)) if !name.is_empty() && !guest.is_empty() => {
+            Ok((name.to_string(), guest.to_string()))
+        }
+        _ => Err(format!("{:?} is not of the form name=guest", pool)),
+    }
+}
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn Error>> {
+    let cli = Cli::parse();
+
+    let mut config = match &cli.config {
+        Some(path) => SandboxConfiguration::from_toml(&std::fs::read_to_string(path)?)?,
+        None => SandboxConfiguration::default(),
+    };
+    config.apply_env_overrides()?;
+
+    let mut service = GuestService::new();
+    for (name, guest) in cli.pools {
+        let pool = SandboxPool::new(&guest, cli.pool_size, config)?;
+        println!(
+            "Serving {} from {} with {} functions",
+            name,
+            guest,
+            pool.function_names().len()
+        );
+        service.add_pool(name, pool);
+    }
+
+    println!("Listening on {}", cli.listen);
+    Server::builder()
+        .add_service(service.into_server())
+        .serve(cli.listen)
+        .await?;
+    Ok(())
+}
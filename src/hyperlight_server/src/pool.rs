@@ -0,0 +1,218 @@
+/*
+Copyright 2024 The Hyperlight Authors.
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+    http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+
+use hyperlight_host::func::guest_caller::timed;
+use hyperlight_host::func::{CallStats, GuestCaller, ParameterValue, ReturnType, ReturnValue};
+use hyperlight_host::sandbox::SandboxConfiguration;
+use hyperlight_host::sandbox_state::sandbox::EvolvableSandbox;
+use hyperlight_host::sandbox_state::transition::Noop;
+use hyperlight_host::{new_error, GuestBinary, MultiUseSandbox, Result, UninitializedSandbox};
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+
+/// A fixed number of sandboxes running the same guest, which calls are
+/// spread across. A call waits until one of the sandboxes is free.
+///
+/// A sandbox's state is restored after every call, including ones that
+/// fail, so each call starts from the state the guest was in once it was
+/// initialized, whichever sandbox it runs in.
+///
+/// A sandbox that can't be restored after a failed call is dropped from
+/// the pool. Once there are none left, calls
+/// fail rather than wait.
+pub struct SandboxPool {
+    sandboxes: Arc<Mutex<Vec<MultiUseSandbox>>>,
+    /// Has a permit for each sandbox in `sandboxes`, and is closed once
+    /// none are left
+    available: Arc<Semaphore>,
+    /// The number of sandboxes in the pool, whether free or checked out
+    remaining: Arc<AtomicUsize>,
+    function_names: Vec<String>,
+}
+
+impl SandboxPool {
+    /// Create `size` sandboxes running the guest binary at `guest_path`,
+    /// each configured with `config`
+    pub fn new(guest_path: &str, size: usize, config: SandboxConfiguration) -> Result<Self> {
+        if size == 0 {
+            return Err(new_error!("A sandbox pool needs at least one sandbox"));
+        }
+        let sandboxes = (0..size)
+            .map(|_| {
+                let sandbox = UninitializedSandbox::new(
+                    GuestBinary::FilePath(guest_path.to_string()),
+                    Some(config),
+                    None, // default run options
+                    None, // default host print function
+                )?;
+                sandbox.evolve(Noop::default())
+            })
+            .collect::<Result<Vec<MultiUseSandbox>>>()?;
+        let function_names = sandboxes[0].guest_function_names();
+        Ok(Self {
+            sandboxes: Arc::new(Mutex::new(sandboxes)),
+            available: Arc::new(Semaphore::new(size)),
+            remaining: Arc::new(AtomicUsize::new(size)),
+            function_names,
+        })
+    }
+
+    /// The names of the functions the guest registered, which are empty if
+    /// the guest doesn't tell the host what its functions are
+    pub fn function_names(&self) -> &[String] {
+        &self.function_names
+    }
+
+    /// Take the next free sandbox out of the pool, waiting for one if need
+    /// be. The sandbox goes back into the pool when the returned guard is
+    /// dropped. Fails if there are no sandboxes left in the pool.
+    pub async fn checkout(&self) -> Result<PooledSandbox> {
+        let permit = self
+            .available
+            .clone()
+            .acquire_owned()
+            .await
+            .map_err(|_| new_error!("There are no sandboxes left in the pool"))?;
+        let sandbox = self
+            .sandboxes
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .pop()
+            .ok_or_else(|| new_error!("There is no free sandbox in the pool"))?;
+        Ok(PooledSandbox {
+            sandbox: Some(sandbox),
+            sandboxes: self.sandboxes.clone(),
+            available: self.available.clone(),
+            remaining: self.remaining.clone(),
+            permit: Some(permit),
+            stats: CallStats::default(),
+            failed: false,
+        })
+    }
+
+    /// Call `function` in the next free sandbox. The call is made on a
+    /// thread for blocking work, so that it doesn't hold up the runtime,
+    /// and runs to the end even if the returned future is dropped.
+    pub async fn call(
+        &self,
+        function: String,
+        return_type: ReturnType,
+        args: Option<Vec<ParameterValue>>,
+    ) -> Result<ReturnValue> {
+        let mut sandbox = self.checkout().await?;
+        tokio::task::spawn_blocking(move || sandbox.call(&function, return_type, args))
+            .await
+            .map_err(|e| new_error!("The guest function call panicked: {}", e))?
+    }
+}
+
+/// A sandbox taken out of a `SandboxPool` with `SandboxPool::checkout`.
+///
+/// Calls made through the guard block, so in async code they belong on a
+/// thread for blocking work. When the guard is dropped, the sandbox goes
+/// back into the pool, restored first if a call made through the guard
+/// failed. If it can't be restored, the pool carries on with one fewer.
+pub struct PooledSandbox {
+    sandbox: Option<MultiUseSandbox>,
+    sandboxes: Arc<Mutex<Vec<MultiUseSandbox>>>,
+    available: Arc<Semaphore>,
+    remaining: Arc<AtomicUsize>,
+    permit: Option<OwnedSemaphorePermit>,
+    /// The calls made through this guard, rather than all the ones the
+    /// sandbox has had
+    stats: CallStats,
+    /// Whether a call made through this guard failed. Failed calls aren't
+    /// restored, so the sandbox is restored before it goes back
+    failed: bool,
+}
+
+impl GuestCaller for PooledSandbox {
+    fn call(
+        &mut self,
+        func_name: &str,
+        func_ret_type: ReturnType,
+        args: Option<Vec<ParameterValue>>,
+    ) -> Result<ReturnValue> {
+        // only `drop` takes the sandbox out
+        let sandbox = self.sandbox.as_mut().unwrap();
+        let res = timed(&mut self.stats, || {
+            sandbox.call_guest_function_by_name(func_name, func_ret_type, args)
+        });
+        self.failed |= res.is_err();
+        res
+    }
+
+    fn stats(&self) -> CallStats {
+        self.stats
+    }
+}
+
+impl Drop for PooledSandbox {
+    fn drop(&mut self) {
+        let (Some(mut sandbox), Some(permit)) = (self.sandbox.take(), self.permit.take()) else {
+            return;
+        };
+        if (self.failed || sandbox.is_poisoned()) && sandbox.clear_poison().is_err() {
+            // the sandbox can't be used again, so the pool carries on
+            // with one fewer, and calls waiting for a sandbox fail once
+            // there are none left
+            permit.forget();
+            if self.remaining.fetch_sub(1, Ordering::SeqCst) == 1 {
+                self.available.close();
+            }
+            return;
+        }
+        self.sandboxes
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .push(sandbox);
+        drop(permit);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use hyperlight_testing::simple_guest_as_string;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn failed_calls_are_restored() {
+        let pool = SandboxPool::new(
+            &simple_guest_as_string().unwrap(),
+            1,
+            SandboxConfiguration::default(),
+        )
+        .unwrap();
+
+        let res = pool
+            .call(
+                "AddToStaticAndFail".to_string(),
+                ReturnType::Int,
+                Some(vec![ParameterValue::Int(5)]),
+            )
+            .await;
+        assert!(res.is_err());
+
+        let res = pool
+            .call("GetStatic".to_string(), ReturnType::Int, None)
+            .await
+            .unwrap();
+        assert_eq!(res, ReturnValue::Int(0));
+    }
+}
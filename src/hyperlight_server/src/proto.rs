@@ -0,0 +1,245 @@
+/*
+Copyright 2024 The Hyperlight Authors.
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+    http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+use hyperlight_host::func::{ParameterValue, ReturnValue};
+use tonic::Status;
+
+// the server and client, generated by build.rs
+include!(concat!(
+    env!("OUT_DIR"),
+    "/hyperlight.server.GuestFunctions.rs"
+));
+
+/// Stands in for the value of a function that returns nothing
+#[derive(Clone, Copy, PartialEq, prost::Message)]
+pub struct Void {}
+
+/// A value passed to or returned from a guest function
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct Value {
+    /// The value, which is only missing if the message is malformed
+    #[prost(oneof = "value::Kind", tags = "1, 2, 3, 4, 5, 6, 7, 8, 9, 10")]
+    pub kind: Option<value::Kind>,
+}
+
+/// The kinds of `Value`
+pub mod value {
+    /// A value of each of the types guest functions can take and return
+    #[derive(Clone, PartialEq, prost::Oneof)]
+    pub enum Kind {
+        /// i32
+        #[prost(int32, tag = "1")]
+        Int(i32),
+        /// u32
+        #[prost(uint32, tag = "2")]
+        UInt(u32),
+        /// i64
+        #[prost(int64, tag = "3")]
+        Long(i64),
+        /// u64
+        #[prost(uint64, tag = "4")]
+        ULong(u64),
+        /// f32
+        #[prost(float, tag = "5")]
+        Float(f32),
+        /// f64
+        #[prost(double, tag = "6")]
+        Double(f64),
+        /// String
+        #[prost(string, tag = "7")]
+        String(String),
+        /// bool
+        #[prost(bool, tag = "8")]
+        Bool(bool),
+        /// Vec<u8>
+        #[prost(bytes = "vec", tag = "9")]
+        Bytes(Vec<u8>),
+        /// Only returned, by functions that return nothing
+        #[prost(message, tag = "10")]
+        Void(super::Void),
+    }
+}
+
+/// The type a guest function returns
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, prost::Enumeration)]
+#[repr(i32)]
+pub enum ReturnType {
+    /// i32
+    Int = 0,
+    /// u32
+    UInt = 1,
+    /// i64
+    Long = 2,
+    /// u64
+    ULong = 3,
+    /// f32
+    Float = 4,
+    /// f64
+    Double = 5,
+    /// String
+    String = 6,
+    /// bool
+    Bool = 7,
+    /// Vec<u8>
+    Bytes = 8,
+    /// ()
+    Void = 9,
+}
+
+/// Call a function in one of the sandboxes of a pool
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct CallRequest {
+    /// The name the pool was given when the server was started
+    #[prost(string, tag = "1")]
+    pub pool: String,
+    /// The name of the guest function
+    #[prost(string, tag = "2")]
+    pub function: String,
+    /// The arguments to pass to the function
+    #[prost(message, repeated, tag = "3")]
+    pub args: Vec<Value>,
+    /// The type the function returns
+    #[prost(enumeration = "ReturnType", tag = "4")]
+    pub return_type: i32,
+}
+
+/// What a guest function returned
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct CallResponse {
+    /// The value the function returned
+    #[prost(message, optional, tag = "1")]
+    pub value: Option<Value>,
+}
+
+/// List the functions the guest of a pool registered
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct ListFunctionsRequest {
+    /// The name the pool was given when the server was started
+    #[prost(string, tag = "1")]
+    pub pool: String,
+}
+
+/// The functions the guest of a pool registered
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct ListFunctionsResponse {
+    /// The names of the functions, which are empty if the guest doesn't
+    /// tell the host what its functions are
+    #[prost(string, repeated, tag = "1")]
+    pub functions: Vec<String>,
+}
+
+impl TryFrom<Value> for ParameterValue {
+    type Error = Status;
+    fn try_from(value: Value) -> Result<Self, Status> {
+        match value.kind {
+            Some(value::Kind::Int(value)) => Ok(ParameterValue::Int(value)),
+            Some(value::Kind::UInt(value)) => Ok(ParameterValue::UInt(value)),
+            Some(value::Kind::Long(value)) => Ok(ParameterValue::Long(value)),
+            Some(value::Kind::ULong(value)) => Ok(ParameterValue::ULong(value)),
+            Some(value::Kind::Float(value)) => Ok(ParameterValue::Float(value)),
+            Some(value::Kind::Double(value)) => Ok(ParameterValue::Double(value)),
+            Some(value::Kind::String(value)) => Ok(ParameterValue::String(value)),
+            Some(value::Kind::Bool(value)) => Ok(ParameterValue::Bool(value)),
+            Some(value::Kind::Bytes(value)) => Ok(ParameterValue::VecBytes(value)),
+            Some(value::Kind::Void(_)) => Err(Status::invalid_argument(
+                "Void can't be passed to a guest function",
+            )),
+            None => Err(Status::invalid_argument("An argument has no value")),
+        }
+    }
+}
+
+impl From<ReturnValue> for Value {
+    fn from(value: ReturnValue) -> Self {
+        let kind = match value {
+            ReturnValue::Int(value) => value::Kind::Int(value),
+            ReturnValue::UInt(value) => value::Kind::UInt(value),
+            ReturnValue::Long(value) => value::Kind::Long(value),
+            ReturnValue::ULong(value) => value::Kind::ULong(value),
+            ReturnValue::Float(value) => value::Kind::Float(value),
+            ReturnValue::Double(value) => value::Kind::Double(value),
+            ReturnValue::String(value) => value::Kind::String(value),
+            ReturnValue::Bool(value) => value::Kind::Bool(value),
+            ReturnValue::VecBytes(value) => value::Kind::Bytes(value),
+            ReturnValue::Void => value::Kind::Void(Void {}),
+        };
+        Self { kind: Some(kind) }
+    }
+}
+
+impl From<ReturnType> for hyperlight_host::func::ReturnType {
+    fn from(return_type: ReturnType) -> Self {
+        match return_type {
+            ReturnType::Int => Self::Int,
+            ReturnType::UInt => Self::UInt,
+            ReturnType::Long => Self::Long,
+            ReturnType::ULong => Self::ULong,
+            ReturnType::Float => Self::Float,
+            ReturnType::Double => Self::Double,
+            ReturnType::String => Self::String,
+            ReturnType::Bool => Self::Bool,
+            ReturnType::Bytes => Self::VecBytes,
+            ReturnType::Void => Self::Void,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use prost::Message;
+
+    use super::*;
+
+    #[test]
+    fn values() {
+        let request = CallRequest {
+            pool: "simple".to_string(),
+            function: "Echo".to_string(),
+            args: vec![
+                Value {
+                    kind: Some(value::Kind::String("hello".to_string())),
+                },
+                Value {
+                    kind: Some(value::Kind::Bytes(vec![1, 2, 3])),
+                },
+            ],
+            return_type: ReturnType::String.into(),
+        };
+        let decoded = CallRequest::decode(request.encode_to_vec().as_slice()).unwrap();
+        assert_eq!(decoded, request);
+        assert_eq!(decoded.return_type(), ReturnType::String);
+
+        let args = decoded
+            .args
+            .into_iter()
+            .map(ParameterValue::try_from)
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        assert_eq!(
+            args,
+            [
+                ParameterValue::String("hello".to_string()),
+                ParameterValue::VecBytes(vec![1, 2, 3])
+            ]
+        );
+        assert!(ParameterValue::try_from(Value { kind: None }).is_err());
+        assert!(ParameterValue::try_from(Value::from(ReturnValue::Void)).is_err());
+        assert_eq!(
+            Value::from(ReturnValue::Long(-1)).kind,
+            Some(value::Kind::Long(-1))
+        );
+    }
+}
@@ -0,0 +1,190 @@
+/*
+Copyright 2024 The Hyperlight Authors.
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+    http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use hyperlight_common::flatbuffer_wrappers::guest_error::ErrorCode;
+use hyperlight_host::func::ParameterValue;
+use hyperlight_host::HyperlightError;
+use tonic::{Request, Response, Status};
+
+use crate::pool::SandboxPool;
+use crate::proto::guest_functions_server::{GuestFunctions, GuestFunctionsServer};
+use crate::proto::{
+    CallRequest, CallResponse, ListFunctionsRequest, ListFunctionsResponse, ReturnType,
+};
+
+/// Serves the `hyperlight.server.GuestFunctions` service, calling the
+/// functions of the guests in a set of named sandbox pools.
+#[derive(Default)]
+pub struct GuestService {
+    pools: HashMap<String, Arc<SandboxPool>>,
+}
+
+impl GuestService {
+    /// Create a service with no pools
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Serve the functions of the guest in `pool` to requests for the pool
+    /// called `name`, replacing any pool already called that
+    pub fn add_pool(&mut self, name: impl Into<String>, pool: SandboxPool) {
+        self.pools.insert(name.into(), Arc::new(pool));
+    }
+
+    /// Wrap the service in a server that can be added to a
+    /// `tonic::transport::Server`
+    pub fn into_server(self) -> GuestFunctionsServer<Self> {
+        GuestFunctionsServer::new(self)
+    }
+
+    // the service's methods fail with a `Status` too
+    #[allow(clippy::result_large_err)]
+    fn pool(&self, name: &str) -> Result<&Arc<SandboxPool>, Status> {
+        self.pools
+            .get(name)
+            .ok_or_else(|| Status::not_found(format!("There is no pool called {:?}", name)))
+    }
+}
+
+#[tonic::async_trait]
+impl GuestFunctions for GuestService {
+    async fn call(&self, request: Request<CallRequest>) -> Result<Response<CallResponse>, Status> {
+        let request = request.into_inner();
+        let return_type = ReturnType::try_from(request.return_type)
+            .map_err(|_| Status::invalid_argument("The return type is not valid"))?;
+        let pool = self.pool(&request.pool)?;
+        let args = request
+            .args
+            .into_iter()
+            .map(ParameterValue::try_from)
+            .collect::<Result<Vec<_>, _>>()?;
+        let args = (!args.is_empty()).then_some(args);
+
+        let value = pool
+            .call(request.function, return_type.into(), args)
+            .await
+            .map_err(status)?;
+        Ok(Response::new(CallResponse {
+            value: Some(value.into()),
+        }))
+    }
+
+    async fn list_functions(
+        &self,
+        request: Request<ListFunctionsRequest>,
+    ) -> Result<Response<ListFunctionsResponse>, Status> {
+        let pool = self.pool(&request.get_ref().pool)?;
+        Ok(Response::new(ListFunctionsResponse {
+            functions: pool.function_names().to_vec(),
+        }))
+    }
+}
+
+/// The gRPC status for a failed guest function call
+fn status(error: HyperlightError) -> Status {
+    let message = error.to_string();
+    match error {
+        HyperlightError::GuestError(ErrorCode::GuestFunctionNotFound, ..) => {
+            Status::not_found(message)
+        }
+        HyperlightError::GuestError(
+            ErrorCode::GuestFunctionIncorrecNoOfParameters
+            | ErrorCode::GuestFunctionParameterTypeMismatch
+            | ErrorCode::UnsupportedParameterType,
+            ..,
+        )
+        | HyperlightError::UnexpectedNoOfArguments(..)
+        | HyperlightError::UnexpectedParameterValueType(..)
+        | HyperlightError::UnexpectedReturnValueType(..) => Status::invalid_argument(message),
+        HyperlightError::ExecutionCanceledByHost() => Status::deadline_exceeded(message),
+        HyperlightError::GuestError(..)
+        | HyperlightError::GuestAborted { .. }
+        | HyperlightError::StackOverflow() => Status::aborted(message),
+        _ => Status::internal(message),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use hyperlight_host::sandbox::SandboxConfiguration;
+    use hyperlight_testing::simple_guest_as_string;
+
+    use super::*;
+    use crate::proto::{value, Value};
+
+    fn service() -> GuestService {
+        let pool = SandboxPool::new(
+            &simple_guest_as_string().unwrap(),
+            2,
+            SandboxConfiguration::default(),
+        )
+        .unwrap();
+        let mut service = GuestService::new();
+        service.add_pool("simple", pool);
+        service
+    }
+
+    #[tokio::test]
+    async fn call() {
+        let service = service();
+        let request = |pool: &str, function: &str| CallRequest {
+            pool: pool.to_string(),
+            function: function.to_string(),
+            args: vec![Value {
+                kind: Some(value::Kind::String("hello".to_string())),
+            }],
+            return_type: ReturnType::String.into(),
+        };
+
+        let response = service
+            .call(Request::new(request("simple", "Echo")))
+            .await
+            .unwrap();
+        assert_eq!(
+            response.into_inner().value.unwrap().kind,
+            Some(value::Kind::String("hello".to_string()))
+        );
+
+        let status = service
+            .call(Request::new(request("other", "Echo")))
+            .await
+            .unwrap_err();
+        assert_eq!(status.code(), tonic::Code::NotFound);
+        let status = service
+            .call(Request::new(request("simple", "NoSuchFunction")))
+            .await
+            .unwrap_err();
+        assert_eq!(status.code(), tonic::Code::NotFound);
+    }
+
+    #[tokio::test]
+    async fn list_functions() {
+        let service = service();
+        let response = service
+            .list_functions(Request::new(ListFunctionsRequest {
+                pool: "simple".to_string(),
+            }))
+            .await
+            .unwrap();
+        assert!(response
+            .into_inner()
+            .functions
+            .contains(&"Echo".to_string()));
+    }
+}
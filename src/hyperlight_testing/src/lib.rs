@@ -17,12 +17,18 @@ limitations under the License.
 // This crate contains testing utilities which need to be shared across multiple
 // crates in this project.
 use std::path::PathBuf;
+use std::process::Command;
 
-use anyhow::{anyhow, Result};
+use anyhow::{anyhow, bail, Result};
 
 pub const MANIFEST_DIR: &str = env!("CARGO_MANIFEST_DIR");
 pub mod logger;
+#[cfg(feature = "sandbox")]
+pub mod mock;
+#[cfg(feature = "sandbox")]
+pub mod sandbox;
 pub mod simplelogger;
+pub mod strategies;
 pub mod tracing_subscriber;
 
 /// Join all the `&str`s in the `v` parameter as a path with appropriate
@@ -63,6 +69,54 @@ pub fn rust_guest_as_pathbuf(guest: &str) -> PathBuf {
     )
 }
 
+/// Build the Rust guest in the cargo project at `project_dir` for the
+/// `x86_64-unknown-none` target, with the same profile as the current
+/// build, and return the path to the ELF binary named `guest`
+pub fn build_rust_guest(project_dir: &str, guest: &str) -> Result<PathBuf> {
+    let (profile, profile_dir) = if cfg!(debug_assertions) {
+        ("dev", "debug")
+    } else {
+        ("release", "release")
+    };
+
+    let status = Command::new("cargo")
+        .args([
+            "build",
+            "--target",
+            "x86_64-unknown-none",
+            "--profile",
+            profile,
+        ])
+        .current_dir(project_dir)
+        .status()?;
+    if !status.success() {
+        bail!("failed to build the guest in {}: {}", project_dir, status);
+    }
+
+    let binary = join_to_path(
+        project_dir,
+        vec!["target", "x86_64-unknown-none", profile_dir, guest],
+    );
+    if !binary.exists() {
+        bail!("the guest binary {} was not built", binary.display());
+    }
+    Ok(binary)
+}
+
+/// Get the path to one of our Rust test guests, building it if it hasn't
+/// already been built and copied to `src/tests/rust_guests/bin`
+pub fn locate_or_build_rust_guest(guest: &str) -> Result<PathBuf> {
+    let binary = rust_guest_as_pathbuf(guest);
+    if binary.exists() {
+        return Ok(binary);
+    }
+    let project_dir = join_to_path(MANIFEST_DIR, vec!["..", "tests", "rust_guests", guest]);
+    let project_dir = project_dir
+        .to_str()
+        .ok_or_else(|| anyhow!("couldn't convert {} PathBuf to string", guest))?;
+    build_rust_guest(project_dir, guest)
+}
+
 /// Get a fully qualified OS-specific path to the simpleguest elf binary
 pub fn simple_guest_as_string() -> Result<String> {
     let buf = rust_guest_as_pathbuf("simpleguest");
@@ -103,6 +157,22 @@ pub fn dummy_guest_as_string() -> Result<String> {
         .ok_or_else(|| anyhow!("couldn't convert dummy guest PathBuf to string"))
 }
 
+/// Get a fully qualified OS-specific path to the chattyguest elf binary
+pub fn chatty_guest_as_string() -> Result<String> {
+    let buf = rust_guest_as_pathbuf("chattyguest");
+    buf.to_str()
+        .map(|s| s.to_string())
+        .ok_or_else(|| anyhow!("couldn't convert chatty guest PathBuf to string"))
+}
+
+/// Get a fully qualified OS-specific path to the computeguest elf binary
+pub fn compute_guest_as_string() -> Result<String> {
+    let buf = rust_guest_as_pathbuf("computeguest");
+    buf.to_str()
+        .map(|s| s.to_string())
+        .ok_or_else(|| anyhow!("couldn't convert compute guest PathBuf to string"))
+}
+
 pub fn c_guest_as_pathbuf(guest: &str) -> PathBuf {
     let build_dir_selector = if cfg!(debug_assertions) {
         "debug"
@@ -0,0 +1,404 @@
+/*
+Copyright 2024 The Hyperlight Authors.
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+    http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+//! Mock host functions, for testing how a guest uses the host functions it
+//! calls without implementing them, and a mock `GuestCaller`, for testing
+//! code that calls guests without a hypervisor.
+//!
+//! ```ignore
+//! let mocks = MockHostFunctions::new();
+//! mocks.expect(
+//!     Expectation::call("HostAdd")
+//!         .with_args(vec![ParameterValue::Int(40), ParameterValue::Int(2)])
+//!         .returning(ReturnValue::Int(42))
+//!         .times(1),
+//! );
+//! mocks.register(&mut uninitialized_sandbox)?;
+//! // ... evolve the sandbox and call the guest ...
+//! // dropping `mocks` panics if `HostAdd` wasn't called exactly once
+//! ```
+//!
+//! `MockGuestCaller` takes the same `Expectation`s, for guest functions.
+
+use std::fmt::Debug;
+use std::sync::{Arc, Mutex};
+
+use hyperlight_host::func::guest_caller::timed;
+use hyperlight_host::func::{CallStats, GuestCaller, ParameterValue, ReturnType, ReturnValue};
+use hyperlight_host::{new_error, Result, UninitializedSandbox};
+
+type ArgsMatcher = Box<dyn Fn(&[ParameterValue]) -> bool + Send>;
+
+/// A call to a host function, or to a guest function through a
+/// `MockGuestCaller`, that is expected to be made, and what to return
+pub struct Expectation {
+    name: String,
+    args: ArgsMatcher,
+    args_description: String,
+    result: std::result::Result<ReturnValue, String>,
+    times: Option<usize>,
+    calls: usize,
+}
+
+impl Expectation {
+    /// Expect calls to the function `name` with any arguments, any number
+    /// of times, returning `ReturnValue::Void`
+    pub fn call(name: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            args: Box::new(|_| true),
+            args_description: "any arguments".to_string(),
+            result: Ok(ReturnValue::Void),
+            times: None,
+            calls: 0,
+        }
+    }
+
+    /// Only match calls with exactly `args`
+    pub fn with_args(mut self, args: Vec<ParameterValue>) -> Self {
+        self.args_description = format!("{:?}", args);
+        self.args = Box::new(move |actual| actual == args.as_slice());
+        self
+    }
+
+    /// Only match calls whose arguments `matcher` accepts
+    pub fn with_args_matching(
+        mut self,
+        matcher: impl Fn(&[ParameterValue]) -> bool + Send + 'static,
+    ) -> Self {
+        self.args_description = "matching arguments".to_string();
+        self.args = Box::new(matcher);
+        self
+    }
+
+    /// Return `value` from the call
+    pub fn returning(mut self, value: ReturnValue) -> Self {
+        self.result = Ok(value);
+        self
+    }
+
+    /// Fail the call with an error with the message `message`
+    pub fn failing(mut self, message: impl Into<String>) -> Self {
+        self.result = Err(message.into());
+        self
+    }
+
+    /// Expect exactly `times` matching calls. Calls beyond that are
+    /// unexpected.
+    pub fn times(mut self, times: usize) -> Self {
+        self.times = Some(times);
+        self
+    }
+
+    fn matches(&self, name: &str, args: &[ParameterValue]) -> bool {
+        self.name == name && (self.args)(args)
+    }
+
+    fn saturated(&self) -> bool {
+        self.times.is_some_and(|times| self.calls >= times)
+    }
+}
+
+impl Debug for Expectation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} with {}", self.name, self.args_description)
+    }
+}
+
+#[derive(Debug, Default)]
+struct State {
+    expectations: Vec<Expectation>,
+    /// Descriptions of the calls no expectation matched
+    unexpected: Vec<String>,
+}
+
+impl State {
+    /// Answer a call from the guest with the first expectation that matches
+    /// it and hasn't had all the calls it expects
+    fn call(&mut self, name: &str, args: Vec<ParameterValue>) -> Result<ReturnValue> {
+        let expectation = self
+            .expectations
+            .iter_mut()
+            .find(|e| e.matches(name, &args) && !e.saturated());
+        match expectation {
+            Some(expectation) => {
+                expectation.calls += 1;
+                expectation
+                    .result
+                    .clone()
+                    .map_err(|message| new_error!("{}", message))
+            }
+            None => {
+                let call = format!("{} with {:?}", name, args);
+                self.unexpected.push(call.clone());
+                Err(new_error!("Unexpected call to {}", call))
+            }
+        }
+    }
+
+    /// Describe each way the calls made differ from the expectations
+    fn failures(&self) -> Vec<String> {
+        let mut failures: Vec<String> = self
+            .unexpected
+            .iter()
+            .map(|call| format!("unexpected call to {}", call))
+            .collect();
+        for expectation in &self.expectations {
+            match expectation.times {
+                Some(times) if times != expectation.calls => failures.push(format!(
+                    "expected {} calls to {:?}, got {}",
+                    times, expectation, expectation.calls
+                )),
+                _ => {}
+            }
+        }
+        failures
+    }
+
+    /// The number of calls made to the function `name`
+    fn calls(&self, name: &str) -> usize {
+        let expected: usize = self
+            .expectations
+            .iter()
+            .filter(|e| e.name == name)
+            .map(|e| e.calls)
+            .sum();
+        let prefix = format!("{} with ", name);
+        expected
+            + self
+                .unexpected
+                .iter()
+                .filter(|call| call.starts_with(&prefix))
+                .count()
+    }
+
+    #[track_caller]
+    fn verify(&self, mocks: &str) {
+        let failures = self.failures();
+        if !failures.is_empty() {
+            panic!("{} failed:\n{}", mocks, failures.join("\n"));
+        }
+    }
+}
+
+/// A set of host functions whose calls are checked against expectations.
+///
+/// The expectations are checked when the `MockHostFunctions` is dropped,
+/// or earlier with `verify`, and it panics if the guest made a call that
+/// none of them matched or an expectation with a call count didn't get
+/// that many calls. A call that no expectation matches also fails in the
+/// guest.
+#[derive(Debug, Default)]
+pub struct MockHostFunctions {
+    state: Arc<Mutex<State>>,
+}
+
+impl MockHostFunctions {
+    /// Create a set of mocks without any expectations
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add an expectation. A call is answered by the first expectation, in
+    /// the order they were added, that matches it and hasn't had all the
+    /// calls it expects.
+    pub fn expect(&self, expectation: Expectation) -> &Self {
+        self.state.lock().unwrap().expectations.push(expectation);
+        self
+    }
+
+    /// Answer the guest's calls to host functions that aren't registered
+    /// with `sandbox` from the expectations. This takes the place of the
+    /// sandbox's fallback host function, and host functions registered by
+    /// name take precedence over the mocks.
+    pub fn register(&self, sandbox: &mut UninitializedSandbox) -> Result<()> {
+        let state = self.state.clone();
+        sandbox.register_fallback_host_function(
+            move |name, args| {
+                state
+                    .lock()
+                    .map_err(|e| new_error!("Error locking at {}:{}: {}", file!(), line!(), e))?
+                    .call(&name, args)
+            },
+            vec![],
+        )
+    }
+
+    /// The number of calls made to the host function `name`
+    pub fn calls(&self, name: &str) -> usize {
+        self.state.lock().unwrap().calls(name)
+    }
+
+    /// Panic if the calls made so far don't meet the expectations
+    #[track_caller]
+    pub fn verify(&self) {
+        self.state.lock().unwrap().verify("host function mocks");
+    }
+}
+
+impl Drop for MockHostFunctions {
+    fn drop(&mut self) {
+        // don't turn a failing test's panic into an abort
+        if !std::thread::panicking() {
+            self.verify();
+        }
+    }
+}
+
+/// A `GuestCaller` whose guest functions are answered from expectations,
+/// so that code that calls guests can be tested without a hypervisor.
+///
+/// Like `MockHostFunctions`, it panics when dropped, or earlier with
+/// `verify`, if a call was made that no expectation matched or an
+/// expectation with a call count didn't get that many calls. The return
+/// type calls are made with isn't checked against the value returned.
+#[derive(Debug, Default)]
+pub struct MockGuestCaller {
+    state: State,
+    stats: CallStats,
+}
+
+impl MockGuestCaller {
+    /// Create a mock without any expectations
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add an expectation, see `MockHostFunctions::expect`
+    pub fn expect(&mut self, expectation: Expectation) -> &mut Self {
+        self.state.expectations.push(expectation);
+        self
+    }
+
+    /// The number of calls made to the guest function `name`
+    pub fn calls(&self, name: &str) -> usize {
+        self.state.calls(name)
+    }
+
+    /// Panic if the calls made so far don't meet the expectations
+    #[track_caller]
+    pub fn verify(&self) {
+        self.state.verify("guest function mocks");
+    }
+}
+
+impl GuestCaller for MockGuestCaller {
+    fn call(
+        &mut self,
+        func_name: &str,
+        _func_ret_type: ReturnType,
+        args: Option<Vec<ParameterValue>>,
+    ) -> Result<ReturnValue> {
+        timed(&mut self.stats, || {
+            self.state.call(func_name, args.unwrap_or_default())
+        })
+    }
+
+    fn stats(&self) -> CallStats {
+        self.stats
+    }
+}
+
+impl Drop for MockGuestCaller {
+    fn drop(&mut self) {
+        if !std::thread::panicking() {
+            self.verify();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use hyperlight_host::func::{GuestCaller, ParameterValue, ReturnValue};
+
+    use super::{Expectation, MockGuestCaller, MockHostFunctions};
+
+    fn call(mocks: &MockHostFunctions, name: &str, args: Vec<ParameterValue>) -> bool {
+        mocks.state.lock().unwrap().call(name, args).is_ok()
+    }
+
+    #[test]
+    fn calls_are_answered_from_expectations() {
+        let mocks = MockHostFunctions::new();
+        mocks
+            .expect(
+                Expectation::call("HostAdd")
+                    .with_args(vec![ParameterValue::Int(1), ParameterValue::Int(2)])
+                    .returning(ReturnValue::Int(3))
+                    .times(1),
+            )
+            .expect(Expectation::call("HostLog").failing("log is full"));
+
+        let result = mocks.state.lock().unwrap().call(
+            "HostAdd",
+            vec![ParameterValue::Int(1), ParameterValue::Int(2)],
+        );
+        assert_eq!(result.unwrap(), ReturnValue::Int(3));
+        assert!(!call(&mocks, "HostLog", vec![]));
+        assert!(!call(&mocks, "HostLog", vec![ParameterValue::Int(1)]));
+        assert_eq!(mocks.calls("HostAdd"), 1);
+        assert_eq!(mocks.calls("HostLog"), 2);
+        mocks.verify();
+    }
+
+    #[test]
+    #[should_panic(expected = "expected 2 calls to HostAdd with any arguments, got 1")]
+    fn missing_calls_panic_on_drop() {
+        let mocks = MockHostFunctions::new();
+        mocks.expect(Expectation::call("HostAdd").times(2));
+        assert!(call(&mocks, "HostAdd", vec![]));
+    }
+
+    #[test]
+    #[should_panic(expected = "unexpected call to HostAdd with [Int(2)]")]
+    fn unexpected_calls_panic_on_drop() {
+        let mocks = MockHostFunctions::new();
+        mocks.expect(
+            Expectation::call("HostAdd")
+                .with_args_matching(|args| args == [ParameterValue::Int(1)])
+                .times(1),
+        );
+        assert!(call(&mocks, "HostAdd", vec![ParameterValue::Int(1)]));
+        // the expectation has had all its calls
+        assert!(!call(&mocks, "HostAdd", vec![ParameterValue::Int(1)]));
+        assert!(!call(&mocks, "HostAdd", vec![ParameterValue::Int(2)]));
+    }
+
+    #[test]
+    fn guest_calls_are_answered_from_expectations() {
+        let mut guest = MockGuestCaller::new();
+        guest
+            .expect(
+                Expectation::call("Add")
+                    .with_args(vec![ParameterValue::Int(1), ParameterValue::Int(2)])
+                    .returning(ReturnValue::Int(3))
+                    .times(1),
+            )
+            .expect(Expectation::call("Reset").failing("busy"));
+
+        let sum: i32 = guest
+            .call_typed(
+                "Add",
+                Some(vec![ParameterValue::Int(1), ParameterValue::Int(2)]),
+            )
+            .unwrap();
+        assert_eq!(sum, 3);
+        assert!(guest.call_typed::<()>("Reset", None).is_err());
+        assert_eq!(guest.calls("Reset"), 1);
+        let stats = guest.stats();
+        assert_eq!((stats.calls, stats.failures), (2, 1));
+    }
+}
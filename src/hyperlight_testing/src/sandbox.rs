@@ -0,0 +1,86 @@
+/*
+Copyright 2024 The Hyperlight Authors.
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+    http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+//! Helpers for creating sandboxes and checking the results of guest calls
+//! in integration tests.
+
+use std::path::Path;
+
+use hyperlight_common::flatbuffer_wrappers::guest_error::ErrorCode;
+use hyperlight_host::sandbox::SandboxConfiguration;
+use hyperlight_host::sandbox_state::sandbox::EvolvableSandbox;
+use hyperlight_host::sandbox_state::transition::Noop;
+use hyperlight_host::{
+    GuestBinary, HyperlightError, MultiUseSandbox, Result, UninitializedSandbox,
+};
+
+/// Create an `UninitializedSandbox` for the guest binary at `path`, with a
+/// configuration that starts as the default and is then changed by
+/// `configure`.
+pub fn new_uninit_sandbox(
+    path: impl AsRef<Path>,
+    configure: impl FnOnce(&mut SandboxConfiguration),
+) -> Result<UninitializedSandbox> {
+    let mut cfg = SandboxConfiguration::default();
+    configure(&mut cfg);
+    let path = path.as_ref().to_string_lossy().to_string();
+    UninitializedSandbox::new(GuestBinary::FilePath(path), Some(cfg), None, None)
+}
+
+/// Create a `MultiUseSandbox` for the guest binary at `path`, as
+/// `new_uninit_sandbox` does.
+pub fn new_sandbox(
+    path: impl AsRef<Path>,
+    configure: impl FnOnce(&mut SandboxConfiguration),
+) -> Result<MultiUseSandbox> {
+    new_uninit_sandbox(path, configure)?.evolve(Noop::default())
+}
+
+/// Panic unless `result` failed with a `GuestError` with the code `code`
+/// and a message containing `message`.
+#[track_caller]
+pub fn assert_guest_error<T: std::fmt::Debug>(result: Result<T>, code: ErrorCode, message: &str) {
+    match result {
+        Err(HyperlightError::GuestError(actual_code, actual_message, _)) => {
+            assert_eq!(
+                actual_code, code,
+                "unexpected error code: {}",
+                actual_message
+            );
+            assert!(
+                actual_message.contains(message),
+                "error message {:?} doesn't contain {:?}",
+                actual_message,
+                message
+            );
+        }
+        other => panic!("expected a guest error, got {:?}", other),
+    }
+}
+
+/// Panic unless `result` failed because the guest aborted with the code
+/// `code`.
+#[track_caller]
+pub fn assert_guest_aborted<T: std::fmt::Debug>(result: Result<T>, code: u8) {
+    match result {
+        Err(HyperlightError::GuestAborted {
+            code: actual_code,
+            message,
+            ..
+        }) => assert_eq!(actual_code, code, "unexpected abort code: {}", message),
+        other => panic!("expected the guest to abort, got {:?}", other),
+    }
+}
@@ -82,11 +82,14 @@ impl Log for SimpleLogger {
         // because the guest derives its log level from the host log level then the number times that enabled is called for
         // the "hyperlight-guest" target will be the same as the number of messages logged by the guest.
         // In other words this function should always return true for the "hyperlight-guest" target.
+        // Messages the guest logs to a target of its own have a "hyperlight-guest::<target>" target.
+        let is_guest_target = metadata.target() == "hyperlight-guest"
+            || metadata.target().starts_with("hyperlight-guest::");
         unsafe {
-            if metadata.target() == "hyperlight-guest" {
+            if is_guest_target {
                 NUMBER_OF_ENABLED_CALLS += 1;
             }
-            metadata.target() == "hyperlight-guest" && metadata.level() <= log::max_level()
+            is_guest_target && metadata.level() <= log::max_level()
         }
     }
     fn log(&self, record: &Record) {
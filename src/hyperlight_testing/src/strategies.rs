@@ -0,0 +1,113 @@
+/*
+Copyright 2024 The Hyperlight Authors.
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+    http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+//! proptest strategies for the values passed to and returned from guest
+//! functions.
+
+use hyperlight_common::flatbuffer_wrappers::function_types::{ParameterType, ParameterValue};
+use hyperlight_common::secret::Secret;
+use proptest::collection::vec;
+use proptest::prelude::*;
+
+/// The longest `String`, `VecBytes` or `Secret` the strategies generate, which keeps
+/// the values well within the default size of the sandbox's input buffer
+pub const MAX_LEN: usize = 1024;
+
+/// Any `ParameterType`.
+pub fn parameter_type() -> impl Strategy<Value = ParameterType> {
+    prop_oneof![
+        Just(ParameterType::Int),
+        Just(ParameterType::UInt),
+        Just(ParameterType::Long),
+        Just(ParameterType::ULong),
+        Just(ParameterType::Float),
+        Just(ParameterType::Double),
+        Just(ParameterType::String),
+        Just(ParameterType::Bool),
+        Just(ParameterType::VecBytes),
+        Just(ParameterType::Secret),
+    ]
+}
+
+/// Any `ParameterValue` of the given type.
+///
+/// Floats and doubles may be NaN, which doesn't compare equal to itself,
+/// so filter them out when asserting that a value round trips.
+pub fn parameter_value_of(parameter_type: ParameterType) -> BoxedStrategy<ParameterValue> {
+    match parameter_type {
+        ParameterType::Int => any::<i32>().prop_map(ParameterValue::Int).boxed(),
+        ParameterType::UInt => any::<u32>().prop_map(ParameterValue::UInt).boxed(),
+        ParameterType::Long => any::<i64>().prop_map(ParameterValue::Long).boxed(),
+        ParameterType::ULong => any::<u64>().prop_map(ParameterValue::ULong).boxed(),
+        ParameterType::Float => any::<f32>().prop_map(ParameterValue::Float).boxed(),
+        ParameterType::Double => any::<f64>().prop_map(ParameterValue::Double).boxed(),
+        ParameterType::String => vec(any::<char>(), 0..MAX_LEN)
+            .prop_map(|chars| ParameterValue::String(chars.into_iter().collect()))
+            .boxed(),
+        ParameterType::Bool => any::<bool>().prop_map(ParameterValue::Bool).boxed(),
+        ParameterType::VecBytes => vec(any::<u8>(), 0..MAX_LEN)
+            .prop_map(ParameterValue::VecBytes)
+            .boxed(),
+        ParameterType::Secret => vec(any::<u8>(), 0..MAX_LEN)
+            .prop_map(|bytes| ParameterValue::Secret(Secret::new(bytes)))
+            .boxed(),
+    }
+}
+
+/// Any `ParameterValue`.
+pub fn parameter_value() -> impl Strategy<Value = ParameterValue> {
+    parameter_type().prop_flat_map(parameter_value_of)
+}
+
+/// Arguments for a guest function taking parameters of the given types.
+pub fn parameter_values_of(
+    parameter_types: Vec<ParameterType>,
+) -> impl Strategy<Value = Vec<ParameterValue>> {
+    parameter_types
+        .into_iter()
+        .map(parameter_value_of)
+        .collect::<Vec<_>>()
+}
+
+#[cfg(test)]
+mod tests {
+    use hyperlight_common::flatbuffer_wrappers::function_types::{ParameterType, ParameterValue};
+    use proptest::prelude::*;
+
+    use super::{parameter_value, parameter_values_of, MAX_LEN};
+
+    proptest! {
+        #[test]
+        fn values_match_their_types(
+            values in parameter_values_of(vec![ParameterType::Int, ParameterType::String])
+        ) {
+            prop_assert!(matches!(
+                values.as_slice(),
+                [ParameterValue::Int(_), ParameterValue::String(_)]
+            ));
+        }
+
+        #[test]
+        fn values_are_bounded(value in parameter_value()) {
+            match value {
+                ParameterValue::String(s) => prop_assert!(s.chars().count() < MAX_LEN),
+                ParameterValue::VecBytes(v) => prop_assert!(v.len() < MAX_LEN),
+                ParameterValue::Secret(v) => prop_assert!(v.expose().len() < MAX_LEN),
+                _ => {}
+            }
+        }
+    }
+}
@@ -230,7 +230,9 @@ int execute_on_stack(void) {
   return -1;
 }
 
-int log_message(const char *message, int64_t level) {
+// hl_log has no way to choose a target, so `target` is ignored
+int log_message(const char *message, const char *target, int64_t level) {
+  (void)target;
   LOG((hl_Level)level, message);
   return -1;
 }
@@ -262,7 +264,7 @@ HYPERLIGHT_WRAP_FUNCTION(set_static, Int, 0)
 HYPERLIGHT_WRAP_FUNCTION(guest_abort_with_msg, Int, 2, Int, String)
 HYPERLIGHT_WRAP_FUNCTION(guest_abort_with_code, Int, 1, Int)
 HYPERLIGHT_WRAP_FUNCTION(execute_on_stack, Int, 0)
-HYPERLIGHT_WRAP_FUNCTION(log_message, Int, 2, String, Long)
+HYPERLIGHT_WRAP_FUNCTION(log_message, Int, 3, String, String, Long)
 
 void hyperlight_main(void)
 {
@@ -34,12 +34,11 @@ use hyperlight_common::flatbuffer_wrappers::util::{
     get_flatbuffer_result_from_int, get_flatbuffer_result_from_void,
 };
 use hyperlight_guest::error::{HyperlightGuestError, Result};
-use hyperlight_guest::guest_function_definition::GuestFunctionDefinition;
-use hyperlight_guest::guest_function_register::register_function;
 use hyperlight_guest::host_function_call::{
     call_host_function, get_host_value_return_as_int, print_output_as_guest_function,
 };
 use hyperlight_guest::logging::log_message;
+use hyperlight_guest::register_guest_function;
 
 fn send_message_to_host_method(
     method_name: &str,
@@ -102,7 +101,7 @@ fn guest_function3(function_call: &FunctionCall) -> Result<Vec<u8>> {
     }
 }
 
-fn guest_function4() -> Result<Vec<u8>> {
+fn guest_function4(_: &FunctionCall) -> Result<Vec<u8>> {
     call_host_function(
         "HostMethod4",
         Some(Vec::from(&[ParameterValue::String(
@@ -158,88 +157,90 @@ fn call_error_method(function_call: &FunctionCall) -> Result<Vec<u8>> {
     }
 }
 
-fn call_host_spin() -> Result<Vec<u8>> {
+fn call_host_method(function_call: &FunctionCall) -> Result<Vec<u8>> {
+    if let (ParameterValue::String(method_name), ParameterValue::String(message)) = (
+        &function_call.parameters.as_ref().unwrap()[0],
+        &function_call.parameters.as_ref().unwrap()[1],
+    ) {
+        send_message_to_host_method(method_name, "", message)
+    } else {
+        return Err(HyperlightGuestError::new(
+            ErrorCode::GuestFunctionParameterTypeMismatch,
+            "Invalid parameters passed to call_host_method".to_string(),
+        ));
+    }
+}
+
+fn call_host_spin(_: &FunctionCall) -> Result<Vec<u8>> {
     call_host_function("Spin", None, ReturnType::Void)?;
     Ok(get_flatbuffer_result_from_void())
 }
 
 #[no_mangle]
 pub extern "C" fn hyperlight_main() {
-    let print_output_def = GuestFunctionDefinition::new(
-        "PrintOutput".to_string(),
-        Vec::from(&[ParameterType::String]),
+    register_guest_function!(
+        "PrintOutput",
+        [ParameterType::String],
         ReturnType::Int,
-        print_output_as_guest_function as i64,
+        print_output_as_guest_function
     );
-    register_function(print_output_def);
 
-    let guest_function_def = GuestFunctionDefinition::new(
-        "GuestMethod".to_string(),
-        Vec::from(&[ParameterType::String]),
+    register_guest_function!(
+        "GuestMethod",
+        [ParameterType::String],
         ReturnType::Int,
-        guest_function as i64,
+        guest_function
     );
-    register_function(guest_function_def);
 
-    let guest_function1_def = GuestFunctionDefinition::new(
-        "GuestMethod1".to_string(),
-        Vec::from(&[ParameterType::String]),
+    register_guest_function!(
+        "GuestMethod1",
+        [ParameterType::String],
         ReturnType::Int,
-        guest_function1 as i64,
+        guest_function1
     );
-    register_function(guest_function1_def);
 
-    let guest_function2_def = GuestFunctionDefinition::new(
-        "GuestMethod2".to_string(),
-        Vec::from(&[ParameterType::String]),
+    register_guest_function!(
+        "GuestMethod2",
+        [ParameterType::String],
         ReturnType::Int,
-        guest_function2 as i64,
+        guest_function2
     );
-    register_function(guest_function2_def);
 
-    let guest_function3_def = GuestFunctionDefinition::new(
-        "GuestMethod3".to_string(),
-        Vec::from(&[ParameterType::String]),
+    register_guest_function!(
+        "GuestMethod3",
+        [ParameterType::String],
         ReturnType::Int,
-        guest_function3 as i64,
+        guest_function3
     );
-    register_function(guest_function3_def);
 
-    let guest_function4_def = GuestFunctionDefinition::new(
-        "GuestMethod4".to_string(),
-        Vec::new(),
-        ReturnType::Int,
-        guest_function4 as i64,
-    );
-    register_function(guest_function4_def);
+    register_guest_function!("GuestMethod4", [], ReturnType::Int, guest_function4);
 
-    let guest_log_message_def = GuestFunctionDefinition::new(
-        "LogMessage".to_string(),
-        Vec::from(&[
+    register_guest_function!(
+        "LogMessage",
+        [
             ParameterType::String,
             ParameterType::String,
-            ParameterType::Int,
-        ]),
+            ParameterType::Int
+        ],
         ReturnType::Int,
-        guest_log_message as i64,
+        guest_log_message
     );
-    register_function(guest_log_message_def);
 
-    let call_error_method_def = GuestFunctionDefinition::new(
-        "CallErrorMethod".to_string(),
-        Vec::from(&[ParameterType::String]),
+    register_guest_function!(
+        "CallErrorMethod",
+        [ParameterType::String],
         ReturnType::Int,
-        call_error_method as i64,
+        call_error_method
     );
-    register_function(call_error_method_def);
 
-    let call_host_spin_def = GuestFunctionDefinition::new(
-        "CallHostSpin".to_string(),
-        Vec::new(),
+    register_guest_function!(
+        "CallHostMethod",
+        [ParameterType::String, ParameterType::String],
         ReturnType::Int,
-        call_host_spin as i64,
+        call_host_method
     );
-    register_function(call_host_spin_def);
+
+    register_guest_function!("CallHostSpin", [], ReturnType::Int, call_host_spin);
 }
 
 #[no_mangle]
@@ -0,0 +1,181 @@
+/*
+Copyright 2024 The Hyperlight Authors.
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+    http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+//! A guest whose functions call the host many times per call, like a
+//! plugin that logs or reports progress as it works. It is used to test
+//! how the host copes with chatty guests, and as an example of the
+//! patterns such guests use.
+
+#![no_std]
+#![no_main]
+
+extern crate alloc;
+extern crate hyperlight_guest;
+
+use alloc::format;
+use alloc::string::ToString;
+use alloc::vec::Vec;
+
+use hyperlight_common::flatbuffer_wrappers::function_call::FunctionCall;
+use hyperlight_common::flatbuffer_wrappers::function_types::{
+    ParameterType, ParameterValue, ReturnType,
+};
+use hyperlight_common::flatbuffer_wrappers::guest_error::ErrorCode;
+use hyperlight_common::flatbuffer_wrappers::guest_log_level::LogLevel;
+use hyperlight_common::flatbuffer_wrappers::util::get_flatbuffer_result_from_int;
+use hyperlight_guest::error::{HyperlightGuestError, Result};
+use hyperlight_guest::guest_function_definition::GuestFunctionDefinition;
+use hyperlight_guest::guest_function_register::register_function;
+use hyperlight_guest::host_function_call::{call_host_function, get_host_value_return_as_int};
+use hyperlight_guest::logging::log_message;
+
+/// Call the host function `function_name`, which takes `args` and
+/// returns an `Int`
+fn call_host_for_int(function_name: &str, args: Vec<ParameterValue>) -> Result<i32> {
+    call_host_function(function_name, Some(args), ReturnType::Int)?;
+    get_host_value_return_as_int()
+}
+
+/// Print `message` `count` times through `HostPrint`, returning the sum of
+/// what the writer returned
+fn print_many(function_call: &FunctionCall) -> Result<Vec<u8>> {
+    if let (ParameterValue::String(message), ParameterValue::Int(count)) = (
+        function_call.parameters.clone().unwrap()[0].clone(),
+        function_call.parameters.clone().unwrap()[1].clone(),
+    ) {
+        let mut total = 0i32;
+        for _ in 0..count {
+            let written = call_host_for_int(
+                "HostPrint",
+                Vec::from(&[ParameterValue::String(message.clone())]),
+            )?;
+            total = total.wrapping_add(written);
+        }
+        Ok(get_flatbuffer_result_from_int(total))
+    } else {
+        Err(HyperlightGuestError::new(
+            ErrorCode::GuestFunctionParameterTypeMismatch,
+            "Invalid parameters passed to print_many".to_string(),
+        ))
+    }
+}
+
+/// Log `count` messages at `level`, returning the number logged
+fn log_many(function_call: &FunctionCall) -> Result<Vec<u8>> {
+    if let (ParameterValue::Int(count), ParameterValue::Int(level)) = (
+        function_call.parameters.clone().unwrap()[0].clone(),
+        function_call.parameters.clone().unwrap()[1].clone(),
+    ) {
+        let level = LogLevel::from(level.clamp(0, 6) as u8);
+        for i in 0..count {
+            let message = format!("log storm message {}", i);
+            log_message(level, &message, "chattyguest", "log_many", file!(), line!());
+        }
+        Ok(get_flatbuffer_result_from_int(count.max(0)))
+    } else {
+        Err(HyperlightGuestError::new(
+            ErrorCode::GuestFunctionParameterTypeMismatch,
+            "Invalid parameters passed to log_many".to_string(),
+        ))
+    }
+}
+
+/// Call the host function `function_name`, which takes and returns an
+/// `Int`, `count` times with the arguments `0..count`, returning the sum
+/// of the results
+fn call_host_many(function_call: &FunctionCall) -> Result<Vec<u8>> {
+    if let (ParameterValue::String(function_name), ParameterValue::Int(count)) = (
+        function_call.parameters.clone().unwrap()[0].clone(),
+        function_call.parameters.clone().unwrap()[1].clone(),
+    ) {
+        let mut total = 0i32;
+        for i in 0..count {
+            let result = call_host_for_int(&function_name, Vec::from(&[ParameterValue::Int(i)]))?;
+            total = total.wrapping_add(result);
+        }
+        Ok(get_flatbuffer_result_from_int(total))
+    } else {
+        Err(HyperlightGuestError::new(
+            ErrorCode::GuestFunctionParameterTypeMismatch,
+            "Invalid parameters passed to call_host_many".to_string(),
+        ))
+    }
+}
+
+/// Call `HostPing` with `depth`, then recurse with `depth - 1`, so that
+/// every host call is made with the frames of the earlier ones still on
+/// the guest's stack
+fn ping_pong_to_depth(depth: i32) -> Result<i32> {
+    if depth <= 0 {
+        return Ok(0);
+    }
+    let pong = call_host_for_int("HostPing", Vec::from(&[ParameterValue::Int(depth)]))?;
+    Ok(pong.wrapping_add(ping_pong_to_depth(depth - 1)?))
+}
+
+fn ping_pong(function_call: &FunctionCall) -> Result<Vec<u8>> {
+    if let ParameterValue::Int(depth) = function_call.parameters.clone().unwrap()[0].clone() {
+        Ok(get_flatbuffer_result_from_int(ping_pong_to_depth(depth)?))
+    } else {
+        Err(HyperlightGuestError::new(
+            ErrorCode::GuestFunctionParameterTypeMismatch,
+            "Invalid parameters passed to ping_pong".to_string(),
+        ))
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn hyperlight_main() {
+    let print_many_def = GuestFunctionDefinition::new(
+        "PrintMany".to_string(),
+        Vec::from(&[ParameterType::String, ParameterType::Int]),
+        ReturnType::Int,
+        print_many,
+    );
+    register_function(print_many_def);
+
+    let log_many_def = GuestFunctionDefinition::new(
+        "LogMany".to_string(),
+        Vec::from(&[ParameterType::Int, ParameterType::Int]),
+        ReturnType::Int,
+        log_many,
+    );
+    register_function(log_many_def);
+
+    let call_host_many_def = GuestFunctionDefinition::new(
+        "CallHostMany".to_string(),
+        Vec::from(&[ParameterType::String, ParameterType::Int]),
+        ReturnType::Int,
+        call_host_many,
+    );
+    register_function(call_host_many_def);
+
+    let ping_pong_def = GuestFunctionDefinition::new(
+        "PingPong".to_string(),
+        Vec::from(&[ParameterType::Int]),
+        ReturnType::Int,
+        ping_pong,
+    );
+    register_function(ping_pong_def);
+}
+
+#[no_mangle]
+pub fn guest_dispatch_function(function_call: FunctionCall) -> Result<Vec<u8>> {
+    Err(HyperlightGuestError::new(
+        ErrorCode::GuestFunctionNotFound,
+        function_call.function_name.clone(),
+    ))
+}
@@ -0,0 +1,217 @@
+/*
+Copyright 2024 The Hyperlight Authors.
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+    http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+//! A guest exposing compute-heavy functions, used as an example of a guest
+//! that does real work and as a workload for benchmarks.
+
+#![no_std]
+#![no_main]
+
+extern crate alloc;
+extern crate hyperlight_guest;
+
+use alloc::string::ToString;
+use alloc::vec;
+use alloc::vec::Vec;
+
+use hyperlight_common::flatbuffer_wrappers::function_call::FunctionCall;
+use hyperlight_common::flatbuffer_wrappers::function_types::{
+    ParameterType, ParameterValue, ReturnType,
+};
+use hyperlight_common::flatbuffer_wrappers::guest_error::ErrorCode;
+use hyperlight_common::flatbuffer_wrappers::util::{
+    get_flatbuffer_result_from_ulong, get_flatbuffer_result_from_vec,
+};
+use hyperlight_guest::error::{HyperlightGuestError, Result};
+use hyperlight_guest::guest_function_definition::GuestFunctionDefinition;
+use hyperlight_guest::guest_function_register::register_function;
+
+/// Deliberately naive, so that the amount of work grows exponentially
+/// with `n`
+fn fib(n: u64) -> u64 {
+    match n {
+        0 | 1 => n,
+        _ => fib(n - 1).wrapping_add(fib(n - 2)),
+    }
+}
+
+fn fibonacci(function_call: &FunctionCall) -> Result<Vec<u8>> {
+    if let ParameterValue::Int(n) = function_call.parameters.clone().unwrap()[0].clone() {
+        if n < 0 {
+            return Err(HyperlightGuestError::new(
+                ErrorCode::GuestFunctionParameterTypeMismatch,
+                "fibonacci is only defined for non-negative numbers".to_string(),
+            ));
+        }
+        Ok(get_flatbuffer_result_from_ulong(fib(n as u64)))
+    } else {
+        Err(HyperlightGuestError::new(
+            ErrorCode::GuestFunctionParameterTypeMismatch,
+            "Invalid parameters passed to fibonacci".to_string(),
+        ))
+    }
+}
+
+/// Multiply the `n` x `n` matrices `a[i][j] = i + j` and `b[i][j] = i * j`,
+/// and return the sum of the elements of the product
+fn matrix_multiply(function_call: &FunctionCall) -> Result<Vec<u8>> {
+    if let ParameterValue::Int(n) = function_call.parameters.clone().unwrap()[0].clone() {
+        if n < 0 {
+            return Err(HyperlightGuestError::new(
+                ErrorCode::GuestFunctionParameterTypeMismatch,
+                "matrix size must not be negative".to_string(),
+            ));
+        }
+        let n = n as usize;
+        let a: Vec<u64> = (0..n * n).map(|k| (k / n + k % n) as u64).collect();
+        let b: Vec<u64> = (0..n * n).map(|k| (k / n * (k % n)) as u64).collect();
+        let mut c = vec![0u64; n * n];
+        for i in 0..n {
+            for k in 0..n {
+                let a_ik = a[i * n + k];
+                for j in 0..n {
+                    c[i * n + j] = c[i * n + j].wrapping_add(a_ik.wrapping_mul(b[k * n + j]));
+                }
+            }
+        }
+        let sum = c.iter().fold(0u64, |sum, x| sum.wrapping_add(*x));
+        Ok(get_flatbuffer_result_from_ulong(sum))
+    } else {
+        Err(HyperlightGuestError::new(
+            ErrorCode::GuestFunctionParameterTypeMismatch,
+            "Invalid parameters passed to matrix_multiply".to_string(),
+        ))
+    }
+}
+
+const SHA256_K: [u32; 64] = [
+    0x428a2f98, 0x71374491, 0xb5c0fbcf, 0xe9b5dba5, 0x3956c25b, 0x59f111f1, 0x923f82a4, 0xab1c5ed5,
+    0xd807aa98, 0x12835b01, 0x243185be, 0x550c7dc3, 0x72be5d74, 0x80deb1fe, 0x9bdc06a7, 0xc19bf174,
+    0xe49b69c1, 0xefbe4786, 0x0fc19dc6, 0x240ca1cc, 0x2de92c6f, 0x4a7484aa, 0x5cb0a9dc, 0x76f988da,
+    0x983e5152, 0xa831c66d, 0xb00327c8, 0xbf597fc7, 0xc6e00bf3, 0xd5a79147, 0x06ca6351, 0x14292967,
+    0x27b70a85, 0x2e1b2138, 0x4d2c6dfc, 0x53380d13, 0x650a7354, 0x766a0abb, 0x81c2c92e, 0x92722c85,
+    0xa2bfe8a1, 0xa81a664b, 0xc24b8b70, 0xc76c51a3, 0xd192e819, 0xd6990624, 0xf40e3585, 0x106aa070,
+    0x19a4c116, 0x1e376c08, 0x2748774c, 0x34b0bcb5, 0x391c0cb3, 0x4ed8aa4a, 0x5b9cca4f, 0x682e6ff3,
+    0x748f82ee, 0x78a5636f, 0x84c87814, 0x8cc70208, 0x90befffa, 0xa4506ceb, 0xbef9a3f7, 0xc67178f2,
+];
+
+const SHA256_H: [u32; 8] = [
+    0x6a09e667, 0xbb67ae85, 0x3c6ef372, 0xa54ff53a, 0x510e527f, 0x9b05688c, 0x1f83d9ab, 0x5be0cd19,
+];
+
+/// A straightforward implementation of SHA-256, so that the guest has no
+/// dependencies other than hyperlight-guest
+fn sha256_digest(data: &[u8]) -> [u8; 32] {
+    let mut message = data.to_vec();
+    message.push(0x80);
+    while message.len() % 64 != 56 {
+        message.push(0);
+    }
+    message.extend_from_slice(&((data.len() as u64) * 8).to_be_bytes());
+
+    let mut h = SHA256_H;
+    for block in message.chunks_exact(64) {
+        let mut w = [0u32; 64];
+        for (i, word) in block.chunks_exact(4).enumerate() {
+            w[i] = u32::from_be_bytes([word[0], word[1], word[2], word[3]]);
+        }
+        for i in 16..64 {
+            let s0 = w[i - 15].rotate_right(7) ^ w[i - 15].rotate_right(18) ^ (w[i - 15] >> 3);
+            let s1 = w[i - 2].rotate_right(17) ^ w[i - 2].rotate_right(19) ^ (w[i - 2] >> 10);
+            w[i] = w[i - 16]
+                .wrapping_add(s0)
+                .wrapping_add(w[i - 7])
+                .wrapping_add(s1);
+        }
+
+        let [mut a, mut b, mut c, mut d, mut e, mut f, mut g, mut hh] = h;
+        for i in 0..64 {
+            let s1 = e.rotate_right(6) ^ e.rotate_right(11) ^ e.rotate_right(25);
+            let ch = (e & f) ^ (!e & g);
+            let t1 = hh
+                .wrapping_add(s1)
+                .wrapping_add(ch)
+                .wrapping_add(SHA256_K[i])
+                .wrapping_add(w[i]);
+            let s0 = a.rotate_right(2) ^ a.rotate_right(13) ^ a.rotate_right(22);
+            let maj = (a & b) ^ (a & c) ^ (b & c);
+            let t2 = s0.wrapping_add(maj);
+            hh = g;
+            g = f;
+            f = e;
+            e = d.wrapping_add(t1);
+            d = c;
+            c = b;
+            b = a;
+            a = t1.wrapping_add(t2);
+        }
+        for (h, x) in h.iter_mut().zip([a, b, c, d, e, f, g, hh]) {
+            *h = h.wrapping_add(x);
+        }
+    }
+
+    let mut digest = [0u8; 32];
+    for (bytes, word) in digest.chunks_exact_mut(4).zip(h) {
+        bytes.copy_from_slice(&word.to_be_bytes());
+    }
+    digest
+}
+
+fn sha256(function_call: &FunctionCall) -> Result<Vec<u8>> {
+    if let ParameterValue::VecBytes(data) = function_call.parameters.clone().unwrap()[0].clone() {
+        Ok(get_flatbuffer_result_from_vec(&sha256_digest(&data)))
+    } else {
+        Err(HyperlightGuestError::new(
+            ErrorCode::GuestFunctionParameterTypeMismatch,
+            "Invalid parameters passed to sha256".to_string(),
+        ))
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn hyperlight_main() {
+    let fibonacci_def = GuestFunctionDefinition::new(
+        "Fibonacci".to_string(),
+        Vec::from(&[ParameterType::Int]),
+        ReturnType::ULong,
+        fibonacci,
+    );
+    register_function(fibonacci_def);
+
+    let matrix_multiply_def = GuestFunctionDefinition::new(
+        "MatrixMultiply".to_string(),
+        Vec::from(&[ParameterType::Int]),
+        ReturnType::ULong,
+        matrix_multiply,
+    );
+    register_function(matrix_multiply_def);
+
+    let sha256_def = GuestFunctionDefinition::new(
+        "Sha256".to_string(),
+        Vec::from(&[ParameterType::VecBytes]),
+        ReturnType::VecBytes,
+        sha256,
+    );
+    register_function(sha256_def);
+}
+
+#[no_mangle]
+pub fn guest_dispatch_function(function_call: FunctionCall) -> Result<Vec<u8>> {
+    Err(HyperlightGuestError::new(
+        ErrorCode::GuestFunctionNotFound,
+        function_call.function_name.clone(),
+    ))
+}
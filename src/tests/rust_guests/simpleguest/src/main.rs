@@ -25,13 +25,14 @@ const MAX_BUFFER_SIZE: usize = 1024;
 
 extern crate alloc;
 
+use alloc::alloc::Layout;
 use alloc::boxed::Box;
 use alloc::format;
 use alloc::string::ToString;
 use alloc::vec::Vec;
 use core::ffi::c_char;
 use core::hint::black_box;
-use core::ptr::write_volatile;
+use core::ptr::{read_volatile, write_volatile};
 
 use hyperlight_common::flatbuffer_wrappers::function_call::{FunctionCall, FunctionCallType};
 use hyperlight_common::flatbuffer_wrappers::function_types::{
@@ -47,22 +48,24 @@ use hyperlight_common::flatbuffer_wrappers::util::{
 };
 use hyperlight_common::mem::PAGE_SIZE;
 use hyperlight_guest::alloca::_alloca;
-use hyperlight_guest::entrypoint::{abort_with_code, abort_with_code_and_message};
+use hyperlight_guest::entrypoint::{
+    abort_with_code, abort_with_code_and_message, fail_initialization, startup_args,
+};
 use hyperlight_guest::error::{HyperlightGuestError, Result};
 use hyperlight_guest::guest_function_definition::GuestFunctionDefinition;
 use hyperlight_guest::guest_function_register::register_function;
 use hyperlight_guest::host_function_call::{
-    call_host_function, get_host_value_return_as_int, get_host_value_return_as_ulong,
+    call_host_function, get_host_value_return_as_int, get_host_value_return_as_ulong, signal_host,
 };
 use hyperlight_guest::memory::malloc;
-use hyperlight_guest::{logging, MIN_STACK_ADDRESS};
+use hyperlight_guest::{hl_assert, logging, peb, MIN_STACK_ADDRESS};
 use log::{error, LevelFilter};
 
 extern crate hyperlight_guest;
 
 static mut BIGARRAY: [i32; 1024 * 1024] = [0; 1024 * 1024];
 
-fn set_static() -> Result<Vec<u8>> {
+fn set_static(_: &FunctionCall) -> Result<Vec<u8>> {
     unsafe {
         let length = BIGARRAY.len();
         for i in 0..length {
@@ -533,6 +536,129 @@ fn echo(function_call: &FunctionCall) -> Result<Vec<u8>> {
     }
 }
 
+fn get_startup_arg(function_call: &FunctionCall) -> Result<Vec<u8>> {
+    if let ParameterValue::Int(index) = function_call.parameters.clone().unwrap()[0].clone() {
+        match startup_args()?.get(index as usize) {
+            Some(ParameterValue::String(value)) => Ok(get_flatbuffer_result_from_string(value)),
+            _ => Err(HyperlightGuestError::new(
+                ErrorCode::GuestError,
+                format!("startup argument {} is not a string", index),
+            )),
+        }
+    } else {
+        Err(HyperlightGuestError::new(
+            ErrorCode::GuestFunctionParameterTypeMismatch,
+            "Invalid parameters passed to get_startup_arg".to_string(),
+        ))
+    }
+}
+
+fn reuse_freed_memory(function_call: &FunctionCall) -> Result<Vec<u8>> {
+    if let ParameterValue::Int(size) = function_call.parameters.clone().unwrap()[0].clone() {
+        let layout = Layout::from_size_align(size as usize, PAGE_SIZE as usize).unwrap();
+        // fill a block and free it, then read the middle of the block the
+        // allocator hands out next, which is the same one
+        let byte = unsafe {
+            let ptr = alloc::alloc::alloc(layout);
+            ptr.write_bytes(0xAA, layout.size());
+            alloc::alloc::dealloc(ptr, layout);
+            let ptr = alloc::alloc::alloc(layout);
+            let byte = read_volatile(ptr.add(layout.size() / 2));
+            alloc::alloc::dealloc(ptr, layout);
+            byte
+        };
+        Ok(get_flatbuffer_result_from_int(byte as i32))
+    } else {
+        Err(HyperlightGuestError::new(
+            ErrorCode::GuestFunctionParameterTypeMismatch,
+            "Invalid parameters passed to reuse_freed_memory".to_string(),
+        ))
+    }
+}
+
+fn on_shutdown(_: &FunctionCall) -> Result<Vec<u8>> {
+    print_output("flushed on shutdown")?;
+    Ok(get_flatbuffer_result_from_void())
+}
+
+fn get_remaining_time(_: &FunctionCall) -> Result<Vec<u8>> {
+    let nanos = hyperlight_guest::time::remaining().map_or(0, |remaining| remaining.as_nanos());
+    Ok(get_flatbuffer_result_from_ulong(nanos as u64))
+}
+
+fn report_progress_steps(function_call: &FunctionCall) -> Result<Vec<u8>> {
+    if let ParameterValue::Int(steps) = function_call.parameters.clone().unwrap()[0].clone() {
+        for step in 1..=steps {
+            hyperlight_guest::progress::report_progress(
+                (step * 100 / steps) as u32,
+                &format!("step {}", step),
+            )?;
+        }
+        Ok(get_flatbuffer_result_from_int(steps))
+    } else {
+        Err(HyperlightGuestError::new(
+            ErrorCode::GuestFunctionParameterTypeMismatch,
+            "Invalid parameters passed to report_progress_steps".to_string(),
+        ))
+    }
+}
+
+fn bus_subscribe(function_call: &FunctionCall) -> Result<Vec<u8>> {
+    if let ParameterValue::String(topic) = function_call.parameters.clone().unwrap()[0].clone() {
+        hyperlight_guest::builtin_services::subscribe(&topic)?;
+        Ok(get_flatbuffer_result_from_void())
+    } else {
+        Err(HyperlightGuestError::new(
+            ErrorCode::GuestFunctionParameterTypeMismatch,
+            "Invalid parameters passed to bus_subscribe".to_string(),
+        ))
+    }
+}
+
+fn bus_publish(function_call: &FunctionCall) -> Result<Vec<u8>> {
+    if let (ParameterValue::String(topic), ParameterValue::VecBytes(payload)) = (
+        function_call.parameters.clone().unwrap()[0].clone(),
+        function_call.parameters.clone().unwrap()[1].clone(),
+    ) {
+        hyperlight_guest::builtin_services::publish(&topic, &payload)?;
+        Ok(get_flatbuffer_result_from_void())
+    } else {
+        Err(HyperlightGuestError::new(
+            ErrorCode::GuestFunctionParameterTypeMismatch,
+            "Invalid parameters passed to bus_publish".to_string(),
+        ))
+    }
+}
+
+fn bus_receive(function_call: &FunctionCall) -> Result<Vec<u8>> {
+    if let ParameterValue::String(topic) = function_call.parameters.clone().unwrap()[0].clone() {
+        let event = hyperlight_guest::builtin_services::receive(&topic)?;
+        Ok(get_flatbuffer_result_from_vec(&event.unwrap_or_default()))
+    } else {
+        Err(HyperlightGuestError::new(
+            ErrorCode::GuestFunctionParameterTypeMismatch,
+            "Invalid parameters passed to bus_receive".to_string(),
+        ))
+    }
+}
+
+fn get_env(function_call: &FunctionCall) -> Result<Vec<u8>> {
+    if let ParameterValue::String(name) = function_call.parameters.clone().unwrap()[0].clone() {
+        match hyperlight_guest::env::get(&name) {
+            Some(value) => Ok(get_flatbuffer_result_from_string(value)),
+            None => Err(HyperlightGuestError::new(
+                ErrorCode::GuestError,
+                format!("{} is not set", name),
+            )),
+        }
+    } else {
+        Err(HyperlightGuestError::new(
+            ErrorCode::GuestFunctionParameterTypeMismatch,
+            "Invalid parameters passed to get_env".to_string(),
+        ))
+    }
+}
+
 fn get_size_prefixed_buffer(function_call: &FunctionCall) -> Result<Vec<u8>> {
     if let ParameterValue::VecBytes(data) = function_call.parameters.clone().unwrap()[0].clone() {
         Ok(get_flatbuffer_result_from_vec(&data))
@@ -553,6 +679,12 @@ fn spin(_: &FunctionCall) -> Result<Vec<u8>> {
     Ok(get_flatbuffer_result_from_void())
 }
 
+fn trigger_exception(_: &FunctionCall) -> Result<Vec<u8>> {
+    // only the first GB of the guest address space is mapped
+    let value = unsafe { read_volatile(0x1_0000_0000 as *const u64) };
+    Ok(get_flatbuffer_result_from_ulong(value))
+}
+
 fn test_abort(function_call: &FunctionCall) -> Result<Vec<u8>> {
     if let ParameterValue::Int(code) = function_call.parameters.clone().unwrap()[0].clone() {
         abort_with_code(code);
@@ -572,6 +704,99 @@ fn test_abort_with_code_and_message(function_call: &FunctionCall) -> Result<Vec<
     Ok(get_flatbuffer_result_from_void())
 }
 
+fn test_guest_assert(function_call: &FunctionCall) -> Result<Vec<u8>> {
+    if let ParameterValue::Int(value) = function_call.parameters.clone().unwrap()[0].clone() {
+        hl_assert!(value == 0, "expected 0 but got {}", value);
+        hl_assert!(value >= 0);
+        Ok(get_flatbuffer_result_from_int(value))
+    } else {
+        Err(HyperlightGuestError::new(
+            ErrorCode::GuestFunctionParameterTypeMismatch,
+            "Invalid parameters passed to test_guest_assert".to_string(),
+        ))
+    }
+}
+
+fn test_custom_error(function_call: &FunctionCall) -> Result<Vec<u8>> {
+    if let (ParameterValue::UInt(code), ParameterValue::String(message)) = (
+        function_call.parameters.clone().unwrap()[0].clone(),
+        function_call.parameters.clone().unwrap()[1].clone(),
+    ) {
+        return Err(HyperlightGuestError::custom(code, message));
+    }
+    Ok(get_flatbuffer_result_from_void())
+}
+
+fn test_signal_host(function_call: &FunctionCall) -> Result<Vec<u8>> {
+    if let (ParameterValue::UInt(port), ParameterValue::UInt(value)) = (
+        function_call.parameters.clone().unwrap()[0].clone(),
+        function_call.parameters.clone().unwrap()[1].clone(),
+    ) {
+        signal_host(port as u16, value as u8)?;
+        Ok(get_flatbuffer_result_from_void())
+    } else {
+        Err(HyperlightGuestError::new(
+            ErrorCode::GuestFunctionParameterTypeMismatch,
+            "Invalid parameters passed to test_signal_host".to_string(),
+        ))
+    }
+}
+
+fn test_error_with_payload(function_call: &FunctionCall) -> Result<Vec<u8>> {
+    if let ParameterValue::String(diagnostics) =
+        function_call.parameters.clone().unwrap()[0].clone()
+    {
+        return Err(HyperlightGuestError::new(
+            ErrorCode::GuestError,
+            "error with payload".to_string(),
+        )
+        .with_payload(diagnostics.into_bytes()));
+    }
+    Ok(get_flatbuffer_result_from_void())
+}
+
+/// Call `malloc` with a frame pointer that points outside the stack, as a
+/// guest that uses the frame pointer register for something else would,
+/// and send the heap profile that recorded the allocation
+#[cfg(feature = "heap_profiling")]
+fn allocate_with_corrupted_frame_pointer(function_call: &FunctionCall) -> Result<Vec<u8>> {
+    use hyperlight_guest::heap_profile::send_heap_profile;
+    use hyperlight_guest::memory::free;
+
+    if let ParameterValue::Int(size) = function_call.parameters.clone().unwrap()[0].clone() {
+        // a non-canonical address, which faults if it is read
+        let corrupted_frame: u64 = 0x8000_0000_0000_0000;
+        let ptr: *mut core::ffi::c_void;
+        unsafe {
+            core::arch::asm!(
+                "mov r12, rsp",
+                "and rsp, -16",
+                // saved twice to keep the stack aligned for the call
+                "push rbp",
+                "push rbp",
+                "mov rbp, {frame}",
+                "call {malloc}",
+                "pop rbp",
+                "pop rbp",
+                "mov rsp, r12",
+                frame = in(reg) corrupted_frame,
+                malloc = sym malloc,
+                in("rdi") size as usize,
+                lateout("rax") ptr,
+                out("r12") _,
+                clobber_abi("C"),
+            );
+            free(ptr);
+        }
+        send_heap_profile()?;
+        return Ok(get_flatbuffer_result_from_void());
+    }
+    Err(HyperlightGuestError::new(
+        ErrorCode::GuestFunctionParameterTypeMismatch,
+        "Invalid parameters passed to allocate_with_corrupted_frame_pointer".to_string(),
+    ))
+}
+
 fn test_guest_panic(function_call: &FunctionCall) -> Result<Vec<u8>> {
     if let ParameterValue::String(message) = function_call.parameters.clone().unwrap()[0].clone() {
         panic!("{}", message);
@@ -623,6 +848,27 @@ fn execute_on_heap(_function_call: &FunctionCall) -> Result<Vec<u8>> {
     Ok(get_flatbuffer_result_from_string("fail"))
 }
 
+fn write_to_code(_function_call: &FunctionCall) -> Result<Vec<u8>> {
+    let code = write_to_code as *const () as *mut u8;
+    unsafe {
+        // write back the byte that is already there, so that nothing breaks
+        // if the write goes through
+        write_volatile(code, read_volatile(code));
+    }
+    // will only reach this point if code is writable
+    Ok(get_flatbuffer_result_from_string("fail"))
+}
+
+fn write_to_host_function_definitions(_function_call: &FunctionCall) -> Result<Vec<u8>> {
+    unsafe {
+        let peb = peb().unwrap();
+        let definitions = (*peb).hostFunctionDefinitions.fbHostFunctionDetails as *mut u8;
+        write_volatile(definitions, read_volatile(definitions));
+    }
+    // will only reach this point if the host function definitions are writable
+    Ok(get_flatbuffer_result_from_string("fail"))
+}
+
 fn test_rust_malloc(function_call: &FunctionCall) -> Result<Vec<u8>> {
     if let ParameterValue::Int(code) = function_call.parameters.clone().unwrap()[0].clone() {
         let ptr = unsafe { malloc(code as usize) };
@@ -636,14 +882,21 @@ fn test_rust_malloc(function_call: &FunctionCall) -> Result<Vec<u8>> {
 }
 
 fn log_message(function_call: &FunctionCall) -> Result<Vec<u8>> {
-    if let (ParameterValue::String(message), ParameterValue::Int(level)) = (
+    if let (
+        ParameterValue::String(message),
+        ParameterValue::String(target),
+        ParameterValue::Int(level),
+    ) = (
         function_call.parameters.clone().unwrap()[0].clone(),
         function_call.parameters.clone().unwrap()[1].clone(),
+        function_call.parameters.clone().unwrap()[2].clone(),
     ) {
         let level = LevelFilter::iter().nth(level as usize).unwrap().to_level();
 
         match level {
-            Some(level) => log::log!(level, "{}", &message),
+            // an empty target logs to the default target
+            Some(level) if target.is_empty() => log::log!(level, "{}", &message),
+            Some(level) => log::log!(target: &target, level, "{}", &message),
             None => {
                 // was passed LevelFilter::Off, do nothing
             }
@@ -674,6 +927,14 @@ fn add_to_static(function_call: &FunctionCall) -> Result<Vec<u8>> {
     }
 }
 
+fn add_to_static_and_fail(function_call: &FunctionCall) -> Result<Vec<u8>> {
+    add_to_static(function_call)?;
+    Err(HyperlightGuestError::new(
+        ErrorCode::GuestError,
+        "Crash on purpose".to_string(),
+    ))
+}
+
 fn get_static(function_call: &FunctionCall) -> Result<Vec<u8>> {
     if function_call.parameters.is_none() {
         Ok(get_flatbuffer_result_from_int(unsafe { COUNTER }))
@@ -685,6 +946,28 @@ fn get_static(function_call: &FunctionCall) -> Result<Vec<u8>> {
     }
 }
 
+fn set_mxcsr(function_call: &FunctionCall) -> Result<Vec<u8>> {
+    if let ParameterValue::Int(value) = function_call.parameters.clone().unwrap()[0].clone() {
+        unsafe {
+            core::arch::asm!("ldmxcsr [{}]", in(reg) &value, options(nostack, readonly));
+        }
+        Ok(get_flatbuffer_result_from_void())
+    } else {
+        Err(HyperlightGuestError::new(
+            ErrorCode::GuestFunctionParameterTypeMismatch,
+            "Invalid parameters passed to set_mxcsr".to_string(),
+        ))
+    }
+}
+
+fn get_mxcsr(_: &FunctionCall) -> Result<Vec<u8>> {
+    let mut value: i32 = 0;
+    unsafe {
+        core::arch::asm!("stmxcsr [{}]", in(reg) &mut value, options(nostack));
+    }
+    Ok(get_flatbuffer_result_from_int(value))
+}
+
 fn violate_seccomp_filters(function_call: &FunctionCall) -> Result<Vec<u8>> {
     if function_call.parameters.is_none() {
         call_host_function("MakeGetpidSyscall", None, ReturnType::ULong)?;
@@ -722,13 +1005,44 @@ fn add(function_call: &FunctionCall) -> Result<Vec<u8>> {
     }
 }
 
+fn forward_secret(function_call: &FunctionCall) -> Result<Vec<u8>> {
+    if let ParameterValue::Secret(secret) = &function_call.parameters.as_ref().unwrap()[0] {
+        call_host_function(
+            "HostCheckSecret",
+            Some(Vec::from(&[ParameterValue::Secret(secret.clone())])),
+            ReturnType::Int,
+        )?;
+
+        let res = get_host_value_return_as_int()?;
+
+        Ok(get_flatbuffer_result_from_int(res))
+    } else {
+        Err(HyperlightGuestError::new(
+            ErrorCode::GuestFunctionParameterTypeMismatch,
+            "Invalid parameters passed to forward_secret".to_string(),
+        ))
+    }
+}
+
 #[no_mangle]
 pub extern "C" fn hyperlight_main() {
+    if let Ok(args) = startup_args() {
+        if let [ParameterValue::String(command), ParameterValue::String(reason)] = args.as_slice() {
+            if command == "FailInitialization" {
+                fail_initialization(HyperlightGuestError::new(
+                    ErrorCode::GuestError,
+                    reason.clone(),
+                ));
+                return;
+            }
+        }
+    }
+
     let set_static_def = GuestFunctionDefinition::new(
         "SetStatic".to_string(),
         Vec::new(),
         ReturnType::Int,
-        set_static as i64,
+        set_static,
     );
 
     register_function(set_static_def);
@@ -737,7 +1051,7 @@ pub extern "C" fn hyperlight_main() {
         "PrintOutput".to_string(),
         Vec::from(&[ParameterType::String]),
         ReturnType::Int,
-        simple_print_output as i64,
+        simple_print_output,
     );
     register_function(simple_print_output_def);
 
@@ -745,7 +1059,7 @@ pub extern "C" fn hyperlight_main() {
         "PrintUsingPrintf".to_string(),
         Vec::from(&[ParameterType::String]),
         ReturnType::Int,
-        simple_print_output as i64, // alias to simple_print_output for now
+        simple_print_output, // alias to simple_print_output for now
     );
     register_function(print_using_printf_def);
 
@@ -753,7 +1067,7 @@ pub extern "C" fn hyperlight_main() {
         "StackAllocate".to_string(),
         Vec::from(&[ParameterType::Int]),
         ReturnType::Int,
-        stack_allocate as i64,
+        stack_allocate,
     );
     register_function(stack_allocate_def);
 
@@ -761,7 +1075,7 @@ pub extern "C" fn hyperlight_main() {
         "StackOverflow".to_string(),
         Vec::from(&[ParameterType::Int]),
         ReturnType::Int,
-        stack_overflow as i64,
+        stack_overflow,
     );
     register_function(stack_overflow_def);
 
@@ -769,7 +1083,7 @@ pub extern "C" fn hyperlight_main() {
         "BufferOverrun".to_string(),
         Vec::from(&[ParameterType::String]),
         ReturnType::Int,
-        buffer_overrun as i64,
+        buffer_overrun,
     );
     register_function(buffer_overrun_def);
 
@@ -777,7 +1091,7 @@ pub extern "C" fn hyperlight_main() {
         "LargeVar".to_string(),
         Vec::new(),
         ReturnType::Int,
-        large_var as i64,
+        large_var,
     );
     register_function(large_var_def);
 
@@ -785,7 +1099,7 @@ pub extern "C" fn hyperlight_main() {
         "SmallVar".to_string(),
         Vec::new(),
         ReturnType::Int,
-        small_var as i64,
+        small_var,
     );
     register_function(small_var_def);
 
@@ -793,7 +1107,7 @@ pub extern "C" fn hyperlight_main() {
         "CallMalloc".to_string(),
         Vec::from(&[ParameterType::Int]),
         ReturnType::Int,
-        call_malloc as i64,
+        call_malloc,
     );
     register_function(call_malloc_def);
 
@@ -801,7 +1115,7 @@ pub extern "C" fn hyperlight_main() {
         "MallocAndFree".to_string(),
         Vec::from(&[ParameterType::Int]),
         ReturnType::Int,
-        malloc_and_free as i64,
+        malloc_and_free,
     );
     register_function(malloc_and_free_def);
 
@@ -809,7 +1123,7 @@ pub extern "C" fn hyperlight_main() {
         "PrintTwoArgs".to_string(),
         Vec::from(&[ParameterType::String, ParameterType::Int]),
         ReturnType::Int,
-        print_two_args as i64,
+        print_two_args,
     );
     register_function(print_two_args_def);
 
@@ -821,7 +1135,7 @@ pub extern "C" fn hyperlight_main() {
             ParameterType::Long,
         ]),
         ReturnType::Int,
-        print_three_args as i64,
+        print_three_args,
     );
     register_function(print_three_args_def);
 
@@ -834,7 +1148,7 @@ pub extern "C" fn hyperlight_main() {
             ParameterType::String,
         ]),
         ReturnType::Int,
-        print_four_args as i64,
+        print_four_args,
     );
     register_function(print_four_args_def);
 
@@ -848,7 +1162,7 @@ pub extern "C" fn hyperlight_main() {
             ParameterType::String,
         ]),
         ReturnType::Int,
-        print_five_args as i64,
+        print_five_args,
     );
     register_function(print_five_args_def);
 
@@ -863,7 +1177,7 @@ pub extern "C" fn hyperlight_main() {
             ParameterType::Bool,
         ]),
         ReturnType::Int,
-        print_six_args as i64,
+        print_six_args,
     );
     register_function(print_six_args_def);
 
@@ -879,7 +1193,7 @@ pub extern "C" fn hyperlight_main() {
             ParameterType::Bool,
         ]),
         ReturnType::Int,
-        print_seven_args as i64,
+        print_seven_args,
     );
     register_function(print_seven_args_def);
 
@@ -896,7 +1210,7 @@ pub extern "C" fn hyperlight_main() {
             ParameterType::UInt,
         ]),
         ReturnType::Int,
-        print_eight_args as i64,
+        print_eight_args,
     );
     register_function(print_eight_args_def);
 
@@ -914,7 +1228,7 @@ pub extern "C" fn hyperlight_main() {
             ParameterType::ULong,
         ]),
         ReturnType::Int,
-        print_nine_args as i64,
+        print_nine_args,
     );
     register_function(print_nine_args_def);
 
@@ -933,7 +1247,7 @@ pub extern "C" fn hyperlight_main() {
             ParameterType::Int,
         ]),
         ReturnType::Int,
-        print_ten_args as i64,
+        print_ten_args,
     );
     register_function(print_ten_args_def);
 
@@ -953,7 +1267,7 @@ pub extern "C" fn hyperlight_main() {
             ParameterType::Float,
         ]),
         ReturnType::Int,
-        print_eleven_args as i64,
+        print_eleven_args,
     );
     register_function(print_eleven_args_def);
 
@@ -961,7 +1275,7 @@ pub extern "C" fn hyperlight_main() {
         "SetByteArrayToZero".to_string(),
         Vec::from(&[ParameterType::VecBytes]),
         ReturnType::VecBytes,
-        set_byte_array_to_zero as i64,
+        set_byte_array_to_zero,
     );
     register_function(set_byte_array_to_zero_def);
 
@@ -969,27 +1283,115 @@ pub extern "C" fn hyperlight_main() {
         "Echo".to_string(),
         Vec::from(&[ParameterType::String]),
         ReturnType::String,
-        echo as i64,
+        echo,
     );
     register_function(echo_def);
 
+    let on_shutdown_def = GuestFunctionDefinition::new(
+        "hyperlight_on_shutdown".to_string(),
+        Vec::new(),
+        ReturnType::Void,
+        on_shutdown,
+    );
+    register_function(on_shutdown_def);
+
+    let get_remaining_time_def = GuestFunctionDefinition::new(
+        "GetRemainingTime".to_string(),
+        Vec::new(),
+        ReturnType::ULong,
+        get_remaining_time,
+    );
+    register_function(get_remaining_time_def);
+
+    let report_progress_steps_def = GuestFunctionDefinition::new(
+        "ReportProgressSteps".to_string(),
+        Vec::from(&[ParameterType::Int]),
+        ReturnType::Int,
+        report_progress_steps,
+    );
+    register_function(report_progress_steps_def);
+
+    let bus_subscribe_def = GuestFunctionDefinition::new(
+        "BusSubscribe".to_string(),
+        Vec::from(&[ParameterType::String]),
+        ReturnType::Void,
+        bus_subscribe,
+    );
+    register_function(bus_subscribe_def);
+
+    let bus_publish_def = GuestFunctionDefinition::new(
+        "BusPublish".to_string(),
+        Vec::from(&[ParameterType::String, ParameterType::VecBytes]),
+        ReturnType::Void,
+        bus_publish,
+    );
+    register_function(bus_publish_def);
+
+    let bus_receive_def = GuestFunctionDefinition::new(
+        "BusReceive".to_string(),
+        Vec::from(&[ParameterType::String]),
+        ReturnType::VecBytes,
+        bus_receive,
+    );
+    register_function(bus_receive_def);
+
+    let get_env_def = GuestFunctionDefinition::new(
+        "GetEnv".to_string(),
+        Vec::from(&[ParameterType::String]),
+        ReturnType::String,
+        get_env,
+    );
+    register_function(get_env_def);
+
+    let get_startup_arg_def = GuestFunctionDefinition::new(
+        "GetStartupArg".to_string(),
+        Vec::from(&[ParameterType::Int]),
+        ReturnType::String,
+        get_startup_arg,
+    );
+    register_function(get_startup_arg_def);
+
+    let reuse_freed_memory_def = GuestFunctionDefinition::new(
+        "ReuseFreedMemory".to_string(),
+        Vec::from(&[ParameterType::Int]),
+        ReturnType::Int,
+        reuse_freed_memory,
+    );
+    register_function(reuse_freed_memory_def);
+
+    let forward_secret_def = GuestFunctionDefinition::new(
+        "ForwardSecret".to_string(),
+        Vec::from(&[ParameterType::Secret]),
+        ReturnType::Int,
+        forward_secret,
+    );
+    register_function(forward_secret_def);
+
     let get_size_prefixed_buffer_def = GuestFunctionDefinition::new(
         "GetSizePrefixedBuffer".to_string(),
         Vec::from(&[ParameterType::VecBytes]),
         ReturnType::Int,
-        get_size_prefixed_buffer as i64,
+        get_size_prefixed_buffer,
     );
     register_function(get_size_prefixed_buffer_def);
 
     let spin_def =
-        GuestFunctionDefinition::new("Spin".to_string(), Vec::new(), ReturnType::Int, spin as i64);
+        GuestFunctionDefinition::new("Spin".to_string(), Vec::new(), ReturnType::Int, spin);
     register_function(spin_def);
 
+    let trigger_exception_def = GuestFunctionDefinition::new(
+        "TriggerException".to_string(),
+        Vec::new(),
+        ReturnType::ULong,
+        trigger_exception,
+    );
+    register_function(trigger_exception_def);
+
     let abort_def = GuestFunctionDefinition::new(
         "GuestAbortWithCode".to_string(),
         Vec::from(&[ParameterType::Int]),
         ReturnType::Void,
-        test_abort as i64,
+        test_abort,
     );
     register_function(abort_def);
 
@@ -997,31 +1399,75 @@ pub extern "C" fn hyperlight_main() {
         "GuestAbortWithMessage".to_string(),
         Vec::from(&[ParameterType::Int, ParameterType::String]),
         ReturnType::Void,
-        test_abort_with_code_and_message as i64,
+        test_abort_with_code_and_message,
     );
     register_function(abort_with_code_message_def);
 
+    let guest_assert_def = GuestFunctionDefinition::new(
+        "GuestAssert".to_string(),
+        Vec::from(&[ParameterType::Int]),
+        ReturnType::Int,
+        test_guest_assert,
+    );
+    register_function(guest_assert_def);
+
     let guest_panic_def = GuestFunctionDefinition::new(
         "guest_panic".to_string(),
         Vec::from(&[ParameterType::String]),
         ReturnType::Void,
-        test_guest_panic as i64,
+        test_guest_panic,
     );
     register_function(guest_panic_def);
 
+    let custom_error_def = GuestFunctionDefinition::new(
+        "CustomError".to_string(),
+        Vec::from(&[ParameterType::UInt, ParameterType::String]),
+        ReturnType::Void,
+        test_custom_error,
+    );
+    register_function(custom_error_def);
+
+    let signal_host_def = GuestFunctionDefinition::new(
+        "SignalHost".to_string(),
+        Vec::from(&[ParameterType::UInt, ParameterType::UInt]),
+        ReturnType::Void,
+        test_signal_host,
+    );
+    register_function(signal_host_def);
+
+    let error_with_payload_def = GuestFunctionDefinition::new(
+        "ErrorWithPayload".to_string(),
+        Vec::from(&[ParameterType::String]),
+        ReturnType::Void,
+        test_error_with_payload,
+    );
+    register_function(error_with_payload_def);
+
+    #[cfg(feature = "heap_profiling")]
+    register_function(GuestFunctionDefinition::new(
+        "AllocateWithCorruptedFramePointer".to_string(),
+        Vec::from(&[ParameterType::Int]),
+        ReturnType::Void,
+        allocate_with_corrupted_frame_pointer,
+    ));
+
     let rust_malloc_def = GuestFunctionDefinition::new(
         "TestMalloc".to_string(),
         Vec::from(&[ParameterType::Int]),
         ReturnType::Int,
-        test_rust_malloc as i64,
+        test_rust_malloc,
     );
     register_function(rust_malloc_def);
 
     let log_message_def = GuestFunctionDefinition::new(
         "LogMessage".to_string(),
-        Vec::from(&[ParameterType::String, ParameterType::Int]),
+        Vec::from(&[
+            ParameterType::String,
+            ParameterType::String,
+            ParameterType::Int,
+        ]),
         ReturnType::Void,
-        log_message as i64,
+        log_message,
     );
     register_function(log_message_def);
 
@@ -1029,7 +1475,7 @@ pub extern "C" fn hyperlight_main() {
         "InfiniteRecursion".to_string(),
         Vec::new(),
         ReturnType::Void,
-        infinite_recursion as i64,
+        infinite_recursion,
     );
     register_function(infinite_recursion_def);
 
@@ -1037,7 +1483,7 @@ pub extern "C" fn hyperlight_main() {
         "test_write_raw_ptr".to_string(),
         Vec::from(&[ParameterType::Long]),
         ReturnType::String,
-        test_write_raw_ptr as i64,
+        test_write_raw_ptr,
     );
     register_function(test_write_raw_ptr_def);
 
@@ -1045,7 +1491,7 @@ pub extern "C" fn hyperlight_main() {
         "ExecuteOnStack".to_string(),
         Vec::new(),
         ReturnType::String,
-        execute_on_stack as i64,
+        execute_on_stack,
     );
     register_function(execute_on_stack_def);
 
@@ -1053,30 +1499,69 @@ pub extern "C" fn hyperlight_main() {
         "ExecuteOnHeap".to_string(),
         Vec::new(),
         ReturnType::String,
-        execute_on_heap as i64,
+        execute_on_heap,
     );
     register_function(execute_on_heap_def);
 
+    let write_to_code_def = GuestFunctionDefinition::new(
+        "WriteToCode".to_string(),
+        Vec::new(),
+        ReturnType::String,
+        write_to_code,
+    );
+    register_function(write_to_code_def);
+
+    let write_to_host_function_definitions_def = GuestFunctionDefinition::new(
+        "WriteToHostFunctionDefinitions".to_string(),
+        Vec::new(),
+        ReturnType::String,
+        write_to_host_function_definitions,
+    );
+    register_function(write_to_host_function_definitions_def);
+
     let add_to_static_def = GuestFunctionDefinition::new(
         "AddToStatic".to_string(),
         Vec::from(&[ParameterType::Int]),
         ReturnType::Int,
-        add_to_static as i64,
+        add_to_static,
     );
     register_function(add_to_static_def);
+    let add_to_static_and_fail_def = GuestFunctionDefinition::new(
+        "AddToStaticAndFail".to_string(),
+        Vec::from(&[ParameterType::Int]),
+        ReturnType::Int,
+        add_to_static_and_fail,
+    );
+    register_function(add_to_static_and_fail_def);
     let get_static_def = GuestFunctionDefinition::new(
         "GetStatic".to_string(),
         Vec::new(),
         ReturnType::Int,
-        get_static as i64,
+        get_static,
     );
     register_function(get_static_def);
 
+    let set_mxcsr_def = GuestFunctionDefinition::new(
+        "SetMxcsr".to_string(),
+        Vec::from(&[ParameterType::Int]),
+        ReturnType::Void,
+        set_mxcsr,
+    );
+    register_function(set_mxcsr_def);
+
+    let get_mxcsr_def = GuestFunctionDefinition::new(
+        "GetMxcsr".to_string(),
+        Vec::new(),
+        ReturnType::Int,
+        get_mxcsr,
+    );
+    register_function(get_mxcsr_def);
+
     let violate_seccomp_filters_def = GuestFunctionDefinition::new(
         "ViolateSeccompFilters".to_string(),
         Vec::new(),
         ReturnType::ULong,
-        violate_seccomp_filters as i64,
+        violate_seccomp_filters,
     );
     register_function(violate_seccomp_filters_def);
 
@@ -1084,7 +1569,7 @@ pub extern "C" fn hyperlight_main() {
         "EchoFloat".to_string(),
         Vec::from(&[ParameterType::Float]),
         ReturnType::Float,
-        echo_float as i64,
+        echo_float,
     );
     register_function(echo_float_def);
 
@@ -1092,7 +1577,7 @@ pub extern "C" fn hyperlight_main() {
         "EchoDouble".to_string(),
         Vec::from(&[ParameterType::Double]),
         ReturnType::Double,
-        echo_double as i64,
+        echo_double,
     );
     register_function(echo_double_def);
 
@@ -1100,7 +1585,7 @@ pub extern "C" fn hyperlight_main() {
         "Add".to_string(),
         Vec::from(&[ParameterType::Int, ParameterType::Int]),
         ReturnType::Int,
-        add as i64,
+        add,
     );
     register_function(add_def);
 }